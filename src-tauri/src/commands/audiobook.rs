@@ -0,0 +1,273 @@
+//! Audiobook export command handlers for Actual Reader.
+//!
+//! Exports narration as a standard M4B audiobook with chapter markers, for
+//! playback in audiobook apps that don't understand .actualbook bundles.
+//! Encoding and muxing are done by ffmpeg, which must be on the PATH.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+
+use tauri::State;
+
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::bundle::ensure_narration_exportable;
+use super::library::resolve_export_path;
+use crate::models::{BookId, NarrationStatus};
+use crate::services::ffmpeg;
+use crate::services::parser::is_heading_html;
+use crate::AppState;
+
+/// AAC bitrate for exported audiobooks; plenty for speech.
+const M4B_BITRATE: &str = "64k";
+
+/// A chapter in an exported audiobook.
+#[derive(Debug, Clone, PartialEq)]
+struct Chapter {
+    title: String,
+    /// Start time in seconds.
+    start: f64,
+    /// End time in seconds.
+    end: f64,
+}
+
+/// Build chapters from the book's heading segments.
+///
+/// Each heading with a marker starts a chapter that runs until the next
+/// heading; the segments in between belong to it. Narration before the
+/// first heading becomes an opening chapter named after the book, as does
+/// the whole book when it has no headings.
+fn build_chapters(
+    book_title: &str,
+    segments: &[(String, String, Option<String>)],
+    markers: &HashMap<String, f64>,
+    duration: f64,
+) -> Vec<Chapter> {
+    let mut starts: Vec<(String, f64)> = segments
+        .iter()
+        .filter(|(_, _, html)| html.as_deref().is_some_and(is_heading_html))
+        .filter_map(|(id, content, _)| {
+            let start = *markers.get(id)?;
+            let title = content.split_whitespace().collect::<Vec<_>>().join(" ");
+            Some((title, start))
+        })
+        .collect();
+
+    let opens_with_heading = starts.first().is_some_and(|(_, start)| *start <= 0.0);
+    if !opens_with_heading {
+        starts.insert(0, (book_title.to_string(), 0.0));
+    }
+
+    let ends: Vec<f64> = starts
+        .iter()
+        .skip(1)
+        .map(|(_, start)| *start)
+        .chain(std::iter::once(duration))
+        .collect();
+
+    starts
+        .into_iter()
+        .zip(ends)
+        .filter(|((_, start), end)| end > start)
+        .map(|((title, start), end)| Chapter { title, start, end })
+        .collect()
+}
+
+/// Escape a value for an ffmpeg metadata file.
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Render book metadata and chapters in ffmpeg's FFMETADATA1 format.
+fn ffmetadata(title: &str, author: Option<&str>, chapters: &[Chapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    metadata.push_str(&format!("title={}\n", escape_metadata(title)));
+    metadata.push_str(&format!("album={}\n", escape_metadata(title)));
+    if let Some(author) = author {
+        metadata.push_str(&format!("artist={}\n", escape_metadata(author)));
+    }
+    metadata.push_str("genre=Audiobook\n");
+
+    for chapter in chapters {
+        metadata.push_str("\n[CHAPTER]\nTIMEBASE=1/1000\n");
+        metadata.push_str(&format!("START={}\n", (chapter.start * 1000.0).round() as u64));
+        metadata.push_str(&format!("END={}\n", (chapter.end * 1000.0).round() as u64));
+        metadata.push_str(&format!("title={}\n", escape_metadata(&chapter.title)));
+    }
+
+    metadata
+}
+
+/// Export a book's narration as an M4B audiobook.
+///
+/// The audio is encoded to AAC and written with one chapter per heading,
+/// titled with the heading text. Requires ffmpeg on the PATH; fails with a
+/// service-unavailable error if it is missing.
+///
+/// If `output_path` is a directory, the file is named after the book's
+/// original filename. Returns the path written.
+#[tauri::command]
+pub async fn export_m4b(
+    book_id: BookId,
+    output_path: String,
+    state: State<'_, AppState>,
+) -> CommandResult<String> {
+    // 1. Load the book, its segments and its markers
+    let (title, author, segments, markers, duration, output_path) = {
+        let conn = state.db.connection().lock().unwrap();
+        let output_path = resolve_export_path(&conn, &book_id, &output_path, "m4b")?;
+
+        let (title, author, status, duration): (String, Option<String>, String, Option<f64>) = conn
+            .query_row(
+                "SELECT title, author, narration_status, duration FROM books WHERE id = ?",
+                rusqlite::params![book_id.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .context("Book not found")?;
+
+        ensure_narration_exportable(
+            NarrationStatus::from_str(&status).unwrap_or(NarrationStatus::None),
+        )?;
+
+        let segments: Vec<(String, String, Option<String>)> = conn
+            .prepare("SELECT id, content, html FROM segments WHERE book_id = ? ORDER BY idx ASC")
+            .context("Failed to prepare segments query")?
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .context("Failed to query segments")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read segment row")?;
+
+        let markers: Vec<(String, f64, f64)> = conn
+            .prepare("SELECT segment_id, start_time, end_time FROM markers WHERE book_id = ?")
+            .context("Failed to prepare markers query")?
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .context("Failed to query markers")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read marker row")?;
+
+        (title, author, segments, markers, duration, output_path)
+    };
+
+    let Some(audio_path) = state.paths().find_narration_audio(book_id.as_str()) else {
+        return Err(CommandError::NotFound(
+            "Narration audio file not found".to_string(),
+        ));
+    };
+
+    // 2. Derive chapters from heading segments
+    let duration = duration
+        .or_else(|| markers.iter().map(|(_, _, end)| *end).reduce(f64::max))
+        .unwrap_or(0.0);
+    let starts: HashMap<String, f64> =
+        markers.into_iter().map(|(id, start, _)| (id, start)).collect();
+    let chapters = build_chapters(&title, &segments, &starts, duration);
+
+    let metadata_path = state
+        .paths()
+        .narration_path(book_id.as_str())
+        .join("chapters.ffmetadata");
+    std::fs::write(&metadata_path, ffmetadata(&title, author.as_deref(), &chapters))
+        .context("Failed to write chapter metadata")?;
+
+    // 3. Encode and mux with ffmpeg
+    let encoded = ffmpeg::run([
+        OsStr::new("-i"),
+        audio_path.as_os_str(),
+        OsStr::new("-i"),
+        metadata_path.as_os_str(),
+        OsStr::new("-map"),
+        OsStr::new("0:a"),
+        OsStr::new("-map_metadata"),
+        OsStr::new("1"),
+        OsStr::new("-map_chapters"),
+        OsStr::new("1"),
+        OsStr::new("-c:a"),
+        OsStr::new("aac"),
+        OsStr::new("-b:a"),
+        OsStr::new(M4B_BITRATE),
+        OsStr::new("-movflags"),
+        OsStr::new("+faststart"),
+        OsStr::new("-f"),
+        OsStr::new("ipod"),
+        output_path.as_os_str(),
+    ])
+    .await;
+
+    let _ = std::fs::remove_file(&metadata_path);
+    encoded.context("Failed to export M4B audiobook")?;
+
+    log::info!(
+        "Exported M4B with {} chapter(s) to: {}",
+        chapters.len(),
+        output_path.display()
+    );
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(id: &str, text: &str, html: &str) -> (String, String, Option<String>) {
+        (id.to_string(), text.to_string(), Some(html.to_string()))
+    }
+
+    #[test]
+    fn test_build_chapters_from_headings() {
+        let segments = vec![
+            segment("s0", "Preface text", "<p>Preface text</p>"),
+            segment("s1", "Chapter One", "<h1>Chapter One</h1>"),
+            segment("s2", "Body", "<p>Body</p>"),
+            segment("s3", "Chapter Two", "<h2>Chapter Two</h2>"),
+        ];
+        let markers: HashMap<String, f64> = [("s0", 0.0), ("s1", 5.0), ("s2", 7.0), ("s3", 20.0)]
+            .into_iter()
+            .map(|(id, start)| (id.to_string(), start))
+            .collect();
+
+        let chapters = build_chapters("Book", &segments, &markers, 30.0);
+
+        assert_eq!(
+            chapters,
+            vec![
+                Chapter { title: "Book".to_string(), start: 0.0, end: 5.0 },
+                Chapter { title: "Chapter One".to_string(), start: 5.0, end: 20.0 },
+                Chapter { title: "Chapter Two".to_string(), start: 20.0, end: 30.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_chapters_without_headings() {
+        let segments = vec![segment("s0", "Text", "<p>Text</p>")];
+        let markers: HashMap<String, f64> = [("s0".to_string(), 0.0)].into_iter().collect();
+
+        let chapters = build_chapters("Book", &segments, &markers, 12.5);
+        assert_eq!(
+            chapters,
+            vec![Chapter { title: "Book".to_string(), start: 0.0, end: 12.5 }]
+        );
+    }
+
+    #[test]
+    fn test_ffmetadata_escapes_values() {
+        let chapters = vec![Chapter { title: "A=B; C".to_string(), start: 0.0, end: 1.25 }];
+        let metadata = ffmetadata("Title #1", Some("Author"), &chapters);
+
+        assert!(metadata.starts_with(";FFMETADATA1\n"));
+        assert!(metadata.contains("title=Title \\#1\n"));
+        assert!(metadata.contains("artist=Author\n"));
+        assert!(metadata.contains("START=0\nEND=1250\ntitle=A\\=B\\; C\n"));
+    }
+}
@@ -4,20 +4,79 @@
 //! Bundles package a book with its narration and markers for transfer between devices.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Write};
-use tauri::State;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, State};
 use uuid::Uuid;
 use zip::write::SimpleFileOptions;
 use zip::{ZipArchive, ZipWriter};
 
-use crate::models::{Book, BookId, Marker, NarrationStatus, Segment, SegmentId, SegmentType, SourceFormat};
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::library::{ensure_disk_space, resolve_book_paths, resolve_export_path};
+use super::reader::query_segments;
+use super::tts::validate_markers;
+use crate::models::{
+    Book, BookId, BookMetadata, ImageData, ImagePosition, Marker, NarrationMeta, NarrationStatus,
+    Segment, SegmentId, SegmentType, SourceFormat,
+};
+use crate::services::tts::{get_wav_duration, time_stretch_wav};
+use crate::storage::{AppPaths, NarrationCodec};
 use crate::AppState;
 
 /// Bundle format version.
 const BUNDLE_VERSION: &str = "1.0";
 
+/// Library archive format version, versioned separately from single-book
+/// bundles. Archives with a different major version are refused.
+const LIBRARY_VERSION: &str = "1.0";
+
+/// Slowest playback speed a bundle can be exported at.
+const MIN_EXPORT_SPEED: f64 = 0.5;
+
+/// Fastest playback speed a bundle can be exported at.
+const MAX_EXPORT_SPEED: f64 = 2.0;
+
+/// Bundle entry name for narration audio in the given codec.
+pub(crate) fn audio_entry_name(codec: NarrationCodec) -> String {
+    format!("narration/audio.{}", codec.extension())
+}
+
+/// Find the narration audio entry in a bundle, whichever codec it uses.
+pub(crate) fn find_audio_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Option<(String, NarrationCodec)> {
+    find_prefixed_audio_entry(archive, "")
+}
+
+/// Find the narration audio entry of a bundle stored under `prefix`.
+fn find_prefixed_audio_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+) -> Option<(String, NarrationCodec)> {
+    NarrationCodec::ALL.into_iter().find_map(|codec| {
+        let name = format!("{}{}", prefix, audio_entry_name(codec));
+        archive.by_name(&name).is_ok().then_some((name, codec))
+    })
+}
+
+/// A file in the bundles directory, as listed by `list_bundles`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleFileInfo {
+    /// File name within the bundles directory, as passed to `delete_bundle`.
+    pub filename: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Unix timestamp (seconds) of the last change, if the platform reports it.
+    pub modified_at: Option<i64>,
+    /// Title from the bundle's manifest; None if it couldn't be read.
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
 /// Information about a bundle file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -39,17 +98,127 @@ struct BundleManifest {
     author: Option<String>,
     source_format: String,
     created_at: i64,
+    /// When the book was last changed, used to avoid overwriting a newer copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<i64>,
     duration: Option<f64>,
     segment_count: u32,
+    /// Playback speed baked into the narration audio, if it was re-timed on export.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speed: Option<f64>,
+    /// Language of the text, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// Publisher, date and identifiers from the source file, if any.
+    #[serde(default, skip_serializing_if = "BookMetadata::is_empty")]
+    metadata: BookMetadata,
+    /// How the narration was generated, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    narration_meta: Option<NarrationMeta>,
+    /// Name of the file the book was imported from, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_filename: Option<String>,
 }
 
 /// Segment data for segments.json.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct BundleSegment {
+pub(crate) struct BundleSegment {
     id: String,
     index: u32,
     content: String,
     html: Option<String>,
+    /// Bundles from before image segments have no type; they're all text.
+    #[serde(default)]
+    segment_type: SegmentType,
+    /// Image of an image segment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image_data: Option<BundleImage>,
+    /// False for a segment left out of narration. Only written when false,
+    /// and bundles from before the flag narrate every segment.
+    #[serde(default = "narrated", skip_serializing_if = "is_narrated")]
+    narrate: bool,
+}
+
+fn narrated() -> bool {
+    true
+}
+
+fn is_narrated(narrate: &bool) -> bool {
+    *narrate
+}
+
+/// Image data of an image segment in segments.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BundleImage {
+    /// Entry name of the image file, under `assets/`.
+    asset: String,
+    caption: Option<String>,
+    caption_prompt: Option<String>,
+    alt_text: Option<String>,
+    page_number: Option<u32>,
+    #[serde(default)]
+    position: ImagePosition,
+}
+
+/// Bundle entry name for an image segment's image file.
+fn asset_entry_name(segment_id: &str, source_path: &str) -> String {
+    let extension = Path::new(source_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_else(|| "bin".to_string());
+    format!("assets/{}.{}", segment_id, extension)
+}
+
+/// Convert segments to their segments.json form.
+pub(crate) fn bundle_segments(segments: &[Segment]) -> Vec<BundleSegment> {
+    segments
+        .iter()
+        .map(|s| BundleSegment {
+            id: s.id.as_str().to_string(),
+            index: s.index,
+            content: s.content.clone(),
+            html: s.html.clone(),
+            segment_type: s.segment_type,
+            image_data: s.image_data.as_ref().map(|image| BundleImage {
+                asset: asset_entry_name(s.id.as_str(), &image.source_path),
+                caption: image.caption.clone(),
+                caption_prompt: image.caption_prompt.clone(),
+                alt_text: image.alt_text.clone(),
+                page_number: image.page_number,
+                position: image.position,
+            }),
+            narrate: s.narrate,
+        })
+        .collect()
+}
+
+/// Write the image files of `segments` into the bundle's `assets/`.
+pub(crate) fn write_bundle_assets<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    prefix: &str,
+    paths: &AppPaths,
+    segments: &[Segment],
+) -> CommandResult<()> {
+    // Images are already compressed
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .unix_permissions(0o644);
+
+    for segment in segments {
+        let Some(image) = &segment.image_data else {
+            continue;
+        };
+        let data = std::fs::read(paths.resolve(&image.source_path)).with_context(|| {
+            format!("Failed to read image for segment {}", segment.id)
+        })?;
+        let entry = asset_entry_name(segment.id.as_str(), &image.source_path);
+        zip.start_file(format!("{}{}", prefix, entry), options)
+            .context("Failed to write image to ZIP")?;
+        zip.write_all(&data).context("Failed to write image content")?;
+    }
+
+    Ok(())
 }
 
 /// Segments file structure.
@@ -72,6 +241,129 @@ struct BundleMarkers {
     markers: Vec<BundleMarker>,
 }
 
+impl From<BundleMarker> for Marker {
+    fn from(marker: BundleMarker) -> Self {
+        Marker {
+            segment_id: SegmentId::new(marker.segment_id),
+            start: marker.start,
+            end: marker.end,
+        }
+    }
+}
+
+/// A markers file accepted by [`parse_external_markers`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExternalMarkers {
+    /// narration/markers.json from a bundle.
+    Bundle(BundleMarkers),
+    /// markers.json as written next to a book's narration.
+    Narration(Vec<Marker>),
+}
+
+/// Parse markers made outside the app and check them against their audio
+/// as a bundle's are, without repairing. Either the bundle's markers.json
+/// or the plain array written next to a narration is accepted.
+pub(crate) fn parse_external_markers(json: &str, audio_duration: f64) -> CommandResult<Vec<Marker>> {
+    let mut markers = match serde_json::from_str(json)
+        .map_err(|e| CommandError::InvalidInput(format!("Invalid markers file: {}", e)))?
+    {
+        ExternalMarkers::Bundle(bundle) => bundle.markers.into_iter().map(Marker::from).collect(),
+        ExternalMarkers::Narration(markers) => markers,
+    };
+    validate_markers(&mut markers, Some(audio_duration), false)?;
+    Ok(markers)
+}
+
+/// Top-level library.json of a library archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LibraryManifest {
+    version: String,
+    created_at: i64,
+    books: Vec<LibraryEntry>,
+}
+
+/// A book in a library archive, stored as a bundle under `books/<id>/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LibraryEntry {
+    id: String,
+    title: String,
+    /// SHA-256 of the book's text, used to skip books the library already has.
+    content_hash: String,
+    /// ISBN from the source file. Matches copies whose text was parsed
+    /// differently, which the content hash misses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    isbn: Option<String>,
+    /// Unique identifier from the source file, checked like the ISBN.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+}
+
+/// Books already in the library, for skipping duplicates in an archive.
+#[derive(Debug, Default)]
+struct LocalBooks {
+    content_hashes: HashSet<String>,
+    isbns: HashSet<String>,
+    identifiers: HashSet<String>,
+}
+
+impl LocalBooks {
+    /// Load the content hash and identifiers of every book.
+    fn load(conn: &rusqlite::Connection) -> CommandResult<Self> {
+        let books: Vec<(String, Option<String>, Option<String>)> = conn
+            .prepare("SELECT id, isbn, identifier FROM books")
+            .context("Failed to prepare query")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .context("Failed to query books")?
+            .collect::<Result<_, _>>()
+            .context("Failed to read book row")?;
+
+        let mut local = Self::default();
+        for (id, isbn, identifier) in books {
+            local.content_hashes.insert(query_content_hash(conn, &id)?);
+            local.isbns.extend(isbn);
+            local.identifiers.extend(identifier);
+        }
+        Ok(local)
+    }
+
+    /// True if the entry's book is already here: the same ISBN or
+    /// identifier when the entry has one, or else the same text.
+    fn contains(&self, entry: &LibraryEntry) -> bool {
+        entry.isbn.as_ref().is_some_and(|isbn| self.isbns.contains(isbn))
+            || entry.identifier.as_ref().is_some_and(|id| self.identifiers.contains(id))
+            || self.content_hashes.contains(&entry.content_hash)
+    }
+
+    /// Remember an imported entry, so a second copy in the archive is skipped.
+    fn insert(&mut self, entry: &LibraryEntry) {
+        self.content_hashes.insert(entry.content_hash.clone());
+        self.isbns.extend(entry.isbn.clone());
+        self.identifiers.extend(entry.identifier.clone());
+    }
+}
+
+/// Progress update while exporting or importing a library archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryProgress {
+    pub current: u32,
+    pub total: u32,
+    pub title: String,
+}
+
+/// Result of importing a library archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryImportResult {
+    /// Books added to or updated in the library.
+    pub imported: Vec<Book>,
+    /// Number of books skipped because the library already has their text.
+    pub duplicates: u32,
+    /// Any errors for books that could not be imported.
+    pub errors: Vec<String>,
+}
+
 /// Get current Unix timestamp.
 fn current_timestamp() -> i64 {
     std::time::SystemTime::now()
@@ -80,22 +372,112 @@ fn current_timestamp() -> i64 {
         .as_secs() as i64
 }
 
+/// Check a book's narration can be exported: it must cover the whole book.
+/// Narration left stale by edits to the text is exported as it is, with a
+/// warning, since its audio and markers still match each other.
+pub(crate) fn ensure_narration_exportable(
+    book_id: &BookId,
+    status: NarrationStatus,
+) -> CommandResult<()> {
+    match status {
+        NarrationStatus::Ready => Ok(()),
+        NarrationStatus::Stale => {
+            log::warn!(
+                "book={}: exporting narration generated before the text was last edited",
+                book_id
+            );
+            Ok(())
+        }
+        NarrationStatus::Partial => Err(CommandError::Conflict(
+            "Only part of the book is narrated; narrate the rest before exporting".to_string(),
+        )),
+        NarrationStatus::None | NarrationStatus::Generating => Err(CommandError::Conflict(
+            "Book must have narration generated before exporting".to_string(),
+        )),
+    }
+}
+
 /// Export a book as an .actualbook bundle.
 ///
 /// Creates a ZIP archive containing:
 /// - manifest.json: Book metadata
 /// - content/segments.json: Text segments
-/// - narration/audio.mp3: Narration audio (if available)
+/// - narration/audio.{wav,mp3,opus}: Narration audio (if available)
 /// - narration/markers.json: Timing markers (if available)
 /// - assets/: Images and other assets (if any)
 ///
 /// The book must have narration generated to be exported.
+///
+/// When `speed` is set (0.5–2.0), the narration is time-stretched to that
+/// playback speed without changing pitch and every marker is rescaled to
+/// match, for players that can't change speed themselves.
+///
+/// If `output_path` is a directory, the bundle is named after the book's
+/// original filename. Returns the path written.
 #[tauri::command]
 pub async fn export_bundle(
     book_id: BookId,
     output_path: String,
+    speed: Option<f64>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<String> {
+    if let Some(speed) = speed {
+        if !(MIN_EXPORT_SPEED..=MAX_EXPORT_SPEED).contains(&speed) {
+            return Err(CommandError::InvalidInput(format!(
+                "Export speed must be between {} and {}, got {}",
+                MIN_EXPORT_SPEED, MAX_EXPORT_SPEED, speed
+            )));
+        }
+    }
+
+    let output_path = {
+        let conn = state.db.connection().lock().unwrap();
+        resolve_export_path(&conn, &book_id, &output_path, "actualbook")?
+    };
+    let output_path = output_path.to_string_lossy().to_string();
+    write_archive(&output_path, |zip| write_bundle(zip, "", &book_id, speed, &state))?;
+
+    log::info!("Exported bundle to: {}", output_path);
+
+    Ok(output_path)
+}
+
+/// Create a ZIP archive at `output_path` and fill it with `write`.
+///
+/// The file is removed again if writing fails, so a failed export leaves
+/// nothing half-written behind.
+fn write_archive<T>(
+    output_path: &str,
+    write: impl FnOnce(&mut ZipWriter<File>) -> CommandResult<T>,
+) -> CommandResult<T> {
+    let output_file = File::create(output_path).context("Failed to create output file")?;
+    let mut zip = ZipWriter::new(output_file);
+
+    let result = write(&mut zip).and_then(|value| {
+        zip.finish().context("Failed to finalize ZIP")?;
+        Ok(value)
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Write a book's bundle entries into a ZIP archive.
+///
+/// Entry names start with `prefix`, which is empty for a single-book bundle.
+/// See [`export_bundle`] for the layout and `speed`. Returns the manifest
+/// that was written.
+fn write_bundle<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    prefix: &str,
+    book_id: &BookId,
+    speed: Option<f64>,
+    state: &AppState,
+) -> CommandResult<BundleManifest> {
+    let scale = |time: f64| speed.map_or(time, |speed| time / speed);
+
     // 1. Verify book exists and has narration
     let book: Book = {
         let conn = state.db.connection().lock().unwrap();
@@ -103,10 +485,11 @@ pub async fn export_bundle(
         let mut stmt = conn
             .prepare(
                 "SELECT id, title, author, source_format, source_path, narration_status,
-                        narration_path, created_at, updated_at, last_opened_at
+                        narration_path, created_at, updated_at, last_opened_at, duration, language, finished_at,
+                        publisher, published_date, identifier, isbn, narration_meta, original_filename
                  FROM books WHERE id = ?",
             )
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            .context("Failed to prepare query")?;
 
         stmt.query_row(rusqlite::params![book_id.as_str()], |row| {
             let source_format_str: String = row.get(3)?;
@@ -125,46 +508,33 @@ pub async fn export_bundle(
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
                 last_opened_at: row.get(9)?,
+                duration: row.get(10)?,
+                language: row.get(11)?,
+                finished_at: row.get(12)?,
+                metadata: BookMetadata {
+                    publisher: row.get(13)?,
+                    published_date: row.get(14)?,
+                    identifier: row.get(15)?,
+                    isbn: row.get(16)?,
+                },
+                narration_meta: NarrationMeta::from_json(row.get::<_, Option<String>>(17)?.as_deref()),
+                original_filename: row.get(18)?,
             })
         })
         .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => "Book not found".to_string(),
-            _ => format!("Database error: {}", e),
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound("Book not found".to_string())
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
         })?
     };
 
-    // Verify book has narration ready
-    if book.narration_status != NarrationStatus::Ready {
-        return Err("Book must have narration generated before exporting".to_string());
-    }
+    ensure_narration_exportable(&book.id, book.narration_status)?;
 
     // 2. Fetch segments
     let segments: Vec<Segment> = {
         let conn = state.db.connection().lock().unwrap();
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, book_id, idx, content, html
-                 FROM segments WHERE book_id = ? ORDER BY idx ASC",
-            )
-            .map_err(|e| format!("Failed to prepare segments query: {}", e))?;
-
-        let result = stmt
-            .query_map(rusqlite::params![book_id.as_str()], |row| {
-                Ok(Segment {
-                    id: SegmentId::new(row.get::<_, String>(0)?),
-                    book_id: BookId::new(row.get::<_, String>(1)?),
-                    index: row.get(2)?,
-                    content: row.get(3)?,
-                    html: row.get(4)?,
-                    segment_type: SegmentType::Text,
-                    image_data: None,
-                })
-            })
-            .map_err(|e| format!("Failed to query segments: {}", e))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to read segment row: {}", e))?;
-        result
+        query_segments(&conn, book_id.as_str())?
     };
 
     // 3. Fetch markers
@@ -176,7 +546,7 @@ pub async fn export_bundle(
                 "SELECT segment_id, start_time, end_time
                  FROM markers WHERE book_id = ? ORDER BY start_time ASC",
             )
-            .map_err(|e| format!("Failed to prepare markers query: {}", e))?;
+            .context("Failed to prepare markers query")?;
 
         let result = stmt
             .query_map(rusqlite::params![book_id.as_str()], |row| {
@@ -186,15 +556,12 @@ pub async fn export_bundle(
                     end: row.get(2)?,
                 })
             })
-            .map_err(|e| format!("Failed to query markers: {}", e))?
+            .context("Failed to query markers")?
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to read marker row: {}", e))?;
+            .context("Failed to read marker row")?;
         result
     };
 
-    // Calculate duration from markers
-    let duration = markers.iter().map(|m| m.end).fold(0.0_f64, |a, b| a.max(b));
-
     // 4. Create manifest
     let manifest = BundleManifest {
         version: BUNDLE_VERSION.to_string(),
@@ -203,21 +570,19 @@ pub async fn export_bundle(
         author: book.author.clone(),
         source_format: book.source_format.as_str().to_string(),
         created_at: book.created_at,
-        duration: if duration > 0.0 { Some(duration) } else { None },
+        updated_at: Some(book.updated_at),
+        duration: book.duration.map(scale),
         segment_count: segments.len() as u32,
+        speed,
+        language: book.language.clone(),
+        metadata: book.metadata.clone(),
+        narration_meta: book.narration_meta.clone(),
+        original_filename: book.original_filename.clone(),
     };
 
     // 5. Create segments.json data
     let bundle_segments = BundleSegments {
-        segments: segments
-            .iter()
-            .map(|s| BundleSegment {
-                id: s.id.as_str().to_string(),
-                index: s.index,
-                content: s.content.clone(),
-                html: s.html.clone(),
-            })
-            .collect(),
+        segments: bundle_segments(&segments),
     };
 
     // 6. Create markers.json data
@@ -226,194 +591,446 @@ pub async fn export_bundle(
             .iter()
             .map(|m| BundleMarker {
                 segment_id: m.segment_id.as_str().to_string(),
-                start: m.start,
-                end: m.end,
+                start: scale(m.start),
+                end: scale(m.end),
             })
             .collect(),
     };
 
     // 7. Get narration audio path
-    let audio_path = state.paths.narration_audio_path(book_id.as_str());
-    if !audio_path.exists() {
-        return Err("Narration audio file not found".to_string());
-    }
+    let Some(audio_path) = state.paths().find_narration_audio(book_id.as_str()) else {
+        return Err(CommandError::NotFound(
+            "Narration audio file not found".to_string(),
+        ));
+    };
+    let codec = NarrationCodec::from_path(&audio_path).unwrap_or(NarrationCodec::Wav);
 
-    // 8. Create ZIP archive
-    let output_file = File::create(&output_path)
-        .map_err(|e| format!("Failed to create output file: {}", e))?;
-    let mut zip = ZipWriter::new(output_file);
+    if speed.is_some() && codec != NarrationCodec::Wav {
+        return Err(CommandError::InvalidInput(
+            "Exporting at a different speed requires narration saved as WAV".to_string(),
+        ));
+    }
 
+    // 8. Write the entries
     let options = SimpleFileOptions::default()
         .compression_method(zip::CompressionMethod::Deflated)
         .unix_permissions(0o644);
 
     // Write manifest.json
     let manifest_json = serde_json::to_string_pretty(&manifest)
-        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
-    zip.start_file("manifest.json", options)
-        .map_err(|e| format!("Failed to write manifest to ZIP: {}", e))?;
-    zip.write_all(manifest_json.as_bytes())
-        .map_err(|e| format!("Failed to write manifest content: {}", e))?;
+        .context("Failed to serialize manifest")?;
+    zip.start_file(format!("{}manifest.json", prefix), options).context("Failed to write manifest to ZIP")?;
+    zip.write_all(manifest_json.as_bytes()).context("Failed to write manifest content")?;
 
     // Write content/segments.json
     let segments_json = serde_json::to_string_pretty(&bundle_segments)
-        .map_err(|e| format!("Failed to serialize segments: {}", e))?;
-    zip.start_file("content/segments.json", options)
-        .map_err(|e| format!("Failed to write segments to ZIP: {}", e))?;
-    zip.write_all(segments_json.as_bytes())
-        .map_err(|e| format!("Failed to write segments content: {}", e))?;
+        .context("Failed to serialize segments")?;
+    zip.start_file(format!("{}content/segments.json", prefix), options).context("Failed to write segments to ZIP")?;
+    zip.write_all(segments_json.as_bytes()).context("Failed to write segments content")?;
 
     // Write narration/markers.json
     let markers_json = serde_json::to_string_pretty(&bundle_markers)
-        .map_err(|e| format!("Failed to serialize markers: {}", e))?;
-    zip.start_file("narration/markers.json", options)
-        .map_err(|e| format!("Failed to write markers to ZIP: {}", e))?;
-    zip.write_all(markers_json.as_bytes())
-        .map_err(|e| format!("Failed to write markers content: {}", e))?;
-
-    // Write narration/audio.mp3
-    let mut audio_file = File::open(&audio_path)
-        .map_err(|e| format!("Failed to open audio file: {}", e))?;
+        .context("Failed to serialize markers")?;
+    zip.start_file(format!("{}narration/markers.json", prefix), options).context("Failed to write markers to ZIP")?;
+    zip.write_all(markers_json.as_bytes()).context("Failed to write markers content")?;
+
+    // Write assets/ for image segments
+    write_bundle_assets(zip, prefix, &state.paths(), &segments)?;
+
+    // Write the narration audio
+    let mut audio_file = File::open(&audio_path).context("Failed to open audio file")?;
     let mut audio_data = Vec::new();
     audio_file
         .read_to_end(&mut audio_data)
-        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+        .context("Failed to read audio file")?;
+
+    if let Some(speed) = speed {
+        audio_data = time_stretch_wav(&audio_data, speed)
+            .context("Failed to adjust narration speed")?;
+    }
 
     // Use STORED compression for audio (already compressed)
     let audio_options = SimpleFileOptions::default()
         .compression_method(zip::CompressionMethod::Stored)
         .unix_permissions(0o644);
-    zip.start_file("narration/audio.mp3", audio_options)
-        .map_err(|e| format!("Failed to write audio to ZIP: {}", e))?;
-    zip.write_all(&audio_data)
-        .map_err(|e| format!("Failed to write audio content: {}", e))?;
+    zip.start_file(format!("{}{}", prefix, audio_entry_name(codec)), audio_options).context("Failed to write audio to ZIP")?;
+    zip.write_all(&audio_data).context("Failed to write audio content")?;
 
-    // Finalize the ZIP
-    zip.finish()
-        .map_err(|e| format!("Failed to finalize ZIP: {}", e))?;
-
-    log::info!("Exported bundle to: {}", output_path);
-
-    Ok(())
+    Ok(manifest)
 }
 
 /// Import a book from an .actualbook bundle.
 ///
 /// Extracts the bundle and adds the book to the library with its
 /// narration and markers intact.
+///
+/// By default the book gets a new ID. With `preserve_id` it keeps the ID it
+/// was exported with, so a book that makes a round trip between devices
+/// updates the original instead of becoming a duplicate; the import is
+/// refused if the library's copy was changed more recently.
+///
+/// Markers that overlap or run past the end of the audio are rejected,
+/// unless `repair_markers` is set, in which case they are trimmed to fit.
 #[tauri::command]
-pub async fn import_bundle(path: String, state: State<'_, AppState>) -> Result<Book, String> {
-    // 1. Open and validate ZIP archive
-    let bundle_file = File::open(&path)
-        .map_err(|e| format!("Failed to open bundle file: {}", e))?;
-    let mut archive = ZipArchive::new(bundle_file)
-        .map_err(|e| format!("Failed to read ZIP archive: {}", e))?;
-
-    // 2. Read and parse manifest.json
-    let manifest: BundleManifest = {
-        let mut manifest_file = archive
-            .by_name("manifest.json")
-            .map_err(|_| "Bundle is missing manifest.json".to_string())?;
-        let mut manifest_content = String::new();
-        manifest_file
-            .read_to_string(&mut manifest_content)
-            .map_err(|e| format!("Failed to read manifest: {}", e))?;
-        serde_json::from_str(&manifest_content)
-            .map_err(|e| format!("Failed to parse manifest: {}", e))?
-    };
+pub async fn import_bundle(
+    path: String,
+    preserve_id: Option<bool>,
+    repair_markers: Option<bool>,
+    state: State<'_, AppState>,
+) -> CommandResult<Book> {
+    let bundle_file = File::open(&path).context("Failed to open bundle file")?;
+    let bundle_size = bundle_file
+        .metadata()
+        .context("Failed to read bundle file")?
+        .len();
+    ensure_disk_space(&state.paths().root, bundle_size)?;
+    let mut archive = ZipArchive::new(bundle_file).context("Failed to read ZIP archive")?;
+
+    let book = import_bundle_archive(
+        &mut archive,
+        &path,
+        preserve_id.unwrap_or(false),
+        repair_markers.unwrap_or(false),
+        &state,
+    )?;
+
+    log::info!("Imported bundle: {} -> {}", path, book.id);
+    state.broadcast(super::sync::SyncUpdate::LibraryChanged);
+
+    Ok(resolve_book_paths(book, &state.paths()))
+}
 
-    // 3. Read segments.json
+/// Import a bundle archive into the library.
+///
+/// With `preserve_id` the book keeps the bundle's book and segment IDs and
+/// replaces any local copy, unless that copy was updated more recently than
+/// the bundle. Otherwise the book gets fresh IDs and is added alongside any
+/// existing copy. `source_path` is stored as the new book's source. Markers
+/// are checked against the audio as in [`super::tts::validate_markers`].
+pub(crate) fn import_bundle_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    source_path: &str,
+    preserve_id: bool,
+    repair_markers: bool,
+    state: &AppState,
+) -> CommandResult<Book> {
+    import_bundle_entries(archive, "", source_path, preserve_id, repair_markers, state)
+}
+
+/// Import a bundle whose entry names start with `prefix`, as in
+/// [`import_bundle_archive`].
+fn import_bundle_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+    source_path: &str,
+    preserve_id: bool,
+    repair_markers: bool,
+    state: &AppState,
+) -> CommandResult<Book> {
+    // 1. Read and parse manifest.json
+    let manifest = read_manifest(archive, prefix)?;
+
+    // 2. Read segments.json
     let bundle_segments: BundleSegments = {
         let mut segments_file = archive
-            .by_name("content/segments.json")
-            .map_err(|_| "Bundle is missing content/segments.json".to_string())?;
+            .by_name(&format!("{}content/segments.json", prefix))
+            .map_err(|_| CommandError::InvalidInput("Bundle is missing content/segments.json".to_string()))?;
         let mut segments_content = String::new();
         segments_file
             .read_to_string(&mut segments_content)
-            .map_err(|e| format!("Failed to read segments: {}", e))?;
-        serde_json::from_str(&segments_content)
-            .map_err(|e| format!("Failed to parse segments: {}", e))?
+            .context("Failed to read segments")?;
+        serde_json::from_str(&segments_content).context("Failed to parse segments")?
     };
 
-    // 4. Read markers.json
+    // 3. Read markers.json
     let bundle_markers: BundleMarkers = {
         let mut markers_file = archive
-            .by_name("narration/markers.json")
-            .map_err(|_| "Bundle is missing narration/markers.json".to_string())?;
+            .by_name(&format!("{}narration/markers.json", prefix))
+            .map_err(|_| CommandError::InvalidInput("Bundle is missing narration/markers.json".to_string()))?;
         let mut markers_content = String::new();
         markers_file
             .read_to_string(&mut markers_content)
-            .map_err(|e| format!("Failed to read markers: {}", e))?;
-        serde_json::from_str(&markers_content)
-            .map_err(|e| format!("Failed to parse markers: {}", e))?
+            .context("Failed to read markers")?;
+        serde_json::from_str(&markers_content).context("Failed to parse markers")?
     };
 
-    // 5. Read audio file
+    // 4. Read audio file
+    let (audio_entry, codec) = find_prefixed_audio_entry(archive, prefix)
+        .ok_or_else(|| CommandError::InvalidInput("Bundle is missing narration audio".to_string()))?;
     let audio_data: Vec<u8> = {
         let mut audio_file = archive
-            .by_name("narration/audio.mp3")
-            .map_err(|_| "Bundle is missing narration/audio.mp3".to_string())?;
+            .by_name(&audio_entry)
+            .context("Failed to open audio")?;
         let mut data = Vec::new();
         audio_file
             .read_to_end(&mut data)
-            .map_err(|e| format!("Failed to read audio: {}", e))?;
+            .context("Failed to read audio")?;
         data
     };
 
-    // 6. Generate new book ID
-    let new_book_id = BookId::new(Uuid::new_v4().to_string());
+    // Compressed audio can't be measured here, so fall back to the
+    // manifest's duration for it
+    let audio_duration = match codec {
+        NarrationCodec::Wav => Some(get_wav_duration(&audio_data).context("Failed to read audio")?),
+        _ => manifest.duration,
+    };
+    let mut markers: Vec<Marker> = bundle_markers.markers.into_iter().map(Marker::from).collect();
+    validate_markers(&mut markers, audio_duration, repair_markers)?;
+
+    // 5. Choose the book ID, checking a preserved one won't clobber newer data
+    if preserve_id && !is_valid_book_id(&manifest.id) {
+        return Err(CommandError::InvalidInput(format!(
+            "Bundle has an invalid book ID: {}",
+            manifest.id
+        )));
+    }
+    let replace = preserve_id && {
+        let conn = state.db.connection().lock().unwrap();
+        ensure_bundle_is_newer(&conn, &manifest)?
+    };
+    let book_id = if preserve_id {
+        BookId::new(manifest.id.clone())
+    } else {
+        BookId::new(Uuid::new_v4().to_string())
+    };
 
-    // 7. Create narration directory and save audio
-    let narration_dir = state.paths.narration_path(new_book_id.as_str());
-    std::fs::create_dir_all(&narration_dir)
-        .map_err(|e| format!("Failed to create narration directory: {}", e))?;
+    // 6. Create narration directory and save audio
+    let narration_dir = state.paths().narration_path(book_id.as_str());
+    let narration_existed = narration_dir.exists();
+    std::fs::create_dir_all(&narration_dir).context("Failed to create narration directory")?;
 
-    let audio_path = state.paths.narration_audio_path(new_book_id.as_str());
-    let mut audio_out = File::create(&audio_path)
-        .map_err(|e| format!("Failed to create audio file: {}", e))?;
-    audio_out
-        .write_all(&audio_data)
-        .map_err(|e| format!("Failed to write audio file: {}", e))?;
+    let audio_path = state.paths().narration_audio_path(book_id.as_str(), codec);
+    std::fs::write(&audio_path, &audio_data).context("Failed to write audio file")?;
 
-    // 8. Build segment ID mapping (old ID -> new ID)
+    // Drop audio from an earlier copy that used a different codec
+    for other in NarrationCodec::ALL.into_iter().filter(|c| *c != codec) {
+        let _ = std::fs::remove_file(state.paths().narration_audio_path(book_id.as_str(), other));
+    }
+
+    // Don't leave orphaned files behind if the book isn't added
+    let images_dir = state.paths().images_path(book_id.as_str());
+    let images_existed = images_dir.exists();
+    let discard_files = || {
+        if !narration_existed {
+            let _ = std::fs::remove_dir_all(&narration_dir);
+        }
+        if !images_existed {
+            let _ = std::fs::remove_dir_all(&images_dir);
+        }
+    };
+
+    // 7. Build segments and the segment ID mapping (old ID -> new ID),
+    // restoring images from assets/
     let mut segment_id_map: HashMap<String, String> = HashMap::new();
-    let new_segments: Vec<(String, u32, String, Option<String>)> = bundle_segments
-        .segments
-        .iter()
-        .map(|s| {
-            let new_id = format!("seg_{}", Uuid::new_v4());
-            segment_id_map.insert(s.id.clone(), new_id.clone());
-            (new_id, s.index, s.content.clone(), s.html.clone())
-        })
-        .collect();
+    let mut new_segments: Vec<Segment> = Vec::with_capacity(bundle_segments.segments.len());
+    for s in &bundle_segments.segments {
+        let new_id = if preserve_id {
+            s.id.clone()
+        } else {
+            format!("seg_{}", Uuid::new_v4())
+        };
+        let image_data = match &s.image_data {
+            Some(image) => {
+                match restore_bundle_image(archive, prefix, &state.paths(), &book_id, &new_id, image) {
+                    Ok(image_data) => Some(image_data),
+                    Err(e) => {
+                        discard_files();
+                        return Err(e);
+                    }
+                }
+            }
+            None => None,
+        };
+        segment_id_map.insert(s.id.clone(), new_id.clone());
+        new_segments.push(Segment {
+            id: SegmentId::new(new_id),
+            book_id: book_id.clone(),
+            index: s.index,
+            content: s.content.clone(),
+            html: s.html.clone(),
+            segment_type: match (&image_data, s.segment_type) {
+                (Some(_), _) => SegmentType::Image,
+                (None, SegmentType::Image) => SegmentType::Text,
+                (None, segment_type) => segment_type,
+            },
+            image_data,
+            narrate: s.narrate,
+        });
+    }
 
-    // 9. Parse source format
+    // 8. Parse source format
     let source_format = SourceFormat::from_str(&manifest.source_format)
         .unwrap_or(SourceFormat::Txt);
 
-    // 10. Create book record
+    // 9. Create book record, keeping the original timestamps for a preserved book
     let now = current_timestamp();
     let book = Book {
-        id: new_book_id.clone(),
+        id: book_id,
         title: manifest.title,
         author: manifest.author,
         source_format,
-        source_path: path.clone(), // Store original bundle path
+        source_path: source_path.to_string(),
         narration_status: NarrationStatus::Ready,
-        narration_path: Some(narration_dir.to_string_lossy().to_string()),
-        created_at: now,
-        updated_at: now,
+        narration_path: Some(state.paths().to_stored(&narration_dir)),
+        created_at: if preserve_id { manifest.created_at } else { now },
+        updated_at: if preserve_id { manifest.updated_at.unwrap_or(now) } else { now },
         last_opened_at: None,
+        duration: manifest.duration,
+        language: manifest.language,
+        finished_at: None,
+        metadata: manifest.metadata,
+        narration_meta: manifest.narration_meta,
+        original_filename: manifest.original_filename,
     };
 
-    // 11. Insert book and segments into database
-    {
+    // 10. Insert book, segments and markers into database
+    let inserted = {
         let conn = state.db.connection().lock().unwrap();
+        insert_bundle(&conn, &book, &new_segments, &markers, &segment_id_map, replace)
+    };
+
+    if inserted.is_err() {
+        discard_files();
+    }
+    inserted?;
+
+    Ok(book)
+}
+
+/// Extract an image segment's file from the bundle's `assets/` into the
+/// book's images directory, returning its image data.
+fn restore_bundle_image<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+    paths: &AppPaths,
+    book_id: &BookId,
+    segment_id: &str,
+    image: &BundleImage,
+) -> CommandResult<ImageData> {
+    // Only files directly under assets/ are accepted
+    let asset = Path::new(&image.asset);
+    let file_name = match (asset.parent(), asset.file_name()) {
+        (Some(parent), Some(name)) if parent == Path::new("assets") => name,
+        _ => {
+            return Err(CommandError::InvalidInput(format!(
+                "Bundle has an invalid image asset: {}",
+                image.asset
+            )))
+        }
+    };
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("bin")
+        .to_lowercase();
+
+    let mut data = Vec::new();
+    archive
+        .by_name(&format!("{}{}", prefix, image.asset))
+        .map_err(|_| CommandError::InvalidInput(format!("Bundle is missing {}", image.asset)))?
+        .read_to_end(&mut data)
+        .context("Failed to read image")?;
+
+    let images_dir = paths.images_path(book_id.as_str());
+    std::fs::create_dir_all(&images_dir).context("Failed to create images directory")?;
+    let dest_path = images_dir.join(format!("{}.{}", segment_id, extension));
+    std::fs::write(&dest_path, &data).context("Failed to write image")?;
+
+    Ok(ImageData {
+        source_path: paths.to_stored(&dest_path),
+        caption: image.caption.clone(),
+        caption_prompt: image.caption_prompt.clone(),
+        alt_text: image.alt_text.clone(),
+        page_number: image.page_number,
+        position: image.position,
+    })
+}
 
-        // Insert book
-        conn.execute(
-            "INSERT INTO books (id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+/// Check that a bundle is at least as new as the library's copy of its book.
+///
+/// Returns whether a local copy exists. Bundles without `updated_at` are
+/// dated by their `created_at`.
+fn ensure_bundle_is_newer(
+    conn: &rusqlite::Connection,
+    manifest: &BundleManifest,
+) -> CommandResult<bool> {
+    let local_updated_at: i64 = match conn.query_row(
+        "SELECT updated_at FROM books WHERE id = ?",
+        rusqlite::params![manifest.id],
+        |row| row.get(0),
+    ) {
+        Ok(updated_at) => updated_at,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+        Err(e) => return Err(CommandError::Database(format!("Database error: {}", e))),
+    };
+
+    if local_updated_at > manifest.updated_at.unwrap_or(manifest.created_at) {
+        return Err(CommandError::Conflict(format!(
+            "The library has a newer copy of '{}'",
+            manifest.title
+        )));
+    }
+
+    Ok(true)
+}
+
+/// Insert a bundled book with its segments and markers.
+///
+/// With `replace`, an existing book with the same ID has its details and
+/// content swapped for the bundle's. The row is updated rather than
+/// replaced so its reading progress and history survive.
+///
+/// Runs in a single transaction, so a failed insert leaves no trace of the
+/// book in the database.
+fn insert_bundle(
+    conn: &rusqlite::Connection,
+    book: &Book,
+    segments: &[Segment],
+    markers: &[Marker],
+    segment_id_map: &HashMap<String, String>,
+    replace: bool,
+) -> CommandResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    if replace {
+        tx.execute(
+            "UPDATE books SET title = ?, author = ?, narration_status = ?, narration_path = ?,
+                              updated_at = ?, duration = ?, language = ?,
+                              publisher = ?, published_date = ?, identifier = ?, isbn = ?,
+                              narration_meta = ?, original_filename = ?
+             WHERE id = ?",
+            rusqlite::params![
+                &book.title,
+                &book.author,
+                book.narration_status.as_str(),
+                &book.narration_path,
+                book.updated_at,
+                book.duration,
+                &book.language,
+                &book.metadata.publisher,
+                &book.metadata.published_date,
+                &book.metadata.identifier,
+                &book.metadata.isbn,
+                book.narration_meta.as_ref().map(NarrationMeta::to_json),
+                &book.original_filename,
+                book.id.as_str(),
+            ],
+        )
+        .context("Failed to update book")?;
+
+        // Markers go with their segments
+        tx.execute(
+            "DELETE FROM segments WHERE book_id = ?",
+            rusqlite::params![book.id.as_str()],
+        )
+        .context("Failed to clear segments")?;
+    } else {
+        tx.execute(
+            "INSERT INTO books (id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at, duration, language, publisher, published_date, identifier, isbn, narration_meta, original_filename, profile_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
             rusqlite::params![
                 book.id.as_str(),
                 &book.title,
@@ -425,35 +1042,70 @@ pub async fn import_bundle(path: String, state: State<'_, AppState>) -> Result<B
                 book.created_at,
                 book.updated_at,
                 book.last_opened_at,
+                book.duration,
+                &book.language,
+                &book.metadata.publisher,
+                &book.metadata.published_date,
+                &book.metadata.identifier,
+                &book.metadata.isbn,
+                book.narration_meta.as_ref().map(NarrationMeta::to_json),
+                &book.original_filename,
+                super::settings::active_profile_id(&tx)?,
             ],
         )
-        .map_err(|e| format!("Failed to insert book: {}", e))?;
+        .context("Failed to insert book")?;
+    }
 
+    {
         // Insert segments
-        let mut stmt = conn
-            .prepare("INSERT INTO segments (id, book_id, idx, content, html) VALUES (?1, ?2, ?3, ?4, ?5)")
-            .map_err(|e| format!("Failed to prepare segment insert: {}", e))?;
+        let mut stmt = tx
+            .prepare("INSERT INTO segments (id, book_id, idx, content, html, segment_type, narrate) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")
+            .context("Failed to prepare segment insert")?;
+
+        let mut image_stmt = tx
+            .prepare(
+                "INSERT INTO segment_images (segment_id, book_id, source_path, alt_text, page_number, position, caption, caption_prompt)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .context("Failed to prepare image insert")?;
 
-        for (seg_id, index, content, html) in &new_segments {
+        for segment in segments {
             stmt.execute(rusqlite::params![
-                seg_id,
+                segment.id.as_str(),
                 book.id.as_str(),
-                index,
-                content,
-                html,
+                segment.index,
+                &segment.content,
+                &segment.html,
+                segment.segment_type.as_str(),
+                segment.narrate,
             ])
-            .map_err(|e| format!("Failed to insert segment: {}", e))?;
+            .context("Failed to insert segment")?;
+
+            if let Some(image) = &segment.image_data {
+                image_stmt
+                    .execute(rusqlite::params![
+                        segment.id.as_str(),
+                        book.id.as_str(),
+                        &image.source_path,
+                        &image.alt_text,
+                        image.page_number,
+                        image.position.as_str(),
+                        &image.caption,
+                        &image.caption_prompt,
+                    ])
+                    .context("Failed to insert segment image")?;
+            }
         }
 
         // Insert markers with updated segment IDs
-        let mut marker_stmt = conn
+        let mut marker_stmt = tx
             .prepare("INSERT INTO markers (id, book_id, segment_id, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)")
-            .map_err(|e| format!("Failed to prepare marker insert: {}", e))?;
+            .context("Failed to prepare marker insert")?;
 
-        for marker in &bundle_markers.markers {
+        for marker in markers {
             // Map old segment ID to new segment ID
             let new_segment_id = segment_id_map
-                .get(&marker.segment_id)
+                .get(marker.segment_id.as_str())
                 .ok_or_else(|| format!("Marker references unknown segment: {}", marker.segment_id))?;
 
             let marker_id = format!("marker_{}", Uuid::new_v4());
@@ -465,46 +1117,365 @@ pub async fn import_bundle(path: String, state: State<'_, AppState>) -> Result<B
                     marker.start,
                     marker.end,
                 ])
-                .map_err(|e| format!("Failed to insert marker: {}", e))?;
+                .context("Failed to insert marker")?;
         }
     }
 
-    log::info!("Imported bundle: {} -> {}", path, new_book_id);
+    update_content_hash(&tx, book.id.as_str())?;
+    tx.commit().context("Failed to commit transaction")?;
 
-    Ok(book)
+    Ok(())
 }
 
-/// Validate a bundle file without importing it.
+/// True if a bundled book ID is safe to keep, since it names directories.
+fn is_valid_book_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A book's content hash, as stored when its text last changed.
 ///
-/// Returns information about the bundle contents for preview purposes.
+/// Books stored before the hash was kept have it computed and stored now.
+pub(crate) fn query_content_hash(conn: &rusqlite::Connection, book_id: &str) -> CommandResult<String> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT content_hash FROM books WHERE id = ?",
+            rusqlite::params![book_id],
+            |row| row.get(0),
+        )
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e),
+        })
+        .context("Failed to query content hash")?;
+
+    match stored {
+        Some(hash) => Ok(hash),
+        None => update_content_hash(conn, book_id),
+    }
+}
+
+/// Compute a book's content hash and store it in the book's row. Called
+/// whenever the book's text is stored or edited.
+pub(crate) fn update_content_hash(
+    conn: &rusqlite::Connection,
+    book_id: &str,
+) -> CommandResult<String> {
+    let hash = compute_content_hash(conn, book_id)?;
+    conn.execute(
+        "UPDATE books SET content_hash = ? WHERE id = ?",
+        rusqlite::params![hash, book_id],
+    )
+    .context("Failed to store content hash")?;
+    Ok(hash)
+}
+
+/// Hash a book's text: the SHA-256 of its segments in order.
+///
+/// Two copies of a book have the same hash whatever their IDs, so it is used
+/// to avoid importing a book twice.
+fn compute_content_hash(conn: &rusqlite::Connection, book_id: &str) -> CommandResult<String> {
+    let mut stmt = conn
+        .prepare("SELECT content FROM segments WHERE book_id = ? ORDER BY idx ASC")
+        .context("Failed to prepare segments query")?;
+    let mut rows = stmt
+        .query(rusqlite::params![book_id])
+        .context("Failed to query segments")?;
+
+    let mut hasher = Sha256::new();
+    while let Some(row) = rows.next().context("Failed to read segment row")? {
+        let content: String = row.get(0).context("Failed to read segment row")?;
+        hasher.update(content.as_bytes());
+        // Separate segments so moving text across a boundary changes the hash
+        hasher.update([0]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Check that a library archive's format version can be read.
+fn check_library_version(version: &str) -> CommandResult<()> {
+    let major = |version: &str| version.split('.').next().unwrap_or("").to_string();
+    if major(version) != major(LIBRARY_VERSION) {
+        return Err(CommandError::InvalidInput(format!(
+            "Unsupported library archive version: {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+/// Export several books into one library archive.
+///
+/// Creates a ZIP archive containing:
+/// - library.json: Archive version and the list of books with content hashes
+/// - books/<id>/: Each book in the single-book bundle layout
+///
+/// Every book must have narration generated. Emits `library_export_progress`
+/// events as each book is written.
 #[tauri::command]
-pub async fn validate_bundle(path: String) -> Result<BundleInfo, String> {
-    // 1. Open ZIP archive
-    let bundle_file = File::open(&path)
-        .map_err(|e| format!("Failed to open bundle file: {}", e))?;
-    let mut archive = ZipArchive::new(bundle_file)
-        .map_err(|e| format!("Failed to read ZIP archive: {}", e))?;
+pub async fn export_library(
+    book_ids: Vec<BookId>,
+    output_path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let mut seen = HashSet::new();
+    let book_ids: Vec<BookId> = book_ids.into_iter().filter(|id| seen.insert(id.clone())).collect();
+    if book_ids.is_empty() {
+        return Err(CommandError::InvalidInput("No books selected for export".to_string()));
+    }
 
-    // 2. Read manifest.json
-    let manifest: BundleManifest = {
+    let total = book_ids.len() as u32;
+    write_archive(&output_path, |zip| {
+        let mut books = Vec::with_capacity(book_ids.len());
+
+        for (n, book_id) in book_ids.iter().enumerate() {
+            let prefix = format!("books/{}/", book_id);
+            let manifest = write_bundle(zip, &prefix, book_id, None, &state)
+                .with_context(|| format!("Failed to export book {}", book_id))?;
+            let content_hash = {
+                let conn = state.db.connection().lock().unwrap();
+                query_content_hash(&conn, book_id.as_str())?
+            };
+
+            let _ = app.emit(
+                "library_export_progress",
+                &LibraryProgress {
+                    current: n as u32 + 1,
+                    total,
+                    title: manifest.title.clone(),
+                },
+            );
+
+            books.push(LibraryEntry {
+                id: manifest.id,
+                title: manifest.title,
+                content_hash,
+                isbn: manifest.metadata.isbn,
+                identifier: manifest.metadata.identifier,
+            });
+        }
+
+        let manifest = LibraryManifest {
+            version: LIBRARY_VERSION.to_string(),
+            created_at: current_timestamp(),
+            books,
+        };
+        let manifest_json = serde_json::to_string_pretty(&manifest)
+            .context("Failed to serialize library manifest")?;
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .unix_permissions(0o644);
+        zip.start_file("library.json", options).context("Failed to write library manifest to ZIP")?;
+        zip.write_all(manifest_json.as_bytes()).context("Failed to write library manifest content")?;
+
+        Ok(())
+    })?;
+
+    log::info!("Exported {} book(s) to library archive: {}", total, output_path);
+
+    Ok(())
+}
+
+/// Import every book in a library archive.
+///
+/// Books keep their IDs, so importing a backup onto the device it came from
+/// updates books in place; a book whose local copy is newer is reported as
+/// an error and left alone. Books the library already has under any ID,
+/// matched by ISBN, identifier or text, are skipped. Emits `library_import_progress` events as each book
+/// is processed.
+#[tauri::command]
+pub async fn import_library(
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<LibraryImportResult> {
+    let archive_file = File::open(&path).context("Failed to open library archive")?;
+    let mut archive = ZipArchive::new(archive_file).context("Failed to read ZIP archive")?;
+
+    let manifest: LibraryManifest = {
         let mut manifest_file = archive
-            .by_name("manifest.json")
-            .map_err(|_| "Bundle is missing manifest.json".to_string())?;
+            .by_name("library.json")
+            .map_err(|_| CommandError::InvalidInput("Archive is missing library.json".to_string()))?;
         let mut manifest_content = String::new();
         manifest_file
             .read_to_string(&mut manifest_content)
-            .map_err(|e| format!("Failed to read manifest: {}", e))?;
-        serde_json::from_str(&manifest_content)
-            .map_err(|e| format!("Failed to parse manifest: {}", e))?
+            .context("Failed to read library manifest")?;
+        serde_json::from_str(&manifest_content).context("Failed to parse library manifest")?
+    };
+    check_library_version(&manifest.version)?;
+
+    let mut local_books = {
+        let conn = state.db.connection().lock().unwrap();
+        LocalBooks::load(&conn)?
+    };
+
+    let mut result = LibraryImportResult {
+        imported: Vec::new(),
+        duplicates: 0,
+        errors: Vec::new(),
+    };
+    let total = manifest.books.len() as u32;
+
+    for (n, entry) in manifest.books.iter().enumerate() {
+        let _ = app.emit(
+            "library_import_progress",
+            &LibraryProgress {
+                current: n as u32 + 1,
+                total,
+                title: entry.title.clone(),
+            },
+        );
+
+        if local_books.contains(entry) {
+            result.duplicates += 1;
+            continue;
+        }
+
+        let prefix = format!("books/{}/", entry.id);
+        match import_bundle_entries(&mut archive, &prefix, &path, true, false, &state) {
+            Ok(book) => {
+                local_books.insert(entry);
+                result.imported.push(resolve_book_paths(book, &state.paths()));
+            }
+            Err(e) => {
+                let error = format!("Failed to import '{}': {}", entry.title, e);
+                log::error!("{}", error);
+                result.errors.push(error);
+            }
+        }
+    }
+
+    log::info!(
+        "Imported library archive {}: {} added, {} duplicate(s), {} error(s)",
+        path,
+        result.imported.len(),
+        result.duplicates,
+        result.errors.len()
+    );
+
+    Ok(result)
+}
+
+/// Read and parse the manifest of a bundle stored under `prefix`.
+fn read_manifest<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+) -> CommandResult<BundleManifest> {
+    let mut manifest_file = archive
+        .by_name(&format!("{}manifest.json", prefix))
+        .map_err(|_| CommandError::InvalidInput("Bundle is missing manifest.json".to_string()))?;
+    let mut manifest_content = String::new();
+    manifest_file
+        .read_to_string(&mut manifest_content)
+        .context("Failed to read manifest")?;
+    serde_json::from_str(&manifest_content).context("Failed to parse manifest")
+}
+
+/// List the files in `dir`, newest first, with the title and author of
+/// each one that is a readable bundle.
+fn list_bundle_files(dir: &Path) -> CommandResult<Vec<BundleFileInfo>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read bundles directory"),
     };
 
+    let mut bundles = Vec::new();
+    for entry in entries {
+        let entry = entry.context("Failed to read bundles directory")?;
+        let metadata = entry.metadata().context("Failed to read bundle metadata")?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let manifest = File::open(&path)
+            .ok()
+            .and_then(|file| ZipArchive::new(file).ok())
+            .and_then(|mut archive| read_manifest(&mut archive, "").ok());
+        let modified_at = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|age| age.as_secs() as i64);
+
+        bundles.push(BundleFileInfo {
+            filename: entry.file_name().to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified_at,
+            title: manifest.as_ref().map(|m| m.title.clone()),
+            author: manifest.and_then(|m| m.author),
+        });
+    }
+
+    bundles.sort_by(|a, b| {
+        b.modified_at
+            .cmp(&a.modified_at)
+            .then_with(|| a.filename.cmp(&b.filename))
+    });
+    Ok(bundles)
+}
+
+/// Delete the file `filename` from `dir`. The name must not reach outside
+/// the directory.
+fn remove_bundle_file(dir: &Path, filename: &str) -> CommandResult<()> {
+    let is_plain_name = Path::new(filename)
+        .file_name()
+        .is_some_and(|name| name == std::ffi::OsStr::new(filename));
+    if !is_plain_name {
+        return Err(CommandError::InvalidInput(format!(
+            "Invalid bundle file name: {}",
+            filename
+        )));
+    }
+
+    let path = dir.join(filename);
+    if !path.is_file() {
+        return Err(CommandError::NotFound("Bundle not found".to_string()));
+    }
+    std::fs::remove_file(&path).context("Failed to delete bundle")
+}
+
+/// List the files in the bundles directory, newest first.
+///
+/// Each bundle's manifest is read for its title and author, which are None
+/// for files that aren't readable bundles, such as an interrupted export.
+#[tauri::command]
+pub async fn list_bundles(state: State<'_, AppState>) -> CommandResult<Vec<BundleFileInfo>> {
+    list_bundle_files(&state.paths().bundles)
+}
+
+/// Delete a file from the bundles directory by the name `list_bundles`
+/// gave it. Books imported from the bundle are unaffected.
+#[tauri::command]
+pub async fn delete_bundle(filename: String, state: State<'_, AppState>) -> CommandResult<()> {
+    remove_bundle_file(&state.paths().bundles, &filename)?;
+    log::info!("Deleted bundle {}", filename);
+    Ok(())
+}
+
+/// Validate a bundle file without importing it.
+///
+/// Returns information about the bundle contents for preview purposes.
+#[tauri::command]
+pub async fn validate_bundle(path: String) -> CommandResult<BundleInfo> {
+    // 1. Open ZIP archive
+    let bundle_file = File::open(&path).context("Failed to open bundle file")?;
+    let mut archive = ZipArchive::new(bundle_file).context("Failed to read ZIP archive")?;
+
+    // 2. Read manifest.json
+    let manifest = read_manifest(&mut archive, "")?;
+
     // 3. Verify required files exist
     let has_segments = archive.by_name("content/segments.json").is_ok();
-    let has_audio = archive.by_name("narration/audio.mp3").is_ok();
+    let has_audio = find_audio_entry(&mut archive).is_some();
     let has_markers = archive.by_name("narration/markers.json").is_ok();
 
     if !has_segments {
-        return Err("Bundle is missing content/segments.json".to_string());
+        return Err(CommandError::InvalidInput(
+            "Bundle is missing content/segments.json".to_string(),
+        ));
     }
 
     // Narration is considered present if both audio and markers exist
@@ -539,8 +1510,17 @@ mod tests {
             author: Some("Test Author".to_string()),
             source_format: "epub".to_string(),
             created_at: 1705334400,
+            updated_at: None,
             duration: Some(3600.5),
             segment_count: 150,
+            speed: None,
+            language: Some("en-GB".to_string()),
+            metadata: BookMetadata {
+                isbn: Some("9780141439518".to_string()),
+                ..BookMetadata::default()
+            },
+            narration_meta: None,
+            original_filename: None,
         };
 
         let json = serde_json::to_string(&manifest).unwrap();
@@ -550,6 +1530,253 @@ mod tests {
         assert_eq!(parsed.title, "Test Book");
         assert_eq!(parsed.author, Some("Test Author".to_string()));
         assert_eq!(parsed.segment_count, 150);
+        assert_eq!(parsed.language.as_deref(), Some("en-GB"));
+        assert_eq!(parsed.metadata.isbn.as_deref(), Some("9780141439518"));
+    }
+
+    #[test]
+    fn test_list_and_delete_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list_bundle_files(&dir.path().join("missing")).unwrap().is_empty());
+
+        let mut zip = ZipWriter::new(File::create(dir.path().join("book.actualbook")).unwrap());
+        zip.start_file("manifest.json", SimpleFileOptions::default()).unwrap();
+        zip.write_all(
+            br#"{"version": "1.0", "id": "b", "title": "Moby-Dick", "author": "Herman Melville",
+                 "source_format": "epub", "created_at": 0, "duration": null, "segment_count": 1}"#,
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        std::fs::write(dir.path().join("partial.actualbook"), b"not a zip").unwrap();
+        std::fs::create_dir(dir.path().join("subdir")).unwrap();
+
+        let mut bundles = list_bundle_files(dir.path()).unwrap();
+        bundles.sort_by(|a, b| a.filename.cmp(&b.filename));
+        let listed: Vec<(&str, Option<&str>, Option<&str>)> = bundles
+            .iter()
+            .map(|b| (b.filename.as_str(), b.title.as_deref(), b.author.as_deref()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("book.actualbook", Some("Moby-Dick"), Some("Herman Melville")),
+                ("partial.actualbook", None, None),
+            ]
+        );
+        assert_eq!(bundles[1].size, 9);
+
+        for name in ["../book.actualbook", "subdir/x", "..", ""] {
+            assert!(matches!(
+                remove_bundle_file(dir.path(), name),
+                Err(CommandError::InvalidInput(_))
+            ));
+        }
+        assert!(matches!(
+            remove_bundle_file(dir.path(), "subdir"),
+            Err(CommandError::NotFound(_))
+        ));
+        remove_bundle_file(dir.path(), "partial.actualbook").unwrap();
+        assert_eq!(list_bundle_files(dir.path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_local_books_match_identifiers_before_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at, isbn)
+             VALUES ('a', 'A', 'epub', 'a.epub', 0, 0, '9780141439518');
+             INSERT INTO segments (id, book_id, idx, content) VALUES ('a1', 'a', 0, 'One');",
+        )
+        .unwrap();
+
+        let mut local = LocalBooks::load(&conn).unwrap();
+        let entry = |hash: &str, isbn: Option<&str>, identifier: Option<&str>| LibraryEntry {
+            id: "x".to_string(),
+            title: "X".to_string(),
+            content_hash: hash.to_string(),
+            isbn: isbn.map(str::to_string),
+            identifier: identifier.map(str::to_string),
+        };
+
+        // Same ISBN with text parsed differently
+        assert!(local.contains(&entry("other", Some("9780141439518"), None)));
+        assert!(local.contains(&entry(&query_content_hash(&conn, "a").unwrap(), None, None)));
+        assert!(!local.contains(&entry("other", Some("0141439513"), Some("urn:uuid:1"))));
+
+        local.insert(&entry("new", None, Some("urn:uuid:1")));
+        assert!(local.contains(&entry("other", None, Some("urn:uuid:1"))));
+    }
+
+    #[test]
+    fn test_parse_external_markers() {
+        let bundle = r#"{"markers": [{"segment_id": "b", "start": 1.0, "end": 2.0},
+                                     {"segment_id": "a", "start": 0.0, "end": 1.0}]}"#;
+        let markers = parse_external_markers(bundle, 2.0).unwrap();
+        let ids: Vec<&str> = markers.iter().map(|m| m.segment_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        let narration = r#"[{"segmentId": "a", "start": 0.0, "end": 1.5}]"#;
+        assert_eq!(parse_external_markers(narration, 2.0).unwrap().len(), 1);
+
+        assert!(parse_external_markers(narration, 1.0).is_err());
+        assert!(parse_external_markers("{\"segments\": []}", 1.0).is_err());
+    }
+
+    #[test]
+    fn test_ensure_narration_exportable() {
+        let book_id = BookId::new("book");
+        assert!(ensure_narration_exportable(&book_id, NarrationStatus::Ready).is_ok());
+        assert!(ensure_narration_exportable(&book_id, NarrationStatus::Stale).is_ok());
+        assert!(matches!(
+            ensure_narration_exportable(&book_id, NarrationStatus::None),
+            Err(CommandError::Conflict(_))
+        ));
+        assert!(matches!(
+            ensure_narration_exportable(&book_id, NarrationStatus::Partial),
+            Err(CommandError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_content_hash_ignores_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+             VALUES ('a', 'A', 'txt', 'a.txt', 0, 0), ('b', 'B', 'txt', 'b.txt', 0, 0),
+                    ('c', 'C', 'txt', 'c.txt', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content) VALUES
+                 ('a1', 'a', 0, 'One'), ('a2', 'a', 1, 'Two'),
+                 ('b2', 'b', 1, 'Two'), ('b1', 'b', 0, 'One'),
+                 ('c1', 'c', 0, 'OneT'), ('c2', 'c', 1, 'wo');",
+        )
+        .unwrap();
+
+        let hash = |id: &str| query_content_hash(&conn, id).unwrap();
+        assert_eq!(hash("a"), hash("b"));
+        assert_ne!(hash("a"), hash("c"));
+        assert_eq!(hash("a").len(), 64);
+
+        // The hash is stored, so it only changes once recomputed after an edit
+        conn.execute("UPDATE segments SET content = 'Uno' WHERE id = 'a1'", []).unwrap();
+        let stored = hash("a");
+        assert_eq!(stored, hash("b"));
+        assert_ne!(update_content_hash(&conn, "a").unwrap(), stored);
+        assert_ne!(hash("a"), hash("b"));
+    }
+
+    #[test]
+    fn test_library_manifest_version() {
+        assert!(check_library_version(LIBRARY_VERSION).is_ok());
+        assert!(check_library_version("1.3").is_ok());
+        assert!(matches!(check_library_version("2.0"), Err(CommandError::InvalidInput(_))));
+
+        assert!(is_valid_book_id("0b6f6a1e-2c1d-4f3e-9a7b-5d8c9e0f1a2b"));
+        assert!(!is_valid_book_id("../escape"));
+        assert!(!is_valid_book_id(""));
+    }
+
+    #[test]
+    fn test_bundle_manifest_speed() {
+        let json = r#"{"version":"1.0","id":"test-id","title":"Test Book","author":null,
+            "source_format":"txt","created_at":1705334400,"duration":10.0,"segment_count":1}"#;
+        let mut manifest: BundleManifest = serde_json::from_str(json).unwrap();
+        assert_eq!(manifest.speed, None);
+        assert!(!serde_json::to_string(&manifest).unwrap().contains("speed"));
+
+        manifest.speed = Some(1.5);
+        let parsed: BundleManifest =
+            serde_json::from_str(&serde_json::to_string(&manifest).unwrap()).unwrap();
+        assert_eq!(parsed.speed, Some(1.5));
+    }
+
+    #[test]
+    fn test_replace_bundle_keeps_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Old Title', 'txt', 'sources/book.txt', 'ready', 0, 100);
+             INSERT INTO segments (id, book_id, idx, content) VALUES ('a', 'book', 0, 'Old');
+             INSERT INTO progress (book_id, segment_index, max_segment_index, updated_at)
+             VALUES ('book', 0, 0, 100);",
+        )
+        .unwrap();
+
+        let mut manifest: BundleManifest = serde_json::from_str(
+            r#"{"version":"1.0","id":"book","title":"New Title","author":null,
+                "source_format":"txt","created_at":0,"updated_at":50,"duration":1.0,"segment_count":1}"#,
+        )
+        .unwrap();
+
+        // The library's copy is newer than the bundle
+        assert!(matches!(
+            ensure_bundle_is_newer(&conn, &manifest),
+            Err(CommandError::Conflict(_))
+        ));
+
+        manifest.updated_at = Some(200);
+        assert!(ensure_bundle_is_newer(&conn, &manifest).unwrap());
+
+        let book = Book {
+            id: BookId::new("book"),
+            title: manifest.title.clone(),
+            author: None,
+            source_format: SourceFormat::Txt,
+            source_path: String::new(),
+            narration_status: NarrationStatus::Ready,
+            narration_path: Some("narration/book".to_string()),
+            created_at: 0,
+            updated_at: 200,
+            last_opened_at: None,
+            duration: Some(1.0),
+            language: None,
+            finished_at: None,
+            metadata: BookMetadata::default(),
+            narration_meta: None,
+            original_filename: None,
+        };
+        let segments = vec![Segment {
+            id: SegmentId::new("a"),
+            book_id: BookId::new("book"),
+            index: 0,
+            content: "New".to_string(),
+            html: None,
+            segment_type: SegmentType::Text,
+            image_data: None,
+            narrate: true,
+        }];
+        let markers = vec![Marker {
+            segment_id: SegmentId::new("a"),
+            start: 0.0,
+            end: 1.0,
+        }];
+        let map = HashMap::from([("a".to_string(), "a".to_string())]);
+        insert_bundle(&conn, &book, &segments, &markers, &map, true).unwrap();
+
+        let (title, source_path): (String, String) = conn
+            .query_row("SELECT title, source_path FROM books WHERE id = 'book'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(title, "New Title");
+        assert_eq!(source_path, "sources/book.txt");
+
+        let content: String = conn
+            .query_row("SELECT content FROM segments WHERE id = 'a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(content, "New");
+
+        let progress: u32 = conn
+            .query_row("SELECT COUNT(*) FROM progress WHERE book_id = 'book'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(progress, 1);
     }
 
     #[test]
@@ -561,12 +1788,18 @@ mod tests {
                     index: 0,
                     content: "Chapter 1".to_string(),
                     html: Some("<h1>Chapter 1</h1>".to_string()),
+                    segment_type: SegmentType::Text,
+                    image_data: None,
+                    narrate: true,
                 },
                 BundleSegment {
                     id: "seg_002".to_string(),
                     index: 1,
                     content: "Paragraph text".to_string(),
                     html: Some("<p>Paragraph text</p>".to_string()),
+                    segment_type: SegmentType::Text,
+                    image_data: None,
+                    narrate: false,
                 },
             ],
         };
@@ -577,6 +1810,79 @@ mod tests {
         assert_eq!(parsed.segments.len(), 2);
         assert_eq!(parsed.segments[0].id, "seg_001");
         assert_eq!(parsed.segments[1].index, 1);
+        assert_eq!(json.matches("narrate").count(), 1);
+        assert!(parsed.segments[0].narrate);
+        assert!(!parsed.segments[1].narrate);
+
+        // Segments from older bundles have no type and are text
+        let old: BundleSegments = serde_json::from_str(
+            r#"{"segments": [{"id": "seg_001", "index": 0, "content": "Text", "html": null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(old.segments[0].segment_type, SegmentType::Text);
+        assert!(old.segments[0].image_data.is_none());
+        assert!(old.segments[0].narrate);
+    }
+
+    #[test]
+    fn test_bundle_image_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let image_path = paths.images_path("book").join("seg_1.PNG");
+        std::fs::create_dir_all(image_path.parent().unwrap()).unwrap();
+        std::fs::write(&image_path, b"png data").unwrap();
+
+        let segments = vec![Segment {
+            id: SegmentId::new("seg_1"),
+            book_id: BookId::new("book"),
+            index: 0,
+            content: "A lighthouse".to_string(),
+            html: None,
+            segment_type: SegmentType::Image,
+            image_data: Some(ImageData {
+                source_path: paths.to_stored(&image_path),
+                caption: Some("A lighthouse".to_string()),
+                caption_prompt: None,
+                alt_text: Some("Lighthouse".to_string()),
+                page_number: Some(3),
+                position: ImagePosition::FullPage,
+            }),
+            narrate: true,
+        }];
+        let bundled = bundle_segments(&segments);
+        assert_eq!(bundled[0].segment_type, SegmentType::Image);
+
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+            write_bundle_assets(&mut zip, "", &paths, &segments).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(&buffer)).unwrap();
+        let image = bundled[0].image_data.as_ref().unwrap();
+        assert_eq!(image.asset, "assets/seg_1.png");
+
+        let restored =
+            restore_bundle_image(&mut archive, "", &paths, &BookId::new("copy"), "seg_2", image)
+                .unwrap();
+        assert_eq!(
+            std::fs::read(paths.resolve(&restored.source_path)).unwrap(),
+            b"png data"
+        );
+        assert_eq!(restored.page_number, Some(3));
+        assert_eq!(restored.position, ImagePosition::FullPage);
+
+        // Assets can't point outside assets/
+        let escaping = BundleImage {
+            asset: "assets/../manifest.json".to_string(),
+            ..image.clone()
+        };
+        assert!(
+            restore_bundle_image(&mut archive, "", &paths, &BookId::new("copy"), "seg_3", &escaping)
+                .is_err()
+        );
     }
 
     #[test]
@@ -622,8 +1928,14 @@ mod tests {
                 author: None,
                 source_format: "txt".to_string(),
                 created_at: 1705334400,
+                updated_at: None,
                 duration: Some(10.0),
                 segment_count: 1,
+                speed: None,
+                language: None,
+                metadata: BookMetadata::default(),
+                narration_meta: None,
+                original_filename: None,
             };
             zip.start_file("manifest.json", options).unwrap();
             zip.write_all(serde_json::to_string(&manifest).unwrap().as_bytes())
@@ -636,6 +1948,9 @@ mod tests {
                     index: 0,
                     content: "Test content".to_string(),
                     html: None,
+                    segment_type: SegmentType::Text,
+                    image_data: None,
+                    narrate: true,
                 }],
             };
             zip.start_file("content/segments.json", options).unwrap();
@@ -655,7 +1970,7 @@ mod tests {
                 .unwrap();
 
             // Write dummy audio
-            zip.start_file("narration/audio.mp3", options).unwrap();
+            zip.start_file("narration/audio.opus", options).unwrap();
             zip.write_all(b"fake audio data").unwrap();
 
             zip.finish().unwrap();
@@ -691,5 +2006,11 @@ mod tests {
             let markers: BundleMarkers = serde_json::from_str(&markers_content).unwrap();
             assert_eq!(markers.markers.len(), 1);
         }
+
+        // Audio is found whatever its codec
+        assert_eq!(
+            find_audio_entry(&mut archive),
+            Some(("narration/audio.opus".to_string(), NarrationCodec::Opus))
+        );
     }
 }
@@ -0,0 +1,63 @@
+//! Caption export command handlers for Actual Reader.
+//!
+//! Writes narration timing as WebVTT or SRT subtitles for accessibility and
+//! external players.
+
+use tauri::State;
+
+use super::error::{CommandResult, ResultExt};
+use crate::models::BookId;
+use crate::services::captions::{format_captions, split_long_cues, CaptionFormat, Cue};
+use crate::AppState;
+
+/// Export a book's narration timing as a caption file.
+///
+/// Each narrated segment becomes a cue with its plain-text content, in
+/// narration order. When `max_cue_seconds` is set, longer cues are split
+/// into several shorter ones.
+#[tauri::command]
+pub async fn export_captions(
+    book_id: BookId,
+    output_path: String,
+    format: CaptionFormat,
+    max_cue_seconds: Option<f64>,
+    state: State<'_, AppState>,
+) -> CommandResult<u32> {
+    let cues: Vec<Cue> = {
+        let conn = state.db.connection().lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.start_time, m.end_time, s.content
+                 FROM markers m JOIN segments s ON s.id = m.segment_id
+                 WHERE m.book_id = ?
+                 ORDER BY m.start_time",
+            )
+            .context("Failed to prepare query")?;
+
+        let result = stmt
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok(Cue {
+                    start: row.get(0)?,
+                    end: row.get(1)?,
+                    text: row.get(2)?,
+                })
+            })
+            .context("Failed to query markers")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read marker row")?;
+        result
+    };
+
+    let cues = match max_cue_seconds {
+        Some(max_seconds) => split_long_cues(cues, max_seconds),
+        None => cues,
+    };
+
+    std::fs::write(&output_path, format_captions(&cues, format))
+        .context("Failed to write caption file")?;
+
+    log::info!("Exported {} caption cue(s) to: {}", cues.len(), output_path);
+
+    Ok(cues.len() as u32)
+}
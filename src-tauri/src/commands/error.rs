@@ -0,0 +1,254 @@
+//! Error type shared by all command handlers.
+//!
+//! Commands return [`CommandError`], which serializes to a tagged object
+//! (`{ "kind": "notFound", "message": "Book not found" }`) so the frontend
+//! can branch on the kind of failure instead of parsing message strings.
+
+use std::fmt::Display;
+use std::sync::PoisonError;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::services::ffmpeg::FfmpegError;
+use crate::services::parser::ParseError;
+use crate::services::tls::TlsError;
+use crate::services::tts::TtsError;
+use crate::services::vision::VisionError;
+
+/// Error returned to the frontend by command handlers.
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum CommandError {
+    /// The requested book, voice, segment, etc. does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// Arguments or input data were rejected.
+    #[error("{0}")]
+    InvalidInput(String),
+    /// A file system operation failed.
+    #[error("{0}")]
+    Io(String),
+    /// A database operation failed.
+    #[error("{0}")]
+    Database(String),
+    /// The TTS server, vision server, or a sync peer could not be reached.
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    /// The request conflicts with work already in progress.
+    #[error("{0}")]
+    Conflict(String),
+    /// Any other failure.
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl CommandError {
+    /// Prefix the message with `context`, keeping the error kind.
+    pub fn context(self, context: impl Display) -> Self {
+        let wrap = |message: String| format!("{}: {}", context, message);
+        match self {
+            Self::NotFound(m) => Self::NotFound(wrap(m)),
+            Self::InvalidInput(m) => Self::InvalidInput(wrap(m)),
+            Self::Io(m) => Self::Io(wrap(m)),
+            Self::Database(m) => Self::Database(wrap(m)),
+            Self::ServiceUnavailable(m) => Self::ServiceUnavailable(wrap(m)),
+            Self::Conflict(m) => Self::Conflict(wrap(m)),
+            Self::Internal(m) => Self::Internal(wrap(m)),
+        }
+    }
+
+    /// The message without the kind.
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(m)
+            | Self::InvalidInput(m)
+            | Self::Io(m)
+            | Self::Database(m)
+            | Self::ServiceUnavailable(m)
+            | Self::Conflict(m)
+            | Self::Internal(m) => m,
+        }
+    }
+}
+
+/// Result type returned by command handlers.
+pub type CommandResult<T> = Result<T, CommandError>;
+
+/// Convert errors to [`CommandError`] while adding a description of what failed.
+pub trait ResultExt<T> {
+    /// Convert the error, prefixing its message with `context`.
+    fn context(self, context: &str) -> CommandResult<T>;
+
+    /// Like [`ResultExt::context`], building the context lazily.
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> CommandResult<T>;
+}
+
+impl<T, E: Into<CommandError>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: &str) -> CommandResult<T> {
+        self.map_err(|e| e.into().context(context))
+    }
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> CommandResult<T> {
+        self.map_err(|e| e.into().context(context()))
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::Internal(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::Internal(message.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound(e.to_string()),
+            _ => Self::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(e.to_string()),
+            _ => Self::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<regex::Error> for CommandError {
+    fn from(e: regex::Error) -> Self {
+        Self::InvalidInput(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::InvalidInput(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for CommandError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            _ => Self::InvalidInput(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::ServiceUnavailable(e.to_string())
+    }
+}
+
+impl From<TlsError> for CommandError {
+    fn from(e: TlsError) -> Self {
+        match e {
+            TlsError::IoError(e) => e.into(),
+            _ => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<mdns_sd::Error> for CommandError {
+    fn from(e: mdns_sd::Error) -> Self {
+        Self::ServiceUnavailable(e.to_string())
+    }
+}
+
+impl<T> From<PoisonError<T>> for CommandError {
+    fn from(e: PoisonError<T>) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+impl From<TtsError> for CommandError {
+    fn from(e: TtsError) -> Self {
+        match e {
+            TtsError::ServerUnavailable(_) | TtsError::HttpError(_) => {
+                Self::ServiceUnavailable(e.to_string())
+            }
+            TtsError::IoError(e) => e.into(),
+            _ => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<VisionError> for CommandError {
+    fn from(e: VisionError) -> Self {
+        match e {
+            VisionError::ServiceUnavailable(_) | VisionError::HttpError(_) => {
+                Self::ServiceUnavailable(e.to_string())
+            }
+            VisionError::ProcessingError(_) => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<FfmpegError> for CommandError {
+    fn from(e: FfmpegError) -> Self {
+        match e {
+            FfmpegError::NotInstalled => Self::ServiceUnavailable(e.to_string()),
+            FfmpegError::IoError(_) => Self::Io(e.to_string()),
+            FfmpegError::Failed(_) => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<ParseError> for CommandError {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::IoError(e) => e.into(),
+            _ => Self::InvalidInput(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_as_kind_and_message() {
+        let error = CommandError::NotFound("Book not found".to_string());
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({ "kind": "notFound", "message": "Book not found" })
+        );
+
+        let error = CommandError::ServiceUnavailable("offline".to_string());
+        assert_eq!(serde_json::to_value(&error).unwrap()["kind"], "serviceUnavailable");
+    }
+
+    #[test]
+    fn test_context_keeps_kind() {
+        let result: Result<(), rusqlite::Error> = Err(rusqlite::Error::QueryReturnedNoRows);
+        let error = result.context("Failed to load book").unwrap_err();
+
+        assert!(matches!(error, CommandError::NotFound(_)));
+        assert!(error.message().starts_with("Failed to load book: "));
+    }
+
+    #[test]
+    fn test_io_errors_map_to_io_kind() {
+        let error: CommandError =
+            std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied").into();
+        assert!(matches!(error, CommandError::Io(_)));
+
+        let error: CommandError =
+            std::io::Error::new(std::io::ErrorKind::NotFound, "missing").into();
+        assert!(matches!(error, CommandError::NotFound(_)));
+    }
+}
@@ -2,209 +2,2883 @@
 //!
 //! Commands for managing the book library: importing, listing, and deleting books.
 
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
 use uuid::Uuid;
 
-use crate::models::{Book, BookId, NarrationStatus, SourceFormat};
-use crate::services::parser::{self, SourceFormat as ParserSourceFormat};
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::progress::ProgressThrottle;
+use crate::models::{
+    Book, BookId, BookMetadata, Marker, NarrationMeta, NarrationStatus, SegmentId, SourceFormat,
+};
+use crate::services::parser::{
+    self, txt, ParseWarning, ParsedBook, SegmentMerger, SourceFormat as ParserSourceFormat,
+};
+use crate::storage::{available_space, dir_size, relativize_book_paths, AppPaths, Database};
 use crate::AppState;
 
 /// Convert parser SourceFormat to model SourceFormat.
 fn parser_format_to_model_format(format: ParserSourceFormat) -> SourceFormat {
     match format {
         ParserSourceFormat::Epub => SourceFormat::Epub,
+        ParserSourceFormat::Html => SourceFormat::Html,
         ParserSourceFormat::Markdown => SourceFormat::Markdown,
         ParserSourceFormat::Txt => SourceFormat::Txt,
     }
 }
 
+/// Detect a file's format from its contents and extension, as
+/// [`parser::detect_format`] does, so a mislabeled file imports as what it is.
+///
+/// Returns the extension to store the file under and the detected format.
+fn detect_format(source_path: &Path) -> CommandResult<(&'static str, SourceFormat)> {
+    let parser_format = parser::detect_format(source_path)?;
+    Ok((parser_format.extension(), parser_format_to_model_format(parser_format)))
+}
+
+/// Minimum segment length for merging short segments, if the import
+/// preferences ask for it.
+fn merge_min_chars(db: &Database) -> CommandResult<Option<usize>> {
+    let preferences = super::settings::load_import_preferences(db)?;
+    Ok(preferences
+        .merge_short_segments
+        .then_some(preferences.merge_segment_min_chars as usize))
+}
+
+/// Detect a file's format and parse it, applying the import preferences.
+///
+/// Returns the file extension, the detected format and the parsed book.
+fn parse_source(
+    source_path: &Path,
+    db: &Database,
+) -> CommandResult<(&'static str, SourceFormat, ParsedBook)> {
+    let (extension, source_format) = detect_format(source_path)?;
+
+    let mut parsed_book = parser::parse_file(source_path).context("Failed to parse file")?;
+    if !parsed_book.has_readable_content() {
+        return Err(no_readable_text());
+    }
+
+    if let Some(min_chars) = merge_min_chars(db)? {
+        parsed_book.merge_short_segments(min_chars);
+    }
+
+    Ok((extension, source_format, parsed_book))
+}
+
+/// Error for a file that parses without any text to read.
+fn no_readable_text() -> CommandError {
+    CommandError::InvalidInput("No readable text found in file".to_string())
+}
+
+/// Check that a plain text file has at least one segment, without reading
+/// the whole file.
+fn check_txt_readable(source_path: &Path) -> CommandResult<()> {
+    let mut segments = txt::stream_txt(source_path).context("Failed to parse file")?;
+    match segments.next() {
+        Some(segment) if segment.context("Failed to parse file")?.is_readable() => Ok(()),
+        _ => Err(no_readable_text()),
+    }
+}
+
+/// Number of leading segments of a plain text file sampled for language
+/// detection.
+const LANGUAGE_SAMPLE_SEGMENTS: usize = 50;
+
+/// Detect the language of a plain text file from its first segments,
+/// without reading the whole file.
+fn detect_txt_language(source_path: &Path) -> CommandResult<Option<String>> {
+    let segments = txt::stream_txt(source_path).context("Failed to parse file")?;
+    let mut samples = Vec::new();
+    for segment in segments.take(LANGUAGE_SAMPLE_SEGMENTS) {
+        samples.push(segment.context("Failed to parse file")?.content);
+    }
+    Ok(parser::detect_language(samples.iter().map(String::as_str)))
+}
+
+/// How an imported book's source file is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Copy the file into the sources directory.
+    Copy,
+    /// Leave the file where it is and store its absolute path. The library
+    /// never moves or deletes a referenced file.
+    Reference,
+}
+
+impl ImportMode {
+    /// Parse the `importMode` preference, defaulting to copying.
+    fn from_setting(value: &str) -> Self {
+        match value {
+            "reference" => Self::Reference,
+            _ => Self::Copy,
+        }
+    }
+}
+
+/// Marks a source file as being imported, or a book's source as being
+/// replaced, until dropped.
+///
+/// Stops a double-click or quick retry from running two imports of the same
+/// file at once, which would add the book twice, or two replacements of one
+/// book's source racing each other.
+struct ImportGuard<'a> {
+    imports: &'a Mutex<HashSet<String>>,
+    key: String,
+}
+
+impl<'a> ImportGuard<'a> {
+    /// Claim `path` for importing, failing if an import of it is already running.
+    fn acquire(imports: &'a Mutex<HashSet<String>>, path: &Path) -> CommandResult<Self> {
+        let key = std::fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .to_string();
+        Self::claim(imports, key, "Import already in progress for this file")
+    }
+
+    /// Claim `book_id`'s source for replacing, failing if a replacement of
+    /// it is already running.
+    fn acquire_book(imports: &'a Mutex<HashSet<String>>, book_id: &BookId) -> CommandResult<Self> {
+        let key = format!("book:{}", book_id);
+        Self::claim(imports, key, "The source of this book is already being replaced")
+    }
+
+    fn claim(
+        imports: &'a Mutex<HashSet<String>>,
+        key: String,
+        conflict: &str,
+    ) -> CommandResult<Self> {
+        if !imports.lock()?.insert(key.clone()) {
+            return Err(CommandError::Conflict(conflict.to_string()));
+        }
+
+        Ok(Self { imports, key })
+    }
+}
+
+impl Drop for ImportGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut imports) = self.imports.lock() {
+            imports.remove(&self.key);
+        }
+    }
+}
+
+/// Attempts at copying a source file that another program has open.
+const COPY_ATTEMPTS: u32 = 3;
+
+/// Pause between attempts at copying a source file.
+const COPY_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Whether an I/O error means another program holds the file open.
+///
+/// Windows reports a file locked by another program as a sharing or lock
+/// violation, and sometimes as access denied. Elsewhere access denied means
+/// just that, and only a busy file is worth waiting for.
+fn is_file_in_use(e: &std::io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    const EBUSY: i32 = 16;
+
+    if cfg!(windows) {
+        e.kind() == std::io::ErrorKind::PermissionDenied
+            || matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION))
+    } else {
+        e.raw_os_error() == Some(EBUSY)
+    }
+}
+
+/// Run `copy`, retrying while the file is in use by another program.
+///
+/// Other failures aren't retried. If the file is still in use after the
+/// last attempt, the error says so rather than showing the OS error.
+async fn copy_with_retry<T>(
+    source: &Path,
+    delay: Duration,
+    mut copy: impl FnMut() -> std::io::Result<T>,
+) -> CommandResult<T> {
+    let mut attempt = 1;
+    loop {
+        match copy() {
+            Ok(value) => return Ok(value),
+            Err(e) if is_file_in_use(&e) && attempt < COPY_ATTEMPTS => {
+                log::debug!("{} is in use (attempt {}): {}", source.display(), attempt, e);
+                attempt += 1;
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) if is_file_in_use(&e) => {
+                let name = source.file_name().unwrap_or(source.as_os_str());
+                return Err(CommandError::Conflict(format!(
+                    "{} is in use by another program. Close it and try again.",
+                    name.to_string_lossy()
+                )));
+            }
+            Err(e) => return Err(e).context("Failed to copy source file"),
+        }
+    }
+}
+
+/// Copy a source file, retrying while another program has it open.
+async fn copy_source(source: &Path, dest: &Path) -> CommandResult<u64> {
+    copy_with_retry(source, COPY_RETRY_DELAY, || std::fs::copy(source, dest)).await
+}
+
+/// Stored path of a source that already lives in the library's sources
+/// directory, where copying it would copy it onto itself.
+fn managed_source(paths: &AppPaths, source: &Path) -> Option<String> {
+    let source = std::fs::canonicalize(source).ok()?;
+    let sources_dir = std::fs::canonicalize(&paths.sources).ok()?;
+    let relative = source.strip_prefix(&sources_dir).ok()?;
+    Some(paths.to_stored(&paths.sources.join(relative)))
+}
+
+/// Space left free on the data volume beyond what an operation needs.
+const DISK_SPACE_RESERVE: u64 = 100 * 1024 * 1024;
+
+const MB: u64 = 1024 * 1024;
+
+/// Refuse an operation needing `needed` bytes unless the volume has room for
+/// it with a tenth to spare, plus `DISK_SPACE_RESERVE`.
+fn check_disk_space(needed: u64, available: u64) -> CommandResult<()> {
+    let required = needed
+        .saturating_add(needed / 10)
+        .saturating_add(DISK_SPACE_RESERVE);
+    if available >= required {
+        return Ok(());
+    }
+    Err(CommandError::Io(format!(
+        "Insufficient disk space: {} MB needed, {} MB available",
+        required.div_ceil(MB),
+        available / MB
+    )))
+}
+
+/// Check the volume holding `dir` has room to write `needed` bytes, so an
+/// operation fails up front rather than halfway with partial files. If the
+/// free space can't be read the operation goes ahead.
+pub(crate) fn ensure_disk_space(dir: &Path, needed: u64) -> CommandResult<()> {
+    match available_space(dir) {
+        Ok(available) => check_disk_space(needed, available),
+        Err(e) => {
+            log::warn!("Failed to read free space of {}: {}", dir.display(), e);
+            Ok(())
+        }
+    }
+}
+
+/// A newly imported book with anything worth telling the user about its parse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedBook {
+    #[serde(flatten)]
+    pub book: Book,
+    /// Content the parser skipped or couldn't decode cleanly; empty for a
+    /// clean parse.
+    pub warnings: Vec<ParseWarning>,
+}
+
 /// Import a book from a file path into the library.
 ///
-/// Parses the file (EPUB, Markdown, TXT, or PDF) and adds it to the library.
-/// Returns the newly created Book.
+/// Parses the file (EPUB, HTML, Markdown, TXT, or PDF) and adds it to the library.
+/// The file is copied into the data directory or referenced where it is,
+/// following `mode` or else the `importMode` preference.
+/// Returns the newly created Book along with any parse warnings.
 #[tauri::command]
-pub async fn import_book(path: String, state: State<'_, AppState>) -> Result<Book, String> {
+pub async fn import_book(
+    path: String,
+    mode: Option<ImportMode>,
+    state: State<'_, AppState>,
+) -> CommandResult<ImportedBook> {
     let source_path = Path::new(&path);
+    let _guard = ImportGuard::acquire(&state.active_imports, source_path)?;
 
-    // 1. Detect format from file extension
-    let extension = source_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or_else(|| "File has no extension".to_string())?;
-
-    let parser_format = ParserSourceFormat::from_extension(extension)
-        .ok_or_else(|| format!("Unsupported file format: {}", extension))?;
+    let mode = match mode {
+        Some(mode) => mode,
+        None => {
+            let preferences = super::settings::load_import_preferences(&state.db)?;
+            ImportMode::from_setting(&preferences.import_mode)
+        }
+    };
 
-    let source_format = parser_format_to_model_format(parser_format);
+    // The text stored in the database takes about the source's size, and a
+    // copy as much again
+    let source_size = std::fs::metadata(source_path)
+        .context("Failed to read source file")?
+        .len();
+    let copies = if mode == ImportMode::Copy { 2 } else { 1 };
+    ensure_disk_space(&state.paths().root, source_size.saturating_mul(copies))?;
 
-    // 2. Parse the file to extract segments
-    let parsed_book = parser::parse_file(source_path)
-        .map_err(|e| format!("Failed to parse file: {}", e))?;
+    // 1-2. Detect the format and parse the file to extract segments. Plain
+    // text can be arbitrarily large, so it is read while inserting instead.
+    // Either way a file with no text is rejected before anything is stored
+    let (extension, source_format) = detect_format(source_path)?;
+    let parsed_book = match source_format {
+        SourceFormat::Txt => {
+            check_txt_readable(source_path)?;
+            None
+        }
+        _ => Some(parse_source(source_path, &state.db)?.2),
+    };
+    let (title, author, language, metadata) = match &parsed_book {
+        Some(parsed_book) => (
+            parsed_book.title.clone(),
+            parsed_book.author.clone(),
+            parsed_book.language.clone(),
+            parsed_book.metadata.clone(),
+        ),
+        None => (
+            txt::txt_title(source_path),
+            None,
+            detect_txt_language(source_path)?,
+            BookMetadata::default(),
+        ),
+    };
+    let merge_min_chars = merge_min_chars(&state.db)?;
+    let skip_boilerplate = super::settings::load_import_preferences(&state.db)?.skip_boilerplate;
 
     // 3. Generate a new BookId (UUID)
     let book_id = BookId::new(Uuid::new_v4().to_string());
 
-    // 4. Copy source file to sources directory
-    let dest_path = state.paths.source_path(book_id.as_str(), extension);
-    std::fs::copy(source_path, &dest_path)
-        .map_err(|e| format!("Failed to copy source file: {}", e))?;
+    // 4. Copy source file to sources directory, or reference it in place. A
+    // file already in the sources directory is used where it is, and isn't
+    // removed if the insert fails.
+    let (stored_source, dest_path) = match mode {
+        ImportMode::Copy => match managed_source(&state.paths(), source_path) {
+            Some(stored) => (stored, None),
+            None => {
+                let dest_path = state.paths().source_path(book_id.as_str(), extension);
+                copy_source(source_path, &dest_path).await?;
+                (state.paths().to_stored(&dest_path), Some(dest_path))
+            }
+        },
+        ImportMode::Reference => {
+            let original =
+                std::fs::canonicalize(source_path).context("Failed to locate source file")?;
+            (original.to_string_lossy().to_string(), None)
+        }
+    };
+    let source_is_reference = mode == ImportMode::Reference;
 
     // 5. Get current timestamp
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| format!("System time error: {}", e))?
+        .map_err(|e| CommandError::Internal(format!("System time error: {}", e)))?
         .as_secs() as i64;
 
     // 6. Insert book into database
     let book = Book {
         id: book_id.clone(),
-        title: parsed_book.title,
-        author: parsed_book.author,
+        title,
+        author,
         source_format,
-        source_path: dest_path.to_string_lossy().to_string(),
+        source_path: stored_source,
         narration_status: NarrationStatus::None,
         narration_path: None,
         created_at: now,
         updated_at: now,
         last_opened_at: None,
+        duration: None,
+        language,
+        finished_at: None,
+        metadata,
+        narration_meta: None,
+        original_filename: source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string()),
     };
 
-    {
+    // Images are written before the database is locked
+    let image_paths = match &parsed_book {
+        Some(parsed_book) => save_images(&state.paths(), &book_id, &parsed_book.images),
+        None => Ok(HashMap::new()),
+    };
+
+    let mut txt_warnings = Vec::new();
+    let inserted = image_paths.and_then(|image_paths| {
         let conn = state.db.connection().lock().unwrap();
+        match &parsed_book {
+            Some(parsed_book) => insert_book(
+                &conn,
+                &book,
+                source_is_reference,
+                &parsed_book.segments,
+                &parsed_book.chapters,
+                &image_paths,
+            ),
+            None => txt::stream_txt(source_path)
+                .context("Failed to parse file")
+                .and_then(|mut segments| {
+                    let inserted = insert_streamed_book(
+                        &conn,
+                        &book,
+                        source_is_reference,
+                        &mut segments,
+                        merge_min_chars,
+                    );
+                    txt_warnings = segments.warnings();
+                    inserted
+                }),
+        }
+    });
 
-        // Insert the book
-        conn.execute(
-            "INSERT INTO books (id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    // Don't leave an orphaned source copy or images behind if the book
+    // wasn't added
+    if inserted.is_err() {
+        if let Some(dest_path) = &dest_path {
+            let _ = std::fs::remove_file(dest_path);
+        }
+        let _ = std::fs::remove_dir_all(state.paths().images_path(book_id.as_str()));
+    }
+    inserted?;
+
+    // Flagging is a convenience, so a failure leaves the book narrating
+    // every segment rather than failing the import
+    if skip_boilerplate {
+        let conn = state.db.connection().lock().unwrap();
+        match flag_boilerplate(&conn, &book.id) {
+            Ok(0) => {}
+            Ok(count) => {
+                log::info!("Left {} boilerplate segment(s) of {} out of narration", count, path)
+            }
+            Err(e) => log::warn!("Failed to flag boilerplate in {}: {}", path, e),
+        }
+    }
+
+    let warnings = match parsed_book {
+        Some(parsed_book) => parsed_book.warnings,
+        None => txt_warnings,
+    };
+    if !warnings.is_empty() {
+        log::info!("Imported {} with {} parse warning(s)", path, warnings.len());
+    }
+    Ok(ImportedBook {
+        book: resolve_book_paths(book, &state.paths()),
+        warnings,
+    })
+}
+
+/// A file in a directory import that couldn't be imported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Outcome of importing every book in a directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryImport {
+    pub imported: Vec<ImportedBook>,
+    pub failed: Vec<ImportFailure>,
+}
+
+/// Files directly inside `dir` in a format that can be imported, by name.
+/// Files without an extension, like READMEs, are left out even if they are
+/// text.
+fn importable_files(dir: &Path) -> CommandResult<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .context("Failed to read directory")?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file() && path.extension().is_some() && detect_format(path).is_ok()
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Import each file in turn, recording failures instead of stopping at them.
+async fn import_each<T, F, Fut>(
+    files: Vec<PathBuf>,
+    mut import: F,
+) -> (Vec<T>, Vec<ImportFailure>)
+where
+    F: FnMut(PathBuf) -> Fut,
+    Fut: Future<Output = CommandResult<T>>,
+{
+    let mut imported = Vec::new();
+    let mut failed = Vec::new();
+    for file in files {
+        let path = file.to_string_lossy().to_string();
+        match import(file).await {
+            Ok(value) => imported.push(value),
+            Err(e) => {
+                log::warn!("Failed to import {}: {}", path, e);
+                failed.push(ImportFailure { path, error: e.to_string() });
+            }
+        }
+    }
+    (imported, failed)
+}
+
+/// Import every supported file directly inside a directory.
+///
+/// Each file is imported as by `import_book`. A file that fails, e.g. because
+/// another program has it open, is reported in `failed` and the rest are
+/// still imported.
+#[tauri::command]
+pub async fn import_directory(
+    path: String,
+    mode: Option<ImportMode>,
+    state: State<'_, AppState>,
+) -> CommandResult<DirectoryImport> {
+    let files = importable_files(Path::new(&path))?;
+    let (imported, failed) = import_each(files, |file| {
+        import_book(file.to_string_lossy().to_string(), mode, state.clone())
+    })
+    .await;
+
+    Ok(DirectoryImport { imported, failed })
+}
+
+/// Largest file `import_from_url` will download, in bytes.
+const MAX_DOWNLOAD_BYTES: u64 = 200 * 1024 * 1024;
+
+/// How long `import_from_url` waits for a download to finish.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Redirects followed before a download is abandoned.
+const MAX_REDIRECTS: usize = 10;
+
+/// Progress update while `import_from_url` downloads a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub url: String,
+    /// Bytes received so far.
+    pub received: u64,
+    /// Size of the file, if the server sent it.
+    pub total: Option<u64>,
+}
+
+/// File extension for a download, from the extension at the end of its URL
+/// path and its `Content-Type`.
+///
+/// A supported URL extension wins, so Markdown served as text/plain stays
+/// Markdown. Otherwise the content type decides; generic binary types and a
+/// missing header need the URL extension. Any other content type, such as
+/// an image or PDF, is refused.
+fn download_extension(
+    url_extension: Option<&str>,
+    content_type: Option<&str>,
+) -> CommandResult<String> {
+    let url_extension = url_extension
+        .filter(|ext| ParserSourceFormat::from_extension(ext).is_some())
+        .map(str::to_lowercase);
+
+    let essence = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let typed_extension = match essence.as_deref() {
+        None | Some("application/octet-stream" | "binary/octet-stream" | "application/zip") => None,
+        Some("application/epub+zip") => Some("epub"),
+        Some("text/plain") => Some("txt"),
+        Some("text/html" | "application/xhtml+xml") => Some("html"),
+        Some("text/markdown" | "text/x-markdown") => Some("md"),
+        Some(other) => {
+            return Err(CommandError::InvalidInput(format!(
+                "Unsupported content type: {}",
+                other
+            )))
+        }
+    };
+
+    url_extension
+        .or(typed_extension.map(str::to_string))
+        .ok_or_else(|| {
+            CommandError::InvalidInput(
+                "Cannot tell the format of the download; the URL should end in .epub, .html, .md or .txt"
+                    .to_string(),
+            )
+        })
+}
+
+/// Import a book from a URL.
+///
+/// Downloads the file to a temporary directory, following redirects, and
+/// imports a copy of it as `import_book` does. Downloads larger than
+/// `MAX_DOWNLOAD_BYTES` or of an unsupported content type are refused
+/// before anything is added to the library. Emits `url_import_progress`
+/// while downloading. Returns the newly created Book.
+#[tauri::command]
+pub async fn import_from_url(
+    url: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<ImportedBook> {
+    let parsed_url = reqwest::Url::parse(url.trim())
+        .map_err(|e| CommandError::InvalidInput(format!("Invalid URL: {}", e)))?;
+    if !matches!(parsed_url.scheme(), "http" | "https") {
+        return Err(CommandError::InvalidInput(
+            "Only http and https URLs can be imported".to_string(),
+        ));
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let mut response = client
+        .get(parsed_url)
+        .send()
+        .await
+        .context("Download failed")?;
+    if !response.status().is_success() {
+        return Err(CommandError::ServiceUnavailable(format!(
+            "Server returned: {}",
+            response.status()
+        )));
+    }
+
+    let total = response.content_length();
+    if total.is_some_and(|total| total > MAX_DOWNLOAD_BYTES) {
+        return Err(CommandError::InvalidInput(format!(
+            "File is larger than the {} MB download limit",
+            MAX_DOWNLOAD_BYTES / (1024 * 1024)
+        )));
+    }
+
+    // Name the file after the last URL segment, after redirects, so plain
+    // text gets a sensible title
+    let final_url = response.url().clone();
+    let file_name = final_url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download")
+        .to_string();
+    let file_path = Path::new(&file_name);
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let extension = download_extension(
+        file_path.extension().and_then(|ext| ext.to_str()),
+        content_type,
+    )?;
+    let stem = file_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.replace(|c: char| c.is_control() || "\\/:*?\"<>|".contains(c), "_"))
+        .unwrap_or_else(|| "download".to_string());
+
+    let temp_dir = std::env::temp_dir().join(format!("actual-reader-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&temp_dir).context("Failed to create download directory")?;
+    let temp_path = temp_dir.join(format!("{}.{}", stem, extension));
+
+    let downloaded = async {
+        let mut file = std::fs::File::create(&temp_path).context("Failed to create download file")?;
+        let progress_events = ProgressThrottle::new(app, "url_import_progress");
+        let mut received: u64 = 0;
+        while let Some(chunk) = response.chunk().await.context("Download failed")? {
+            received += chunk.len() as u64;
+            if received > MAX_DOWNLOAD_BYTES {
+                return Err(CommandError::InvalidInput(format!(
+                    "File is larger than the {} MB download limit",
+                    MAX_DOWNLOAD_BYTES / (1024 * 1024)
+                )));
+            }
+            file.write_all(&chunk).context("Failed to save download")?;
+            progress_events.emit(
+                DownloadProgress {
+                    url: url.clone(),
+                    received,
+                    total,
+                },
+                received == chunk.len() as u64,
+            );
+        }
+        file.sync_all().context("Failed to save download")?;
+        progress_events.emit(
+            DownloadProgress {
+                url: url.clone(),
+                received,
+                total: Some(received),
+            },
+            true,
+        );
+        log::info!("Downloaded {} ({} bytes) for import", final_url, received);
+        Ok(())
+    }
+    .await;
+
+    let imported = match downloaded {
+        Ok(()) => {
+            import_book(
+                temp_path.to_string_lossy().to_string(),
+                Some(ImportMode::Copy),
+                state,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    imported
+}
+
+/// Replace a book's source file with a new one, keeping the book's id.
+///
+/// The new file is copied into the sources directory and parsed, and its
+/// segments replace the old ones. Narration is kept when the new file has
+/// the same number of segments, marked stale if the text changed; otherwise
+/// it's reset and must be regenerated. The title and author are kept; the source format follows
+/// the new file's extension. Returns the updated Book.
+///
+/// The database changes are made in one transaction, and the new file is
+/// moved into place under a name the old one doesn't use, so a failure at
+/// any step leaves the book on its old source. The old file is removed only
+/// once the change is committed.
+#[tauri::command]
+pub async fn replace_source(
+    book_id: BookId,
+    new_path: String,
+    state: State<'_, AppState>,
+) -> CommandResult<Book> {
+    let new_source = Path::new(&new_path);
+    let _guard = ImportGuard::acquire_book(&state.active_imports, &book_id)?;
+
+    if state.active_generations.read().await.contains_key(book_id.as_str()) {
+        return Err(CommandError::Conflict(
+            "Cannot replace the source while narration is being generated".to_string(),
+        ));
+    }
+
+    let (old_source, old_is_reference, old_shared, old_images) = {
+        let conn = state.db.connection().lock().unwrap();
+        let (source, is_reference): (String, bool) = conn
+            .query_row(
+                "SELECT source_path, source_is_reference FROM books WHERE id = ?",
+                [book_id.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .context("Book not found")?;
+        let images: Vec<String> = conn
+            .prepare("SELECT source_path FROM segment_images WHERE book_id = ?")
+            .context("Failed to prepare images query")?
+            .query_map([book_id.as_str()], |row| row.get(0))
+            .context("Failed to query images")?
+            .collect::<Result<_, _>>()
+            .context("Failed to read image row")?;
+        (source, is_reference, source_shared(&conn, &book_id)?, images)
+    };
+
+    let (extension, source_format, parsed_book) = parse_source(new_source, &state.db)?;
+
+    // The old file has to survive until the change is committed, so the new
+    // one needs its own name
+    let old_path = state.paths().resolve(&old_source);
+    let mut dest_path = state.paths().source_path(book_id.as_str(), extension);
+    if old_path == dest_path {
+        let name = format!("{}-{}", book_id, Uuid::new_v4());
+        dest_path = state.paths().source_path(&name, extension);
+    }
+
+    // Copy beside the final name first so the old file survives a failure.
+    // New images are named by the new segment ids, so they can't overwrite
+    // the old ones
+    let staged_path = dest_path.with_extension(format!("{}.new", extension));
+    copy_source(new_source, &staged_path).await?;
+    let image_paths = match save_images(&state.paths(), &book_id, &parsed_book.images) {
+        Ok(image_paths) => image_paths,
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged_path);
+            return Err(e);
+        }
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| CommandError::Internal(format!("System time error: {}", e)))?
+        .as_secs() as i64;
+
+    let replaced = (|| -> CommandResult<Option<Vec<Marker>>> {
+        let conn = state.db.connection().lock().unwrap();
+        let tx = conn
+            .unchecked_transaction()
+            .context("Failed to start transaction")?;
+        let markers = replace_segments(&tx, &book_id, &parsed_book, &image_paths)?;
+        tx.execute(
+            "UPDATE books SET source_format = ?, source_path = ?, source_is_reference = 0, updated_at = ?
+             WHERE id = ?",
             rusqlite::params![
-                book.id.as_str(),
-                &book.title,
-                &book.author,
-                book.source_format.as_str(),
-                &book.source_path,
-                book.narration_status.as_str(),
-                &book.narration_path,
-                book.created_at,
-                book.updated_at,
-                book.last_opened_at,
+                source_format.as_str(),
+                state.paths().to_stored(&dest_path),
+                now,
+                book_id.as_str(),
             ],
         )
-        .map_err(|e| format!("Failed to insert book: {}", e))?;
+        .context("Failed to update book")?;
+        // The books left on the old file take it over, so the last of them
+        // to go deletes it
+        if old_shared && !old_is_reference {
+            tx.execute(
+                "UPDATE books SET source_is_reference = 0 WHERE source_path = ? AND id != ?",
+                rusqlite::params![old_source, book_id.as_str()],
+            )
+            .context("Failed to update books sharing the source")?;
+        }
 
-        // Insert all segments
-        let mut stmt = conn
+        std::fs::rename(&staged_path, &dest_path)
+            .context("Failed to move source file into place")?;
+        if let Err(e) = tx.commit() {
+            let _ = std::fs::remove_file(&dest_path);
+            return Err(CommandError::Database(format!("Failed to commit transaction: {}", e)));
+        }
+        Ok(markers)
+    })();
+
+    let markers = match replaced {
+        Ok(markers) => markers,
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged_path);
+            remove_images(&state.paths(), image_paths.values());
+            return Err(e);
+        }
+    };
+
+    // The database points at the new source now. A referenced original
+    // belongs to the user, and one still used by another book belongs to
+    // that book, so either is left where it is
+    if !old_is_reference && !old_shared && old_path.is_file() {
+        if let Err(e) = std::fs::remove_file(&old_path) {
+            log::warn!("Failed to delete old source {}: {}", old_path.display(), e);
+        }
+    }
+
+    // The old segments' images went with them
+    remove_images(&state.paths(), &old_images);
+
+    // Keep the narration's markers file in step with the new segment ids
+    if let Some(markers) = markers {
+        let markers_json = serde_json::to_string_pretty(&markers)
+            .context("Failed to serialize markers")?;
+        std::fs::write(state.paths().markers_path(book_id.as_str()), markers_json)
+            .context("Failed to save markers")?;
+    }
+
+    log::info!("Replaced source of {} with: {}", book_id, new_path);
+
+    let conn = state.db.connection().lock().unwrap();
+    let book = conn
+        .query_row(
+            &format!("SELECT {} FROM books WHERE id = ?", BOOK_COLUMNS),
+            [book_id.as_str()],
+            read_book_row,
+        )
+        .context("Book not found")?;
+
+    Ok(resolve_book_paths(book, &state.paths()))
+}
+
+/// Insert a new book with its segments and chapters, and the images saved
+/// for it by [`save_images`].
+///
+/// Runs in a single transaction, so a failed insert leaves no trace of the
+/// book in the database.
+fn insert_book(
+    conn: &rusqlite::Connection,
+    book: &Book,
+    source_is_reference: bool,
+    segments: &[parser::Segment],
+    chapters: &[parser::Chapter],
+    image_paths: &HashMap<String, String>,
+) -> CommandResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    insert_book_row(&tx, book, source_is_reference)?;
+    insert_segments(&tx, &book.id, segments, chapters, image_paths)?;
+    super::bundle::update_content_hash(&tx, book.id.as_str())?;
+
+    tx.commit().context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+/// Insert a new book whose segments are parsed as they are inserted, so the
+/// whole text never has to be held in memory.
+///
+/// Short segments are merged on the way in if `merge_min_chars` is set. Like
+/// [`insert_book`], runs in a single transaction.
+fn insert_streamed_book(
+    conn: &rusqlite::Connection,
+    book: &Book,
+    source_is_reference: bool,
+    segments: impl Iterator<Item = Result<parser::Segment, parser::ParseError>>,
+    merge_min_chars: Option<usize>,
+) -> CommandResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    insert_book_row(&tx, book, source_is_reference)?;
+
+    {
+        let mut stmt = tx
             .prepare(
-                "INSERT INTO segments (id, book_id, idx, content, html) VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO segments (id, book_id, idx, content, html, segment_type) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             )
-            .map_err(|e| format!("Failed to prepare segment insert: {}", e))?;
-
-        for segment in &parsed_book.segments {
+            .context("Failed to prepare segment insert")?;
+        let mut insert = |segment: parser::Segment| {
             stmt.execute(rusqlite::params![
                 &segment.id,
                 book.id.as_str(),
                 segment.index,
                 &segment.content,
                 &segment.html,
+                segment.segment_type.as_str(),
             ])
-            .map_err(|e| format!("Failed to insert segment: {}", e))?;
+            .context("Failed to insert segment")
+        };
+
+        let mut merger = merge_min_chars.map(SegmentMerger::new);
+        for segment in segments {
+            let segment = segment.context("Failed to parse file")?;
+            let ready = match merger.as_mut() {
+                Some(merger) => merger.push(segment),
+                None => Some(segment),
+            };
+            if let Some(segment) = ready {
+                insert(segment)?;
+            }
+        }
+        if let Some(segment) = merger.and_then(SegmentMerger::finish) {
+            insert(segment)?;
         }
     }
+    super::bundle::update_content_hash(&tx, book.id.as_str())?;
+
+    tx.commit().context("Failed to commit transaction")?;
 
-    Ok(book)
+    Ok(())
 }
 
-/// Get all books in the library.
+/// Insert a book's row into the books table, in the active profile.
 ///
-/// Returns a list of all books, sorted by most recently opened (then by creation date).
-#[tauri::command]
-pub async fn get_library(state: State<'_, AppState>) -> Result<Vec<Book>, String> {
-    let conn = state.db.connection().lock().unwrap();
+/// `source_is_reference` marks a source file imported by reference, which
+/// the library must leave alone.
+fn insert_book_row(
+    conn: &rusqlite::Connection,
+    book: &Book,
+    source_is_reference: bool,
+) -> CommandResult<()> {
+    conn.execute(
+        "INSERT INTO books (id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at, duration, source_is_reference, language, publisher, published_date, identifier, isbn, original_filename, profile_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        rusqlite::params![
+            book.id.as_str(),
+            &book.title,
+            &book.author,
+            book.source_format.as_str(),
+            &book.source_path,
+            book.narration_status.as_str(),
+            &book.narration_path,
+            book.created_at,
+            book.updated_at,
+            book.last_opened_at,
+            book.duration,
+            source_is_reference,
+            &book.language,
+            &book.metadata.publisher,
+            &book.metadata.published_date,
+            &book.metadata.identifier,
+            &book.metadata.isbn,
+            &book.original_filename,
+            super::settings::active_profile_id(conn)?,
+        ],
+    )
+    .context("Failed to insert book")?;
+
+    Ok(())
+}
 
+/// Insert a book's segments and chapters.
+///
+/// Segments with a path in `image_paths`, by segment id, get a
+/// `segment_images` row with their text as the alt text.
+pub(crate) fn insert_segments(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    segments: &[parser::Segment],
+    chapters: &[parser::Chapter],
+    image_paths: &HashMap<String, String>,
+) -> CommandResult<()> {
+    // Insert all segments
     let mut stmt = conn
         .prepare(
-            "SELECT id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at
-             FROM books
-             ORDER BY last_opened_at DESC NULLS LAST, created_at DESC",
+            "INSERT INTO segments (id, book_id, idx, content, html, segment_type) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare segment insert")?;
 
-    let books = stmt
-        .query_map([], |row| {
-            let source_format_str: String = row.get(3)?;
-            let narration_status_str: String = row.get(5)?;
-
-            Ok(Book {
-                id: BookId::new(row.get::<_, String>(0)?),
-                title: row.get(1)?,
-                author: row.get(2)?,
-                source_format: SourceFormat::from_str(&source_format_str)
-                    .unwrap_or(SourceFormat::Txt),
-                source_path: row.get(4)?,
-                narration_status: NarrationStatus::from_str(&narration_status_str)
-                    .unwrap_or(NarrationStatus::None),
-                narration_path: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-                last_opened_at: row.get(9)?,
-            })
-        })
-        .map_err(|e| format!("Failed to query books: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read book row: {}", e))?;
+    for segment in segments {
+        stmt.execute(rusqlite::params![
+            &segment.id,
+            book_id.as_str(),
+            segment.index,
+            &segment.content,
+            &segment.html,
+            segment.segment_type.as_str(),
+        ])
+        .context("Failed to insert segment")?;
+    }
 
-    Ok(books)
-}
+    let mut stmt = conn
+        .prepare(
+            "INSERT INTO segment_images (segment_id, book_id, source_path, alt_text) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .context("Failed to prepare image insert")?;
 
-/// Delete a book from the library.
-///
-/// Removes the book, its segments, markers, progress, and associated files
-/// (source file and narration if present).
-#[tauri::command]
-pub async fn delete_book(id: BookId, state: State<'_, AppState>) -> Result<(), String> {
-    // 1. Get the book info before deletion (for file paths)
-    let (source_path, narration_path): (String, Option<String>) = {
-        let conn = state.db.connection().lock().unwrap();
+    for segment in segments {
+        let Some(source_path) = image_paths.get(&segment.id) else {
+            continue;
+        };
+        let alt_text = Some(segment.content.as_str()).filter(|alt| !alt.is_empty());
+        stmt.execute(rusqlite::params![&segment.id, book_id.as_str(), source_path, alt_text])
+            .context("Failed to insert image")?;
+    }
 
-        let mut stmt = conn
-            .prepare("SELECT source_path, narration_path FROM books WHERE id = ?1")
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    // Insert the table of contents
+    let mut stmt = conn
+        .prepare(
+            "INSERT INTO chapters (book_id, sort_order, title, start_segment_index, level) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .context("Failed to prepare chapter insert")?;
 
-        stmt.query_row([id.as_str()], |row| Ok((row.get(0)?, row.get(1)?)))
-            .map_err(|e| format!("Book not found: {}", e))?
-    };
+    for (order, chapter) in chapters.iter().enumerate() {
+        stmt.execute(rusqlite::params![
+            book_id.as_str(),
+            order as u32,
+            &chapter.title,
+            chapter.start_index,
+            chapter.level,
+        ])
+        .context("Failed to insert chapter")?;
+    }
 
-    // 2. Delete from database (CASCADE handles segments, markers, progress)
-    {
-        let conn = state.db.connection().lock().unwrap();
+    Ok(())
+}
 
-        conn.execute("DELETE FROM books WHERE id = ?1", [id.as_str()])
-            .map_err(|e| format!("Failed to delete book: {}", e))?;
+/// Write a parsed book's images into the book's images directory, named by
+/// segment id.
+///
+/// Returns the stored path of each image by segment id. If a write fails,
+/// the images already written are removed.
+pub(crate) fn save_images(
+    paths: &AppPaths,
+    book_id: &BookId,
+    images: &[parser::ParsedImage],
+) -> CommandResult<HashMap<String, String>> {
+    let mut image_paths = HashMap::new();
+    if images.is_empty() {
+        return Ok(image_paths);
     }
 
-    // 3. Delete source file from sources directory
-    let source_file = Path::new(&source_path);
-    if source_file.exists() {
-        std::fs::remove_file(source_file)
-            .map_err(|e| format!("Failed to delete source file: {}", e))?;
+    let images_dir = paths.images_path(book_id.as_str());
+    std::fs::create_dir_all(&images_dir).context("Failed to create images directory")?;
+    for image in images {
+        let dest_path = images_dir.join(format!("{}.{}", image.segment_id, image.extension));
+        if let Err(e) = std::fs::write(&dest_path, &image.data) {
+            let _ = std::fs::remove_file(&dest_path);
+            remove_images(paths, image_paths.values());
+            return Err(CommandError::Io(format!("Failed to write image: {}", e)));
+        }
+        image_paths.insert(image.segment_id.clone(), paths.to_stored(&dest_path));
     }
 
-    // 4. Delete narration directory if exists
-    if let Some(narration_dir) = narration_path {
-        let narration_path = Path::new(&narration_dir);
-        if narration_path.exists() && narration_path.is_dir() {
-            std::fs::remove_dir_all(narration_path)
-                .map_err(|e| format!("Failed to delete narration directory: {}", e))?;
-        }
-    } else {
-        // Also check the default narration path location
-        let default_narration_path = state.paths.narration_path(id.as_str());
-        if default_narration_path.exists() {
-            std::fs::remove_dir_all(&default_narration_path)
-                .map_err(|e| format!("Failed to delete narration directory: {}", e))?;
+    Ok(image_paths)
+}
+
+/// Delete image files by their stored paths, ignoring any already gone.
+fn remove_images<'a>(paths: &AppPaths, stored: impl IntoIterator<Item = &'a String>) {
+    for stored in stored {
+        let path = paths.resolve(stored);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to delete image {}: {}", path.display(), e);
+            }
         }
     }
+}
 
-    Ok(())
+/// Leave likely boilerplate out of a book's narration: page numbers,
+/// copyright notices, and short lines repeated throughout the book, such as
+/// running headers. Returns the number of segments flagged.
+///
+/// Only short segments are read back, so a large streamed book isn't
+/// loaded into memory.
+fn flag_boilerplate(conn: &rusqlite::Connection, book_id: &BookId) -> CommandResult<usize> {
+    let repeated: Vec<String> = conn
+        .prepare(
+            "SELECT content FROM segments
+             WHERE book_id = ? AND segment_type != 'image' AND length(content) <= ?
+             GROUP BY content HAVING COUNT(*) >= ?",
+        )
+        .context("Failed to prepare query")?
+        .query_map(
+            rusqlite::params![
+                book_id.as_str(),
+                parser::RUNNING_HEADER_MAX_CHARS,
+                parser::RUNNING_HEADER_MIN_REPEATS,
+            ],
+            |row| row.get(0),
+        )
+        .context("Failed to query segments")?
+        .collect::<Result<_, _>>()
+        .context("Failed to read segment")?;
+
+    let mut flagged = 0;
+    for content in repeated.iter().filter(|c| parser::could_be_running_header(c)) {
+        flagged += conn
+            .execute(
+                "UPDATE segments SET narrate = 0
+                 WHERE book_id = ? AND content = ? AND segment_type != 'image'",
+                rusqlite::params![book_id.as_str(), content],
+            )
+            .context("Failed to update segment")?;
+    }
+
+    let mut boilerplate = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, content FROM segments
+                 WHERE book_id = ? AND segment_type != 'image' AND narrate = 1
+                     AND length(content) <= ?",
+            )
+            .context("Failed to prepare query")?;
+        let mut rows = stmt
+            .query(rusqlite::params![book_id.as_str(), parser::BOILERPLATE_MAX_CHARS])
+            .context("Failed to query segments")?;
+        while let Some(row) = rows.next().context("Failed to read segment")? {
+            let content: String = row.get(1).context("Failed to read segment")?;
+            if parser::looks_like_boilerplate(&content) {
+                boilerplate.push(row.get::<_, String>(0).context("Failed to read segment")?);
+            }
+        }
+    }
+    for id in &boilerplate {
+        conn.execute("UPDATE segments SET narrate = 0 WHERE id = ?", rusqlite::params![id])
+            .context("Failed to update segment")?;
+    }
+
+    Ok(flagged + boilerplate.len())
+}
+
+/// Swap a book's segments for newly parsed ones.
+///
+/// Markers move to the new segment with the same index, and ready narration
+/// is marked stale if any segment's text changed. If the number of segments
+/// changed, the narration no longer lines up with the text, so the markers
+/// are dropped and the narration status is reset. Returns the remapped
+/// markers, or None if the narration was reset or there was none.
+///
+/// Runs in the caller's transaction, which also updates the book's row.
+/// `image_paths` holds the new images saved by [`save_images`].
+fn replace_segments(
+    tx: &rusqlite::Connection,
+    book_id: &BookId,
+    parsed_book: &ParsedBook,
+    image_paths: &HashMap<String, String>,
+) -> CommandResult<Option<Vec<Marker>>> {
+    let old_contents: Vec<String> = tx
+        .prepare("SELECT content FROM segments WHERE book_id = ? ORDER BY idx")
+        .context("Failed to prepare segments query")?
+        .query_map([book_id.as_str()], |row| row.get(0))
+        .context("Failed to query segments")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read segment row")?;
+
+    let old_markers: Vec<(u32, f64, f64)> = tx
+        .prepare(
+            "SELECT s.idx, m.start_time, m.end_time
+             FROM markers m JOIN segments s ON s.id = m.segment_id
+             WHERE m.book_id = ? ORDER BY s.idx",
+        )
+        .context("Failed to prepare markers query")?
+        .query_map([book_id.as_str()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .context("Failed to query markers")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read marker row")?;
+
+    // Deleting segments also deletes their markers and image data
+    tx.execute("DELETE FROM segments WHERE book_id = ?", [book_id.as_str()])
+        .context("Failed to delete segments")?;
+    tx.execute("DELETE FROM chapters WHERE book_id = ?", [book_id.as_str()])
+        .context("Failed to delete chapters")?;
+
+    insert_segments(tx, book_id, &parsed_book.segments, &parsed_book.chapters, image_paths)?;
+    super::bundle::update_content_hash(tx, book_id.as_str())?;
+
+    let markers = if old_markers.is_empty() {
+        None
+    } else if parsed_book.segments.len() == old_contents.len() {
+        let markers: Vec<Marker> = old_markers
+            .into_iter()
+            .filter_map(|(index, start, end)| {
+                let segment = parsed_book.segments.get(index as usize)?;
+                Some(Marker {
+                    segment_id: SegmentId::new(segment.id.clone()),
+                    start,
+                    end,
+                })
+            })
+            .collect();
+
+        let mut stmt = tx
+            .prepare("INSERT INTO markers (id, book_id, segment_id, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)")
+            .context("Failed to prepare marker insert")?;
+
+        for marker in &markers {
+            stmt.execute(rusqlite::params![
+                format!("marker_{}", Uuid::new_v4()),
+                book_id.as_str(),
+                marker.segment_id.as_str(),
+                marker.start,
+                marker.end,
+            ])
+            .context("Failed to insert marker")?;
+        }
+
+        let text_changed = parsed_book
+            .segments
+            .iter()
+            .zip(&old_contents)
+            .any(|(segment, old)| segment.content != *old);
+        if text_changed {
+            super::reader::mark_narration_stale(&tx, book_id)?;
+        }
+
+        Some(markers)
+    } else {
+        tx.execute(
+            "UPDATE books SET narration_status = ?, duration = NULL WHERE id = ?",
+            rusqlite::params![NarrationStatus::None.as_str(), book_id.as_str()],
+        )
+        .context("Failed to reset narration status")?;
+
+        None
+    };
+
+    Ok(markers)
+}
+
+/// Map a `books` row selected with [`BOOK_COLUMNS`] to a Book.
+fn read_book_row(row: &rusqlite::Row) -> rusqlite::Result<Book> {
+    let source_format_str: String = row.get(3)?;
+    let narration_status_str: String = row.get(5)?;
+
+    Ok(Book {
+        id: BookId::new(row.get::<_, String>(0)?),
+        title: row.get(1)?,
+        author: row.get(2)?,
+        source_format: SourceFormat::from_str(&source_format_str)
+            .unwrap_or(SourceFormat::Txt),
+        source_path: row.get(4)?,
+        narration_status: NarrationStatus::from_str(&narration_status_str)
+            .unwrap_or(NarrationStatus::None),
+        narration_path: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        last_opened_at: row.get(9)?,
+        duration: row.get(10)?,
+        language: row.get(11)?,
+        finished_at: row.get(12)?,
+        metadata: BookMetadata {
+            publisher: row.get(13)?,
+            published_date: row.get(14)?,
+            identifier: row.get(15)?,
+            isbn: row.get(16)?,
+        },
+        narration_meta: NarrationMeta::from_json(row.get::<_, Option<String>>(17)?.as_deref()),
+        original_filename: row.get(18)?,
+    })
+}
+
+/// Read a book by ID.
+pub(crate) fn query_book(conn: &rusqlite::Connection, id: &BookId) -> CommandResult<Book> {
+    conn.query_row(
+        &format!("SELECT {} FROM books WHERE id = ?", BOOK_COLUMNS),
+        rusqlite::params![id.as_str()],
+        read_book_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound(format!("Book not found: {}", id))
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Where to write an export of a book: `output_path` itself, or if that is
+/// a directory, a file in it named by [`Book::export_file_name`].
+pub(crate) fn resolve_export_path(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    output_path: &str,
+    extension: &str,
+) -> CommandResult<PathBuf> {
+    let output = PathBuf::from(output_path);
+    if !output.is_dir() {
+        return Ok(output);
+    }
+    let book = query_book(conn, book_id)?;
+    Ok(output.join(book.export_file_name(extension)))
+}
+
+/// Columns read by [`read_book_row`], in order.
+const BOOK_COLUMNS: &str = "id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at, duration, language, finished_at, publisher, published_date, identifier, isbn, narration_meta, original_filename";
+
+/// What a file would import as, without importing it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedBookPreview {
+    pub title: String,
+    pub author: Option<String>,
+    pub source_format: SourceFormat,
+    /// Total number of segments the file parses into.
+    pub segment_count: u32,
+    /// Content of the first segments, up to the requested limit.
+    pub segments: Vec<String>,
+    /// Declared or detected language of the text.
+    pub language: Option<String>,
+    /// Problems that wouldn't stop the import.
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Parse a file and preview the result without adding it to the library.
+///
+/// Nothing is written to the database and the file is not copied, so the
+/// import UI can show what was detected before the user commits.
+#[tauri::command]
+pub async fn preview_parse(
+    path: String,
+    limit: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<ParsedBookPreview> {
+    let (_, source_format, parsed_book) = parse_source(Path::new(&path), &state.db)?;
+
+    Ok(ParsedBookPreview {
+        title: parsed_book.title,
+        author: parsed_book.author,
+        source_format,
+        segment_count: parsed_book.segments.len() as u32,
+        language: parsed_book.language,
+        warnings: parsed_book.warnings,
+        segments: parsed_book
+            .segments
+            .into_iter()
+            .take(limit as usize)
+            .map(|segment| segment.content)
+            .collect(),
+    })
+}
+
+/// Resolve a book's stored file paths to absolute paths under the current root.
+pub(crate) fn resolve_book_paths(mut book: Book, paths: &AppPaths) -> Book {
+    if !book.source_path.is_empty() {
+        book.source_path = paths.resolve(&book.source_path).to_string_lossy().to_string();
+    }
+    book.narration_path = book
+        .narration_path
+        .map(|p| paths.resolve(&p).to_string_lossy().to_string());
+    book
+}
+
+/// Order in which `get_library` returns books.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LibrarySort {
+    /// Most recently opened first, then never-opened books newest first.
+    #[default]
+    RecentlyOpened,
+    /// Most recently imported first.
+    RecentlyAdded,
+    /// Title, A to Z.
+    TitleAsc,
+    /// Author, A to Z, with books without an author last.
+    AuthorAsc,
+    /// Shortest narration first, with unnarrated books last.
+    Duration,
+}
+
+impl LibrarySort {
+    /// ORDER BY clause for this sort.
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::RecentlyOpened => "last_opened_at DESC NULLS LAST, created_at DESC",
+            Self::RecentlyAdded => "created_at DESC",
+            Self::TitleAsc => "title COLLATE NOCASE ASC, created_at DESC",
+            Self::AuthorAsc => {
+                "author COLLATE NOCASE ASC NULLS LAST, title COLLATE NOCASE ASC, created_at DESC"
+            }
+            Self::Duration => "duration ASC NULLS LAST, title COLLATE NOCASE ASC",
+        }
+    }
+}
+
+/// Load the books in `profile` in the given order, at most `limit` of them
+/// if set.
+fn query_library(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    profile: &str,
+    sort: LibrarySort,
+    limit: Option<u32>,
+) -> CommandResult<Vec<Book>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM books WHERE profile_id = ? ORDER BY {} LIMIT ?",
+            BOOK_COLUMNS,
+            sort.order_by()
+        ))
+        .context("Failed to prepare query")?;
+
+    // A negative LIMIT means no limit
+    let limit = limit.map_or(-1, i64::from);
+    let books = stmt
+        .query_map(rusqlite::params![profile, limit], read_book_row)
+        .context("Failed to query books")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read book row")?;
+
+    Ok(books
+        .into_iter()
+        .map(|book| resolve_book_paths(book, paths))
+        .collect())
+}
+
+/// Get the books in the library.
+///
+/// Sorted by most recently opened (then by creation date) unless `sort` says
+/// otherwise, so shelves like "Recently Added" come straight from the
+/// database. `limit` caps how many books are returned. Only books in
+/// `profile` are listed, the active profile if unset.
+#[tauri::command]
+pub async fn get_library(
+    sort: Option<LibrarySort>,
+    limit: Option<u32>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<Book>> {
+    let conn = state.db.connection().lock().unwrap();
+    let profile = match profile {
+        Some(profile) => profile,
+        None => super::settings::active_profile_id(&conn)?,
+    };
+    query_library(&conn, &state.paths(), &profile, sort.unwrap_or_default(), limit)
+}
+
+/// Metadata changes made by `update_book`.
+///
+/// A field left as None is unchanged. An empty author or language clears it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookUpdate {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// BCP 47 tag or ISO 639 code, e.g. "en-GB" or "fra".
+    pub language: Option<String>,
+}
+
+/// True if `language` looks like a language tag: letters, digits and
+/// hyphens, at most 35 characters.
+fn is_valid_language_tag(language: &str) -> bool {
+    language.len() <= 35
+        && language.starts_with(|c: char| c.is_ascii_alphabetic())
+        && language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Apply `update` to a book at time `now` and return the updated row.
+fn apply_book_update(
+    conn: &rusqlite::Connection,
+    id: &BookId,
+    update: &BookUpdate,
+    now: i64,
+) -> CommandResult<Book> {
+    let title = update.title.as_deref().map(str::trim);
+    if title == Some("") {
+        return Err(CommandError::InvalidInput("Title cannot be empty".to_string()));
+    }
+    let author = update.author.as_deref().map(str::trim);
+    let language = update.language.as_deref().map(str::trim);
+    if let Some(language) = language.filter(|l| !l.is_empty()) {
+        if !is_valid_language_tag(language) {
+            return Err(CommandError::InvalidInput(format!(
+                "Invalid language tag: {}",
+                language
+            )));
+        }
+    }
+
+    // NULLIF turns an empty string into NULL, clearing the column
+    let updated = conn
+        .execute(
+            "UPDATE books SET
+                title = COALESCE(?1, title),
+                author = CASE WHEN ?2 IS NULL THEN author ELSE NULLIF(?2, '') END,
+                language = CASE WHEN ?3 IS NULL THEN language ELSE NULLIF(?3, '') END,
+                updated_at = ?4
+             WHERE id = ?5",
+            rusqlite::params![title, author, language, now, id.as_str()],
+        )
+        .context("Failed to update book")?;
+    if updated == 0 {
+        return Err(CommandError::NotFound(format!("Book not found: {}", id)));
+    }
+
+    conn.query_row(
+        &format!("SELECT {} FROM books WHERE id = ?", BOOK_COLUMNS),
+        rusqlite::params![id.as_str()],
+        read_book_row,
+    )
+    .context("Failed to read book")
+}
+
+/// Edit a book's title, author or language.
+///
+/// Lets the user correct metadata read from the file, or a misdetected
+/// language, which is passed to the TTS engine on the next generation.
+#[tauri::command]
+pub async fn update_book(
+    id: BookId,
+    update: BookUpdate,
+    state: State<'_, AppState>,
+) -> CommandResult<Book> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| CommandError::Internal(format!("System time error: {}", e)))?
+        .as_secs() as i64;
+
+    let conn = state.db.connection().lock().unwrap();
+    let book = apply_book_update(&conn, &id, &update, now)?;
+    state.broadcast(super::sync::SyncUpdate::LibraryChanged);
+    Ok(resolve_book_paths(book, &state.paths()))
+}
+
+/// Correct a book's recorded source format.
+///
+/// Sets `format` if given, or else detects it again from the book's source
+/// file by its contents, for books whose format was recorded wrongly, e.g.
+/// one imported from a bundle. Only the recorded format changes; the text
+/// is not parsed again. Returns the updated Book.
+#[tauri::command]
+pub async fn set_source_format(
+    book_id: BookId,
+    format: Option<SourceFormat>,
+    state: State<'_, AppState>,
+) -> CommandResult<Book> {
+    let conn = state.db.connection().lock().unwrap();
+    let book = query_book(&conn, &book_id)?;
+
+    let format = match format {
+        Some(format) => format,
+        None => {
+            let source = state.paths().resolve(&book.source_path);
+            if !source.is_file() {
+                return Err(CommandError::InvalidInput(
+                    "The book's source file is missing; choose its format instead".to_string(),
+                ));
+            }
+            detect_format(&source)?.1
+        }
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| CommandError::Internal(format!("System time error: {}", e)))?
+        .as_secs() as i64;
+    conn.execute(
+        "UPDATE books SET source_format = ?, updated_at = ? WHERE id = ?",
+        rusqlite::params![format.as_str(), now, book_id.as_str()],
+    )
+    .context("Failed to update book")?;
+
+    let book = query_book(&conn, &book_id)?;
+    Ok(resolve_book_paths(book, &state.paths()))
+}
+
+/// Which parts of a book `search_library` should match against.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchScope {
+    /// Title and author only.
+    Metadata,
+    /// Segment text only.
+    Content,
+    /// Both metadata and segment text.
+    Both,
+}
+
+/// A book matching a library search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub book: Book,
+    /// True if the title or author matched.
+    pub metadata_match: bool,
+    /// Number of segments whose text matched.
+    pub content_matches: u32,
+    /// Index of the first matching segment, for content matches.
+    pub segment_index: Option<u32>,
+    /// Text surrounding the first content match.
+    pub snippet: Option<String>,
+}
+
+/// Number of characters of context kept on each side of a snippet match.
+const SNIPPET_RADIUS: usize = 60;
+
+/// Escape `%`, `_` and `\` so a query can be used as a literal LIKE pattern.
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 2);
+    pattern.push('%');
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Build a short excerpt of `content` around the first case-insensitive
+/// occurrence of `query`.
+fn make_snippet(content: &str, query: &str, radius: usize) -> String {
+    let haystack = content.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    let Some(start) = haystack.find(&needle) else {
+        return content.chars().take(radius * 2).collect();
+    };
+    let end = start + needle.len();
+
+    let before: Vec<(usize, char)> = content[..start].char_indices().collect();
+    let from = before
+        .len()
+        .checked_sub(radius)
+        .map(|i| before[i].0)
+        .unwrap_or(0);
+    let to = content[end..]
+        .char_indices()
+        .nth(radius)
+        .map(|(i, _)| end + i)
+        .unwrap_or(content.len());
+
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(content[from..to].trim());
+    if to < content.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Relevance of a metadata match; higher is better, zero means no match.
+fn metadata_score(book: &Book, query: &str) -> u32 {
+    let query = query.to_lowercase();
+    let title = book.title.to_lowercase();
+
+    let title_score = if title == query {
+        100
+    } else if title.starts_with(&query) {
+        75
+    } else if title.contains(&query) {
+        50
+    } else {
+        0
+    };
+
+    let author_score = match &book.author {
+        Some(author) if author.to_lowercase().contains(&query) => 25,
+        _ => 0,
+    };
+
+    title_score + author_score
+}
+
+/// Search the active profile's books by title, author, and/or segment text.
+///
+/// Results are ranked with title matches first, then author matches, then
+/// books whose text matches most often; ties keep library order. Content
+/// matches include the first matching segment index and a snippet so the
+/// reader can jump straight there. An empty query returns the whole library.
+#[tauri::command]
+pub async fn search_library(
+    query: String,
+    scope: SearchScope,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<SearchResult>> {
+    let conn = state.db.connection().lock().unwrap();
+    let profile = super::settings::active_profile_id(&conn)?;
+    let books = query_library(&conn, &state.paths(), &profile, LibrarySort::default(), None)?;
+    let query = query.trim();
+
+    if query.is_empty() {
+        return Ok(books
+            .into_iter()
+            .map(|book| SearchResult {
+                book,
+                metadata_match: false,
+                content_matches: 0,
+                segment_index: None,
+                snippet: None,
+            })
+            .collect());
+    }
+
+    let pattern = like_pattern(query);
+
+    // Title/author matches
+    let metadata_ids: HashSet<String> = if scope != SearchScope::Content {
+        let mut stmt = conn
+            .prepare("SELECT id FROM books WHERE title LIKE ?1 ESCAPE '\\' OR author LIKE ?1 ESCAPE '\\'")
+            .context("Failed to prepare query")?;
+
+        let result = stmt
+            .query_map(rusqlite::params![pattern], |row| row.get::<_, String>(0))
+            .context("Failed to search books")?
+            .collect::<Result<HashSet<_>, _>>()
+            .context("Failed to read book row")?;
+        result
+    } else {
+        HashSet::new()
+    };
+
+    // First matching segment and match count per book
+    let content_hits: HashMap<String, (u32, String, u32)> = if scope != SearchScope::Metadata {
+        let mut stmt = conn
+            .prepare(
+                "SELECT book_id, MIN(idx), content, COUNT(*) FROM segments
+                 WHERE content LIKE ?1 ESCAPE '\\'
+                 GROUP BY book_id",
+            )
+            .context("Failed to prepare query")?;
+
+        let result = stmt
+            .query_map(rusqlite::params![pattern], |row| {
+                Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+            })
+            .context("Failed to search segments")?
+            .collect::<Result<HashMap<_, _>, _>>()
+            .context("Failed to read segment row")?;
+        result
+    } else {
+        HashMap::new()
+    };
+
+    let mut ranked: Vec<(u32, SearchResult)> = books
+        .into_iter()
+        .filter_map(|book| {
+            let metadata_match = metadata_ids.contains(book.id.as_str());
+            let content_hit = content_hits.get(book.id.as_str());
+            if !metadata_match && content_hit.is_none() {
+                return None;
+            }
+
+            let mut score = if metadata_match {
+                metadata_score(&book, query)
+            } else {
+                0
+            };
+            let (content_matches, segment_index, snippet) = match content_hit {
+                Some((index, content, count)) => {
+                    score += (*count).min(20);
+                    (
+                        *count,
+                        Some(*index),
+                        Some(make_snippet(content, query, SNIPPET_RADIUS)),
+                    )
+                }
+                None => (0, None, None),
+            };
+
+            Some((
+                score,
+                SearchResult {
+                    book,
+                    metadata_match,
+                    content_matches,
+                    segment_index,
+                    snippet,
+                },
+            ))
+        })
+        .collect();
+
+    // Stable sort keeps library order among equally ranked books
+    ranked.sort_by(|a, b| b.0.cmp(&a.0));
+
+    Ok(ranked.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Whether another book uses the same source file as `book_id`.
+///
+/// Parts of a split book share the original's source, and a managed file
+/// imported again is shared with the book it came from.
+fn source_shared(conn: &rusqlite::Connection, book_id: &BookId) -> CommandResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM books other JOIN books book ON other.source_path = book.source_path
+                       WHERE book.id = ?1 AND other.id != ?1)",
+        [book_id.as_str()],
+        |row| row.get(0),
+    )
+    .context("Failed to check source file")
+}
+
+/// Delete a book's row, returning whether its source file is still used by
+/// another book.
+///
+/// If the book owned a managed source that other books share, as the
+/// original of a split book does, they take the file over as in
+/// [`replace_source`], so the last of them to go deletes it.
+fn delete_book_row(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    source_path: &str,
+    source_is_reference: bool,
+) -> CommandResult<bool> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    tx.execute("DELETE FROM books WHERE id = ?1", [book_id.as_str()])
+        .context("Failed to delete book")?;
+
+    // A re-imported managed file is shared with the book it came from
+    let shared: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE source_path = ?1)",
+            [source_path],
+            |row| row.get(0),
+        )
+        .context("Failed to check source file")?;
+    if shared && !source_is_reference {
+        tx.execute(
+            "UPDATE books SET source_is_reference = 0 WHERE source_path = ?1",
+            [source_path],
+        )
+        .context("Failed to update books sharing the source")?;
+    }
+
+    tx.commit().context("Failed to commit transaction")?;
+    Ok(shared)
+}
+
+/// Delete a book from the library.
+///
+/// Removes the book, its segments, markers, progress, and associated files
+/// (source file and narration if present). A source file imported by
+/// reference, or still used by another book, is kept.
+#[tauri::command]
+pub async fn delete_book(id: BookId, state: State<'_, AppState>) -> CommandResult<()> {
+    // 1. Get the book info before deletion (for file paths)
+    let (source_path, narration_path, source_is_reference): (String, Option<String>, bool) = {
+        let conn = state.db.connection().lock().unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT source_path, narration_path, source_is_reference FROM books WHERE id = ?1")
+            .context("Failed to prepare query")?;
+
+        stmt.query_row([id.as_str()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .context("Book not found")?
+    };
+
+    // 2. Delete from database (CASCADE handles segments, markers, progress)
+    let source_shared = {
+        let conn = state.db.connection().lock().unwrap();
+        delete_book_row(&conn, &id, &source_path, source_is_reference)?
+    };
+    state.broadcast(super::sync::SyncUpdate::LibraryChanged);
+
+    // 3. Delete source file from sources directory
+    let source_file = state.paths().resolve(&source_path);
+    if !source_is_reference && !source_shared && source_file.is_file() {
+        std::fs::remove_file(&source_file).context("Failed to delete source file")?;
+    }
+
+    // 4. Delete narration directory if exists
+    if let Some(narration_dir) = narration_path {
+        let narration_path = state.paths().resolve(&narration_dir);
+        if narration_path.exists() && narration_path.is_dir() {
+            std::fs::remove_dir_all(&narration_path)
+                .context("Failed to delete narration directory")?;
+        }
+    } else {
+        // Also check the default narration path location
+        let default_narration_path = state.paths().narration_path(id.as_str());
+        if default_narration_path.exists() {
+            std::fs::remove_dir_all(&default_narration_path)
+                .context("Failed to delete narration directory")?;
+        }
+    }
+
+    // 5. Delete images of image segments
+    let images_path = state.paths().images_path(id.as_str());
+    if images_path.exists() {
+        std::fs::remove_dir_all(&images_path).context("Failed to delete images directory")?;
+    }
+
+    Ok(())
+}
+
+/// Whether a book's files can be found on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookHealth {
+    pub book_id: BookId,
+    pub title: String,
+    /// Resolved source file path (empty for books imported without one).
+    pub source_path: String,
+    /// True if the source file exists, or the book has no source file.
+    pub source_exists: bool,
+    /// True if the source file was imported by reference and lives outside
+    /// the data directory; if it's missing, it was moved or deleted there.
+    pub source_is_reference: bool,
+    /// Resolved narration path, if narration was generated or imported.
+    pub narration_path: Option<String>,
+    /// True if the narration exists, or the book has no narration.
+    pub narration_exists: bool,
+    /// True if a path is still stored as absolute rather than relative to the
+    /// data directory. Referenced source files are always absolute and don't count.
+    pub absolute_paths: bool,
+}
+
+impl BookHealth {
+    /// True if every file the book refers to was found.
+    fn is_healthy(&self) -> bool {
+        self.source_exists && self.narration_exists
+    }
+}
+
+/// Result of [`repair_paths`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathRepairReport {
+    /// Number of books whose stored paths were rewritten.
+    pub repaired: u32,
+    /// Books that still have missing files after the repair.
+    pub unresolved: Vec<BookHealth>,
+}
+
+/// Check every book's stored paths against the current data directory.
+fn check_library(conn: &rusqlite::Connection, paths: &AppPaths) -> CommandResult<Vec<BookHealth>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, source_path, narration_path, source_is_reference
+             FROM books ORDER BY title",
+        )
+        .context("Failed to prepare query")?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })
+        .context("Failed to query books")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read book row")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, title, source_path, narration_path, source_is_reference)| {
+            let is_absolute = |p: &str| Path::new(p).is_absolute();
+            let absolute_paths = (!source_is_reference && is_absolute(&source_path))
+                || narration_path.as_deref().is_some_and(is_absolute);
+
+            let (source_path, source_exists) = if source_path.is_empty() {
+                (source_path, true)
+            } else {
+                let resolved = paths.resolve(&source_path);
+                (resolved.to_string_lossy().to_string(), resolved.is_file())
+            };
+            let narration = narration_path.map(|p| paths.resolve(&p));
+            let narration_exists = narration.as_ref().map_or(true, |p| p.exists());
+
+            BookHealth {
+                book_id: BookId::new(id),
+                title,
+                source_path,
+                source_exists,
+                source_is_reference,
+                narration_path: narration.map(|p| p.to_string_lossy().to_string()),
+                narration_exists,
+                absolute_paths,
+            }
+        })
+        .collect())
+}
+
+/// Report, per book, whether its source file and narration exist on disk.
+#[tauri::command]
+pub async fn verify_library(state: State<'_, AppState>) -> CommandResult<Vec<BookHealth>> {
+    let conn = state.db.connection().lock().unwrap();
+    check_library(&conn, &state.paths())
+}
+
+/// Disk space used by one book.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookStorage {
+    pub book_id: BookId,
+    pub title: String,
+    /// Bytes in the book's narration directory, segment cache included.
+    pub narration_bytes: u64,
+    /// Bytes of the source copy; 0 for a source referenced in place.
+    pub source_bytes: u64,
+}
+
+/// Disk space used by the data directory, by category.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsage {
+    pub sources_bytes: u64,
+    pub narration_bytes: u64,
+    pub bundles_bytes: u64,
+    pub voices_bytes: u64,
+    /// The database file with its write-ahead log.
+    pub database_bytes: u64,
+    pub total_bytes: u64,
+    /// Free space on the data directory's volume, None if it couldn't be read.
+    pub available_bytes: Option<u64>,
+    /// Per-book breakdown, largest narration first.
+    pub books: Vec<BookStorage>,
+}
+
+/// Measure the data directory and each book's share of it.
+///
+/// The books are listed under the database lock, which is released before
+/// the directories are walked.
+fn measure_storage(db: &Database, paths: &AppPaths) -> CommandResult<StorageUsage> {
+    let rows = {
+        let conn = db.connection().lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, title, source_path, source_is_reference FROM books")
+            .context("Failed to prepare query")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, bool>(3)?,
+                ))
+            })
+            .context("Failed to query books")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read book row")?;
+        rows
+    };
+
+    let mut books = rows
+        .into_iter()
+        .map(|(id, title, source_path, source_is_reference)| {
+            let source_bytes = if source_is_reference || source_path.is_empty() {
+                0
+            } else {
+                dir_size(&paths.resolve(&source_path))?
+            };
+            Ok(BookStorage {
+                narration_bytes: dir_size(&paths.narration_path(&id))?,
+                source_bytes,
+                book_id: BookId::new(id),
+                title,
+            })
+        })
+        .collect::<std::io::Result<Vec<_>>>()
+        .context("Failed to measure book files")?;
+    books.sort_by(|a, b| b.narration_bytes.cmp(&a.narration_bytes));
+
+    let measure = |path: &Path| {
+        dir_size(path).with_context(|| format!("Failed to measure {}", path.display()))
+    };
+    let database_bytes = ["", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| {
+            let mut file = paths.database.clone().into_os_string();
+            file.push(suffix);
+            measure(Path::new(&file))
+        })
+        .sum::<CommandResult<u64>>()?;
+
+    let sources_bytes = measure(&paths.sources)?;
+    let narration_bytes = measure(&paths.narration)?;
+    let bundles_bytes = measure(&paths.bundles)?;
+    let voices_bytes = measure(&paths.voices)?;
+
+    Ok(StorageUsage {
+        sources_bytes,
+        narration_bytes,
+        bundles_bytes,
+        voices_bytes,
+        database_bytes,
+        total_bytes: sources_bytes + narration_bytes + bundles_bytes + voices_bytes + database_bytes,
+        available_bytes: available_space(&paths.root).ok(),
+        books,
+    })
+}
+
+/// Report how much disk space the library uses.
+///
+/// Totals are given for sources, narration, bundles, voice samples and the
+/// database, with a per-book breakdown so users can see what to delete.
+#[tauri::command]
+pub async fn get_storage_usage(state: State<'_, AppState>) -> CommandResult<StorageUsage> {
+    measure_storage(&state.db, &state.paths())
+}
+
+/// Rewrite stored absolute book paths relative to the current data directory.
+///
+/// Fixes books whose paths point at the data directory's old location after
+/// it was moved. Returns how many books were updated and which still have
+/// missing files.
+#[tauri::command]
+pub async fn repair_paths(state: State<'_, AppState>) -> CommandResult<PathRepairReport> {
+    let repaired =
+        relativize_book_paths(&state.db, &state.paths()).context("Failed to repair book paths")?;
+
+    let conn = state.db.connection().lock().unwrap();
+    let unresolved = check_library(&conn, &state.paths())?
+        .into_iter()
+        .filter(|health| !health.is_healthy())
+        .collect();
+
+    log::info!("Repaired file paths of {} book(s)", repaired);
+
+    Ok(PathRepairReport {
+        repaired: repaired as u32,
+        unresolved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DEFAULT_PROFILE_ID;
+
+    #[test]
+    fn test_like_pattern_escapes_wildcards() {
+        assert_eq!(like_pattern("whale"), "%whale%");
+        assert_eq!(like_pattern("100%"), "%100\\%%");
+        assert_eq!(like_pattern("snake_case"), "%snake\\_case%");
+    }
+
+    #[test]
+    fn test_make_snippet() {
+        let content = "Call me Ishmael. Some years ago, never mind how long precisely.";
+
+        assert_eq!(make_snippet(content, "ishmael", 100), content);
+        assert_eq!(make_snippet(content, "years", 5), "…Some years ago,…");
+        assert_eq!(make_snippet(content, "Call", 3), "Call me…");
+        assert_eq!(make_snippet("no match here", "whale", 3), "no mat");
+    }
+
+    #[test]
+    fn test_metadata_score_ranks_title_over_author() {
+        let mut book = Book {
+            id: BookId::new("b1"),
+            title: "Moby Dick".to_string(),
+            author: Some("Herman Melville".to_string()),
+            source_format: SourceFormat::Epub,
+            source_path: String::new(),
+            narration_status: NarrationStatus::None,
+            narration_path: None,
+            created_at: 0,
+            updated_at: 0,
+            last_opened_at: None,
+            duration: None,
+            language: None,
+            finished_at: None,
+            metadata: BookMetadata::default(),
+            narration_meta: None,
+            original_filename: None,
+        };
+
+        assert_eq!(metadata_score(&book, "moby dick"), 100);
+        assert_eq!(metadata_score(&book, "moby"), 75);
+        assert_eq!(metadata_score(&book, "dick"), 50);
+        assert_eq!(metadata_score(&book, "melville"), 25);
+        assert_eq!(metadata_score(&book, "austen"), 0);
+
+        book.author = None;
+        assert_eq!(metadata_score(&book, "melville"), 0);
+    }
+
+    #[test]
+    fn test_import_guard_rejects_concurrent_import() {
+        let imports = Mutex::new(HashSet::new());
+        let path = Path::new("/books/moby-dick.epub");
+
+        let guard = ImportGuard::acquire(&imports, path).unwrap();
+        assert!(matches!(
+            ImportGuard::acquire(&imports, path),
+            Err(CommandError::Conflict(_))
+        ));
+        assert!(ImportGuard::acquire(&imports, Path::new("/books/other.epub")).is_ok());
+
+        drop(guard);
+        assert!(ImportGuard::acquire(&imports, path).is_ok());
+
+        // Replacing a book's source is claimed by book, whatever the file
+        let book_id = BookId::new("book");
+        let replacing = ImportGuard::acquire_book(&imports, &book_id).unwrap();
+        assert!(matches!(
+            ImportGuard::acquire_book(&imports, &book_id),
+            Err(CommandError::Conflict(_))
+        ));
+        assert!(ImportGuard::acquire_book(&imports, &BookId::new("other")).is_ok());
+        drop(replacing);
+        assert!(ImportGuard::acquire_book(&imports, &book_id).is_ok());
+    }
+
+    /// The error the OS gives for a file another program has open.
+    fn in_use() -> std::io::Error {
+        std::io::Error::from_raw_os_error(if cfg!(windows) { 32 } else { 16 })
+    }
+
+    #[test]
+    fn test_is_file_in_use() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(is_file_in_use(&in_use()));
+        assert_eq!(is_file_in_use(&denied), cfg!(windows));
+        assert!(!is_file_in_use(&std::io::Error::from(std::io::ErrorKind::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_copy_with_retry() {
+        let source = Path::new("/books/Moby Dick.epub");
+
+        // Succeeds once the other program lets go
+        let mut attempts = 0;
+        let copied = copy_with_retry(source, Duration::ZERO, || {
+            attempts += 1;
+            if attempts < COPY_ATTEMPTS { Err(in_use()) } else { Ok(42) }
+        })
+        .await;
+        assert_eq!(copied.unwrap(), 42);
+
+        // Still in use after the last attempt
+        let mut attempts = 0;
+        let err = copy_with_retry(source, Duration::ZERO, || -> std::io::Result<u64> {
+            attempts += 1;
+            Err(in_use())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(attempts, COPY_ATTEMPTS);
+        assert!(matches!(err, CommandError::Conflict(_)));
+        assert!(err.to_string().contains("Moby Dick.epub is in use by another program"));
+
+        // Other errors aren't retried
+        let mut attempts = 0;
+        let err = copy_with_retry(source, Duration::ZERO, || -> std::io::Result<u64> {
+            attempts += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(attempts, 1);
+        assert!(!matches!(err, CommandError::Conflict(_)));
+    }
+
+    #[test]
+    fn test_import_each_continues_past_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.md", "notes.xyz"] {
+            std::fs::write(dir.path().join(name), "Some text.").unwrap();
+        }
+        let files = importable_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 3);
+
+        // Copying b.txt fails as if another program held it open
+        let copy_dir = dir.path().join("copies");
+        std::fs::create_dir(&copy_dir).unwrap();
+        let import = |file: PathBuf| {
+            let dest = copy_dir.join(file.file_name().unwrap());
+            async move {
+                copy_with_retry(&file, Duration::ZERO, || {
+                    if file.ends_with("b.txt") {
+                        Err(in_use())
+                    } else {
+                        std::fs::copy(&file, &dest)
+                    }
+                })
+                .await?;
+                Ok::<_, CommandError>(file.file_name().unwrap().to_string_lossy().to_string())
+            }
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let (imported, failed) = runtime.block_on(import_each(files, import));
+
+        assert_eq!(imported, ["a.txt", "c.md"]);
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].path.ends_with("b.txt"));
+        assert!(failed[0].error.contains("in use by another program"));
+        assert!(copy_dir.join("c.md").exists());
+    }
+
+    #[test]
+    fn test_download_extension() {
+        let ext = |url_ext, content_type| download_extension(url_ext, content_type).ok();
+
+        assert_eq!(ext(Some("EPUB"), Some("application/epub+zip")).as_deref(), Some("epub"));
+        assert_eq!(ext(Some("md"), Some("text/plain; charset=utf-8")).as_deref(), Some("md"));
+        assert_eq!(ext(Some("utf-8"), Some("text/plain; charset=utf-8")).as_deref(), Some("txt"));
+        assert_eq!(ext(None, Some("text/html")).as_deref(), Some("html"));
+        assert_eq!(ext(Some("txt"), None).as_deref(), Some("txt"));
+        assert_eq!(ext(Some("epub"), Some("application/octet-stream")).as_deref(), Some("epub"));
+
+        assert_eq!(ext(None, Some("application/octet-stream")), None);
+        assert_eq!(ext(Some("pdf"), None), None);
+        assert_eq!(ext(Some("epub"), Some("image/jpeg")), None);
+    }
+
+    #[test]
+    fn test_rejects_files_without_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        let empty_md = write("empty.md", "");
+        let blank_txt = write("blank.txt", "  \n\n\t\r\n   \n");
+        let html_without_text = write("blank.html", "<html><body><p> </p></body></html>");
+        let text = write("text.txt", "\n\nCall me Ishmael.\n");
+
+        for path in [&empty_md, &blank_txt, &html_without_text] {
+            assert!(
+                matches!(parse_source(path, &db), Err(CommandError::InvalidInput(_))),
+                "{} should be rejected",
+                path.display()
+            );
+        }
+        assert!(matches!(check_txt_readable(&blank_txt), Err(CommandError::InvalidInput(_))));
+        assert!(matches!(
+            check_txt_readable(&write("empty.txt", "")),
+            Err(CommandError::InvalidInput(_))
+        ));
+
+        assert!(check_txt_readable(&text).is_ok());
+        assert!(parse_source(&text, &db).is_ok());
+    }
+
+    #[test]
+    fn test_insert_book_rolls_back_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        let book = Book {
+            id: BookId::new("book"),
+            title: "Title".to_string(),
+            author: None,
+            source_format: SourceFormat::Txt,
+            source_path: "sources/book.txt".to_string(),
+            narration_status: NarrationStatus::None,
+            narration_path: None,
+            created_at: 0,
+            updated_at: 0,
+            last_opened_at: None,
+            duration: None,
+            language: None,
+            finished_at: None,
+            metadata: BookMetadata::default(),
+            narration_meta: None,
+            original_filename: None,
+        };
+        // The second segment reuses index 0, violating UNIQUE(book_id, idx)
+        let segments = vec![
+            parser::Segment::new(0, "First".to_string(), None),
+            parser::Segment::new(0, "Second".to_string(), None),
+        ];
+
+        assert!(insert_book(&conn, &book, false, &segments, &[], &HashMap::new()).is_err());
+
+        let count = |table: &str| -> u32 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("books"), 0);
+        assert_eq!(count("segments"), 0);
+    }
+
+    #[test]
+    fn test_check_disk_space() {
+        assert!(check_disk_space(0, DISK_SPACE_RESERVE).is_ok());
+        assert!(check_disk_space(100 * MB, 110 * MB + DISK_SPACE_RESERVE).is_ok());
+
+        let err = check_disk_space(100 * MB, 110 * MB + DISK_SPACE_RESERVE - 1).unwrap_err();
+        assert!(matches!(err, CommandError::Io(_)));
+        assert!(err.message().starts_with("Insufficient disk space: 210 MB needed"));
+        assert!(check_disk_space(u64::MAX, u64::MAX - 1).is_err());
+    }
+
+    #[test]
+    fn test_managed_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().join("data"));
+        paths.ensure_dirs().unwrap();
+
+        let managed = paths.source_path("existing", "txt");
+        std::fs::write(&managed, "text").unwrap();
+        assert_eq!(managed_source(&paths, &managed), Some(paths.to_stored(&managed)));
+        // The same file reached through a detour still counts
+        let detour = paths.root.join("narration").join("..").join("sources").join("existing.txt");
+        assert_eq!(managed_source(&paths, &detour), Some(paths.to_stored(&managed)));
+
+        let outside = dir.path().join("book.txt");
+        std::fs::write(&outside, "text").unwrap();
+        assert_eq!(managed_source(&paths, &outside), None);
+        assert_eq!(managed_source(&paths, &paths.source_path("missing", "txt")), None);
+    }
+
+    #[test]
+    fn test_export_path_uses_original_filename() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        let mut book = Book {
+            id: BookId::new("book"),
+            title: "Title: Part 1".to_string(),
+            author: None,
+            source_format: SourceFormat::Epub,
+            source_path: "sources/book.epub".to_string(),
+            narration_status: NarrationStatus::None,
+            narration_path: None,
+            created_at: 0,
+            updated_at: 0,
+            last_opened_at: None,
+            duration: None,
+            language: None,
+            finished_at: None,
+            metadata: BookMetadata::default(),
+            narration_meta: None,
+            original_filename: Some("My Novel.epub".to_string()),
+        };
+        let segments = [parser::Segment::new(0, "Text".to_string(), None)];
+        insert_book(&conn, &book, false, &segments, &[], &HashMap::new()).unwrap();
+        let stored = query_book(&conn, &book.id).unwrap();
+        assert_eq!(stored.original_filename.as_deref(), Some("My Novel.epub"));
+
+        let out = dir.path().to_string_lossy().to_string();
+        let path = resolve_export_path(&conn, &book.id, &out, "m4b").unwrap();
+        assert_eq!(path, dir.path().join("My Novel.m4b"));
+        let file = dir.path().join("chosen.m4b").to_string_lossy().to_string();
+        let path = resolve_export_path(&conn, &book.id, &file, "m4b").unwrap();
+        assert_eq!(path.to_string_lossy(), file);
+
+        // Without an original filename the title is used, made safe for a file name
+        book.original_filename = None;
+        assert_eq!(book.export_file_name("actualbook"), "Title_ Part 1.actualbook");
+    }
+
+    #[test]
+    fn test_insert_streamed_book() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        let book = |id: &str| Book {
+            id: BookId::new(id),
+            title: "Title".to_string(),
+            author: None,
+            source_format: SourceFormat::Txt,
+            source_path: format!("sources/{}.txt", id),
+            narration_status: NarrationStatus::None,
+            narration_path: None,
+            created_at: 0,
+            updated_at: 0,
+            last_opened_at: None,
+            duration: None,
+            language: None,
+            finished_at: None,
+            metadata: BookMetadata::default(),
+            narration_meta: None,
+            original_filename: None,
+        };
+        let text = "\"Hi.\"\n\n\"Hello.\"\n\nA much longer paragraph of narration.\n\nShort.";
+        let contents = |id: &str| -> Vec<(u32, String)> {
+            conn.prepare("SELECT idx, content FROM segments WHERE book_id = ? ORDER BY idx")
+                .unwrap()
+                .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+
+        let segments = txt::TxtSegments::new(text.as_bytes());
+        insert_streamed_book(&conn, &book("plain"), false, segments, None).unwrap();
+        assert_eq!(contents("plain").len(), 4);
+
+        let segments = txt::TxtSegments::new(text.as_bytes());
+        insert_streamed_book(&conn, &book("merged"), false, segments, Some(20)).unwrap();
+        assert_eq!(
+            contents("merged"),
+            vec![
+                (0, "\"Hi.\"\n\"Hello.\"".to_string()),
+                (1, "A much longer paragraph of narration.".to_string()),
+                (2, "Short.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flag_boilerplate() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        // Five pages, each with a running header, some text and a number;
+        // the dialogue repeats as often but reads as a sentence
+        let mut contents = vec!["\u{a9} 2020 Jane Doe. All rights reserved.".to_string()];
+        for page in 1..=5 {
+            contents.push("THE TITLE".to_string());
+            contents.push(format!("Text of page {}.", page));
+            contents.push("\"No.\"".to_string());
+            contents.push(page.to_string());
+        }
+        for (index, content) in contents.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO segments (id, book_id, idx, content) VALUES (?, 'book', ?, ?)",
+                rusqlite::params![format!("s{}", index), index, content],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(flag_boilerplate(&conn, &BookId::new("book")).unwrap(), 11);
+        let narrated: Vec<String> = conn
+            .prepare("SELECT content FROM segments WHERE narrate = 1 ORDER BY idx")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(narrated.len(), 10);
+        assert!(narrated.iter().all(|c| c.starts_with("Text") || c == "\"No.\""));
+    }
+
+    #[test]
+    fn test_replace_segments_remaps_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        let book_id = BookId::new("book");
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 'ready', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content) VALUES ('a', 'book', 0, 'One'), ('b', 'book', 1, 'Two');
+             INSERT INTO markers (id, book_id, segment_id, start_time, end_time)
+             VALUES ('m1', 'book', 'a', 0.0, 1.0), ('m2', 'book', 'b', 1.0, 2.5);",
+        )
+        .unwrap();
+
+        let parsed = |texts: &[&str]| ParsedBook {
+            title: "Title".to_string(),
+            author: None,
+            segments: texts
+                .iter()
+                .enumerate()
+                .map(|(i, text)| parser::Segment::new(i as u32, text.to_string(), None))
+                .collect(),
+            chapters: Vec::new(),
+            language: None,
+            metadata: BookMetadata::default(),
+            warnings: Vec::new(),
+            images: Vec::new(),
+        };
+        let status = || -> String {
+            conn.query_row("SELECT narration_status FROM books WHERE id = 'book'", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        // Same segment count: markers follow the segments by index
+        let no_images = HashMap::new();
+        let same = parsed(&["One", "Two"]);
+        let markers = replace_segments(&conn, &book_id, &same, &no_images).unwrap().unwrap();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[1].segment_id.as_str(), same.segments[1].id);
+        assert_eq!(markers[1].end, 2.5);
+        assert_eq!(status(), "ready");
+
+        // Same count but edited text: the narration is kept but stale
+        let edited = parsed(&["One.", "Two"]);
+        let markers = replace_segments(&conn, &book_id, &edited, &no_images).unwrap().unwrap();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(status(), "stale");
+
+        // Different count: narration no longer lines up
+        let longer = parsed(&["One.", "Two.", "Three."]);
+        assert!(replace_segments(&conn, &book_id, &longer, &no_images).unwrap().is_none());
+        assert_eq!(status(), "none");
+        let marker_count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM markers", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(marker_count, 0);
+    }
+
+    #[test]
+    fn test_check_library_reports_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+        std::fs::write(paths.source_path("present", "txt"), "text").unwrap();
+
+        let conn = db.connection().lock().unwrap();
+        let referenced = dir.path().join("elsewhere.txt");
+        std::fs::write(&referenced, "text").unwrap();
+        let referenced = referenced.to_string_lossy().to_string();
+
+        for (id, source, narration, is_reference) in [
+            ("present", "sources/present.txt", None, false),
+            ("missing", "/old/root/sources/missing.txt", Some("narration/missing"), false),
+            ("referenced", referenced.as_str(), None, true),
+            ("moved_away", "/home/me/books/moved.txt", None, true),
+        ] {
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, narration_path, created_at, updated_at, source_is_reference)
+                 VALUES (?1, ?1, 'txt', ?2, 'none', ?3, 0, 0, ?4)",
+                rusqlite::params![id, source, narration, is_reference],
+            )
+            .unwrap();
+        }
+
+        let health = check_library(&conn, &paths).unwrap();
+        let present = health.iter().find(|h| h.book_id.as_str() == "present").unwrap();
+        let missing = health.iter().find(|h| h.book_id.as_str() == "missing").unwrap();
+
+        assert!(present.is_healthy());
+        assert!(!present.absolute_paths);
+        assert!(!missing.source_exists);
+        assert!(!missing.narration_exists);
+        assert!(missing.absolute_paths);
+
+        let referenced = health.iter().find(|h| h.book_id.as_str() == "referenced").unwrap();
+        assert!(referenced.is_healthy());
+        assert!(referenced.source_is_reference);
+        assert!(!referenced.absolute_paths);
+
+        let moved_away = health.iter().find(|h| h.book_id.as_str() == "moved_away").unwrap();
+        assert!(!moved_away.source_exists);
+        assert!(moved_away.source_is_reference);
+    }
+
+    #[test]
+    fn test_query_library_sorts() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, author, source_format, source_path, created_at, updated_at, last_opened_at, duration) VALUES
+                 ('a', 'beta', 'Zola', 'txt', 'a.txt', 1, 1, 30, NULL),
+                 ('b', 'Alpha', NULL, 'txt', 'b.txt', 2, 2, NULL, 60.0),
+                 ('c', 'Gamma', 'austen', 'txt', 'c.txt', 3, 3, 20, 10.0);
+             INSERT INTO profiles (id, name, created_at) VALUES ('work', 'Work', 1);
+             INSERT INTO books (id, title, source_format, source_path, created_at, updated_at, profile_id)
+                 VALUES ('d', 'Delta', 'txt', 'd.txt', 4, 4, 'work');",
+        )
+        .unwrap();
+
+        // Books in other profiles are left out
+        let ids = |sort: LibrarySort, limit: Option<u32>| -> Vec<String> {
+            query_library(&conn, &paths, DEFAULT_PROFILE_ID, sort, limit)
+                .unwrap()
+                .into_iter()
+                .map(|book| book.id.as_str().to_string())
+                .collect()
+        };
+
+        assert_eq!(ids(LibrarySort::RecentlyOpened, None), ["a", "c", "b"]);
+        assert_eq!(ids(LibrarySort::RecentlyAdded, None), ["c", "b", "a"]);
+        assert_eq!(ids(LibrarySort::TitleAsc, None), ["b", "a", "c"]);
+        assert_eq!(ids(LibrarySort::AuthorAsc, None), ["c", "a", "b"]);
+        assert_eq!(ids(LibrarySort::Duration, None), ["c", "b", "a"]);
+        assert_eq!(ids(LibrarySort::RecentlyAdded, Some(2)), ["c", "b"]);
+        let work = query_library(&conn, &paths, "work", LibrarySort::default(), None).unwrap();
+        assert_eq!(work.len(), 1);
+        assert_eq!(work[0].id.as_str(), "d");
+    }
+
+    #[test]
+    fn test_apply_book_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute(
+            "INSERT INTO books (id, title, author, source_format, source_path, narration_status, created_at, updated_at, language)
+             VALUES ('book', 'Title', 'Author', 'txt', 'sources/book.txt', 'none', 0, 0, 'deu')",
+            [],
+        )
+        .unwrap();
+        let id = BookId::new("book");
+
+        let book = apply_book_update(
+            &conn,
+            &id,
+            &BookUpdate { language: Some(" nl-BE ".to_string()), ..Default::default() },
+            10,
+        )
+        .unwrap();
+        assert_eq!(book.title, "Title");
+        assert_eq!(book.author.as_deref(), Some("Author"));
+        assert_eq!(book.language.as_deref(), Some("nl-BE"));
+        assert_eq!(book.updated_at, 10);
+
+        let book = apply_book_update(
+            &conn,
+            &id,
+            &BookUpdate {
+                title: Some("New Title".to_string()),
+                author: Some(String::new()),
+                language: Some(String::new()),
+            },
+            20,
+        )
+        .unwrap();
+        assert_eq!(book.title, "New Title");
+        assert_eq!(book.author, None);
+        assert_eq!(book.language, None);
+
+        let invalid = |update: BookUpdate| apply_book_update(&conn, &id, &update, 30);
+        assert!(matches!(
+            invalid(BookUpdate { title: Some(" ".to_string()), ..Default::default() }),
+            Err(CommandError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            invalid(BookUpdate { language: Some("en_US".to_string()), ..Default::default() }),
+            Err(CommandError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            apply_book_update(&conn, &BookId::new("missing"), &BookUpdate::default(), 30),
+            Err(CommandError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_measure_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+
+        for (id, source, is_reference) in [
+            ("small", "sources/small.txt", false),
+            ("large", "/home/me/large.txt", true),
+        ] {
+            db.connection().lock().unwrap().execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at, source_is_reference)
+                 VALUES (?1, ?1, 'txt', ?2, 'none', 0, 0, ?3)",
+                rusqlite::params![id, source, is_reference],
+            )
+            .unwrap();
+        }
+        std::fs::write(paths.sources.join("small.txt"), [0u8; 100]).unwrap();
+        std::fs::create_dir_all(paths.segment_cache_dir("large")).unwrap();
+        let audio = paths.narration_audio_path("large", crate::storage::NarrationCodec::Wav);
+        std::fs::write(audio, [0u8; 500]).unwrap();
+        std::fs::write(paths.segment_cache_path("large", "seg_1"), [0u8; 50]).unwrap();
+        std::fs::write(paths.bundles.join("old.actualbook"), [0u8; 7]).unwrap();
+
+        let usage = measure_storage(&db, &paths).unwrap();
+        assert_eq!(usage.sources_bytes, 100);
+        assert_eq!(usage.narration_bytes, 550);
+        assert_eq!(usage.bundles_bytes, 7);
+        assert!(usage.database_bytes > 0);
+        assert_eq!(
+            usage.total_bytes,
+            100 + 550 + 7 + usage.voices_bytes + usage.database_bytes
+        );
+
+        assert_eq!(usage.books[0].book_id.as_str(), "large");
+        assert_eq!(usage.books[0].narration_bytes, 550);
+        assert_eq!(usage.books[0].source_bytes, 0);
+        assert_eq!(usage.books[1].source_bytes, 100);
+    }
+
+    #[test]
+    fn test_source_shared() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        for (id, source, is_reference) in [
+            ("original", "sources/original.epub", false),
+            ("part", "sources/original.epub", true),
+            ("other", "sources/other.epub", false),
+        ] {
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at, source_is_reference)
+                 VALUES (?1, ?1, 'epub', ?2, 'none', 0, 0, ?3)",
+                rusqlite::params![id, source, is_reference],
+            )
+            .unwrap();
+        }
+
+        assert!(source_shared(&conn, &BookId::new("original")).unwrap());
+        assert!(source_shared(&conn, &BookId::new("part")).unwrap());
+        assert!(!source_shared(&conn, &BookId::new("other")).unwrap());
+
+        conn.execute("DELETE FROM books WHERE id = 'part'", []).unwrap();
+        assert!(!source_shared(&conn, &BookId::new("original")).unwrap());
+    }
+
+    #[test]
+    fn test_delete_book_row_hands_over_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        for (id, is_reference) in [("original", false), ("part_1", true), ("part_2", true)] {
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at, source_is_reference)
+                 VALUES (?1, ?1, 'epub', 'sources/original.epub', 'none', 0, 0, ?2)",
+                rusqlite::params![id, is_reference],
+            )
+            .unwrap();
+        }
+        let owned = || -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM books WHERE source_is_reference = 0", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        let source = "sources/original.epub";
+
+        // Deleting the original first leaves the parts owning the file
+        assert!(delete_book_row(&conn, &BookId::new("original"), source, false).unwrap());
+        assert_eq!(owned(), 2);
+
+        assert!(delete_book_row(&conn, &BookId::new("part_1"), source, false).unwrap());
+        assert!(!delete_book_row(&conn, &BookId::new("part_2"), source, false).unwrap());
+    }
 }
@@ -3,15 +3,26 @@
 //! This module contains all IPC command handlers that bridge the frontend
 //! to backend services. Commands follow the interface defined in ARCHITECTURE.md.
 
+mod audiobook;
 mod bundle;
+mod captions;
+mod error;
 mod library;
+mod profile;
+mod progress;
+mod pronunciation;
 mod reader;
 mod settings;
 mod sync;
 mod tts;
 
+pub use audiobook::*;
 pub use bundle::*;
+pub use captions::*;
+pub use error::*;
 pub use library::*;
+pub use profile::*;
+pub use pronunciation::*;
 pub use reader::*;
 pub use settings::*;
 pub use sync::*;
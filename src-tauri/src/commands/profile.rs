@@ -0,0 +1,228 @@
+//! Profile command handlers for Actual Reader.
+//!
+//! Profiles split one install into separate libraries, such as "Work" and
+//! "Personal". Every book belongs to one profile, and the library shows the
+//! books of the active one. Profiles are local organization only: the sync
+//! server and bundles carry no profile information.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tauri::State;
+use uuid::Uuid;
+
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::settings::{active_profile_id, set_active_profile};
+use crate::models::{BookId, Profile};
+use crate::AppState;
+
+/// Longest profile name accepted, in characters.
+const MAX_PROFILE_NAME_CHARS: usize = 100;
+
+/// Get the current Unix timestamp in seconds.
+fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Columns read by [`read_profile_row`], in order.
+const PROFILE_COLUMNS: &str =
+    "p.id, p.name, p.created_at, (SELECT COUNT(*) FROM books b WHERE b.profile_id = p.id)";
+
+fn read_profile_row(row: &rusqlite::Row) -> rusqlite::Result<Profile> {
+    Ok(Profile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        book_count: row.get(3)?,
+    })
+}
+
+/// Load a profile by id.
+fn query_profile(conn: &rusqlite::Connection, id: &str) -> CommandResult<Profile> {
+    conn.query_row(
+        &format!("SELECT {} FROM profiles p WHERE p.id = ?", PROFILE_COLUMNS),
+        rusqlite::params![id],
+        read_profile_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound("Profile not found".to_string())
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Load every profile, oldest first.
+fn query_profiles(conn: &rusqlite::Connection) -> CommandResult<Vec<Profile>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM profiles p ORDER BY p.created_at ASC, p.name COLLATE NOCASE ASC",
+            PROFILE_COLUMNS
+        ))
+        .context("Failed to prepare query")?;
+
+    let profiles = stmt
+        .query_map([], read_profile_row)
+        .context("Failed to query profiles")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read profile row")?;
+
+    Ok(profiles)
+}
+
+/// Add a profile with the given name, which must be unique ignoring case.
+fn insert_profile(conn: &rusqlite::Connection, name: &str, now: i64) -> CommandResult<Profile> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::InvalidInput("Profile name cannot be empty".to_string()));
+    }
+    if name.chars().count() > MAX_PROFILE_NAME_CHARS {
+        return Err(CommandError::InvalidInput(format!(
+            "Profile name must be at most {} characters",
+            MAX_PROFILE_NAME_CHARS
+        )));
+    }
+
+    let taken: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM profiles WHERE name = ? COLLATE NOCASE)",
+            rusqlite::params![name],
+            |row| row.get(0),
+        )
+        .context("Failed to check profile names")?;
+    if taken {
+        return Err(CommandError::Conflict(format!("A profile named '{}' already exists", name)));
+    }
+
+    let profile = Profile {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        created_at: now,
+        book_count: 0,
+    };
+    conn.execute(
+        "INSERT INTO profiles (id, name, created_at) VALUES (?, ?, ?)",
+        rusqlite::params![profile.id, profile.name, profile.created_at],
+    )
+    .context("Failed to create profile")?;
+
+    Ok(profile)
+}
+
+/// Move a book into another profile.
+fn assign_book_profile(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    profile_id: &str,
+) -> CommandResult<()> {
+    query_profile(conn, profile_id)?;
+    let updated = conn
+        .execute(
+            "UPDATE books SET profile_id = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![profile_id, current_timestamp(), book_id.as_str()],
+        )
+        .context("Failed to move book")?;
+    if updated == 0 {
+        return Err(CommandError::NotFound("Book not found".to_string()));
+    }
+    Ok(())
+}
+
+/// Get every profile with its book count, oldest first.
+#[tauri::command]
+pub async fn list_profiles(state: State<'_, AppState>) -> CommandResult<Vec<Profile>> {
+    let conn = state.db.connection().lock().unwrap();
+    query_profiles(&conn)
+}
+
+/// Get the profile the library currently shows.
+#[tauri::command]
+pub async fn get_active_profile(state: State<'_, AppState>) -> CommandResult<Profile> {
+    let conn = state.db.connection().lock().unwrap();
+    let id = active_profile_id(&conn)?;
+    query_profile(&conn, &id)
+}
+
+/// Create an empty profile. Names must be unique, ignoring case.
+///
+/// The active profile is unchanged; call `switch_profile` to use it.
+#[tauri::command]
+pub async fn create_profile(name: String, state: State<'_, AppState>) -> CommandResult<Profile> {
+    let conn = state.db.connection().lock().unwrap();
+    insert_profile(&conn, &name, current_timestamp())
+}
+
+/// Make a profile active, so the library shows its books and new imports
+/// are added to it.
+#[tauri::command]
+pub async fn switch_profile(
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<Profile> {
+    let conn = state.db.connection().lock().unwrap();
+    let profile = query_profile(&conn, &profile_id)?;
+    set_active_profile(&conn, &profile.id)?;
+    log::info!("Switched to profile {}", profile.id);
+    Ok(profile)
+}
+
+/// Move a book into another profile, keeping its narration and progress.
+#[tauri::command]
+pub async fn move_book_to_profile(
+    book_id: BookId,
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let conn = state.db.connection().lock().unwrap();
+    assign_book_profile(&conn, &book_id, &profile_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DEFAULT_PROFILE_ID;
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+             VALUES ('book', 'Book', 'txt', 'sources/book.txt', 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        let work = insert_profile(&conn, "  Work ", 10).unwrap();
+        assert_eq!(work.name, "Work");
+        assert!(matches!(insert_profile(&conn, "work", 11), Err(CommandError::Conflict(_))));
+        assert!(matches!(insert_profile(&conn, " ", 11), Err(CommandError::InvalidInput(_))));
+
+        // An unset or dangling setting falls back to the default profile
+        assert_eq!(active_profile_id(&conn).unwrap(), DEFAULT_PROFILE_ID);
+        set_active_profile(&conn, "deleted").unwrap();
+        assert_eq!(active_profile_id(&conn).unwrap(), DEFAULT_PROFILE_ID);
+        set_active_profile(&conn, &work.id).unwrap();
+        assert_eq!(active_profile_id(&conn).unwrap(), work.id);
+
+        assign_book_profile(&conn, &BookId::new("book"), &work.id).unwrap();
+        let counts: Vec<(String, u32)> = query_profiles(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.name, p.book_count))
+            .collect();
+        assert_eq!(counts, vec![("Default".to_string(), 0), ("Work".to_string(), 1)]);
+
+        assert!(matches!(
+            assign_book_profile(&conn, &BookId::new("book"), "missing"),
+            Err(CommandError::NotFound(_))
+        ));
+        assert!(matches!(
+            assign_book_profile(&conn, &BookId::new("missing"), &work.id),
+            Err(CommandError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+//! Rate limiting for progress events.
+//!
+//! Long operations can report progress far more often than the UI can use;
+//! narrating a large book reports once per segment. `ProgressThrottle` emits
+//! at most one update per `MIN_INTERVAL`, holding back only the latest of the
+//! rest and flushing it on a timer so the state before a pause isn't lost.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// Minimum time between two throttled updates of one event (~10 per second).
+pub(crate) const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Emits the progress events of one operation, coalescing frequent updates.
+///
+/// Updates still held back when the throttle is dropped are discarded, so a
+/// stale update can't follow the operation's completion or error event.
+pub(crate) struct ProgressThrottle<T> {
+    app_handle: AppHandle,
+    event: &'static str,
+    state: Arc<Mutex<Coalescer<T>>>,
+}
+
+impl<T> ProgressThrottle<T>
+where
+    T: Serialize + Clone + Send + 'static,
+{
+    pub(crate) fn new(app_handle: AppHandle, event: &'static str) -> Self {
+        Self {
+            app_handle,
+            event,
+            state: Arc::new(Mutex::new(Coalescer::new(MIN_INTERVAL))),
+        }
+    }
+
+    pub(crate) fn app_handle(&self) -> &AppHandle {
+        &self.app_handle
+    }
+
+    /// Emit an update, or hold it back until the interval has passed.
+    ///
+    /// Milestones such as stage transitions and the final update are emitted
+    /// immediately, replacing any update held back.
+    pub(crate) fn emit(&self, payload: T, milestone: bool) {
+        let mut state = lock(&self.state);
+        let now = Instant::now();
+        let offer = if milestone {
+            Offer::Emit(state.force(payload, now))
+        } else {
+            state.offer(payload, now)
+        };
+
+        // Emit while holding the lock so a timer flush can't overtake it
+        match offer {
+            Offer::Emit(payload) => {
+                let _ = self.app_handle.emit(self.event, payload);
+            }
+            Offer::Schedule(delay) => {
+                let shared = self.state.clone();
+                let app_handle = self.app_handle.clone();
+                let event = self.event;
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let mut state = lock(&shared);
+                    if let Some(payload) = state.take_pending(Instant::now()) {
+                        let _ = app_handle.emit(event, payload);
+                    }
+                });
+            }
+            Offer::Held => {}
+        }
+    }
+}
+
+impl<T> Drop for ProgressThrottle<T> {
+    fn drop(&mut self) {
+        lock(&self.state).close();
+    }
+}
+
+/// Lock the throttle state; a panic while emitting leaves it consistent.
+fn lock<T>(state: &Mutex<Coalescer<T>>) -> MutexGuard<'_, Coalescer<T>> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// What to do with an update offered to a `Coalescer`.
+#[derive(Debug, PartialEq)]
+enum Offer<T> {
+    /// Emit the update now.
+    Emit(T),
+    /// The update is held back; flush it after the delay.
+    Schedule(Duration),
+    /// The update replaced one already held back, whose flush is scheduled.
+    Held,
+}
+
+/// Timing of a throttled event, separate from emitting so it can be tested.
+struct Coalescer<T> {
+    interval: Duration,
+    last_emit: Option<Instant>,
+    /// Latest update held back.
+    pending: Option<T>,
+    flush_scheduled: bool,
+    closed: bool,
+}
+
+impl<T> Coalescer<T> {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_emit: None,
+            pending: None,
+            flush_scheduled: false,
+            closed: false,
+        }
+    }
+
+    /// Offer an update, emitting it if the interval has passed since the
+    /// last emit and holding it back otherwise.
+    fn offer(&mut self, payload: T, now: Instant) -> Offer<T> {
+        let elapsed = self.last_emit.map(|last| now.saturating_duration_since(last));
+        match elapsed {
+            Some(elapsed) if elapsed < self.interval => {
+                self.pending = Some(payload);
+                if self.flush_scheduled {
+                    Offer::Held
+                } else {
+                    self.flush_scheduled = true;
+                    Offer::Schedule(self.interval - elapsed)
+                }
+            }
+            _ => Offer::Emit(self.force(payload, now)),
+        }
+    }
+
+    /// Take an update to emit now regardless of the interval, dropping any
+    /// update held back.
+    fn force(&mut self, payload: T, now: Instant) -> T {
+        self.pending = None;
+        self.last_emit = Some(now);
+        payload
+    }
+
+    /// Take the update held back, when its scheduled flush comes due.
+    fn take_pending(&mut self, now: Instant) -> Option<T> {
+        self.flush_scheduled = false;
+        if self.closed {
+            return None;
+        }
+        let payload = self.pending.take()?;
+        self.last_emit = Some(now);
+        Some(payload)
+    }
+
+    /// Stop emitting; anything held back is discarded.
+    fn close(&mut self) {
+        self.closed = true;
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalescer_holds_back_frequent_updates() {
+        let interval = Duration::from_millis(100);
+        let mut coalescer = Coalescer::new(interval);
+        let start = Instant::now();
+
+        assert_eq!(coalescer.offer(1, start), Offer::Emit(1));
+        assert_eq!(
+            coalescer.offer(2, start + Duration::from_millis(30)),
+            Offer::Schedule(Duration::from_millis(70))
+        );
+        assert_eq!(coalescer.offer(3, start + Duration::from_millis(60)), Offer::Held);
+
+        // Only the latest update is flushed
+        assert_eq!(coalescer.take_pending(start + interval), Some(3));
+        assert_eq!(coalescer.take_pending(start + interval), None);
+
+        assert_eq!(
+            coalescer.offer(4, start + Duration::from_millis(250)),
+            Offer::Emit(4)
+        );
+    }
+
+    #[test]
+    fn test_coalescer_force_replaces_pending() {
+        let mut coalescer = Coalescer::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        coalescer.offer(1, start);
+        coalescer.offer(2, start + Duration::from_millis(10));
+        assert_eq!(coalescer.force(3, start + Duration::from_millis(20)), 3);
+
+        // The scheduled flush finds nothing left to emit
+        assert_eq!(coalescer.take_pending(start + Duration::from_millis(100)), None);
+    }
+
+    #[test]
+    fn test_coalescer_close_discards_pending() {
+        let mut coalescer = Coalescer::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        coalescer.offer(1, start);
+        coalescer.offer(2, start + Duration::from_millis(10));
+        coalescer.close();
+
+        assert_eq!(coalescer.take_pending(start + Duration::from_millis(100)), None);
+    }
+}
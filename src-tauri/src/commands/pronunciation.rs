@@ -0,0 +1,127 @@
+//! Pronunciation dictionary command handlers for Actual Reader.
+//!
+//! Manages the replacements applied to segment text before it is sent to
+//! the TTS engine.
+
+use tauri::State;
+
+use super::error::{CommandError, CommandResult, ResultExt};
+use crate::models::Pronunciation;
+use crate::services::pronunciation::PronunciationRules;
+use crate::AppState;
+
+/// Load every dictionary entry in the order rules are applied.
+pub(crate) fn query_pronunciations(conn: &rusqlite::Connection) -> CommandResult<Vec<Pronunciation>> {
+    let mut stmt = conn
+        .prepare("SELECT id, pattern, replacement, is_regex FROM pronunciations ORDER BY id ASC")
+        .context("Failed to prepare query")?;
+
+    let entries = stmt
+        .query_map([], |row| {
+            Ok(Pronunciation {
+                id: row.get(0)?,
+                pattern: row.get(1)?,
+                replacement: row.get(2)?,
+                is_regex: row.get(3)?,
+            })
+        })
+        .context("Failed to query pronunciations")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read pronunciation row")?;
+
+    Ok(entries)
+}
+
+/// Check that an entry can be compiled into a rule.
+fn validate_entry(entry: &Pronunciation) -> CommandResult<()> {
+    if entry.pattern.is_empty() {
+        return Err(CommandError::InvalidInput("Pattern cannot be empty".to_string()));
+    }
+    PronunciationRules::compile(std::slice::from_ref(entry))
+        .with_context(|| format!("Invalid pattern '{}'", entry.pattern))?;
+    Ok(())
+}
+
+/// Get the pronunciation dictionary, in the order rules are applied.
+#[tauri::command]
+pub async fn get_pronunciations(state: State<'_, AppState>) -> CommandResult<Vec<Pronunciation>> {
+    let conn = state.db.connection().lock().unwrap();
+    query_pronunciations(&conn)
+}
+
+/// Add a pronunciation rule.
+///
+/// Literal patterns match whole words; regular expressions may use `$1`-style
+/// captures in the replacement. New rules are applied after existing ones.
+#[tauri::command]
+pub async fn add_pronunciation(
+    pattern: String,
+    replacement: String,
+    is_regex: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<Pronunciation> {
+    let mut entry = Pronunciation {
+        id: 0,
+        pattern,
+        replacement,
+        is_regex,
+    };
+    validate_entry(&entry)?;
+
+    let conn = state.db.connection().lock().unwrap();
+    conn.execute(
+        "INSERT INTO pronunciations (pattern, replacement, is_regex) VALUES (?, ?, ?)",
+        rusqlite::params![entry.pattern, entry.replacement, entry.is_regex],
+    )
+    .context("Failed to add pronunciation")?;
+    entry.id = conn.last_insert_rowid();
+
+    Ok(entry)
+}
+
+/// Change a pronunciation rule, keeping its place in the order.
+#[tauri::command]
+pub async fn update_pronunciation(
+    id: i64,
+    pattern: String,
+    replacement: String,
+    is_regex: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<Pronunciation> {
+    let entry = Pronunciation {
+        id,
+        pattern,
+        replacement,
+        is_regex,
+    };
+    validate_entry(&entry)?;
+
+    let conn = state.db.connection().lock().unwrap();
+    let updated = conn
+        .execute(
+            "UPDATE pronunciations SET pattern = ?, replacement = ?, is_regex = ? WHERE id = ?",
+            rusqlite::params![entry.pattern, entry.replacement, entry.is_regex, id],
+        )
+        .context("Failed to update pronunciation")?;
+
+    if updated == 0 {
+        return Err(CommandError::NotFound("Pronunciation not found".to_string()));
+    }
+
+    Ok(entry)
+}
+
+/// Remove a pronunciation rule.
+#[tauri::command]
+pub async fn delete_pronunciation(id: i64, state: State<'_, AppState>) -> CommandResult<()> {
+    let conn = state.db.connection().lock().unwrap();
+    let deleted = conn
+        .execute("DELETE FROM pronunciations WHERE id = ?", rusqlite::params![id])
+        .context("Failed to delete pronunciation")?;
+
+    if deleted == 0 {
+        return Err(CommandError::NotFound("Pronunciation not found".to_string()));
+    }
+
+    Ok(())
+}
@@ -2,15 +2,52 @@
 //!
 //! Commands for reading books: fetching book data, segments, markers, and managing progress.
 
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
 
+use super::bundle::update_content_hash;
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::library::{query_book, resolve_book_paths};
 use crate::models::{
-    Book, BookId, Marker, NarrationStatus, Progress, Segment, SegmentId, SegmentType, SourceFormat,
+    Book, BookId, BookMetadata, Chapter, ImageData, ImagePosition, Marker, NarrationMeta,
+    NarrationStatus, Progress, Segment, SegmentId, SegmentType, SourceFormat,
 };
+use crate::services::tts::{probe_audio, read_wav_slice};
+use crate::storage::{AppPaths, Database, NarrationCodec};
 use crate::AppState;
 
+/// Saves further apart than this (seconds) start a new listening session.
+const SESSION_GAP_SECS: i64 = 300;
+
+/// Fastest playback rate; audio jumps beyond this are treated as seeks.
+const MAX_PLAYBACK_RATE: f64 = 2.0;
+
+/// Listening time attributed to a single book.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookReadingTime {
+    pub book_id: BookId,
+    pub title: String,
+    pub seconds_listened: f64,
+}
+
+/// Aggregated reading statistics across the library.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingStats {
+    /// Total narration time listened across all books (seconds).
+    pub total_seconds_listened: f64,
+    /// Listening time per book, most listened first.
+    pub books: Vec<BookReadingTime>,
+    /// Number of books whose progress has reached the last segment.
+    pub books_completed: u32,
+    /// Consecutive days (UTC) with listening activity, ending today or yesterday.
+    pub current_streak_days: u32,
+}
+
 /// Get the current Unix timestamp in seconds.
 fn current_timestamp() -> i64 {
     SystemTime::now()
@@ -23,7 +60,7 @@ fn current_timestamp() -> i64 {
 ///
 /// Also updates the book's last_opened_at timestamp.
 #[tauri::command]
-pub async fn get_book(id: BookId, state: State<'_, AppState>) -> Result<Book, String> {
+pub async fn get_book(id: BookId, state: State<'_, AppState>) -> CommandResult<Book> {
     let conn = state.db.connection().lock().unwrap();
     let now = current_timestamp();
 
@@ -32,16 +69,17 @@ pub async fn get_book(id: BookId, state: State<'_, AppState>) -> Result<Book, St
         "UPDATE books SET last_opened_at = ? WHERE id = ?",
         rusqlite::params![now, id.as_str()],
     )
-    .map_err(|e| format!("Failed to update last_opened_at: {}", e))?;
+    .context("Failed to update last_opened_at")?;
 
     // Fetch the book
     let mut stmt = conn
         .prepare(
             "SELECT id, title, author, source_format, source_path, narration_status,
-                    narration_path, created_at, updated_at, last_opened_at
+                    narration_path, created_at, updated_at, last_opened_at, duration, language, finished_at,
+                    publisher, published_date, identifier, isbn, narration_meta, original_filename
              FROM books WHERE id = ?",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?;
 
     let book = stmt
         .query_row(rusqlite::params![id.as_str()], |row| {
@@ -61,14 +99,65 @@ pub async fn get_book(id: BookId, state: State<'_, AppState>) -> Result<Book, St
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
                 last_opened_at: row.get(9)?,
+                duration: row.get(10)?,
+                language: row.get(11)?,
+                finished_at: row.get(12)?,
+                metadata: BookMetadata {
+                    publisher: row.get(13)?,
+                    published_date: row.get(14)?,
+                    identifier: row.get(15)?,
+                    isbn: row.get(16)?,
+                },
+                narration_meta: NarrationMeta::from_json(row.get::<_, Option<String>>(17)?.as_deref()),
+                original_filename: row.get(18)?,
             })
         })
         .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => "Book not found".to_string(),
-            _ => format!("Database error: {}", e),
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound("Book not found".to_string())
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
         })?;
 
-    Ok(book)
+    Ok(resolve_book_paths(book, &state.paths()))
+}
+
+/// Columns read by `read_segment_row`, from `segments s` joined with `segment_images i`.
+const SEGMENT_COLUMNS: &str = "s.id, s.book_id, s.idx, s.content, s.html,
+    i.source_path, i.caption, i.caption_prompt, i.alt_text, i.page_number, i.position,
+    s.segment_type, s.narrate";
+
+/// Map a row selected with `SEGMENT_COLUMNS` to a Segment.
+fn read_segment_row(row: &rusqlite::Row) -> rusqlite::Result<Segment> {
+    // Segments with a segment_images row are images
+    let image_data = match row.get::<_, Option<String>>(5)? {
+        Some(source_path) => Some(ImageData {
+            source_path,
+            caption: row.get(6)?,
+            caption_prompt: row.get(7)?,
+            alt_text: row.get(8)?,
+            page_number: row.get(9)?,
+            position: ImagePosition::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
+        }),
+        None => None,
+    };
+
+    Ok(Segment {
+        id: SegmentId::new(row.get::<_, String>(0)?),
+        book_id: BookId::new(row.get::<_, String>(1)?),
+        index: row.get(2)?,
+        content: row.get(3)?,
+        html: row.get(4)?,
+        segment_type: if image_data.is_some() {
+            SegmentType::Image
+        } else {
+            SegmentType::from_str(&row.get::<_, String>(11)?)
+                .filter(|t| *t != SegmentType::Image)
+                .unwrap_or_default()
+        },
+        image_data,
+        narrate: row.get(12)?,
+    })
 }
 
 /// Get all segments for a book.
@@ -78,33 +167,837 @@ pub async fn get_book(id: BookId, state: State<'_, AppState>) -> Result<Book, St
 pub async fn get_segments(
     book_id: BookId,
     state: State<'_, AppState>,
-) -> Result<Vec<Segment>, String> {
+) -> CommandResult<Vec<Segment>> {
     let conn = state.db.connection().lock().unwrap();
+    query_segments(&conn, book_id.as_str())
+}
 
+/// Get all segments of a book in order, with image data for image segments.
+pub(crate) fn query_segments(
+    conn: &rusqlite::Connection,
+    book_id: &str,
+) -> CommandResult<Vec<Segment>> {
     let mut stmt = conn
+        .prepare_cached(&format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments s LEFT JOIN segment_images i ON i.segment_id = s.id
+             WHERE s.book_id = ? ORDER BY s.idx ASC"
+        ))
+        .context("Failed to prepare query")?;
+
+    let segments = stmt
+        .query_map(rusqlite::params![book_id], read_segment_row)
+        .context("Failed to query segments")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read segment row")?;
+
+    Ok(segments)
+}
+
+/// Get a single segment by ID.
+fn query_segment(conn: &rusqlite::Connection, segment_id: &SegmentId) -> CommandResult<Segment> {
+    conn.query_row(
+        &format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments s LEFT JOIN segment_images i ON i.segment_id = s.id
+             WHERE s.id = ?"
+        ),
+        rusqlite::params![segment_id.as_str()],
+        read_segment_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound("Segment not found".to_string())
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Reject edits to a book whose narration is being generated, since the
+/// audio would be built from text that is changing underneath it.
+async fn ensure_not_generating(state: &AppState, segment_id: &SegmentId) -> CommandResult<()> {
+    let book_id = {
+        let conn = state.db.connection().lock().unwrap();
+        query_segment(&conn, segment_id)?.book_id
+    };
+
+    ensure_book_not_generating(state, &book_id).await
+}
+
+/// Reject structural changes to a book whose narration is being generated.
+async fn ensure_book_not_generating(state: &AppState, book_id: &BookId) -> CommandResult<()> {
+    if state.active_generations.read().await.contains_key(book_id.as_str()) {
+        return Err(CommandError::Conflict(
+            "Cannot edit segments while narration is being generated".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Record an edit to a book's text, flagging ready or partial narration as
+/// stale because the audio no longer matches.
+pub(crate) fn mark_narration_stale(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) -> CommandResult<()> {
+    conn.execute(
+        "UPDATE books SET
+             narration_status = CASE WHEN narration_status IN (?1, ?2) THEN ?3 ELSE narration_status END,
+             updated_at = ?4
+         WHERE id = ?5",
+        rusqlite::params![
+            NarrationStatus::Ready.as_str(),
+            NarrationStatus::Partial.as_str(),
+            NarrationStatus::Stale.as_str(),
+            current_timestamp(),
+            book_id.as_str(),
+        ],
+    )
+    .context("Failed to update narration status")?;
+    Ok(())
+}
+
+/// Shift the index of every segment at or after `from` by `delta`, along
+/// with chapter starts and reading progress that point at them.
+///
+/// Segment indexes are unique per book, so they are moved through negative
+/// values to avoid colliding partway through the update.
+fn shift_segment_indices(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    from: u32,
+    delta: i64,
+) -> CommandResult<()> {
+    conn.execute(
+        "UPDATE segments SET idx = -1 - (idx + ?1) WHERE book_id = ?2 AND idx >= ?3",
+        rusqlite::params![delta, book_id.as_str(), from],
+    )
+    .context("Failed to shift segments")?;
+    conn.execute(
+        "UPDATE segments SET idx = -1 - idx WHERE book_id = ? AND idx < 0",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to shift segments")?;
+
+    conn.execute(
+        "UPDATE chapters SET start_segment_index = start_segment_index + ?1
+         WHERE book_id = ?2 AND start_segment_index >= ?3",
+        rusqlite::params![delta, book_id.as_str(), from],
+    )
+    .context("Failed to shift chapters")?;
+
+    conn.execute(
+        "UPDATE progress SET
+             segment_index = CASE WHEN segment_index >= ?3 THEN segment_index + ?1 ELSE segment_index END,
+             max_segment_index = CASE WHEN max_segment_index >= ?3 THEN max_segment_index + ?1 ELSE max_segment_index END
+         WHERE book_id = ?2",
+        rusqlite::params![delta, book_id.as_str(), from],
+    )
+    .context("Failed to shift progress")?;
+
+    Ok(())
+}
+
+/// Renumber a book's segments to a contiguous 0-based sequence, keeping
+/// their order.
+///
+/// Chapter starts, reading progress and voice ranges that point into a gap
+/// move to the next segment after it. Markers reference segments by ID, so
+/// they need no changes. Returns the number of segments in the book.
+///
+/// Callers run this inside their own transaction.
+fn reindex_book_segments(conn: &rusqlite::Connection, book_id: &BookId) -> CommandResult<u32> {
+    let ids: Vec<(String, u32)> = conn
+        .prepare("SELECT id, idx FROM segments WHERE book_id = ? ORDER BY idx ASC")
+        .context("Failed to prepare query")?
+        .query_map(rusqlite::params![book_id.as_str()], |row| Ok((row.get(0)?, row.get(1)?)))
+        .context("Failed to query segments")?
+        .collect::<Result<_, _>>()
+        .context("Failed to read segment row")?;
+
+    let count = ids.len() as u32;
+    if ids.iter().enumerate().all(|(i, (_, idx))| *idx == i as u32) {
+        return Ok(count);
+    }
+
+    // References are remapped first, while segments still hold their old
+    // indexes: a reference to index x becomes the number of segments before x
+    conn.execute(
+        "UPDATE chapters SET start_segment_index = (
+             SELECT COUNT(*) FROM segments s
+             WHERE s.book_id = chapters.book_id AND s.idx < chapters.start_segment_index)
+         WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to reindex chapters")?;
+
+    conn.execute(
+        "UPDATE progress SET
+             segment_index = (
+                 SELECT COUNT(*) FROM segments s
+                 WHERE s.book_id = progress.book_id AND s.idx < progress.segment_index),
+             max_segment_index = (
+                 SELECT COUNT(*) FROM segments s
+                 WHERE s.book_id = progress.book_id AND s.idx < progress.max_segment_index)
+         WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to reindex progress")?;
+
+    // Ranges that only covered a gap no longer cover any segment
+    conn.execute(
+        "DELETE FROM segment_voices WHERE book_id = ?1 AND NOT EXISTS (
+             SELECT 1 FROM segments s
+             WHERE s.book_id = ?1 AND s.idx BETWEEN segment_voices.start_index AND segment_voices.end_index)",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to remove empty voice overrides")?;
+
+    // Range starts are part of the key, so they also go through negatives
+    conn.execute(
+        "UPDATE segment_voices SET
+             start_index = -1 - (
+                 SELECT COUNT(*) FROM segments s
+                 WHERE s.book_id = segment_voices.book_id AND s.idx < segment_voices.start_index),
+             end_index = (
+                 SELECT COUNT(*) FROM segments s
+                 WHERE s.book_id = segment_voices.book_id AND s.idx <= segment_voices.end_index) - 1
+         WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to reindex voice overrides")?;
+    conn.execute(
+        "UPDATE segment_voices SET start_index = -1 - start_index WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to reindex voice overrides")?;
+
+    // Move segments through negative indexes to avoid colliding with
+    // indexes that are still in use
+    conn.execute(
+        "UPDATE segments SET idx = -1 - idx WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to reindex segments")?;
+    {
+        let mut stmt = conn
+            .prepare("UPDATE segments SET idx = ? WHERE id = ?")
+            .context("Failed to prepare statement")?;
+        for (index, (id, _)) in ids.iter().enumerate() {
+            stmt.execute(rusqlite::params![index as u32, id])
+                .context("Failed to reindex segments")?;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Split text at a character offset, trimming whitespace at the cut.
+///
+/// Returns None if either side would be empty.
+fn split_text(content: &str, offset: usize) -> Option<(&str, &str)> {
+    let (at, _) = content.char_indices().nth(offset)?;
+    let (first, second) = content.split_at(at);
+    let (first, second) = (first.trim_end(), second.trim_start());
+
+    if first.trim().is_empty() || second.trim().is_empty() {
+        return None;
+    }
+    Some((first, second))
+}
+
+/// Replace a segment's text.
+fn edit_segment(
+    conn: &rusqlite::Connection,
+    segment_id: &SegmentId,
+    content: &str,
+    html: Option<&str>,
+) -> CommandResult<()> {
+    let segment = query_segment(conn, segment_id)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    tx.execute(
+        "UPDATE segments SET content = ?, html = ? WHERE id = ?",
+        rusqlite::params![content, html, segment_id.as_str()],
+    )
+    .context("Failed to update segment")?;
+    mark_narration_stale(&tx, &segment.book_id)?;
+    update_content_hash(&tx, segment.book_id.as_str())?;
+
+    tx.commit().context("Failed to commit transaction")?;
+    Ok(())
+}
+
+/// Include a segment in narration or leave it out.
+fn set_narrate(
+    conn: &rusqlite::Connection,
+    segment_id: &SegmentId,
+    narrate: bool,
+) -> CommandResult<()> {
+    let segment = query_segment(conn, segment_id)?;
+    if segment.narrate == narrate {
+        return Ok(());
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    tx.execute(
+        "UPDATE segments SET narrate = ? WHERE id = ?",
+        rusqlite::params![narrate, segment_id.as_str()],
+    )
+    .context("Failed to update segment")?;
+    mark_narration_stale(&tx, &segment.book_id)?;
+
+    tx.commit().context("Failed to commit transaction")?;
+    Ok(())
+}
+
+/// Split a text segment in two at a character offset.
+///
+/// The first half keeps the segment's ID and any narration markers; the
+/// second half becomes a new segment directly after it. Returns the new
+/// segment's ID.
+fn split_segment_at(
+    conn: &rusqlite::Connection,
+    segment_id: &SegmentId,
+    offset: usize,
+) -> CommandResult<SegmentId> {
+    let segment = query_segment(conn, segment_id)?;
+    if segment.segment_type == SegmentType::Image {
+        return Err(CommandError::InvalidInput("Image segments cannot be split".to_string()));
+    }
+
+    let (first, second) = split_text(&segment.content, offset).ok_or_else(|| {
+        CommandError::InvalidInput("Split would leave an empty segment".to_string())
+    })?;
+    let new_id = SegmentId::new(format!("seg_{}", uuid::Uuid::new_v4()));
+    let book_id = &segment.book_id;
+    let index = segment.index;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    shift_segment_indices(&tx, book_id, index + 1, 1)?;
+
+    // Markup can't be split reliably, so both halves fall back to plain text
+    tx.execute(
+        "UPDATE segments SET content = ?, html = NULL WHERE id = ?",
+        rusqlite::params![first, segment_id.as_str()],
+    )
+    .context("Failed to update segment")?;
+    tx.execute(
+        "INSERT INTO segments (id, book_id, idx, content, segment_type, narrate)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            new_id.as_str(),
+            book_id.as_str(),
+            index + 1,
+            second,
+            segment.segment_type.as_str(),
+            segment.narrate,
+        ],
+    )
+    .context("Failed to insert segment")?;
+
+    // Voice ranges covering the split segment also cover its second half
+    tx.execute(
+        "UPDATE segment_voices SET
+             start_index = CASE WHEN start_index > ?2 THEN start_index + 1 ELSE start_index END,
+             end_index = end_index + 1
+         WHERE book_id = ?1 AND end_index >= ?2",
+        rusqlite::params![book_id.as_str(), index],
+    )
+    .context("Failed to shift voice overrides")?;
+
+    reindex_book_segments(&tx, book_id)?;
+    mark_narration_stale(&tx, book_id)?;
+    update_content_hash(&tx, book_id.as_str())?;
+    tx.commit().context("Failed to commit transaction")?;
+
+    Ok(new_id)
+}
+
+/// Merge a text segment with the one after it.
+///
+/// The merged segment keeps the first segment's ID; its narration marker is
+/// extended to cover the second segment's audio.
+fn merge_segment_with_next(conn: &rusqlite::Connection, segment_id: &SegmentId) -> CommandResult<()> {
+    let segment = query_segment(conn, segment_id)?;
+    let book_id = &segment.book_id;
+    let next_index = segment.index + 1;
+
+    let next = conn
+        .query_row(
+            "SELECT id FROM segments WHERE book_id = ? AND idx = ?",
+            rusqlite::params![book_id.as_str(), next_index],
+            |row| row.get::<_, String>(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::InvalidInput("Segment is the last in the book".to_string())
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
+        })?;
+    let next = query_segment(conn, &SegmentId::new(next))?;
+
+    if segment.segment_type == SegmentType::Image || next.segment_type == SegmentType::Image {
+        return Err(CommandError::InvalidInput("Image segments cannot be merged".to_string()));
+    }
+
+    let content = format!("{}\n{}", segment.content, next.content);
+    let html = match (segment.html, next.html) {
+        (Some(a), Some(b)) => Some(a + &b),
+        _ => None,
+    };
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    tx.execute(
+        "UPDATE segments SET content = ?, html = ? WHERE id = ?",
+        rusqlite::params![content, html, segment_id.as_str()],
+    )
+    .context("Failed to update segment")?;
+
+    tx.execute(
+        "UPDATE markers SET end_time = MAX(end_time, COALESCE(
+             (SELECT MAX(end_time) FROM markers WHERE segment_id = ?1), end_time))
+         WHERE segment_id = ?2",
+        rusqlite::params![next.id.as_str(), segment_id.as_str()],
+    )
+    .context("Failed to merge markers")?;
+
+    // Deleting the segment also deletes its markers
+    tx.execute("DELETE FROM segments WHERE id = ?", rusqlite::params![next.id.as_str()])
+        .context("Failed to delete segment")?;
+
+    // Voice ranges covering only the removed segment go with it
+    tx.execute(
+        "DELETE FROM segment_voices WHERE book_id = ?1 AND start_index = ?2 AND end_index = ?2",
+        rusqlite::params![book_id.as_str(), next_index],
+    )
+    .context("Failed to remove voice override")?;
+    tx.execute(
+        "UPDATE segment_voices SET
+             start_index = CASE WHEN start_index > ?2 THEN start_index - 1 ELSE start_index END,
+             end_index = end_index - 1
+         WHERE book_id = ?1 AND end_index >= ?2",
+        rusqlite::params![book_id.as_str(), next_index],
+    )
+    .context("Failed to shift voice overrides")?;
+
+    shift_segment_indices(&tx, book_id, next_index, -1)?;
+    reindex_book_segments(&tx, book_id)?;
+
+    mark_narration_stale(&tx, book_id)?;
+    update_content_hash(&tx, book_id.as_str())?;
+    tx.commit().context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+/// Correct a segment's text, e.g. to fix a parser mistake.
+///
+/// If the book's narration was ready it is marked stale, since the audio no
+/// longer matches the text. Returns the updated segment.
+#[tauri::command]
+pub async fn update_segment(
+    segment_id: SegmentId,
+    content: String,
+    html: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<Segment> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(CommandError::InvalidInput("Segment content cannot be empty".to_string()));
+    }
+
+    ensure_not_generating(&state, &segment_id).await?;
+
+    let conn = state.db.connection().lock().unwrap();
+    edit_segment(&conn, &segment_id, content, html.as_deref())?;
+    query_segment(&conn, &segment_id)
+}
+
+/// Choose whether a segment is narrated, e.g. to leave out a page number.
+///
+/// Segments left out stay in the reader but get no audio or marker the
+/// next time narration is generated. If the book's narration was ready
+/// and the flag changes, it is marked stale. Returns the updated segment.
+#[tauri::command]
+pub async fn set_segment_narrate(
+    segment_id: SegmentId,
+    narrate: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<Segment> {
+    ensure_not_generating(&state, &segment_id).await?;
+
+    let conn = state.db.connection().lock().unwrap();
+    set_narrate(&conn, &segment_id, narrate)?;
+    query_segment(&conn, &segment_id)
+}
+
+/// Split a text segment in two at a character offset into its content.
+///
+/// Later segments move down by one index. Returns both halves.
+#[tauri::command]
+pub async fn split_segment(
+    segment_id: SegmentId,
+    offset: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<Segment>> {
+    ensure_not_generating(&state, &segment_id).await?;
+
+    let conn = state.db.connection().lock().unwrap();
+    let new_id = split_segment_at(&conn, &segment_id, offset as usize)?;
+    Ok(vec![query_segment(&conn, &segment_id)?, query_segment(&conn, &new_id)?])
+}
+
+/// Merge a text segment with the segment that follows it.
+///
+/// Later segments move up by one index. Returns the merged segment.
+#[tauri::command]
+pub async fn merge_segments(
+    segment_id: SegmentId,
+    state: State<'_, AppState>,
+) -> CommandResult<Segment> {
+    ensure_not_generating(&state, &segment_id).await?;
+
+    let conn = state.db.connection().lock().unwrap();
+    merge_segment_with_next(&conn, &segment_id)?;
+    query_segment(&conn, &segment_id)
+}
+
+/// Renumber a book's segments to a contiguous 0-based sequence.
+///
+/// Repairs gaps left by structural edits; chapter starts, reading progress
+/// and voice overrides follow their segments. Returns the segment count.
+#[tauri::command]
+pub async fn reindex_segments(book_id: BookId, state: State<'_, AppState>) -> CommandResult<u32> {
+    ensure_book_not_generating(&state, &book_id).await?;
+
+    let conn = state.db.connection().lock().unwrap();
+    conn.query_row(
+        "SELECT 1 FROM books WHERE id = ?",
+        rusqlite::params![book_id.as_str()],
+        |_| Ok(()),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound(format!("Book not found: {}", book_id))
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    let count = reindex_book_segments(&tx, &book_id)?;
+    tx.commit().context("Failed to commit transaction")?;
+
+    Ok(count)
+}
+
+/// Split a book into consecutive parts, each starting at one of `cuts`.
+///
+/// The book keeps the segments before the first cut. Each later part becomes
+/// a new book with its segments re-indexed from 0, taking the chapters, voice
+/// overrides and reading position that fall inside it, and a copy of its
+/// images. A part is titled after a chapter starting where it does, if any.
+///
+/// Parts share the source file without owning it, so deleting one leaves it
+/// in place. The narration audio stays with the first part, whose ready
+/// narration is marked stale; the other parts have none, since its offsets
+/// don't apply to them. Returns the IDs of every part in order.
+fn split_book_at(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    book_id: &BookId,
+    cuts: &[u32],
+) -> CommandResult<Vec<BookId>> {
+    let title: String = conn
+        .query_row(
+            "SELECT title FROM books WHERE id = ?",
+            rusqlite::params![book_id.as_str()],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound(format!("Book not found: {}", book_id))
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
+        })?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    let count = reindex_book_segments(&tx, book_id)?;
+
+    let mut cuts = cuts.to_vec();
+    cuts.sort_unstable();
+    cuts.dedup();
+    let (Some(&first), Some(&last)) = (cuts.first(), cuts.last()) else {
+        return Err(CommandError::InvalidInput("No split points given".to_string()));
+    };
+    if first == 0 || last >= count {
+        return Err(CommandError::InvalidInput(format!(
+            "Split points must be segment indexes between 1 and {}",
+            count.saturating_sub(1)
+        )));
+    }
+
+    let chapter_titles: Vec<(u32, String)> = tx
         .prepare(
-            "SELECT id, book_id, idx, content, html
-             FROM segments WHERE book_id = ? ORDER BY idx ASC",
+            "SELECT start_segment_index, title FROM chapters
+             WHERE book_id = ? ORDER BY sort_order ASC",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?
+        .query_map(rusqlite::params![book_id.as_str()], |row| Ok((row.get(0)?, row.get(1)?)))
+        .context("Failed to query chapters")?
+        .collect::<Result<_, _>>()
+        .context("Failed to read chapter row")?;
 
-    let segments = stmt
+    let now = current_timestamp();
+    let ends = cuts.iter().skip(1).copied().chain(std::iter::once(count));
+    let mut part_ids = vec![book_id.clone()];
+
+    let split = (|| -> CommandResult<()> {
+        for (n, (start, end)) in cuts.iter().copied().zip(ends).enumerate() {
+            let part_id = BookId::new(uuid::Uuid::new_v4().to_string());
+            part_ids.push(part_id.clone());
+
+            let part_title = chapter_titles
+                .iter()
+                .find(|(index, _)| *index == start)
+                .map(|(_, title)| title.clone())
+                .unwrap_or_else(|| format!("{} (Part {})", title, n + 2));
+
+            // Identifiers are left behind so the parts aren't mistaken for
+            // the original when syncing or importing bundles
+            tx.execute(
+                "INSERT INTO books (id, title, author, source_format, source_path, narration_status,
+                                    created_at, updated_at, caption_prompt, source_is_reference,
+                                    language, publisher, published_date, profile_id)
+                 SELECT ?1, ?2, author, source_format, source_path, ?3, ?4, ?4, caption_prompt, 1,
+                        language, publisher, published_date, profile_id
+                 FROM books WHERE id = ?5",
+                rusqlite::params![
+                    part_id.as_str(),
+                    part_title,
+                    NarrationStatus::None.as_str(),
+                    now,
+                    book_id.as_str(),
+                ],
+            )
+            .context("Failed to insert book")?;
+
+            tx.execute(
+                "DELETE FROM markers WHERE segment_id IN (
+                     SELECT id FROM segments WHERE book_id = ?2 AND idx >= ?3 AND idx < ?4)",
+                rusqlite::params![part_id.as_str(), book_id.as_str(), start, end],
+            )
+            .context("Failed to remove markers")?;
+
+            copy_part_images(&tx, paths, book_id, &part_id, start, end)?;
+
+            tx.execute(
+                "UPDATE segments SET book_id = ?1, idx = idx - ?3
+                 WHERE book_id = ?2 AND idx >= ?3 AND idx < ?4",
+                rusqlite::params![part_id.as_str(), book_id.as_str(), start, end],
+            )
+            .context("Failed to move segments")?;
+            tx.execute(
+                "UPDATE segment_images SET book_id = ?1
+                 WHERE segment_id IN (SELECT id FROM segments WHERE book_id = ?1)",
+                rusqlite::params![part_id.as_str()],
+            )
+            .context("Failed to move images")?;
+
+            tx.execute(
+                "INSERT INTO chapters (book_id, sort_order, title, start_segment_index, level)
+                 SELECT ?1, sort_order, title, start_segment_index - ?3, level FROM chapters
+                 WHERE book_id = ?2 AND start_segment_index >= ?3 AND start_segment_index < ?4",
+                rusqlite::params![part_id.as_str(), book_id.as_str(), start, end],
+            )
+            .context("Failed to copy chapters")?;
+
+            // Voice ranges are clipped to the part
+            tx.execute(
+                "INSERT INTO segment_voices (book_id, start_index, end_index, voice_id)
+                 SELECT ?1, MAX(start_index, ?3) - ?3, MIN(end_index, ?4 - 1) - ?3, voice_id
+                 FROM segment_voices
+                 WHERE book_id = ?2 AND start_index < ?4 AND end_index >= ?3",
+                rusqlite::params![part_id.as_str(), book_id.as_str(), start, end],
+            )
+            .context("Failed to copy voice overrides")?;
+
+            // The part being read picks up the reading position, without an
+            // audio time as it has no narration
+            tx.execute(
+                "INSERT INTO progress (book_id, segment_index, audio_time, max_segment_index, updated_at)
+                 SELECT ?1, segment_index - ?3, NULL, segment_index - ?3, ?5 FROM progress
+                 WHERE book_id = ?2 AND segment_index >= ?3 AND segment_index < ?4",
+                rusqlite::params![part_id.as_str(), book_id.as_str(), start, end, now],
+            )
+            .context("Failed to copy progress")?;
+
+            update_content_hash(&tx, part_id.as_str())?;
+        }
+
+        // Trim what moved out of the first part
+        let params = rusqlite::params![book_id.as_str(), first];
+        tx.execute(
+            "DELETE FROM chapters WHERE book_id = ?1 AND start_segment_index >= ?2",
+            params,
+        )
+        .context("Failed to remove chapters")?;
+        tx.execute(
+            "DELETE FROM segment_voices WHERE book_id = ?1 AND start_index >= ?2",
+            params,
+        )
+        .context("Failed to remove voice overrides")?;
+        tx.execute(
+            "UPDATE segment_voices SET end_index = MIN(end_index, ?2 - 1) WHERE book_id = ?1",
+            params,
+        )
+        .context("Failed to clip voice overrides")?;
+        tx.execute(
+            "UPDATE progress SET
+                 segment_index = MIN(segment_index, ?2 - 1),
+                 audio_time = CASE WHEN segment_index >= ?2 THEN NULL ELSE audio_time END,
+                 max_segment_index = MIN(max_segment_index, ?2 - 1),
+                 max_audio_time = CASE WHEN max_segment_index >= ?2 THEN NULL ELSE max_audio_time END
+             WHERE book_id = ?1",
+            params,
+        )
+        .context("Failed to clip progress")?;
+
+        mark_narration_stale(&tx, book_id)?;
+        update_content_hash(&tx, book_id.as_str())?;
+        tx.commit().context("Failed to commit transaction")
+    })();
+
+    if let Err(e) = split {
+        for part_id in &part_ids[1..] {
+            let _ = std::fs::remove_dir_all(paths.images_path(part_id.as_str()));
+        }
+        return Err(e);
+    }
+
+    Ok(part_ids)
+}
+
+/// Copy the images of a book's segments with indexes in `start..end` into
+/// `part_id`'s images directory, pointing their rows at the copies.
+///
+/// Images outside the book's own directory are shared rather than copied.
+fn copy_part_images(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    book_id: &BookId,
+    part_id: &BookId,
+    start: u32,
+    end: u32,
+) -> CommandResult<()> {
+    let images: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT i.segment_id, i.source_path FROM segment_images i
+             JOIN segments s ON s.id = i.segment_id
+             WHERE s.book_id = ? AND s.idx >= ? AND s.idx < ?",
+        )
+        .context("Failed to prepare query")?
+        .query_map(rusqlite::params![book_id.as_str(), start, end], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .context("Failed to query images")?
+        .collect::<Result<_, _>>()
+        .context("Failed to read image row")?;
+
+    let book_images = paths.images_path(book_id.as_str());
+    let part_images = paths.images_path(part_id.as_str());
+    for (segment_id, stored) in images {
+        let source = paths.resolve(&stored);
+        let (Ok(name), true) = (source.strip_prefix(&book_images), source.is_file()) else {
+            continue;
+        };
+
+        let dest = part_images.join(name);
+        std::fs::create_dir_all(&part_images).context("Failed to create images directory")?;
+        std::fs::copy(&source, &dest).context("Failed to copy image")?;
+        conn.execute(
+            "UPDATE segment_images SET source_path = ? WHERE segment_id = ?",
+            rusqlite::params![paths.to_stored(&dest), segment_id],
+        )
+        .context("Failed to update image")?;
+    }
+
+    Ok(())
+}
+
+/// Split a book into several at the given segment indexes, e.g. an omnibus
+/// imported as one file.
+///
+/// The book becomes the first part. Returns every part in order.
+#[tauri::command]
+pub async fn split_book(
+    book_id: BookId,
+    at_segment_indices: Vec<u32>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<Book>> {
+    ensure_book_not_generating(&state, &book_id).await?;
+
+    let paths = state.paths();
+    let conn = state.db.connection().lock().unwrap();
+    let part_ids = split_book_at(&conn, &paths, &book_id, &at_segment_indices)?;
+
+    part_ids
+        .iter()
+        .map(|id| query_book(&conn, id).map(|book| resolve_book_paths(book, &paths)))
+        .collect()
+}
+
+/// Get the table of contents for a book.
+///
+/// Returns chapters in table-of-contents order; each points at the index of
+/// its first segment so the reader can jump to it. Empty for books whose
+/// source has no table of contents.
+#[tauri::command]
+pub async fn get_chapters(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<Chapter>> {
+    let conn = state.db.connection().lock().unwrap();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT book_id, title, start_segment_index, sort_order, level
+             FROM chapters WHERE book_id = ? ORDER BY sort_order ASC",
+        )
+        .context("Failed to prepare query")?;
+
+    let chapters = stmt
         .query_map(rusqlite::params![book_id.as_str()], |row| {
-            Ok(Segment {
-                id: SegmentId::new(row.get::<_, String>(0)?),
-                book_id: BookId::new(row.get::<_, String>(1)?),
-                index: row.get(2)?,
-                content: row.get(3)?,
-                html: row.get(4)?,
-                segment_type: SegmentType::Text, // Default to text; could be extended
-                image_data: None,                // Not stored in basic schema
+            Ok(Chapter {
+                book_id: BookId::new(row.get::<_, String>(0)?),
+                title: row.get(1)?,
+                start_segment_index: row.get(2)?,
+                order: row.get(3)?,
+                level: row.get(4)?,
             })
         })
-        .map_err(|e| format!("Failed to query segments: {}", e))?
+        .context("Failed to query chapters")?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read segment row: {}", e))?;
+        .context("Failed to read chapter row")?;
 
-    Ok(segments)
+    Ok(chapters)
 }
 
 /// Get all narration markers for a book.
@@ -114,15 +1007,22 @@ pub async fn get_segments(
 pub async fn get_markers(
     book_id: BookId,
     state: State<'_, AppState>,
-) -> Result<Vec<Marker>, String> {
+) -> CommandResult<Vec<Marker>> {
     let conn = state.db.connection().lock().unwrap();
+    query_markers(&conn, &book_id)
+}
 
+/// Load a book's markers in order by start time.
+pub(crate) fn query_markers(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) -> CommandResult<Vec<Marker>> {
     let mut stmt = conn
-        .prepare(
+        .prepare_cached(
             "SELECT segment_id, start_time, end_time
              FROM markers WHERE book_id = ? ORDER BY start_time ASC",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?;
 
     let markers = stmt
         .query_map(rusqlite::params![book_id.as_str()], |row| {
@@ -132,67 +1032,963 @@ pub async fn get_markers(
                 end: row.get(2)?,
             })
         })
-        .map_err(|e| format!("Failed to query markers: {}", e))?
+        .context("Failed to query markers")?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read marker row: {}", e))?;
+        .context("Failed to read marker row")?;
 
     Ok(markers)
 }
 
+/// Most segments `get_reading_context` returns on either side of the active one.
+const MAX_CONTEXT_RADIUS: u32 = 50;
+
+/// The segment being narrated at a point in the audio, with its neighbors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingContext {
+    pub active_segment_id: SegmentId,
+    /// The active segment and up to `radius` segments on either side, in
+    /// index order.
+    pub segments: Vec<Segment>,
+    /// Markers of those segments, in order by start time.
+    pub markers: Vec<Marker>,
+}
+
+/// Find the segment narrated at `audio_time` and the segments around it.
+///
+/// The active segment is the one whose marker starts last at or before
+/// `audio_time`, so a gap between markers keeps the previous segment active;
+/// before the first marker it is the first narrated segment.
+fn query_reading_context(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    audio_time: f64,
+    radius: u32,
+) -> CommandResult<ReadingContext> {
+    if !audio_time.is_finite() || audio_time < 0.0 {
+        return Err(CommandError::InvalidInput(format!(
+            "Invalid audio time: {}",
+            audio_time
+        )));
+    }
+
+    // The marker starting last at or before the time; failing that, the
+    // earliest one after it. Both are seeks on the (book_id, start_time) index
+    let find_marker = |sql: &str| -> rusqlite::Result<(String, u32)> {
+        conn.prepare_cached(sql)?.query_row(
+            rusqlite::params![book_id.as_str(), audio_time],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    };
+    let active = match find_marker(
+        "SELECT m.segment_id, s.idx
+         FROM markers m JOIN segments s ON s.id = m.segment_id
+         WHERE m.book_id = ?1 AND m.start_time <= ?2
+         ORDER BY m.start_time DESC
+         LIMIT 1",
+    ) {
+        Err(rusqlite::Error::QueryReturnedNoRows) => find_marker(
+            "SELECT m.segment_id, s.idx
+             FROM markers m JOIN segments s ON s.id = m.segment_id
+             WHERE m.book_id = ?1 AND m.start_time > ?2
+             ORDER BY m.start_time ASC
+             LIMIT 1",
+        ),
+        result => result,
+    };
+    let (active_segment_id, active_index) = active.map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound(format!("Book has no narration markers: {}", book_id))
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })?;
+
+    let radius = radius.min(MAX_CONTEXT_RADIUS);
+    let first = active_index.saturating_sub(radius);
+    let last = active_index.saturating_add(radius);
+
+    let segments = conn
+        .prepare_cached(&format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments s LEFT JOIN segment_images i ON i.segment_id = s.id
+             WHERE s.book_id = ?1 AND s.idx BETWEEN ?2 AND ?3 ORDER BY s.idx ASC"
+        ))
+        .context("Failed to prepare query")?
+        .query_map(rusqlite::params![book_id.as_str(), first, last], read_segment_row)
+        .context("Failed to query segments")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read segment row")?;
+
+    let markers = conn
+        .prepare_cached(
+            "SELECT m.segment_id, m.start_time, m.end_time
+             FROM markers m JOIN segments s ON s.id = m.segment_id
+             WHERE m.book_id = ?1 AND s.idx BETWEEN ?2 AND ?3
+             ORDER BY m.start_time ASC",
+        )
+        .context("Failed to prepare query")?
+        .query_map(rusqlite::params![book_id.as_str(), first, last], |row| {
+            Ok(Marker {
+                segment_id: SegmentId::new(row.get::<_, String>(0)?),
+                start: row.get(1)?,
+                end: row.get(2)?,
+            })
+        })
+        .context("Failed to query markers")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read marker row")?;
+
+    Ok(ReadingContext {
+        active_segment_id: SegmentId::new(active_segment_id),
+        segments,
+        markers,
+    })
+}
+
+/// Get the segment narrated at `audio_time` with `radius` segments on either
+/// side and their markers.
+///
+/// Combines the marker lookup and a windowed segment fetch so the "now
+/// playing" view needs one call per update during playback. `radius` is
+/// capped at 50.
+#[tauri::command]
+pub async fn get_reading_context(
+    book_id: BookId,
+    audio_time: f64,
+    radius: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<ReadingContext> {
+    let conn = state.db.connection().lock().unwrap();
+    query_reading_context(&conn, &book_id, audio_time, radius)
+}
+
+/// A segment and its narration timing, as written by `export_narration_data`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NarrationDataSegment {
+    pub id: SegmentId,
+    pub index: u32,
+    pub segment_type: SegmentType,
+    pub content: String,
+    /// Marker times in seconds, None if the segment has no marker.
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub duration: Option<f64>,
+}
+
+/// A book's segments joined with their markers, for inspecting narration
+/// timing without unpacking a bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NarrationData {
+    pub book_id: BookId,
+    pub title: String,
+    pub narration_status: NarrationStatus,
+    /// Duration of the narration audio file, None if there isn't one.
+    pub audio_duration: Option<f64>,
+    /// End of the last marker, to check against `audio_duration`.
+    pub markers_end: Option<f64>,
+    /// Segments in index order.
+    pub segments: Vec<NarrationDataSegment>,
+    /// Segments without a marker, e.g. while narration is incomplete.
+    pub missing_markers: Vec<SegmentId>,
+}
+
+impl NarrationData {
+    fn new(
+        book: &Book,
+        segments: Vec<Segment>,
+        markers: &[Marker],
+        audio_duration: Option<f64>,
+    ) -> Self {
+        let by_segment: HashMap<&str, &Marker> =
+            markers.iter().map(|m| (m.segment_id.as_str(), m)).collect();
+
+        let mut missing_markers = Vec::new();
+        let segments = segments
+            .into_iter()
+            .map(|segment| {
+                let marker = by_segment.get(segment.id.as_str());
+                if marker.is_none() {
+                    missing_markers.push(segment.id.clone());
+                }
+                NarrationDataSegment {
+                    start: marker.map(|m| m.start),
+                    end: marker.map(|m| m.end),
+                    duration: marker.map(|m| m.end - m.start),
+                    id: segment.id,
+                    index: segment.index,
+                    segment_type: segment.segment_type,
+                    content: segment.content,
+                }
+            })
+            .collect();
+
+        Self {
+            book_id: book.id.clone(),
+            title: book.title.clone(),
+            narration_status: book.narration_status,
+            audio_duration,
+            markers_end: markers.iter().map(|m| m.end).reduce(f64::max),
+            segments,
+            missing_markers,
+        }
+    }
+}
+
+/// Write a book's segments and narration markers to a JSON file.
+///
+/// A diagnostic aid: each segment is listed with its text and marker times,
+/// alongside the narration audio's duration for cross-checking. Works for
+/// books in any narration state, listing the segments that lack markers.
+#[tauri::command]
+pub async fn export_narration_data(
+    book_id: BookId,
+    output_path: String,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (book, segments, markers) = {
+        let conn = state.db.connection().lock().unwrap();
+        let book = query_book(&conn, &book_id)?;
+        (book, query_segments(&conn, book_id.as_str())?, query_markers(&conn, &book_id)?)
+    };
+
+    let audio_duration = state
+        .paths()
+        .find_narration_audio(book_id.as_str())
+        .and_then(|path| probe_audio(&path).ok())
+        .map(|info| info.duration);
+
+    let data = NarrationData::new(&book, segments, &markers, audio_duration);
+    let json = serde_json::to_string_pretty(&data).context("Failed to serialize narration data")?;
+    std::fs::write(&output_path, json).context("Failed to write narration data")?;
+
+    log::info!("Exported narration data for {} to: {}", book_id, output_path);
+
+    Ok(())
+}
+
+/// Read one segment's narration audio as a standalone WAV.
+///
+/// The segment's marker times are cut from the book's WAV narration; books
+/// narrated in a compressed codec have no slice to offer.
+pub(crate) fn read_segment_audio(
+    db: &Database,
+    paths: &AppPaths,
+    book_id: &BookId,
+    segment_id: &SegmentId,
+) -> CommandResult<Vec<u8>> {
+    let (start, end): (f64, f64) = {
+        let conn = db.connection().lock().unwrap();
+        conn.query_row(
+            "SELECT start_time, end_time FROM markers WHERE book_id = ? AND segment_id = ?",
+            rusqlite::params![book_id.as_str(), segment_id.as_str()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound(format!("No narration for segment {}", segment_id))
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
+        })?
+    };
+
+    let wav_path = paths.narration_audio_path(book_id.as_str(), NarrationCodec::Wav);
+    if !wav_path.exists() {
+        return Err(match paths.find_narration_audio(book_id.as_str()) {
+            Some(_) => CommandError::InvalidInput(
+                "Segment audio is only available for WAV narration".to_string(),
+            ),
+            None => CommandError::NotFound("No narration audio for this book".to_string()),
+        });
+    }
+
+    read_wav_slice(&wav_path, start, end).context("Failed to read segment audio")
+}
+
+/// Get a single segment's narration audio as a standalone WAV, for
+/// re-listening to one paragraph without loading the whole narration.
+#[tauri::command]
+pub async fn get_segment_audio(
+    book_id: BookId,
+    segment_id: SegmentId,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<u8>> {
+    read_segment_audio(&state.db, &state.paths(), &book_id, &segment_id)
+}
+
 /// Get reading progress for a book.
 ///
-/// Returns None if no progress has been saved yet.
+/// Returns None if no progress has been saved yet. Includes both the current
+/// position and the furthest position ever reached.
 #[tauri::command]
 pub async fn get_progress(
     book_id: BookId,
     state: State<'_, AppState>,
-) -> Result<Option<Progress>, String> {
+) -> CommandResult<Option<Progress>> {
     let conn = state.db.connection().lock().unwrap();
+    query_progress(&conn, &book_id)
+}
 
+/// Read a book's progress row, if one has been saved.
+pub(crate) fn query_progress(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) -> CommandResult<Option<Progress>> {
     let mut stmt = conn
-        .prepare(
-            "SELECT book_id, segment_index, audio_time, updated_at
+        .prepare_cached(
+            "SELECT book_id, segment_index, audio_time, max_segment_index, max_audio_time, updated_at
              FROM progress WHERE book_id = ?",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?;
 
     let result = stmt.query_row(rusqlite::params![book_id.as_str()], |row| {
         Ok(Progress {
             book_id: BookId::new(row.get::<_, String>(0)?),
             segment_index: row.get(1)?,
             audio_time: row.get(2)?,
-            updated_at: row.get(3)?,
+            max_segment_index: row.get(3)?,
+            max_audio_time: row.get(4)?,
+            updated_at: row.get(5)?,
         })
     });
 
     match result {
         Ok(progress) => Ok(Some(progress)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(format!("Database error: {}", e)),
+        Err(e) => Err(CommandError::Database(format!("Database error: {}", e))),
     }
 }
 
+/// How close to the end of the narration counts as finishing it, in seconds.
+const FINISHED_AUDIO_TOLERANCE_SECONDS: f64 = 5.0;
+
+/// Whether a position is at the end of a book: on its last segment, or
+/// within [`FINISHED_AUDIO_TOLERANCE_SECONDS`] of the end of its narration.
+fn is_finished(
+    segment_index: u32,
+    segment_count: u32,
+    audio_time: Option<f64>,
+    duration: Option<f64>,
+) -> bool {
+    let last_segment = segment_count > 0 && segment_index + 1 >= segment_count;
+    let end_of_audio = match (audio_time, duration) {
+        (Some(time), Some(duration)) if duration > 0.0 => {
+            time >= duration - FINISHED_AUDIO_TOLERANCE_SECONDS
+        }
+        _ => false,
+    };
+    last_segment || end_of_audio
+}
+
+/// Set a book's `finished_at` the first time progress reaches its end.
+///
+/// A book that was already finished keeps its original time. Returns whether
+/// the book was finished by this position.
+pub(crate) fn mark_finished(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    segment_index: u32,
+    audio_time: Option<f64>,
+    now: i64,
+) -> CommandResult<bool> {
+    let row = conn.query_row(
+        "SELECT finished_at, duration, (SELECT COUNT(*) FROM segments WHERE book_id = books.id)
+         FROM books WHERE id = ?",
+        rusqlite::params![book_id.as_str()],
+        |row| Ok((row.get::<_, Option<i64>>(0)?, row.get(1)?, row.get(2)?)),
+    );
+    let (finished_at, duration, segment_count) = match row {
+        Ok(row) => row,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+        Err(e) => return Err(CommandError::Database(format!("Database error: {}", e))),
+    };
+
+    if finished_at.is_some() || !is_finished(segment_index, segment_count, audio_time, duration) {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE books SET finished_at = ? WHERE id = ? AND finished_at IS NULL",
+        rusqlite::params![now, book_id.as_str()],
+    )
+    .context("Failed to mark book finished")?;
+    Ok(true)
+}
+
+/// Emit `book_finished` for a book [`mark_finished`] just finished.
+pub(crate) fn emit_book_finished(app: &AppHandle, book_id: &BookId, finished_at: i64) {
+    log::info!("book={}: finished", book_id);
+    let _ = app.emit(
+        "book_finished",
+        serde_json::json!({ "bookId": book_id, "finishedAt": finished_at }),
+    );
+}
+
 /// Save reading progress for a book.
 ///
 /// Creates or updates the progress record. The progress includes:
 /// - segment_index: Current segment being read
 /// - audio_time: Current position in narration (if playing)
+///
+/// The current position moves freely; the furthest position only ever
+/// moves forward. The first time the position reaches the end of the book
+/// its `finished_at` is set and `book_finished` is emitted.
 #[tauri::command]
 pub async fn save_progress(
     book_id: BookId,
     segment_index: u32,
     audio_time: Option<f64>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let conn = state.db.connection().lock().unwrap();
     let now = current_timestamp();
 
+    // Previous position, used to attribute listening time
+    let previous: Option<(Option<f64>, i64)> = match conn.query_row(
+        "SELECT audio_time, updated_at FROM progress WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(row) => Some(row),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(CommandError::Database(format!("Database error: {}", e))),
+    };
+
     conn.execute(
-        "INSERT OR REPLACE INTO progress (book_id, segment_index, audio_time, updated_at)
-         VALUES (?, ?, ?, ?)",
+        "INSERT INTO progress (book_id, segment_index, audio_time, max_segment_index, max_audio_time, updated_at)
+         VALUES (?1, ?2, ?3, ?2, ?3, ?4)
+         ON CONFLICT(book_id) DO UPDATE SET
+             segment_index = excluded.segment_index,
+             audio_time = excluded.audio_time,
+             max_segment_index = MAX(max_segment_index, excluded.segment_index),
+             max_audio_time = MAX(COALESCE(max_audio_time, excluded.audio_time),
+                                  COALESCE(excluded.audio_time, max_audio_time)),
+             updated_at = excluded.updated_at",
         rusqlite::params![book_id.as_str(), segment_index, audio_time, now],
     )
-    .map_err(|e| format!("Failed to save progress: {}", e))?;
+    .context("Failed to save progress")?;
+
+    if let Some((previous_audio_time, previous_updated_at)) = previous {
+        if let Some(delta) = listened_delta(previous_audio_time, previous_updated_at, audio_time, now) {
+            record_listening(&conn, &book_id, previous_updated_at, now, delta)?;
+        }
+    }
+
+    if mark_finished(&conn, &book_id, segment_index, audio_time, now)? {
+        emit_book_finished(&app, &book_id, now);
+    }
+
+    super::sync::broadcast_progress(&state.sync_updates, &conn, &book_id);
 
     Ok(())
 }
+
+/// Compute how much narration was listened to between two progress saves.
+///
+/// Returns None when audio moved backward or jumped further than playback
+/// could have covered in the elapsed wall-clock time (i.e. a seek).
+fn listened_delta(
+    previous_audio_time: Option<f64>,
+    previous_updated_at: i64,
+    audio_time: Option<f64>,
+    now: i64,
+) -> Option<f64> {
+    let delta = audio_time? - previous_audio_time?;
+    let elapsed = (now - previous_updated_at).max(0) as f64;
+
+    // Allow a little slack since timestamps have one-second resolution
+    if delta <= 0.0 || delta > elapsed * MAX_PLAYBACK_RATE + 2.0 {
+        return None;
+    }
+
+    Some(delta)
+}
+
+/// Add listened time to the book's current session, starting a new one if
+/// the last session ended too long ago.
+fn record_listening(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    started_at: i64,
+    now: i64,
+    seconds: f64,
+) -> CommandResult<()> {
+    let extended = conn
+        .execute(
+            "UPDATE reading_sessions SET ended_at = ?1, seconds_listened = seconds_listened + ?2
+             WHERE id = (
+                 SELECT id FROM reading_sessions
+                 WHERE book_id = ?3 AND ended_at >= ?4
+                 ORDER BY ended_at DESC LIMIT 1
+             )",
+            rusqlite::params![now, seconds, book_id.as_str(), now - SESSION_GAP_SECS],
+        )
+        .context("Failed to update reading session")?;
+
+    if extended == 0 {
+        conn.execute(
+            "INSERT INTO reading_sessions (book_id, started_at, ended_at, seconds_listened)
+             VALUES (?, ?, ?, ?)",
+            rusqlite::params![book_id.as_str(), started_at, now, seconds],
+        )
+        .context("Failed to record reading session")?;
+    }
+
+    Ok(())
+}
+
+/// Get aggregated reading statistics.
+///
+/// Includes total and per-book listening time, the number of completed books,
+/// and the current daily listening streak.
+#[tauri::command]
+pub async fn get_reading_stats(state: State<'_, AppState>) -> CommandResult<ReadingStats> {
+    let conn = state.db.connection().lock().unwrap();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT b.id, b.title, SUM(r.seconds_listened) AS listened
+             FROM reading_sessions r JOIN books b ON b.id = r.book_id
+             GROUP BY b.id ORDER BY listened DESC",
+        )
+        .context("Failed to prepare query")?;
+
+    let books = stmt
+        .query_map([], |row| {
+            Ok(BookReadingTime {
+                book_id: BookId::new(row.get::<_, String>(0)?),
+                title: row.get(1)?,
+                seconds_listened: row.get(2)?,
+            })
+        })
+        .context("Failed to query reading sessions")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read session row")?;
+
+    let total_seconds_listened: f64 = books.iter().map(|b| b.seconds_listened).sum();
+
+    let books_completed: u32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM progress p
+             WHERE p.max_segment_index >= (SELECT MAX(idx) FROM segments s WHERE s.book_id = p.book_id)",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to count completed books")?;
+
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT ended_at / 86400 FROM reading_sessions")
+        .context("Failed to prepare query")?;
+
+    let days = stmt
+        .query_map([], |row| row.get::<_, i64>(0))
+        .context("Failed to query listening days")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read listening day")?;
+
+    Ok(ReadingStats {
+        total_seconds_listened,
+        books,
+        books_completed,
+        current_streak_days: compute_streak(days, current_timestamp() / 86400),
+    })
+}
+
+/// Count consecutive listening days ending today or yesterday.
+fn compute_streak(mut days: Vec<i64>, today: i64) -> u32 {
+    days.sort_unstable();
+    days.dedup();
+
+    // The streak is still alive if the last listening day was today or yesterday
+    let mut expected = match days.last() {
+        Some(&last) if last == today || last == today - 1 => last,
+        _ => return 0,
+    };
+
+    let mut streak = 0;
+    for &day in days.iter().rev() {
+        if day != expected {
+            break;
+        }
+        streak += 1;
+        expected -= 1;
+    }
+
+    streak
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("Hello world", 5), Some(("Hello", "world")));
+        assert_eq!(split_text("Héllo wörld", 6), Some(("Héllo", "wörld")));
+        assert_eq!(split_text("Hello", 0), None);
+        assert_eq!(split_text("Hello ", 5), None);
+        assert_eq!(split_text("Hello", 9), None);
+    }
+
+    #[test]
+    fn test_segment_edits_preserve_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 'ready', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content, segment_type)
+             VALUES ('a', 'book', 0, 'One', 'text'), ('b', 'book', 1, 'Two Three', 'quote'), ('c', 'book', 2, 'Four', 'h2');
+             INSERT INTO chapters (book_id, sort_order, title, start_segment_index)
+             VALUES ('book', 0, 'End', 2);
+             INSERT INTO markers (id, book_id, segment_id, start_time, end_time)
+             VALUES ('m1', 'book', 'a', 0.0, 1.0), ('m2', 'book', 'b', 1.0, 2.0);
+             INSERT INTO progress (book_id, segment_index, max_segment_index, updated_at)
+             VALUES ('book', 2, 2, 0);",
+        )
+        .unwrap();
+
+        let contents = || -> Vec<String> {
+            conn.prepare("SELECT content FROM segments WHERE book_id = 'book' ORDER BY idx")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let single = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+
+        let hash = || super::super::bundle::query_content_hash(&conn, "book").unwrap();
+        let original_hash = hash();
+        edit_segment(&conn, &SegmentId::new("a"), "One.", None).unwrap();
+        let status: String = conn
+            .query_row("SELECT narration_status FROM books WHERE id = 'book'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(status, "stale");
+        assert_ne!(hash(), original_hash);
+
+        // Leaving a segment out of narration also makes the audio stale,
+        // including partial narration
+        conn.execute("UPDATE books SET narration_status = 'partial'", []).unwrap();
+        set_narrate(&conn, &SegmentId::new("b"), false).unwrap();
+        assert!(!query_segment(&conn, &SegmentId::new("b")).unwrap().narrate);
+        assert_eq!(single("SELECT narration_status = 'stale' FROM books"), 1);
+
+        let new_id = split_segment_at(&conn, &SegmentId::new("b"), 3).unwrap();
+        assert_eq!(contents(), ["One.", "Two", "Three", "Four"]);
+        let second = query_segment(&conn, &new_id).unwrap();
+        assert_eq!(second.index, 2);
+        assert_eq!(second.segment_type, SegmentType::Quote);
+        assert!(!second.narrate);
+        assert_eq!(
+            query_segments(&conn, "book").unwrap()[3].segment_type,
+            SegmentType::Heading { level: 2 }
+        );
+        assert_eq!(single("SELECT start_segment_index FROM chapters"), 3);
+        assert_eq!(single("SELECT segment_index FROM progress"), 3);
+
+        merge_segment_with_next(&conn, &SegmentId::new("a")).unwrap();
+        assert_eq!(contents(), ["One.\nTwo", "Three", "Four"]);
+        assert_eq!(single("SELECT start_segment_index FROM chapters"), 2);
+        assert_eq!(single("SELECT segment_index FROM progress"), 2);
+        let end: f64 = conn
+            .query_row("SELECT end_time FROM markers WHERE segment_id = 'a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(end, 2.0);
+
+        assert!(matches!(
+            merge_segment_with_next(&conn, &SegmentId::new("c")),
+            Err(CommandError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_reindex_closes_gaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content)
+             VALUES ('a', 'book', 0, 'One'), ('b', 'book', 3, 'Two'), ('c', 'book', 4, 'Three'),
+                    ('d', 'book', 9, 'Four');
+             INSERT INTO chapters (book_id, sort_order, title, start_segment_index)
+             VALUES ('book', 0, 'Middle', 4), ('book', 1, 'Gap', 6);
+             INSERT INTO progress (book_id, segment_index, max_segment_index, updated_at)
+             VALUES ('book', 3, 9, 0);",
+        )
+        .unwrap();
+
+        assert_eq!(reindex_book_segments(&conn, &BookId::new("book")).unwrap(), 4);
+
+        let indexes: Vec<(String, u32)> = conn
+            .prepare("SELECT id, idx FROM segments WHERE book_id = 'book' ORDER BY idx")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<(String, u32)> =
+            vec![("a".into(), 0), ("b".into(), 1), ("c".into(), 2), ("d".into(), 3)];
+        assert_eq!(indexes, expected);
+
+        let starts: Vec<u32> = conn
+            .prepare("SELECT start_segment_index FROM chapters ORDER BY sort_order")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(starts, [2, 3]);
+
+        let progress = query_progress(&conn, &BookId::new("book")).unwrap().unwrap();
+        assert_eq!((progress.segment_index, progress.max_segment_index), (1, 3));
+
+        // Already contiguous: nothing changes
+        assert_eq!(reindex_book_segments(&conn, &BookId::new("book")).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_split_book() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        let image = paths.images_path("book").join("e.png");
+        std::fs::create_dir_all(image.parent().unwrap()).unwrap();
+        std::fs::write(&image, b"png").unwrap();
+
+        conn.execute_batch(&format!(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, identifier,
+                                created_at, updated_at)
+             VALUES ('book', 'Omnibus', 'epub', 'sources/book.epub', 'ready', 'urn:isbn:1', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content, segment_type) VALUES
+                 ('a', 'book', 0, 'One', 'text'), ('b', 'book', 1, 'Two', 'text'),
+                 ('c', 'book', 2, 'Three', 'text'), ('d', 'book', 3, 'Four', 'text'),
+                 ('e', 'book', 4, '[image]', 'image'), ('f', 'book', 5, 'Six', 'text');
+             INSERT INTO segment_images (segment_id, book_id, source_path)
+             VALUES ('e', 'book', '{}');
+             INSERT INTO chapters (book_id, sort_order, title, start_segment_index) VALUES
+                 ('book', 0, 'Book One', 0), ('book', 1, 'Book Two', 2), ('book', 2, 'Interlude', 3);
+             INSERT INTO markers (id, book_id, segment_id, start_time, end_time) VALUES
+                 ('m1', 'book', 'a', 0.0, 1.0), ('m3', 'book', 'c', 2.0, 3.0);
+             INSERT INTO voices (id, name, engine) VALUES ('v', 'Voice', 'chatterbox');
+             INSERT INTO segment_voices (book_id, start_index, end_index, voice_id)
+             VALUES ('book', 1, 2, 'v');
+             INSERT INTO progress (book_id, segment_index, audio_time, max_segment_index, updated_at)
+             VALUES ('book', 3, 3.5, 3, 0);",
+            paths.to_stored(&image)
+        ))
+        .unwrap();
+
+        let book_id = BookId::new("book");
+        assert!(split_book_at(&conn, &paths, &book_id, &[]).is_err());
+        assert!(split_book_at(&conn, &paths, &book_id, &[0]).is_err());
+        assert!(split_book_at(&conn, &paths, &book_id, &[6]).is_err());
+
+        let parts = split_book_at(&conn, &paths, &book_id, &[4, 2]).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], book_id);
+
+        let first = query_book(&conn, &parts[0]).unwrap();
+        let second = query_book(&conn, &parts[1]).unwrap();
+        let third = query_book(&conn, &parts[2]).unwrap();
+        assert_eq!(first.narration_status, NarrationStatus::Stale);
+        assert_eq!(second.title, "Book Two");
+        assert_eq!(third.title, "Omnibus (Part 3)");
+        assert_eq!(second.narration_status, NarrationStatus::None);
+        assert_eq!(second.metadata.identifier, None);
+
+        let contents = |id: &BookId| -> Vec<(String, u32)> {
+            conn.prepare("SELECT content, idx FROM segments WHERE book_id = ? ORDER BY idx")
+                .unwrap()
+                .query_map([id.as_str()], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(contents(&parts[0]), [("One".to_string(), 0), ("Two".to_string(), 1)]);
+        assert_eq!(contents(&parts[1]), [("Three".to_string(), 0), ("Four".to_string(), 1)]);
+        assert_eq!(contents(&parts[2]), [("[image]".to_string(), 0), ("Six".to_string(), 1)]);
+
+        let single = |sql: &str, id: &BookId| -> i64 {
+            conn.query_row(sql, [id.as_str()], |row| row.get(0)).unwrap()
+        };
+        // Only the first part keeps narration markers
+        assert_eq!(single("SELECT COUNT(*) FROM markers WHERE book_id = ?", &book_id), 1);
+        let interlude = "SELECT start_segment_index FROM chapters WHERE book_id = ? AND title = 'Interlude'";
+        assert_eq!(single(interlude, &parts[1]), 1);
+        assert_eq!(single("SELECT COUNT(*) FROM chapters WHERE book_id = ?", &parts[0]), 1);
+
+        // The voice range over segments 1-2 is clipped to each side of the cut
+        assert_eq!(single("SELECT end_index FROM segment_voices WHERE book_id = ?", &parts[0]), 1);
+        assert_eq!(single("SELECT end_index FROM segment_voices WHERE book_id = ?", &parts[1]), 0);
+
+        // The reading position moves to the part it was in
+        let progress = query_progress(&conn, &parts[1]).unwrap().unwrap();
+        assert_eq!((progress.segment_index, progress.audio_time), (1, None));
+        let progress = query_progress(&conn, &parts[0]).unwrap().unwrap();
+        assert_eq!((progress.segment_index, progress.audio_time), (1, None));
+
+        // The image is copied so deleting the original leaves it intact
+        let stored: String = conn
+            .query_row("SELECT source_path FROM segment_images WHERE segment_id = 'e'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(paths.resolve(&stored).starts_with(paths.images_path(parts[2].as_str())));
+        assert!(paths.resolve(&stored).is_file());
+    }
+
+    #[test]
+    fn test_narration_data_joins_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 'generating', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content)
+             VALUES ('a', 'book', 0, 'One'), ('b', 'book', 1, 'Two'), ('c', 'book', 2, 'Three');
+             INSERT INTO markers (id, book_id, segment_id, start_time, end_time)
+             VALUES ('m2', 'book', 'b', 1.5, 4.0), ('m1', 'book', 'a', 0.0, 1.5);",
+        )
+        .unwrap();
+
+        let book_id = BookId::new("book");
+        let data = NarrationData::new(
+            &query_book(&conn, &book_id).unwrap(),
+            query_segments(&conn, "book").unwrap(),
+            &query_markers(&conn, &book_id).unwrap(),
+            Some(4.0),
+        );
+
+        assert_eq!(data.narration_status, NarrationStatus::Generating);
+        let timing: Vec<(u32, &str, Option<f64>)> = data
+            .segments
+            .iter()
+            .map(|s| (s.index, s.content.as_str(), s.duration))
+            .collect();
+        assert_eq!(timing, [(0, "One", Some(1.5)), (1, "Two", Some(2.5)), (2, "Three", None)]);
+        assert_eq!(data.segments[1].start, Some(1.5));
+        assert_eq!(data.markers_end, Some(4.0));
+        assert_eq!(data.missing_markers, [SegmentId::new("c")]);
+    }
+
+    #[test]
+    fn test_reading_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 'ready', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content, segment_type)
+             VALUES ('a', 'book', 0, 'One', 'text'), ('b', 'book', 1, 'Two', 'text'),
+                    ('c', 'book', 2, 'Three', 'text'), ('d', 'book', 3, 'Four', 'text');
+             INSERT INTO markers (id, book_id, segment_id, start_time, end_time)
+             VALUES ('m1', 'book', 'a', 0.5, 1.0), ('m2', 'book', 'b', 1.0, 2.0),
+                    ('m3', 'book', 'c', 2.5, 3.0), ('m4', 'book', 'd', 3.0, 4.0);",
+        )
+        .unwrap();
+        let book = BookId::new("book");
+        let ids = |context: &ReadingContext| -> Vec<String> {
+            context.segments.iter().map(|s| s.id.as_str().to_string()).collect()
+        };
+
+        let context = query_reading_context(&conn, &book, 1.5, 1).unwrap();
+        assert_eq!(context.active_segment_id.as_str(), "b");
+        assert_eq!(ids(&context), ["a", "b", "c"]);
+        assert_eq!(context.markers.len(), 3);
+
+        // In the gap after a marker, and before the first marker
+        let context = query_reading_context(&conn, &book, 2.2, 0).unwrap();
+        assert_eq!(context.active_segment_id.as_str(), "b");
+        assert_eq!(ids(&context), ["b"]);
+        let context = query_reading_context(&conn, &book, 0.0, 1).unwrap();
+        assert_eq!(context.active_segment_id.as_str(), "a");
+        assert_eq!(ids(&context), ["a", "b"]);
+
+        // Past the end, the window is clipped to the book
+        let context = query_reading_context(&conn, &book, 99.0, 2).unwrap();
+        assert_eq!(context.active_segment_id.as_str(), "d");
+        assert_eq!(ids(&context), ["b", "c", "d"]);
+
+        assert!(matches!(
+            query_reading_context(&conn, &book, f64::NAN, 1),
+            Err(CommandError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            query_reading_context(&conn, &BookId::new("other"), 1.0, 1),
+            Err(CommandError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_listened_delta() {
+        // Normal playback: 10s of audio over 10s wall-clock
+        assert_eq!(listened_delta(Some(100.0), 1000, Some(110.0), 1010), Some(10.0));
+        // Backward scrub
+        assert_eq!(listened_delta(Some(100.0), 1000, Some(50.0), 1010), None);
+        // Forward seek far beyond what playback could cover
+        assert_eq!(listened_delta(Some(100.0), 1000, Some(900.0), 1010), None);
+        // No audio position on either side
+        assert_eq!(listened_delta(None, 1000, Some(10.0), 1010), None);
+        assert_eq!(listened_delta(Some(10.0), 1000, None, 1010), None);
+    }
+
+    #[test]
+    fn test_compute_streak() {
+        assert_eq!(compute_streak(vec![], 100), 0);
+        assert_eq!(compute_streak(vec![98, 99, 100], 100), 3);
+        assert_eq!(compute_streak(vec![97, 98, 99], 100), 3);
+        assert_eq!(compute_streak(vec![95, 99, 100, 100], 100), 2);
+        assert_eq!(compute_streak(vec![90, 91], 100), 0);
+    }
+
+    #[test]
+    fn test_mark_finished() {
+        assert!(is_finished(2, 3, None, None));
+        assert!(!is_finished(1, 3, None, None));
+        assert!(is_finished(0, 3, Some(97.0), Some(100.0)));
+        assert!(!is_finished(0, 3, Some(90.0), Some(100.0)));
+        assert!(!is_finished(0, 0, None, None));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at, duration)
+                 VALUES ('book', 'Book', 'txt', 'book.txt', 0, 0, 100.0);
+             INSERT INTO segments (id, book_id, idx, content) VALUES
+                 ('a', 'book', 0, 'One.'), ('b', 'book', 1, 'Two.'), ('c', 'book', 2, 'Three.');",
+        )
+        .unwrap();
+        let book_id = BookId::new("book");
+        let finished_at = || -> Option<i64> {
+            conn.query_row("SELECT finished_at FROM books WHERE id = 'book'", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        assert!(!mark_finished(&conn, &book_id, 1, Some(50.0), 10).unwrap());
+        assert_eq!(finished_at(), None);
+
+        assert!(mark_finished(&conn, &book_id, 2, None, 20).unwrap());
+        assert_eq!(finished_at(), Some(20));
+
+        // Reaching the end again keeps the first time
+        assert!(!mark_finished(&conn, &book_id, 2, Some(99.0), 30).unwrap());
+        assert_eq!(finished_at(), Some(20));
+
+        assert!(!mark_finished(&conn, &BookId::new("missing"), 2, None, 40).unwrap());
+    }
+}
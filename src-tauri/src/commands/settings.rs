@@ -2,17 +2,32 @@
 //!
 //! Commands for managing application settings stored as key-value pairs.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 
+use super::error::{CommandError, CommandResult, ResultExt};
 use crate::models::VoiceId;
+use crate::services::tts::{
+    ChatterboxParams, CHATTERBOX_URL as DEFAULT_TTS_URL, DEFAULT_CFG, DEFAULT_EXAG, DEFAULT_TEMP,
+};
+use crate::services::vision::{DEFAULT_CAPTION_PROMPT, DEFAULT_ENDPOINT as DEFAULT_VISION_URL};
+use crate::storage::{
+    init_database, list_files, relativize_book_paths, write_data_location, AppPaths, Database,
+    DATA_LOCATION_FILE, DEFAULT_PROFILE_ID,
+};
 use crate::AppState;
 
 /// All application settings.
+///
+/// Missing fields deserialize to their defaults so older frontends can still
+/// call `update_settings` after new settings are added.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Settings {
     /// UI theme: "light", "dark", or "system".
     pub theme: String,
@@ -32,6 +47,74 @@ pub struct Settings {
     pub auto_play: bool,
     /// Local sync server port.
     pub sync_port: u16,
+    /// Base URL of the Chatterbox TTS server.
+    pub chatterbox_url: String,
+    /// Base URL of the Qwen2.5-VL vision server.
+    pub vision_url: String,
+    /// Timeout for each connection attempt to a sync server, in milliseconds.
+    pub sync_connect_timeout_ms: u64,
+    /// How long to browse for sync servers on the network, in milliseconds.
+    pub sync_discovery_timeout_ms: u64,
+    /// Prompt used to caption images during narration.
+    pub caption_prompt: String,
+    /// Prompt for full-page illustrations (empty uses `caption_prompt`).
+    pub caption_prompt_full_page: String,
+    /// Prompt for small inline images (empty uses `caption_prompt`).
+    pub caption_prompt_inline: String,
+    /// How image segments are narrated: "skip", "altTextOnly", or "caption".
+    pub image_narration_mode: String,
+    /// Template spoken in place of a heading's text, with `{level_name}`
+    /// ("Part", "Chapter" or "Section") and `{text}` placeholders; empty
+    /// narrates headings as bare text.
+    pub heading_announcement: String,
+    /// Sample rate narration audio is converted to, in Hz.
+    pub narration_sample_rate: u32,
+    /// Channel count narration audio is converted to (1 or 2).
+    pub narration_channels: u16,
+    /// Bits per sample of the finished narration's PCM audio (16 or 24).
+    pub narration_bit_depth: u16,
+    /// Peak-normalize each narrated segment to even out loudness.
+    pub normalize_narration: bool,
+    /// Keep a book's per-segment audio cache once its narration is ready, so
+    /// single segments can be regenerated and markers rebuilt without
+    /// re-synthesizing the book.
+    pub keep_narration_cache: bool,
+    /// Audio format narration is saved in: "wav", "mp3", or "opus".
+    pub narration_codec: String,
+    /// Characters of text synthesized per second of wall-clock time, calibrated from past narration jobs.
+    pub synthesis_chars_per_second: f64,
+    /// Characters of text spoken per second of narration audio, calibrated from past narration jobs.
+    pub speech_chars_per_second: f64,
+    /// Minimum level written to the log; read at startup.
+    pub log_level: String,
+    /// Serve sync over HTTPS with a self-signed certificate; turn off to debug with plain HTTP.
+    pub sync_tls: bool,
+    /// Trim leading and trailing silence from each narrated segment; off by
+    /// default so existing narration sounds the same.
+    pub trim_silence: bool,
+    /// Amplitude, as a fraction of full scale, below which audio counts as silence when trimming.
+    pub silence_threshold: f64,
+    /// Silence inserted after each narrated segment, in milliseconds; 0, the
+    /// default, plays segments back to back as synthesized.
+    pub segment_gap_ms: u32,
+    /// Chatterbox exaggeration for books without their own.
+    pub exag_default: f64,
+    /// Chatterbox CFG weight for books without their own.
+    pub cfg_default: f64,
+    /// Chatterbox temperature for books without their own.
+    pub temp_default: f64,
+    /// Comma-separated origins browsers may call the sync server from, or
+    /// "*" for any. An origin without a port matches any port.
+    pub sync_cors_origins: String,
+    /// Minutes without a request after which the sync server stops itself;
+    /// 0 keeps it running until stopped.
+    pub sync_server_idle_timeout_mins: u64,
+    /// Start the sync server when the app launches. It stops once idle like
+    /// one started by hand; set `sync_server_idle_timeout_mins` to 0 to keep
+    /// it up.
+    pub auto_start_sync_server: bool,
+    /// Largest bundle accepted when downloading books from a sync server, in megabytes.
+    pub sync_max_bundle_mb: u64,
 }
 
 impl Default for Settings {
@@ -46,12 +129,69 @@ impl Default for Settings {
             default_voice: None,
             auto_play: false,
             sync_port: 42069,
+            chatterbox_url: DEFAULT_TTS_URL.to_string(),
+            vision_url: DEFAULT_VISION_URL.to_string(),
+            sync_connect_timeout_ms: 10000,
+            sync_discovery_timeout_ms: 3000,
+            caption_prompt: DEFAULT_CAPTION_PROMPT.to_string(),
+            caption_prompt_full_page: String::new(),
+            caption_prompt_inline: String::new(),
+            image_narration_mode: "caption".to_string(),
+            heading_announcement: String::new(),
+            narration_sample_rate: 24000,
+            narration_channels: 1,
+            narration_bit_depth: 16,
+            normalize_narration: false,
+            keep_narration_cache: false,
+            narration_codec: "wav".to_string(),
+            synthesis_chars_per_second: 20.0,
+            speech_chars_per_second: 15.0,
+            log_level: "warn".to_string(),
+            sync_tls: true,
+            trim_silence: false,
+            silence_threshold: 0.01,
+            segment_gap_ms: 0,
+            exag_default: DEFAULT_EXAG,
+            cfg_default: DEFAULT_CFG,
+            temp_default: DEFAULT_TEMP,
+            sync_cors_origins: DEFAULT_SYNC_CORS_ORIGINS.to_string(),
+            sync_server_idle_timeout_mins: 30,
+            auto_start_sync_server: false,
+            sync_max_bundle_mb: 2048,
         }
     }
 }
 
+/// Origins allowed to call the sync server by default: the app's own webview
+/// on each platform, plus loopback on any port.
+const DEFAULT_SYNC_CORS_ORIGINS: &str = "tauri://localhost, http://tauri.localhost, https://tauri.localhost, \
+     http://localhost, http://127.0.0.1, http://[::1]";
+
+/// Expected type and range of a stored setting value.
+#[derive(Debug, Clone, Copy)]
+enum SettingKind {
+    /// One of a fixed set of strings.
+    Choice(&'static [&'static str]),
+    /// Integer within an inclusive range.
+    Integer { min: i64, max: i64 },
+    /// Finite float within an inclusive range.
+    Float { min: f64, max: f64 },
+    /// "true" or "false".
+    Bool,
+    /// Hex color ("#rgb" or "#rrggbb").
+    Color,
+    /// HTTP(S) base URL.
+    Url,
+    /// Comma-separated web origins ("scheme://host[:port]"), or "*".
+    Origins,
+    /// Free-form text (may be empty).
+    Text,
+}
+
 /// Setting keys used in the database.
 mod keys {
+    use super::SettingKind;
+
     pub const THEME: &str = "theme";
     pub const FONT_SIZE: &str = "fontSize";
     pub const FONT_FAMILY: &str = "fontFamily";
@@ -63,6 +203,214 @@ mod keys {
     pub const SYNC_PORT: &str = "syncPort";
     pub const AUTO_PROCESS: &str = "autoProcess";
     pub const SHOW_IMPORT_MODAL: &str = "showImportModal";
+    pub const CHATTERBOX_URL: &str = "chatterboxUrl";
+    pub const VISION_URL: &str = "visionUrl";
+    pub const SYNC_CONNECT_TIMEOUT_MS: &str = "syncConnectTimeoutMs";
+    pub const SYNC_DISCOVERY_TIMEOUT_MS: &str = "syncDiscoveryTimeoutMs";
+    pub const CAPTION_PROMPT: &str = "captionPrompt";
+    pub const CAPTION_PROMPT_FULL_PAGE: &str = "captionPromptFullPage";
+    pub const CAPTION_PROMPT_INLINE: &str = "captionPromptInline";
+    pub const IMAGE_NARRATION_MODE: &str = "imageNarrationMode";
+    pub const HEADING_ANNOUNCEMENT: &str = "headingAnnouncement";
+    pub const NARRATION_SAMPLE_RATE: &str = "narrationSampleRate";
+    pub const NARRATION_CHANNELS: &str = "narrationChannels";
+    pub const NARRATION_BIT_DEPTH: &str = "narrationBitDepth";
+    pub const NORMALIZE_NARRATION: &str = "normalizeNarration";
+    pub const KEEP_NARRATION_CACHE: &str = "keepNarrationCache";
+    pub const MERGE_SHORT_SEGMENTS: &str = "mergeShortSegments";
+    pub const MERGE_SEGMENT_MIN_CHARS: &str = "mergeSegmentMinChars";
+    pub const SKIP_BOILERPLATE: &str = "skipBoilerplate";
+    pub const IMPORT_MODE: &str = "importMode";
+    pub const NARRATION_CODEC: &str = "narrationCodec";
+    pub const SYNTHESIS_CHARS_PER_SECOND: &str = "synthesisCharsPerSecond";
+    pub const SPEECH_CHARS_PER_SECOND: &str = "speechCharsPerSecond";
+    pub const LOG_LEVEL: &str = "logLevel";
+    pub const SYNC_TLS: &str = "syncTls";
+    pub const TRIM_SILENCE: &str = "trimSilence";
+    pub const SILENCE_THRESHOLD: &str = "silenceThreshold";
+    pub const SEGMENT_GAP_MS: &str = "segmentGapMs";
+    pub const EXAG_DEFAULT: &str = "exagDefault";
+    pub const CFG_DEFAULT: &str = "cfgDefault";
+    pub const TEMP_DEFAULT: &str = "tempDefault";
+    pub const SYNC_CORS_ORIGINS: &str = "syncCorsOrigins";
+    pub const SYNC_SERVER_IDLE_TIMEOUT_MINS: &str = "syncServerIdleTimeoutMins";
+    pub const AUTO_START_SYNC_SERVER: &str = "autoStartSyncServer";
+    pub const SYNC_MAX_BUNDLE_MB: &str = "syncMaxBundleMb";
+
+    /// Set once first run has created the default voice, or found voices
+    /// already there.
+    pub const DEFAULT_VOICE_CREATED: &str = "defaultVoiceCreated";
+    /// Profile whose books the library shows; changed by `switch_profile`.
+    pub const ACTIVE_PROFILE: &str = "activeProfile";
+
+    /// Keys holding app state rather than preferences. They aren't in the
+    /// registry, so they can't be set or imported, and exports and resets
+    /// leave them alone.
+    pub const STATE_KEYS: &[&str] = &[DEFAULT_VOICE_CREATED, ACTIVE_PROFILE];
+
+    /// Every known setting key with the kind of value it accepts.
+    pub const REGISTRY: &[(&str, SettingKind)] = &[
+        (THEME, SettingKind::Choice(&["light", "dark", "system"])),
+        (FONT_SIZE, SettingKind::Integer { min: 8, max: 72 }),
+        (FONT_FAMILY, SettingKind::Text),
+        (LINE_HEIGHT, SettingKind::Float { min: 1.0, max: 3.0 }),
+        (PLAYBACK_SPEED, SettingKind::Float { min: 0.5, max: 2.0 }),
+        (HIGHLIGHT_COLOR, SettingKind::Color),
+        (DEFAULT_VOICE, SettingKind::Text),
+        (AUTO_PLAY, SettingKind::Bool),
+        (SYNC_PORT, SettingKind::Integer { min: 1, max: 65535 }),
+        (AUTO_PROCESS, SettingKind::Bool),
+        (SHOW_IMPORT_MODAL, SettingKind::Bool),
+        (CHATTERBOX_URL, SettingKind::Url),
+        (VISION_URL, SettingKind::Url),
+        (SYNC_CONNECT_TIMEOUT_MS, SettingKind::Integer { min: 500, max: 120000 }),
+        (SYNC_DISCOVERY_TIMEOUT_MS, SettingKind::Integer { min: 500, max: 60000 }),
+        (CAPTION_PROMPT, SettingKind::Text),
+        (CAPTION_PROMPT_FULL_PAGE, SettingKind::Text),
+        (CAPTION_PROMPT_INLINE, SettingKind::Text),
+        (IMAGE_NARRATION_MODE, SettingKind::Choice(&["skip", "altTextOnly", "caption"])),
+        (HEADING_ANNOUNCEMENT, SettingKind::Text),
+        (
+            NARRATION_SAMPLE_RATE,
+            SettingKind::Choice(&["16000", "22050", "24000", "32000", "44100", "48000"]),
+        ),
+        (NARRATION_CHANNELS, SettingKind::Integer { min: 1, max: 2 }),
+        (NARRATION_BIT_DEPTH, SettingKind::Choice(&["16", "24"])),
+        (NORMALIZE_NARRATION, SettingKind::Bool),
+        (KEEP_NARRATION_CACHE, SettingKind::Bool),
+        (MERGE_SHORT_SEGMENTS, SettingKind::Bool),
+        (MERGE_SEGMENT_MIN_CHARS, SettingKind::Integer { min: 1, max: 5000 }),
+        (SKIP_BOILERPLATE, SettingKind::Bool),
+        (IMPORT_MODE, SettingKind::Choice(&["copy", "reference"])),
+        (NARRATION_CODEC, SettingKind::Choice(&["wav", "mp3", "opus"])),
+        (SYNTHESIS_CHARS_PER_SECOND, SettingKind::Float { min: 0.1, max: 10000.0 }),
+        (SPEECH_CHARS_PER_SECOND, SettingKind::Float { min: 1.0, max: 100.0 }),
+        (LOG_LEVEL, SettingKind::Choice(&["error", "warn", "info", "debug", "trace"])),
+        (SYNC_TLS, SettingKind::Bool),
+        (TRIM_SILENCE, SettingKind::Bool),
+        (SILENCE_THRESHOLD, SettingKind::Float { min: 0.0, max: 0.5 }),
+        (SEGMENT_GAP_MS, SettingKind::Integer { min: 0, max: 5000 }),
+        (EXAG_DEFAULT, SettingKind::Float { min: 0.0, max: 2.0 }),
+        (CFG_DEFAULT, SettingKind::Float { min: 0.0, max: 1.0 }),
+        (TEMP_DEFAULT, SettingKind::Float { min: 0.05, max: 5.0 }),
+        (SYNC_CORS_ORIGINS, SettingKind::Origins),
+        (SYNC_SERVER_IDLE_TIMEOUT_MINS, SettingKind::Integer { min: 0, max: 1440 }),
+        (AUTO_START_SYNC_SERVER, SettingKind::Bool),
+        (SYNC_MAX_BUNDLE_MB, SettingKind::Integer { min: 1, max: 65536 }),
+    ];
+
+    /// Look up the value kind for a setting key.
+    pub fn kind_of(key: &str) -> Option<SettingKind> {
+        REGISTRY
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, kind)| *kind)
+    }
+}
+
+/// Validate a value against the expected kind for `key`.
+///
+/// Returns the normalized value to store, or a descriptive error.
+fn validate_setting(key: &str, value: &str) -> Result<String, String> {
+    let kind = keys::kind_of(key).ok_or_else(|| format!("Unknown setting: {}", key))?;
+    let trimmed = value.trim();
+
+    match kind {
+        SettingKind::Choice(options) => {
+            if options.contains(&trimmed) {
+                Ok(trimmed.to_string())
+            } else {
+                Err(format!(
+                    "Invalid value for {}: '{}'. Expected one of: {}",
+                    key,
+                    value,
+                    options.join(", ")
+                ))
+            }
+        }
+        SettingKind::Integer { min, max } => {
+            let parsed: i64 = trimmed
+                .parse()
+                .map_err(|_| format!("Invalid value for {}: '{}' is not an integer", key, value))?;
+            if parsed < min || parsed > max {
+                return Err(format!(
+                    "Invalid value for {}: {} is out of range ({}-{})",
+                    key, parsed, min, max
+                ));
+            }
+            Ok(parsed.to_string())
+        }
+        SettingKind::Float { min, max } => {
+            let parsed: f64 = trimmed
+                .parse()
+                .ok()
+                .filter(|v: &f64| v.is_finite())
+                .ok_or_else(|| format!("Invalid value for {}: '{}' is not a number", key, value))?;
+            if parsed < min || parsed > max {
+                return Err(format!(
+                    "Invalid value for {}: {} is out of range ({}-{})",
+                    key, parsed, min, max
+                ));
+            }
+            Ok(parsed.to_string())
+        }
+        SettingKind::Bool => match trimmed {
+            "true" | "false" => Ok(trimmed.to_string()),
+            _ => Err(format!(
+                "Invalid value for {}: '{}'. Expected true or false",
+                key, value
+            )),
+        },
+        SettingKind::Color => {
+            let hex = trimmed.strip_prefix('#').unwrap_or("");
+            if (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok(trimmed.to_lowercase())
+            } else {
+                Err(format!(
+                    "Invalid value for {}: '{}' is not a hex color like #ffeb3b",
+                    key, value
+                ))
+            }
+        }
+        SettingKind::Url => {
+            let url = trimmed.trim_end_matches('/');
+            let host = url
+                .strip_prefix("http://")
+                .or_else(|| url.strip_prefix("https://"))
+                .unwrap_or("");
+            if host.is_empty() || host.contains(char::is_whitespace) {
+                return Err(format!(
+                    "Invalid value for {}: '{}' is not an http(s) URL",
+                    key, value
+                ));
+            }
+            Ok(url.to_string())
+        }
+        SettingKind::Origins => {
+            if trimmed == "*" {
+                return Ok(trimmed.to_string());
+            }
+            let mut origins = Vec::new();
+            for origin in trimmed.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+                let origin = origin.trim_end_matches('/').to_lowercase();
+                let valid = origin.split_once("://").is_some_and(|(scheme, host)| {
+                    !scheme.is_empty()
+                        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                        && !host.is_empty()
+                        && !host.contains(|c: char| c == '/' || c.is_whitespace())
+                });
+                if !valid {
+                    return Err(format!(
+                        "Invalid value for {}: '{}' is not an origin like http://localhost:5174, or *",
+                        key, origin
+                    ));
+                }
+                origins.push(origin);
+            }
+            Ok(origins.join(", "))
+        }
+        SettingKind::Text => Ok(value.to_string()),
+    }
 }
 
 impl Settings {
@@ -107,6 +455,122 @@ impl Settings {
                 .get(keys::SYNC_PORT)
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(defaults.sync_port),
+            chatterbox_url: map
+                .get(keys::CHATTERBOX_URL)
+                .cloned()
+                .unwrap_or(defaults.chatterbox_url),
+            vision_url: map
+                .get(keys::VISION_URL)
+                .cloned()
+                .unwrap_or(defaults.vision_url),
+            sync_connect_timeout_ms: map
+                .get(keys::SYNC_CONNECT_TIMEOUT_MS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.sync_connect_timeout_ms),
+            sync_discovery_timeout_ms: map
+                .get(keys::SYNC_DISCOVERY_TIMEOUT_MS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.sync_discovery_timeout_ms),
+            caption_prompt: map
+                .get(keys::CAPTION_PROMPT)
+                .cloned()
+                .unwrap_or(defaults.caption_prompt),
+            caption_prompt_full_page: map
+                .get(keys::CAPTION_PROMPT_FULL_PAGE)
+                .cloned()
+                .unwrap_or(defaults.caption_prompt_full_page),
+            caption_prompt_inline: map
+                .get(keys::CAPTION_PROMPT_INLINE)
+                .cloned()
+                .unwrap_or(defaults.caption_prompt_inline),
+            image_narration_mode: map
+                .get(keys::IMAGE_NARRATION_MODE)
+                .cloned()
+                .unwrap_or(defaults.image_narration_mode),
+            heading_announcement: map
+                .get(keys::HEADING_ANNOUNCEMENT)
+                .cloned()
+                .unwrap_or(defaults.heading_announcement),
+            narration_sample_rate: map
+                .get(keys::NARRATION_SAMPLE_RATE)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.narration_sample_rate),
+            narration_channels: map
+                .get(keys::NARRATION_CHANNELS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.narration_channels),
+            narration_bit_depth: map
+                .get(keys::NARRATION_BIT_DEPTH)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.narration_bit_depth),
+            normalize_narration: map
+                .get(keys::NORMALIZE_NARRATION)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.normalize_narration),
+            keep_narration_cache: map
+                .get(keys::KEEP_NARRATION_CACHE)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.keep_narration_cache),
+            narration_codec: map
+                .get(keys::NARRATION_CODEC)
+                .cloned()
+                .unwrap_or(defaults.narration_codec),
+            synthesis_chars_per_second: map
+                .get(keys::SYNTHESIS_CHARS_PER_SECOND)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.synthesis_chars_per_second),
+            speech_chars_per_second: map
+                .get(keys::SPEECH_CHARS_PER_SECOND)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.speech_chars_per_second),
+            log_level: map
+                .get(keys::LOG_LEVEL)
+                .cloned()
+                .unwrap_or(defaults.log_level),
+            sync_tls: map
+                .get(keys::SYNC_TLS)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.sync_tls),
+            trim_silence: map
+                .get(keys::TRIM_SILENCE)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.trim_silence),
+            silence_threshold: map
+                .get(keys::SILENCE_THRESHOLD)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.silence_threshold),
+            segment_gap_ms: map
+                .get(keys::SEGMENT_GAP_MS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.segment_gap_ms),
+            exag_default: map
+                .get(keys::EXAG_DEFAULT)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.exag_default),
+            cfg_default: map
+                .get(keys::CFG_DEFAULT)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.cfg_default),
+            temp_default: map
+                .get(keys::TEMP_DEFAULT)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.temp_default),
+            sync_cors_origins: map
+                .get(keys::SYNC_CORS_ORIGINS)
+                .cloned()
+                .unwrap_or(defaults.sync_cors_origins),
+            sync_server_idle_timeout_mins: map
+                .get(keys::SYNC_SERVER_IDLE_TIMEOUT_MINS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.sync_server_idle_timeout_mins),
+            auto_start_sync_server: map
+                .get(keys::AUTO_START_SYNC_SERVER)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.auto_start_sync_server),
+            sync_max_bundle_mb: map
+                .get(keys::SYNC_MAX_BUNDLE_MB)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.sync_max_bundle_mb),
         }
     }
 
@@ -128,18 +592,78 @@ impl Settings {
             ),
             (keys::AUTO_PLAY, self.auto_play.to_string()),
             (keys::SYNC_PORT, self.sync_port.to_string()),
+            (keys::CHATTERBOX_URL, self.chatterbox_url.clone()),
+            (keys::VISION_URL, self.vision_url.clone()),
+            (keys::SYNC_CONNECT_TIMEOUT_MS, self.sync_connect_timeout_ms.to_string()),
+            (keys::SYNC_DISCOVERY_TIMEOUT_MS, self.sync_discovery_timeout_ms.to_string()),
+            (keys::CAPTION_PROMPT, self.caption_prompt.clone()),
+            (keys::CAPTION_PROMPT_FULL_PAGE, self.caption_prompt_full_page.clone()),
+            (keys::CAPTION_PROMPT_INLINE, self.caption_prompt_inline.clone()),
+            (keys::IMAGE_NARRATION_MODE, self.image_narration_mode.clone()),
+            (keys::HEADING_ANNOUNCEMENT, self.heading_announcement.clone()),
+            (keys::NARRATION_SAMPLE_RATE, self.narration_sample_rate.to_string()),
+            (keys::NARRATION_CHANNELS, self.narration_channels.to_string()),
+            (keys::NARRATION_BIT_DEPTH, self.narration_bit_depth.to_string()),
+            (keys::NORMALIZE_NARRATION, self.normalize_narration.to_string()),
+            (keys::KEEP_NARRATION_CACHE, self.keep_narration_cache.to_string()),
+            (keys::NARRATION_CODEC, self.narration_codec.clone()),
+            (keys::SYNTHESIS_CHARS_PER_SECOND, self.synthesis_chars_per_second.to_string()),
+            (keys::SPEECH_CHARS_PER_SECOND, self.speech_chars_per_second.to_string()),
+            (keys::LOG_LEVEL, self.log_level.clone()),
+            (keys::SYNC_TLS, self.sync_tls.to_string()),
+            (keys::TRIM_SILENCE, self.trim_silence.to_string()),
+            (keys::SILENCE_THRESHOLD, self.silence_threshold.to_string()),
+            (keys::SEGMENT_GAP_MS, self.segment_gap_ms.to_string()),
+            (keys::EXAG_DEFAULT, self.exag_default.to_string()),
+            (keys::CFG_DEFAULT, self.cfg_default.to_string()),
+            (keys::TEMP_DEFAULT, self.temp_default.to_string()),
+            (keys::SYNC_CORS_ORIGINS, self.sync_cors_origins.clone()),
+            (
+                keys::SYNC_SERVER_IDLE_TIMEOUT_MINS,
+                self.sync_server_idle_timeout_mins.to_string(),
+            ),
+            (keys::AUTO_START_SYNC_SERVER, self.auto_start_sync_server.to_string()),
+            (keys::SYNC_MAX_BUNDLE_MB, self.sync_max_bundle_mb.to_string()),
         ]
     }
+
+    /// Chatterbox parameters for narration.
+    ///
+    /// Parameters resolve from a per-segment override, then the book's own
+    /// parameters, then these global defaults, then the hardcoded
+    /// [`ChatterboxParams::default`]. Segments and books carry no overrides
+    /// yet, so the global defaults apply to every request.
+    pub fn chatterbox_params(&self) -> ChatterboxParams {
+        ChatterboxParams {
+            exag: self.exag_default as f32,
+            cfg: self.cfg_default as f32,
+            temp: self.temp_default as f32,
+        }
+    }
+
+    /// The log filter selected by the `logLevel` setting.
+    pub fn log_level_filter(&self) -> log::LevelFilter {
+        self.log_level.parse().unwrap_or(log::LevelFilter::Warn)
+    }
 }
 
 /// Import preferences for new books.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct ImportPreferences {
     /// Automatically generate narration when importing.
     pub auto_process: bool,
     /// Show the import options modal.
     pub show_import_modal: bool,
+    /// Merge runs of short consecutive segments on import.
+    pub merge_short_segments: bool,
+    /// Segments shorter than this many characters are merged with their neighbors.
+    pub merge_segment_min_chars: u32,
+    /// Leave likely page numbers, running headers and copyright notices out
+    /// of narration on import.
+    pub skip_boilerplate: bool,
+    /// Whether imports copy the source file ("copy") or reference it in place ("reference").
+    pub import_mode: String,
 }
 
 impl Default for ImportPreferences {
@@ -147,6 +671,10 @@ impl Default for ImportPreferences {
         Self {
             auto_process: false,
             show_import_modal: true,
+            merge_short_segments: false,
+            merge_segment_min_chars: 120,
+            skip_boilerplate: false,
+            import_mode: "copy".to_string(),
         }
     }
 }
@@ -165,6 +693,22 @@ impl ImportPreferences {
                 .get(keys::SHOW_IMPORT_MODAL)
                 .map(|v| v == "true")
                 .unwrap_or(defaults.show_import_modal),
+            merge_short_segments: map
+                .get(keys::MERGE_SHORT_SEGMENTS)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.merge_short_segments),
+            merge_segment_min_chars: map
+                .get(keys::MERGE_SEGMENT_MIN_CHARS)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.merge_segment_min_chars),
+            skip_boilerplate: map
+                .get(keys::SKIP_BOILERPLATE)
+                .map(|v| v == "true")
+                .unwrap_or(defaults.skip_boilerplate),
+            import_mode: map
+                .get(keys::IMPORT_MODE)
+                .cloned()
+                .unwrap_or(defaults.import_mode),
         }
     }
 
@@ -173,89 +717,252 @@ impl ImportPreferences {
         vec![
             (keys::AUTO_PROCESS, self.auto_process.to_string()),
             (keys::SHOW_IMPORT_MODAL, self.show_import_modal.to_string()),
+            (keys::MERGE_SHORT_SEGMENTS, self.merge_short_segments.to_string()),
+            (keys::MERGE_SEGMENT_MIN_CHARS, self.merge_segment_min_chars.to_string()),
+            (keys::SKIP_BOILERPLATE, self.skip_boilerplate.to_string()),
+            (keys::IMPORT_MODE, self.import_mode.clone()),
         ]
     }
 }
 
 /// Query all settings from the database as a HashMap.
-fn query_all_settings(state: &AppState) -> Result<HashMap<String, String>, String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+fn query_all_settings(db: &Database) -> CommandResult<HashMap<String, String>> {
+    let conn = db.connection().lock()?;
 
     let mut stmt = conn
         .prepare("SELECT key, value FROM settings")
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?;
 
     let rows = stmt
         .query_map([], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         })
-        .map_err(|e| format!("Failed to query settings: {}", e))?;
+        .context("Failed to query settings")?;
 
     let mut map = HashMap::new();
     for row in rows {
-        let (key, value) = row.map_err(|e| format!("Failed to read row: {}", e))?;
+        let (key, value) = row.context("Failed to read row")?;
         map.insert(key, value);
     }
 
     Ok(map)
 }
 
+/// Load the current settings for use by other commands and background tasks.
+pub(crate) fn load_settings(db: &Database) -> CommandResult<Settings> {
+    let map = query_all_settings(db)?;
+    Ok(Settings::from_map(&map))
+}
+
+/// Load import preferences, with defaults for any missing keys.
+pub(crate) fn load_import_preferences(db: &Database) -> CommandResult<ImportPreferences> {
+    let map = query_all_settings(db)?;
+    Ok(ImportPreferences::from_map(&map))
+}
+
+/// Whether the `defaultVoice` preference names the given voice.
+pub(crate) fn is_default_voice_setting(
+    conn: &rusqlite::Connection,
+    voice_id: &VoiceId,
+) -> CommandResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM settings WHERE key = ?1 AND value = ?2)",
+        rusqlite::params![keys::DEFAULT_VOICE, voice_id.as_str()],
+        |row| row.get(0),
+    )
+    .context("Failed to read default voice setting")
+}
+
+/// Unset the `defaultVoice` preference if it names the given voice.
+///
+/// Takes a connection so it can run inside the caller's transaction.
+pub(crate) fn clear_default_voice_setting(
+    conn: &rusqlite::Connection,
+    voice_id: &VoiceId,
+) -> CommandResult<()> {
+    conn.execute(
+        "DELETE FROM settings WHERE key = ?1 AND value = ?2",
+        rusqlite::params![keys::DEFAULT_VOICE, voice_id.as_str()],
+    )
+    .context("Failed to clear default voice setting")?;
+    Ok(())
+}
+
+/// Whether first run has already dealt with the default voice.
+pub(crate) fn default_voice_created(conn: &rusqlite::Connection) -> CommandResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM settings WHERE key = ?)",
+        rusqlite::params![keys::DEFAULT_VOICE_CREATED],
+        |row| row.get(0),
+    )
+    .context("Failed to read settings")
+}
+
+/// Record that first run has dealt with the default voice, so it is never
+/// created again.
+pub(crate) fn mark_default_voice_created(conn: &rusqlite::Connection) -> CommandResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, 'true')",
+        rusqlite::params![keys::DEFAULT_VOICE_CREATED],
+    )
+    .context("Failed to update settings")?;
+    Ok(())
+}
+
+/// Id of the profile named by the `activeProfile` state key, or the
+/// default profile if it is unset or names a profile that no longer exists.
+pub(crate) fn active_profile_id(conn: &rusqlite::Connection) -> CommandResult<String> {
+    let active = conn.query_row(
+        "SELECT p.id FROM settings s JOIN profiles p ON p.id = s.value WHERE s.key = ?",
+        rusqlite::params![keys::ACTIVE_PROFILE],
+        |row| row.get(0),
+    );
+    match active {
+        Ok(id) => Ok(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(DEFAULT_PROFILE_ID.to_string()),
+        Err(e) => Err(CommandError::Database(format!("Database error: {}", e))),
+    }
+}
+
+/// Set the `activeProfile` state key.
+pub(crate) fn set_active_profile(
+    conn: &rusqlite::Connection,
+    profile_id: &str,
+) -> CommandResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        rusqlite::params![keys::ACTIVE_PROFILE, profile_id],
+    )
+    .context("Failed to set active profile")?;
+    Ok(())
+}
+
+/// Weight given to the latest job when calibrating narration rates.
+const RATE_CALIBRATION_WEIGHT: f64 = 0.5;
+
+/// Move a stored rate toward a newly measured one, kept within the
+/// setting's allowed range.
+fn blend_rate(key: &str, previous: f64, measured: f64) -> f64 {
+    let blended = previous + (measured - previous) * RATE_CALIBRATION_WEIGHT;
+    match keys::kind_of(key) {
+        Some(SettingKind::Float { min, max }) => blended.clamp(min, max),
+        _ => blended,
+    }
+}
+
+/// Calibrate the stored synthesis and speech rates from a finished narration.
+///
+/// Jobs that narrated nothing or finished instantly are ignored.
+pub(crate) fn record_narration_rates(
+    db: &Database,
+    narrated_chars: usize,
+    synthesis_seconds: f64,
+    audio_seconds: f64,
+) -> CommandResult<()> {
+    if narrated_chars == 0 || synthesis_seconds <= 0.0 || audio_seconds <= 0.0 {
+        return Ok(());
+    }
+
+    let settings = load_settings(db)?;
+    let chars = narrated_chars as f64;
+    let rates = [
+        (
+            keys::SYNTHESIS_CHARS_PER_SECOND,
+            settings.synthesis_chars_per_second,
+            chars / synthesis_seconds,
+        ),
+        (
+            keys::SPEECH_CHARS_PER_SECOND,
+            settings.speech_chars_per_second,
+            chars / audio_seconds,
+        ),
+    ];
+
+    let conn = db.connection().lock()?;
+    for (key, previous, measured) in rates {
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            rusqlite::params![key, blend_rate(key, previous, measured).to_string()],
+        )
+        .with_context(|| format!("Failed to update setting '{}'", key))?;
+    }
+
+    Ok(())
+}
+
 /// Get all settings.
 ///
 /// Returns the current settings, with defaults for any missing keys.
 #[tauri::command]
-pub async fn get_settings(state: State<'_, AppState>) -> Result<Settings, String> {
-    let map = query_all_settings(&state)?;
+pub async fn get_settings(state: State<'_, AppState>) -> CommandResult<Settings> {
+    let map = query_all_settings(&state.db)?;
     Ok(Settings::from_map(&map))
 }
 
 /// Update a setting.
 ///
-/// Updates a single setting key with a new value.
+/// Updates a single setting key with a new value. Known keys are validated
+/// against their expected type and range; unknown keys are rejected unless
+/// `allow_unknown` is set, in which case the value is stored verbatim.
 #[tauri::command]
-pub async fn set_setting(key: String, value: String, state: State<'_, AppState>) -> Result<(), String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+pub async fn set_setting(
+    key: String,
+    value: String,
+    allow_unknown: Option<bool>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let value = if keys::kind_of(&key).is_some() {
+        validate_setting(&key, &value).map_err(CommandError::InvalidInput)?
+    } else if allow_unknown.unwrap_or(false) {
+        value
+    } else {
+        return Err(CommandError::InvalidInput(format!("Unknown setting: {}", key)));
+    };
+
+    let conn = state.db.connection().lock()?;
 
     conn.execute(
         "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
         rusqlite::params![key, value],
     )
-    .map_err(|e| format!("Failed to set setting: {}", e))?;
+    .context("Failed to set setting")?;
 
     Ok(())
 }
 
 /// Update multiple settings at once.
+///
+/// Each value is validated like in `set_setting`; nothing is saved if any
+/// value is invalid.
 #[tauri::command]
-pub async fn update_settings(settings: Settings, state: State<'_, AppState>) -> Result<(), String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+pub async fn update_settings(settings: Settings, state: State<'_, AppState>) -> CommandResult<()> {
+    let conn = state.db.connection().lock()?;
 
     let tx = conn
         .unchecked_transaction()
-        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        .context("Failed to start transaction")?;
 
     {
         let mut stmt = tx
             .prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")
-            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            .context("Failed to prepare statement")?;
 
         for (key, value) in settings.to_pairs() {
+            let value = validate_setting(key, &value).map_err(CommandError::InvalidInput)?;
             stmt.execute(rusqlite::params![key, value])
-                .map_err(|e| format!("Failed to update setting '{}': {}", key, e))?;
+                .with_context(|| format!("Failed to update setting '{}'", key))?;
         }
     }
 
-    tx.commit()
-        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    tx.commit().context("Failed to commit transaction")?;
 
     Ok(())
 }
 
 /// Get import preferences.
 #[tauri::command]
-pub async fn get_import_preferences(state: State<'_, AppState>) -> Result<ImportPreferences, String> {
-    let map = query_all_settings(&state)?;
-    Ok(ImportPreferences::from_map(&map))
+pub async fn get_import_preferences(state: State<'_, AppState>) -> CommandResult<ImportPreferences> {
+    load_import_preferences(&state.db)
 }
 
 /// Update import preferences.
@@ -263,45 +970,718 @@ pub async fn get_import_preferences(state: State<'_, AppState>) -> Result<Import
 pub async fn set_import_preferences(
     preferences: ImportPreferences,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+) -> CommandResult<()> {
+    let conn = state.db.connection().lock()?;
 
     let tx = conn
         .unchecked_transaction()
-        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        .context("Failed to start transaction")?;
 
     {
         let mut stmt = tx
             .prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")
-            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            .context("Failed to prepare statement")?;
 
         for (key, value) in preferences.to_pairs() {
             stmt.execute(rusqlite::params![key, value])
-                .map_err(|e| format!("Failed to update preference '{}': {}", key, e))?;
+                .with_context(|| format!("Failed to update preference '{}'", key))?;
         }
     }
 
-    tx.commit()
-        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    tx.commit().context("Failed to commit transaction")?;
 
     Ok(())
 }
 
 /// Reset all settings to defaults.
 #[tauri::command]
-pub async fn reset_settings(state: State<'_, AppState>) -> Result<(), String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+pub async fn reset_settings(state: State<'_, AppState>) -> CommandResult<()> {
+    let conn = state.db.connection().lock()?;
 
-    conn.execute("DELETE FROM settings", [])
-        .map_err(|e| format!("Failed to reset settings: {}", e))?;
+    // App state such as the default voice marker isn't a preference to reset
+    let placeholders = vec!["?"; keys::STATE_KEYS.len()].join(", ");
+    conn.execute(
+        &format!("DELETE FROM settings WHERE key NOT IN ({})", placeholders),
+        rusqlite::params_from_iter(keys::STATE_KEYS),
+    )
+    .context("Failed to reset settings")?;
 
     Ok(())
 }
 
+/// Export all settings as a pretty-printed JSON object.
+///
+/// Stored values are layered over the defaults so the export is complete
+/// even for keys the user has never changed.
+#[tauri::command]
+pub async fn export_settings(state: State<'_, AppState>) -> CommandResult<String> {
+    let stored = query_all_settings(&state.db)?;
+
+    let mut map: BTreeMap<String, String> = Settings::default()
+        .to_pairs()
+        .into_iter()
+        .chain(ImportPreferences::default().to_pairs())
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    map.extend(stored.into_iter().filter(|(key, _)| !keys::STATE_KEYS.contains(&key.as_str())));
+
+    serde_json::to_string_pretty(&map).context("Failed to serialize settings")
+}
+
+/// Import settings from a JSON object produced by `export_settings`.
+///
+/// Every known key is validated before anything is written; an invalid value
+/// fails the whole import. Unknown keys are skipped with a warning and
+/// returned so the UI can report them.
+#[tauri::command]
+pub async fn import_settings(json: String, state: State<'_, AppState>) -> CommandResult<Vec<String>> {
+    let parsed: BTreeMap<String, serde_json::Value> =
+        serde_json::from_str(&json).context("Invalid settings JSON")?;
+
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+
+    for (key, raw) in parsed {
+        if keys::kind_of(&key).is_none() {
+            log::warn!("Skipping unknown setting during import: {}", key);
+            skipped.push(key);
+            continue;
+        }
+
+        let value = match raw {
+            serde_json::Value::String(s) => s,
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            serde_json::Value::Null => String::new(),
+            other => {
+                return Err(CommandError::InvalidInput(format!(
+                    "Invalid value for {}: {}",
+                    key, other
+                )))
+            }
+        };
+
+        let value = validate_setting(&key, &value).map_err(CommandError::InvalidInput)?;
+        pairs.push((key, value));
+    }
+
+    let conn = state.db.connection().lock()?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    {
+        let mut stmt = tx
+            .prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")
+            .context("Failed to prepare statement")?;
+
+        for (key, value) in &pairs {
+            stmt.execute(rusqlite::params![key, value])
+                .with_context(|| format!("Failed to import setting '{}'", key))?;
+        }
+    }
+
+    tx.commit().context("Failed to commit transaction")?;
+
+    Ok(skipped)
+}
+
 /// Get the application data directory path.
 ///
 /// Returns the path where Actual Reader stores its data (library.db, sources, narration, etc.).
 #[tauri::command]
-pub async fn get_data_directory(state: State<'_, AppState>) -> Result<String, String> {
-    Ok(state.paths.root.display().to_string())
+pub async fn get_data_directory(state: State<'_, AppState>) -> CommandResult<String> {
+    Ok(state.paths().root.display().to_string())
+}
+
+/// Open the application data directory in the system file manager.
+#[tauri::command]
+pub async fn open_data_directory(app: AppHandle, state: State<'_, AppState>) -> CommandResult<()> {
+    use tauri_plugin_opener::OpenerExt;
+
+    app.opener()
+        .open_path(state.paths().root.to_string_lossy(), None::<&str>)
+        .map_err(|e| CommandError::Internal(format!("Failed to open data directory: {}", e)))
+}
+
+/// Progress update while copying the data directory to a new location.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocationProgress {
+    /// Files copied so far.
+    pub current: u32,
+    /// Files to copy.
+    pub total: u32,
+    /// Bytes copied so far.
+    pub copied_bytes: u64,
+    /// Bytes to copy.
+    pub total_bytes: u64,
+}
+
+/// Check that `new_root` can receive the data directory at `old_root`.
+///
+/// It must be absolute, outside the current data directory, and either
+/// missing or empty apart from a data location record.
+fn validate_new_root(old_root: &Path, new_root: &Path) -> CommandResult<()> {
+    if !new_root.is_absolute() {
+        return Err(CommandError::InvalidInput(format!(
+            "Data directory must be an absolute path: {}",
+            new_root.display()
+        )));
+    }
+
+    // Resolve symlinks through the nearest existing ancestor so a link
+    // can't hide that the target is inside the current directory
+    let old_root = old_root.canonicalize().context("Failed to resolve data directory")?;
+    let mut existing = new_root.to_path_buf();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                rest.push(name.to_os_string());
+                existing = parent.to_path_buf();
+            }
+            _ => break,
+        }
+    }
+    let mut resolved = existing.canonicalize().unwrap_or(existing);
+    resolved.extend(rest.iter().rev());
+    if resolved.starts_with(&old_root) {
+        return Err(CommandError::InvalidInput(
+            "New data directory can't be inside the current one".to_string(),
+        ));
+    }
+
+    if new_root.exists() {
+        if !new_root.is_dir() {
+            return Err(CommandError::InvalidInput(format!(
+                "Not a directory: {}",
+                new_root.display()
+            )));
+        }
+        let occupied = std::fs::read_dir(new_root)
+            .context("Failed to read new data directory")?
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name() != DATA_LOCATION_FILE);
+        if occupied {
+            return Err(CommandError::InvalidInput(format!(
+                "New data directory is not empty: {}",
+                new_root.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a path relative to the data directory is the database or one of
+/// SQLite's journal files, which are copied with `VACUUM INTO` instead.
+fn is_database_file(paths: &AppPaths, relative: &Path) -> bool {
+    let Some(name) = paths.database.file_name() else {
+        return false;
+    };
+    relative.parent() == Some(Path::new(""))
+        && relative
+            .to_string_lossy()
+            .starts_with(name.to_string_lossy().as_ref())
+}
+
+/// A file in the data directory, relative to its root, with the size and
+/// modification time it had when copied.
+type CopiedFile = (PathBuf, u64, Option<SystemTime>);
+
+/// Copy the files of the data directory at `old` to `new`, except the
+/// database and those in `copied` that haven't changed since.
+///
+/// Files are copied one at a time and their sizes compared. Returns every
+/// file copied so far, `copied` included. `old` is never modified.
+fn copy_data_files(
+    old: &AppPaths,
+    new: &AppPaths,
+    copied: &[CopiedFile],
+    mut on_progress: impl FnMut(&RelocationProgress),
+) -> CommandResult<Vec<CopiedFile>> {
+    let listed: Vec<CopiedFile> = list_files(&old.root)
+        .context("Failed to list data directory")?
+        .into_iter()
+        .filter(|file| file.as_os_str() != DATA_LOCATION_FILE && !is_database_file(old, file))
+        .map(|file| {
+            let metadata = std::fs::metadata(old.root.join(&file))
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            Ok((file, metadata.len(), metadata.modified().ok()))
+        })
+        .collect::<CommandResult<_>>()?;
+    let copied: HashSet<&CopiedFile> = copied.iter().collect();
+    let (unchanged, files): (Vec<CopiedFile>, Vec<CopiedFile>) =
+        listed.into_iter().partition(|file| copied.contains(file));
+
+    new.ensure_dirs().context("Failed to create new data directory")?;
+
+    let mut progress = RelocationProgress {
+        current: 0,
+        total: files.len() as u32,
+        copied_bytes: 0,
+        total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+    };
+    on_progress(&progress);
+
+    for (file, size, _) in &files {
+        let dest = new.root.join(file);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let copied = std::fs::copy(old.root.join(file), &dest)
+            .with_context(|| format!("Failed to copy {}", file.display()))?;
+        if copied != *size {
+            return Err(CommandError::Io(format!(
+                "Copy of {} is incomplete ({} of {} bytes)",
+                file.display(),
+                copied,
+                size
+            )));
+        }
+
+        progress.current += 1;
+        progress.copied_bytes += size;
+        on_progress(&progress);
+    }
+
+    Ok(unchanged.into_iter().chain(files).collect())
+}
+
+/// Copy the database at `old` to `new` and check the copy.
+///
+/// The copy is a consistent snapshot from `conn`, which the caller keeps
+/// locked, and its paths are made relative to the new root.
+fn copy_database(conn: &Connection, old: &AppPaths, new: &AppPaths) -> CommandResult<Database> {
+    conn.execute(
+        "VACUUM INTO ?1",
+        [new.database.to_string_lossy().as_ref()],
+    )
+    .context("Failed to copy database")?;
+
+    let db = init_database(&new.database).context("Failed to open copied database")?;
+    relativize_book_paths(&db, new).context("Failed to update copied book paths")?;
+
+    {
+        let new_conn = db.connection().lock()?;
+
+        // Voice samples are stored as absolute paths
+        let voices: Vec<(String, String)> = new_conn
+            .prepare("SELECT id, sample_path FROM voices WHERE sample_path IS NOT NULL")
+            .and_then(|mut stmt| {
+                let voices = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect();
+                voices
+            })
+            .context("Failed to query voices")?;
+        for (id, sample_path) in voices {
+            if let Ok(relative) = Path::new(&sample_path).strip_prefix(&old.root) {
+                new_conn
+                    .execute(
+                        "UPDATE voices SET sample_path = ? WHERE id = ?",
+                        rusqlite::params![new.root.join(relative).to_string_lossy().to_string(), id],
+                    )
+                    .context("Failed to update voice sample path")?;
+            }
+        }
+
+        let integrity: String = new_conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .context("Failed to check copied database")?;
+        if integrity != "ok" {
+            return Err(CommandError::Database(format!(
+                "Copied database is damaged: {}",
+                integrity
+            )));
+        }
+
+        let count_books = |conn: &Connection| -> rusqlite::Result<i64> {
+            conn.query_row("SELECT COUNT(*) FROM books", [], |row| row.get(0))
+        };
+        if count_books(&new_conn).context("Failed to check copied database")?
+            != count_books(conn).context("Failed to check database")?
+        {
+            return Err(CommandError::Database(
+                "Copied database is missing books".to_string(),
+            ));
+        }
+    }
+
+    Ok(db)
+}
+
+/// Remove `files` (relative to `root`) and any directories they leave empty.
+///
+/// Failures are logged and skipped: the files are leftovers by now.
+fn remove_data_files(root: &Path, files: &[PathBuf]) {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for file in files {
+        if let Err(e) = std::fs::remove_file(root.join(file)) {
+            log::warn!("Failed to remove {}: {}", root.join(file).display(), e);
+        }
+        let mut parent = file.parent();
+        while let Some(dir) = parent.filter(|dir| !dir.as_os_str().is_empty()) {
+            dirs.push(dir.to_path_buf());
+            parent = dir.parent();
+        }
+    }
+
+    // Deepest first, so children are gone before their parents
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+    for dir in dirs {
+        let _ = std::fs::remove_dir(root.join(dir));
+    }
+    let _ = std::fs::remove_dir(root);
+}
+
+/// Undo a partial copy into `root`, removing it entirely if it was created
+/// for the move.
+fn discard_copy(root: &Path, created: bool) {
+    if created {
+        let _ = std::fs::remove_dir_all(root);
+        return;
+    }
+    for entry in std::fs::read_dir(root).into_iter().flatten().flatten() {
+        if entry.file_name() == DATA_LOCATION_FILE {
+            continue;
+        }
+        let path = entry.path();
+        let _ = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+    }
+}
+
+/// Move the application data directory to `new_root`.
+///
+/// Copies every file, verifies the copy, then records the new location and
+/// switches to it before deleting the old files. Until the location is
+/// recorded the old directory stays in use, so an interruption leaves one of
+/// the two complete. Emits `data_relocation_progress` while copying and
+/// returns the new path.
+#[tauri::command]
+pub async fn relocate_data_directory(
+    new_root: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<String> {
+    if !state.active_generations.read().await.is_empty() {
+        return Err(CommandError::Conflict(
+            "Can't move the data directory while narration is generating".to_string(),
+        ));
+    }
+    if state.sync_server.read().await.is_some() {
+        return Err(CommandError::Conflict(
+            "Stop the sync server before moving the data directory".to_string(),
+        ));
+    }
+    if !state.active_imports.lock().unwrap().is_empty() {
+        return Err(CommandError::Conflict(
+            "Can't move the data directory while a book is importing".to_string(),
+        ));
+    }
+
+    let default_root = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| CommandError::Internal(format!("Failed to get app data directory: {}", e)))?;
+    let old = state.paths();
+    let new_root = PathBuf::from(new_root.trim());
+    validate_new_root(&old.root, &new_root)?;
+    let new = AppPaths::new(new_root);
+    let created = !new.root.exists();
+
+    // Copy the files without holding the database lock, then take it and
+    // catch up on whatever changed meanwhile, so nothing changes between the
+    // last copy and the switch
+    let copied = copy_data_files(&old, &new, &[], |progress| {
+        let _ = app.emit("data_relocation_progress", progress);
+    });
+    let mut conn = state.db.connection().lock()?;
+    let copied = copied.and_then(|copied| {
+        let copied = copy_data_files(&old, &new, &copied, |_| {})?;
+        Ok((copy_database(&conn, &old, &new)?, copied))
+    });
+    let (new_db, files) = match copied {
+        Ok((new_db, copied)) => {
+            let files: HashSet<PathBuf> = copied.into_iter().map(|(file, ..)| file).collect();
+            (new_db, files)
+        }
+        Err(e) => {
+            drop(conn);
+            discard_copy(&new.root, created);
+            return Err(e);
+        }
+    };
+
+    // Recording the new location is the commit point: before it a restart
+    // uses the old directory, after it the new one
+    if let Err(e) = write_data_location(&default_root, &new.root) {
+        drop(new_db);
+        discard_copy(&new.root, created);
+        return Err(CommandError::Io(format!(
+            "Failed to record new data directory: {}",
+            e
+        )));
+    }
+
+    *conn = new_db.into_connection();
+    state.set_paths(new.clone());
+    drop(conn);
+
+    let old_files: Vec<PathBuf> = list_files(&old.root)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|file| files.contains(file) || is_database_file(&old, file))
+        .collect();
+    remove_data_files(&old.root, &old_files);
+
+    log::info!(
+        "Moved data directory from {} to {}",
+        old.root.display(),
+        new.root.display()
+    );
+    Ok(new.root.display().to_string())
+}
+
+/// File name (without extension) of the log written to the app log directory.
+pub(crate) const LOG_FILE_NAME: &str = "actual-reader";
+
+/// Number of log lines returned when the caller does not ask for a count.
+const DEFAULT_LOG_LINES: u32 = 200;
+
+/// Return the last lines of text, oldest first.
+fn tail_lines(text: &str, count: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    lines[start..].iter().map(|line| line.to_string()).collect()
+}
+
+/// Get the most recent lines of the application log for in-app display.
+///
+/// Returns an empty list when nothing has been logged yet.
+#[tauri::command]
+pub async fn get_logs(app: AppHandle, lines: Option<u32>) -> CommandResult<Vec<String>> {
+    let path = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| CommandError::Internal(format!("Failed to locate log directory: {}", e)))?
+        .join(format!("{}.log", LOG_FILE_NAME));
+
+    let text = match std::fs::read(&path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read log file"),
+    };
+
+    Ok(tail_lines(&text, lines.unwrap_or(DEFAULT_LOG_LINES) as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_defaults_round_trip() {
+        // The frontend store sends its keys and values as they are in
+        // DEFAULT_SETTINGS, serialized as settingsStore's serializeValue does
+        let types = include_str!("../../../src/types/index.ts");
+        let start = types.find("export const DEFAULT_SETTINGS").unwrap();
+        let block = &types[start..];
+        let block = &block[block.find('{').unwrap() + 1..block.find("};").unwrap()];
+
+        let mut count = 0;
+        for line in block.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.trim_end_matches(',').split_once(':').unwrap();
+            let value = value.trim().trim_matches('\'');
+            assert!(validate_setting(key.trim(), value).is_ok(), "{}: {}", key, value);
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_registry_covers_all_stored_keys() {
+        let settings_pairs = Settings::default().to_pairs();
+        let import_pairs = ImportPreferences::default().to_pairs();
+
+        for (key, value) in settings_pairs.iter().chain(import_pairs.iter()) {
+            assert!(keys::kind_of(key).is_some(), "{} missing from registry", key);
+            assert!(
+                validate_setting(key, value).is_ok(),
+                "default for {} fails validation",
+                key
+            );
+        }
+
+        // State keys are written by their own commands, never by settings
+        for key in keys::STATE_KEYS {
+            assert!(keys::kind_of(key).is_none(), "{} is in the registry", key);
+            assert!(settings_pairs.iter().all(|(k, _)| k != key), "{} is a setting", key);
+        }
+    }
+
+    #[test]
+    fn test_validate_setting_ranges() {
+        assert_eq!(validate_setting(keys::PLAYBACK_SPEED, "1.5").unwrap(), "1.5");
+        assert!(validate_setting(keys::PLAYBACK_SPEED, "3").is_err());
+        assert!(validate_setting(keys::PLAYBACK_SPEED, "NaN").is_err());
+        assert!(validate_setting(keys::FONT_SIZE, "huge").is_err());
+        assert!(validate_setting(keys::SYNC_PORT, "70000").is_err());
+        assert_eq!(validate_setting(keys::SYNC_PORT, " 8080 ").unwrap(), "8080");
+        assert!(validate_setting(keys::CFG_DEFAULT, "1.5").is_err());
+        assert!(validate_setting(keys::TEMP_DEFAULT, "0").is_err());
+    }
+
+    #[test]
+    fn test_validate_cors_origins() {
+        assert_eq!(validate_setting(keys::SYNC_CORS_ORIGINS, " * ").unwrap(), "*");
+        assert_eq!(
+            validate_setting(keys::SYNC_CORS_ORIGINS, "HTTP://Example.com/,tauri://localhost").unwrap(),
+            "http://example.com, tauri://localhost"
+        );
+        assert_eq!(validate_setting(keys::SYNC_CORS_ORIGINS, "").unwrap(), "");
+        assert!(validate_setting(keys::SYNC_CORS_ORIGINS, "example.com").is_err());
+        assert!(validate_setting(keys::SYNC_CORS_ORIGINS, "http://example.com/path").is_err());
+        assert!(validate_setting(keys::SYNC_CORS_ORIGINS, "http://a.com, *").is_err());
+    }
+
+    #[test]
+    fn test_chatterbox_params_from_settings() {
+        assert_eq!(Settings::default().chatterbox_params(), ChatterboxParams::default());
+
+        let map = HashMap::from([
+            (keys::EXAG_DEFAULT.to_string(), "0.7".to_string()),
+            (keys::TEMP_DEFAULT.to_string(), "1.2".to_string()),
+        ]);
+        let params = Settings::from_map(&map).chatterbox_params();
+        assert_eq!(params.exag, 0.7);
+        assert_eq!(params.cfg, DEFAULT_CFG as f32);
+        assert_eq!(params.temp, 1.2);
+    }
+
+    #[test]
+    fn test_validate_setting_choices_and_formats() {
+        assert!(validate_setting(keys::THEME, "dark").is_ok());
+        assert!(validate_setting(keys::THEME, "blue").is_err());
+        assert!(validate_setting(keys::AUTO_PLAY, "yes").is_err());
+        assert_eq!(validate_setting(keys::HIGHLIGHT_COLOR, "#FFF").unwrap(), "#fff");
+        assert!(validate_setting(keys::HIGHLIGHT_COLOR, "yellow").is_err());
+        assert!(validate_setting(keys::NARRATION_SAMPLE_RATE, "44100").is_ok());
+        assert!(validate_setting(keys::NARRATION_SAMPLE_RATE, "12345").is_err());
+        assert!(validate_setting(keys::NARRATION_BIT_DEPTH, "24").is_ok());
+        assert!(validate_setting(keys::NARRATION_BIT_DEPTH, "32").is_err());
+        assert!(validate_setting(keys::DEFAULT_VOICE, "").is_ok());
+        assert!(validate_setting("notASetting", "x").is_err());
+    }
+
+    #[test]
+    fn test_blend_rate() {
+        assert_eq!(blend_rate(keys::SYNTHESIS_CHARS_PER_SECOND, 20.0, 40.0), 30.0);
+        assert_eq!(blend_rate(keys::SPEECH_CHARS_PER_SECOND, 15.0, 1000.0), 100.0);
+        assert_eq!(blend_rate(keys::SPEECH_CHARS_PER_SECOND, 15.0, 0.0), 7.5);
+    }
+
+    #[test]
+    fn test_log_level_filter() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.log_level_filter(), log::LevelFilter::Warn);
+
+        settings.log_level = "debug".to_string();
+        assert_eq!(settings.log_level_filter(), log::LevelFilter::Debug);
+
+        settings.log_level = "loud".to_string();
+        assert_eq!(settings.log_level_filter(), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn test_tail_lines() {
+        assert_eq!(tail_lines("a\nb\nc\n", 2), vec!["b", "c"]);
+        assert_eq!(tail_lines("a\nb", 5), vec!["a", "b"]);
+        assert!(tail_lines("", 5).is_empty());
+    }
+
+    #[test]
+    fn test_copy_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = AppPaths::new(dir.path().join("old"));
+        old.ensure_dirs().unwrap();
+        let db = init_database(&old.database).unwrap();
+        std::fs::write(old.source_path("book-1", "txt"), "some text").unwrap();
+        {
+            let conn = db.connection().lock().unwrap();
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                 VALUES ('book-1', 'Book', 'txt', ?1, 'none', 0, 0)",
+                [old.source_path("book-1", "txt").to_string_lossy().as_ref()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO voices (id, name, engine, sample_path) VALUES ('voice_1', 'Voice', 'chatterbox', ?1)",
+                [old.voice_sample_path("voice_1", "wav").to_string_lossy().as_ref()],
+            )
+            .unwrap();
+        }
+
+        let new = AppPaths::new(dir.path().join("new"));
+        validate_new_root(&old.root, &new.root).unwrap();
+        assert!(validate_new_root(&old.root, &old.root.join("nested")).is_err());
+        assert!(validate_new_root(&old.root, Path::new("relative")).is_err());
+
+        let mut updates = Vec::new();
+        let copied =
+            copy_data_files(&old, &new, &[], |progress| updates.push(progress.clone())).unwrap();
+
+        let files: Vec<&PathBuf> = copied.iter().map(|(file, ..)| file).collect();
+        assert_eq!(files, vec![&PathBuf::from("sources").join("book-1.txt")]);
+        assert_eq!(
+            std::fs::read_to_string(new.source_path("book-1", "txt")).unwrap(),
+            "some text"
+        );
+        let last = updates.last().unwrap();
+        assert_eq!((last.current, last.total), (1, 1));
+        assert_eq!(last.copied_bytes, 9);
+
+        // Catching up copies only what was added since
+        std::fs::write(old.source_path("book-2", "txt"), "more").unwrap();
+        updates.clear();
+        let copied = copy_data_files(&old, &new, &copied, |progress| {
+            updates.push(progress.clone());
+        })
+        .unwrap();
+        assert_eq!(copied.len(), 2);
+        assert_eq!(updates.last().unwrap().total_bytes, 4);
+        assert_eq!(std::fs::read_to_string(new.source_path("book-2", "txt")).unwrap(), "more");
+
+        let new_db = copy_database(&db.connection().lock().unwrap(), &old, &new).unwrap();
+
+        // The copied database points at the copied files
+        let source_path: String = new_db
+            .connection()
+            .lock()
+            .unwrap()
+            .query_row("SELECT source_path FROM books WHERE id = 'book-1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(new.resolve(&source_path), new.source_path("book-1", "txt"));
+
+        // Voice samples follow the move
+        let sample_path: String = new_db
+            .connection()
+            .lock()
+            .unwrap()
+            .query_row("SELECT sample_path FROM voices WHERE id = 'voice_1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(PathBuf::from(sample_path), new.voice_sample_path("voice_1", "wav"));
+
+        // The old directory is untouched, and the new one is no longer empty
+        assert!(old.source_path("book-1", "txt").exists());
+        assert!(validate_new_root(&old.root, &new.root).is_err());
+    }
 }
@@ -3,31 +3,73 @@
 //! Commands for syncing books and progress between desktop and mobile devices
 //! over local WiFi.
 
-use std::collections::HashMap;
-use std::io::Read as IoRead;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read as IoRead, Seek, SeekFrom};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use axum::extract::{Path as AxumPath, State as AxumState};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path as AxumPath, Request, State as AxumState};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::Json;
 use axum::Router;
+use futures_util::StreamExt;
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
 use serde::{Deserialize, Serialize};
-use tauri::{Emitter, State};
-use tower_http::cors::{Any, CorsLayer};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::tungstenite;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use uuid::Uuid;
 
-use crate::models::{Book, BookId, NarrationStatus, SourceFormat};
-use crate::storage::AppPaths;
+use super::bundle::{
+    audio_entry_name, bundle_segments, ensure_narration_exportable, import_bundle_archive,
+    query_content_hash, write_bundle_assets, BundleSegment,
+};
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::progress::ProgressThrottle;
+use super::reader::{
+    emit_book_finished, mark_finished, query_progress, query_segments, read_segment_audio,
+};
+use crate::models::{
+    Book, BookId, BookMetadata, NarrationMeta, NarrationStatus, Progress, SegmentId, SourceFormat,
+};
+use crate::services::parser::ZIP_MAGIC;
+use crate::services::tls::{PinnedCertVerifier, ServerIdentity};
+use crate::storage::{AppPaths, NarrationCodec};
 use crate::AppState;
 
 /// Service type for mDNS discovery.
 const MDNS_SERVICE_TYPE: &str = "_actualreader._tcp.local.";
 
+/// mDNS TXT key carrying the SHA-256 fingerprint of the server's certificate.
+const MDNS_FINGERPRINT_KEY: &str = "fp";
+
+/// Longest random pause before checking the network for our mDNS name, so
+/// instances started together don't all see it free.
+const MDNS_REGISTER_JITTER_MS: u64 = 250;
+
+/// How long to browse for servers already advertised before registering.
+const MDNS_CLASH_CHECK: Duration = Duration::from_millis(750);
+
+/// Number of instance names tried before giving up on registration.
+const MDNS_NAME_ATTEMPTS: u32 = 5;
+
+/// Number of times `connect_to_server` probes /info before giving up.
+const CONNECT_ATTEMPTS: u32 = 3;
+
+/// Pause between failed connection attempts.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Shortest and longest pause before reconnecting to a server's live updates.
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
 /// Information about a discovered sync server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -40,6 +82,265 @@ pub struct SyncServer {
     pub port: u16,
     /// Number of books available on the server.
     pub book_count: Option<u32>,
+    /// Fingerprint of the server's TLS certificate, which clients pin.
+    /// None if the server speaks plain HTTP.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+impl SyncServer {
+    /// URL of an endpoint on the server, over HTTPS if it has a certificate.
+    fn url(&self, path: &str) -> String {
+        server_url(&self.address, self.port, self.fingerprint.is_some(), path)
+    }
+
+    /// URL of the server's WebSocket, over TLS if it has a certificate.
+    fn ws_url(&self) -> String {
+        let scheme = if self.fingerprint.is_some() { "wss" } else { "ws" };
+        format!("{}://{}:{}/ws", scheme, self.address, self.port)
+    }
+}
+
+/// Web origins allowed to call the sync server, from the `syncCorsOrigins`
+/// setting.
+#[derive(Debug, Clone, PartialEq)]
+enum CorsOrigins {
+    /// "*": any website may call the server.
+    Any,
+    /// Only these origins; one without a port matches it on any port.
+    List(Vec<String>),
+}
+
+impl CorsOrigins {
+    fn parse(setting: &str) -> Self {
+        if setting.trim() == "*" {
+            return Self::Any;
+        }
+        Self::List(
+            setting
+                .split(',')
+                .map(|origin| origin.trim().trim_end_matches('/').to_lowercase())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Whether a request's `Origin` header is allowed.
+    fn allows(&self, origin: &str) -> bool {
+        let allowed = match self {
+            Self::Any => return true,
+            Self::List(allowed) => allowed,
+        };
+        let origin = origin.to_lowercase();
+        allowed.iter().any(|entry| match origin.strip_prefix(entry.as_str()) {
+            Some("") => true,
+            Some(port) => port
+                .strip_prefix(':')
+                .is_some_and(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit())),
+            None => false,
+        })
+    }
+
+    fn allow_origin(&self) -> AllowOrigin {
+        match self {
+            Self::Any => AllowOrigin::any(),
+            Self::List(_) => {
+                let origins = self.clone();
+                AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+                    origin.to_str().is_ok_and(|origin| origins.allows(origin))
+                })
+            }
+        }
+    }
+}
+
+/// Refuse browser requests from origins outside the allowlist.
+///
+/// CORS alone only hides responses from the page; this stops a foreign page
+/// from reaching the handlers at all. Requests without an `Origin` header,
+/// such as those from other devices syncing, pass through.
+async fn reject_foreign_origin(origins: &CorsOrigins, request: Request, next: Next) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        if !origin.to_str().is_ok_and(|origin| origins.allows(origin)) {
+            log::warn!("Sync server rejected request from origin {:?}", origin);
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Record that the sync server handled a request, for the idle timeout.
+///
+/// Marked again when the response is ready, so a long download isn't taken
+/// for idleness.
+async fn record_activity(
+    AxumState(state): AxumState<SyncServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    state.touch();
+    let response = next.run(request).await;
+    state.touch();
+    response
+}
+
+/// Build the sync server's routes, with CORS limited to `origins`.
+///
+/// Path parameters use axum 0.7's `:name` syntax; `{name}` would only match
+/// those literal characters.
+fn sync_router(sync_state: SyncServerState, origins: CorsOrigins) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(origins.allow_origin())
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    Router::new()
+        .route("/info", get(handle_get_info))
+        .route("/books", get(handle_get_books))
+        .route("/book/:id", get(handle_get_book))
+        .route("/book/:id/audio", get(handle_get_book_audio))
+        .route("/book/:id/markers", get(handle_get_book_markers))
+        .route("/book/:id/segment/:segment_id/audio", get(handle_get_segment_audio))
+        .route(
+            "/book/:id/progress",
+            get(handle_get_book_progress).post(handle_post_book_progress),
+        )
+        .route("/book/:id/segments", get(handle_get_book_segments))
+        .route("/ws", get(handle_ws))
+        .layer(axum::middleware::from_fn_with_state(sync_state.clone(), record_activity))
+        .layer(cors)
+        .layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+            let origins = origins.clone();
+            async move { reject_foreign_origin(&origins, request, next).await }
+        }))
+        .with_state(sync_state)
+}
+
+/// Why the sync server stopped, as sent in the `sync_server_stopped` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncServerStopReason {
+    /// `stop_sync_server` was called.
+    Requested,
+    /// No request arrived within the `syncServerIdleTimeoutMins` setting.
+    Idle,
+}
+
+/// Payload of the `sync_server_stopped` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncServerStopped {
+    pub reason: SyncServerStopReason,
+}
+
+/// Time left before a server last used at `last_request` counts as idle,
+/// or None once it does.
+fn idle_remaining(last_request: Instant, timeout: Duration, now: Instant) -> Option<Duration> {
+    timeout
+        .checked_sub(now.saturating_duration_since(last_request))
+        .filter(|remaining| !remaining.is_zero())
+}
+
+/// Stop the sync server registered as `service_fullname` once it has gone
+/// `timeout` without a request.
+///
+/// Returns without stopping anything if that server was stopped or replaced
+/// in the meantime.
+async fn stop_when_idle(
+    app: AppHandle,
+    last_request: Arc<std::sync::Mutex<Instant>>,
+    timeout: Duration,
+    service_fullname: String,
+) {
+    let last_used = || *last_request.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        if let Some(remaining) = idle_remaining(last_used(), timeout, Instant::now()) {
+            tokio::time::sleep(remaining).await;
+            continue;
+        }
+
+        let state = app.state::<AppState>();
+        let mut server_guard = state.sync_server.write().await;
+        if server_guard.as_ref().map(|handle| &handle.service_fullname) != Some(&service_fullname) {
+            return;
+        }
+        // A request may have arrived while waiting for the lock
+        if idle_remaining(last_used(), timeout, Instant::now()).is_some() {
+            continue;
+        }
+
+        if let Some(handle) = server_guard.take() {
+            match handle.shutdown() {
+                Ok(()) => log::info!(
+                    "Sync server stopped after {} minute(s) without requests",
+                    timeout.as_secs() / 60
+                ),
+                Err(e) => log::error!("Failed to stop idle sync server: {}", e),
+            }
+            let _ = app.emit(
+                "sync_server_stopped",
+                SyncServerStopped {
+                    reason: SyncServerStopReason::Idle,
+                },
+            );
+        }
+        return;
+    }
+}
+
+/// Sync servers found by browsing the network.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDiscovery {
+    /// Servers resolved before browsing ended.
+    pub servers: Vec<SyncServer>,
+    /// Browsing failed before the timeout, so servers may be missing and
+    /// connecting manually may still work.
+    pub partial: bool,
+}
+
+/// A sync server saved for reconnecting without discovery.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownServer {
+    /// Device name, also used to find the server again over mDNS.
+    pub name: String,
+    /// Last known IP address of the server.
+    pub address: String,
+    /// Last known port of the server.
+    pub port: u16,
+    /// Unix timestamp of the last successful sync.
+    pub last_seen: Option<i64>,
+    /// Pairing token for the server, if one was issued.
+    pub token: Option<String>,
+    /// Pinned fingerprint of the server's TLS certificate.
+    pub fingerprint: Option<String>,
+}
+
+// Written by hand so the pairing token never ends up in a log line.
+impl std::fmt::Debug for KnownServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KnownServer")
+            .field("name", &self.name)
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("last_seen", &self.last_seen)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("fingerprint", &self.fingerprint)
+            .finish()
+    }
+}
+
+impl From<KnownServer> for SyncServer {
+    fn from(server: KnownServer) -> Self {
+        SyncServer {
+            name: server.name,
+            address: server.address,
+            port: server.port,
+            book_count: None,
+            fingerprint: server.fingerprint,
+        }
+    }
 }
 
 /// Result of a sync operation.
@@ -66,6 +367,10 @@ pub struct ServerInfo {
     pub version: String,
     /// Identifier for Actual Reader servers.
     pub server_type: String,
+    /// Version of the /books list, also sent as its ETag; changes whenever
+    /// the list does. Missing from servers that predate it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub books_version: Option<String>,
 }
 
 /// Book info for the book list endpoint.
@@ -82,6 +387,207 @@ pub struct BookInfo {
     pub source_format: String,
     /// Whether the book has narration.
     pub has_narration: bool,
+    /// SHA-256 of the book's text, for recognizing a copy under another ID.
+    /// Missing from servers that predate it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Size of the book's narration audio in bytes, which makes up nearly
+    /// all of its bundle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// What syncing with a server would transfer, as returned by `preview_sync`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPreview {
+    /// Narrated books on the server that would be downloaded.
+    pub to_add: Vec<BookInfo>,
+    /// Books on the server that this library already has, by ID or text.
+    pub already_present: Vec<BookInfo>,
+}
+
+/// IDs and text hashes of the local library, for comparing with a server's.
+#[derive(Debug, Default)]
+struct LocalLibrary {
+    ids: HashSet<String>,
+    content_hashes: HashSet<String>,
+}
+
+impl LocalLibrary {
+    fn load(conn: &rusqlite::Connection) -> CommandResult<Self> {
+        let ids: Vec<String> = conn
+            .prepare("SELECT id FROM books")
+            .context("Failed to query local books")?
+            .query_map([], |row| row.get(0))
+            .context("Failed to read books")?
+            .collect::<Result<_, _>>()
+            .context("Failed to read book row")?;
+
+        let mut local = Self::default();
+        for id in ids {
+            local.content_hashes.insert(query_content_hash(conn, &id)?);
+            local.ids.insert(id);
+        }
+        Ok(local)
+    }
+
+    /// True if the library has the book under the same ID or the same text.
+    fn contains(&self, book: &BookInfo) -> bool {
+        self.ids.contains(&book.id)
+            || book
+                .content_hash
+                .as_ref()
+                .is_some_and(|hash| self.content_hashes.contains(hash))
+    }
+
+    /// Split a server's books into those to download and those already here.
+    /// Books without narration are left out, as they can't be transferred.
+    fn preview(&self, books: Vec<BookInfo>) -> SyncPreview {
+        let (already_present, to_add) = books
+            .into_iter()
+            .filter(|book| book.has_narration)
+            .partition(|book| self.contains(book));
+        SyncPreview {
+            to_add,
+            already_present,
+        }
+    }
+}
+
+/// A change pushed to clients connected to GET /ws, as JSON tagged by `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SyncUpdate {
+    /// A book's reading progress changed on the server.
+    Progress(Progress),
+    /// Books the server lists may have changed; fetch /books again.
+    LibraryChanged,
+    /// Updates were dropped because the client fell behind; fetch again
+    /// whatever it shows.
+    Resync,
+}
+
+/// Push a book's current progress to WebSocket sync clients, if any are
+/// connected.
+pub(crate) fn broadcast_progress(
+    updates: &broadcast::Sender<SyncUpdate>,
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) {
+    if updates.receiver_count() == 0 {
+        return;
+    }
+    match query_progress(conn, book_id) {
+        Ok(Some(progress)) => {
+            let _ = updates.send(SyncUpdate::Progress(progress));
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("book={}: failed to read progress to broadcast: {}", book_id, e),
+    }
+}
+
+/// Progress update accepted by POST /book/{id}/progress.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressUpdate {
+    /// Current segment index (0-based).
+    pub segment_index: u32,
+    /// Position in narration (seconds), None if no narration.
+    pub audio_time: Option<f64>,
+    /// Furthest segment index reached on the client; defaults to the current one.
+    #[serde(default)]
+    pub max_segment_index: Option<u32>,
+    /// Furthest narration position reached on the client; defaults to the current one.
+    #[serde(default)]
+    pub max_audio_time: Option<f64>,
+    /// When the client saved this position.
+    pub updated_at: i64,
+}
+
+/// Why probing a sync server's /info endpoint failed.
+#[derive(Debug)]
+enum ProbeError {
+    /// No response within the timeout.
+    TimedOut(Duration),
+    /// The connection could not be established.
+    Refused(String),
+    /// The server answered over TLS but the handshake failed, e.g. because
+    /// its certificate isn't the pinned one.
+    Tls(String),
+    /// Something answered, but it isn't an Actual Reader sync server.
+    NotActualReader(String),
+}
+
+impl ProbeError {
+    /// Whether another attempt might succeed.
+    fn is_retryable(&self) -> bool {
+        !matches!(self, Self::NotActualReader(_) | Self::Tls(_))
+    }
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TimedOut(timeout) => write!(
+                f,
+                "Connection timed out after {} ms",
+                timeout.as_millis()
+            ),
+            Self::Refused(reason) => write!(f, "Connection refused: {}", reason),
+            Self::Tls(reason) => write!(f, "Secure connection failed: {}", reason),
+            Self::NotActualReader(reason) => {
+                write!(f, "Not an Actual Reader server: {}", reason)
+            }
+        }
+    }
+}
+
+impl From<ProbeError> for CommandError {
+    fn from(e: ProbeError) -> Self {
+        match e {
+            ProbeError::NotActualReader(_) => CommandError::InvalidInput(e.to_string()),
+            _ => CommandError::ServiceUnavailable(e.to_string()),
+        }
+    }
+}
+
+/// Get the current Unix timestamp in seconds.
+fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// URL of an endpoint on a sync server.
+fn server_url(address: &str, port: u16, tls: bool, path: &str) -> String {
+    let scheme = if tls { "https" } else { "http" };
+    format!("{}://{}:{}{}", scheme, address, port, path)
+}
+
+/// Build an HTTP client for talking to sync servers.
+///
+/// With a verifier, HTTPS certificates are checked against its pinned
+/// fingerprint rather than a CA, since sync servers sign their own.
+fn sync_client(
+    verifier: Option<Arc<PinnedCertVerifier>>,
+    timeout: Duration,
+) -> CommandResult<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+    if let Some(verifier) = verifier {
+        builder = builder.use_preconfigured_tls(verifier.client_config()?);
+    }
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// Build an HTTP client for a server, pinned to its certificate if it has one.
+fn client_for(server: &SyncServer, timeout: Duration) -> CommandResult<reqwest::Client> {
+    let verifier = server
+        .fingerprint
+        .as_ref()
+        .map(|fp| Arc::new(PinnedCertVerifier::new(Some(fp.clone()))));
+    sync_client(verifier, timeout)
 }
 
 /// Shared state for the sync HTTP server.
@@ -90,17 +596,88 @@ struct SyncServerState {
     db: Arc<crate::storage::Database>,
     paths: AppPaths,
     server_name: String,
+    /// When the server last handled a request.
+    last_request: Arc<std::sync::Mutex<Instant>>,
+    /// Changes to push to WebSocket clients.
+    updates: broadcast::Sender<SyncUpdate>,
+    /// Set when the server stops, so open WebSockets close rather than
+    /// holding up a graceful shutdown.
+    stopping: watch::Receiver<bool>,
+    /// App to tell when a client's progress finishes a book, if any.
+    app: Option<AppHandle>,
+}
+
+impl SyncServerState {
+    /// Mark the server as used now.
+    fn touch(&self) {
+        *self.last_request.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+}
+
+/// Merge a client's progress for a book into the local progress row.
+///
+/// The most recently saved current position wins; the furthest position
+/// takes the maximum of both sides. Returns the merged progress with the
+/// time the book was finished if this merge finished it, or None if the book
+/// doesn't exist.
+fn merge_progress(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    update: &ProgressUpdate,
+) -> CommandResult<Option<(Progress, Option<i64>)>> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?)",
+            rusqlite::params![book_id.as_str()],
+            |row| row.get(0),
+        )
+        .context("Failed to look up book")?;
+    if !exists {
+        return Ok(None);
+    }
+
+    conn.execute(
+        "INSERT INTO progress (book_id, segment_index, audio_time, max_segment_index, max_audio_time, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(book_id) DO UPDATE SET
+             segment_index = CASE WHEN excluded.updated_at > updated_at
+                                  THEN excluded.segment_index ELSE segment_index END,
+             audio_time = CASE WHEN excluded.updated_at > updated_at
+                               THEN excluded.audio_time ELSE audio_time END,
+             max_segment_index = MAX(max_segment_index, excluded.max_segment_index),
+             max_audio_time = MAX(COALESCE(max_audio_time, excluded.max_audio_time),
+                                  COALESCE(excluded.max_audio_time, max_audio_time)),
+             updated_at = MAX(updated_at, excluded.updated_at)",
+        rusqlite::params![
+            book_id.as_str(),
+            update.segment_index,
+            update.audio_time,
+            update.max_segment_index.unwrap_or(update.segment_index),
+            update.max_audio_time.or(update.audio_time),
+            update.updated_at,
+        ],
+    )
+    .context("Failed to merge progress")?;
+
+    // A book finished on the client is finished here too
+    let Some(progress) = query_progress(conn, book_id)? else {
+        return Ok(None);
+    };
+    let now = current_timestamp();
+    let finished =
+        mark_finished(conn, book_id, progress.max_segment_index, progress.max_audio_time, now)?;
+    Ok(Some((progress, finished.then_some(now))))
 }
 
 /// Get information about the sync server.
 async fn handle_get_info(AxumState(state): AxumState<SyncServerState>) -> impl IntoResponse {
-    let book_count = match get_narrated_book_count(&state) {
-        Ok(count) => count,
+    let (book_count, books_version) = match get_narrated_summary(&state) {
+        Ok(summary) => summary,
         Err(e) => {
             log::error!("Failed to get book count: {}", e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e})),
+                Json(serde_json::json!({"error": e.to_string()})),
             );
         }
     };
@@ -112,53 +689,112 @@ async fn handle_get_info(AxumState(state): AxumState<SyncServerState>) -> impl I
             book_count,
             version: env!("CARGO_PKG_VERSION").to_string(),
             server_type: "actual-reader".to_string(),
+            books_version: Some(books_version),
         })),
     )
 }
 
-/// Get count of books with narration.
-fn get_narrated_book_count(state: &SyncServerState) -> Result<u32, String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+/// Get count of books with narration, and the version of their list.
+fn get_narrated_summary(state: &SyncServerState) -> CommandResult<(u32, String)> {
+    let conn = state.db.connection().lock()?;
 
     let count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM books WHERE narration_status = 'ready'",
-            [],
-            |row| row.get(0),
+        .prepare_cached("SELECT COUNT(*) FROM books WHERE narration_status IN ('ready', 'stale')")
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)))
+        .context("Failed to count books")?;
+
+    Ok((count as u32, narrated_books_version(&conn)?))
+}
+
+/// Version of the book list served at GET /books: a hash of each listed
+/// book's ID and `updated_at`, so it changes whenever the list does.
+fn narrated_books_version(conn: &rusqlite::Connection) -> CommandResult<String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, updated_at FROM books WHERE narration_status IN ('ready', 'stale') ORDER BY id",
         )
-        .map_err(|e| format!("Failed to count books: {}", e))?;
+        .context("Failed to prepare query")?;
+    let mut rows = stmt.query([]).context("Failed to query books")?;
+
+    let mut hasher = Sha256::new();
+    while let Some(row) = rows.next().context("Failed to read book row")? {
+        let id: String = row.get(0).context("Failed to read book row")?;
+        let updated_at: i64 = row.get(1).context("Failed to read book row")?;
+        hasher.update(id.as_bytes());
+        hasher.update([0]);
+        hasher.update(updated_at.to_le_bytes());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    Ok(count as u32)
+/// Whether an `If-None-Match` header names the entity tag of `version`.
+fn etag_matches(if_none_match: &str, version: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|tag| {
+        tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == version
+    })
 }
 
 /// Get list of books available for sync.
-async fn handle_get_books(AxumState(state): AxumState<SyncServerState>) -> impl IntoResponse {
+///
+/// The response's ETag is the list's version. A request whose
+/// `If-None-Match` names it gets 304 Not Modified without the list.
+async fn handle_get_books(
+    AxumState(state): AxumState<SyncServerState>,
+    headers: HeaderMap,
+) -> Response {
+    let error_response = |e: CommandError| {
+        log::error!("Failed to get books: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": e.to_string()})),
+        )
+            .into_response()
+    };
+
+    // Read before the list, so a change in between leaves the ETag stale
+    // rather than claiming a list the client never saw
+    let version = match state
+        .db
+        .connection()
+        .lock()
+        .map_err(CommandError::from)
+        .and_then(|conn| narrated_books_version(&conn))
+    {
+        Ok(version) => version,
+        Err(e) => return error_response(e),
+    };
+    let etag = [(header::ETAG, format!("\"{}\"", version))];
+
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &version));
+    if unchanged {
+        return (StatusCode::NOT_MODIFIED, etag).into_response();
+    }
+
     let books = match get_narrated_books(&state) {
         Ok(books) => books,
-        Err(e) => {
-            log::error!("Failed to get books: {}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e})),
-            );
-        }
+        Err(e) => return error_response(e),
     };
 
-    (StatusCode::OK, Json(serde_json::json!({ "books": books })))
+    (StatusCode::OK, etag, Json(serde_json::json!({ "books": books }))).into_response()
 }
 
-/// Get all books with narration ready.
-fn get_narrated_books(state: &SyncServerState) -> Result<Vec<BookInfo>, String> {
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+/// Get all books with whole-book narration, including narration that is
+/// stale from later edits to the text, which is served as it is.
+fn get_narrated_books(state: &SyncServerState) -> CommandResult<Vec<BookInfo>> {
+    let conn = state.db.connection().lock()?;
 
     let mut stmt = conn
-        .prepare(
+        .prepare_cached(
             "SELECT id, title, author, source_format, narration_status
              FROM books
-             WHERE narration_status = 'ready'
+             WHERE narration_status IN ('ready', 'stale')
              ORDER BY title",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .context("Failed to prepare query")?;
 
     let books = stmt
         .query_map([], |row| {
@@ -167,14 +803,31 @@ fn get_narrated_books(state: &SyncServerState) -> Result<Vec<BookInfo>, String>
                 title: row.get(1)?,
                 author: row.get(2)?,
                 source_format: row.get(3)?,
-                has_narration: row.get::<_, String>(4)? == "ready",
+                has_narration: matches!(row.get::<_, String>(4)?.as_str(), "ready" | "stale"),
+                content_hash: None,
+                size: None,
             })
         })
-        .map_err(|e| format!("Failed to query books: {}", e))?
+        .context("Failed to query books")?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read book row: {}", e))?;
-
-    Ok(books)
+        .context("Failed to read book row")?;
+
+    books
+        .into_iter()
+        .map(|book| {
+            let content_hash = query_content_hash(&conn, &book.id)?;
+            let size = state
+                .paths
+                .find_narration_audio(&book.id)
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len());
+            Ok(BookInfo {
+                content_hash: Some(content_hash),
+                size,
+                ..book
+            })
+        })
+        .collect()
 }
 
 /// Download a book as an .actualbook bundle.
@@ -183,10 +836,10 @@ async fn handle_get_book(
     AxumState(state): AxumState<SyncServerState>,
 ) -> impl IntoResponse {
     // Create the bundle in memory
-    let bundle_data = match create_book_bundle(&state, &book_id) {
-        Ok(data) => data,
+    let (bundle_data, file_name) = match create_book_bundle(&state, &book_id) {
+        Ok(bundle) => bundle,
         Err(e) => {
-            log::error!("Failed to create bundle for book {}: {}", book_id, e);
+            log::error!("book={}: failed to create bundle: {}", book_id, e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 vec![],
@@ -199,159 +852,497 @@ async fn handle_get_book(
         StatusCode::OK,
         [
             ("content-type", "application/octet-stream"),
-            (
-                "content-disposition",
-                &format!("attachment; filename=\"{}.actualbook\"", book_id),
-            ),
+            ("content-disposition", &content_disposition(&file_name)),
         ],
         bundle_data,
     )
         .into_response()
 }
 
-/// Create an .actualbook bundle for a book.
-fn create_book_bundle(state: &SyncServerState, book_id: &str) -> Result<Vec<u8>, String> {
-    use std::io::Write;
-    use zip::write::SimpleFileOptions;
-    use zip::ZipWriter;
-
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
-
-    // 1. Get book metadata
-    let book: Book = conn
-        .query_row(
-            "SELECT id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at
-             FROM books WHERE id = ?1",
-            [book_id],
-            |row| {
-                let source_format_str: String = row.get(3)?;
-                let narration_status_str: String = row.get(5)?;
-                Ok(Book {
-                    id: BookId::new(row.get::<_, String>(0)?),
-                    title: row.get(1)?,
-                    author: row.get(2)?,
-                    source_format: SourceFormat::from_str(&source_format_str).unwrap_or(SourceFormat::Txt),
-                    source_path: row.get(4)?,
-                    narration_status: NarrationStatus::from_str(&narration_status_str).unwrap_or(NarrationStatus::None),
-                    narration_path: row.get(6)?,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                    last_opened_at: row.get(9)?,
-                })
-            },
-        )
-        .map_err(|e| format!("Book not found: {}", e))?;
-
-    // Check if narration is ready
-    if book.narration_status != NarrationStatus::Ready {
-        return Err("Book does not have narration ready".to_string());
-    }
-
-    // 2. Get segments
-    let segments: Vec<serde_json::Value> = {
-        let mut stmt = conn
-            .prepare("SELECT id, idx, content, html FROM segments WHERE book_id = ?1 ORDER BY idx")
-            .map_err(|e| format!("Failed to prepare segments query: {}", e))?;
-
-        let result = stmt.query_map([book_id], |row| {
-            Ok(serde_json::json!({
-                "id": row.get::<_, String>(0)?,
-                "index": row.get::<_, i64>(1)?,
-                "content": row.get::<_, String>(2)?,
-                "html": row.get::<_, Option<String>>(3)?
-            }))
+/// `Content-Disposition` for downloading a file as `file_name`, with an
+/// ASCII fallback for clients that don't read the UTF-8 `filename*`.
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii_graphic() || c == ' ' => c,
+            _ => '_',
         })
-        .map_err(|e| format!("Failed to query segments: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read segment: {}", e))?;
-        result
-    };
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
 
-    // 3. Get markers
-    let markers: Vec<serde_json::Value> = {
-        let mut stmt = conn
-            .prepare("SELECT segment_id, start_time, end_time FROM markers WHERE book_id = ?1 ORDER BY start_time")
-            .map_err(|e| format!("Failed to prepare markers query: {}", e))?;
+/// Query a book's segments in the JSON shape used by bundles.
+fn query_segments_json(
+    conn: &rusqlite::Connection,
+    book_id: &str,
+) -> CommandResult<Vec<BundleSegment>> {
+    Ok(bundle_segments(&query_segments(conn, book_id)?))
+}
+
+/// Query a book's markers in the JSON shape used by bundles.
+fn query_markers_json(
+    conn: &rusqlite::Connection,
+    book_id: &str,
+) -> CommandResult<Vec<serde_json::Value>> {
+    let mut stmt = conn
+        .prepare_cached("SELECT segment_id, start_time, end_time FROM markers WHERE book_id = ?1 ORDER BY start_time")
+        .context("Failed to prepare markers query")?;
 
-        let result = stmt.query_map([book_id], |row| {
+    let result = stmt
+        .query_map([book_id], |row| {
             Ok(serde_json::json!({
                 "segment_id": row.get::<_, String>(0)?,
                 "start": row.get::<_, f64>(1)?,
                 "end": row.get::<_, f64>(2)?
             }))
         })
-        .map_err(|e| format!("Failed to query markers: {}", e))?
+        .context("Failed to query markers")?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read marker: {}", e))?;
-        result
+        .context("Failed to read marker")?;
+
+    Ok(result)
+}
+
+/// Parse a single-range `Range` header against a resource of `len` bytes.
+///
+/// Returns the inclusive byte range to serve, `Ok(None)` for a header this
+/// server ignores (serve the whole file), or `Err(())` if the range can't be
+/// satisfied.
+fn parse_byte_range(header: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
     };
 
-    // Calculate duration from markers
-    let duration: f64 = markers
-        .iter()
-        .filter_map(|m| m.get("end").and_then(|e| e.as_f64()))
-        .fold(0.0, f64::max);
+    // Multi-range requests are answered with the full file
+    if spec.contains(',') {
+        return Ok(None);
+    }
 
-    // 4. Create manifest
-    let manifest = serde_json::json!({
-        "version": "1.0",
-        "id": book.id.as_str(),
-        "title": book.title,
-        "author": book.author,
-        "source_format": book.source_format.as_str(),
-        "created_at": book.created_at,
-        "duration": duration,
-        "segment_count": segments.len()
-    });
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
 
-    // 5. Create ZIP archive in memory
-    let mut buffer = std::io::Cursor::new(Vec::new());
-    {
+    let range = match (start.trim(), end.trim()) {
+        // bytes=-N: the last N bytes
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().map_err(|_| ())?;
+            if suffix == 0 || len == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        // bytes=N-: from N to the end
+        (start, "") => {
+            let start: u64 = start.parse().map_err(|_| ())?;
+            (start, len.saturating_sub(1))
+        }
+        (start, end) => {
+            let start: u64 = start.parse().map_err(|_| ())?;
+            let end: u64 = end.parse().map_err(|_| ())?;
+            (start, end.min(len.saturating_sub(1)))
+        }
+    };
+
+    if range.0 >= len || range.0 > range.1 {
+        return Err(());
+    }
+
+    Ok(Some(range))
+}
+
+/// Content-Type for a narration audio file, based on its extension.
+fn audio_content_type(path: &std::path::Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("wav") => "audio/wav",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") | Some("opus") => "audio/ogg",
+        Some("m4a") | Some("aac") => "audio/mp4",
+        Some("flac") => "audio/flac",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Stream a book's narration audio, honoring HTTP `Range` requests.
+async fn handle_get_book_audio(
+    AxumPath(book_id): AxumPath<String>,
+    AxumState(state): AxumState<SyncServerState>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(audio_path) = state.paths.find_narration_audio(&book_id) else {
+        return (StatusCode::NOT_FOUND, "No narration audio for this book").into_response();
+    };
+
+    match read_audio_range(&audio_path, headers.get(header::RANGE)) {
+        Ok(response) => response,
+        Err(e) => {
+            log::error!("book={}: failed to stream audio: {}", book_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Get one segment's narration audio as a standalone WAV.
+async fn handle_get_segment_audio(
+    AxumPath((book_id, segment_id)): AxumPath<(String, String)>,
+    AxumState(state): AxumState<SyncServerState>,
+) -> Response {
+    let audio = read_segment_audio(
+        &state.db,
+        &state.paths,
+        &BookId::new(book_id.clone()),
+        &SegmentId::new(segment_id.clone()),
+    );
+
+    match audio {
+        Ok(data) => (StatusCode::OK, [(header::CONTENT_TYPE, "audio/wav")], data).into_response(),
+        Err(CommandError::NotFound(message)) => (StatusCode::NOT_FOUND, message).into_response(),
+        Err(CommandError::InvalidInput(message)) => {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, message).into_response()
+        }
+        Err(e) => {
+            log::error!("book={} segment_id={}: failed to read segment audio: {}", book_id, segment_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Build the full or partial response for an audio file.
+fn read_audio_range(
+    path: &std::path::Path,
+    range: Option<&axum::http::HeaderValue>,
+) -> CommandResult<Response> {
+    let mut file =
+        std::fs::File::open(path).context("Failed to open audio file")?;
+    let len = file
+        .metadata()
+        .context("Failed to read audio metadata")?
+        .len();
+    let content_type = audio_content_type(path);
+
+    let range = match range.and_then(|value| value.to_str().ok()) {
+        Some(value) => match parse_byte_range(value, len) {
+            Ok(range) => range,
+            Err(()) => {
+                return Ok((
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+                )
+                    .into_response());
+            }
+        },
+        None => None,
+    };
+
+    let Some((start, end)) = range else {
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data).context("Failed to read audio file")?;
+        return Ok((
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (header::ACCEPT_RANGES, "bytes".to_string()),
+            ],
+            data,
+        )
+            .into_response());
+    };
+
+    let mut data = vec![0u8; (end - start + 1) as usize];
+    file.seek(SeekFrom::Start(start))
+        .and_then(|_| file.read_exact(&mut data))
+        .context("Failed to read audio range")?;
+
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len)),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+/// Get a book's reading progress so a client can continue where it left off.
+async fn handle_get_book_progress(
+    AxumPath(book_id): AxumPath<String>,
+    AxumState(state): AxumState<SyncServerState>,
+) -> impl IntoResponse {
+    let progress = state
+        .db
+        .connection()
+        .lock()
+        .map_err(CommandError::from)
+        .and_then(|conn| query_progress(&conn, &BookId::new(book_id)));
+
+    match progress {
+        Ok(Some(progress)) => (StatusCode::OK, Json(serde_json::json!(progress))),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "No progress for this book"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": e.to_string()})),
+        ),
+    }
+}
+
+/// Merge a client's progress for a book, returning the merged result.
+async fn handle_post_book_progress(
+    AxumPath(book_id): AxumPath<String>,
+    AxumState(state): AxumState<SyncServerState>,
+    Json(update): Json<ProgressUpdate>,
+) -> impl IntoResponse {
+    let progress = state
+        .db
+        .connection()
+        .lock()
+        .map_err(CommandError::from)
+        .and_then(|conn| merge_progress(&conn, &BookId::new(book_id.clone()), &update));
+
+    match progress {
+        Ok(Some((progress, finished_at))) => {
+            if let (Some(app), Some(finished_at)) = (&state.app, finished_at) {
+                emit_book_finished(app, &progress.book_id, finished_at);
+            }
+            let _ = state.updates.send(SyncUpdate::Progress(progress.clone()));
+            log::debug!(
+                "book={} segment={}: merged remote progress",
+                book_id, progress.segment_index
+            );
+            (StatusCode::OK, Json(serde_json::json!(progress)))
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Book not found"})),
+        ),
+        Err(e) => {
+            log::error!("book={}: failed to merge progress: {}", book_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+        }
+    }
+}
+
+/// Upgrade to a WebSocket that pushes progress and library changes.
+async fn handle_ws(
+    ws: WebSocketUpgrade,
+    AxumState(state): AxumState<SyncServerState>,
+) -> Response {
+    let updates = state.updates.subscribe();
+    ws.on_upgrade(move |socket| push_updates(socket, updates, state))
+}
+
+/// Send each update to a WebSocket client until it disconnects or the
+/// server stops. Updates count as activity for the idle timeout.
+async fn push_updates(
+    mut socket: WebSocket,
+    mut updates: broadcast::Receiver<SyncUpdate>,
+    state: SyncServerState,
+) {
+    let mut stopping = state.stopping.clone();
+    loop {
+        let update = tokio::select! {
+            update = updates.recv() => update,
+            message = socket.recv() => match message {
+                // Pings are answered by axum, and clients send nothing else
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            _ = stopping.changed() => break,
+        };
+        let update = match update {
+            Ok(update) => update,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::debug!("WebSocket client fell behind by {} update(s)", skipped);
+                SyncUpdate::Resync
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        state.touch();
+        let text = match serde_json::to_string(&update) {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Failed to serialize sync update: {}", e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Get a book's narration markers for streaming playback.
+async fn handle_get_book_markers(
+    AxumPath(book_id): AxumPath<String>,
+    AxumState(state): AxumState<SyncServerState>,
+) -> impl IntoResponse {
+    let markers = state
+        .db
+        .connection()
+        .lock()
+        .map_err(CommandError::from)
+        .and_then(|conn| query_markers_json(&conn, &book_id));
+
+    match markers {
+        Ok(markers) => (StatusCode::OK, Json(serde_json::json!({ "markers": markers }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": e.to_string()})),
+        ),
+    }
+}
+
+/// Get a book's segments for streaming playback.
+async fn handle_get_book_segments(
+    AxumPath(book_id): AxumPath<String>,
+    AxumState(state): AxumState<SyncServerState>,
+) -> impl IntoResponse {
+    let segments = state
+        .db
+        .connection()
+        .lock()
+        .map_err(CommandError::from)
+        .and_then(|conn| query_segments_json(&conn, &book_id));
+
+    match segments {
+        Ok(segments) if segments.is_empty() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Book not found"})),
+        ),
+        Ok(segments) => (StatusCode::OK, Json(serde_json::json!({ "segments": segments }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": e.to_string()})),
+        ),
+    }
+}
+
+/// Create an .actualbook bundle for a book, with the file name to offer it as.
+fn create_book_bundle(state: &SyncServerState, book_id: &str) -> CommandResult<(Vec<u8>, String)> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    let conn = state.db.connection().lock()?;
+
+    // 1. Get book metadata
+    let book: Book = conn
+        .query_row(
+            "SELECT id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at, duration, language, finished_at,
+                    publisher, published_date, identifier, isbn, narration_meta, original_filename
+             FROM books WHERE id = ?1",
+            [book_id],
+            |row| {
+                let source_format_str: String = row.get(3)?;
+                let narration_status_str: String = row.get(5)?;
+                Ok(Book {
+                    id: BookId::new(row.get::<_, String>(0)?),
+                    title: row.get(1)?,
+                    author: row.get(2)?,
+                    source_format: SourceFormat::from_str(&source_format_str).unwrap_or(SourceFormat::Txt),
+                    source_path: row.get(4)?,
+                    narration_status: NarrationStatus::from_str(&narration_status_str).unwrap_or(NarrationStatus::None),
+                    narration_path: row.get(6)?,
+                    created_at: row.get(7)?,
+                    updated_at: row.get(8)?,
+                    last_opened_at: row.get(9)?,
+                    duration: row.get(10)?,
+                    language: row.get(11)?,
+                    finished_at: row.get(12)?,
+                    metadata: BookMetadata {
+                        publisher: row.get(13)?,
+                        published_date: row.get(14)?,
+                        identifier: row.get(15)?,
+                        isbn: row.get(16)?,
+                    },
+                    narration_meta: NarrationMeta::from_json(row.get::<_, Option<String>>(17)?.as_deref()),
+                    original_filename: row.get(18)?,
+                })
+            },
+        )
+        .context("Book not found")?;
+
+    ensure_narration_exportable(&book.id, book.narration_status)?;
+
+    // 2. Get segments
+    let segments = query_segments(&conn, book_id)?;
+
+    // 3. Get markers
+    let markers = query_markers_json(&conn, book_id)?;
+
+    // 4. Create manifest
+    let manifest = serde_json::json!({
+        "version": "1.0",
+        "id": book.id.as_str(),
+        "title": book.title,
+        "author": book.author,
+        "source_format": book.source_format.as_str(),
+        "created_at": book.created_at,
+        "updated_at": book.updated_at,
+        "duration": book.duration,
+        "segment_count": segments.len(),
+        "narration_meta": book.narration_meta,
+        "original_filename": book.original_filename
+    });
+
+    // 5. Create ZIP archive in memory
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    {
         let mut zip = ZipWriter::new(&mut buffer);
         let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
 
         // Write manifest.json
-        zip.start_file("manifest.json", options)
-            .map_err(|e| format!("Failed to create manifest.json: {}", e))?;
+        zip.start_file("manifest.json", options).context("Failed to create manifest.json")?;
         let manifest_bytes = serde_json::to_vec_pretty(&manifest)
-            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
-        zip.write_all(&manifest_bytes)
-            .map_err(|e| format!("Failed to write manifest: {}", e))?;
+            .context("Failed to serialize manifest")?;
+        zip.write_all(&manifest_bytes).context("Failed to write manifest")?;
 
         // Write content/segments.json
-        zip.start_file("content/segments.json", options)
-            .map_err(|e| format!("Failed to create segments.json: {}", e))?;
-        let segments_json = serde_json::json!({ "segments": segments });
+        zip.start_file("content/segments.json", options).context("Failed to create segments.json")?;
+        let segments_json = serde_json::json!({ "segments": bundle_segments(&segments) });
         let segments_bytes = serde_json::to_vec_pretty(&segments_json)
-            .map_err(|e| format!("Failed to serialize segments: {}", e))?;
-        zip.write_all(&segments_bytes)
-            .map_err(|e| format!("Failed to write segments: {}", e))?;
+            .context("Failed to serialize segments")?;
+        zip.write_all(&segments_bytes).context("Failed to write segments")?;
 
         // Write narration/markers.json
-        zip.start_file("narration/markers.json", options)
-            .map_err(|e| format!("Failed to create markers.json: {}", e))?;
+        zip.start_file("narration/markers.json", options).context("Failed to create markers.json")?;
         let markers_json = serde_json::json!({ "markers": markers });
         let markers_bytes = serde_json::to_vec_pretty(&markers_json)
-            .map_err(|e| format!("Failed to serialize markers: {}", e))?;
-        zip.write_all(&markers_bytes)
-            .map_err(|e| format!("Failed to write markers: {}", e))?;
-
-        // Write narration/audio.mp3 if it exists
-        let audio_path = state.paths.narration_audio_path(book_id);
-        if audio_path.exists() {
-            zip.start_file("narration/audio.mp3", options)
-                .map_err(|e| format!("Failed to create audio.mp3: {}", e))?;
-            let audio_data = std::fs::read(&audio_path)
-                .map_err(|e| format!("Failed to read audio file: {}", e))?;
-            zip.write_all(&audio_data)
-                .map_err(|e| format!("Failed to write audio: {}", e))?;
+            .context("Failed to serialize markers")?;
+        zip.write_all(&markers_bytes).context("Failed to write markers")?;
+
+        // Write assets/ for image segments
+        write_bundle_assets(&mut zip, "", &state.paths, &segments)?;
+
+        // Write the narration audio if it exists
+        if let Some(audio_path) = state.paths.find_narration_audio(book_id) {
+            let codec = NarrationCodec::from_path(&audio_path).unwrap_or(NarrationCodec::Wav);
+            zip.start_file(audio_entry_name(codec), options).context("Failed to create audio entry")?;
+            let audio_data = std::fs::read(&audio_path).context("Failed to read audio file")?;
+            zip.write_all(&audio_data).context("Failed to write audio")?;
         }
 
-        zip.finish()
-            .map_err(|e| format!("Failed to finish ZIP: {}", e))?;
+        zip.finish().context("Failed to finish ZIP")?;
     }
 
-    Ok(buffer.into_inner())
+    Ok((buffer.into_inner(), book.export_file_name("actualbook")))
 }
 
 /// Get the local IP address to bind to.
@@ -379,24 +1370,63 @@ fn get_server_name() -> String {
 /// Start the sync server (desktop only).
 ///
 /// Starts an HTTP server on the local network that mobile devices can connect to.
+/// Unless the `syncTls` setting is off, it serves HTTPS with a self-signed
+/// certificate kept in the data directory, whose fingerprint is advertised
+/// over mDNS for clients to pin.
 /// The server provides:
 /// - mDNS discovery (automatic)
 /// - Book list endpoint
 /// - Bundle download endpoints
 /// - Progress sync endpoint
+/// - Live progress and library updates over WebSocket (`/ws`)
+///
+/// The server stops itself after the `syncServerIdleTimeoutMins` setting
+/// passes without a request.
 #[tauri::command]
-pub async fn start_sync_server(state: State<'_, AppState>) -> Result<SyncServer, String> {
+pub async fn start_sync_server(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<SyncServer> {
+    serve_sync(app, &state).await
+}
+
+/// Start the sync server when the app launches, if the `autoStartSyncServer`
+/// setting asks to. It stops once idle like one started with
+/// [`start_sync_server`]. Failing to start, e.g. because the port is in use,
+/// is logged rather than stopping the app.
+pub async fn auto_start_sync_server(app: AppHandle) {
+    let state = app.state::<AppState>();
+    match super::settings::load_settings(&state.db) {
+        Ok(settings) if settings.auto_start_sync_server => {}
+        Ok(_) => return,
+        Err(e) => {
+            log::error!("Failed to read settings to auto-start the sync server: {}", e);
+            return;
+        }
+    }
+
+    match serve_sync(app.clone(), &state).await {
+        Ok(server) => log::info!("Sync server started on port {}", server.port),
+        Err(e) => log::error!("Failed to auto-start sync server: {}", e),
+    }
+}
+
+/// Start the sync server, stopping it once idle if the
+/// `syncServerIdleTimeoutMins` setting asks to.
+async fn serve_sync(app: AppHandle, state: &AppState) -> CommandResult<SyncServer> {
     // Check if server is already running
     {
         let server_guard = state.sync_server.read().await;
         if server_guard.is_some() {
-            return Err("Sync server is already running".to_string());
+            return Err(CommandError::Conflict(
+                "Sync server is already running".to_string(),
+            ));
         }
     }
 
     // 1. Get configured port from settings
     let port: u16 = {
-        let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+        let conn = state.db.connection().lock()?;
         conn.query_row(
             "SELECT value FROM settings WHERE key = 'syncPort'",
             [],
@@ -410,59 +1440,98 @@ pub async fn start_sync_server(state: State<'_, AppState>) -> Result<SyncServer,
     let server_name = get_server_name();
     let local_ip = get_local_ip();
 
-    // 2. Create shared state for HTTP handlers
+    let settings = super::settings::load_settings(&state.db)?;
+    let identity = if settings.sync_tls {
+        Some(ServerIdentity::load_or_create(&state.paths().root, &server_name)?)
+    } else {
+        log::warn!("Sync server TLS is off; traffic will be sent in plain HTTP");
+        None
+    };
+
+    // 2. Create shared state for HTTP handlers, and the shutdown channel
+    // with a flag WebSockets watch to close when it fires
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let (stopping_tx, stopping_rx) = watch::channel(false);
     let sync_state = SyncServerState {
         db: state.db.clone(),
-        paths: state.paths.clone(),
+        paths: state.paths(),
         server_name: server_name.clone(),
+        last_request: Arc::new(std::sync::Mutex::new(Instant::now())),
+        updates: state.sync_updates.clone(),
+        stopping: stopping_rx,
+        app: Some(app.clone()),
     };
+    let last_request = sync_state.last_request.clone();
 
     // 3. Build the HTTP router
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    let app = Router::new()
-        .route("/info", get(handle_get_info))
-        .route("/books", get(handle_get_books))
-        .route("/book/{id}", get(handle_get_book))
-        .layer(cors)
-        .with_state(sync_state);
-
-    // 4. Create shutdown channel
-    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let origins = CorsOrigins::parse(&settings.sync_cors_origins);
+    if origins == CorsOrigins::Any {
+        log::warn!("Sync server accepts cross-origin requests from any website");
+    }
+    let router = sync_router(sync_state, origins);
 
-    // 5. Start HTTP server
+    // 4. Start HTTP server
     let addr: SocketAddr = format!("0.0.0.0:{}", port)
         .parse()
-        .map_err(|e| format!("Invalid address: {}", e))?;
+        .map_err(|e| CommandError::InvalidInput(format!("Invalid address: {}", e)))?;
 
     let listener = tokio::net::TcpListener::bind(addr)
         .await
-        .map_err(|e| format!("Failed to bind to port {}: {}", port, e))?;
+        .with_context(|| format!("Failed to bind to port {}", port))?;
 
     let actual_port = listener
         .local_addr()
         .map(|a| a.port())
         .unwrap_or(port);
 
-    tokio::spawn(async move {
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async {
+    let fingerprint = match identity {
+        Some(identity) => {
+            let fingerprint = identity.fingerprint();
+            let config = axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(
+                identity.server_config()?,
+            ));
+            let listener = listener.into_std().context("Failed to prepare listener")?;
+
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+            tokio::spawn(async move {
                 let _ = shutdown_rx.await;
-            })
-            .await
-            .ok();
-    });
-
-    log::info!("Sync server started on port {}", actual_port);
+                let _ = stopping_tx.send(true);
+                shutdown_handle.graceful_shutdown(None);
+            });
+            tokio::spawn(async move {
+                axum_server::from_tcp_rustls(listener, config)
+                    .handle(handle)
+                    .serve(router.into_make_service())
+                    .await
+                    .ok();
+            });
+
+            Some(fingerprint)
+        }
+        None => {
+            tokio::spawn(async move {
+                axum::serve(listener, router)
+                    .with_graceful_shutdown(async move {
+                        let _ = shutdown_rx.await;
+                        let _ = stopping_tx.send(true);
+                    })
+                    .await
+                    .ok();
+            });
+
+            None
+        }
+    };
 
-    // 6. Register mDNS service
-    let mdns = ServiceDaemon::new().map_err(|e| format!("Failed to create mDNS daemon: {}", e))?;
+    log::info!(
+        "Sync server started on port {} ({})",
+        actual_port,
+        if fingerprint.is_some() { "https" } else { "http" }
+    );
 
-    // Create service info
-    let instance_name = format!("{}-{}", server_name.replace(' ', "-"), Uuid::new_v4().to_string()[..8].to_string());
+    // 5. Register mDNS service
+    let mdns = ServiceDaemon::new().context("Failed to create mDNS daemon")?;
 
     // Get all local IPs for mDNS registration
     let host_ipv4 = if local_ip != "0.0.0.0" {
@@ -471,34 +1540,74 @@ pub async fn start_sync_server(state: State<'_, AppState>) -> Result<SyncServer,
         "127.0.0.1".to_string()
     };
 
+    // Look for names already advertised, after a random pause so instances
+    // started together don't all find the network empty
+    let jitter = Uuid::new_v4().as_u128() as u64 % MDNS_REGISTER_JITTER_MS;
+    tokio::time::sleep(Duration::from_millis(jitter)).await;
+    let advertised = match mdns.browse(MDNS_SERVICE_TYPE) {
+        Ok(receiver) => {
+            let (records, _) = collect_server_records(&receiver, MDNS_CLASH_CHECK);
+            mdns.stop_browse(MDNS_SERVICE_TYPE).ok();
+            records
+        }
+        Err(e) => {
+            log::warn!("Failed to check for mDNS name clashes: {}", e);
+            HashMap::new()
+        }
+    };
+
+    // A record is ours if it was left by an earlier run on this host: same
+    // address, and the same certificate or, without TLS, the same port
+    let is_own = |server: &SyncServer| {
+        server.address == host_ipv4
+            && match (&fingerprint, &server.fingerprint) {
+                (Some(ours), Some(theirs)) => ours == theirs,
+                (None, None) => server.port == actual_port,
+                _ => false,
+            }
+    };
+    let instance_name = choose_instance_name(&server_name, &advertised, is_own, || {
+        Uuid::new_v4().to_string()[..8].to_string()
+    })?;
+
+    // Advertise the certificate fingerprint so clients can pin it
+    let mut properties = HashMap::new();
+    if let Some(fingerprint) = &fingerprint {
+        properties.insert(MDNS_FINGERPRINT_KEY.to_string(), fingerprint.clone());
+    }
+
     let service_info = ServiceInfo::new(
         MDNS_SERVICE_TYPE,
         &instance_name,
         &format!("{}.local.", instance_name),
         &host_ipv4,
         actual_port,
-        None,
+        properties,
     )
-    .map_err(|e| format!("Failed to create mDNS service info: {}", e))?;
+    .context("Failed to create mDNS service info")?;
 
     let service_fullname = service_info.get_fullname().to_string();
 
-    mdns.register(service_info)
-        .map_err(|e| format!("Failed to register mDNS service: {}", e))?;
+    mdns.register(service_info).context("Failed to register mDNS service")?;
 
-    log::info!("mDNS service registered: {}", service_fullname);
+    log::info!("mDNS service registered as {}", service_fullname);
 
-    // 7. Store server handle
+    // 6. Store server handle, then stop it once idle if the settings ask to
     {
         let mut server_guard = state.sync_server.write().await;
         *server_guard = Some(crate::SyncServerHandle {
             shutdown_tx,
             mdns_daemon: mdns,
-            service_fullname,
+            service_fullname: service_fullname.clone(),
+            fingerprint: fingerprint.clone(),
         });
     }
+    if settings.sync_server_idle_timeout_mins > 0 {
+        let timeout = Duration::from_secs(settings.sync_server_idle_timeout_mins * 60);
+        tokio::spawn(stop_when_idle(app, last_request, timeout, service_fullname));
+    }
 
-    // 8. Return server info
+    // 7. Return server info
     Ok(SyncServer {
         name: server_name,
         address: if local_ip == "0.0.0.0" {
@@ -508,53 +1617,148 @@ pub async fn start_sync_server(state: State<'_, AppState>) -> Result<SyncServer,
         },
         port: actual_port,
         book_count: None,
+        fingerprint,
     })
 }
 
 /// Stop the sync server.
 #[tauri::command]
-pub async fn stop_sync_server(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_sync_server(app: AppHandle, state: State<'_, AppState>) -> CommandResult<()> {
     let mut server_guard = state.sync_server.write().await;
 
     if let Some(handle) = server_guard.take() {
-        // 1. Unregister mDNS service
-        handle
-            .mdns_daemon
-            .unregister(&handle.service_fullname)
-            .map_err(|e| format!("Failed to unregister mDNS service: {}", e))?;
-
-        // Shutdown mDNS daemon
         handle
-            .mdns_daemon
             .shutdown()
-            .map_err(|e| format!("Failed to shutdown mDNS daemon: {}", e))?;
-
-        // 2. Signal HTTP server to shutdown
-        let _ = handle.shutdown_tx.send(());
+            .context("Failed to stop mDNS service")?;
 
         log::info!("Sync server stopped");
+        let _ = app.emit(
+            "sync_server_stopped",
+            SyncServerStopped {
+                reason: SyncServerStopReason::Requested,
+            },
+        );
         Ok(())
     } else {
-        Err("Sync server is not running".to_string())
+        Err(CommandError::Conflict(
+            "Sync server is not running".to_string(),
+        ))
     }
 }
 
 /// Discover sync servers on the local network.
 ///
 /// Uses mDNS to find other Actual Reader instances running sync servers.
-/// Returns a list of discovered servers.
+/// Browses for `timeout_ms`, defaulting to the `syncDiscoveryTimeoutMs`
+/// setting. mDNS errors are logged rather than returned: the servers found
+/// so far are kept and the result is marked `partial`.
 #[tauri::command]
-pub async fn discover_sync_servers() -> Result<Vec<SyncServer>, String> {
-    let mdns = ServiceDaemon::new().map_err(|e| format!("Failed to create mDNS daemon: {}", e))?;
+pub async fn discover_sync_servers(
+    timeout_ms: Option<u64>,
+    state: State<'_, AppState>,
+) -> CommandResult<SyncDiscovery> {
+    let timeout_ms = match timeout_ms {
+        Some(ms) => ms,
+        None => super::settings::load_settings(&state.db)?.sync_discovery_timeout_ms,
+    };
+
+    Ok(browse_servers(Duration::from_millis(timeout_ms)))
+}
+
+/// Browse mDNS for sync servers for `timeout`.
+fn browse_servers(timeout: Duration) -> SyncDiscovery {
+    let mdns = match ServiceDaemon::new() {
+        Ok(mdns) => mdns,
+        Err(e) => {
+            log::warn!("Failed to create mDNS daemon: {}", e);
+            return SyncDiscovery { servers: Vec::new(), partial: true };
+        }
+    };
+
+    let discovery = match mdns.browse(MDNS_SERVICE_TYPE) {
+        Ok(receiver) => {
+            let discovery = collect_servers(&receiver, timeout);
+            mdns.stop_browse(MDNS_SERVICE_TYPE).ok();
+            discovery
+        }
+        Err(e) => {
+            log::warn!("Failed to browse mDNS services: {}", e);
+            SyncDiscovery { servers: Vec::new(), partial: true }
+        }
+    };
+
+    mdns.shutdown().ok();
+
+    discovery
+}
+
+/// mDNS instance name for a server named `server_name`.
+fn instance_name(server_name: &str, suffix: &str) -> String {
+    format!("{}-{}", server_name.replace(' ', "-"), suffix)
+}
+
+/// Pick the instance name to register, given the servers already
+/// `advertised` by full service name.
+///
+/// A record that `is_own` says an earlier run on this host left behind is
+/// taken over, so the new registration replaces it instead of showing up
+/// as a second server. Otherwise names get a suffix from `new_suffix`,
+/// drawing again while the name is taken.
+fn choose_instance_name(
+    server_name: &str,
+    advertised: &HashMap<String, SyncServer>,
+    is_own: impl Fn(&SyncServer) -> bool,
+    mut new_suffix: impl FnMut() -> String,
+) -> CommandResult<String> {
+    let prefix = instance_name(server_name, "");
+    let stale = advertised
+        .iter()
+        .filter(|(_, server)| is_own(server))
+        .filter_map(|(fullname, _)| fullname.strip_suffix(MDNS_SERVICE_TYPE)?.strip_suffix('.'))
+        .filter(|instance| instance.starts_with(&prefix))
+        .min();
+    if let Some(instance) = stale {
+        log::info!("Replacing stale mDNS record {}", instance);
+        return Ok(instance.to_string());
+    }
+
+    for _ in 0..MDNS_NAME_ATTEMPTS {
+        let candidate = instance_name(server_name, &new_suffix());
+        let fullname = format!("{}.{}", candidate, MDNS_SERVICE_TYPE);
+        if !advertised.keys().any(|name| name.eq_ignore_ascii_case(&fullname)) {
+            return Ok(candidate);
+        }
+        log::warn!("mDNS name {} is already taken; trying another", candidate);
+    }
+
+    Err(CommandError::Conflict(format!(
+        "No free mDNS name found for {} after {} attempts",
+        server_name, MDNS_NAME_ATTEMPTS
+    )))
+}
 
-    let receiver = mdns
-        .browse(MDNS_SERVICE_TYPE)
-        .map_err(|e| format!("Failed to browse mDNS services: {}", e))?;
+/// Collect servers from mDNS browse events for `timeout`.
+///
+/// If the event channel fails early, the servers resolved so far are
+/// returned as a partial result.
+fn collect_servers(receiver: &flume::Receiver<ServiceEvent>, timeout: Duration) -> SyncDiscovery {
+    let (servers, partial) = collect_server_records(receiver, timeout);
+    SyncDiscovery {
+        servers: servers.into_values().collect(),
+        partial,
+    }
+}
 
+/// Collect servers from mDNS browse events for `timeout`, by full service
+/// name, and whether the event channel failed early.
+fn collect_server_records(
+    receiver: &flume::Receiver<ServiceEvent>,
+    timeout: Duration,
+) -> (HashMap<String, SyncServer>, bool) {
     let mut servers: HashMap<String, SyncServer> = HashMap::new();
+    let mut partial = false;
 
     // Listen for services for a short time
-    let timeout = Duration::from_secs(3);
     let start = std::time::Instant::now();
 
     while start.elapsed() < timeout {
@@ -576,6 +1780,9 @@ pub async fn discover_sync_servers() -> Result<Vec<SyncServer>, String> {
                         address,
                         port: info.get_port(),
                         book_count: None,
+                        fingerprint: info
+                            .get_property_val_str(MDNS_FINGERPRINT_KEY)
+                            .map(|fp| fp.to_string()),
                     };
 
                     servers.insert(name, server);
@@ -586,96 +1793,387 @@ pub async fn discover_sync_servers() -> Result<Vec<SyncServer>, String> {
                 _ => {}
             },
             Err(flume::RecvTimeoutError::Timeout) => continue,
-            Err(_) => break,
+            Err(e) => {
+                log::warn!(
+                    "mDNS browsing stopped after {:?} with {} server(s) found: {}",
+                    start.elapsed(),
+                    servers.len(),
+                    e
+                );
+                partial = true;
+                break;
+            }
         }
     }
 
-    // Stop browsing
-    mdns.stop_browse(MDNS_SERVICE_TYPE).ok();
-    mdns.shutdown().ok();
-
-    Ok(servers.into_values().collect())
+    (servers, partial)
 }
 
-/// Connect to a sync server manually by address.
+/// The TLS error behind a failed request, if the handshake got far enough
+/// to tell the peer speaks TLS.
 ///
-/// Used when mDNS discovery doesn't work (e.g., complex networks, VLANs).
-#[tauri::command]
-pub async fn connect_to_server(address: String, port: u16) -> Result<SyncServer, String> {
-    let url = format!("http://{}:{}/info", address, port);
-
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+/// A peer answering the handshake with something other than TLS records
+/// (a plain HTTP server) isn't counted.
+fn tls_failure(error: &(dyn std::error::Error + 'static)) -> Option<&rustls::Error> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        let tls = error.downcast_ref::<rustls::Error>().or_else(|| {
+            error
+                .downcast_ref::<std::io::Error>()
+                .and_then(|e| e.get_ref())
+                .and_then(|e| e.downcast_ref::<rustls::Error>())
+        });
+        match tls {
+            Some(rustls::Error::InvalidMessage(_)) => return None,
+            Some(tls) => return Some(tls),
+            None => source = error.source(),
+        }
+    }
+    None
+}
 
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to connect to server: {}", e))?;
+/// Fetch and verify a sync server's /info response.
+async fn probe_server(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Duration,
+) -> Result<ServerInfo, ProbeError> {
+    let response = client.get(url).send().await.map_err(|e| {
+        if e.is_timeout() {
+            ProbeError::TimedOut(timeout)
+        } else if let Some(tls) = tls_failure(&e) {
+            ProbeError::Tls(tls.to_string())
+        } else {
+            ProbeError::Refused(e.to_string())
+        }
+    })?;
 
     if !response.status().is_success() {
-        return Err(format!(
-            "Server returned error: {}",
+        return Err(ProbeError::NotActualReader(format!(
+            "server returned {}",
             response.status()
-        ));
+        )));
     }
 
-    let info: ServerInfo = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse server response: {}", e))?;
+    let info: ServerInfo = response.json().await.map_err(|e| {
+        if e.is_timeout() {
+            ProbeError::TimedOut(timeout)
+        } else {
+            ProbeError::NotActualReader(format!("unexpected response: {}", e))
+        }
+    })?;
 
     // Verify it's an Actual Reader server
     if info.server_type != "actual-reader" {
-        return Err("Not an Actual Reader server".to_string());
+        return Err(ProbeError::NotActualReader(format!(
+            "server type is '{}'",
+            info.server_type
+        )));
     }
 
-    Ok(SyncServer {
-        name: info.name,
-        address,
-        port,
-        book_count: Some(info.book_count),
-    })
+    Ok(info)
 }
 
-/// Sync with a server.
+/// Probe /info, retrying timeouts and refused connections.
+async fn probe_with_retries(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Duration,
+) -> Result<ServerInfo, ProbeError> {
+    let mut attempt = 1;
+    loop {
+        match probe_server(client, url, timeout).await {
+            Ok(info) => return Ok(info),
+            Err(e) if e.is_retryable() && attempt < CONNECT_ATTEMPTS => {
+                log::warn!("Attempt {} to reach {} failed: {}", attempt, url, e);
+                attempt += 1;
+                tokio::time::sleep(CONNECT_RETRY_DELAY).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Connect to a sync server manually by address.
 ///
-/// Transfers books and progress between this device and the server.
-/// The sync is bidirectional:
-/// - Books with narration are transferred as bundles
-/// - Progress is merged (most recent wins)
+/// Used when mDNS discovery doesn't work (e.g., complex networks, VLANs).
+/// Each attempt waits up to `timeout_ms`, defaulting to the
+/// `syncConnectTimeoutMs` setting. Timeouts and refused connections are
+/// retried; errors start with "Connection timed out", "Connection refused",
+/// "Secure connection failed", or "Not an Actual Reader server" so the UI
+/// can tell them apart.
+///
+/// With a `fingerprint`, only HTTPS with that certificate is accepted.
+/// Without one the certificate the server presents is trusted and returned
+/// for pinning, falling back to plain HTTP for servers with TLS turned off.
+/// A server that presented a certificate is never retried over plain HTTP,
+/// so a failed handshake can't downgrade the connection.
 #[tauri::command]
-pub async fn sync_with_server(
+pub async fn connect_to_server(
+    address: String,
+    port: u16,
+    fingerprint: Option<String>,
+    timeout_ms: Option<u64>,
+    state: State<'_, AppState>,
+) -> CommandResult<SyncServer> {
+    let timeout_ms = match timeout_ms {
+        Some(ms) => ms,
+        None => super::settings::load_settings(&state.db)?.sync_connect_timeout_ms,
+    };
+    let timeout = Duration::from_millis(timeout_ms);
+
+    let verifier = Arc::new(PinnedCertVerifier::new(fingerprint.clone()));
+    let client = sync_client(Some(verifier.clone()), timeout)?;
+    let url = server_url(&address, port, true, "/info");
+
+    let (info, fingerprint) = match probe_with_retries(&client, &url, timeout).await {
+        Ok(info) => (info, verifier.seen()),
+        Err(ProbeError::Refused(reason))
+            if fingerprint.is_none() && verifier.seen().is_none() =>
+        {
+            log::warn!(
+                "No HTTPS at {}:{} ({}), trying plain HTTP",
+                address, port, reason
+            );
+            let client = sync_client(None, timeout)?;
+            let url = server_url(&address, port, false, "/info");
+            (probe_with_retries(&client, &url, timeout).await?, None)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(SyncServer {
+        name: info.name,
+        address,
+        port,
+        book_count: Some(info.book_count),
+        fingerprint,
+    })
+}
+
+/// Save a sync server so later syncs can reach it without discovery.
+///
+/// Saving a server with the same name replaces its address, port, token and
+/// pinned certificate fingerprint.
+#[tauri::command]
+pub async fn save_known_server(
     server: SyncServer,
-    app: tauri::AppHandle,
+    token: Option<String>,
     state: State<'_, AppState>,
-) -> Result<SyncResult, String> {
-    let mut result = SyncResult {
-        books_added: 0,
-        progress_synced: 0,
-        errors: Vec::new(),
+) -> CommandResult<KnownServer> {
+    let conn = state.db.connection().lock()?;
+
+    conn.execute(
+        "INSERT INTO known_servers (name, address, port, token, fingerprint) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET
+             address = excluded.address,
+             port = excluded.port,
+             token = excluded.token,
+             fingerprint = excluded.fingerprint",
+        rusqlite::params![server.name, server.address, server.port, token, server.fingerprint],
+    )
+    .context("Failed to save server")?;
+
+    query_known_server(&conn, &server.name)
+}
+
+/// List saved sync servers, most recently synced first.
+#[tauri::command]
+pub async fn list_known_servers(state: State<'_, AppState>) -> CommandResult<Vec<KnownServer>> {
+    let conn = state.db.connection().lock()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT name, address, port, last_seen, token, fingerprint FROM known_servers
+             ORDER BY last_seen IS NULL, last_seen DESC, name",
+        )
+        .context("Failed to prepare query")?;
+
+    let servers = stmt
+        .query_map([], read_known_server)
+        .context("Failed to query servers")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read server")?;
+    Ok(servers)
+}
+
+/// Forget a saved sync server.
+#[tauri::command]
+pub async fn forget_server(name: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let conn = state.db.connection().lock()?;
+    let removed = conn
+        .execute("DELETE FROM known_servers WHERE name = ?", rusqlite::params![name])
+        .context("Failed to forget server")?;
+
+    if removed == 0 {
+        return Err(CommandError::NotFound(format!("Unknown server: {}", name)));
+    }
+
+    Ok(())
+}
+
+/// Map a `known_servers` row to a KnownServer.
+fn read_known_server(row: &rusqlite::Row) -> rusqlite::Result<KnownServer> {
+    Ok(KnownServer {
+        name: row.get(0)?,
+        address: row.get(1)?,
+        port: row.get(2)?,
+        last_seen: row.get(3)?,
+        token: row.get(4)?,
+        fingerprint: row.get(5)?,
+    })
+}
+
+/// Look up a saved sync server by name.
+fn query_known_server(conn: &rusqlite::Connection, name: &str) -> CommandResult<KnownServer> {
+    conn.query_row(
+        "SELECT name, address, port, last_seen, token, fingerprint FROM known_servers WHERE name = ?",
+        rusqlite::params![name],
+        read_known_server,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound(format!("Unknown server: {}", name))
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Find a reachable address for a saved server.
+///
+/// Tries the saved address first and, if that fails, looks the server up
+/// by name over mDNS in case its address has changed. A pinned certificate
+/// stays pinned wherever the server turns up.
+async fn resolve_known_server(
+    known: KnownServer,
+    state: &AppState,
+) -> CommandResult<SyncServer> {
+    let settings = super::settings::load_settings(&state.db)?;
+    let timeout = Duration::from_millis(settings.sync_connect_timeout_ms);
+
+    let saved: SyncServer = known.clone().into();
+    let client = client_for(&saved, timeout)?;
+    let saved_error = match probe_server(&client, &saved.url("/info"), timeout).await {
+        Ok(_) => return Ok(saved),
+        Err(e) => e,
     };
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(300)) // 5 minute timeout for large files
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    log::warn!(
+        "Saved server {} unreachable at {}:{} ({}), searching the network",
+        known.name, known.address, known.port, saved_error
+    );
+
+    let discovery_timeout = Duration::from_millis(settings.sync_discovery_timeout_ms);
+    let discovered = browse_servers(discovery_timeout)
+        .servers
+        .into_iter()
+        .find(|server| server.name == known.name);
+
+    match discovered {
+        Some(mut server) => {
+            if known.fingerprint.is_some() {
+                server.fingerprint = known.fingerprint;
+            }
+            let client = client_for(&server, timeout)?;
+            probe_server(&client, &server.url("/info"), timeout).await?;
+            Ok(server)
+        }
+        None => Err(saved_error.into()),
+    }
+}
 
-    // 1. GET /books from server
-    let books_url = format!("http://{}:{}/books", server.address, server.port);
-    let response = client
-        .get(&books_url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to get book list: {}", e))?;
+/// Record a successful sync with a saved server, keeping its address current.
+///
+/// `books_version` is the version of the server's book list once every book
+/// on it has been synced; otherwise the previous version is kept.
+fn touch_known_server(
+    state: &AppState,
+    server: &SyncServer,
+    books_version: Option<&str>,
+) -> CommandResult<()> {
+    let conn = state.db.connection().lock()?;
+    conn.execute(
+        "UPDATE known_servers
+         SET address = ?, port = ?, last_seen = ?, books_version = COALESCE(?, books_version)
+         WHERE name = ?",
+        rusqlite::params![
+            server.address,
+            server.port,
+            current_timestamp(),
+            books_version,
+            server.name
+        ],
+    )
+    .context("Failed to update saved server")?;
+    Ok(())
+}
+
+/// Version of a saved server's book list at its last full sync, if any.
+fn query_books_version(conn: &rusqlite::Connection, name: &str) -> CommandResult<Option<String>> {
+    let version = conn.query_row(
+        "SELECT books_version FROM known_servers WHERE name = ?",
+        rusqlite::params![name],
+        |row| row.get(0),
+    );
+    match version {
+        Ok(version) => Ok(version),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(CommandError::Database(format!("Database error: {}", e))),
+    }
+}
+
+/// Resolve the server to sync with: a discovered `server`, or the name of a
+/// saved server as `known_server`.
+async fn resolve_sync_server(
+    server: Option<SyncServer>,
+    known_server: Option<String>,
+    state: &AppState,
+) -> CommandResult<SyncServer> {
+    match (server, known_server) {
+        (Some(server), _) => Ok(server),
+        (None, Some(name)) => {
+            let known = {
+                let conn = state.db.connection().lock()?;
+                query_known_server(&conn, &name)?
+            };
+            resolve_known_server(known, state).await
+        }
+        (None, None) => Err(CommandError::InvalidInput(
+            "Either a server or a saved server name is required".to_string(),
+        )),
+    }
+}
+
+/// A server's book list from GET /books.
+struct ServerBooks {
+    books: Vec<BookInfo>,
+    /// Version of the list from its ETag. None from servers that predate it.
+    version: Option<String>,
+}
+
+/// Fetch a server's book list from GET /books.
+///
+/// With the `known_version` of an earlier list, returns None if the server
+/// says the list hasn't changed since.
+async fn fetch_server_books(
+    client: &reqwest::Client,
+    server: &SyncServer,
+    known_version: Option<&str>,
+) -> CommandResult<Option<ServerBooks>> {
+    let books_url = server.url("/books");
+    let mut request = client.get(&books_url);
+    if let Some(version) = known_version {
+        request = request.header(reqwest::header::IF_NONE_MATCH, format!("\"{}\"", version));
+    }
+    let response = request.send().await.context("Failed to get book list")?;
 
+    if known_version.is_some() && response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
     if !response.status().is_success() {
-        return Err(format!(
+        return Err(CommandError::ServiceUnavailable(format!(
             "Failed to get book list: {}",
             response.status()
-        ));
+        )));
     }
 
     #[derive(Deserialize)]
@@ -683,72 +2181,193 @@ pub async fn sync_with_server(
         books: Vec<BookInfo>,
     }
 
+    let version = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(|tag| tag.trim_start_matches("W/").trim_matches('"').to_string());
     let books_response: BooksResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse book list: {}", e))?;
+        .context("Failed to parse book list")?;
+    Ok(Some(ServerBooks {
+        books: books_response.books,
+        version,
+    }))
+}
 
-    // 2. Compare with local library
-    let local_book_ids: std::collections::HashSet<String> = {
-        let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
-        let mut stmt = conn
-            .prepare("SELECT id FROM books")
-            .map_err(|e| format!("Failed to query local books: {}", e))?;
+/// Preview what syncing with a server would download.
+///
+/// Fetches the server's book list and compares it with the local library by
+/// ID and by text, so users can review a large transfer and pick books to
+/// pass to `sync_with_server`. Sync never uploads books, so only downloads
+/// are listed. Takes the server like `sync_with_server`.
+#[tauri::command]
+pub async fn preview_sync(
+    server: Option<SyncServer>,
+    known_server: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<SyncPreview> {
+    let server = resolve_sync_server(server, known_server, &state).await?;
+    let client = client_for(&server, Duration::from_secs(30))?;
+    // Without a known version the list always comes back
+    let books = fetch_server_books(&client, &server, None)
+        .await?
+        .map(|list| list.books)
+        .unwrap_or_default();
+
+    let local = {
+        let conn = state.db.connection().lock()?;
+        LocalLibrary::load(&conn)?
+    };
+    Ok(local.preview(books))
+}
+
+/// Sync with a server.
+///
+/// Transfers books and progress between this device and the server.
+/// The sync is bidirectional:
+/// - Books with narration are transferred as bundles
+/// - Progress is merged (most recent wins)
+///
+/// Either pass a discovered `server`, or the name of a saved server as
+/// `known_server`; a saved server that can't be reached at its last address
+/// is looked up again over mDNS before giving up. Saved servers have their
+/// `lastSeen` updated after a successful sync.
+///
+/// Every book `preview_sync` lists to add is downloaded, unless `book_ids`
+/// picks which of them to transfer.
+///
+/// A saved server remembers the version of its book list after a sync that
+/// downloaded everything on it. While the list is unchanged, later syncs
+/// skip comparing it, so a book deleted here since isn't downloaded again
+/// unless it is picked with `book_ids`.
+#[tauri::command]
+pub async fn sync_with_server(
+    server: Option<SyncServer>,
+    known_server: Option<String>,
+    book_ids: Option<Vec<String>>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<SyncResult> {
+    let server = resolve_sync_server(server, known_server, &state).await?;
 
-        let result = stmt.query_map([], |row| row.get::<_, String>(0))
-            .map_err(|e| format!("Failed to read books: {}", e))?
-            .filter_map(|r| r.ok())
-            .collect();
-        result
+    log::info!(
+        "server={} address={}:{}: starting sync",
+        server.name, server.address, server.port
+    );
+
+    let mut result = SyncResult {
+        books_added: 0,
+        progress_synced: 0,
+        errors: Vec::new(),
     };
 
-    let books_to_download: Vec<&BookInfo> = books_response
-        .books
-        .iter()
-        .filter(|book| !local_book_ids.contains(&book.id) && book.has_narration)
-        .collect();
+    // 5 minute timeout for large files
+    let client = client_for(&server, Duration::from_secs(300))?;
+    let max_bundle_mb = super::settings::load_settings(&state.db)?.sync_max_bundle_mb;
+    let max_bundle_bytes = max_bundle_mb * 1024 * 1024;
+
+    // 1. GET /books from server, unless it hasn't changed since the last
+    // full sync
+    let known_version = match &book_ids {
+        Some(_) => None,
+        None => {
+            let conn = state.db.connection().lock()?;
+            query_books_version(&conn, &server.name)?
+        }
+    };
+    let Some(server_books) =
+        fetch_server_books(&client, &server, known_version.as_deref()).await?
+    else {
+        log::info!("server={}: book list unchanged since the last sync", server.name);
+        if let Err(e) = touch_known_server(&state, &server, None) {
+            log::warn!("Failed to update saved server {}: {}", server.name, e);
+        }
+        return Ok(result);
+    };
+    let server_book_count = server_books.books.len();
+
+    // 2. Compare with local library
+    let local = {
+        let conn = state.db.connection().lock()?;
+        LocalLibrary::load(&conn)?
+    };
+    let mut books_to_download = local.preview(server_books.books).to_add;
+
+    if let Some(book_ids) = &book_ids {
+        for id in book_ids {
+            if !books_to_download.iter().any(|book| &book.id == id) {
+                result.errors.push(format!("Book {} is not available to download", id));
+            }
+        }
+        books_to_download.retain(|book| book_ids.contains(&book.id));
+    }
 
     let total_books = books_to_download.len();
+    log::info!(
+        "server={}: {} book(s) on server, {} to download",
+        server.name,
+        server_book_count,
+        total_books
+    );
 
     // 3. Download and import each missing book
+    let progress_events = ProgressThrottle::new(app, "sync_progress");
     for (index, book_info) in books_to_download.iter().enumerate() {
-        // Emit progress event
+        // Emit progress event, throttled so small books don't flood the UI
         let progress = ((index as f64) / (total_books as f64) * 100.0) as u32;
-        app.emit("sync_progress", serde_json::json!({
-            "percent": progress,
-            "current": index + 1,
-            "total": total_books,
-            "book_title": book_info.title
-        }))
-        .ok();
+        progress_events.emit(
+            serde_json::json!({
+                "percent": progress,
+                "current": index + 1,
+                "total": total_books,
+                "book_title": book_info.title
+            }),
+            index == 0,
+        );
 
         // Download bundle
-        let book_url = format!(
-            "http://{}:{}/book/{}",
-            server.address, server.port, book_info.id
-        );
+        let book_url = server.url(&format!("/book/{}", book_info.id));
 
-        match download_and_import_book(&client, &book_url, &state).await {
+        match download_and_import_book(&client, &book_url, max_bundle_bytes, &state).await {
             Ok(_) => {
                 result.books_added += 1;
-                log::info!("Imported book: {}", book_info.title);
+                log::info!("server={} book={}: imported '{}'", server.name, book_info.id, book_info.title);
             }
             Err(e) => {
                 let error = format!("Failed to import '{}': {}", book_info.title, e);
-                log::error!("{}", error);
+                log::error!("server={} book={}: {}", server.name, book_info.id, error);
                 result.errors.push(error);
             }
         }
     }
 
     // Emit completion
-    app.emit("sync_progress", serde_json::json!({
-        "percent": 100,
-        "current": total_books,
-        "total": total_books,
-        "complete": true
-    }))
-    .ok();
+    progress_events.emit(
+        serde_json::json!({
+            "percent": 100,
+            "current": total_books,
+            "total": total_books,
+            "complete": true
+        }),
+        true,
+    );
+
+    log::info!(
+        "server={}: sync finished with {} book(s) added and {} error(s)",
+        server.name,
+        result.books_added,
+        result.errors.len()
+    );
+
+    // Only a sync that left nothing behind may be skipped next time
+    let synced_version = server_books
+        .version
+        .filter(|_| book_ids.is_none() && result.errors.is_empty());
+    if let Err(e) = touch_known_server(&state, &server, synced_version.as_deref()) {
+        log::warn!("Failed to update saved server {}: {}", server.name, e);
+    }
 
     Ok(result)
 }
@@ -757,192 +2376,124 @@ pub async fn sync_with_server(
 async fn download_and_import_book(
     client: &reqwest::Client,
     url: &str,
+    max_bytes: u64,
     state: &AppState,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     // Download the bundle
-    let response = client
+    let mut response = client
         .get(url)
         .send()
         .await
-        .map_err(|e| format!("Download failed: {}", e))?;
+        .context("Download failed")?;
 
     if !response.status().is_success() {
-        return Err(format!("Server returned: {}", response.status()));
+        return Err(CommandError::ServiceUnavailable(format!(
+            "Server returned: {}",
+            response.status()
+        )));
     }
 
-    let bundle_data = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    // Refuse anything that isn't a bundle, or is too big, before reading it
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let declared = response.content_length();
+    check_bundle_headers(content_type, declared, max_bytes)?;
+
+    // The declared length is only a hint; memory grows as data arrives
+    let capacity = declared.unwrap_or(0).min(BUNDLE_INITIAL_CAPACITY);
+    let mut bundle_data = Vec::with_capacity(capacity as usize);
+    while let Some(chunk) = response.chunk().await.context("Failed to read response")? {
+        append_bundle_chunk(&mut bundle_data, &chunk, declared, max_bytes)?;
+    }
+
+    if !bundle_data.starts_with(ZIP_MAGIC) {
+        return Err(CommandError::InvalidInput(
+            "Server sent a file that is not a bundle (not a ZIP archive)".to_string(),
+        ));
+    }
 
     // Import the bundle
     import_bundle_data(&bundle_data, state)
 }
 
-/// Import a book from bundle data.
-fn import_bundle_data(data: &[u8], state: &AppState) -> Result<(), String> {
-    use std::io::Cursor;
-    use zip::ZipArchive;
-
-    let cursor = Cursor::new(data);
-    let mut archive =
-        ZipArchive::new(cursor).map_err(|e| format!("Invalid bundle archive: {}", e))?;
-
-    // 1. Read and parse manifest.json
-    let manifest: serde_json::Value = {
-        let mut manifest_file = archive
-            .by_name("manifest.json")
-            .map_err(|e| format!("Missing manifest.json: {}", e))?;
-        let mut contents = String::new();
-        manifest_file
-            .read_to_string(&mut contents)
-            .map_err(|e| format!("Failed to read manifest: {}", e))?;
-        serde_json::from_str(&contents).map_err(|e| format!("Invalid manifest JSON: {}", e))?
-    };
-
-    let book_id = manifest
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing id in manifest")?;
-    let title = manifest
-        .get("title")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing title in manifest")?;
-    let author = manifest.get("author").and_then(|v| v.as_str());
-    let source_format_str = manifest
-        .get("source_format")
-        .and_then(|v| v.as_str())
-        .unwrap_or("txt");
-    let created_at = manifest
-        .get("created_at")
-        .and_then(|v| v.as_i64())
-        .unwrap_or_else(|| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64
-        });
-
-    // 2. Read segments
-    #[derive(Deserialize)]
-    struct SegmentsFile {
-        segments: Vec<serde_json::Value>,
-    }
-
-    let segments: SegmentsFile = {
-        let mut segments_file = archive
-            .by_name("content/segments.json")
-            .map_err(|e| format!("Missing segments.json: {}", e))?;
-        let mut contents = String::new();
-        segments_file
-            .read_to_string(&mut contents)
-            .map_err(|e| format!("Failed to read segments: {}", e))?;
-        serde_json::from_str(&contents).map_err(|e| format!("Invalid segments JSON: {}", e))?
-    };
-
-    // 3. Read markers
-    #[derive(Deserialize)]
-    struct MarkersFile {
-        markers: Vec<serde_json::Value>,
-    }
-
-    let markers: MarkersFile = {
-        let mut markers_file = archive
-            .by_name("narration/markers.json")
-            .map_err(|e| format!("Missing markers.json: {}", e))?;
-        let mut contents = String::new();
-        markers_file
-            .read_to_string(&mut contents)
-            .map_err(|e| format!("Failed to read markers: {}", e))?;
-        serde_json::from_str(&contents).map_err(|e| format!("Invalid markers JSON: {}", e))?
-    };
-
-    // 4. Extract audio file
-    let narration_dir = state.paths.narration_path(book_id);
-    std::fs::create_dir_all(&narration_dir)
-        .map_err(|e| format!("Failed to create narration directory: {}", e))?;
-
-    let audio_path = state.paths.narration_audio_path(book_id);
-    if let Ok(mut audio_file) = archive.by_name("narration/audio.mp3") {
-        let mut audio_data = Vec::new();
-        audio_file
-            .read_to_end(&mut audio_data)
-            .map_err(|e| format!("Failed to read audio: {}", e))?;
-        std::fs::write(&audio_path, &audio_data)
-            .map_err(|e| format!("Failed to write audio file: {}", e))?;
+/// Most memory reserved for a bundle download before any of it arrives.
+const BUNDLE_INITIAL_CAPACITY: u64 = 4 * 1024 * 1024;
+
+/// Check a bundle download's Content-Type and Content-Length before its body
+/// is read.
+fn check_bundle_headers(
+    content_type: Option<&str>,
+    content_length: Option<u64>,
+    max_bytes: u64,
+) -> CommandResult<()> {
+    let mime = content_type
+        .and_then(|value| value.split(';').next())
+        .map(str::trim)
+        .unwrap_or_default();
+    if !mime.eq_ignore_ascii_case("application/octet-stream") {
+        return Err(CommandError::InvalidInput(format!(
+            "Server sent {} instead of a bundle",
+            if mime.is_empty() { "no content type" } else { mime }
+        )));
     }
 
-    // 5. Insert into database
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-
-    let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
-
-    // Insert book
-    conn.execute(
-        "INSERT OR REPLACE INTO books (id, title, author, source_format, source_path, narration_status, narration_path, created_at, updated_at, last_opened_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, NULL)",
-        rusqlite::params![
-            book_id,
-            title,
-            author,
-            source_format_str,
-            "", // No source file for imported bundles
-            "ready",
-            narration_dir.to_string_lossy().to_string(),
-            created_at,
-            now,
-        ],
-    )
-    .map_err(|e| format!("Failed to insert book: {}", e))?;
-
-    // Insert segments
-    let mut stmt = conn
-        .prepare("INSERT OR REPLACE INTO segments (id, book_id, idx, content, html) VALUES (?1, ?2, ?3, ?4, ?5)")
-        .map_err(|e| format!("Failed to prepare segment insert: {}", e))?;
-
-    for segment in &segments.segments {
-        let seg_id = segment.get("id").and_then(|v| v.as_str()).unwrap_or("");
-        let index = segment.get("index").and_then(|v| v.as_i64()).unwrap_or(0);
-        let content = segment.get("content").and_then(|v| v.as_str()).unwrap_or("");
-        let html = segment.get("html").and_then(|v| v.as_str());
-
-        stmt.execute(rusqlite::params![seg_id, book_id, index, content, html])
-            .map_err(|e| format!("Failed to insert segment: {}", e))?;
+    match content_length {
+        Some(length) if length > max_bytes => Err(CommandError::InvalidInput(format!(
+            "Bundle is {} bytes, over the {} byte limit",
+            length, max_bytes
+        ))),
+        _ => Ok(()),
     }
+}
 
-    // Insert markers
-    let mut stmt = conn
-        .prepare("INSERT OR REPLACE INTO markers (id, book_id, segment_id, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)")
-        .map_err(|e| format!("Failed to prepare marker insert: {}", e))?;
-
-    for marker in &markers.markers {
-        let segment_id = marker
-            .get("segment_id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let start = marker.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
-        let end = marker.get("end").and_then(|v| v.as_f64()).unwrap_or(0.0);
-        let marker_id = format!("mrk_{}", Uuid::new_v4());
-
-        stmt.execute(rusqlite::params![marker_id, book_id, segment_id, start, end])
-            .map_err(|e| format!("Failed to insert marker: {}", e))?;
+/// Add a chunk of a bundle download to `data`, failing once the download
+/// passes its declared length or `max_bytes`.
+fn append_bundle_chunk(
+    data: &mut Vec<u8>,
+    chunk: &[u8],
+    declared: Option<u64>,
+    max_bytes: u64,
+) -> CommandResult<()> {
+    let received = (data.len() + chunk.len()) as u64;
+    if let Some(declared) = declared.filter(|declared| received > *declared) {
+        return Err(CommandError::InvalidInput(format!(
+            "Server sent more than the {} bytes it declared",
+            declared
+        )));
+    }
+    if received > max_bytes {
+        return Err(CommandError::InvalidInput(format!(
+            "Bundle download passed the {} byte limit",
+            max_bytes
+        )));
     }
+    data.extend_from_slice(chunk);
+    Ok(())
+}
+
+/// Import a book from bundle data.
+///
+/// Synced books keep their original ID so progress lines up across devices.
+fn import_bundle_data(data: &[u8], state: &AppState) -> CommandResult<()> {
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(data)).context("Invalid bundle archive")?;
 
+    // No source file for synced books
+    import_bundle_archive(&mut archive, "", true, false, state)?;
     Ok(())
 }
 
 /// Get the current sync server status.
 #[tauri::command]
-pub async fn get_sync_status(state: State<'_, AppState>) -> Result<Option<SyncServer>, String> {
+pub async fn get_sync_status(state: State<'_, AppState>) -> CommandResult<Option<SyncServer>> {
     let server_guard = state.sync_server.read().await;
 
-    if server_guard.is_some() {
+    if let Some(handle) = server_guard.as_ref() {
         // Server is running, get its info
         let port: u16 = {
-            let conn = state.db.connection().lock().map_err(|e| e.to_string())?;
+            let conn = state.db.connection().lock()?;
             conn.query_row(
                 "SELECT value FROM settings WHERE key = 'syncPort'",
                 [],
@@ -958,8 +2509,596 @@ pub async fn get_sync_status(state: State<'_, AppState>) -> Result<Option<SyncSe
             address: get_local_ip(),
             port,
             book_count: None,
+            fingerprint: handle.fingerprint.clone(),
         }))
     } else {
         Ok(None)
     }
 }
+
+/// A subscribed server's WebSocket.
+type UpdateStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// An update from a subscribed server, emitted as `server_update`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerUpdate {
+    /// Name of the server it came from.
+    pub server: String,
+    pub update: SyncUpdate,
+}
+
+/// Emitted as `server_updates_disconnected` when the connection to a
+/// subscribed server drops.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerUpdatesDisconnected {
+    pub server: String,
+    pub reason: String,
+}
+
+/// Open a WebSocket to a server's /ws, pinned to its certificate if it has one.
+async fn connect_updates(server: &SyncServer, timeout: Duration) -> CommandResult<UpdateStream> {
+    let connector = match &server.fingerprint {
+        Some(fingerprint) => {
+            let verifier = Arc::new(PinnedCertVerifier::new(Some(fingerprint.clone())));
+            Some(tokio_tungstenite::Connector::Rustls(Arc::new(verifier.client_config()?)))
+        }
+        None => None,
+    };
+    let connect =
+        tokio_tungstenite::connect_async_tls_with_config(server.ws_url(), None, false, connector);
+
+    match tokio::time::timeout(timeout, connect).await {
+        Ok(Ok((stream, _))) => Ok(stream),
+        Ok(Err(e)) => Err(CommandError::ServiceUnavailable(format!(
+            "Failed to connect to {}: {}",
+            server.name, e
+        ))),
+        Err(_) => Err(CommandError::ServiceUnavailable(format!(
+            "Connection to {} timed out after {} ms",
+            server.name,
+            timeout.as_millis()
+        ))),
+    }
+}
+
+/// Emit each update from a server until the connection ends, returning why
+/// it ended.
+async fn relay_updates(app: &AppHandle, server: &SyncServer, stream: &mut UpdateStream) -> String {
+    while let Some(message) = stream.next().await {
+        let text = match message {
+            Ok(tungstenite::Message::Text(text)) => text,
+            Ok(tungstenite::Message::Close(_)) => return "Server closed the connection".to_string(),
+            // Pings are answered while reading
+            Ok(_) => continue,
+            Err(e) => return e.to_string(),
+        };
+        match serde_json::from_str::<SyncUpdate>(&text) {
+            Ok(update) => {
+                let _ = app.emit(
+                    "server_update",
+                    ServerUpdate {
+                        server: server.name.clone(),
+                        update,
+                    },
+                );
+            }
+            Err(e) => log::warn!("Ignoring unrecognized update from {}: {}", server.name, e),
+        }
+    }
+    "Connection closed".to_string()
+}
+
+/// Double the pause between reconnection attempts, up to `RECONNECT_DELAY_MAX`.
+fn next_reconnect_delay(delay: Duration) -> Duration {
+    (delay * 2).min(RECONNECT_DELAY_MAX)
+}
+
+/// Relay a server's updates, reconnecting with backoff whenever the
+/// connection drops, until the task is aborted.
+async fn follow_server_updates(
+    app: AppHandle,
+    server: SyncServer,
+    mut stream: UpdateStream,
+    timeout: Duration,
+) {
+    loop {
+        let reason = relay_updates(&app, &server, &mut stream).await;
+        log::info!("Lost updates from {}: {}", server.name, reason);
+        let _ = app.emit(
+            "server_updates_disconnected",
+            ServerUpdatesDisconnected {
+                server: server.name.clone(),
+                reason,
+            },
+        );
+
+        let mut delay = RECONNECT_DELAY_MIN;
+        stream = loop {
+            tokio::time::sleep(delay).await;
+            match connect_updates(&server, timeout).await {
+                Ok(stream) => break stream,
+                Err(e) => {
+                    log::debug!("Reconnecting to {} failed: {}", server.name, e);
+                    delay = next_reconnect_delay(delay);
+                }
+            }
+        };
+
+        // Whatever changed while disconnected was missed
+        log::info!("Reconnected to updates from {}", server.name);
+        let _ = app.emit(
+            "server_update",
+            ServerUpdate {
+                server: server.name.clone(),
+                update: SyncUpdate::Resync,
+            },
+        );
+    }
+}
+
+/// Follow a sync server's live updates, replacing any existing subscription.
+///
+/// Connects to the server's `/ws`, failing if it can't be reached, then emits
+/// each update as a `server_update` event. If the connection drops,
+/// `server_updates_disconnected` is emitted and the app keeps reconnecting,
+/// emitting a `resync` update once it's back, until
+/// `unsubscribe_server_updates` is called.
+#[tauri::command]
+pub async fn subscribe_server_updates(
+    server: SyncServer,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let settings = super::settings::load_settings(&state.db)?;
+    let timeout = Duration::from_millis(settings.sync_connect_timeout_ms);
+    let stream = connect_updates(&server, timeout).await?;
+    log::info!("Subscribed to updates from {}", server.name);
+
+    let task = tokio::spawn(follow_server_updates(app, server, stream, timeout));
+    if let Some(previous) = state.server_updates.lock().unwrap().replace(task) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+/// Stop following a sync server's live updates. Does nothing if not
+/// subscribed.
+#[tauri::command]
+pub async fn unsubscribe_server_updates(state: State<'_, AppState>) -> CommandResult<()> {
+    if let Some(task) = state.server_updates.lock().unwrap().take() {
+        task.abort();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_update_json() {
+        let progress = Progress {
+            book_id: BookId::new("book"),
+            segment_index: 3,
+            audio_time: Some(12.5),
+            max_segment_index: 4,
+            max_audio_time: Some(20.0),
+            updated_at: 100,
+        };
+        let json = serde_json::to_value(SyncUpdate::Progress(progress)).unwrap();
+        assert_eq!(json["type"], "progress");
+        assert_eq!(json["bookId"], "book");
+        assert_eq!(json["segmentIndex"], 3);
+
+        let json = serde_json::to_string(&SyncUpdate::LibraryChanged).unwrap();
+        assert_eq!(json, r#"{"type":"libraryChanged"}"#);
+        assert!(matches!(
+            serde_json::from_str::<SyncUpdate>(r#"{"type":"resync"}"#).unwrap(),
+            SyncUpdate::Resync
+        ));
+    }
+
+    #[test]
+    fn test_next_reconnect_delay() {
+        assert_eq!(next_reconnect_delay(RECONNECT_DELAY_MIN), Duration::from_secs(2));
+        assert_eq!(next_reconnect_delay(Duration::from_secs(20)), RECONNECT_DELAY_MAX);
+        assert_eq!(next_reconnect_delay(RECONNECT_DELAY_MAX), RECONNECT_DELAY_MAX);
+    }
+
+    #[test]
+    fn test_idle_remaining() {
+        let timeout = Duration::from_secs(600);
+        let last = Instant::now();
+
+        assert_eq!(idle_remaining(last, timeout, last), Some(timeout));
+        assert_eq!(
+            idle_remaining(last, timeout, last + Duration::from_secs(450)),
+            Some(Duration::from_secs(150))
+        );
+        assert_eq!(idle_remaining(last, timeout, last + timeout), None);
+        assert_eq!(idle_remaining(last, timeout, last + Duration::from_secs(900)), None);
+        // A request after the check started counts as activity
+        assert_eq!(idle_remaining(last + Duration::from_secs(5), timeout, last), Some(timeout));
+    }
+
+    #[test]
+    fn test_local_library_preview() {
+        let book = |id: &str, hash: Option<&str>, has_narration: bool| BookInfo {
+            id: id.to_string(),
+            title: id.to_string(),
+            author: None,
+            source_format: "epub".to_string(),
+            has_narration,
+            content_hash: hash.map(str::to_string),
+            size: Some(1024),
+        };
+        let local = LocalLibrary {
+            ids: HashSet::from(["same-id".to_string()]),
+            content_hashes: HashSet::from(["same-text".to_string()]),
+        };
+
+        let preview = local.preview(vec![
+            book("same-id", None, true),
+            book("copy", Some("same-text"), true),
+            book("new", Some("other-text"), true),
+            book("older-server", None, true),
+            book("unnarrated", None, false),
+        ]);
+
+        let ids = |books: &[BookInfo]| books.iter().map(|b| b.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&preview.to_add), vec!["new", "older-server"]);
+        assert_eq!(ids(&preview.already_present), vec!["same-id", "copy"]);
+    }
+
+    #[test]
+    fn test_cors_origins() {
+        let origins = CorsOrigins::parse("tauri://localhost, http://localhost, https://app.example:8443/");
+        assert!(origins.allows("tauri://localhost"));
+        assert!(origins.allows("http://localhost"));
+        assert!(origins.allows("http://LOCALHOST:5174"));
+        assert!(origins.allows("https://app.example:8443"));
+        assert!(!origins.allows("https://app.example"));
+        assert!(!origins.allows("http://localhost.evil.com"));
+        assert!(!origins.allows("http://localhost:80x"));
+        assert!(!origins.allows("https://evil.example"));
+
+        assert_eq!(CorsOrigins::parse(" * "), CorsOrigins::Any);
+        assert!(CorsOrigins::Any.allows("https://evil.example"));
+        assert!(!CorsOrigins::parse("").allows("http://localhost"));
+    }
+
+    #[test]
+    fn test_narrated_books_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('a', 'A', 'txt', 'a.txt', 'ready', 0, 0),
+                    ('b', 'B', 'txt', 'b.txt', 'none', 0, 0);",
+        )
+        .unwrap();
+        let version = narrated_books_version(&conn).unwrap();
+        assert_eq!(narrated_books_version(&conn).unwrap(), version);
+
+        // Books without narration aren't listed, so don't count
+        conn.execute("UPDATE books SET updated_at = 5 WHERE id = 'b'", []).unwrap();
+        assert_eq!(narrated_books_version(&conn).unwrap(), version);
+
+        conn.execute("UPDATE books SET updated_at = 5 WHERE id = 'a'", []).unwrap();
+        let edited = narrated_books_version(&conn).unwrap();
+        assert_ne!(edited, version);
+        conn.execute("UPDATE books SET narration_status = 'stale' WHERE id = 'b'", []).unwrap();
+        assert_ne!(narrated_books_version(&conn).unwrap(), edited);
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"abc\"", "abc"));
+        assert!(etag_matches("W/\"abc\"", "abc"));
+        assert!(etag_matches("\"old\", \"abc\"", "abc"));
+        assert!(etag_matches("*", "abc"));
+        assert!(!etag_matches("\"abcd\"", "abc"));
+        assert!(!etag_matches("", "abc"));
+    }
+
+    #[test]
+    fn test_choose_instance_name() {
+        let server = |address: &str, port: u16, fingerprint: Option<&str>| SyncServer {
+            name: "host".to_string(),
+            address: address.to_string(),
+            port,
+            book_count: None,
+            fingerprint: fingerprint.map(str::to_string),
+        };
+        let fullname = |instance: &str| format!("{}.{}", instance, MDNS_SERVICE_TYPE);
+        let is_own = |s: &SyncServer| s.address == "10.0.0.2" && s.port == 42069;
+        let suffixes = || {
+            let mut suffixes = vec!["ccc", "bbb", "aaa"];
+            move || suffixes.pop().unwrap_or("zzz").to_string()
+        };
+
+        let mut advertised = HashMap::new();
+        assert_eq!(
+            choose_instance_name("My Mac", &advertised, is_own, suffixes()).unwrap(),
+            "My-Mac-aaa"
+        );
+
+        // Names taken by other servers get a new suffix, ignoring case
+        advertised.insert(fullname("My-Mac-aaa"), server("10.0.0.3", 42069, None));
+        advertised.insert(fullname("my-mac-bbb"), server("10.0.0.3", 42069, None));
+        assert_eq!(
+            choose_instance_name("My Mac", &advertised, is_own, suffixes()).unwrap(),
+            "My-Mac-ccc"
+        );
+        assert!(matches!(
+            choose_instance_name("My Mac", &advertised, is_own, || "aaa".to_string()),
+            Err(CommandError::Conflict(_))
+        ));
+
+        // A record of ours left by an earlier run is taken over, but only
+        // under our own name
+        advertised.insert(fullname("Other-Host-ddd"), server("10.0.0.2", 42069, None));
+        assert_eq!(
+            choose_instance_name("My Mac", &advertised, is_own, suffixes()).unwrap(),
+            "My-Mac-ccc"
+        );
+        advertised.insert(fullname("My-Mac-eee"), server("10.0.0.2", 42069, None));
+        assert_eq!(
+            choose_instance_name("My Mac", &advertised, is_own, suffixes()).unwrap(),
+            "My-Mac-eee"
+        );
+    }
+
+    #[test]
+    fn test_collect_servers_partial_on_channel_error() {
+        let (sender, receiver) = flume::unbounded::<ServiceEvent>();
+        sender.send(ServiceEvent::SearchStarted(MDNS_SERVICE_TYPE.to_string())).unwrap();
+        drop(sender);
+
+        let start = std::time::Instant::now();
+        let discovery = collect_servers(&receiver, Duration::from_secs(10));
+        assert!(discovery.partial);
+        assert!(discovery.servers.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_merge_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'sources/book.txt', 'ready', 0, 0);
+             UPDATE books SET duration = 300.0;
+             INSERT INTO progress (book_id, segment_index, audio_time, max_segment_index, max_audio_time, updated_at)
+             VALUES ('book', 10, 100.0, 20, 200.0, 1000);",
+        )
+        .unwrap();
+        let book_id = BookId::new("book");
+        let update = |segment_index, audio_time, updated_at| ProgressUpdate {
+            segment_index,
+            audio_time: Some(audio_time),
+            max_segment_index: None,
+            max_audio_time: None,
+            updated_at,
+        };
+
+        // An older update doesn't move the current position
+        let (merged, finished_at) =
+            merge_progress(&conn, &book_id, &update(5, 50.0, 500)).unwrap().unwrap();
+        assert_eq!((merged.segment_index, merged.updated_at), (10, 1000));
+        assert!(finished_at.is_none());
+
+        // A newer one does, and pushes the furthest position forward, here
+        // to the end of the book
+        let (merged, finished_at) =
+            merge_progress(&conn, &book_id, &update(30, 300.0, 2000)).unwrap().unwrap();
+        assert_eq!((merged.segment_index, merged.updated_at), (30, 2000));
+        assert_eq!(merged.max_segment_index, 30);
+        assert_eq!(merged.max_audio_time, Some(300.0));
+        assert!(finished_at.is_some());
+
+        // Rewinding keeps the furthest position, and the book stays finished
+        let (merged, finished_at) =
+            merge_progress(&conn, &book_id, &update(2, 20.0, 3000)).unwrap().unwrap();
+        assert_eq!(merged.segment_index, 2);
+        assert_eq!(merged.max_segment_index, 30);
+        assert!(finished_at.is_none());
+
+        assert!(merge_progress(&conn, &BookId::new("missing"), &update(0, 0.0, 0))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_check_bundle_headers() {
+        let octet = Some("application/octet-stream");
+        assert!(check_bundle_headers(octet, Some(100), 100).is_ok());
+        let with_params = Some("Application/Octet-Stream; charset=binary");
+        assert!(check_bundle_headers(with_params, None, 100).is_ok());
+
+        let error = check_bundle_headers(Some("text/html"), Some(10), 100).unwrap_err().to_string();
+        assert!(error.contains("text/html"));
+        let error = check_bundle_headers(None, Some(10), 100).unwrap_err().to_string();
+        assert!(error.contains("no content type"));
+        let error = check_bundle_headers(octet, Some(101), 100).unwrap_err().to_string();
+        assert!(error.contains("byte limit"));
+    }
+
+    #[test]
+    fn test_append_bundle_chunk() {
+        let mut data = Vec::new();
+        append_bundle_chunk(&mut data, b"PK\x03\x04", Some(6), 100).unwrap();
+        append_bundle_chunk(&mut data, b"ab", Some(6), 100).unwrap();
+        assert_eq!(data.len(), 6);
+
+        let error = append_bundle_chunk(&mut data, b"c", Some(6), 100).unwrap_err().to_string();
+        assert!(error.contains("declared"));
+        // A failed chunk isn't kept
+        assert_eq!(data.len(), 6);
+
+        let error = append_bundle_chunk(&mut data, b"cdef", None, 8).unwrap_err().to_string();
+        assert!(error.contains("byte limit"));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("My Book.actualbook"),
+            "attachment; filename=\"My Book.actualbook\"; filename*=UTF-8''My%20Book.actualbook"
+        );
+        assert_eq!(
+            content_disposition("Ça \"va\".actualbook"),
+            "attachment; filename=\"_a _va_.actualbook\"; filename*=UTF-8''%C3%87a%20%22va%22.actualbook"
+        );
+    }
+
+    #[test]
+    fn test_parse_byte_range() {
+        assert_eq!(parse_byte_range("bytes=0-99", 1000), Ok(Some((0, 99))));
+        assert_eq!(parse_byte_range("bytes=500-", 1000), Ok(Some((500, 999))));
+        assert_eq!(parse_byte_range("bytes=-100", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_byte_range("bytes=900-5000", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_byte_range("bytes=-5000", 1000), Ok(Some((0, 999))));
+
+        assert_eq!(parse_byte_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_byte_range("bytes=50-10", 1000), Err(()));
+        assert_eq!(parse_byte_range("bytes=abc-", 1000), Err(()));
+
+        // Unsupported forms fall back to the whole file
+        assert_eq!(parse_byte_range("items=0-5", 1000), Ok(None));
+        assert_eq!(parse_byte_range("bytes=0-5,10-20", 1000), Ok(None));
+    }
+
+    #[test]
+    fn test_probe_error_messages_are_distinguishable() {
+        let timed_out = ProbeError::TimedOut(Duration::from_millis(2500));
+        let refused = ProbeError::Refused("no route to host".to_string());
+        let foreign = ProbeError::NotActualReader("server returned 404 Not Found".to_string());
+        let tls = ProbeError::Tls("invalid peer certificate".to_string());
+
+        assert!(timed_out.to_string().starts_with("Connection timed out after 2500 ms"));
+        assert!(refused.to_string().starts_with("Connection refused"));
+        assert!(foreign.to_string().starts_with("Not an Actual Reader server"));
+        assert!(tls.to_string().starts_with("Secure connection failed"));
+
+        assert!(timed_out.is_retryable());
+        assert!(refused.is_retryable());
+        assert!(!foreign.is_retryable());
+        assert!(!tls.is_retryable());
+    }
+
+    #[test]
+    fn test_tls_failure_ignores_plain_http_peers() {
+        use rustls::CertificateError;
+
+        let mismatch = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure),
+        );
+        assert!(tls_failure(&mismatch).is_some());
+
+        let plain_http = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::InvalidMessage(rustls::InvalidMessage::InvalidContentType),
+        );
+        assert!(tls_failure(&plain_http).is_none());
+
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(tls_failure(&refused).is_none());
+    }
+
+    /// Server state over a library holding one narrated book, "book", whose
+    /// segments "a" and "b" each have a second of WAV narration.
+    fn test_server_state(root: &std::path::Path) -> SyncServerState {
+        let paths = AppPaths::new(root.to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+        db.connection()
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                 VALUES ('book', 'Book', 'txt', 'sources/book.txt', 'ready', 0, 0);
+                 INSERT INTO segments (id, book_id, idx, content)
+                 VALUES ('a', 'book', 0, 'One'), ('b', 'book', 1, 'Two');
+                 INSERT INTO markers (id, book_id, segment_id, start_time, end_time)
+                 VALUES ('m1', 'book', 'a', 0.0, 1.0), ('m2', 'book', 'b', 1.0, 2.0);",
+            )
+            .unwrap();
+
+        // Two seconds of 8 kHz mono 16-bit silence
+        let data_size = 8000u32 * 2 * 2;
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // channels
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&16000u32.to_le_bytes()); // byte rate
+        wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        wav.resize(44 + data_size as usize, 0);
+        std::fs::create_dir_all(paths.narration_path("book")).unwrap();
+        std::fs::write(paths.narration_audio_path("book", NarrationCodec::Wav), wav).unwrap();
+
+        SyncServerState {
+            db: Arc::new(db),
+            paths,
+            server_name: "Test".to_string(),
+            last_request: Arc::new(std::sync::Mutex::new(Instant::now())),
+            updates: broadcast::channel(4).0,
+            stopping: watch::channel(false).1,
+            app: None,
+        }
+    }
+
+    /// Send `request` through the sync router, returning the status.
+    async fn route(state: &SyncServerState, request: Request) -> StatusCode {
+        use tower::ServiceExt;
+
+        let router = sync_router(state.clone(), CorsOrigins::Any);
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    fn get_request(uri: &str) -> Request {
+        axum::http::Request::get(uri).body(axum::body::Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_book_routes_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_server_state(dir.path());
+
+        for uri in [
+            "/book/book",
+            "/book/book/audio",
+            "/book/book/markers",
+            "/book/book/segments",
+            "/book/book/segment/b/audio",
+        ] {
+            assert_eq!(route(&state, get_request(uri)).await, StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_route_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_server_state(dir.path());
+
+        let post = axum::http::Request::post("/book/book/progress")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(r#"{"segmentIndex": 1, "updatedAt": 100}"#))
+            .unwrap();
+        assert_eq!(route(&state, post).await, StatusCode::OK);
+        assert_eq!(route(&state, get_request("/book/book/progress")).await, StatusCode::OK);
+    }
+}
@@ -3,20 +3,40 @@
 //! Commands for narration generation using Chatterbox TTS engine.
 //! These commands are only available on desktop platforms.
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter, State};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
 
-use crate::models::{BookId, Marker, SegmentId, Voice, VoiceId};
-use crate::services::tts::{get_wav_duration, TtsService};
+use base64::Engine;
+
+use super::error::{CommandError, CommandResult, ResultExt};
+use super::progress::ProgressThrottle;
+use super::pronunciation::query_pronunciations;
+use crate::models::{
+    BookId, ImagePosition, Marker, NarrationMeta, NarrationStatus, SegmentId, SegmentType, Voice,
+    VoiceId,
+};
+use crate::services::ffmpeg;
+use crate::services::pronunciation::PronunciationRules;
+use crate::services::tts::{
+    append_silence, convert_wav, get_wav_duration, normalize_peak, probe_audio, requantize_wav,
+    splice_wav, trim_silence, voice_sample_wav, AudioFormat, ChatterboxParams, TtsService,
+    WavWriter, NORMALIZE_TARGET_PEAK,
+};
+use crate::services::vision::VisionService;
+use crate::storage::{dir_size, AppPaths, Database, NarrationCodec};
 use crate::{AppState, GenerationHandle};
 
 /// Stage of narration generation.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum GenerationStage {
     Extracting,
@@ -36,6 +56,50 @@ pub struct GenerationProgress {
     pub message: String,
 }
 
+/// A narration generation in progress, as returned by `list_active_generations`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationStatus {
+    pub book_id: BookId,
+    /// Stage of the latest progress update; None before the first one.
+    pub stage: Option<GenerationStage>,
+    pub current: u32,
+    pub total: u32,
+}
+
+/// Emit a `generation_progress` event, remembering it as the book's latest
+/// progress for `list_active_generations`.
+///
+/// Updates within a stage are throttled; the first update of each stage and
+/// the last of a stage are always emitted.
+fn emit_progress(events: &ProgressThrottle<GenerationProgress>, progress: GenerationProgress) {
+    let mut milestone = progress.current >= progress.total;
+    if let Some(state) = events.app_handle().try_state::<AppState>() {
+        if let Ok(mut latest) = state.generation_progress.lock() {
+            let previous = latest.insert(progress.book_id.as_str().to_string(), progress.clone());
+            milestone |= previous.map_or(true, |previous| previous.stage != progress.stage);
+        }
+    }
+    events.emit(progress, milestone);
+}
+
+/// Payload of the `segment_narrated` event, emitted as each segment finishes.
+///
+/// Lets the UI preview narration generated so far. Audio is referenced by
+/// path rather than sent in the event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentNarrated {
+    pub book_id: BookId,
+    pub segment_id: SegmentId,
+    /// Start time in the final narration, in seconds.
+    pub start: f64,
+    /// End time in the final narration, in seconds.
+    pub end: f64,
+    /// Cached audio file for this segment.
+    pub audio_path: Option<String>,
+}
+
 /// Error event payload.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -44,6 +108,559 @@ pub struct GenerationError {
     pub message: String,
 }
 
+/// A voice override covering an inclusive range of segment indices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentVoiceOverride {
+    pub book_id: BookId,
+    pub start_index: u32,
+    pub end_index: u32,
+    pub voice_id: VoiceId,
+}
+
+/// Everything that refers to a voice, checked before it's deleted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceUsage {
+    /// The voice is marked as the default in the voices list.
+    pub is_default: bool,
+    /// The `defaultVoice` setting names this voice.
+    pub default_setting: bool,
+    /// Books with per-segment overrides narrated in this voice.
+    pub book_ids: Vec<BookId>,
+    /// Total number of override ranges using this voice.
+    pub override_count: u32,
+}
+
+impl VoiceUsage {
+    /// True if deleting the voice would leave something pointing at nothing.
+    pub fn is_used(&self) -> bool {
+        self.is_default || self.default_setting || self.override_count > 0
+    }
+}
+
+/// Result of probing a single backend service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceProbe {
+    /// Base URL that was probed.
+    pub url: String,
+    /// Whether the service responded.
+    pub available: bool,
+    /// Round-trip time of the probe in milliseconds.
+    pub latency_ms: u64,
+}
+
+/// Reachability of the narration backend services.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceStatus {
+    /// Chatterbox TTS server.
+    pub tts: ServiceProbe,
+    /// Qwen2.5-VL vision server.
+    pub vision: ServiceProbe,
+}
+
+/// Estimated cost of narrating a book, computed without synthesizing anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NarrationEstimate {
+    /// Number of segments that would be narrated.
+    pub segment_count: u32,
+    /// Total characters of text that would be spoken.
+    pub total_chars: u64,
+    /// Estimated wall-clock time to synthesize the narration, in seconds.
+    pub generation_seconds: f64,
+    /// Estimated length of the narration audio, in seconds.
+    pub audio_seconds: f64,
+    /// Estimated size of the narration audio file, in bytes.
+    pub audio_bytes: u64,
+}
+
+impl NarrationEstimate {
+    /// Estimate from the character count of each narrated segment and the
+    /// calibrated rates in settings.
+    fn from_char_counts(
+        char_counts: impl IntoIterator<Item = usize>,
+        settings: &super::settings::Settings,
+    ) -> Self {
+        let (segment_count, total_chars) = char_counts
+            .into_iter()
+            .filter(|chars| *chars > 0)
+            .fold((0u32, 0u64), |(count, total), chars| {
+                (count + 1, total + chars as u64)
+            });
+
+        let codec = NarrationCodec::from_extension(&settings.narration_codec)
+            .unwrap_or(NarrationCodec::Wav);
+        let bytes_per_second = match ffmpeg::encoded_kbps(codec) {
+            Some(kbps) => f64::from(kbps) * 1000.0 / 8.0,
+            // Uncompressed PCM
+            None => {
+                f64::from(settings.narration_sample_rate)
+                    * f64::from(settings.narration_channels)
+                    * f64::from(settings.narration_bit_depth / 8)
+            }
+        };
+
+        let audio_seconds = total_chars as f64 / settings.speech_chars_per_second;
+        Self {
+            segment_count,
+            total_chars,
+            generation_seconds: total_chars as f64 / settings.synthesis_chars_per_second,
+            audio_seconds,
+            audio_bytes: (audio_seconds * bytes_per_second).round() as u64,
+        }
+    }
+}
+
+/// A segment queued for narration along with the voice chosen for it.
+#[derive(Debug, Clone)]
+struct NarrationSegment {
+    id: String,
+    /// Position in the book.
+    index: u32,
+    content: String,
+    voice_sample: String,
+    /// Set for image segments.
+    image: Option<NarrationImage>,
+    /// Set for heading segments.
+    heading_level: Option<u8>,
+    /// False for segments left out of narration.
+    narrate: bool,
+}
+
+impl NarrationSegment {
+    /// Text that will be spoken for this segment; empty if it is skipped.
+    fn narration_text(&self, mode: ImageNarrationMode) -> &str {
+        if !self.narrate {
+            return "";
+        }
+        let text = match (&self.image, mode) {
+            (Some(_), ImageNarrationMode::Skip) => "",
+            (Some(image), ImageNarrationMode::AltTextOnly) => {
+                image.alt_text.as_deref().unwrap_or("")
+            }
+            (Some(image), ImageNarrationMode::Caption) => {
+                image.narration_text().unwrap_or(&self.content)
+            }
+            (None, _) => self.content.as_str(),
+        };
+        text.trim()
+    }
+}
+
+/// Word a heading level is announced with: parts, then chapters, then sections.
+fn heading_level_name(level: u8) -> &'static str {
+    match level {
+        1 => "Part",
+        2 => "Chapter",
+        _ => "Section",
+    }
+}
+
+/// Fill a `headingAnnouncement` template with a heading's level and text.
+fn announce_heading(template: &str, level: u8, text: &str) -> String {
+    // Substitute the text last so placeholders inside it are left alone
+    template
+        .replace("{level_name}", heading_level_name(level))
+        .replace("{text}", text)
+}
+
+/// Image details needed to caption and narrate an image segment.
+#[derive(Debug, Clone)]
+struct NarrationImage {
+    source_path: String,
+    alt_text: Option<String>,
+    /// Existing caption, if one has been generated.
+    caption: Option<String>,
+    /// Prompt the existing caption was generated with.
+    caption_prompt: Option<String>,
+    /// Prompt to caption with for this generation.
+    prompt: String,
+}
+
+impl NarrationImage {
+    /// Whether the stored caption is missing or was made with a different prompt.
+    fn needs_caption(&self) -> bool {
+        self.caption.is_none() || self.caption_prompt.as_deref() != Some(self.prompt.as_str())
+    }
+
+    /// Text to narrate: the caption, falling back to the alt text.
+    fn narration_text(&self) -> Option<&str> {
+        self.caption
+            .as_deref()
+            .or(self.alt_text.as_deref())
+            .filter(|text| !text.trim().is_empty())
+    }
+}
+
+/// How image segments are turned into narration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageNarrationMode {
+    /// Leave images out of the audio and markers entirely.
+    Skip,
+    /// Narrate the source alt text without calling the vision service.
+    AltTextOnly,
+    /// Narrate a caption generated by the vision service.
+    Caption,
+}
+
+impl ImageNarrationMode {
+    /// Parse the `imageNarrationMode` setting, defaulting to captions.
+    fn from_setting(value: &str) -> Self {
+        match value {
+            "skip" => Self::Skip,
+            "altTextOnly" => Self::AltTextOnly,
+            _ => Self::Caption,
+        }
+    }
+}
+
+/// Services and options for a single narration generation run.
+struct GenerationConfig {
+    tts: TtsService,
+    vision: VisionService,
+    image_mode: ImageNarrationMode,
+    /// Format every segment is converted to before concatenation.
+    audio_format: AudioFormat,
+    /// Peak-normalize each segment so loudness is consistent.
+    normalize: bool,
+    /// Amplitude below which leading and trailing audio is trimmed as
+    /// silence, or None to keep segments as synthesized.
+    trim_threshold: Option<f32>,
+    /// Silence appended to each segment, in milliseconds.
+    segment_gap_ms: u32,
+    /// Format the finished narration is saved in.
+    codec: NarrationCodec,
+    /// Bits per sample of the finished narration; segments are processed
+    /// at 16 bits and requantized as they're concatenated.
+    bit_depth: u16,
+    /// Replacements applied to text before it is synthesized.
+    pronunciations: PronunciationRules,
+    /// Template headings are announced with, or empty to speak them as is.
+    heading_announcement: String,
+    /// Keep the segment cache once the narration is ready.
+    keep_cache: bool,
+    /// Language of the book, passed on to the TTS engine.
+    language: Option<String>,
+    /// Chatterbox sampling parameters.
+    params: ChatterboxParams,
+    /// Voice the book is narrated with, recorded in its narration metadata.
+    voice_id: Option<VoiceId>,
+    voice_name: Option<String>,
+}
+
+impl GenerationConfig {
+    fn from_settings(settings: &super::settings::Settings) -> Self {
+        Self {
+            tts: TtsService::with_url(settings.chatterbox_url.clone()),
+            vision: VisionService::new(settings.vision_url.clone()),
+            image_mode: ImageNarrationMode::from_setting(&settings.image_narration_mode),
+            audio_format: AudioFormat {
+                sample_rate: settings.narration_sample_rate,
+                channels: settings.narration_channels,
+            },
+            normalize: settings.normalize_narration,
+            trim_threshold: settings
+                .trim_silence
+                .then_some(settings.silence_threshold as f32),
+            segment_gap_ms: settings.segment_gap_ms,
+            codec: NarrationCodec::from_extension(&settings.narration_codec)
+                .unwrap_or(NarrationCodec::Wav),
+            bit_depth: settings.narration_bit_depth,
+            pronunciations: PronunciationRules::default(),
+            heading_announcement: settings.heading_announcement.clone(),
+            keep_cache: settings.keep_narration_cache,
+            language: None,
+            params: settings.chatterbox_params(),
+            voice_id: None,
+            voice_name: None,
+        }
+    }
+
+    /// Text to synthesize for a segment; empty if it is skipped.
+    ///
+    /// Heading announcements and pronunciation rules only change what is
+    /// spoken; the segment text and its marker stay as they are.
+    fn spoken_text<'a>(&self, segment: &'a NarrationSegment) -> Cow<'a, str> {
+        let text = segment.narration_text(self.image_mode);
+        match segment.heading_level {
+            Some(level) if !text.is_empty() && !self.heading_announcement.trim().is_empty() => {
+                let announced = announce_heading(&self.heading_announcement, level, text);
+                Cow::Owned(self.pronunciations.apply(&announced).into_owned())
+            }
+            _ => self.pronunciations.apply(text),
+        }
+    }
+
+    /// Describe a narration generated with this config, finished now.
+    fn narration_meta(&self) -> NarrationMeta {
+        NarrationMeta {
+            engine: "chatterbox".to_string(),
+            voice_id: self.voice_id.clone(),
+            voice_name: self.voice_name.clone(),
+            exag: self.params.exag,
+            cfg: self.params.cfg,
+            temp: self.params.temp,
+            codec: self.codec.extension().to_string(),
+            generated_at: current_timestamp(),
+        }
+    }
+}
+
+/// Choose the captioning prompt for an image.
+///
+/// A book-level prompt wins, then a prompt for the image's position, then
+/// the default `captionPrompt` setting.
+fn choose_caption_prompt(
+    book_prompt: Option<&str>,
+    position: ImagePosition,
+    settings: &super::settings::Settings,
+) -> String {
+    let position_prompt = match position {
+        ImagePosition::FullPage => settings.caption_prompt_full_page.as_str(),
+        ImagePosition::Inline => settings.caption_prompt_inline.as_str(),
+        _ => "",
+    };
+
+    [book_prompt.unwrap_or(""), position_prompt]
+        .into_iter()
+        .map(str::trim)
+        .find(|prompt| !prompt.is_empty())
+        .unwrap_or(settings.caption_prompt.as_str())
+        .to_string()
+}
+
+/// Load a book's segments for narration in reading order.
+///
+/// `voice_for` picks the voice sample for each segment index.
+fn query_narration_segments(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    settings: &super::settings::Settings,
+    voice_for: impl Fn(u32) -> String,
+) -> CommandResult<Vec<NarrationSegment>> {
+    // Book-level captioning prompt, if one has been set
+    let book_caption_prompt: Option<String> = conn
+        .query_row(
+            "SELECT caption_prompt FROM books WHERE id = ?",
+            rusqlite::params![book_id.as_str()],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound("Book not found".to_string())
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
+        })?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.idx, s.content,
+                    i.source_path, i.alt_text, i.position, i.caption, i.caption_prompt,
+                    s.segment_type, s.narrate
+             FROM segments s LEFT JOIN segment_images i ON i.segment_id = s.id
+             WHERE s.book_id = ? ORDER BY s.idx ASC",
+        )
+        .context("Failed to prepare query")?;
+
+    let segments = stmt
+        .query_map(rusqlite::params![book_id.as_str()], |row| {
+            let image = match row.get::<_, Option<String>>(3)? {
+                Some(source_path) => {
+                    let position = row
+                        .get::<_, Option<String>>(5)?
+                        .and_then(|p| ImagePosition::from_str(&p))
+                        .unwrap_or_default();
+                    Some(NarrationImage {
+                        source_path,
+                        alt_text: row.get(4)?,
+                        caption: row.get(6)?,
+                        caption_prompt: row.get(7)?,
+                        prompt: choose_caption_prompt(
+                            book_caption_prompt.as_deref(),
+                            position,
+                            settings,
+                        ),
+                    })
+                }
+                None => None,
+            };
+
+            let heading_level = match SegmentType::from_str(&row.get::<_, String>(8)?) {
+                Some(SegmentType::Heading { level }) if image.is_none() => Some(level),
+                _ => None,
+            };
+
+            let index = row.get(1)?;
+            Ok(NarrationSegment {
+                id: row.get(0)?,
+                index,
+                content: row.get(2)?,
+                voice_sample: voice_for(index),
+                image,
+                heading_level,
+                narrate: row.get(9)?,
+            })
+        })
+        .context("Failed to query segments")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read segment")?;
+
+    Ok(segments)
+}
+
+/// Segments to narrate from `generate_narration`'s optional `start_index`
+/// and `end_index`, both inclusive, or None for the whole book.
+///
+/// A missing bound is the start or end of the book, and a range covering
+/// every segment is the whole book.
+fn narration_range(
+    start_index: Option<u32>,
+    end_index: Option<u32>,
+    last_index: u32,
+) -> CommandResult<Option<RangeInclusive<u32>>> {
+    let start = start_index.unwrap_or(0);
+    let end = end_index.unwrap_or(last_index);
+    if start > end || end > last_index {
+        return Err(CommandError::InvalidInput(format!(
+            "Invalid segment range {}-{}: the book's segments are 0-{}",
+            start, end, last_index
+        )));
+    }
+    Ok((start > 0 || end < last_index).then_some(start..=end))
+}
+
+/// Where a narrated segment range goes in a book's existing narration.
+#[derive(Debug)]
+struct NarrationSplice {
+    range: RangeInclusive<u32>,
+    /// Markers of segments before the range, which stay as they are.
+    before: Vec<Marker>,
+    /// Markers of segments after the range, which shift by the change in
+    /// length.
+    after: Vec<Marker>,
+    /// Span of the existing audio the range replaces, in seconds. Where no
+    /// segment in the range was narrated, the range is inserted at `start`.
+    start: f64,
+    end: f64,
+    /// Segments outside the range with no marker. The splice completes a
+    /// partial narration when none of them has anything to say.
+    unnarrated: Vec<NarrationSegment>,
+}
+
+impl NarrationSplice {
+    /// Plan splicing `range` into narration with `markers`, each paired with
+    /// its segment's index and in reading order.
+    fn new(markers: &[(u32, Marker)], range: RangeInclusive<u32>) -> Self {
+        let select = |keep: &dyn Fn(u32) -> bool| -> Vec<Marker> {
+            markers
+                .iter()
+                .filter(|(index, _)| keep(*index))
+                .map(|(_, marker)| marker.clone())
+                .collect()
+        };
+        let before = select(&|index| index < *range.start());
+        let inside = select(&|index| range.contains(&index));
+        let after = select(&|index| index > *range.end());
+
+        let start = inside
+            .first()
+            .or(after.first())
+            .map(|marker| marker.start)
+            .or_else(|| before.last().map(|marker| marker.end))
+            .unwrap_or(0.0);
+        let end = inside.last().map_or(start, |marker| marker.end);
+
+        Self { range, before, after, start, end, unnarrated: Vec::new() }
+    }
+
+    /// The book's markers once the range is narrated as `narrated`, which
+    /// are timed from [`NarrationSplice::start`].
+    fn markers(&self, narrated: Vec<Marker>) -> Vec<Marker> {
+        let narrated_end = narrated.last().map_or(self.start, |marker| marker.end);
+        let delta = narrated_end - self.end;
+
+        let mut markers = self.before.clone();
+        markers.extend(narrated);
+        markers.extend(self.after.iter().map(|marker| Marker {
+            start: marker.start + delta,
+            end: marker.end + delta,
+            ..marker.clone()
+        }));
+        markers
+    }
+}
+
+/// Plan splicing a narrated `range` into a book's narration, or None if the
+/// book has no ready, stale or partial narration to splice into.
+///
+/// Compressed narration is rebuilt from the segment cache, so every segment
+/// narrated outside the range must still have its cached audio.
+fn plan_splice(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    book_id: &BookId,
+    segments: &[NarrationSegment],
+    range: RangeInclusive<u32>,
+) -> CommandResult<Option<NarrationSplice>> {
+    let status: String = conn
+        .query_row(
+            "SELECT narration_status FROM books WHERE id = ?",
+            rusqlite::params![book_id.as_str()],
+            |row| row.get(0),
+        )
+        .context("Failed to query narration status")?;
+    let spliceable = [NarrationStatus::Ready, NarrationStatus::Stale, NarrationStatus::Partial];
+    if !spliceable.iter().any(|s| s.as_str() == status) {
+        return Ok(None);
+    }
+
+    let audio_path = paths
+        .find_narration_audio(book_id.as_str())
+        .ok_or_else(|| CommandError::NotFound("Narration audio not found".to_string()))?;
+    let indices: HashMap<&str, u32> = segments
+        .iter()
+        .map(|segment| (segment.id.as_str(), segment.index))
+        .collect();
+    let markers: Vec<(u32, Marker)> = super::reader::query_markers(conn, book_id)?
+        .into_iter()
+        .filter_map(|marker| Some((*indices.get(marker.segment_id.as_str())?, marker)))
+        .collect();
+    let mut splice = NarrationSplice::new(&markers, range);
+    let narrated: HashSet<&str> = markers
+        .iter()
+        .map(|(_, marker)| marker.segment_id.as_str())
+        .collect();
+    splice.unnarrated = segments
+        .iter()
+        .filter(|segment| {
+            !splice.range.contains(&segment.index) && !narrated.contains(segment.id.as_str())
+        })
+        .cloned()
+        .collect();
+
+    let codec = NarrationCodec::from_path(&audio_path).unwrap_or(NarrationCodec::Wav);
+    if codec != NarrationCodec::Wav {
+        let uncached = splice.before.iter().chain(&splice.after).find(|marker| {
+            !paths
+                .segment_cache_path(book_id.as_str(), marker.segment_id.as_str())
+                .is_file()
+        });
+        if let Some(marker) = uncached {
+            return Err(CommandError::InvalidInput(format!(
+                "Cannot splice into {} narration without cached audio for segment {}; \
+                 narrate the whole book, or turn on keepNarrationCache first",
+                codec.extension(),
+                marker.segment_id
+            )));
+        }
+    }
+
+    Ok(Some(splice))
+}
+
 /// Get the current Unix timestamp in seconds.
 fn current_timestamp() -> i64 {
     SystemTime::now()
@@ -62,66 +679,137 @@ fn current_timestamp() -> i64 {
 ///
 /// Progress updates are emitted via the `generation_progress` event.
 /// Completion is signaled via `generation_complete` or `generation_error` events.
+///
+/// `start_index` and `end_index` narrate only that inclusive range of
+/// segments, such as the first chapter as a preview. A book with ready or
+/// stale narration has the range spliced into it, replacing the range's old
+/// audio and shifting later markers, and keeps its status. Otherwise the
+/// range becomes the book's narration. Compressed narration can only be
+/// spliced into while the segment cache holds every other segment.
 #[tauri::command]
 pub async fn generate_narration(
     book_id: BookId,
     voice_id: VoiceId,
+    start_index: Option<u32>,
+    end_index: Option<u32>,
     app_handle: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     // Check if generation is already in progress for this book
     {
         let generations = state.active_generations.read().await;
         if generations.contains_key(book_id.as_str()) {
-            return Err("Generation already in progress for this book".to_string());
+            return Err(CommandError::Conflict(
+                "Generation already in progress for this book".to_string(),
+            ));
         }
     }
 
-    // Get the voice sample path
-    let voice_sample_path = {
-        let conn = state.db.connection().lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT sample_path FROM voices WHERE id = ?")
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let settings = super::settings::load_settings(&state.db)?;
 
-        stmt.query_row(rusqlite::params![voice_id.as_str()], |row| {
-            row.get::<_, String>(0)
-        })
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => "Voice not found".to_string(),
-            _ => format!("Database error: {}", e),
-        })?
+    // Get the voice sample path, and its name for the narration metadata
+    let (voice_sample_path, voice_name) = {
+        let conn = state.db.connection().lock().unwrap();
+        let sample_path = query_voice_sample(&conn, &voice_id)?;
+        let name: Option<String> = conn
+            .query_row(
+                "SELECT name FROM voices WHERE id = ?",
+                rusqlite::params![voice_id.as_str()],
+                |row| row.get(0),
+            )
+            .ok();
+        (sample_path, name)
     };
 
-    // Get segments for the book
-    let segments: Vec<(String, String)> = {
+    // Per-segment voice overrides, resolved to their sample paths
+    let overrides = {
         let conn = state.db.connection().lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT id, content FROM segments WHERE book_id = ? ORDER BY idx ASC")
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        query_voice_overrides(&conn, &book_id)?
+    };
 
-        let result: Vec<(String, String)> = stmt
-            .query_map(rusqlite::params![book_id.as_str()], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })
-            .map_err(|e| format!("Failed to query segments: {}", e))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to read segment: {}", e))?;
-        result
+    // Get segments for the book, choosing each segment's voice
+    let mut segments = {
+        let conn = state.db.connection().lock().unwrap();
+        query_narration_segments(&conn, &book_id, &settings, |index| {
+            override_sample(&overrides, index).unwrap_or_else(|| voice_sample_path.clone())
+        })?
     };
 
     if segments.is_empty() {
-        return Err("Book has no segments to narrate".to_string());
+        return Err(CommandError::InvalidInput(
+            "Book has no segments to narrate".to_string(),
+        ));
     }
 
-    // Update narration_status to 'generating'
-    {
+    // Narrate only the requested range, spliced into any existing narration.
+    // A range narrated on its own leaves the book partially narrated
+    let last_index = segments.last().map_or(0, |segment| segment.index);
+    let range = narration_range(start_index, end_index, last_index)?;
+    let partial = range.is_some();
+    let splice = match range {
+        Some(range) => {
+            let splice = {
+                let conn = state.db.connection().lock().unwrap();
+                plan_splice(&conn, &state.paths(), &book_id, &segments, range.clone())?
+            };
+            segments.retain(|segment| range.contains(&segment.index));
+            log::info!(
+                "book={}: narrating segments {}-{}{}",
+                book_id,
+                range.start(),
+                range.end(),
+                if splice.is_some() { " into the existing narration" } else { "" }
+            );
+            splice
+        }
+        None => None,
+    };
+
+    // Compile pronunciation rules up front so a bad pattern fails here
+    let pronunciations = {
+        let conn = state.db.connection().lock().unwrap();
+        PronunciationRules::compile(&query_pronunciations(&conn)?)
+            .context("Invalid pronunciation rule")?
+    };
+
+    // The finished narration, plus the 16-bit WAV copy of each segment kept
+    // in the segment cache
+    let image_mode = ImageNarrationMode::from_setting(&settings.image_narration_mode);
+    let estimate = NarrationEstimate::from_char_counts(
+        segments
+            .iter()
+            .map(|segment| segment.narration_text(image_mode).chars().count()),
+        &settings,
+    );
+    let cache_bytes = estimate.audio_seconds
+        * f64::from(settings.narration_sample_rate)
+        * f64::from(settings.narration_channels)
+        * 2.0;
+    super::library::ensure_disk_space(
+        &state.paths().narration,
+        estimate.audio_bytes.saturating_add(cache_bytes.round() as u64),
+    )?;
+
+    let language: Option<String> = {
+        let conn = state.db.connection().lock().unwrap();
+        conn.query_row(
+            "SELECT language FROM books WHERE id = ?",
+            rusqlite::params![book_id.as_str()],
+            |row| row.get(0),
+        )
+        .context("Failed to query book language")?
+    };
+
+    // Update narration_status to 'generating'. Narration being spliced into
+    // stays playable until the range replaces part of it
+    let splicing = splice.is_some();
+    if !splicing {
         let conn = state.db.connection().lock().unwrap();
         conn.execute(
             "UPDATE books SET narration_status = 'generating', updated_at = ? WHERE id = ?",
             rusqlite::params![current_timestamp(), book_id.as_str()],
         )
-        .map_err(|e| format!("Failed to update book status: {}", e))?;
+        .context("Failed to update book status")?;
     }
 
     // Create cancellation flag
@@ -131,16 +819,28 @@ pub async fn generate_narration(
     // Clone necessary data for the spawned task
     let book_id_clone = book_id.clone();
     let db = state.db.clone();
-    let narration_dir = state.paths.narration.clone();
+    let paths = state.paths();
     let active_generations = state.active_generations.clone();
+    let generation_progress = state.generation_progress.clone();
+    let sync_updates = state.sync_updates.clone();
+    let config = GenerationConfig {
+        pronunciations,
+        language,
+        voice_id: Some(voice_id.clone()),
+        voice_name,
+        ..GenerationConfig::from_settings(&settings)
+    };
 
     // Spawn the generation task
     let task_handle = tokio::spawn(async move {
         let result = run_generation(
             &book_id_clone,
-            &voice_sample_path,
+            &config,
             segments,
-            &narration_dir,
+            splice,
+            partial,
+            &db,
+            &paths,
             &app_handle,
             cancel_flag_clone,
         )
@@ -149,39 +849,40 @@ pub async fn generate_narration(
         // Handle result - use a block to ensure conn is dropped before the await
         let now = current_timestamp();
         match result {
-            Ok(narration_path) => {
-                // Update book status to 'ready'
-                {
-                    let conn = db.connection().lock().unwrap();
-                    if let Err(e) = conn.execute(
-                        "UPDATE books SET narration_status = 'ready', narration_path = ?, updated_at = ? WHERE id = ?",
-                        rusqlite::params![narration_path, now, book_id_clone.as_str()],
-                    ) {
-                        log::error!("Failed to update book status: {}", e);
-                    }
-                }
+            Ok((narration_path, duration)) => {
+                // run_generation already marked the book ready with its markers
+                log::info!(
+                    "book={}: narration ready at {} ({:.1}s of audio)",
+                    book_id_clone,
+                    narration_path,
+                    duration
+                );
 
                 // Emit completion event
                 if let Err(e) = app_handle.emit("generation_complete", &book_id_clone) {
                     log::error!("Failed to emit completion event: {}", e);
                 }
+                let _ = sync_updates.send(super::sync::SyncUpdate::LibraryChanged);
             }
             Err(e) => {
-                // Update book status back to 'none'
-                {
+                log::warn!("book={}: generation failed: {}", book_id_clone, e);
+
+                // Update book status back to 'none', unless the range was
+                // being spliced into narration that is still intact
+                if !splicing {
                     let conn = db.connection().lock().unwrap();
                     if let Err(db_err) = conn.execute(
                         "UPDATE books SET narration_status = 'none', updated_at = ? WHERE id = ?",
                         rusqlite::params![now, book_id_clone.as_str()],
                     ) {
-                        log::error!("Failed to reset book status: {}", db_err);
+                        log::error!("book={}: failed to reset book status: {}", book_id_clone, db_err);
                     }
                 }
 
                 // Emit error event
                 let error = GenerationError {
                     book_id: book_id_clone.clone(),
-                    message: e,
+                    message: e.to_string(),
                 };
                 if let Err(emit_err) = app_handle.emit("generation_error", &error) {
                     log::error!("Failed to emit error event: {}", emit_err);
@@ -192,6 +893,9 @@ pub async fn generate_narration(
         // Remove from active generations
         let mut generations = active_generations.write().await;
         generations.remove(book_id_clone.as_str());
+        if let Ok(mut latest) = generation_progress.lock() {
+            latest.remove(book_id_clone.as_str());
+        }
     });
 
     // Store the generation handle
@@ -209,31 +913,100 @@ pub async fn generate_narration(
     Ok(())
 }
 
+/// Estimate how long narrating a book would take and how large it would be.
+///
+/// Uses the same segments and image narration rules as `generate_narration`
+/// but synthesizes nothing. Images that still need a caption are counted by
+/// their current caption or alt text. The rates come from the
+/// `synthesisCharsPerSecond` and `speechCharsPerSecond` settings, which are
+/// calibrated after every completed generation.
+#[tauri::command]
+pub async fn estimate_narration(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<NarrationEstimate> {
+    let settings = super::settings::load_settings(&state.db)?;
+    let image_mode = ImageNarrationMode::from_setting(&settings.image_narration_mode);
+
+    let segments = {
+        let conn = state.db.connection().lock().unwrap();
+        query_narration_segments(&conn, &book_id, &settings, |_| String::new())?
+    };
+
+    Ok(NarrationEstimate::from_char_counts(
+        segments
+            .iter()
+            .map(|segment| segment.narration_text(image_mode).chars().count()),
+        &settings,
+    ))
+}
+
 /// Internal function to run the generation process.
+///
+/// Each segment's marker is saved as it completes, and segments already
+/// narrated by an interrupted run are reused from the segment cache. The
+/// book is marked ready once the audio and the full marker set are saved.
+///
+/// With a `splice`, the segments are a range narrated into the existing
+/// narration instead. The spliced audio is staged and checked against the
+/// markers before either is saved, so the narration stays intact if the run
+/// fails. A `partial` run
+/// without one narrates a range of a book that had no narration, which is
+/// marked partial rather than ready.
+///
+/// Returns the path of the narration audio file and its total duration in seconds.
+#[allow(clippy::too_many_arguments)]
 async fn run_generation(
     book_id: &BookId,
-    voice_sample: &str,
-    segments: Vec<(String, String)>,
-    narration_dir: &Path,
+    config: &GenerationConfig,
+    mut segments: Vec<NarrationSegment>,
+    splice: Option<NarrationSplice>,
+    partial: bool,
+    db: &Database,
+    paths: &AppPaths,
     app_handle: &AppHandle,
     cancel_flag: Arc<AtomicBool>,
-) -> Result<String, String> {
-    let tts = TtsService::new();
+) -> CommandResult<(String, f64)> {
+    let tts = &config.tts;
 
     // Check if TTS server is available
     if !tts.is_available().await {
-        return Err("Chatterbox TTS server is not available. Please ensure it's running at http://localhost:60001".to_string());
+        return Err(CommandError::ServiceUnavailable(format!(
+            "Chatterbox TTS server is not available. Please ensure it's running at {}",
+            tts.base_url()
+        )));
     }
 
     let total_segments = segments.len() as u32;
-    let mut audio_segments: Vec<Vec<u8>> = Vec::with_capacity(segments.len());
+    let mut segment_files: Vec<PathBuf> = Vec::with_capacity(segments.len());
+    let progress_events = ProgressThrottle::new(app_handle.clone(), "generation_progress");
     let mut markers: Vec<Marker> = Vec::with_capacity(segments.len());
-    let mut current_time: f64 = 0.0;
+    let mut current_time: f64 = splice.as_ref().map_or(0.0, |splice| splice.start);
+    let mut narrated_chars: usize = 0;
+    let mut synthesis_seconds: f64 = 0.0;
+    let mut synthesized_seconds: f64 = 0.0;
+
+    // Markers left by an interrupted generation, to resume from
+    let partial_markers = {
+        let conn = db.connection().lock().unwrap();
+        query_partial_markers(&conn, book_id)?
+    };
+
+    // Per-segment audio is cached so markers can be rebuilt without re-synthesis
+    let cache_dir = paths.segment_cache_dir(book_id.as_str());
+    std::fs::create_dir_all(&cache_dir).context("Failed to create segment cache directory")?;
+
+    log::info!(
+        "book={}: generating narration for {} segment(s) with {} codec",
+        book_id,
+        total_segments,
+        config.codec.extension()
+    );
 
     // Emit extracting stage
-    let _ = app_handle.emit(
-        "generation_progress",
-        &GenerationProgress {
+    emit_progress(
+        &progress_events,
+        GenerationProgress {
             book_id: book_id.clone(),
             stage: GenerationStage::Extracting,
             current: 0,
@@ -242,23 +1015,40 @@ async fn run_generation(
         },
     );
 
+    if config.image_mode == ImageNarrationMode::Caption {
+        caption_images(
+            book_id,
+            &config.vision,
+            &mut segments,
+            db,
+            paths,
+            |progress| emit_progress(&progress_events, progress),
+            &cancel_flag,
+        )
+        .await?;
+    }
+
     // Generate audio for each segment
-    for (i, (segment_id, content)) in segments.into_iter().enumerate() {
+    for (i, segment) in segments.into_iter().enumerate() {
         // Check for cancellation
         if cancel_flag.load(Ordering::Relaxed) {
-            return Err("Generation cancelled".to_string());
+            log::info!("book={} segment={}: generation cancelled", book_id, i);
+            return Err(CommandError::Conflict("Generation cancelled".to_string()));
         }
 
-        // Skip empty segments
-        let content = content.trim();
-        if content.is_empty() {
+        let spoken = config.spoken_text(&segment);
+
+        // Skip empty segments, dropping any stale cached audio so the cache
+        // only covers segments that are actually narrated
+        if spoken.trim().is_empty() {
+            let _ = std::fs::remove_file(paths.segment_cache_path(book_id.as_str(), &segment.id));
             continue;
         }
 
         // Emit progress
-        let _ = app_handle.emit(
-            "generation_progress",
-            &GenerationProgress {
+        emit_progress(
+            &progress_events,
+            GenerationProgress {
                 book_id: book_id.clone(),
                 stage: GenerationStage::Narrating,
                 current: i as u32 + 1,
@@ -267,36 +1057,87 @@ async fn run_generation(
             },
         );
 
-        // Generate audio for this segment
-        let audio = tts
-            .generate_audio(content, voice_sample, 0.3, 0.5, 0.8)
-            .await
-            .map_err(|e| format!("TTS generation failed for segment {}: {}", i + 1, e))?;
+        // Reuse audio cached by an interrupted generation when its marker
+        // was persisted for the same input
+        let cache_path = paths.segment_cache_path(book_id.as_str(), &segment.id);
+        let key = partial_marker_key(&segment, &spoken, config);
+        let resumed = partial_markers
+            .get(&segment.id)
+            .and_then(|partial| partial.resume_duration(&key, &cache_path));
+
+        let duration = match resumed {
+            Some(duration) => {
+                log::debug!("book={} segment={}: resumed from segment cache", book_id, i);
+                duration
+            }
+            None => {
+                // Generate audio for this segment
+                let started = Instant::now();
+                let audio = synthesize_segment(config, &spoken, &segment.voice_sample, i + 1).await?;
+                let elapsed = started.elapsed().as_secs_f64();
+                synthesis_seconds += elapsed;
+                narrated_chars += spoken.chars().count();
+                log::debug!(
+                    "book={} segment={}: synthesized {} chars in {:.2}s",
+                    book_id,
+                    i,
+                    spoken.chars().count(),
+                    elapsed
+                );
+
+                // Get duration of this audio segment
+                let duration = get_wav_duration(&audio).context("Failed to get audio duration")?;
+                std::fs::write(&cache_path, &audio).context("Failed to cache segment audio")?;
+                synthesized_seconds += duration;
+                duration
+            }
+        };
 
-        // Get duration of this audio segment
-        let duration = get_wav_duration(&audio)
-            .map_err(|e| format!("Failed to get audio duration: {}", e))?;
+        // Audio trimmed to nothing gets no marker, like an empty segment,
+        // since a marker must have some length
+        if duration <= 0.0 {
+            let _ = std::fs::remove_file(&cache_path);
+            continue;
+        }
 
-        // Create marker for this segment
-        markers.push(Marker {
-            segment_id: SegmentId::new(segment_id),
+        // Create marker for this segment and persist it, so timing survives
+        // a crash before the narration is finalized
+        let marker = Marker {
+            segment_id: SegmentId::new(segment.id),
             start: current_time,
             end: current_time + duration,
-        });
+        };
+        if splice.is_none() {
+            let conn = db.connection().lock().unwrap();
+            save_partial_marker(&conn, book_id, &marker, &key)?;
+        }
+
+        let _ = app_handle.emit(
+            "segment_narrated",
+            &SegmentNarrated {
+                book_id: book_id.clone(),
+                segment_id: marker.segment_id.clone(),
+                start: marker.start,
+                end: marker.end,
+                audio_path: Some(cache_path.to_string_lossy().to_string()),
+            },
+        );
+
+        markers.push(marker);
 
         current_time += duration;
-        audio_segments.push(audio);
+        segment_files.push(cache_path);
     }
 
     // Check for cancellation before finalizing
     if cancel_flag.load(Ordering::Relaxed) {
-        return Err("Generation cancelled".to_string());
+        return Err(CommandError::Conflict("Generation cancelled".to_string()));
     }
 
     // Emit finalizing stage
-    let _ = app_handle.emit(
-        "generation_progress",
-        &GenerationProgress {
+    emit_progress(
+        &progress_events,
+        GenerationProgress {
             book_id: book_id.clone(),
             stage: GenerationStage::Finalizing,
             current: total_segments,
@@ -305,47 +1146,1897 @@ async fn run_generation(
         },
     );
 
-    // Concatenate all audio segments
-    let final_audio = if audio_segments.is_empty() {
-        return Err("No audio was generated (all segments were empty)".to_string());
-    } else {
-        tts.concatenate_audio(audio_segments)
-            .map_err(|e| format!("Failed to concatenate audio: {}", e))?
+    if segment_files.is_empty() {
+        return Err(CommandError::InvalidInput(
+            "No audio was generated (all segments were empty)".to_string(),
+        ));
+    }
+
+    let (narration_path, duration) = match splice {
+        Some(splice) => {
+            splice_narration(book_id, config, &splice, markers, &segment_files, db, paths).await?
+        }
+        None => {
+            let status = if partial { NarrationStatus::Partial } else { NarrationStatus::Ready };
+            let narration_path = save_narration(
+                book_id,
+                config,
+                &mut markers,
+                &segment_files,
+                current_time,
+                status,
+                db,
+                paths,
+            )
+            .await?;
+            (narration_path, current_time)
+        }
     };
 
+    if let Err(e) = super::settings::record_narration_rates(
+        db,
+        narrated_chars,
+        synthesis_seconds,
+        synthesized_seconds,
+    ) {
+        log::warn!("Failed to calibrate narration rates: {}", e);
+    }
+
+    if !config.keep_cache {
+        match remove_segment_cache(paths, book_id) {
+            Ok(bytes) => log::info!("book={}: removed {} bytes of segment cache", book_id, bytes),
+            Err(e) => log::warn!("book={}: failed to remove segment cache: {}", book_id, e),
+        }
+    }
+
+    Ok((narration_path, duration))
+}
+
+/// Save a book's narration: concatenate the segment audio, encode it and
+/// store its markers, giving the book `status`. Returns the stored path of
+/// the narration audio.
+#[allow(clippy::too_many_arguments)]
+async fn save_narration(
+    book_id: &BookId,
+    config: &GenerationConfig,
+    markers: &mut Vec<Marker>,
+    segment_files: &[PathBuf],
+    duration: f64,
+    status: NarrationStatus,
+    db: &Database,
+    paths: &AppPaths,
+) -> CommandResult<String> {
     // Create narration directory for this book
-    let book_narration_dir = narration_dir.join(book_id.as_str());
-    std::fs::create_dir_all(&book_narration_dir)
-        .map_err(|e| format!("Failed to create narration directory: {}", e))?;
+    let book_narration_dir = paths.narration_path(book_id.as_str());
+    std::fs::create_dir_all(&book_narration_dir).context("Failed to create narration directory")?;
+
+    // Concatenate the cached segments into the audio file, then encode it
+    // if a compressed codec is configured
+    let wav_path = paths.narration_audio_path(book_id.as_str(), NarrationCodec::Wav);
+    let concatenated = concatenate_segment_files(&wav_path, segment_files, config.bit_depth);
+    let audio_duration = match concatenated {
+        Ok(audio_duration) => audio_duration,
+        Err(e) => {
+            let _ = std::fs::remove_file(&wav_path);
+            return Err(e);
+        }
+    };
+    if let Err(e) = validate_markers(markers, Some(audio_duration), false) {
+        let _ = std::fs::remove_file(&wav_path);
+        return Err(e);
+    }
 
-    // Save the audio file (as WAV for now - could convert to MP3 later)
-    let audio_path = book_narration_dir.join("audio.wav");
-    std::fs::write(&audio_path, &final_audio)
-        .map_err(|e| format!("Failed to save audio file: {}", e))?;
+    let audio_path = paths.narration_audio_path(book_id.as_str(), config.codec);
+    if config.codec != NarrationCodec::Wav {
+        let encoded = ffmpeg::encode_narration(&wav_path, &audio_path, config.codec).await;
+        let _ = std::fs::remove_file(&wav_path);
+        encoded.with_context(|| format!("Failed to encode narration as {}", config.codec.extension()))?;
+    }
+
+    // Remove audio left over from a generation with a different codec
+    for codec in NarrationCodec::ALL.into_iter().filter(|c| *c != config.codec) {
+        let _ = std::fs::remove_file(paths.narration_audio_path(book_id.as_str(), codec));
+    }
 
-    // Save markers
-    let markers_path = book_narration_dir.join("markers.json");
-    let markers_json = serde_json::to_string_pretty(&markers)
-        .map_err(|e| format!("Failed to serialize markers: {}", e))?;
-    std::fs::write(&markers_path, markers_json)
-        .map_err(|e| format!("Failed to save markers: {}", e))?;
+    // Replace the markers with the complete set and set the status in one
+    // transaction, so the status never points at partial timing data
+    let narration_path = paths.to_stored(&audio_path);
+    {
+        let conn = db.connection().lock().unwrap();
+        let meta = config.narration_meta();
+        finalize_narration(
+            &conn,
+            paths,
+            book_id,
+            markers,
+            &narration_path,
+            duration,
+            status,
+            Some(&meta),
+        )?;
+    }
 
-    Ok(audio_path.to_string_lossy().to_string())
+    Ok(narration_path)
 }
 
-/// Cancel ongoing narration generation.
+/// Splice a narrated segment range into the book's existing narration and
+/// store the updated markers. The book keeps its narration metadata and its
+/// status, except that a partial narration becomes ready once every segment
+/// with something to say has a marker.
 ///
-/// Stops the current generation process if one is running.
-/// The book status will be reset to 'none'.
-#[tauri::command]
-pub async fn cancel_generation(book_id: BookId, state: State<'_, AppState>) -> Result<(), String> {
-    // Get the generation handle
-    let handle = {
-        let mut generations = state.active_generations.write().await;
-        generations.remove(book_id.as_str())
-    };
+/// WAV narration is spliced, while compressed narration is rebuilt from the
+/// segment cache and re-encoded. Either way the result is staged and checked
+/// against the markers before it replaces the narration, so a failure leaves
+/// the earlier narration intact. Returns the stored path of the narration
+/// audio and the book's new duration.
+async fn splice_narration(
+    book_id: &BookId,
+    config: &GenerationConfig,
+    splice: &NarrationSplice,
+    narrated: Vec<Marker>,
+    segment_files: &[PathBuf],
+    db: &Database,
+    paths: &AppPaths,
+) -> CommandResult<(String, f64)> {
+    let audio_path = paths
+        .find_narration_audio(book_id.as_str())
+        .ok_or_else(|| CommandError::NotFound("Narration audio not found".to_string()))?;
+    let codec = NarrationCodec::from_path(&audio_path).unwrap_or(NarrationCodec::Wav);
+    let change = narrated.last().map_or(splice.start, |marker| marker.end) - splice.end;
+    let mut markers = splice.markers(narrated);
 
-    match handle {
+    let staged = staged_narration_path(&audio_path);
+    let audio_duration = if codec == NarrationCodec::Wav {
+        // Concatenate the range beside the narration, then cut it in
+        let range_path = audio_path.with_extension("range.wav");
+        let spliced = concatenate_segment_files(&range_path, segment_files, config.bit_depth)
+            .and_then(|_| std::fs::read(&range_path).context("Failed to read narrated range"))
+            .and_then(|audio| {
+                splice_wav(&audio_path, &staged, splice.start, splice.end, &audio)
+                    .context("Failed to splice range into narration")
+            });
+        let _ = std::fs::remove_file(&range_path);
+        spliced?
+    } else {
+        // Rebuilt audio has no room for gaps left by deleted segments, so
+        // the markers are laid back to back
+        let mut time = 0.0;
+        for marker in &mut markers {
+            let length = marker.end - marker.start;
+            marker.start = time;
+            marker.end = time + length;
+            time = marker.end;
+        }
+        let segment_files: Vec<PathBuf> = markers
+            .iter()
+            .map(|m| paths.segment_cache_path(book_id.as_str(), m.segment_id.as_str()))
+            .collect();
+        rebuild_encoded_narration(paths, book_id, &staged, codec, &segment_files, config)
+            .await?
+    };
+
+    // Markers may leave gaps where segments were deleted, but must not run
+    // past the audio
+    if let Err(e) = validate_markers(&mut markers, Some(audio_duration), false) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    // Narrating the last unnarrated range of a partial book completes it
+    let complete = splice
+        .unnarrated
+        .iter()
+        .all(|segment| config.spoken_text(segment).trim().is_empty());
+    let duration = swap_in_narration(&staged, &audio_path, || {
+        let conn = db.connection().lock().unwrap();
+        store_spliced_markers(&conn, book_id, &markers, complete)
+    })?;
+    write_markers_json(paths, book_id, &markers)?;
+
+    log::info!(
+        "book={}: spliced segments {}-{} into the narration ({:+.2}s)",
+        book_id,
+        splice.range.start(),
+        splice.range.end(),
+        change
+    );
+
+    Ok((paths.to_stored(&audio_path), duration))
+}
+
+/// Rebuild compressed narration from the cached audio of its segments,
+/// encoding it in `codec` to `output` and leaving the current audio alone.
+/// Returns the duration of the new audio in seconds.
+async fn rebuild_encoded_narration(
+    paths: &AppPaths,
+    book_id: &BookId,
+    output: &Path,
+    codec: NarrationCodec,
+    segment_files: &[PathBuf],
+    config: &GenerationConfig,
+) -> CommandResult<f64> {
+    if let Some(missing) = segment_files.iter().find(|path| !path.is_file()) {
+        return Err(CommandError::InvalidInput(format!(
+            "Cannot rebuild {} narration without cached audio for every segment ({} is missing)",
+            codec.extension(),
+            missing.display()
+        )));
+    }
+
+    let wav_path = paths.narration_audio_path(book_id.as_str(), NarrationCodec::Wav);
+    let rebuilt = match concatenate_segment_files(&wav_path, segment_files, config.bit_depth) {
+        Ok(duration) => ffmpeg::encode_narration(&wav_path, output, codec)
+            .await
+            .with_context(|| format!("Failed to encode narration as {}", codec.extension()))
+            .map(|()| duration),
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&wav_path);
+    if rebuilt.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    rebuilt
+}
+
+/// Where narration audio is staged before it replaces `audio_path`.
+fn staged_narration_path(audio_path: &Path) -> PathBuf {
+    let extension = audio_path.extension().and_then(|e| e.to_str()).unwrap_or("wav");
+    audio_path.with_extension(format!("new.{}", extension))
+}
+
+/// Move staged narration audio over `audio_path`, then run `commit` to store
+/// what goes with it. The previous audio, if any, is kept as a backup until
+/// `commit` succeeds and put back if it fails, so the database never
+/// describes audio that isn't on disk. The staged file is removed on failure.
+fn swap_in_narration<T>(
+    staged: &Path,
+    audio_path: &Path,
+    commit: impl FnOnce() -> CommandResult<T>,
+) -> CommandResult<T> {
+    let backup = audio_path.with_extension(format!(
+        "{}.bak",
+        audio_path.extension().and_then(|e| e.to_str()).unwrap_or("wav")
+    ));
+    let backed_up = match std::fs::rename(audio_path, &backup) {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => {
+            let _ = std::fs::remove_file(staged);
+            return Err(e).context("Failed to back up narration audio");
+        }
+    };
+    let restore = || {
+        if backed_up {
+            let _ = std::fs::rename(&backup, audio_path);
+        } else {
+            let _ = std::fs::remove_file(audio_path);
+        }
+    };
+
+    if let Err(e) = std::fs::rename(staged, audio_path) {
+        let _ = std::fs::remove_file(staged);
+        restore();
+        return Err(e).context("Failed to move narration audio into place");
+    }
+    match commit() {
+        Ok(value) => {
+            let _ = std::fs::remove_file(&backup);
+            Ok(value)
+        }
+        Err(e) => {
+            restore();
+            Err(e)
+        }
+    }
+}
+
+/// Replace a book's markers after its narration was spliced and set its
+/// duration to the end of the last marker, in one transaction. A partial
+/// narration becomes ready if the splice is `complete`. Returns the new
+/// duration.
+fn store_spliced_markers(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    markers: &[Marker],
+    complete: bool,
+) -> CommandResult<f64> {
+    let duration = markers.last().map_or(0.0, |marker| marker.end);
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    replace_markers(&tx, book_id, markers)?;
+    tx.execute(
+        "UPDATE books SET
+             duration = ?1,
+             narration_status = CASE WHEN ?2 AND narration_status = ?3 THEN ?4 ELSE narration_status END,
+             updated_at = ?5
+         WHERE id = ?6",
+        rusqlite::params![
+            duration,
+            complete,
+            NarrationStatus::Partial.as_str(),
+            NarrationStatus::Ready.as_str(),
+            current_timestamp(),
+            book_id.as_str(),
+        ],
+    )
+    .context("Failed to update book duration")?;
+    tx.commit().context("Failed to commit markers")?;
+    Ok(duration)
+}
+
+/// Synthesize a segment's spoken text and prepare it for concatenation:
+/// converted to the narration format, normalized, trimmed and padded with
+/// the segment gap as the config asks.
+///
+/// `number` is the segment's 1-based position, used in error messages.
+async fn synthesize_segment(
+    config: &GenerationConfig,
+    spoken: &str,
+    voice_sample: &str,
+    number: usize,
+) -> CommandResult<Vec<u8>> {
+    let audio = config
+        .tts
+        .generate_audio(
+            spoken,
+            voice_sample,
+            config.language.as_deref(),
+            config.params.exag,
+            config.params.cfg,
+            config.params.temp,
+        )
+        .await
+        .with_context(|| format!("TTS generation failed for segment {}", number))?;
+
+    // Normalize sample rate and channels so every segment can be concatenated
+    let audio = convert_wav(&audio, config.audio_format)
+        .with_context(|| format!("Failed to convert audio for segment {}", number))?;
+
+    let audio = if config.normalize {
+        normalize_peak(&audio, NORMALIZE_TARGET_PEAK)
+            .with_context(|| format!("Failed to normalize audio for segment {}", number))?
+    } else {
+        audio
+    };
+
+    // Drop the engine's silent padding, then add a controlled gap,
+    // so the marker covers exactly the audio that will be played
+    let audio = match config.trim_threshold {
+        Some(threshold) => trim_silence(&audio, threshold)
+            .with_context(|| format!("Failed to trim audio for segment {}", number))?,
+        None => audio,
+    };
+    if config.segment_gap_ms == 0 {
+        return Ok(audio);
+    }
+    append_silence(&audio, config.segment_gap_ms)
+        .with_context(|| format!("Failed to pad audio for segment {}", number))
+}
+
+/// Concatenate cached segment audio into one WAV file, reading one segment
+/// at a time so memory use doesn't grow with the length of the book.
+///
+/// Each segment is requantized to `bit_depth` on the way; resampling
+/// happened when it was cached, so its duration, and the markers, are
+/// unchanged. Returns the duration of the concatenated audio in seconds.
+fn concatenate_segment_files(
+    output: &Path,
+    segment_files: &[PathBuf],
+    bit_depth: u16,
+) -> CommandResult<f64> {
+    let mut writer = WavWriter::create(output).context("Failed to create audio file")?;
+    for path in segment_files {
+        let audio = std::fs::read(path).context("Failed to read cached segment audio")?;
+        let audio = requantize_wav(&audio, bit_depth).context("Failed to requantize audio")?;
+        writer.append(&audio).context("Failed to concatenate audio")?;
+    }
+    writer.finish().context("Failed to save audio file")
+}
+
+/// Caption image segments whose caption is missing or stale.
+///
+/// Each new caption is stored in `segment_images` together with the prompt
+/// used, so a later generation with the same prompt reuses it. If the vision
+/// service is unreachable or a caption fails, the image falls back to its
+/// alt text. `progress` is called before each image is captioned.
+async fn caption_images(
+    book_id: &BookId,
+    vision: &VisionService,
+    segments: &mut [NarrationSegment],
+    db: &Database,
+    paths: &AppPaths,
+    progress: impl Fn(GenerationProgress),
+    cancel_flag: &AtomicBool,
+) -> CommandResult<()> {
+    let pending: Vec<usize> = segments
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.narrate && s.image.as_ref().is_some_and(NarrationImage::needs_caption))
+        .map(|(i, _)| i)
+        .collect();
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    if !vision.health_check().await {
+        log::warn!(
+            "book={}: vision service at {} is unavailable; narrating {} image(s) from alt text",
+            book_id,
+            vision.endpoint(),
+            pending.len()
+        );
+        return Ok(());
+    }
+
+    let total = pending.len() as u32;
+    for (n, &i) in pending.iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(CommandError::Conflict("Generation cancelled".to_string()));
+        }
+
+        progress(GenerationProgress {
+            book_id: book_id.clone(),
+            stage: GenerationStage::Captioning,
+            current: n as u32 + 1,
+            total,
+            message: format!("Captioning image {} of {}...", n + 1, total),
+        });
+
+        let segment = &mut segments[i];
+        let Some(image) = segment.image.as_mut() else {
+            continue;
+        };
+
+        let image_base64 = match std::fs::read(paths.resolve(&image.source_path)) {
+            Ok(data) => base64::engine::general_purpose::STANDARD.encode(data),
+            Err(e) => {
+                log::warn!(
+                    "book={} segment_id={}: failed to read image {}: {}",
+                    book_id,
+                    segment.id,
+                    image.source_path,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let caption = match vision.caption_image_with_prompt(&image_base64, &image.prompt).await {
+            Ok(caption) => caption,
+            Err(e) => {
+                log::warn!("book={} segment_id={}: failed to caption image: {}", book_id, segment.id, e);
+                continue;
+            }
+        };
+
+        {
+            let conn = db.connection().lock().unwrap();
+            conn.execute(
+                "UPDATE segment_images SET caption = ?, caption_prompt = ? WHERE segment_id = ?",
+                rusqlite::params![&caption, &image.prompt, &segment.id],
+            )
+            .context("Failed to save caption")?;
+        }
+
+        image.caption = Some(caption);
+        image.caption_prompt = Some(image.prompt.clone());
+    }
+
+    Ok(())
+}
+
+/// Result of rebuilding or checking a book's narration markers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkerRebuildReport {
+    /// True if markers were rewritten from cached segment audio.
+    pub rebuilt: bool,
+    /// Number of markers after the operation.
+    pub marker_count: u32,
+    /// Sum of all marker durations (seconds).
+    pub markers_duration: f64,
+    /// Duration of the narration audio file, if it could be measured.
+    pub audio_duration: Option<f64>,
+    /// Audio duration minus marker duration (seconds), if both are known.
+    pub drift: Option<f64>,
+}
+
+/// Largest gap or overlap between markers, or between the last marker and
+/// the end of the audio, accepted when finalizing a narration (seconds).
+const MARKER_TOLERANCE_SECONDS: f64 = 0.01;
+
+/// A marker saved by a generation that hasn't been finalized yet.
+struct PartialMarker {
+    start: f64,
+    end: f64,
+    /// [`partial_marker_key`] of the input the segment was narrated from.
+    key: String,
+}
+
+impl PartialMarker {
+    /// Duration of the cached audio if this marker can be resumed: it was
+    /// narrated from the same input and the cached audio still has its
+    /// duration. The marker itself is re-timed, since earlier segments may
+    /// have changed length.
+    fn resume_duration(&self, key: &str, cache_path: &Path) -> Option<f64> {
+        if self.key != key {
+            return None;
+        }
+        let duration = std::fs::read(cache_path)
+            .ok()
+            .and_then(|data| get_wav_duration(&data).ok())?;
+        ((self.end - self.start - duration).abs() <= MARKER_TOLERANCE_SECONDS).then_some(duration)
+    }
+}
+
+/// Key identifying what a segment's audio was generated from, so a resumed
+/// generation only reuses audio made from the same text, voice, format and
+/// Chatterbox parameters.
+fn partial_marker_key(segment: &NarrationSegment, spoken: &str, config: &GenerationConfig) -> String {
+    let input = format!(
+        "{}\0{}\0{}\0{}\0{}\0{}\0{:?}\0{}\0{}\0{}\0{}",
+        spoken,
+        segment.voice_sample,
+        config.language.as_deref().unwrap_or(""),
+        config.audio_format.sample_rate,
+        config.audio_format.channels,
+        config.normalize,
+        config.trim_threshold,
+        config.segment_gap_ms,
+        config.params.exag,
+        config.params.cfg,
+        config.params.temp
+    );
+    Sha256::digest(input.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Load the markers an interrupted generation of a book saved, by segment id.
+fn query_partial_markers(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) -> CommandResult<HashMap<String, PartialMarker>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT segment_id, start_time, end_time, partial_key FROM markers
+             WHERE book_id = ? AND partial_key IS NOT NULL",
+        )
+        .context("Failed to prepare query")?;
+
+    let markers = stmt
+        .query_map(rusqlite::params![book_id.as_str()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                PartialMarker { start: row.get(1)?, end: row.get(2)?, key: row.get(3)? },
+            ))
+        })
+        .context("Failed to query markers")?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .context("Failed to read marker row")?;
+    Ok(markers)
+}
+
+/// Save a segment's marker as soon as its audio is cached, replacing any
+/// earlier marker for the segment.
+fn save_partial_marker(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    marker: &Marker,
+    key: &str,
+) -> CommandResult<()> {
+    conn.execute(
+        "INSERT INTO markers (id, book_id, segment_id, start_time, end_time, partial_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(segment_id) DO UPDATE SET
+            start_time = excluded.start_time,
+            end_time = excluded.end_time,
+            partial_key = excluded.partial_key",
+        rusqlite::params![
+            format!("marker_{}", uuid::Uuid::new_v4()),
+            book_id.as_str(),
+            marker.segment_id.as_str(),
+            marker.start,
+            marker.end,
+            key,
+        ],
+    )
+    .context("Failed to save marker")?;
+    Ok(())
+}
+
+/// Slack allowed when checking markers against their audio, in seconds, to
+/// absorb rounding from re-timed bundle exports.
+const MARKER_CHECK_TOLERANCE_SECONDS: f64 = 0.05;
+
+/// Check that markers fit their narration audio, whether generated here,
+/// imported from a bundle or attached.
+///
+/// Markers are put in start order, then each must have `start < end`, must
+/// not overlap the marker before it and must end within `audio_duration`
+/// when that is known. Gaps are allowed. With `repair`, overlaps are
+/// trimmed, ends are clamped to the audio and markers left empty are
+/// dropped instead of failing.
+pub(crate) fn validate_markers(
+    markers: &mut Vec<Marker>,
+    audio_duration: Option<f64>,
+    repair: bool,
+) -> CommandResult<()> {
+    markers.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut valid: Vec<Marker> = Vec::with_capacity(markers.len());
+    for mut marker in markers.drain(..) {
+        let describe = |problem: String| {
+            CommandError::InvalidInput(format!(
+                "Invalid marker for segment {}: {}",
+                marker.segment_id, problem
+            ))
+        };
+
+        if !marker.start.is_finite() || !marker.end.is_finite() || marker.start < 0.0 {
+            if repair {
+                continue;
+            }
+            return Err(describe(format!("bad times {} to {}", marker.start, marker.end)));
+        }
+
+        if let Some(previous) = valid.last() {
+            if marker.start < previous.end - MARKER_CHECK_TOLERANCE_SECONDS {
+                if !repair {
+                    return Err(describe(format!(
+                        "starts at {:.3}s, before the previous marker ends at {:.3}s",
+                        marker.start, previous.end
+                    )));
+                }
+                marker.start = previous.end;
+            }
+        }
+
+        if let Some(duration) = audio_duration {
+            if marker.end > duration + MARKER_CHECK_TOLERANCE_SECONDS {
+                if !repair {
+                    return Err(describe(format!(
+                        "ends at {:.3}s, after the audio ends at {:.3}s",
+                        marker.end, duration
+                    )));
+                }
+                marker.end = duration;
+            }
+        }
+
+        if marker.start >= marker.end {
+            if repair {
+                continue;
+            }
+            return Err(describe(format!(
+                "starts at {:.3}s but ends at {:.3}s",
+                marker.start, marker.end
+            )));
+        }
+
+        valid.push(marker);
+    }
+
+    *markers = valid;
+    Ok(())
+}
+
+/// Replace all markers for a book in the database, within the caller's
+/// transaction.
+fn replace_markers(tx: &rusqlite::Transaction, book_id: &BookId, markers: &[Marker]) -> CommandResult<()> {
+    tx.execute(
+        "DELETE FROM markers WHERE book_id = ?",
+        rusqlite::params![book_id.as_str()],
+    )
+    .context("Failed to clear markers")?;
+
+    let mut stmt = tx
+        .prepare("INSERT INTO markers (id, book_id, segment_id, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)")
+        .context("Failed to prepare marker insert")?;
+
+    for marker in markers {
+        stmt.execute(rusqlite::params![
+            format!("marker_{}", uuid::Uuid::new_v4()),
+            book_id.as_str(),
+            marker.segment_id.as_str(),
+            marker.start,
+            marker.end,
+        ])
+        .context("Failed to insert marker")?;
+    }
+    Ok(())
+}
+
+/// Write a book's markers to markers.json next to its narration.
+fn write_markers_json(paths: &AppPaths, book_id: &BookId, markers: &[Marker]) -> CommandResult<()> {
+    let markers_json = serde_json::to_string_pretty(markers)
+        .context("Failed to serialize markers")?;
+    std::fs::write(paths.markers_path(book_id.as_str()), markers_json)
+        .context("Failed to save markers")
+}
+
+/// Replace all markers for a book in the database and in markers.json.
+fn write_markers(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    book_id: &BookId,
+    markers: &[Marker],
+) -> CommandResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    replace_markers(&tx, book_id, markers)?;
+    tx.commit().context("Failed to commit markers")?;
+
+    write_markers_json(paths, book_id, markers)
+}
+
+/// Store the complete marker set of a finished generation and give the book
+/// `status`, in one transaction so neither is saved without the other, then
+/// write markers.json. `meta` is None for narration made outside the app.
+#[allow(clippy::too_many_arguments)]
+fn finalize_narration(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    book_id: &BookId,
+    markers: &[Marker],
+    narration_path: &str,
+    duration: f64,
+    status: NarrationStatus,
+    meta: Option<&NarrationMeta>,
+) -> CommandResult<()> {
+    store_narration(conn, book_id, markers, narration_path, duration, status, meta)?;
+    write_markers_json(paths, book_id, markers)
+}
+
+/// The database half of [`finalize_narration`], for callers that write
+/// markers.json themselves.
+fn store_narration(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    markers: &[Marker],
+    narration_path: &str,
+    duration: f64,
+    status: NarrationStatus,
+    meta: Option<&NarrationMeta>,
+) -> CommandResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+    replace_markers(&tx, book_id, markers)?;
+    tx.execute(
+        "UPDATE books SET narration_status = ?, narration_path = ?, duration = ?, narration_meta = ?, updated_at = ?
+         WHERE id = ?",
+        rusqlite::params![
+            status.as_str(),
+            narration_path,
+            duration,
+            meta.map(NarrationMeta::to_json),
+            current_timestamp(),
+            book_id.as_str(),
+        ],
+    )
+    .context("Failed to update book status")?;
+    tx.commit().context("Failed to commit markers")
+}
+
+/// Delete a book's per-segment audio cache, returning the bytes it held.
+fn remove_segment_cache(paths: &AppPaths, book_id: &BookId) -> CommandResult<u64> {
+    let cache_dir = paths.segment_cache_dir(book_id.as_str());
+    let bytes = dir_size(&cache_dir).context("Failed to measure segment cache")?;
+    match std::fs::remove_dir_all(&cache_dir) {
+        Ok(()) => Ok(bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e).context("Failed to remove segment cache"),
+    }
+}
+
+/// Delete a ready book's per-segment audio cache, keeping the narration
+/// audio and markers. Returns the bytes reclaimed.
+///
+/// Generation does this itself unless `keepNarrationCache` is on. Without
+/// the cache, markers can only be checked rather than rebuilt, and single
+/// segments of compressed narration can't be regenerated.
+#[tauri::command]
+pub async fn compact_book_narration(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<u64> {
+    // Hold the lock so no generation can start on the cache being removed
+    let generations = state.active_generations.read().await;
+    if generations.contains_key(book_id.as_str()) {
+        return Err(CommandError::Conflict(
+            "Cannot compact narration while it is being generated".to_string(),
+        ));
+    }
+
+    let paths = state.paths();
+    {
+        let conn = state.db.connection().lock().unwrap();
+        let book = super::library::query_book(&conn, &book_id)?;
+        if book.narration_status != NarrationStatus::Ready {
+            return Err(CommandError::Conflict("Narration must be ready to compact".to_string()));
+        }
+        if super::reader::query_markers(&conn, &book_id)?.is_empty() {
+            return Err(CommandError::Conflict("Narration has no markers".to_string()));
+        }
+    }
+    if paths.find_narration_audio(book_id.as_str()).is_none() {
+        return Err(CommandError::NotFound("Narration audio not found".to_string()));
+    }
+
+    let bytes = remove_segment_cache(&paths, &book_id)?;
+    log::info!("book={}: compacted narration, reclaiming {} bytes", book_id, bytes);
+    Ok(bytes)
+}
+
+/// Rebuild a book's narration markers.
+///
+/// If every narrated segment has cached audio, markers are recomputed as
+/// contiguous start/end times from the cached durations and written to both
+/// the `markers` table and markers.json. Image segments without cached audio
+/// are treated as skipped. Otherwise the existing markers are
+/// left untouched and compared against the narration audio duration so
+/// drift can be reported.
+#[tauri::command]
+pub async fn rebuild_markers(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<MarkerRebuildReport> {
+    let segments: Vec<(String, String, bool, bool)> = {
+        let conn = state.db.connection().lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.id, s.content, i.segment_id IS NOT NULL, s.narrate
+                 FROM segments s LEFT JOIN segment_images i ON i.segment_id = s.id
+                 WHERE s.book_id = ? ORDER BY s.idx ASC",
+            )
+            .context("Failed to prepare query")?;
+
+        let result: Vec<(String, String, bool, bool)> = stmt
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .context("Failed to query segments")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read segment")?;
+        result
+    };
+
+    if segments.is_empty() {
+        return Err(CommandError::NotFound(
+            "Book not found or has no segments".to_string(),
+        ));
+    }
+
+    let audio_duration = state
+        .paths()
+        .find_narration_audio(book_id.as_str())
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|data| get_wav_duration(&data).ok());
+
+    // Rebuild from the per-segment cache when it covers every narrated segment
+    let mut markers: Vec<Marker> = Vec::with_capacity(segments.len());
+    let mut cache_complete = true;
+    let mut current_time: f64 = 0.0;
+
+    for (segment_id, content, is_image, narrate) in &segments {
+        // Empty and left-out segments are never narrated, so they have no
+        // cache entry
+        if !narrate || (content.trim().is_empty() && !is_image) {
+            continue;
+        }
+
+        let cache_path = state.paths().segment_cache_path(book_id.as_str(), segment_id);
+        let duration = std::fs::read(&cache_path)
+            .ok()
+            .and_then(|data| get_wav_duration(&data).ok());
+
+        match duration {
+            // Images without cached audio were skipped by the image mode
+            None if *is_image => {}
+            Some(duration) => {
+                markers.push(Marker {
+                    segment_id: SegmentId::new(segment_id.clone()),
+                    start: current_time,
+                    end: current_time + duration,
+                });
+                current_time += duration;
+            }
+            None => {
+                cache_complete = false;
+                break;
+            }
+        }
+    }
+
+    if cache_complete {
+        {
+            let conn = state.db.connection().lock().unwrap();
+            write_markers(&conn, &state.paths(), &book_id, &markers)?;
+
+            conn.execute(
+                "UPDATE books SET duration = ?, updated_at = ? WHERE id = ?",
+                rusqlite::params![current_time, current_timestamp(), book_id.as_str()],
+            )
+            .context("Failed to update book duration")?;
+        }
+
+        log::info!("Rebuilt {} markers for book {} from segment cache", markers.len(), book_id);
+
+        return Ok(MarkerRebuildReport {
+            rebuilt: true,
+            marker_count: markers.len() as u32,
+            markers_duration: current_time,
+            audio_duration,
+            drift: audio_duration.map(|d| d - current_time),
+        });
+    }
+
+    // No usable cache: validate the existing markers against the audio instead
+    let existing: Vec<(f64, f64)> = {
+        let conn = state.db.connection().lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT start_time, end_time FROM markers WHERE book_id = ? ORDER BY start_time ASC")
+            .context("Failed to prepare query")?;
+
+        let result: Vec<(f64, f64)> = stmt
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?))
+            })
+            .context("Failed to query markers")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read marker row")?;
+        result
+    };
+
+    let markers_duration: f64 = existing.iter().map(|(start, end)| end - start).sum();
+
+    Ok(MarkerRebuildReport {
+        rebuilt: false,
+        marker_count: existing.len() as u32,
+        markers_duration,
+        audio_duration,
+        drift: audio_duration.map(|d| d - markers_duration),
+    })
+}
+
+/// A stretch of narration not covered by any marker.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkerGap {
+    /// Marker before the gap, or None if the gap starts the narration.
+    pub after: Option<SegmentId>,
+    /// Marker after the gap, or None if the gap runs to the end of the audio.
+    pub before: Option<SegmentId>,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Two markers whose ranges intersect between `start` and `end`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkerOverlap {
+    pub first: SegmentId,
+    pub second: SegmentId,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Gaps and overlaps found in a book's marker timing.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkerReport {
+    pub marker_count: u32,
+    pub gaps: Vec<MarkerGap>,
+    pub overlaps: Vec<MarkerOverlap>,
+    /// Duration of the narration audio, if it could be measured. Without it
+    /// a gap after the last marker can't be detected.
+    pub audio_duration: Option<f64>,
+}
+
+/// How [`repair_markers`] fixes marker timing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MarkerRepairStrategy {
+    /// Extend a marker's end to the next marker's start where there is a gap.
+    CloseGaps,
+    /// Pull a marker's end back to the next marker's start where they overlap,
+    /// dropping a marker that starts with the next one.
+    ClampOverlaps,
+}
+
+/// Find gaps and overlaps in markers sorted by start time. Differences within
+/// [`MARKER_TOLERANCE_SECONDS`] are ignored.
+fn check_marker_timing(markers: &[Marker], audio_duration: Option<f64>) -> MarkerReport {
+    let mut gaps = Vec::new();
+    let mut overlaps = Vec::new();
+    // The marker reaching furthest so far, and how far it reaches
+    let mut covering: Option<&Marker> = None;
+    let mut covered_until = 0.0;
+
+    for marker in markers {
+        if marker.start > covered_until + MARKER_TOLERANCE_SECONDS {
+            gaps.push(MarkerGap {
+                after: covering.map(|m| m.segment_id.clone()),
+                before: Some(marker.segment_id.clone()),
+                start: covered_until,
+                end: marker.start,
+            });
+        } else if let Some(previous) = covering {
+            if marker.start < covered_until - MARKER_TOLERANCE_SECONDS {
+                overlaps.push(MarkerOverlap {
+                    first: previous.segment_id.clone(),
+                    second: marker.segment_id.clone(),
+                    start: marker.start,
+                    end: covered_until.min(marker.end),
+                });
+            }
+        }
+        if covering.is_none() || marker.end > covered_until {
+            covering = Some(marker);
+            covered_until = marker.end;
+        }
+    }
+
+    if let Some(duration) = audio_duration {
+        if duration > covered_until + MARKER_TOLERANCE_SECONDS {
+            gaps.push(MarkerGap {
+                after: covering.map(|m| m.segment_id.clone()),
+                before: None,
+                start: covered_until,
+                end: duration,
+            });
+        }
+    }
+
+    MarkerReport {
+        marker_count: markers.len() as u32,
+        gaps,
+        overlaps,
+        audio_duration,
+    }
+}
+
+/// Snap each marker's end to the next marker's start where `strategy`
+/// applies. Markers must be sorted by start time. A marker this leaves
+/// without any length, as when two start together, is dropped so the next
+/// marker covers its audio. Returns the number of markers changed or dropped.
+fn repair_marker_timing(markers: &mut Vec<Marker>, strategy: MarkerRepairStrategy) -> usize {
+    let mut changed = 0;
+    let mut emptied = HashSet::new();
+    for i in 1..markers.len() {
+        let next_start = markers[i].start;
+        let marker = &mut markers[i - 1];
+        let applies = match strategy {
+            MarkerRepairStrategy::CloseGaps => next_start > marker.end + MARKER_TOLERANCE_SECONDS,
+            MarkerRepairStrategy::ClampOverlaps => {
+                next_start < marker.end - MARKER_TOLERANCE_SECONDS
+            }
+        };
+        if applies {
+            marker.end = next_start;
+            changed += 1;
+            if marker.end <= marker.start + MARKER_TOLERANCE_SECONDS {
+                emptied.insert(i - 1);
+            }
+        }
+    }
+
+    let mut index = 0;
+    markers.retain(|_| {
+        index += 1;
+        !emptied.contains(&(index - 1))
+    });
+    changed
+}
+
+/// Measure a book's narration audio, if it has any.
+fn narration_audio_duration(paths: &AppPaths, book_id: &BookId) -> Option<f64> {
+    paths
+        .find_narration_audio(book_id.as_str())
+        .and_then(|path| probe_audio(&path).ok())
+        .map(|info| info.duration)
+}
+
+/// Report gaps and overlaps in a book's markers, working only on their
+/// timing, so broken narration can be diagnosed without re-listening.
+#[tauri::command]
+pub async fn check_markers(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<MarkerReport> {
+    let markers = {
+        let conn = state.db.connection().lock().unwrap();
+        super::library::query_book(&conn, &book_id)?;
+        super::reader::query_markers(&conn, &book_id)?
+    };
+
+    let audio_duration = narration_audio_duration(&state.paths(), &book_id);
+    Ok(check_marker_timing(&markers, audio_duration))
+}
+
+/// Fix gaps or overlaps between a book's markers by snapping marker ends to
+/// the next marker's start, then report what remains. The audio is not
+/// touched. Gaps before the first marker and after the last are left alone.
+#[tauri::command]
+pub async fn repair_markers(
+    book_id: BookId,
+    strategy: MarkerRepairStrategy,
+    state: State<'_, AppState>,
+) -> CommandResult<MarkerReport> {
+    if state.active_generations.read().await.contains_key(book_id.as_str()) {
+        return Err(CommandError::Conflict(
+            "Cannot repair markers while narration is being generated".to_string(),
+        ));
+    }
+
+    let paths = state.paths();
+    let markers = {
+        let conn = state.db.connection().lock().unwrap();
+        super::library::query_book(&conn, &book_id)?;
+        let mut markers = super::reader::query_markers(&conn, &book_id)?;
+        if repair_marker_timing(&mut markers, strategy) > 0 {
+            write_markers(&conn, &paths, &book_id, &markers)?;
+        }
+        markers
+    };
+
+    let audio_duration = narration_audio_duration(&paths, &book_id);
+    Ok(check_marker_timing(&markers, audio_duration))
+}
+
+/// Check that every marker points at a different segment of the book.
+fn check_marker_segments(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+    markers: &[Marker],
+) -> CommandResult<()> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM segments WHERE book_id = ?")
+        .context("Failed to prepare query")?;
+    let segment_ids: HashSet<String> = stmt
+        .query_map(rusqlite::params![book_id.as_str()], |row| row.get(0))
+        .context("Failed to query segments")?
+        .collect::<Result<_, _>>()
+        .context("Failed to read segment")?;
+
+    let mut seen = HashSet::new();
+    for marker in markers {
+        if !segment_ids.contains(marker.segment_id.as_str()) {
+            return Err(CommandError::InvalidInput(format!(
+                "Marker refers to segment {}, which is not in this book",
+                marker.segment_id.as_str()
+            )));
+        }
+        if !seen.insert(marker.segment_id.as_str()) {
+            return Err(CommandError::InvalidInput(format!(
+                "Segment {} has more than one marker",
+                marker.segment_id.as_str()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Use narration made outside the app for a book.
+///
+/// The audio must be WAV, MP3 or Opus, named by its extension, and the
+/// markers file either a bundle's markers.json or the array written next to
+/// a narration. Markers are checked as on bundle import, and must each refer
+/// to a different segment of the book. The audio is copied into the book's
+/// narration directory, replacing any earlier narration, and the book is
+/// marked ready with no narration metadata. The copy is staged beside the
+/// narration and moved into place before the database is updated, with the
+/// earlier narration kept as a backup and put back if the update fails.
+#[tauri::command]
+pub async fn attach_narration(
+    book_id: BookId,
+    audio_path: String,
+    markers_json_path: String,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    if state.active_generations.read().await.contains_key(book_id.as_str()) {
+        return Err(CommandError::Conflict(
+            "Generation already in progress for this book".to_string(),
+        ));
+    }
+
+    let source = PathBuf::from(&audio_path);
+    let codec = NarrationCodec::from_path(&source).ok_or_else(|| {
+        CommandError::InvalidInput("Narration audio must be a WAV, MP3 or Opus file".to_string())
+    })?;
+    let duration = probe_audio(&source)
+        .map_err(|e| CommandError::InvalidInput(format!("Invalid narration audio: {}", e)))?
+        .duration;
+
+    let markers_json =
+        std::fs::read_to_string(&markers_json_path).context("Failed to read markers file")?;
+    let markers = super::bundle::parse_external_markers(&markers_json, duration)?;
+    if markers.is_empty() {
+        return Err(CommandError::InvalidInput("Markers file has no markers".to_string()));
+    }
+
+    let paths = state.paths();
+    {
+        let conn = state.db.connection().lock().unwrap();
+        super::library::query_book(&conn, &book_id)?;
+        check_marker_segments(&conn, &book_id, &markers)?;
+    }
+
+    let narration_dir = paths.narration_path(book_id.as_str());
+    let narration_existed = narration_dir.exists();
+    std::fs::create_dir_all(&narration_dir).context("Failed to create narration directory")?;
+
+    let audio = paths.narration_audio_path(book_id.as_str(), codec);
+    let staged = staged_narration_path(&audio);
+    let attached = match std::fs::copy(&source, &staged) {
+        Ok(_) => swap_in_narration(&staged, &audio, || {
+            let conn = state.db.connection().lock().unwrap();
+            let narration_path = paths.to_stored(&audio);
+            let ready = NarrationStatus::Ready;
+            store_narration(&conn, &book_id, &markers, &narration_path, duration, ready, None)
+        }),
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged);
+            Err(e).context("Failed to copy narration audio")
+        }
+    };
+    if let Err(e) = attached {
+        if !narration_existed {
+            let _ = std::fs::remove_dir_all(&narration_dir);
+        }
+        return Err(e);
+    }
+    write_markers_json(&paths, &book_id, &markers)?;
+
+    // Remove audio left over from a narration with a different codec
+    for other in NarrationCodec::ALL.into_iter().filter(|c| *c != codec) {
+        let _ = std::fs::remove_file(paths.narration_audio_path(book_id.as_str(), other));
+    }
+
+    state.broadcast(super::sync::SyncUpdate::LibraryChanged);
+    Ok(())
+}
+
+/// Markers after a segment's audio is replaced by `duration` seconds of new
+/// audio: the segment keeps its start and every later marker moves by the
+/// change in length. None if the segment has no marker.
+fn splice_markers(
+    markers: &[Marker],
+    segment_id: &SegmentId,
+    duration: f64,
+) -> Option<Vec<Marker>> {
+    let old = markers.iter().find(|m| &m.segment_id == segment_id)?;
+    let delta = duration - (old.end - old.start);
+    let old_end = old.end;
+
+    Some(
+        markers
+            .iter()
+            .map(|marker| {
+                if &marker.segment_id == segment_id {
+                    Marker { end: marker.start + duration, ..marker.clone() }
+                } else if marker.start >= old_end - MARKER_TOLERANCE_SECONDS {
+                    Marker {
+                        start: marker.start + delta,
+                        end: marker.end + delta,
+                        ..marker.clone()
+                    }
+                } else {
+                    marker.clone()
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Re-narrate one segment and splice it into the book's narration.
+///
+/// For fixing a single mispronounced or glitched paragraph without
+/// regenerating the book. The segment is synthesized with the current
+/// settings and pronunciations, in its override voice or else the default
+/// voice, and its audio replaces the range its marker covered; later markers
+/// shift by the change in length. WAV narration is edited in place, while
+/// compressed narration is rebuilt from the segment cache and re-encoded.
+///
+/// The book's narration must be ready. The regeneration is listed as an
+/// active generation while it runs, so a full generation of the book can't
+/// start alongside it. Returns the updated markers.
+#[tauri::command]
+pub async fn regenerate_segment(
+    book_id: BookId,
+    segment_id: SegmentId,
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<Marker>> {
+    let settings = super::settings::load_settings(&state.db)?;
+
+    let (segment, pronunciations, language) = {
+        let conn = state.db.connection().lock().unwrap();
+
+        let (status, language): (String, Option<String>) = conn
+            .query_row(
+                "SELECT narration_status, language FROM books WHERE id = ?",
+                rusqlite::params![book_id.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => {
+                    CommandError::NotFound("Book not found".to_string())
+                }
+                _ => CommandError::Database(format!("Database error: {}", e)),
+            })?;
+        if status != NarrationStatus::Ready.as_str() {
+            return Err(CommandError::Conflict(
+                "Narration must be ready to regenerate a segment".to_string(),
+            ));
+        }
+
+        let overrides = query_voice_overrides(&conn, &book_id)?;
+        let default_sample = query_default_voice_sample(&conn, &settings)?;
+        let segment = query_narration_segments(&conn, &book_id, &settings, |index| {
+            override_sample(&overrides, index).unwrap_or_else(|| default_sample.clone())
+        })?
+        .into_iter()
+        .find(|segment| segment.id == segment_id.as_str())
+        .ok_or_else(|| CommandError::NotFound("Segment not found".to_string()))?;
+
+        let markers = super::reader::query_markers(&conn, &book_id)?;
+        if !markers.iter().any(|m| m.segment_id == segment_id) {
+            return Err(CommandError::InvalidInput(
+                "Segment has no narration to replace".to_string(),
+            ));
+        }
+
+        let pronunciations = PronunciationRules::compile(&query_pronunciations(&conn)?)
+            .context("Invalid pronunciation rule")?;
+
+        (segment, pronunciations, language)
+    };
+
+    let config = GenerationConfig {
+        pronunciations,
+        language,
+        ..GenerationConfig::from_settings(&settings)
+    };
+
+    // Register and spawn under one write lock, so a full generation can't
+    // start between the check and the task reading the markers it splices
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+    {
+        let mut generations = state.active_generations.write().await;
+        if generations.contains_key(book_id.as_str()) {
+            return Err(CommandError::Conflict(
+                "Generation already in progress for this book".to_string(),
+            ));
+        }
+
+        let book_id = book_id.clone();
+        let db = state.db.clone();
+        let paths = state.paths();
+        let active_generations = state.active_generations.clone();
+        let cancel = cancel_flag.clone();
+        let task_handle = tokio::spawn(async move {
+            let result = run_segment_regeneration(
+                &book_id, &config, segment, &db, &paths, &app_handle, &cancel,
+            )
+            .await;
+
+            active_generations.write().await.remove(book_id.as_str());
+            let _ = result_tx.send(result);
+        });
+
+        generations.insert(
+            book_id.as_str().to_string(),
+            GenerationHandle {
+                cancel_flag,
+                task_handle,
+            },
+        );
+    }
+
+    result_rx
+        .await
+        .map_err(|_| CommandError::Internal("Segment regeneration was interrupted".to_string()))?
+}
+
+/// Synthesize a segment and splice it into the narration, for
+/// `regenerate_segment`. Returns the updated markers.
+///
+/// The spliced audio is staged and checked against the new markers before
+/// it replaces the narration, and the old audio is put back if the markers
+/// can't be saved.
+async fn run_segment_regeneration(
+    book_id: &BookId,
+    config: &GenerationConfig,
+    segment: NarrationSegment,
+    db: &Database,
+    paths: &AppPaths,
+    app_handle: &AppHandle,
+    cancel_flag: &AtomicBool,
+) -> CommandResult<Vec<Marker>> {
+    if !config.tts.is_available().await {
+        return Err(CommandError::ServiceUnavailable(format!(
+            "Chatterbox TTS server is not available. Please ensure it's running at {}",
+            config.tts.base_url()
+        )));
+    }
+
+    let spoken = config.spoken_text(&segment);
+    if spoken.trim().is_empty() {
+        return Err(CommandError::InvalidInput(
+            "Segment has no text to narrate".to_string(),
+        ));
+    }
+
+    let segment_id = SegmentId::new(segment.id.clone());
+    let no_narration = || CommandError::InvalidInput("Segment has no narration to replace".to_string());
+    let markers = {
+        let conn = db.connection().lock().unwrap();
+        super::reader::query_markers(&conn, book_id)?
+    };
+    let position = markers
+        .iter()
+        .position(|m| m.segment_id == segment_id)
+        .ok_or_else(no_narration)?;
+    let old = markers[position].clone();
+
+    let audio = synthesize_segment(config, &spoken, &segment.voice_sample, position + 1).await?;
+    let duration = get_wav_duration(&audio).context("Failed to get audio duration")?;
+    if duration <= 0.0 {
+        return Err(CommandError::InvalidInput(
+            "Segment's audio was silent throughout and trimmed to nothing".to_string(),
+        ));
+    }
+
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err(CommandError::Conflict("Generation cancelled".to_string()));
+    }
+
+    let mut new_markers =
+        splice_markers(&markers, &segment_id, duration).ok_or_else(no_narration)?;
+
+    let cache_path = paths.segment_cache_path(book_id.as_str(), &segment.id);
+    if let Some(cache_dir) = cache_path.parent() {
+        std::fs::create_dir_all(cache_dir).context("Failed to create segment cache directory")?;
+    }
+    std::fs::write(&cache_path, &audio).context("Failed to cache segment audio")?;
+
+    let audio_path = paths
+        .find_narration_audio(book_id.as_str())
+        .ok_or_else(|| CommandError::NotFound("Narration audio not found".to_string()))?;
+    let codec = NarrationCodec::from_path(&audio_path).unwrap_or(NarrationCodec::Wav);
+
+    let staged = staged_narration_path(&audio_path);
+    let audio_duration = if codec == NarrationCodec::Wav {
+        splice_wav(&audio_path, &staged, old.start, old.end, &audio)
+            .context("Failed to splice segment into narration")?
+    } else {
+        // Compressed audio can't be cut at a byte offset, so rebuild it
+        // from the cached audio of every segment
+        let segment_files: Vec<PathBuf> = new_markers
+            .iter()
+            .map(|m| paths.segment_cache_path(book_id.as_str(), m.segment_id.as_str()))
+            .collect();
+        rebuild_encoded_narration(paths, book_id, &staged, codec, &segment_files, config)
+            .await?
+    };
+    if let Err(e) = validate_markers(&mut new_markers, Some(audio_duration), false) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    swap_in_narration(&staged, &audio_path, || {
+        let conn = db.connection().lock().unwrap();
+        store_spliced_markers(&conn, book_id, &new_markers, false)
+    })?;
+    write_markers_json(paths, book_id, &new_markers)?;
+
+    let marker = &new_markers[position];
+    let _ = app_handle.emit(
+        "segment_narrated",
+        &SegmentNarrated {
+            book_id: book_id.clone(),
+            segment_id: marker.segment_id.clone(),
+            start: marker.start,
+            end: marker.end,
+            audio_path: Some(cache_path.to_string_lossy().to_string()),
+        },
+    );
+
+    log::info!(
+        "book={} segment_id={}: regenerated segment ({:+.2}s)",
+        book_id,
+        segment_id,
+        duration - (old.end - old.start)
+    );
+
+    Ok(new_markers)
+}
+
+/// Find an existing override range that intersects `start..=end`.
+fn find_overlapping_range(existing: &[(u32, u32)], start: u32, end: u32) -> Option<(u32, u32)> {
+    existing
+        .iter()
+        .copied()
+        .find(|&(other_start, other_end)| other_start <= end && start <= other_end)
+}
+
+/// Get all per-segment voice overrides for a book, ordered by start index.
+#[tauri::command]
+pub async fn get_segment_voices(
+    book_id: BookId,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<SegmentVoiceOverride>> {
+    let conn = state.db.connection().lock().unwrap();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT start_index, end_index, voice_id FROM segment_voices
+             WHERE book_id = ? ORDER BY start_index ASC",
+        )
+        .context("Failed to prepare query")?;
+
+    let overrides = stmt
+        .query_map(rusqlite::params![book_id.as_str()], |row| {
+            Ok(SegmentVoiceOverride {
+                book_id: book_id.clone(),
+                start_index: row.get(0)?,
+                end_index: row.get(1)?,
+                voice_id: VoiceId::new(row.get::<_, String>(2)?),
+            })
+        })
+        .context("Failed to query voice overrides")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read voice override")?;
+
+    Ok(overrides)
+}
+
+/// Narrate an inclusive range of segments with a specific voice.
+///
+/// Segments outside every override range use the voice passed to
+/// `generate_narration`. Ranges may not overlap existing overrides.
+#[tauri::command]
+pub async fn set_segment_voice(
+    book_id: BookId,
+    start_index: u32,
+    end_index: u32,
+    voice_id: VoiceId,
+    state: State<'_, AppState>,
+) -> CommandResult<SegmentVoiceOverride> {
+    if start_index > end_index {
+        return Err(CommandError::InvalidInput(format!(
+            "Invalid segment range: start {} is after end {}",
+            start_index, end_index
+        )));
+    }
+
+    let conn = state.db.connection().lock().unwrap();
+
+    // Verify the voice exists
+    let voice_exists: bool = conn
+        .query_row(
+            "SELECT 1 FROM voices WHERE id = ?",
+            rusqlite::params![voice_id.as_str()],
+            |_| Ok(true),
+        )
+        .unwrap_or(false);
+
+    if !voice_exists {
+        return Err(CommandError::NotFound("Voice not found".to_string()));
+    }
+
+    // Reject ranges that overlap an existing override
+    let existing: Vec<(u32, u32)> = {
+        let mut stmt = conn
+            .prepare("SELECT start_index, end_index FROM segment_voices WHERE book_id = ?")
+            .context("Failed to prepare query")?;
+
+        let result = stmt
+            .query_map(rusqlite::params![book_id.as_str()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .context("Failed to query voice overrides")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read voice override")?;
+        result
+    };
+
+    if let Some((other_start, other_end)) = find_overlapping_range(&existing, start_index, end_index) {
+        return Err(CommandError::Conflict(format!(
+            "Segments {}-{} overlap an existing voice override ({}-{})",
+            start_index, end_index, other_start, other_end
+        )));
+    }
+
+    conn.execute(
+        "INSERT INTO segment_voices (book_id, start_index, end_index, voice_id) VALUES (?, ?, ?, ?)",
+        rusqlite::params![book_id.as_str(), start_index, end_index, voice_id.as_str()],
+    )
+    .context("Failed to save voice override")?;
+
+    Ok(SegmentVoiceOverride {
+        book_id,
+        start_index,
+        end_index,
+        voice_id,
+    })
+}
+
+/// Remove per-segment voice overrides for a book.
+///
+/// Clears the override starting at `start_index`, or every override for the
+/// book when no index is given.
+#[tauri::command]
+pub async fn clear_segment_voice(
+    book_id: BookId,
+    start_index: Option<u32>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let conn = state.db.connection().lock().unwrap();
+
+    match start_index {
+        Some(start_index) => conn.execute(
+            "DELETE FROM segment_voices WHERE book_id = ? AND start_index = ?",
+            rusqlite::params![book_id.as_str(), start_index],
+        ),
+        None => conn.execute(
+            "DELETE FROM segment_voices WHERE book_id = ?",
+            rusqlite::params![book_id.as_str()],
+        ),
+    }
+    .context("Failed to clear voice override")?;
+
+    Ok(())
+}
+
+/// Load a book's per-segment voice overrides as inclusive index ranges with
+/// the sample path of their voice.
+fn query_voice_overrides(
+    conn: &rusqlite::Connection,
+    book_id: &BookId,
+) -> CommandResult<Vec<(u32, u32, String)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.start_index, o.end_index, v.sample_path
+             FROM segment_voices o JOIN voices v ON v.id = o.voice_id
+             WHERE o.book_id = ?",
+        )
+        .context("Failed to prepare query")?;
+
+    let overrides = stmt
+        .query_map(rusqlite::params![book_id.as_str()], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .context("Failed to query voice overrides")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read voice override")?;
+    Ok(overrides)
+}
+
+/// Sample path of the override covering a segment index, if any.
+fn override_sample(overrides: &[(u32, u32, String)], index: u32) -> Option<String> {
+    overrides
+        .iter()
+        .find(|(start, end, _)| (*start..=*end).contains(&index))
+        .map(|(_, _, sample)| sample.clone())
+}
+
+/// Sample path of the default voice: the `defaultVoice` setting, or else the
+/// voice marked as default.
+fn query_default_voice_sample(
+    conn: &rusqlite::Connection,
+    settings: &super::settings::Settings,
+) -> CommandResult<String> {
+    if let Some(voice_id) = &settings.default_voice {
+        return query_voice_sample(conn, voice_id);
+    }
+
+    conn.query_row(
+        "SELECT sample_path FROM voices WHERE is_default = 1 ORDER BY name LIMIT 1",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound("No default voice is set".to_string())
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Look up the sample file a voice clones from.
+fn query_voice_sample(conn: &rusqlite::Connection, voice_id: &VoiceId) -> CommandResult<String> {
+    conn.query_row(
+        "SELECT sample_path FROM voices WHERE id = ?",
+        rusqlite::params![voice_id.as_str()],
+        |row| row.get::<_, String>(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            CommandError::NotFound("Voice not found".to_string())
+        }
+        _ => CommandError::Database(format!("Database error: {}", e)),
+    })
+}
+
+/// Phrase spoken by `preview_voice` when no text is given.
+const PREVIEW_PANGRAM: &str = "The quick brown fox jumps over the lazy dog.";
+
+/// Longest text `preview_voice` will synthesize, in characters.
+const MAX_PREVIEW_CHARS: usize = 200;
+
+/// Text to preview a voice with: the given text, trimmed and cut back to a
+/// word boundary within the limit, or the pangram if there's none.
+fn preview_text(text: Option<&str>) -> String {
+    let text = text.map(str::trim).filter(|t| !t.is_empty());
+    let Some(text) = text else {
+        return PREVIEW_PANGRAM.to_string();
+    };
+
+    match text.char_indices().nth(MAX_PREVIEW_CHARS) {
+        None => text.to_string(),
+        Some((cut, _)) => {
+            let head = &text[..cut];
+            let end = head.rfind(char::is_whitespace).unwrap_or(cut);
+            head[..end].trim_end().to_string()
+        }
+    }
+}
+
+/// Synthesize a short phrase with a voice so it can be heard before
+/// narrating a whole book.
+///
+/// Speaks `text` (capped to keep previews quick) or a pangram, using the
+/// configured Chatterbox server, and returns the WAV bytes.
+#[tauri::command]
+pub async fn preview_voice(
+    id: VoiceId,
+    text: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<u8>> {
+    let voice_sample = {
+        let conn = state.db.connection().lock().unwrap();
+        query_voice_sample(&conn, &id)?
+    };
+    let settings = super::settings::load_settings(&state.db)?;
+    let params = settings.chatterbox_params();
+    let tts = TtsService::with_url(settings.chatterbox_url);
+
+    if !tts.is_available().await {
+        return Err(CommandError::ServiceUnavailable(format!(
+            "Chatterbox TTS server is not available. Please ensure it's running at {}",
+            tts.base_url()
+        )));
+    }
+
+    let text = preview_text(text.as_deref());
+    tts.generate_audio(&text, &voice_sample, None, params.exag, params.cfg, params.temp)
+        .await
+        .context("Voice preview failed")
+}
+
+/// Check whether the TTS and vision services are reachable.
+///
+/// Both configured services are probed concurrently so the UI can warn
+/// before a generation is started.
+#[tauri::command]
+pub async fn get_service_status(state: State<'_, AppState>) -> CommandResult<ServiceStatus> {
+    let settings = super::settings::load_settings(&state.db)?;
+
+    let tts = TtsService::with_url(settings.chatterbox_url.clone());
+    let vision = VisionService::new(settings.vision_url.clone());
+
+    let probe_tts = async {
+        let started = Instant::now();
+        let available = tts.is_available().await;
+        (available, started.elapsed().as_millis() as u64)
+    };
+    let probe_vision = async {
+        let started = Instant::now();
+        let available = vision.health_check().await;
+        (available, started.elapsed().as_millis() as u64)
+    };
+
+    let ((tts_available, tts_latency), (vision_available, vision_latency)) =
+        tokio::join!(probe_tts, probe_vision);
+
+    Ok(ServiceStatus {
+        tts: ServiceProbe {
+            url: settings.chatterbox_url,
+            available: tts_available,
+            latency_ms: tts_latency,
+        },
+        vision: ServiceProbe {
+            url: settings.vision_url,
+            available: vision_available,
+            latency_ms: vision_latency,
+        },
+    })
+}
+
+/// Set or clear the captioning prompt used for a book's images.
+///
+/// When set, it takes precedence over the `captionPrompt` settings. Images
+/// are re-captioned on the next generation if their prompt changed.
+#[tauri::command]
+pub async fn set_book_caption_prompt(
+    book_id: BookId,
+    prompt: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let prompt = prompt
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+
+    let conn = state.db.connection().lock().unwrap();
+    let updated = conn
+        .execute(
+            "UPDATE books SET caption_prompt = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![prompt, current_timestamp(), book_id.as_str()],
+        )
+        .context("Failed to update caption prompt")?;
+
+    if updated == 0 {
+        return Err(CommandError::NotFound("Book not found".to_string()));
+    }
+
+    Ok(())
+}
+
+/// List the narration generations currently running.
+///
+/// Each entry carries the book's latest progress update, so a freshly loaded
+/// UI can show progress without waiting for the next `generation_progress`
+/// event. Sorted by book ID.
+#[tauri::command]
+pub async fn list_active_generations(
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<GenerationStatus>> {
+    let book_ids: Vec<String> = state.active_generations.read().await.keys().cloned().collect();
+    let latest = state.generation_progress.lock()?;
+
+    let mut statuses: Vec<GenerationStatus> = book_ids
+        .into_iter()
+        .map(|book_id| match latest.get(&book_id) {
+            Some(progress) => GenerationStatus {
+                book_id: progress.book_id.clone(),
+                stage: Some(progress.stage),
+                current: progress.current,
+                total: progress.total,
+            },
+            None => GenerationStatus {
+                book_id: BookId::new(book_id),
+                stage: None,
+                current: 0,
+                total: 0,
+            },
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.book_id.as_str().cmp(b.book_id.as_str()));
+
+    Ok(statuses)
+}
+
+/// Cancel ongoing narration generation.
+///
+/// Stops the current generation process if one is running.
+/// The book status will be reset to 'none'.
+#[tauri::command]
+pub async fn cancel_generation(book_id: BookId, state: State<'_, AppState>) -> CommandResult<()> {
+    // Get the generation handle
+    let handle = {
+        let mut generations = state.active_generations.write().await;
+        generations.remove(book_id.as_str())
+    };
+
+    match handle {
         Some(gen_handle) => {
             // Signal cancellation
             gen_handle.cancel_flag.store(true, Ordering::Relaxed);
@@ -357,17 +3048,20 @@ pub async fn cancel_generation(book_id: BookId, state: State<'_, AppState>) -> R
             )
             .await;
 
-            // Update book status to 'none'
+            // Update book status to 'none'. A segment regeneration runs on a
+            // ready book, whose narration is left as it was
             let conn = state.db.connection().lock().unwrap();
-            conn.execute(
-                "UPDATE books SET narration_status = 'none', updated_at = ? WHERE id = ?",
-                rusqlite::params![current_timestamp(), book_id.as_str()],
-            )
-            .map_err(|e| format!("Failed to update book status: {}", e))?;
+            let reset = conn
+                .execute(
+                    "UPDATE books SET narration_status = 'none', updated_at = ?
+                     WHERE id = ? AND narration_status = 'generating'",
+                    rusqlite::params![current_timestamp(), book_id.as_str()],
+                )
+                .context("Failed to update book status")?;
 
             // Clean up partial files
-            let narration_dir = state.paths.narration.join(book_id.as_str());
-            if narration_dir.exists() {
+            let narration_dir = state.paths().narration.join(book_id.as_str());
+            if reset > 0 && narration_dir.exists() {
                 let _ = std::fs::remove_dir_all(&narration_dir);
             }
 
@@ -384,12 +3078,15 @@ pub async fn cancel_generation(book_id: BookId, state: State<'_, AppState>) -> R
 ///
 /// Returns the list of voice profiles that can be used for narration generation.
 #[tauri::command]
-pub async fn get_voices(state: State<'_, AppState>) -> Result<Vec<Voice>, String> {
+pub async fn get_voices(state: State<'_, AppState>) -> CommandResult<Vec<Voice>> {
     let conn = state.db.connection().lock().unwrap();
 
     let mut stmt = conn
-        .prepare("SELECT id, name, sample_path, is_default FROM voices ORDER BY is_default DESC, name ASC")
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .prepare(
+            "SELECT id, name, sample_path, is_default, original_filename FROM voices
+             ORDER BY is_default DESC, name ASC",
+        )
+        .context("Failed to prepare query")?;
 
     let voices = stmt
         .query_map([], |row| {
@@ -397,30 +3094,42 @@ pub async fn get_voices(state: State<'_, AppState>) -> Result<Vec<Voice>, String
                 id: VoiceId::new(row.get::<_, String>(0)?),
                 name: row.get(1)?,
                 sample_path: row.get(2)?,
+                original_filename: row.get(4)?,
                 is_default: row.get::<_, i32>(3)? != 0,
+                sample_info: None,
             })
         })
-        .map_err(|e| format!("Failed to query voices: {}", e))?
+        .context("Failed to query voices")?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to read voice row: {}", e))?;
+        .context("Failed to read voice row")?;
 
     Ok(voices)
 }
 
+/// Shortest voice sample, in seconds, that clones well.
+const MIN_VOICE_SAMPLE_SECONDS: f64 = 3.0;
+
 /// Create a new voice profile from a sample.
 ///
-/// The sample should be a WAV or MP3 file containing a clear voice recording.
-/// Chatterbox will use this sample for voice cloning.
+/// The sample should be a WAV, MP3, Ogg Vorbis or FLAC file containing a
+/// clear voice recording. Chatterbox will use this sample for voice cloning.
+/// The sample's header is probed up front so a corrupt or too-short recording
+/// is rejected here, and its format is returned for the UI to flag
+/// low-quality samples. Anything but 16-bit PCM WAV is converted to it so
+/// Chatterbox can always read it; the original file name is kept for display.
 #[tauri::command]
 pub async fn create_voice(
     name: String,
     sample_path: String,
     state: State<'_, AppState>,
-) -> Result<Voice, String> {
+) -> CommandResult<Voice> {
     // Validate the sample file exists
     let source_path = Path::new(&sample_path);
     if !source_path.exists() {
-        return Err(format!("Sample file not found: {}", sample_path));
+        return Err(CommandError::NotFound(format!(
+            "Sample file not found: {}",
+            sample_path
+        )));
     }
 
     // Get the file extension
@@ -432,19 +3141,34 @@ pub async fn create_voice(
 
     // Validate it's an audio file
     if !["wav", "mp3", "ogg", "flac"].contains(&extension.as_str()) {
-        return Err(format!(
+        return Err(CommandError::InvalidInput(format!(
             "Invalid audio format: {}. Supported formats: wav, mp3, ogg, flac",
             extension
-        ));
+        )));
+    }
+
+    // Confirm the sample actually decodes, and is long enough to clone from
+    let sample_info = probe_audio(source_path)
+        .map_err(|e| CommandError::InvalidInput(format!("Unreadable voice sample: {}", e)))?;
+    if sample_info.duration < MIN_VOICE_SAMPLE_SECONDS {
+        return Err(CommandError::InvalidInput(format!(
+            "Voice sample is too short ({:.1}s); use at least {}s of speech",
+            sample_info.duration, MIN_VOICE_SAMPLE_SECONDS
+        )));
     }
 
     // Generate a new voice ID
     let voice_id = VoiceId::new(format!("voice_{}", uuid::Uuid::new_v4()));
 
-    // Copy the sample to the voices directory
-    let dest_path = state.paths.voice_sample_path(voice_id.as_str(), &extension);
-    std::fs::copy(&source_path, &dest_path)
-        .map_err(|e| format!("Failed to copy sample file: {}", e))?;
+    // Store the sample in the voices directory as 16-bit PCM WAV
+    let dest_path = state.paths().voice_sample_path(voice_id.as_str(), "wav");
+    let wav = voice_sample_wav(source_path).map_err(|e| {
+        CommandError::InvalidInput(format!("Could not convert voice sample to WAV: {}", e))
+    })?;
+    std::fs::write(&dest_path, wav).context("Failed to write sample file")?;
+    let original_filename = source_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
 
     // Check if this is the first voice (make it default)
     let is_first_voice = {
@@ -459,71 +3183,244 @@ pub async fn create_voice(
     {
         let conn = state.db.connection().lock().unwrap();
         conn.execute(
-            "INSERT INTO voices (id, name, engine, sample_path, is_default) VALUES (?, ?, 'chatterbox', ?, ?)",
+            "INSERT INTO voices (id, name, engine, sample_path, is_default, original_filename)
+             VALUES (?, ?, 'chatterbox', ?, ?, ?)",
             rusqlite::params![
                 voice_id.as_str(),
                 &name,
                 dest_path.to_string_lossy().to_string(),
-                if is_first_voice { 1 } else { 0 }
+                if is_first_voice { 1 } else { 0 },
+                &original_filename
             ],
         )
-        .map_err(|e| format!("Failed to insert voice: {}", e))?;
+        .context("Failed to insert voice")?;
     }
 
     Ok(Voice {
         id: voice_id,
         name,
         sample_path: dest_path.to_string_lossy().to_string(),
+        original_filename,
         is_default: is_first_voice,
+        sample_info: Some(sample_info),
     })
 }
 
-/// Delete a voice profile.
+/// Bundled voice sample, relative to the app's resource directory.
+pub(crate) const DEFAULT_VOICE_RESOURCE: &str = "resources/default-voice.wav";
+
+/// Name of the voice created from the bundled sample.
+const DEFAULT_VOICE_NAME: &str = "Default";
+
+/// Create the default voice from `sample`, once per install.
+///
+/// Only the first successful run does anything: it creates the voice, or
+/// just records that it ran if the library already has voices, as after an
+/// upgrade. Deleting every voice later doesn't bring the default back. The
+/// check and insert share a transaction, so running this at startup and
+/// from onboarding creates at most one voice. A build without the sample
+/// skips this quietly and tries again next time. Returns the voice if it was
+/// created.
+pub(crate) fn ensure_default_voice(
+    conn: &rusqlite::Connection,
+    paths: &AppPaths,
+    sample: &Path,
+) -> CommandResult<Option<Voice>> {
+    let tx = conn.unchecked_transaction().context("Failed to start transaction")?;
+
+    if super::settings::default_voice_created(&tx)? {
+        return Ok(None);
+    }
+    let has_voice: bool = tx
+        .query_row("SELECT EXISTS(SELECT 1 FROM voices)", [], |row| row.get(0))
+        .context("Failed to query voices")?;
+    if has_voice {
+        super::settings::mark_default_voice_created(&tx)?;
+        tx.commit().context("Failed to commit transaction")?;
+        return Ok(None);
+    }
+
+    if !sample.exists() {
+        log::debug!("No bundled voice sample at {}", sample.display());
+        return Ok(None);
+    }
+    let sample_info = probe_audio(sample)
+        .map_err(|e| CommandError::Internal(format!("Unreadable bundled voice sample: {}", e)))?;
+
+    let voice_id = VoiceId::new(format!("voice_{}", uuid::Uuid::new_v4()));
+    let dest_path = paths.voice_sample_path(voice_id.as_str(), "wav");
+    std::fs::copy(sample, &dest_path).context("Failed to copy bundled voice sample")?;
+
+    let inserted = tx
+        .execute(
+            "INSERT INTO voices (id, name, engine, sample_path, is_default) VALUES (?, ?, 'chatterbox', ?, 1)",
+            rusqlite::params![
+                voice_id.as_str(),
+                DEFAULT_VOICE_NAME,
+                dest_path.to_string_lossy().to_string()
+            ],
+        )
+        .context("Failed to insert voice")
+        .and_then(|_| super::settings::mark_default_voice_created(&tx))
+        .and_then(|_| tx.commit().context("Failed to commit transaction"));
+    if let Err(e) = inserted {
+        let _ = std::fs::remove_file(&dest_path);
+        return Err(e);
+    }
+
+    Ok(Some(Voice {
+        id: voice_id,
+        name: DEFAULT_VOICE_NAME.to_string(),
+        sample_path: dest_path.to_string_lossy().to_string(),
+        original_filename: None,
+        is_default: true,
+        sample_info: Some(sample_info),
+    }))
+}
+
+/// Create the default voice from the sample bundled with the app.
 ///
-/// Removes the voice from the database and deletes the sample file.
+/// For onboarding on a fresh install; returns `None` without changing
+/// anything if the default voice was already created, voices already
+/// existed when the app first started, or the app has no bundled sample.
 #[tauri::command]
-pub async fn delete_voice(id: VoiceId, state: State<'_, AppState>) -> Result<(), String> {
-    // Get the voice info first
-    let (sample_path, is_default): (String, bool) = {
-        let conn = state.db.connection().lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT sample_path, is_default FROM voices WHERE id = ?")
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+pub async fn create_default_voice(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<Option<Voice>> {
+    use tauri::path::BaseDirectory;
 
-        stmt.query_row(rusqlite::params![id.as_str()], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)? != 0))
-        })
+    let sample = app
+        .path()
+        .resolve(DEFAULT_VOICE_RESOURCE, BaseDirectory::Resource)
+        .map_err(|e| CommandError::Internal(format!("Failed to locate bundled voice: {}", e)))?;
+
+    let conn = state.db.connection().lock().unwrap();
+    ensure_default_voice(&conn, &state.paths(), &sample)
+}
+
+/// Find everything that refers to a voice.
+fn query_voice_usage(conn: &rusqlite::Connection, id: &VoiceId) -> CommandResult<VoiceUsage> {
+    let is_default: bool = conn
+        .query_row(
+            "SELECT is_default FROM voices WHERE id = ?",
+            rusqlite::params![id.as_str()],
+            |row| row.get(0),
+        )
         .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => "Voice not found".to_string(),
-            _ => format!("Database error: {}", e),
-        })?
-    };
+            rusqlite::Error::QueryReturnedNoRows => {
+                CommandError::NotFound("Voice not found".to_string())
+            }
+            _ => CommandError::Database(format!("Database error: {}", e)),
+        })?;
 
-    // Check if any books are using this voice (optional - could also just warn)
-    // For now, we allow deletion but the user should be aware
+    let mut stmt = conn
+        .prepare(
+            "SELECT book_id, COUNT(*) FROM segment_voices WHERE voice_id = ?
+             GROUP BY book_id ORDER BY book_id",
+        )
+        .context("Failed to prepare query")?;
+    let overrides = stmt
+        .query_map(rusqlite::params![id.as_str()], |row| {
+            Ok((BookId::new(row.get::<_, String>(0)?), row.get::<_, u32>(1)?))
+        })
+        .context("Failed to query voice overrides")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read voice override")?;
 
-    // Delete from database
-    {
+    Ok(VoiceUsage {
+        is_default,
+        default_setting: super::settings::is_default_voice_setting(conn, id)?,
+        override_count: overrides.iter().map(|(_, count)| count).sum(),
+        book_ids: overrides.into_iter().map(|(book_id, _)| book_id).collect(),
+    })
+}
+
+/// Get what refers to a voice: the default flag, the `defaultVoice`
+/// setting, and per-segment overrides.
+#[tauri::command]
+pub async fn voice_usage(id: VoiceId, state: State<'_, AppState>) -> CommandResult<VoiceUsage> {
+    let conn = state.db.connection().lock().unwrap();
+    query_voice_usage(&conn, &id)
+}
+
+/// Remove a voice and clear every reference to it, in one transaction.
+///
+/// Overrides using the voice are dropped, the `defaultVoice` setting is
+/// unset if it names the voice, and if it was the default another voice is
+/// promoted in its place.
+fn remove_voice(conn: &rusqlite::Connection, id: &VoiceId) -> CommandResult<()> {
+    let tx = conn.unchecked_transaction().context("Failed to start transaction")?;
+
+    tx.execute(
+        "DELETE FROM segment_voices WHERE voice_id = ?",
+        rusqlite::params![id.as_str()],
+    )
+    .context("Failed to delete voice overrides")?;
+    super::settings::clear_default_voice_setting(&tx, id)?;
+    tx.execute("DELETE FROM voices WHERE id = ?", rusqlite::params![id.as_str()])
+        .context("Failed to delete voice")?;
+
+    // Set the first remaining voice as default if none is left
+    tx.execute(
+        "UPDATE voices SET is_default = 1
+         WHERE id = (SELECT id FROM voices LIMIT 1)
+           AND NOT EXISTS (SELECT 1 FROM voices WHERE is_default = 1)",
+        [],
+    )
+    .context("Failed to promote default voice")?;
+
+    tx.commit().context("Failed to commit transaction")?;
+    Ok(())
+}
+
+/// Delete a voice profile.
+///
+/// Removes the voice from the database and deletes the sample file. A voice
+/// that's the default or used by segment overrides is refused with a conflict
+/// unless `force` is set, in which case those references are cleared too.
+#[tauri::command]
+pub async fn delete_voice(
+    id: VoiceId,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let sample_path: String = {
         let conn = state.db.connection().lock().unwrap();
-        conn.execute("DELETE FROM voices WHERE id = ?", rusqlite::params![id.as_str()])
-            .map_err(|e| format!("Failed to delete voice: {}", e))?;
-    }
+
+        let usage = query_voice_usage(&conn, &id)?;
+        if usage.is_used() && !force.unwrap_or(false) {
+            let mut uses = Vec::new();
+            if usage.is_default || usage.default_setting {
+                uses.push("it is the default voice".to_string());
+            }
+            if usage.override_count > 0 {
+                uses.push(format!(
+                    "{} book(s) narrate passages with it",
+                    usage.book_ids.len()
+                ));
+            }
+            return Err(CommandError::Conflict(format!(
+                "Voice is in use: {}",
+                uses.join(" and ")
+            )));
+        }
+
+        let sample_path = conn
+            .query_row(
+                "SELECT sample_path FROM voices WHERE id = ?",
+                rusqlite::params![id.as_str()],
+                |row| row.get(0),
+            )
+            .context("Failed to read voice")?;
+        remove_voice(&conn, &id)?;
+        sample_path
+    };
 
     // Delete the sample file
     let sample_file = Path::new(&sample_path);
     if sample_file.exists() {
-        std::fs::remove_file(sample_file)
-            .map_err(|e| format!("Failed to delete sample file: {}", e))?;
-    }
-
-    // If this was the default voice, set another voice as default
-    if is_default {
-        let conn = state.db.connection().lock().unwrap();
-        // Set the first remaining voice as default
-        let _ = conn.execute(
-            "UPDATE voices SET is_default = 1 WHERE id = (SELECT id FROM voices LIMIT 1)",
-            [],
-        );
+        std::fs::remove_file(sample_file).context("Failed to delete sample file")?;
     }
 
     Ok(())
@@ -531,7 +3428,7 @@ pub async fn delete_voice(id: VoiceId, state: State<'_, AppState>) -> Result<(),
 
 /// Set a voice as the default for new narration generation.
 #[tauri::command]
-pub async fn set_default_voice(id: VoiceId, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn set_default_voice(id: VoiceId, state: State<'_, AppState>) -> CommandResult<()> {
     let conn = state.db.connection().lock().unwrap();
 
     // Verify the voice exists
@@ -544,19 +3441,719 @@ pub async fn set_default_voice(id: VoiceId, state: State<'_, AppState>) -> Resul
         .unwrap_or(false);
 
     if !exists {
-        return Err("Voice not found".to_string());
+        return Err(CommandError::NotFound("Voice not found".to_string()));
     }
 
     // Clear is_default on all voices
-    conn.execute("UPDATE voices SET is_default = 0", [])
-        .map_err(|e| format!("Failed to clear default voices: {}", e))?;
+    conn.execute("UPDATE voices SET is_default = 0", []).context("Failed to clear default voices")?;
 
     // Set is_default on the specified voice
     conn.execute(
         "UPDATE voices SET is_default = 1 WHERE id = ?",
         rusqlite::params![id.as_str()],
     )
-    .map_err(|e| format!("Failed to set default voice: {}", e))?;
+    .context("Failed to set default voice")?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second of silent 16-bit mono audio at 1kHz.
+    fn silent_wav() -> Vec<u8> {
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&[1, 0, 1, 0]);
+        wav.extend_from_slice(&1000u32.to_le_bytes());
+        wav.extend_from_slice(&2000u32.to_le_bytes());
+        wav.extend_from_slice(&[2, 0, 16, 0]);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&2000u32.to_le_bytes());
+        wav.extend_from_slice(&[0; 2000]);
+        wav
+    }
+
+    #[test]
+    fn test_choose_caption_prompt() {
+        let mut settings = crate::commands::Settings::default();
+        settings.caption_prompt_full_page = "Describe the illustration.".to_string();
+
+        assert_eq!(
+            choose_caption_prompt(None, ImagePosition::Middle, &settings),
+            settings.caption_prompt
+        );
+        assert_eq!(
+            choose_caption_prompt(None, ImagePosition::FullPage, &settings),
+            "Describe the illustration."
+        );
+        // Inline prompt is empty, so the default applies
+        assert_eq!(
+            choose_caption_prompt(None, ImagePosition::Inline, &settings),
+            settings.caption_prompt
+        );
+        assert_eq!(
+            choose_caption_prompt(Some("Describe this diagram's structure."), ImagePosition::FullPage, &settings),
+            "Describe this diagram's structure."
+        );
+        assert_eq!(
+            choose_caption_prompt(Some("  "), ImagePosition::FullPage, &settings),
+            "Describe the illustration."
+        );
+    }
+
+    #[test]
+    fn test_image_narration_mode_from_setting() {
+        assert_eq!(ImageNarrationMode::from_setting("skip"), ImageNarrationMode::Skip);
+        assert_eq!(
+            ImageNarrationMode::from_setting("altTextOnly"),
+            ImageNarrationMode::AltTextOnly
+        );
+        assert_eq!(ImageNarrationMode::from_setting("caption"), ImageNarrationMode::Caption);
+        assert_eq!(ImageNarrationMode::from_setting("unknown"), ImageNarrationMode::Caption);
+    }
+
+    #[test]
+    fn test_image_needs_caption() {
+        let mut image = NarrationImage {
+            source_path: "figure.png".to_string(),
+            alt_text: Some("A whale".to_string()),
+            caption: None,
+            caption_prompt: None,
+            prompt: "Describe.".to_string(),
+        };
+        assert!(image.needs_caption());
+        assert_eq!(image.narration_text(), Some("A whale"));
+
+        image.caption = Some("A sperm whale breaching.".to_string());
+        image.caption_prompt = Some("Describe.".to_string());
+        assert!(!image.needs_caption());
+        assert_eq!(image.narration_text(), Some("A sperm whale breaching."));
+
+        image.prompt = "Describe in detail.".to_string();
+        assert!(image.needs_caption());
+    }
+
+    #[test]
+    fn test_narration_estimate() {
+        let settings = crate::commands::Settings {
+            synthesis_chars_per_second: 50.0,
+            speech_chars_per_second: 10.0,
+            narration_sample_rate: 24000,
+            narration_channels: 1,
+            narration_codec: "wav".to_string(),
+            ..crate::commands::Settings::default()
+        };
+
+        let estimate = NarrationEstimate::from_char_counts([400, 0, 600], &settings);
+        assert_eq!(estimate.segment_count, 2);
+        assert_eq!(estimate.total_chars, 1000);
+        assert_eq!(estimate.generation_seconds, 20.0);
+        assert_eq!(estimate.audio_seconds, 100.0);
+        assert_eq!(estimate.audio_bytes, 4_800_000);
+
+        let opus = crate::commands::Settings {
+            narration_codec: "opus".to_string(),
+            ..settings
+        };
+        let estimate = NarrationEstimate::from_char_counts([1000], &opus);
+        assert_eq!(estimate.audio_bytes, 400_000);
+    }
+
+    #[test]
+    fn test_heading_announcement() {
+        let heading = |level, content: &str| NarrationSegment {
+            id: "h".to_string(),
+            index: 0,
+            content: content.to_string(),
+            voice_sample: String::new(),
+            image: None,
+            heading_level: level,
+            narrate: true,
+        };
+        let mut config = GenerationConfig::from_settings(&crate::commands::Settings {
+            heading_announcement: "{level_name}: {text}".to_string(),
+            ..crate::commands::Settings::default()
+        });
+
+        assert_eq!(config.spoken_text(&heading(Some(2), " Three ")), "Chapter: Three");
+        assert_eq!(config.spoken_text(&heading(Some(1), "Beginnings")), "Part: Beginnings");
+        assert_eq!(config.spoken_text(&heading(Some(4), "{text}")), "Section: {text}");
+        // Body text and empty headings are spoken as they are
+        assert_eq!(config.spoken_text(&heading(None, "Three")), "Three");
+        assert_eq!(config.spoken_text(&heading(Some(2), "  ")), "");
+
+        config.heading_announcement = String::new();
+        assert_eq!(config.spoken_text(&heading(Some(2), "Three")), "Three");
+    }
+
+    #[test]
+    fn test_segment_narration_text() {
+        let segment = NarrationSegment {
+            id: "s".to_string(),
+            index: 0,
+            content: "  [image]  ".to_string(),
+            voice_sample: String::new(),
+            image: Some(NarrationImage {
+                source_path: "img.png".to_string(),
+                alt_text: Some(" A cat ".to_string()),
+                caption: None,
+                caption_prompt: None,
+                prompt: "Describe".to_string(),
+            }),
+            heading_level: None,
+            narrate: true,
+        };
+
+        assert_eq!(segment.narration_text(ImageNarrationMode::Skip), "");
+        assert_eq!(segment.narration_text(ImageNarrationMode::AltTextOnly), "A cat");
+        assert_eq!(segment.narration_text(ImageNarrationMode::Caption), "A cat");
+
+        // Segments left out of narration are skipped whatever the mode
+        let left_out = NarrationSegment { narrate: false, ..segment };
+        assert_eq!(left_out.narration_text(ImageNarrationMode::AltTextOnly), "");
+    }
+
+    #[test]
+    fn test_find_overlapping_range() {
+        let existing = vec![(0, 4), (10, 12)];
+
+        assert_eq!(find_overlapping_range(&existing, 5, 9), None);
+        assert_eq!(find_overlapping_range(&existing, 4, 6), Some((0, 4)));
+        assert_eq!(find_overlapping_range(&existing, 8, 20), Some((10, 12)));
+        assert_eq!(find_overlapping_range(&existing, 11, 11), Some((10, 12)));
+        assert_eq!(find_overlapping_range(&[], 0, 100), None);
+    }
+
+    #[test]
+    fn test_voice_usage_and_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO voices (id, name, engine, sample_path, is_default) VALUES
+                 ('voice_a', 'A', 'chatterbox', 'a.wav', 1),
+                 ('voice_b', 'B', 'chatterbox', 'b.wav', 0);
+             INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                 VALUES ('book_1', 'One', 'txt', 'one.txt', 'none', 0, 0);
+             INSERT INTO segment_voices (book_id, start_index, end_index, voice_id) VALUES
+                 ('book_1', 0, 2, 'voice_a'),
+                 ('book_1', 5, 6, 'voice_a');
+             INSERT INTO settings (key, value) VALUES ('defaultVoice', 'voice_a');",
+        )
+        .unwrap();
+
+        let usage = query_voice_usage(&conn, &VoiceId::new("voice_a")).unwrap();
+        assert!(usage.is_default && usage.default_setting);
+        assert_eq!(usage.book_ids, vec![BookId::new("book_1")]);
+        assert_eq!(usage.override_count, 2);
+        assert!(!query_voice_usage(&conn, &VoiceId::new("voice_b")).unwrap().is_used());
+        assert!(matches!(
+            query_voice_usage(&conn, &VoiceId::new("voice_missing")),
+            Err(CommandError::NotFound(_))
+        ));
+
+        remove_voice(&conn, &VoiceId::new("voice_a")).unwrap();
+
+        let overrides: u32 = conn
+            .query_row("SELECT COUNT(*) FROM segment_voices", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(overrides, 0);
+        let setting: u32 = conn
+            .query_row("SELECT COUNT(*) FROM settings WHERE key = 'defaultVoice'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(setting, 0);
+        let usage = query_voice_usage(&conn, &VoiceId::new("voice_b")).unwrap();
+        assert!(usage.is_default);
+    }
+
+    #[test]
+    fn test_splice_markers() {
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+        let markers = vec![marker("a", 0.0, 1.0), marker("b", 1.0, 3.0), marker("c", 3.0, 4.5)];
+
+        let mut spliced = splice_markers(&markers, &SegmentId::new("b"), 2.5).unwrap();
+        let times: Vec<(f64, f64)> = spliced.iter().map(|m| (m.start, m.end)).collect();
+        assert_eq!(times, vec![(0.0, 1.0), (1.0, 3.5), (3.5, 5.0)]);
+        validate_markers(&mut spliced, Some(5.0), false).unwrap();
+
+        let shorter = splice_markers(&markers, &SegmentId::new("a"), 0.5).unwrap();
+        assert_eq!(shorter.last().map(|m| (m.start, m.end)), Some((2.5, 4.0)));
+
+        assert!(splice_markers(&markers, &SegmentId::new("missing"), 1.0).is_none());
+    }
+
+    #[test]
+    fn test_narration_range() {
+        assert_eq!(narration_range(None, None, 9).unwrap(), None);
+        assert_eq!(narration_range(Some(0), Some(9), 9).unwrap(), None);
+        assert_eq!(narration_range(None, Some(3), 9).unwrap(), Some(0..=3));
+        assert_eq!(narration_range(Some(4), None, 9).unwrap(), Some(4..=9));
+        assert_eq!(narration_range(Some(5), Some(5), 9).unwrap(), Some(5..=5));
+        assert!(narration_range(Some(6), Some(5), 9).is_err());
+        assert!(narration_range(Some(5), Some(10), 9).is_err());
+    }
+
+    #[test]
+    fn test_narration_splice() {
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+        let times = |markers: &[Marker]| -> Vec<(String, f64, f64)> {
+            markers
+                .iter()
+                .map(|m| (m.segment_id.as_str().to_string(), m.start, m.end))
+                .collect()
+        };
+        // Segment 2 was never narrated
+        let markers = vec![
+            (0, marker("a", 0.0, 1.0)),
+            (1, marker("b", 1.0, 3.0)),
+            (3, marker("d", 3.0, 4.0)),
+            (4, marker("e", 4.0, 6.0)),
+        ];
+
+        // The range's audio is replaced and later markers move with its length
+        let splice = NarrationSplice::new(&markers, 1..=3);
+        assert_eq!((splice.start, splice.end), (1.0, 4.0));
+        let narrated = vec![marker("b", 1.0, 2.0), marker("c", 2.0, 2.5), marker("d", 2.5, 5.0)];
+        assert_eq!(
+            times(&splice.markers(narrated)),
+            times(&[
+                marker("a", 0.0, 1.0),
+                marker("b", 1.0, 2.0),
+                marker("c", 2.0, 2.5),
+                marker("d", 2.5, 5.0),
+                marker("e", 5.0, 7.0),
+            ])
+        );
+
+        // A range with no narration is inserted before the next marker, or
+        // after the last one
+        let splice = NarrationSplice::new(&markers, 2..=2);
+        assert_eq!((splice.start, splice.end), (3.0, 3.0));
+        let spliced = splice.markers(vec![marker("c", 3.0, 3.5)]);
+        assert_eq!(spliced.last().map(|m| (m.start, m.end)), Some((4.5, 6.5)));
+        let splice = NarrationSplice::new(&markers, 5..=6);
+        assert_eq!((splice.start, splice.end), (6.0, 6.0));
+        assert_eq!(NarrationSplice::new(&[], 0..=1).start, 0.0);
+    }
+
+    #[test]
+    fn test_store_spliced_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+             VALUES ('book', 'Title', 'txt', 'book.txt', 'partial', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content, segment_type)
+             VALUES ('a', 'book', 0, 'One', 'text'), ('b', 'book', 1, 'Two', 'text');",
+        )
+        .unwrap();
+        let book_id = BookId::new("book");
+        let status = || -> String {
+            conn.query_row("SELECT narration_status FROM books", [], |row| row.get(0))
+                .unwrap()
+        };
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+
+        // A splice that leaves segments unnarrated keeps the book partial
+        let duration = store_spliced_markers(&conn, &book_id, &[marker("a", 0.0, 1.5)], false);
+        assert_eq!(duration.unwrap(), 1.5);
+        assert_eq!(status(), "partial");
+
+        let markers = [marker("a", 0.0, 1.5), marker("b", 1.5, 2.0)];
+        assert_eq!(store_spliced_markers(&conn, &book_id, &markers, true).unwrap(), 2.0);
+        assert_eq!(status(), "ready");
+
+        // Completing a stale narration doesn't make it current
+        conn.execute("UPDATE books SET narration_status = 'stale'", []).unwrap();
+        store_spliced_markers(&conn, &book_id, &markers, true).unwrap();
+        assert_eq!(status(), "stale");
+    }
+
+    #[test]
+    fn test_validate_markers() {
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+        let times = |markers: &[Marker]| -> Vec<(String, f64, f64)> {
+            markers
+                .iter()
+                .map(|m| (m.segment_id.as_str().to_string(), m.start, m.end))
+                .collect()
+        };
+
+        // Gaps are allowed
+        let mut markers = vec![marker("a", 0.0, 1.5), marker("b", 2.0, 4.0)];
+        assert!(validate_markers(&mut markers, Some(4.0), false).is_ok());
+        assert!(validate_markers(&mut Vec::new(), Some(0.0), false).is_ok());
+
+        let overlapping =
+            || vec![marker("b", 2.0, 4.0), marker("a", 0.0, 2.5), marker("c", 4.0, 5.0)];
+        let mut markers = overlapping();
+        let error = validate_markers(&mut markers, Some(5.0), false).unwrap_err();
+        assert!(matches!(error, CommandError::InvalidInput(ref m) if m.contains("segment b")));
+        let mut markers = overlapping();
+        validate_markers(&mut markers, Some(5.0), true).unwrap();
+        assert_eq!(
+            times(&markers),
+            times(&[marker("a", 0.0, 2.5), marker("b", 2.5, 4.0), marker("c", 4.0, 5.0)])
+        );
+
+        // Within the tolerance, touching markers are fine
+        let mut markers = vec![marker("a", 0.0, 1.02), marker("b", 1.0, 2.0)];
+        assert!(validate_markers(&mut markers, None, false).is_ok());
+
+        // Markers must have some length
+        assert!(validate_markers(&mut vec![marker("a", 1.0, 1.0)], None, false).is_err());
+        let mut markers = vec![marker("a", 0.0, 1.0), marker("b", 1.0, 1.0)];
+        validate_markers(&mut markers, None, true).unwrap();
+        assert_eq!(markers.len(), 1);
+
+        let mut markers = vec![marker("a", 0.0, 3.0), marker("b", 3.0, 6.0), marker("c", 7.0, 8.0)];
+        let error = validate_markers(&mut markers, Some(5.0), false).unwrap_err();
+        assert!(
+            matches!(error, CommandError::InvalidInput(ref m) if m.contains("after the audio ends"))
+        );
+
+        // Repairing clamps the end and drops markers entirely past the audio
+        validate_markers(&mut markers, Some(5.0), true).unwrap();
+        assert_eq!(times(&markers), times(&[marker("a", 0.0, 3.0), marker("b", 3.0, 5.0)]));
+
+        // Without a known duration only the ordering is checked
+        assert!(validate_markers(&mut vec![marker("a", 0.0, 300.0)], None, false).is_ok());
+        assert!(validate_markers(&mut vec![marker("a", -1.0, 1.0)], Some(5.0), false).is_err());
+    }
+
+    #[test]
+    fn test_check_and_repair_marker_timing() {
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+        let mut markers = vec![
+            marker("a", 0.5, 1.0),
+            marker("b", 1.5, 3.0),
+            marker("c", 2.5, 4.0),
+            marker("d", 4.005, 5.0),
+        ];
+
+        let report = check_marker_timing(&markers, Some(6.0));
+        assert_eq!(report.marker_count, 4);
+        let gaps: Vec<(f64, f64)> = report.gaps.iter().map(|g| (g.start, g.end)).collect();
+        assert_eq!(gaps, vec![(0.0, 0.5), (1.0, 1.5), (5.0, 6.0)]);
+        assert_eq!(report.gaps[0].after, None);
+        assert_eq!(report.gaps[1].after, Some(SegmentId::new("a")));
+        assert_eq!(report.gaps[2].before, None);
+        assert_eq!(
+            report.overlaps,
+            vec![MarkerOverlap {
+                first: SegmentId::new("b"),
+                second: SegmentId::new("c"),
+                start: 2.5,
+                end: 3.0,
+            }]
+        );
+
+        assert_eq!(repair_marker_timing(&mut markers, MarkerRepairStrategy::ClampOverlaps), 1);
+        assert_eq!(markers[1].end, 2.5);
+        assert_eq!(repair_marker_timing(&mut markers, MarkerRepairStrategy::CloseGaps), 1);
+        assert_eq!(markers[0].end, 1.5);
+
+        let report = check_marker_timing(&markers, None);
+        assert!(report.overlaps.is_empty());
+        // Only the leading gap is left
+        assert_eq!(report.gaps.len(), 1);
+        assert_eq!(report.gaps[0].end, 0.5);
+
+        // Clamping a marker that starts with the next drops it
+        let mut markers = vec![marker("a", 0.0, 2.0), marker("b", 1.0, 2.0), marker("c", 1.0, 3.0)];
+        assert_eq!(repair_marker_timing(&mut markers, MarkerRepairStrategy::ClampOverlaps), 2);
+        let kept: Vec<(&str, f64, f64)> =
+            markers.iter().map(|m| (m.segment_id.as_str(), m.start, m.end)).collect();
+        assert_eq!(kept, vec![("a", 0.0, 1.0), ("c", 1.0, 3.0)]);
+        assert!(check_marker_timing(&markers, None).overlaps.is_empty());
+    }
+
+    #[test]
+    fn test_check_marker_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::storage::init_database(&dir.path().join("test.db")).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                 VALUES ('book', 'Book', 'txt', 'book.txt', 'none', 0, 0),
+                        ('other', 'Other', 'txt', 'other.txt', 'none', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content) VALUES
+                 ('a', 'book', 0, 'One.'),
+                 ('b', 'book', 1, 'Two.'),
+                 ('c', 'other', 0, 'Three.');",
+        )
+        .unwrap();
+        let book_id = BookId::new("book");
+        let marker = |id: &str, start: f64, end: f64| Marker {
+            segment_id: SegmentId::new(id),
+            start,
+            end,
+        };
+
+        check_marker_segments(&conn, &book_id, &[marker("a", 0.0, 1.0), marker("b", 1.0, 2.0)])
+            .unwrap();
+        let foreign = check_marker_segments(&conn, &book_id, &[marker("c", 0.0, 1.0)]);
+        assert!(matches!(foreign, Err(CommandError::InvalidInput(_))));
+        let repeated =
+            check_marker_segments(&conn, &book_id, &[marker("a", 0.0, 1.0), marker("a", 1.0, 2.0)]);
+        assert!(matches!(repeated, Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_remove_segment_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        let book_id = BookId::new("book");
+        std::fs::create_dir_all(paths.segment_cache_dir("book")).unwrap();
+        std::fs::write(paths.segment_cache_path("book", "a"), [0u8; 30]).unwrap();
+        std::fs::write(paths.segment_cache_path("book", "b"), [0u8; 12]).unwrap();
+        let audio_path = paths.narration_audio_path("book", NarrationCodec::Wav);
+        std::fs::write(&audio_path, [0u8; 8]).unwrap();
+        std::fs::write(paths.markers_path("book"), "[]").unwrap();
+
+        assert_eq!(remove_segment_cache(&paths, &book_id).unwrap(), 42);
+        assert!(!paths.segment_cache_dir("book").exists());
+        assert!(audio_path.exists());
+        assert!(paths.markers_path("book").exists());
+
+        assert_eq!(remove_segment_cache(&paths, &book_id).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_partial_markers_resume_and_finalize() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+        let conn = db.connection().lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                 VALUES ('book', 'Book', 'txt', 'book.txt', 'generating', 0, 0);
+             INSERT INTO segments (id, book_id, idx, content) VALUES
+                 ('a', 'book', 0, 'One.'),
+                 ('b', 'book', 1, 'Two.');",
+        )
+        .unwrap();
+        let book_id = BookId::new("book");
+        std::fs::create_dir_all(paths.narration_path("book")).unwrap();
+
+        let wav = silent_wav();
+        let cache_path = dir.path().join("a.wav");
+        std::fs::write(&cache_path, &wav).unwrap();
+
+        let first = Marker { segment_id: SegmentId::new("a"), start: 0.0, end: 1.0 };
+        save_partial_marker(&conn, &book_id, &first, "key_a").unwrap();
+        save_partial_marker(&conn, &book_id, &first, "key_a").unwrap();
+
+        let partial = query_partial_markers(&conn, &book_id).unwrap();
+        assert_eq!(partial.len(), 1);
+        assert_eq!(partial["a"].resume_duration("key_a", &cache_path), Some(1.0));
+        assert_eq!(partial["a"].resume_duration("key_other", &cache_path), None);
+        assert_eq!(partial["a"].resume_duration("key_a", &dir.path().join("missing.wav")), None);
+
+        let markers = vec![first, Marker { segment_id: SegmentId::new("b"), start: 1.0, end: 2.0 }];
+        let meta = NarrationMeta {
+            engine: "chatterbox".to_string(),
+            voice_id: Some(VoiceId::new("voice")),
+            voice_name: Some("Narrator".to_string()),
+            exag: 0.5,
+            cfg: 0.5,
+            temp: 0.8,
+            codec: "wav".to_string(),
+            generated_at: 1705334400,
+        };
+        let narration_path = "narration/book/audio.wav";
+        let ready = NarrationStatus::Ready;
+        finalize_narration(&conn, &paths, &book_id, &markers, narration_path, 2.0, ready, Some(&meta))
+            .unwrap();
+
+        assert!(query_partial_markers(&conn, &book_id).unwrap().is_empty());
+        let (count, status, stored): (u32, String, Option<String>) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM markers WHERE book_id = 'book'), narration_status,
+                        narration_meta
+                 FROM books WHERE id = 'book'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(status, "ready");
+        assert_eq!(NarrationMeta::from_json(stored.as_deref()), Some(meta.clone()));
+
+        // A range narrated on its own leaves the book partial
+        let partial = NarrationStatus::Partial;
+        finalize_narration(&conn, &paths, &book_id, &markers, narration_path, 2.0, partial, Some(&meta))
+            .unwrap();
+        let status: String = conn
+            .query_row("SELECT narration_status FROM books WHERE id = 'book'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(status, "partial");
+        assert!(paths.markers_path("book").exists());
+    }
+
+    #[tokio::test]
+    async fn test_caption_imported_image() {
+        use super::super::{library, settings::Settings};
+        use crate::services::parser;
+        use axum::routing::{get, post};
+        use axum::Json;
+
+        // A vision service that only recognises the imported image
+        let expected = base64::engine::general_purpose::STANDARD.encode(b"png data");
+        let caption = move |Json(request): Json<serde_json::Value>| {
+            let known = request["image_base64"] == expected.as_str();
+            async move {
+                let caption = if known { "A lighthouse at dusk" } else { "Unknown" };
+                Json(serde_json::json!({ "caption": caption }))
+            }
+        };
+        let router = axum::Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/caption", post(caption));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let vision = VisionService::new(format!("http://{}", listener.local_addr().unwrap()));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().join("data"));
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+
+        // Import the parsed book's segments and images
+        let source = dir.path().join("book.html");
+        std::fs::write(
+            &source,
+            r#"<html><body><p>The coast.</p><img src="light.png" alt="Lighthouse"/></body></html>"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("light.png"), b"png data").unwrap();
+        let parsed = parser::parse_file(&source).unwrap();
+        let book_id = BookId::new("book");
+        let image_paths = library::save_images(&paths, &book_id, &parsed.images).unwrap();
+        let settings = Settings::default();
+        let mut segments = {
+            let conn = db.connection().lock().unwrap();
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+                 VALUES ('book', 'Book', 'html', 'book.html', 0, 0)",
+                [],
+            )
+            .unwrap();
+            library::insert_segments(&conn, &book_id, &parsed.segments, &[], &image_paths).unwrap();
+            query_narration_segments(&conn, &book_id, &settings, |_| String::new()).unwrap()
+        };
+        let image = segments[1].image.as_ref().unwrap();
+        assert_eq!(image.alt_text.as_deref(), Some("Lighthouse"));
+        assert!(image.needs_caption());
+
+        let captioned = std::cell::Cell::new(0);
+        let cancel_flag = AtomicBool::new(false);
+        let on_progress = |progress: GenerationProgress| captioned.set(progress.current);
+        caption_images(&book_id, &vision, &mut segments, &db, &paths, on_progress, &cancel_flag)
+            .await
+            .unwrap();
+
+        assert_eq!(captioned.get(), 1);
+        let image = segments[1].image.as_ref().unwrap();
+        assert_eq!(image.caption.as_deref(), Some("A lighthouse at dusk"));
+        assert!(!image.needs_caption());
+
+        // The caption is stored for the next generation
+        let conn = db.connection().lock().unwrap();
+        let (stored, prompt): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT caption, caption_prompt FROM segment_images WHERE segment_id = ?",
+                [&segments[1].id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(stored.as_deref(), Some("A lighthouse at dusk"));
+        assert_eq!(prompt, Some(image.prompt.clone()));
+    }
+
+    #[test]
+    fn test_ensure_default_voice() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().join("data"));
+        paths.ensure_dirs().unwrap();
+        let db = crate::storage::init_database(&paths.database).unwrap();
+        let conn = db.connection().lock().unwrap();
+
+        // Without a bundled sample nothing happens, and a later run can
+        // still create the voice
+        let missing = dir.path().join("missing.wav");
+        assert!(ensure_default_voice(&conn, &paths, &missing).unwrap().is_none());
+
+        let wav = silent_wav();
+        let sample = dir.path().join("default-voice.wav");
+        std::fs::write(&sample, &wav).unwrap();
+
+        let voice = ensure_default_voice(&conn, &paths, &sample).unwrap().unwrap();
+        assert!(voice.is_default);
+        assert!(Path::new(&voice.sample_path).exists());
+
+        // Already created, so nothing more is, even once the voice is gone
+        assert!(ensure_default_voice(&conn, &paths, &sample).unwrap().is_none());
+        let count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM voices WHERE is_default = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        conn.execute("DELETE FROM voices", []).unwrap();
+        assert!(ensure_default_voice(&conn, &paths, &sample).unwrap().is_none());
+
+        // A library that already has voices is only marked as done
+        conn.execute("DELETE FROM settings", []).unwrap();
+        conn.execute(
+            "INSERT INTO voices (id, name, engine, sample_path) VALUES ('v', 'Mine', 'chatterbox', 'v.wav')",
+            [],
+        )
+        .unwrap();
+        assert!(ensure_default_voice(&conn, &paths, &sample).unwrap().is_none());
+        let single = |sql: &str| -> u32 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(single("SELECT COUNT(*) FROM settings WHERE key = 'defaultVoiceCreated'"), 1);
+        assert_eq!(single("SELECT COUNT(*) FROM voices"), 1);
+    }
+
+    #[test]
+    fn test_preview_text() {
+        assert_eq!(preview_text(None), PREVIEW_PANGRAM);
+        assert_eq!(preview_text(Some("   ")), PREVIEW_PANGRAM);
+        assert_eq!(preview_text(Some("  Hello there. ")), "Hello there.");
+
+        let long = "word ".repeat(100);
+        let capped = preview_text(Some(&long));
+        assert!(capped.chars().count() <= MAX_PREVIEW_CHARS);
+        assert!(capped.ends_with("word"));
+
+        // No whitespace to break at: cut at the limit, on a char boundary
+        let unbroken = "é".repeat(MAX_PREVIEW_CHARS + 10);
+        assert_eq!(preview_text(Some(&unbroken)).chars().count(), MAX_PREVIEW_CHARS);
+    }
+}
@@ -5,13 +5,25 @@ pub mod models;
 pub mod services;
 pub mod storage;
 
-use std::collections::HashMap;
-use std::sync::atomic::AtomicBool;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use storage::{init_database, AppPaths, Database};
-use tauri::Manager;
+use std::time::Duration;
+use storage::{
+    init_database, read_data_location, relativize_book_paths, reset_stale_generations, AppPaths,
+    Database,
+};
+use tauri::path::BaseDirectory;
+use tauri::{Manager, RunEvent};
+use tauri_plugin_log::{Target, TargetKind};
 use tokio::sync::RwLock;
 
+/// How long to wait for cancelled generations to wind down on exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Updates buffered for each WebSocket sync client before it falls behind.
+const SYNC_UPDATE_CAPACITY: usize = 64;
+
 /// Handle for the running sync server.
 pub struct SyncServerHandle {
     /// Shutdown signal sender.
@@ -20,6 +32,21 @@ pub struct SyncServerHandle {
     pub mdns_daemon: mdns_sd::ServiceDaemon,
     /// The full service name registered with mDNS.
     pub service_fullname: String,
+    /// Fingerprint of the certificate served, if the server uses TLS.
+    pub fingerprint: Option<String>,
+}
+
+impl SyncServerHandle {
+    /// Withdraw the mDNS advertisement and stop the HTTP server.
+    pub fn shutdown(self) -> Result<(), mdns_sd::Error> {
+        let unregistered = self
+            .mdns_daemon
+            .unregister(&self.service_fullname)
+            .map(|_| ());
+        let _ = self.shutdown_tx.send(());
+        unregistered?;
+        self.mdns_daemon.shutdown().map(|_| ())
+    }
 }
 
 /// Handle for an active narration generation task.
@@ -30,48 +57,168 @@ pub struct GenerationHandle {
     pub task_handle: tokio::task::JoinHandle<()>,
 }
 
+/// Stop background work before the app exits.
+///
+/// Cancels every active generation and waits up to `SHUTDOWN_TIMEOUT` for
+/// the tasks to finish, aborting any that don't, then stops the sync server
+/// so its mDNS advertisement doesn't outlive the app.
+async fn shutdown(state: &AppState) {
+    // Take the handles so finishing tasks don't wait on this lock
+    let generations: Vec<(String, GenerationHandle)> =
+        state.active_generations.write().await.drain().collect();
+
+    for (_, handle) in &generations {
+        handle.cancel_flag.store(true, Ordering::Relaxed);
+    }
+
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_TIMEOUT;
+    for (book_id, handle) in generations {
+        let abort = handle.task_handle.abort_handle();
+        if tokio::time::timeout_at(deadline, handle.task_handle).await.is_err() {
+            log::warn!("Generation for book {} did not stop in time; aborting", book_id);
+            abort.abort();
+        }
+    }
+
+    if let Some(task) = state.server_updates.lock().unwrap().take() {
+        task.abort();
+    }
+
+    if let Some(handle) = state.sync_server.write().await.take() {
+        match handle.shutdown() {
+            Ok(()) => log::info!("Sync server stopped"),
+            Err(e) => log::error!("Failed to stop sync server: {}", e),
+        }
+    }
+}
+
 /// Application state shared across all commands.
 pub struct AppState {
     pub db: Arc<Database>,
-    pub paths: AppPaths,
+    /// Data directory paths; replaced when the data directory is relocated.
+    paths: std::sync::RwLock<AppPaths>,
     /// Handle to the running sync server, if any.
     pub sync_server: Arc<RwLock<Option<SyncServerHandle>>>,
     /// Active narration generation tasks, keyed by book ID.
     pub active_generations: Arc<RwLock<HashMap<String, GenerationHandle>>>,
+    /// Latest progress update of each active generation, keyed by book ID.
+    pub generation_progress: Arc<std::sync::Mutex<HashMap<String, commands::GenerationProgress>>>,
+    /// Source files currently being imported, by canonical path.
+    pub active_imports: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Changes pushed to clients connected to the sync server's `/ws`.
+    pub sync_updates: tokio::sync::broadcast::Sender<commands::SyncUpdate>,
+    /// Task following another server's `/ws`, if subscribed to one.
+    pub server_updates: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+impl AppState {
+    /// Current data directory paths.
+    pub fn paths(&self) -> AppPaths {
+        self.paths.read().unwrap().clone()
+    }
+
+    /// Point the app at a new data directory.
+    pub(crate) fn set_paths(&self, paths: AppPaths) {
+        *self.paths.write().unwrap() = paths;
+    }
+
+    /// Push a change to WebSocket sync clients; dropped if none are connected.
+    pub(crate) fn broadcast(&self, update: commands::SyncUpdate) {
+        let _ = self.sync_updates.send(update);
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             // Library commands
             commands::import_book,
+            commands::import_from_url,
+            commands::import_directory,
+            commands::preview_parse,
+            commands::replace_source,
             commands::get_library,
+            commands::update_book,
+            commands::set_source_format,
             commands::delete_book,
+            commands::search_library,
+            commands::verify_library,
+            commands::repair_paths,
+            commands::get_storage_usage,
+            commands::list_profiles,
+            commands::get_active_profile,
+            commands::create_profile,
+            commands::switch_profile,
+            commands::move_book_to_profile,
             // Reader commands
             commands::get_book,
             commands::get_segments,
+            commands::get_chapters,
+            commands::update_segment,
+            commands::set_segment_narrate,
+            commands::split_segment,
+            commands::merge_segments,
+            commands::reindex_segments,
+            commands::split_book,
             commands::get_markers,
+            commands::get_reading_context,
+            commands::export_narration_data,
+            commands::get_segment_audio,
             commands::get_progress,
             commands::save_progress,
+            commands::get_reading_stats,
             // TTS commands (desktop only)
             commands::generate_narration,
+            commands::estimate_narration,
             commands::cancel_generation,
+            commands::list_active_generations,
+            commands::get_service_status,
+            commands::rebuild_markers,
+            commands::compact_book_narration,
+            commands::check_markers,
+            commands::repair_markers,
+            commands::attach_narration,
+            commands::regenerate_segment,
             commands::get_voices,
             commands::create_voice,
+            commands::create_default_voice,
             commands::delete_voice,
             commands::set_default_voice,
+            commands::voice_usage,
+            commands::preview_voice,
+            commands::get_pronunciations,
+            commands::add_pronunciation,
+            commands::update_pronunciation,
+            commands::delete_pronunciation,
+            commands::get_segment_voices,
+            commands::set_segment_voice,
+            commands::clear_segment_voice,
+            commands::set_book_caption_prompt,
             // Bundle commands
             commands::export_bundle,
+            commands::export_m4b,
+            commands::export_captions,
             commands::import_bundle,
             commands::validate_bundle,
+            commands::list_bundles,
+            commands::delete_bundle,
+            commands::export_library,
+            commands::import_library,
             // Sync commands
             commands::start_sync_server,
             commands::stop_sync_server,
             commands::discover_sync_servers,
             commands::connect_to_server,
+            commands::preview_sync,
             commands::sync_with_server,
+            commands::subscribe_server_updates,
+            commands::unsubscribe_server_updates,
+            commands::save_known_server,
+            commands::list_known_servers,
+            commands::forget_server,
             commands::get_sync_status,
             // Settings commands
             commands::get_settings,
@@ -80,45 +227,122 @@ pub fn run() {
             commands::get_import_preferences,
             commands::set_import_preferences,
             commands::reset_settings,
+            commands::export_settings,
+            commands::import_settings,
             commands::get_data_directory,
+            commands::open_data_directory,
+            commands::relocate_data_directory,
+            commands::get_logs,
         ])
         .setup(|app| {
-            // Set up logging in debug mode
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
-            }
-
             // Get the app data directory
             let app_data_dir = app
                 .path()
                 .app_data_dir()
                 .expect("Failed to get app data directory");
 
-            // Set up application paths
-            let paths = AppPaths::new(app_data_dir);
+            // Set up application paths, following a relocated data directory
+            // unless its library has gone missing (e.g. an unmounted drive)
+            let location = read_data_location(&app_data_dir);
+            let missing_location = location
+                .clone()
+                .filter(|root| !AppPaths::new(root.clone()).database.exists());
+            let root = match location {
+                Some(root) if missing_location.is_none() => root,
+                _ => app_data_dir,
+            };
+            let paths = AppPaths::new(root);
             paths.ensure_dirs().expect("Failed to create app directories");
 
             // Initialize the database
             let db = init_database(&paths.database)
                 .expect("Failed to initialize database");
 
+            // Set up logging at the configured level; debug builds always log
+            // at least Info
+            let mut level = commands::load_settings(&db)
+                .map(|settings| settings.log_level_filter())
+                .unwrap_or(log::LevelFilter::Warn);
+            if cfg!(debug_assertions) {
+                level = level.max(log::LevelFilter::Info);
+            }
+            app.handle().plugin(
+                tauri_plugin_log::Builder::default()
+                    .level(level)
+                    .targets([
+                        Target::new(TargetKind::Stdout),
+                        Target::new(TargetKind::LogDir {
+                            file_name: Some(commands::LOG_FILE_NAME.to_string()),
+                        }),
+                    ])
+                    .build(),
+            )?;
+
+            if let Some(root) = missing_location {
+                log::error!(
+                    "Data directory {} has no library; using {}",
+                    root.display(),
+                    paths.root.display()
+                );
+            }
+
+            // No generation can be running yet, so any 'generating' book was
+            // interrupted by a previous crash or forced quit
+            match reset_stale_generations(&db, &[] as &[&str]) {
+                Ok(0) => {}
+                Ok(count) => log::warn!("Reset {} book(s) stuck in 'generating'", count),
+                Err(e) => log::error!("Failed to reset interrupted generations: {}", e),
+            }
+
+            // Older rows store absolute paths, which break when the data
+            // directory moves
+            match relativize_book_paths(&db, &paths) {
+                Ok(0) => {}
+                Ok(count) => log::info!("Converted file paths of {} book(s) to relative", count),
+                Err(e) => log::error!("Failed to convert book paths: {}", e),
+            }
+
+            // Give a fresh install a voice to narrate with
+            let default_voice = app
+                .path()
+                .resolve(commands::DEFAULT_VOICE_RESOURCE, BaseDirectory::Resource)
+                .map_err(|e| e.to_string())
+                .and_then(|sample| {
+                    let conn = db.connection().lock().unwrap();
+                    commands::ensure_default_voice(&conn, &paths, &sample).map_err(|e| e.to_string())
+                });
+            match default_voice {
+                Ok(Some(voice)) => log::info!("Created default voice {}", voice.id),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to create default voice: {}", e),
+            }
+
             // Store state for use in commands
             let state = AppState {
                 db: Arc::new(db),
-                paths,
+                paths: std::sync::RwLock::new(paths),
                 sync_server: Arc::new(RwLock::new(None)),
                 active_generations: Arc::new(RwLock::new(HashMap::new())),
+                generation_progress: Arc::new(std::sync::Mutex::new(HashMap::new())),
+                active_imports: Arc::new(std::sync::Mutex::new(HashSet::new())),
+                sync_updates: tokio::sync::broadcast::channel(SYNC_UPDATE_CAPACITY).0,
+                server_updates: Arc::new(std::sync::Mutex::new(None)),
             };
             app.manage(state);
 
+            tauri::async_runtime::spawn(commands::auto_start_sync_server(app.handle().clone()));
+
             log::info!("Actual Reader initialized successfully");
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { .. } = event {
+                if let Some(state) = app.try_state::<AppState>() {
+                    tauri::async_runtime::block_on(shutdown(&state));
+                }
+            }
+        });
 }
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::VoiceId;
+
 /// Unique identifier for a Book (UUID v4).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct BookId(pub String);
@@ -29,6 +31,7 @@ impl std::fmt::Display for BookId {
 #[serde(rename_all = "lowercase")]
 pub enum SourceFormat {
     Epub,
+    Html,
     Markdown,
     Txt,
     Pdf,
@@ -39,6 +42,7 @@ impl SourceFormat {
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Epub => "epub",
+            Self::Html => "html",
             Self::Markdown => "markdown",
             Self::Txt => "txt",
             Self::Pdf => "pdf",
@@ -49,6 +53,7 @@ impl SourceFormat {
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "epub" => Some(Self::Epub),
+            "html" => Some(Self::Html),
             "markdown" => Some(Self::Markdown),
             "txt" => Some(Self::Txt),
             "pdf" => Some(Self::Pdf),
@@ -64,6 +69,10 @@ pub enum NarrationStatus {
     None,
     Generating,
     Ready,
+    /// Narration exists but the text has been edited since it was generated.
+    Stale,
+    /// Only a range of segments is narrated, until the whole book is.
+    Partial,
 }
 
 impl NarrationStatus {
@@ -73,6 +82,8 @@ impl NarrationStatus {
             Self::None => "none",
             Self::Generating => "generating",
             Self::Ready => "ready",
+            Self::Stale => "stale",
+            Self::Partial => "partial",
         }
     }
 
@@ -82,11 +93,70 @@ impl NarrationStatus {
             "none" => Some(Self::None),
             "generating" => Some(Self::Generating),
             "ready" => Some(Self::Ready),
+            "stale" => Some(Self::Stale),
+            "partial" => Some(Self::Partial),
             _ => None,
         }
     }
 }
 
+/// Publication details read from the source file.
+///
+/// Only EPUBs carry these; every field is None for other formats or when
+/// the file doesn't declare it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadata {
+    pub publisher: Option<String>,
+    /// Publication date as declared, usually ISO 8601 ("2004", "2004-05-17").
+    pub published_date: Option<String>,
+    /// The package's unique identifier, e.g. a UUID or ISBN URN.
+    pub identifier: Option<String>,
+    /// ISBN-10 or ISBN-13 digits, without hyphens.
+    pub isbn: Option<String>,
+}
+
+impl BookMetadata {
+    /// True if no field is set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// How a book's narration was generated, so it can be reproduced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NarrationMeta {
+    /// TTS engine that synthesized the narration, e.g. "chatterbox".
+    pub engine: String,
+    /// Voice the book was narrated with, apart from per-segment overrides.
+    pub voice_id: Option<VoiceId>,
+    /// Name of that voice when the narration was generated.
+    pub voice_name: Option<String>,
+    /// Chatterbox exaggeration.
+    pub exag: f32,
+    /// Chatterbox CFG weight.
+    pub cfg: f32,
+    /// Chatterbox temperature.
+    pub temp: f32,
+    /// Format the narration was saved in: "wav", "mp3", or "opus".
+    pub codec: String,
+    /// When generation finished (Unix seconds).
+    pub generated_at: i64,
+}
+
+impl NarrationMeta {
+    /// Parse the `narration_meta` column; None if empty or unreadable.
+    pub fn from_json(json: Option<&str>) -> Option<Self> {
+        serde_json::from_str(json?).ok()
+    }
+
+    /// Serialize for the `narration_meta` column.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
 /// A book in the library.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -98,8 +168,46 @@ pub struct Book {
     pub source_path: String,
     pub narration_status: NarrationStatus,
     pub narration_path: Option<String>,
+    /// Total narration length in seconds, once narration has been generated.
+    pub duration: Option<f64>,
     pub created_at: i64,
     pub updated_at: i64,
     /// None if the book has never been opened (for "Recent" section).
     pub last_opened_at: Option<i64>,
+    /// Language of the text: the BCP 47 tag declared by an EPUB, or an
+    /// ISO 639-3 code when detected from the text. None if unknown.
+    pub language: Option<String>,
+    /// When reading first reached the end of the book; None if never finished.
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+    /// Publisher, date and identifiers from the source file.
+    #[serde(default)]
+    pub metadata: BookMetadata,
+    /// How the narration was generated; None for books narrated before
+    /// this was recorded or without narration.
+    #[serde(default)]
+    pub narration_meta: Option<NarrationMeta>,
+    /// Name of the file the book was imported from, before the library
+    /// renamed its copy to `<id>.<ext>`. None for books imported before
+    /// this was recorded.
+    #[serde(default)]
+    pub original_filename: Option<String>,
+}
+
+impl Book {
+    /// Default name for an exported file with the given extension: the
+    /// original filename's stem, else the title, else the book ID. Characters
+    /// not allowed in file names are replaced.
+    pub fn export_file_name(&self, extension: &str) -> String {
+        let stem = self
+            .original_filename
+            .as_deref()
+            .and_then(|name| std::path::Path::new(name).file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .filter(|stem| !stem.trim().is_empty())
+            .or_else(|| Some(self.title.clone()).filter(|title| !title.trim().is_empty()))
+            .unwrap_or_else(|| self.id.to_string());
+        let stem = stem.replace(|c: char| c.is_control() || "\\/:*?\"<>|".contains(c), "_");
+        format!("{}.{}", stem.trim(), extension)
+    }
 }
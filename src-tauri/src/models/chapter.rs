@@ -0,0 +1,19 @@
+//! Chapter model - a table-of-contents entry pointing into a book's segments.
+
+use serde::{Deserialize, Serialize};
+
+use super::BookId;
+
+/// A chapter from the book's table of contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    pub book_id: BookId,
+    pub title: String,
+    /// Index of the chapter's first segment (0-based).
+    pub start_segment_index: u32,
+    /// Position in the table of contents (0-based).
+    pub order: u32,
+    /// Nesting depth; 0 for top-level chapters.
+    pub level: u32,
+}
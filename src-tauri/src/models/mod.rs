@@ -3,13 +3,19 @@
 //! All types follow the exact definitions from SCHEMAS.md.
 
 mod book;
+mod chapter;
 mod marker;
+mod profile;
 mod progress;
+mod pronunciation;
 mod segment;
 mod voice;
 
-pub use book::{Book, BookId, NarrationStatus, SourceFormat};
+pub use book::{Book, BookId, BookMetadata, NarrationMeta, NarrationStatus, SourceFormat};
+pub use chapter::Chapter;
 pub use marker::Marker;
+pub use profile::Profile;
 pub use progress::Progress;
+pub use pronunciation::Pronunciation;
 pub use segment::{ImageData, ImagePosition, Segment, SegmentId, SegmentType};
-pub use voice::{Voice, VoiceId};
+pub use voice::{SampleInfo, Voice, VoiceId};
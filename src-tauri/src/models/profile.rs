@@ -0,0 +1,15 @@
+//! Profile model - a separate library within one install.
+
+use serde::{Deserialize, Serialize};
+
+/// A library of books, such as "Work" or "Personal".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    /// Unix timestamp; 0 for the default profile.
+    pub created_at: i64,
+    /// Number of books in the profile.
+    pub book_count: u32,
+}
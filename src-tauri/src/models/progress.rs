@@ -13,5 +13,12 @@ pub struct Progress {
     pub segment_index: u32,
     /// Position in narration (seconds), None if no narration.
     pub audio_time: Option<f64>,
+    /// Furthest segment index ever reached; never moves backward. Missing
+    /// from progress sent by older clients.
+    #[serde(default)]
+    pub max_segment_index: u32,
+    /// Furthest position in narration ever reached (seconds), None if no narration.
+    #[serde(default)]
+    pub max_audio_time: Option<f64>,
     pub updated_at: i64,
 }
@@ -0,0 +1,17 @@
+//! Pronunciation model - a replacement applied to text before narration.
+
+use serde::{Deserialize, Serialize};
+
+/// A pronunciation dictionary entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pronunciation {
+    /// Row ID; entries are applied in ascending ID order.
+    pub id: i64,
+    /// Text or regular expression to find.
+    pub pattern: String,
+    /// Text to speak instead.
+    pub replacement: String,
+    /// Whether `pattern` is a regular expression.
+    pub is_regex: bool,
+}
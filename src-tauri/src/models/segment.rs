@@ -26,12 +26,50 @@ impl std::fmt::Display for SegmentId {
     }
 }
 
-/// Type of segment - either text content or an image.
+/// Type of segment - text content, an image, or a structural block of text.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum SegmentType {
     Text,
     Image,
+    /// Heading, with `level` 1-6 as in `<h1>`-`<h6>`.
+    Heading { level: u8 },
+    /// Block quotation.
+    Quote,
+    /// Preformatted code block.
+    Code,
+}
+
+impl SegmentType {
+    /// Convert to database string representation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Image => "image",
+            Self::Heading { level: 1 } => "h1",
+            Self::Heading { level: 2 } => "h2",
+            Self::Heading { level: 3 } => "h3",
+            Self::Heading { level: 4 } => "h4",
+            Self::Heading { level: 5 } => "h5",
+            Self::Heading { .. } => "h6",
+            Self::Quote => "quote",
+            Self::Code => "code",
+        }
+    }
+
+    /// Parse from database string representation.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(Self::Text),
+            "image" => Some(Self::Image),
+            "quote" => Some(Self::Quote),
+            "code" => Some(Self::Code),
+            _ => match s.strip_prefix('h')?.parse() {
+                Ok(level @ 1..=6) => Some(Self::Heading { level }),
+                _ => None,
+            },
+        }
+    }
 }
 
 impl Default for SegmentType {
@@ -51,6 +89,31 @@ pub enum ImagePosition {
     Inline,
 }
 
+impl ImagePosition {
+    /// Convert to database string representation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Top => "top",
+            Self::Middle => "middle",
+            Self::Bottom => "bottom",
+            Self::FullPage => "full-page",
+            Self::Inline => "inline",
+        }
+    }
+
+    /// Parse from database string representation.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "top" => Some(Self::Top),
+            "middle" => Some(Self::Middle),
+            "bottom" => Some(Self::Bottom),
+            "full-page" => Some(Self::FullPage),
+            "inline" => Some(Self::Inline),
+            _ => None,
+        }
+    }
+}
+
 impl Default for ImagePosition {
     fn default() -> Self {
         Self::Middle
@@ -65,6 +128,8 @@ pub struct ImageData {
     pub source_path: String,
     /// AI-generated caption for narration.
     pub caption: Option<String>,
+    /// Prompt the caption was generated with.
+    pub caption_prompt: Option<String>,
     /// Original alt text from the source document.
     pub alt_text: Option<String>,
     /// Page number if available.
@@ -90,4 +155,12 @@ pub struct Segment {
     pub segment_type: SegmentType,
     /// Image data (only for image segments).
     pub image_data: Option<ImageData>,
+    /// False for segments shown in the reader but left out of narration,
+    /// such as page numbers.
+    #[serde(default = "default_narrate")]
+    pub narrate: bool,
+}
+
+fn default_narrate() -> bool {
+    true
 }
@@ -32,7 +32,23 @@ impl std::fmt::Display for VoiceId {
 pub struct Voice {
     pub id: VoiceId,
     pub name: String,
-    /// Path to voice sample for cloning.
+    /// Path to voice sample for cloning, always a WAV file.
     pub sample_path: String,
+    /// File name of the sample the user picked, before any conversion to WAV.
+    #[serde(default)]
+    pub original_filename: Option<String>,
     pub is_default: bool,
+    /// Probed format of the sample; only returned when the voice is created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_info: Option<SampleInfo>,
+}
+
+/// Format of a voice sample, read from its audio header.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleInfo {
+    /// Duration in seconds.
+    pub duration: f64,
+    pub sample_rate: u32,
+    pub channels: u16,
 }
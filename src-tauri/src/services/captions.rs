@@ -0,0 +1,219 @@
+//! Caption file formatting for narration timing.
+//!
+//! Turns segment timings into WebVTT or SubRip (SRT) subtitle files so the
+//! narration can be followed in external players.
+
+use serde::{Deserialize, Serialize};
+
+/// Subtitle format to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionFormat {
+    /// WebVTT (`.vtt`).
+    Vtt,
+    /// SubRip (`.srt`).
+    Srt,
+}
+
+/// A single timed caption.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// Start time in seconds.
+    pub start: f64,
+    /// End time in seconds.
+    pub end: f64,
+    /// Plain text shown during the cue.
+    pub text: String,
+}
+
+/// Split cues longer than `max_seconds` into shorter consecutive cues.
+///
+/// Text is broken at sentence ends where possible, then at word boundaries,
+/// and each piece gets a share of the cue's time proportional to its length.
+pub fn split_long_cues(cues: Vec<Cue>, max_seconds: f64) -> Vec<Cue> {
+    if max_seconds.is_nan() || max_seconds <= 0.0 {
+        return cues;
+    }
+
+    let mut result = Vec::with_capacity(cues.len());
+    for cue in cues {
+        let duration = cue.end - cue.start;
+        if duration <= max_seconds {
+            result.push(cue);
+            continue;
+        }
+
+        let pieces_wanted = (duration / max_seconds).ceil() as usize;
+        let pieces = split_text(&cue.text, pieces_wanted);
+        let total_chars: usize = pieces.iter().map(|p| p.chars().count()).sum::<usize>().max(1);
+
+        let mut start = cue.start;
+        let mut chars_so_far = 0;
+        for (i, piece) in pieces.iter().enumerate() {
+            chars_so_far += piece.chars().count();
+            let end = if i + 1 == pieces.len() {
+                cue.end
+            } else {
+                cue.start + duration * chars_so_far as f64 / total_chars as f64
+            };
+            result.push(Cue {
+                start,
+                end,
+                text: piece.clone(),
+            });
+            start = end;
+        }
+    }
+
+    result
+}
+
+/// Split text into about `pieces` parts of similar length.
+///
+/// Prefers breaking after sentence-ending punctuation; falls back to
+/// spaces. Never breaks inside a word.
+fn split_text(text: &str, pieces: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if pieces <= 1 || words.len() <= 1 {
+        return vec![words.join(" ")];
+    }
+
+    let target = text.chars().count() / pieces;
+    let mut result = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_len = 0;
+
+    for (i, word) in words.iter().enumerate() {
+        current.push(word);
+        current_len += word.chars().count() + 1;
+
+        let remaining_pieces = pieces - result.len();
+        let at_sentence_end = word.ends_with(['.', '!', '?', ';', ':']);
+        let long_enough = current_len >= target;
+        let too_long = current_len >= target * 3 / 2;
+        let is_last = i + 1 == words.len();
+
+        if !is_last && remaining_pieces > 1 && ((long_enough && at_sentence_end) || too_long) {
+            result.push(current.join(" "));
+            current.clear();
+            current_len = 0;
+        }
+    }
+
+    if !current.is_empty() {
+        result.push(current.join(" "));
+    }
+
+    result
+}
+
+/// Format a timestamp in seconds for the given format.
+///
+/// WebVTT uses `HH:MM:SS.mmm`; SRT uses `HH:MM:SS,mmm`.
+fn format_timestamp(seconds: f64, format: CaptionFormat) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, rest) = (millis / 3_600_000, millis % 3_600_000);
+    let (minutes, rest) = (rest / 60_000, rest % 60_000);
+    let (secs, millis) = (rest / 1000, rest % 1000);
+    let separator = match format {
+        CaptionFormat::Vtt => '.',
+        CaptionFormat::Srt => ',',
+    };
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, secs, separator, millis)
+}
+
+/// Make cue text safe for the given format.
+///
+/// Both formats end a cue at a blank line and reserve `-->`, so line breaks
+/// are collapsed and arrows broken up. WebVTT also treats `&` and `<` as
+/// markup.
+fn escape_text(text: &str, format: CaptionFormat) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.replace("-->", "->");
+    match format {
+        CaptionFormat::Vtt => text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+        CaptionFormat::Srt => text,
+    }
+}
+
+/// Render cues as a complete caption file.
+///
+/// Cues with no text are dropped.
+pub fn format_captions(cues: &[Cue], format: CaptionFormat) -> String {
+    let mut output = String::new();
+    if format == CaptionFormat::Vtt {
+        output.push_str("WEBVTT\n\n");
+    }
+
+    let cues = cues.iter().filter(|cue| !cue.text.trim().is_empty());
+    for (i, cue) in cues.enumerate() {
+        output.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_timestamp(cue.start, format),
+            format_timestamp(cue.end, format),
+            escape_text(&cue.text, format)
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f64, end: f64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0.0, CaptionFormat::Vtt), "00:00:00.000");
+        assert_eq!(format_timestamp(3661.5, CaptionFormat::Vtt), "01:01:01.500");
+        assert_eq!(format_timestamp(61.0456, CaptionFormat::Srt), "00:01:01,046");
+    }
+
+    #[test]
+    fn test_format_vtt() {
+        let cues = vec![cue(0.0, 1.5, "Fish & <chips>"), cue(1.5, 2.0, "  "), cue(2.0, 3.0, "A --> B")];
+        assert_eq!(
+            format_captions(&cues, CaptionFormat::Vtt),
+            "WEBVTT\n\n1\n00:00:00.000 --> 00:00:01.500\nFish &amp; &lt;chips&gt;\n\n\
+             2\n00:00:02.000 --> 00:00:03.000\nA -&gt; B\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_srt_collapses_blank_lines() {
+        let cues = vec![cue(0.0, 1.0, "First line\n\nsecond line")];
+        assert_eq!(
+            format_captions(&cues, CaptionFormat::Srt),
+            "1\n00:00:00,000 --> 00:00:01,000\nFirst line second line\n\n"
+        );
+    }
+
+    #[test]
+    fn test_split_long_cues() {
+        let long = cue(10.0, 22.0, "One sentence here. Another sentence there. And a third one.");
+        let split = split_long_cues(vec![cue(0.0, 2.0, "Short."), long], 5.0);
+
+        assert_eq!(split[0], cue(0.0, 2.0, "Short."));
+        let parts = &split[1..];
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].text, "One sentence here.");
+        assert_eq!(parts[0].start, 10.0);
+        assert_eq!(parts[2].end, 22.0);
+        assert!(parts.windows(2).all(|w| w[0].end == w[1].start));
+
+        let rejoined: Vec<&str> = parts.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(rejoined.join(" "), "One sentence here. Another sentence there. And a third one.");
+    }
+}
@@ -0,0 +1,113 @@
+//! ffmpeg process helpers.
+//!
+//! Audio encoding and muxing that the app doesn't do itself is handed to
+//! ffmpeg, which must be on the PATH.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::storage::NarrationCodec;
+
+/// MP3 bitrate for narration, in kbit/s.
+const MP3_KBPS: u32 = 64;
+
+/// Opus bitrate for narration, in kbit/s; transparent for speech at a
+/// fraction of MP3's size.
+const OPUS_KBPS: u32 = 32;
+
+/// Errors that can occur when running ffmpeg.
+#[derive(Debug, Error)]
+pub enum FfmpegError {
+    #[error("ffmpeg is required but was not found on the PATH")]
+    NotInstalled,
+
+    #[error("Failed to run ffmpeg: {0}")]
+    IoError(std::io::Error),
+
+    #[error("ffmpeg failed: {0}")]
+    Failed(String),
+}
+
+/// Run ffmpeg with the given arguments, overwriting any existing output.
+///
+/// On failure the last line of ffmpeg's error output is returned, which is
+/// usually the one that explains what went wrong.
+pub async fn run<I, S>(args: I) -> Result<(), FfmpegError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = tokio::process::Command::new("ffmpeg")
+        .arg("-y")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FfmpegError::NotInstalled,
+            _ => FfmpegError::IoError(e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let detail = stderr.lines().last().unwrap_or("unknown error");
+        return Err(FfmpegError::Failed(detail.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Encoder arguments for a narration codec.
+fn encoder_args(codec: NarrationCodec) -> Vec<String> {
+    let args = match codec {
+        NarrationCodec::Wav => vec!["-c:a", "pcm_s16le"],
+        NarrationCodec::Mp3 => vec!["-c:a", "libmp3lame"],
+        NarrationCodec::Opus => vec!["-c:a", "libopus", "-application", "voip"],
+    };
+    let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+
+    if let Some(kbps) = encoded_kbps(codec) {
+        args.extend(["-b:a".to_string(), format!("{}k", kbps)]);
+    }
+
+    args
+}
+
+/// Bitrate narration is encoded at, in kbit/s; None for uncompressed WAV.
+pub fn encoded_kbps(codec: NarrationCodec) -> Option<u32> {
+    match codec {
+        NarrationCodec::Wav => None,
+        NarrationCodec::Mp3 => Some(MP3_KBPS),
+        NarrationCodec::Opus => Some(OPUS_KBPS),
+    }
+}
+
+/// Encode a WAV file as narration audio in the given codec.
+pub async fn encode_narration(
+    input: &Path,
+    output: &Path,
+    codec: NarrationCodec,
+) -> Result<(), FfmpegError> {
+    let encoder_args = encoder_args(codec);
+    let mut args: Vec<&OsStr> = vec![OsStr::new("-i"), input.as_os_str()];
+    args.extend(encoder_args.iter().map(OsStr::new));
+    args.push(output.as_os_str());
+
+    run(args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoder_args() {
+        assert_eq!(encoder_args(NarrationCodec::Mp3), ["-c:a", "libmp3lame", "-b:a", "64k"]);
+        assert_eq!(
+            encoder_args(NarrationCodec::Opus),
+            ["-c:a", "libopus", "-application", "voip", "-b:a", "32k"]
+        );
+        assert_eq!(encoder_args(NarrationCodec::Wav), ["-c:a", "pcm_s16le"]);
+    }
+}
@@ -1,10 +1,18 @@
 //! Backend services for Actual Reader.
 //!
 //! This module contains the core business logic services:
-//! - `parser` - Document parsing (EPUB, Markdown, TXT)
+//! - `captions` - WebVTT/SRT formatting of narration timing
+//! - `ffmpeg` - Audio encoding and muxing with ffmpeg
+//! - `parser` - Document parsing (EPUB, HTML, Markdown, TXT)
+//! - `pronunciation` - Text replacements applied before narration
+//! - `tls` - Self-signed certificates and pinning for sync
 //! - `tts` - Text-to-speech generation using Chatterbox
 //! - `vision` - Image captioning using Qwen2.5-VL
 
+pub mod captions;
+pub mod ffmpeg;
 pub mod parser;
+pub mod pronunciation;
+pub mod tls;
 pub mod tts;
 pub mod vision;
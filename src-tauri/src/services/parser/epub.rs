@@ -2,16 +2,44 @@
 //!
 //! Parses EPUB files and extracts text content into segments.
 
-use std::path::Path;
-use epub::doc::EpubDoc;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use encoding_rs::{Encoding, UTF_8};
+use epub::doc::{EpubDoc, NavPoint};
+use percent_encoding::percent_decode_str;
 
-use super::{ParseError, ParsedBook, Segment};
+use super::{
+    detect_language, empty_chapter_warnings, BookMetadata, Chapter, ParseError, ParseWarning,
+    ParseWarningKind, ParsedBook, ParsedImage, Segment,
+};
+use crate::models::SegmentType;
+
+/// A spine document and where its segments begin.
+struct SpineDocument {
+    /// Resource path within the EPUB, as used by the table of contents
+    path: PathBuf,
+    /// Index of the document's first segment
+    first_segment: u32,
+    /// Raw XHTML content
+    content: String,
+}
 
 /// Parse an EPUB file into a ParsedBook.
 ///
-/// Extracts title and author from EPUB metadata, then iterates through
+/// Extracts title, author, language and publication details (see
+/// [`read_metadata`]) from EPUB metadata, then iterates through
 /// the spine (reading order) to extract text content from each chapter.
 /// Content is split into segments at paragraph and heading boundaries.
+/// Chapters are read from the table of contents (NCX or nav document).
+/// Documents are decoded as [`decode_document`] describes. Spine documents
+/// that can't be read or decoded cleanly, table-of-contents entries that
+/// point outside the spine and chapters without text are reported as
+/// warnings.
+///
+/// Spine entries that point into the same file (`text.xhtml#ch2`) are
+/// split at their anchors by [`spine_documents`], so each part of the file
+/// is read once. Images are read from the archive as [`load_images`]
+/// describes.
 ///
 /// # Arguments
 /// * `path` - Path to the EPUB file
@@ -39,36 +67,524 @@ pub fn parse_epub(path: &Path) -> Result<ParsedBook, ParseError> {
         .or_else(|| doc.mdata("author"))
         .map(|item| item.value.clone());
 
+    // Language declared in dc:language, if any
+    let declared_language = doc
+        .mdata("language")
+        .map(|item| item.value.trim().to_string())
+        .filter(|language| !language.is_empty());
+
+    let metadata = read_metadata(&doc);
+
     // Extract content from all spine items (chapters in reading order)
     let mut segments = Vec::new();
     let mut segment_index: u32 = 0;
+    let mut documents = Vec::new();
+    let mut warnings = Vec::new();
 
-    let num_chapters = doc.get_num_chapters();
+    let spine: Vec<PathBuf> = (0..doc.get_num_chapters())
+        .filter_map(|chapter_num| {
+            doc.set_current_chapter(chapter_num);
+            doc.get_current_path()
+        })
+        .collect();
 
-    for chapter_num in 0..num_chapters {
-        doc.set_current_chapter(chapter_num);
+    // Manifest paths may not match the case of the archive entry
+    let entries = ArchiveEntries::read(path);
+    let read = |file: &Path| {
+        let Some(bytes) = read_resource(&mut doc, &entries, file) else {
+            warnings.push(ParseWarning::new(
+                ParseWarningKind::SkippedContent,
+                format!("Could not read {}; it was left out", file.display()),
+            ));
+            return None;
+        };
 
-        // get_current_str returns Option<(content_string, mime_type)>
-        if let Some((content, _mime)) = doc.get_current_str() {
-            // Parse HTML content and extract text segments
-            let chapter_segments = extract_segments_from_html(&content, &mut segment_index);
-            segments.extend(chapter_segments);
+        let (content, malformed) = decode_document(&bytes);
+        if let Some(encoding) = malformed {
+            warnings.push(ParseWarning::new(
+                ParseWarningKind::EncodingFallback,
+                format!(
+                    "{} is not valid {}; characters that couldn't be decoded were replaced",
+                    file.display(),
+                    encoding
+                ),
+            ));
         }
+        Some(content)
+    };
+
+    let mut images = Vec::new();
+    for (path, content) in spine_documents(&spine, read) {
+        let first_segment = segment_index;
+
+        // Parse HTML content and extract text segments
+        let mut chapter_segments = extract_segments_from_html(&content, &mut segment_index);
+        images.extend(load_images(
+            &mut chapter_segments,
+            |src| {
+                let file = resolve_resource_path(&path, src)?;
+                let data = read_resource(&mut doc, &entries, &file)?;
+                Some((file, data))
+            },
+            &mut warnings,
+        ));
+        segments.extend(chapter_segments);
+
+        documents.push(SpineDocument {
+            path,
+            first_segment,
+            content,
+        });
     }
 
+    let mut chapters = Vec::new();
+    collect_chapters(&doc.toc, &documents, segment_index, 0, &mut chapters, &mut warnings);
+    warnings.extend(empty_chapter_warnings(&chapters, &segments));
+
+    let language = declared_language
+        .or_else(|| detect_language(segments.iter().map(|s| s.content.as_str())));
+
     Ok(ParsedBook {
         title,
         author,
         segments,
+        chapters,
+        language,
+        metadata,
+        warnings,
+        images,
+    })
+}
+
+/// Read a resource from the EPUB, matching its path case-insensitively if
+/// there is no exact match.
+fn read_resource<R: std::io::Read + std::io::Seek>(
+    doc: &mut EpubDoc<R>,
+    entries: &ArchiveEntries,
+    file: &Path,
+) -> Option<Vec<u8>> {
+    doc.get_resource_by_path(file)
+        .or_else(|| doc.get_resource_by_path(entries.find(file)?))
+}
+
+/// Read the file of each image segment with `load`, which is given the
+/// reference to the image (see [`image_reference`]) and returns the file's
+/// path and content.
+///
+/// An image that can't be read is reported as a warning, and its segment
+/// is kept as text holding the alt text, so segment indices don't shift.
+pub(super) fn load_images(
+    segments: &mut [Segment],
+    mut load: impl FnMut(&str) -> Option<(PathBuf, Vec<u8>)>,
+    warnings: &mut Vec<ParseWarning>,
+) -> Vec<ParsedImage> {
+    let mut images = Vec::new();
+    for segment in segments.iter_mut().filter(|s| s.segment_type == SegmentType::Image) {
+        let src = segment.html.as_deref().and_then(image_reference).unwrap_or_default();
+        match load(&src) {
+            Some((file, data)) => images.push(ParsedImage::new(&segment.id, &file, data)),
+            None => {
+                segment.segment_type = SegmentType::Text;
+                warnings.push(ParseWarning::new(
+                    ParseWarningKind::SkippedContent,
+                    format!("Could not read image \"{}\"; it was left out", src),
+                ));
+            }
+        }
+    }
+    images
+}
+
+/// Bytes at the start of a document searched for an encoding declaration.
+const ENCODING_SNIFF_BYTES: usize = 1024;
+
+/// Decode an XHTML document to text.
+///
+/// A byte order mark decides the encoding, then the encoding declared in the
+/// XML prolog or a `<meta charset>`, then UTF-8. Bytes that aren't valid in
+/// that encoding are replaced with U+FFFD, and the encoding's name is
+/// returned so the caller can warn about it.
+pub(super) fn decode_document(bytes: &[u8]) -> (String, Option<&'static str>) {
+    let (encoding, bom_length) = Encoding::for_bom(bytes)
+        .unwrap_or_else(|| (declared_encoding(bytes).unwrap_or(UTF_8), 0));
+    let bytes = &bytes[bom_length..];
+
+    match encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => (text.into_owned(), None),
+        None => {
+            let (text, _) = encoding.decode_without_bom_handling(bytes);
+            (text.into_owned(), Some(encoding.name()))
+        }
+    }
+}
+
+/// Encoding named by the XML prolog's `encoding` or by a `charset` in the
+/// document's head.
+///
+/// Only ASCII-compatible documents can be read this far, so a declared
+/// UTF-16 is taken as UTF-8, as browsers do.
+fn declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    // Declarations are ASCII, so a lossy view is enough to find them
+    let head = &bytes[..bytes.len().min(ENCODING_SNIFF_BYTES)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+
+    let prolog = head
+        .trim_start()
+        .strip_prefix("<?xml")
+        .and_then(|prolog| prolog.split_once("?>"))
+        .map(|(prolog, _)| prolog);
+    let label = prolog
+        .and_then(|prolog| attribute_value(prolog, "encoding"))
+        .or_else(|| attribute_value(&head, "charset"))?;
+
+    Encoding::for_label(label.as_bytes()).map(Encoding::output_encoding)
+}
+
+/// Value of the first `name=value` pair in `text`, quoted or not.
+fn attribute_value<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = text;
+    while let Some(at) = rest.find(name) {
+        rest = &rest[at + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start().trim_start_matches(['"', '\'']);
+        let end = value
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ';' | '>' | '?'))
+            .unwrap_or(value.len());
+        return Some(&value[..end]).filter(|value| !value.is_empty());
+    }
+    None
+}
+
+/// Read publisher, date and identifiers from the package metadata.
+///
+/// The identifier is the one the package names as unique, or else the
+/// first `dc:identifier`. The ISBN is taken from whichever identifier holds
+/// one. Anything missing or blank stays None.
+fn read_metadata<R: std::io::Read + std::io::Seek>(doc: &EpubDoc<R>) -> BookMetadata {
+    let value = |property: &str| {
+        doc.mdata(property)
+            .map(|item| item.value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let identifiers: Vec<&str> = doc
+        .metadata
+        .iter()
+        .filter(|item| item.property == "identifier")
+        .map(|item| item.value.trim())
+        .filter(|value| !value.is_empty())
+        .collect();
+
+    let identifier = doc
+        .unique_identifier
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .or_else(|| identifiers.first().copied())
+        .map(str::to_string);
+
+    BookMetadata {
+        publisher: value("publisher"),
+        published_date: value("date"),
+        isbn: identifier
+            .iter()
+            .map(String::as_str)
+            .chain(identifiers.iter().copied())
+            .find_map(parse_isbn),
+        identifier,
+    }
+}
+
+/// Extract an ISBN from an identifier such as `urn:isbn:978-0-14-143951-8`,
+/// `ISBN 0141439513` or a bare number.
+///
+/// Returns the digits (and a final `X` for ISBN-10) if the check digit is
+/// valid, so UUIDs and other numeric ids aren't mistaken for one.
+fn parse_isbn(identifier: &str) -> Option<String> {
+    let lower = identifier.trim().to_ascii_lowercase();
+    let number = lower
+        .strip_prefix("urn:isbn:")
+        .or_else(|| lower.strip_prefix("isbn:"))
+        .or_else(|| lower.strip_prefix("isbn"))
+        .unwrap_or(&lower)
+        .trim();
+    if number.chars().any(|c| !(c.is_ascii_digit() || matches!(c, '-' | ' ' | 'x'))) {
+        return None;
+    }
+
+    let digits: String = number
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let values: Vec<u32> = digits
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'X' if i == 9 && digits.len() == 10 => Some(10),
+            c => c.to_digit(10),
+        })
+        .collect::<Option<_>>()?;
+
+    let valid = match values.len() {
+        10 => values.iter().enumerate().map(|(i, v)| (10 - i as u32) * v).sum::<u32>() % 11 == 0,
+        13 => {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| if i % 2 == 0 { *v } else { 3 * v })
+                .sum::<u32>()
+                % 10
+                == 0
+        }
+        _ => false,
+    };
+
+    valid.then_some(digits)
+}
+
+/// Resolve a resource reference (an `href` or `src`) in the document at
+/// `document` to its path within the EPUB.
+///
+/// The reference is percent-decoded and taken relative to the document's
+/// directory, or the archive root if it starts with `/`, with `.` and `..`
+/// collapsed. Any `#fragment` or `?query` is dropped. Returns None for
+/// external URLs, `data:` URIs, bare fragments, and references that climb
+/// out of the archive.
+pub(super) fn resolve_resource_path(document: &Path, href: &str) -> Option<PathBuf> {
+    let href = href.trim();
+    let href = &href[..href.find(['#', '?']).unwrap_or(href.len())];
+    if href.is_empty() || has_url_scheme(href) {
+        return None;
+    }
+    let decoded = percent_decode_str(href).decode_utf8().ok()?;
+
+    let document = document.to_string_lossy().replace('\\', "/");
+    let dir = document.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let joined = match decoded.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("{}/{}", dir, decoded),
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    (!parts.is_empty()).then(|| parts.iter().collect())
+}
+
+/// Normalize a path within the EPUB as read from the manifest or table of
+/// contents, as in [`resolve_resource_path`].
+fn normalize_resource_path(path: &Path) -> Option<PathBuf> {
+    resolve_resource_path(Path::new(""), &path.to_string_lossy())
+}
+
+/// Whether a reference starts with a URL scheme (`http:`, `data:`), so it
+/// points outside the EPUB.
+pub(super) fn has_url_scheme(href: &str) -> bool {
+    href.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
     })
 }
 
+/// Paths of the entries in an EPUB archive, by lowercase path, for finding
+/// resources referenced with the wrong case.
+struct ArchiveEntries(HashMap<String, PathBuf>);
+
+impl ArchiveEntries {
+    /// List the entries of the EPUB at `path`; empty if it can't be read.
+    fn read(path: &Path) -> Self {
+        let names = std::fs::File::open(path)
+            .map_err(zip::result::ZipError::from)
+            .and_then(zip::ZipArchive::new)
+            .map(|archive| archive.file_names().map(PathBuf::from).collect::<Vec<_>>())
+            .unwrap_or_default();
+        Self(
+            names
+                .into_iter()
+                .map(|name| (name.to_string_lossy().to_lowercase(), name))
+                .collect(),
+        )
+    }
+
+    /// The entry matching `path` if the case is ignored.
+    fn find(&self, path: &Path) -> Option<&Path> {
+        self.0
+            .get(&path.to_string_lossy().to_lowercase())
+            .map(PathBuf::as_path)
+    }
+}
+
+/// Split a spine path into its file and `#fragment`, if any.
+fn split_fragment(path: &Path) -> (PathBuf, Option<String>) {
+    let path_str = path.to_string_lossy();
+    match path_str.split_once('#') {
+        Some((file, fragment)) => (PathBuf::from(file), Some(fragment.to_string())),
+        None => (path.to_path_buf(), None),
+    }
+}
+
+/// Resolve spine entries to the documents to parse, in reading order.
+///
+/// Each file is read once with `read`, by its path as normalized by
+/// [`normalize_resource_path`]. When several spine entries point
+/// into the same file, the file is cut at their anchors: the first entry
+/// gets everything up to the next entry's anchor, and so on. Entries that
+/// would repeat content already taken (a second entry for the same file
+/// with no anchor, or an anchor that can't be found further along) are
+/// dropped.
+fn spine_documents(
+    spine: &[PathBuf],
+    mut read: impl FnMut(&Path) -> Option<String>,
+) -> Vec<(PathBuf, String)> {
+    // Spine positions pointing into each file, in reading order
+    let mut files: Vec<(PathBuf, Vec<(usize, Option<String>)>)> = Vec::new();
+    let mut file_index: HashMap<PathBuf, usize> = HashMap::new();
+    for (position, entry) in spine.iter().enumerate() {
+        let (file, fragment) = split_fragment(entry);
+        let file = normalize_resource_path(&file).unwrap_or(file);
+        let index = *file_index.entry(file.clone()).or_insert_with(|| {
+            files.push((file, Vec::new()));
+            files.len() - 1
+        });
+        files[index].1.push((position, fragment));
+    }
+
+    let mut parts: BTreeMap<usize, (PathBuf, String)> = BTreeMap::new();
+    for (file, entries) in files {
+        let Some(content) = read(&file) else {
+            continue;
+        };
+
+        let mut starts: Vec<(usize, usize)> = Vec::new();
+        for (position, fragment) in entries {
+            let start = match starts.last() {
+                None => 0,
+                Some(&(_, previous)) => match fragment.and_then(|f| anchor_offset(&content, &f)) {
+                    Some(offset) if offset > previous => offset,
+                    _ => continue,
+                },
+            };
+            starts.push((position, start));
+        }
+
+        for (i, &(position, start)) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(content.len(), |&(_, next)| next);
+            parts.insert(position, (file.clone(), content[start..end].to_string()));
+        }
+    }
+
+    parts.into_values().collect()
+}
+
+/// Flatten table-of-contents entries into chapters, depth first.
+///
+/// Entries that point outside the spine or past the last segment are
+/// skipped, with a warning; their children are still included.
+fn collect_chapters(
+    toc: &[NavPoint],
+    documents: &[SpineDocument],
+    segment_count: u32,
+    level: u32,
+    chapters: &mut Vec<Chapter>,
+    warnings: &mut Vec<ParseWarning>,
+) {
+    for point in toc {
+        let title = point.label.split_whitespace().collect::<Vec<_>>().join(" ");
+        let start_index = resolve_toc_target(&point.content, documents);
+
+        match start_index.filter(|&i| i < segment_count) {
+            Some(_) if title.is_empty() => {}
+            Some(start_index) => chapters.push(Chapter {
+                title,
+                start_index,
+                level,
+            }),
+            None => warnings.push(ParseWarning::new(
+                ParseWarningKind::SkippedContent,
+                format!(
+                    "Table of contents entry \"{}\" points at {}, which has no text in the book",
+                    title,
+                    point.content.display()
+                ),
+            )),
+        }
+
+        collect_chapters(&point.children, documents, segment_count, level + 1, chapters, warnings);
+    }
+}
+
+/// Find the first segment at a table-of-contents target (`file.xhtml#anchor`).
+///
+/// Without an anchor, or if the anchor can't be found, this is the first
+/// segment of the document.
+fn resolve_toc_target(target: &Path, documents: &[SpineDocument]) -> Option<u32> {
+    let target = target.to_string_lossy();
+    let (file, fragment) = match target.split_once('#') {
+        Some((file, fragment)) => (file, Some(fragment)),
+        None => (target.as_ref(), None),
+    };
+
+    // A file split into several parts has the anchor in one of them
+    let file = normalize_resource_path(Path::new(file)).unwrap_or_else(|| PathBuf::from(file));
+    let file = file.to_string_lossy();
+    let mut parts = documents
+        .iter()
+        .filter(|d| d.path.to_string_lossy().eq_ignore_ascii_case(&file))
+        .peekable();
+    let first = *parts.peek()?;
+    let found = fragment.and_then(|fragment| {
+        parts.find_map(|document| {
+            segments_before_anchor(&document.content, fragment)
+                .map(|offset| document.first_segment + offset)
+        })
+    });
+
+    Some(found.unwrap_or(first.first_segment))
+}
+
+/// Byte offset of the start of the tag carrying the given id.
+fn anchor_offset(html: &str, id: &str) -> Option<usize> {
+    let anchor = [format!("id=\"{}\"", id), format!("id='{}'", id)]
+        .iter()
+        .filter_map(|pattern| html.find(pattern.as_str()))
+        .min()?;
+
+    Some(html[..anchor].rfind('<').unwrap_or(anchor))
+}
+
+/// Count the segments that come before the element with the given id.
+fn segments_before_anchor(html: &str, id: &str) -> Option<u32> {
+    // Cut at the start of the tag carrying the id, so its element isn't counted
+    let tag_start = anchor_offset(html, id)?;
+
+    let mut count = 0;
+    extract_segments_from_html(&html[..tag_start], &mut count);
+    Some(count)
+}
+
 /// Extract segments from HTML content.
 ///
-/// Parses the HTML and creates a segment for each paragraph (`<p>`) or
-/// heading (`<h1>` - `<h6>`) element. Preserves the original HTML in
-/// the segment's html field.
-fn extract_segments_from_html(html: &str, start_index: &mut u32) -> Vec<Segment> {
+/// Parses the HTML and creates a segment for each paragraph (`<p>`),
+/// heading (`<h1>` - `<h6>`), block quote (`<blockquote>`) or preformatted
+/// (`<pre>`) element. Preserves the original HTML in the segment's html
+/// field, which also sets the segment's type.
+///
+/// Each image (see [`IMAGE_TAGS`]) outside those elements, or in one with no
+/// text, becomes an image segment holding its alt text. Images within text are left in its
+/// HTML.
+pub(super) fn extract_segments_from_html(html: &str, start_index: &mut u32) -> Vec<Segment> {
     let mut segments = Vec::new();
 
     // Simple HTML parsing - extract text between paragraph and heading tags
@@ -77,11 +593,11 @@ fn extract_segments_from_html(html: &str, start_index: &mut u32) -> Vec<Segment>
     let mut remaining = html;
 
     while !remaining.is_empty() {
-        // Find the next segment-worthy element (p, h1-h6)
+        // Find the next segment-worthy element (p, h1-h6, blockquote, pre)
         if let Some(segment_result) = find_next_segment(remaining) {
             let (text_content, html_content, rest) = segment_result;
 
-            // Skip empty segments
+            // Empty segments are skipped, unless they hold images
             let trimmed = text_content.trim();
             if !trimmed.is_empty() {
                 segments.push(Segment::new(
@@ -90,6 +606,11 @@ fn extract_segments_from_html(html: &str, start_index: &mut u32) -> Vec<Segment>
                     Some(html_content),
                 ));
                 *start_index += 1;
+            } else {
+                for image in image_elements(&html_content) {
+                    segments.push(image_segment(image, *start_index));
+                    *start_index += 1;
+                }
             }
 
             remaining = rest;
@@ -101,18 +622,21 @@ fn extract_segments_from_html(html: &str, start_index: &mut u32) -> Vec<Segment>
     segments
 }
 
-/// Find the next paragraph or heading element in HTML.
+/// Find the next paragraph, heading, block quote, preformatted or image
+/// element in HTML.
 ///
 /// Returns (plain_text, html_element, remaining_html) or None if no more elements.
-fn find_next_segment(html: &str) -> Option<(String, String, &str)> {
+/// An image has no plain text.
+pub(super) fn find_next_segment(html: &str) -> Option<(String, String, &str)> {
     // Tags that represent segments
-    let segment_tags = ["p", "h1", "h2", "h3", "h4", "h5", "h6"];
+    let segment_tags = [
+        "p", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "pre", "img", "image",
+    ];
 
     let mut earliest_match: Option<(usize, &str)> = None;
 
     for tag in &segment_tags {
-        let open_tag = format!("<{}", tag);
-        if let Some(pos) = html.find(&open_tag) {
+        if let Some(pos) = find_open_tag(html, tag) {
             if earliest_match.is_none() || pos < earliest_match.unwrap().0 {
                 earliest_match = Some((pos, tag));
             }
@@ -125,6 +649,13 @@ fn find_next_segment(html: &str) -> Option<(String, String, &str)> {
     let after_open = &html[start_pos..];
     let tag_end = after_open.find('>')?;
 
+    // An image is a void element, with no closing tag
+    if IMAGE_TAGS.contains(&tag) {
+        let element_end = start_pos + tag_end + 1;
+        let element = html[start_pos..element_end].to_string();
+        return Some((String::new(), element, &html[element_end..]));
+    }
+
     // Find closing tag
     let close_tag = format!("</{}>", tag);
     let content_start = start_pos + tag_end + 1;
@@ -145,8 +676,91 @@ fn find_next_segment(html: &str) -> Option<(String, String, &str)> {
     Some((plain_text, full_html, &html[full_element_end..]))
 }
 
+/// The image elements in HTML, in order.
+fn image_elements(html: &str) -> Vec<&str> {
+    let mut images = Vec::new();
+    let mut remaining = html;
+    let next_image = |html: &str| {
+        IMAGE_TAGS.iter().filter_map(|tag| find_open_tag(html, tag)).min()
+    };
+    while let Some(start) = next_image(remaining) {
+        let Some(end) = remaining[start..].find('>').map(|end| start + end + 1) else {
+            break;
+        };
+        images.push(&remaining[start..end]);
+        remaining = &remaining[end..];
+    }
+    images
+}
+
+/// Segment for an image element, holding its alt text.
+fn image_segment(element: &str, index: u32) -> Segment {
+    let alt = attribute(element, "alt")
+        .map(|alt| strip_html_tags(&alt))
+        .unwrap_or_default();
+    Segment::new(index, alt, Some(element.to_string()))
+}
+
+/// Elements that are images: HTML `<img>`, and SVG `<image>` as EPUB cover
+/// pages use.
+const IMAGE_TAGS: [&str; 2] = ["img", "image"];
+
+/// Where an image element's file is: the `src` of an `<img>`, or the
+/// `xlink:href` or `href` of an SVG `<image>`.
+fn image_reference(element: &str) -> Option<String> {
+    attribute(element, "src")
+        .or_else(|| attribute(element, "xlink:href"))
+        .or_else(|| attribute(element, "href"))
+}
+
+/// Position of the first opening `tag` element, so `<p` doesn't match `<pre>`.
+fn find_open_tag(html: &str, tag: &str) -> Option<usize> {
+    let open_tag = format!("<{}", tag);
+    let mut from = 0;
+    while let Some(pos) = html[from..].find(&open_tag) {
+        let start = from + pos;
+        let after = html[start + open_tag.len()..].chars().next();
+        if matches!(after, Some('>' | '/' | ' ' | '\t' | '\n' | '\r')) {
+            return Some(start);
+        }
+        from = start + open_tag.len();
+    }
+    None
+}
+
+/// Value of an attribute within a single tag, quoted or not.
+pub(super) fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+
+    loop {
+        let found = search + lower[search..].find(name)?;
+        search = found + name.len();
+
+        // Must be a whole attribute name followed by '='
+        let preceded_by_space = lower[..found].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[search..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        return Some(match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or("").to_string(),
+            // A path may contain slashes, so only a trailing one is taken off
+            _ => value
+                .split(|c: char| c.is_ascii_whitespace() || c == '>')
+                .next()
+                .unwrap_or("")
+                .trim_end_matches('/')
+                .to_string(),
+        });
+    }
+}
+
 /// Strip HTML tags from a string, returning plain text.
-fn strip_html_tags(html: &str) -> String {
+pub(super) fn strip_html_tags(html: &str) -> String {
     let mut result = String::with_capacity(html.len());
     let mut in_tag = false;
     let mut chars = html.chars().peekable();
@@ -235,6 +849,488 @@ mod tests {
         assert_eq!(element2, "<p>Second paragraph</p>");
     }
 
+    #[test]
+    fn test_segments_before_anchor() {
+        let html = r#"<h1>Part One</h1><p>Intro.</p><h2 id="ch2">Two</h2><p>Text.</p><section id='s3'><p>Three.</p></section>"#;
+        assert_eq!(segments_before_anchor(html, "ch2"), Some(2));
+        assert_eq!(segments_before_anchor(html, "s3"), Some(4));
+        assert_eq!(segments_before_anchor(html, "missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_toc_target() {
+        let documents = vec![
+            SpineDocument {
+                path: PathBuf::from("OEBPS/one.xhtml"),
+                first_segment: 0,
+                content: "<p>A</p><p>B</p>".to_string(),
+            },
+            SpineDocument {
+                path: PathBuf::from("OEBPS/two.xhtml"),
+                first_segment: 2,
+                content: r#"<p>C</p><h2 id="later">D</h2>"#.to_string(),
+            },
+        ];
+
+        assert_eq!(resolve_toc_target(Path::new("OEBPS/one.xhtml"), &documents), Some(0));
+        assert_eq!(resolve_toc_target(Path::new("OEBPS/two.xhtml#later"), &documents), Some(3));
+        assert_eq!(resolve_toc_target(Path::new("OEBPS/two.xhtml#nope"), &documents), Some(2));
+        assert_eq!(resolve_toc_target(Path::new("OEBPS/three.xhtml"), &documents), None);
+    }
+
+    #[test]
+    fn test_spine_documents_splits_shared_file() {
+        let text = r#"<h1 id="ch1">One</h1><p>A.</p><h1 id="ch2">Two</h1><p>B.</p>"#;
+        let spine = vec![
+            PathBuf::from("OEBPS/text.xhtml#ch1"),
+            PathBuf::from("OEBPS/other.xhtml"),
+            PathBuf::from("OEBPS/text.xhtml#ch2"),
+            PathBuf::from("OEBPS/other.xhtml"),
+            PathBuf::from("OEBPS/text.xhtml#missing"),
+        ];
+        let mut reads = Vec::new();
+        let documents = spine_documents(&spine, |file| {
+            reads.push(file.to_path_buf());
+            match file.to_str() {
+                Some("OEBPS/text.xhtml") => Some(text.to_string()),
+                Some("OEBPS/other.xhtml") => Some("<p>Other.</p>".to_string()),
+                _ => None,
+            }
+        });
+
+        let parts: Vec<(&str, &str)> = documents
+            .iter()
+            .map(|(path, content)| (path.to_str().unwrap(), content.as_str()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("OEBPS/text.xhtml", r#"<h1 id="ch1">One</h1><p>A.</p>"#),
+                ("OEBPS/other.xhtml", "<p>Other.</p>"),
+                ("OEBPS/text.xhtml", r#"<h1 id="ch2">Two</h1><p>B.</p>"#),
+            ]
+        );
+        assert_eq!(reads.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_isbn() {
+        assert_eq!(parse_isbn("urn:isbn:978-0-14-143951-8").as_deref(), Some("9780141439518"));
+        assert_eq!(parse_isbn("ISBN 0-14-143951-3").as_deref(), Some("0141439513"));
+        assert_eq!(parse_isbn("080442957x").as_deref(), Some("080442957X"));
+        assert_eq!(parse_isbn("9780141439519"), None);
+        assert_eq!(parse_isbn("urn:uuid:1b4e28ba-2fa1-11d2-883f-0016d3cca427"), None);
+        assert_eq!(parse_isbn("12345"), None);
+    }
+
+    #[test]
+    fn test_resolve_resource_path() {
+        let document = Path::new("OEBPS/Text/chapter1.xhtml");
+        let resolve = |href| resolve_resource_path(document, href);
+
+        assert_eq!(resolve("Chapter%20Two.xhtml"), Some("OEBPS/Text/Chapter Two.xhtml".into()));
+        assert_eq!(resolve("../Images/plate%201.png"), Some("OEBPS/Images/plate 1.png".into()));
+        assert_eq!(resolve("./../Images/./caf%C3%A9.jpg"), Some("OEBPS/Images/café.jpg".into()));
+        assert_eq!(resolve("notes.xhtml#note-3"), Some("OEBPS/Text/notes.xhtml".into()));
+        assert_eq!(resolve("image.svg?v=2#frag"), Some("OEBPS/Text/image.svg".into()));
+        assert_eq!(resolve("/OEBPS/cover.jpg"), Some("OEBPS/cover.jpg".into()));
+
+        assert_eq!(resolve("#note-3"), None);
+        assert_eq!(resolve("../../../escape.png"), None);
+        assert_eq!(resolve("https://example.com/a.png"), None);
+        assert_eq!(resolve("data:image/png;base64,AAAA"), None);
+        assert_eq!(resolve("bad%FF.png"), None);
+
+        assert_eq!(
+            normalize_resource_path(Path::new("OEBPS/../OEBPS/text.xhtml")),
+            Some("OEBPS/text.xhtml".into())
+        );
+    }
+
+    /// Write an EPUB with the given files besides the mimetype and the
+    /// container pointing at `OEBPS/content.opf`.
+    fn write_epub<C: AsRef<[u8]>>(path: &Path, files: &[(&str, C)]) {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let container = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+        let standard = [("mimetype", "application/epub+zip"), ("META-INF/container.xml", container)];
+        for (name, content) in standard {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_ref()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_parse_epub_with_fragment_spine() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fragments.epub");
+        let files = [
+            (
+                "OEBPS/content.opf",
+                r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Fragments</dc:title>
+    <dc:identifier id="id">fragments</dc:identifier>
+    <dc:identifier>urn:isbn:978-0-14-143951-8</dc:identifier>
+    <dc:publisher>Penguin Classics</dc:publisher>
+    <dc:date>2003-04-29</dc:date>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="ch1" href="text.xhtml#ch1" media-type="application/xhtml+xml"/>
+    <item id="ch2" href="text.xhtml#ch2" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="ch1"/>
+    <itemref idref="ch2"/>
+  </spine>
+</package>"#,
+            ),
+            (
+                "OEBPS/toc.ncx",
+                r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel><content src="text.xhtml#ch1"/></navPoint>
+    <navPoint id="n2" playOrder="2"><navLabel><text>Two</text></navLabel><content src="text.xhtml#ch2"/></navPoint>
+  </navMap>
+</ncx>"#,
+            ),
+            (
+                "OEBPS/text.xhtml",
+                r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<h1 id="ch1">One</h1><p>First chapter.</p>
+<h1 id="ch2">Two</h1><p>Second chapter.</p>
+</body></html>"#,
+            ),
+        ];
+        write_epub(&path, &files);
+
+        let book = parse_epub(&path).unwrap();
+        let contents: Vec<&str> = book.segments.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["One", "First chapter.", "Two", "Second chapter."]);
+
+        let chapters: Vec<(&str, u32)> =
+            book.chapters.iter().map(|c| (c.title.as_str(), c.start_index)).collect();
+        assert_eq!(chapters, vec![("One", 0), ("Two", 2)]);
+
+        assert_eq!(
+            book.metadata,
+            BookMetadata {
+                publisher: Some("Penguin Classics".to_string()),
+                published_date: Some("2003-04-29".to_string()),
+                identifier: Some("fragments".to_string()),
+                isbn: Some("9780141439518".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_epub_with_encoded_and_miscased_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paths.epub");
+        write_epub(
+            &path,
+            &[
+                (
+                    "OEBPS/content.opf",
+                    r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Paths</dc:title>
+    <dc:identifier id="id">paths</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="one" href="Text/Chapter%20One.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="text/chapter-two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx"><itemref idref="one"/><itemref idref="two"/></spine>
+</package>"#,
+                ),
+                (
+                    "OEBPS/toc.ncx",
+                    r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel><content src="Text/Chapter%20One.xhtml#later"/></navPoint>
+    <navPoint id="n2" playOrder="2"><navLabel><text>Two</text></navLabel><content src="Text/Chapter-Two.xhtml"/></navPoint>
+  </navMap>
+</ncx>"#,
+                ),
+                (
+                    "OEBPS/Text/Chapter One.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<p>Epigraph.</p><h1 id="later">One</h1><p>First chapter.</p>
+</body></html>"#,
+                ),
+                (
+                    "OEBPS/Text/Chapter-Two.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<h1>Two</h1><p>Second chapter.</p>
+</body></html>"#,
+                ),
+            ],
+        );
+
+        let book = parse_epub(&path).unwrap();
+        let contents: Vec<&str> = book.segments.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["Epigraph.", "One", "First chapter.", "Two", "Second chapter."]);
+
+        let chapters: Vec<(&str, u32)> =
+            book.chapters.iter().map(|c| (c.title.as_str(), c.start_index)).collect();
+        assert_eq!(chapters, vec![("One", 1), ("Two", 3)]);
+    }
+
+    #[test]
+    fn test_parse_epub_with_only_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pictures.epub");
+        write_epub(
+            &path,
+            &[
+                (
+                    "OEBPS/content.opf",
+                    r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Pictures</dc:title>
+    <dc:identifier id="id">pictures</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="page" href="page.xhtml" media-type="application/xhtml+xml"/>
+    <item id="plate" href="plate.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="page"/></spine>
+</package>"#,
+                ),
+                (
+                    "OEBPS/page.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<div><img src="plate.png" alt=""/></div>
+<p><img src="plate.png" alt="The &amp; plate"/></p>
+</body></html>"#,
+                ),
+                ("OEBPS/plate.png", "not really a png"),
+            ],
+        );
+
+        // An image is worth importing even without alt text
+        let book = parse_epub(&path).unwrap();
+        assert!(book.has_readable_content());
+        let segments: Vec<(SegmentType, &str)> =
+            book.segments.iter().map(|s| (s.segment_type, s.content.as_str())).collect();
+        assert_eq!(segments, vec![(SegmentType::Image, ""), (SegmentType::Image, "The & plate")]);
+
+        assert_eq!(book.images.len(), 2);
+        assert_eq!(book.images[1].segment_id, book.segments[1].id);
+        assert_eq!(book.images[1].extension, "png");
+        assert_eq!(book.images[1].data, b"not really a png");
+    }
+
+    #[test]
+    fn test_parse_epub_image_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("images.epub");
+        write_epub(
+            &path,
+            &[
+                (
+                    "OEBPS/content.opf",
+                    r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Images</dc:title>
+    <dc:identifier id="id">images</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="cover"/><itemref idref="page"/></spine>
+</package>"#,
+                ),
+                (
+                    "OEBPS/Text/cover.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<svg xmlns:xlink="http://www.w3.org/1999/xlink"><image xlink:href="../Images/Cover.JPG"/></svg>
+</body></html>"#,
+                ),
+                (
+                    "OEBPS/Text/page.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<p>Text.</p><p><img src="../images/x.png#frag" alt="X"/></p><div><img src="./../images/Plate%201.png"/></div>
+</body></html>"#,
+                ),
+                ("OEBPS/images/cover.jpg", "cover"),
+                ("OEBPS/images/x.png", "x"),
+                ("OEBPS/images/Plate 1.png", "plate"),
+            ],
+        );
+
+        // Images resolve against their document, whatever the case of the archive entry
+        let book = parse_epub(&path).unwrap();
+        assert!(book.warnings.is_empty(), "{:?}", book.warnings);
+        let images: Vec<(&str, &[u8])> =
+            book.images.iter().map(|i| (i.extension.as_str(), i.data.as_slice())).collect();
+        assert_eq!(images, vec![("jpg", &b"cover"[..]), ("png", b"x"), ("png", b"plate")]);
+
+        let types: Vec<SegmentType> = book.segments.iter().map(|s| s.segment_type).collect();
+        assert_eq!(
+            types,
+            vec![SegmentType::Image, SegmentType::Text, SegmentType::Image, SegmentType::Image]
+        );
+    }
+
+    #[test]
+    fn test_parse_epub_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("warnings.epub");
+        write_epub(
+            &path,
+            &[
+                (
+                    "OEBPS/content.opf",
+                    r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Warnings</dc:title>
+    <dc:identifier id="id">warnings</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="gone" href="gone.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx"><itemref idref="one"/><itemref idref="gone"/><itemref idref="two"/></spine>
+</package>"#,
+                ),
+                (
+                    "OEBPS/toc.ncx",
+                    r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel><content src="one.xhtml"/></navPoint>
+    <navPoint id="n2" playOrder="2"><navLabel><text>Gone</text></navLabel><content src="gone.xhtml"/></navPoint>
+    <navPoint id="n3" playOrder="3"><navLabel><text>Two</text></navLabel><content src="two.xhtml"/></navPoint>
+  </navMap>
+</ncx>"#,
+                ),
+                (
+                    "OEBPS/one.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<h1>One</h1><p>The only text.</p>
+</body></html>"#,
+                ),
+                (
+                    "OEBPS/two.xhtml",
+                    r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<h1>Two</h1><div><img src="plate.png" alt=""/></div>
+</body></html>"#,
+                ),
+            ],
+        );
+
+        // A parse with warnings still succeeds
+        let book = parse_epub(&path).unwrap();
+        assert_eq!(book.chapters.len(), 2);
+
+        let kinds: Vec<ParseWarningKind> = book.warnings.iter().map(|w| w.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseWarningKind::SkippedContent,
+                ParseWarningKind::SkippedContent,
+                ParseWarningKind::SkippedContent,
+                ParseWarningKind::EmptyChapter,
+            ]
+        );
+        assert!(book.warnings[0].message.contains("gone.xhtml"));
+        assert!(book.warnings[1].message.contains("\"plate.png\""));
+        assert!(book.warnings[2].message.contains("\"Gone\""));
+        assert!(book.warnings[3].message.contains("\"Two\""));
+    }
+
+    #[test]
+    fn test_decode_document() {
+        let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><p>Caf\xe9 cr\xe8me</p>";
+        let (text, malformed) = decode_document(latin1);
+        assert!(text.ends_with("<p>Café crème</p>"));
+        assert_eq!(malformed, None);
+
+        let meta = b"<html><head><meta http-equiv=\"Content-Type\"
+            content=\"text/html; charset=windows-1252\"/></head>\x93Hi\x94</html>";
+        assert!(decode_document(meta).0.contains("\u{201c}Hi\u{201d}"));
+
+        let shift_jis = b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><p>\x93\xfa\x96\x7b</p>";
+        assert!(decode_document(shift_jis).0.ends_with("<p>日本</p>"));
+
+        // A byte order mark wins over the declaration
+        let mut utf16 = vec![0xff, 0xfe];
+        utf16.extend("<p>é</p>".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode_document(&utf16), ("<p>é</p>".to_string(), None));
+        let utf8_bom = "\u{feff}<p>é</p>".as_bytes();
+        assert_eq!(decode_document(utf8_bom), ("<p>é</p>".to_string(), None));
+
+        // Undeclared, invalid UTF-8 is decoded lossily
+        assert_eq!(
+            decode_document(b"<p>Caf\xe9</p>"),
+            ("<p>Caf\u{fffd}</p>".to_string(), Some("UTF-8"))
+        );
+    }
+
+    #[test]
+    fn test_parse_epub_latin1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.epub");
+        let opf = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Latin-1</dc:title>
+    <dc:identifier id="id">latin1</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="declared" href="declared.xhtml" media-type="application/xhtml+xml"/>
+    <item id="undeclared" href="undeclared.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="declared"/><itemref idref="undeclared"/></spine>
+</package>"#;
+        let declared: &[u8] = b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>
+<html xmlns=\"http://www.w3.org/1999/xhtml\"><body><p>Na\xefve caf\xe9.</p></body></html>";
+        let undeclared: &[u8] =
+            b"<html xmlns=\"http://www.w3.org/1999/xhtml\"><body><p>Fa\xe7ade.</p></body></html>";
+        write_epub(
+            &path,
+            &[
+                ("OEBPS/content.opf", opf.as_bytes()),
+                ("OEBPS/declared.xhtml", declared),
+                ("OEBPS/undeclared.xhtml", undeclared),
+            ],
+        );
+
+        let book = parse_epub(&path).unwrap();
+        let contents: Vec<&str> = book.segments.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["Naïve café.", "Fa\u{fffd}ade."]);
+
+        assert_eq!(book.warnings.len(), 1);
+        assert_eq!(book.warnings[0].kind, ParseWarningKind::EncodingFallback);
+        assert!(book.warnings[0].message.contains("undeclared.xhtml is not valid UTF-8"));
+    }
+
     #[test]
     fn test_extract_segments_headings() {
         let html = "<h1>Chapter One</h1><p>Some text here.</p>";
@@ -247,4 +1343,47 @@ mod tests {
         assert_eq!(segments[1].content, "Some text here.");
         assert_eq!(segments[1].index, 1);
     }
+
+    #[test]
+    fn test_extract_segments_types() {
+        let html = "<h2 class=\"t\">Two</h2><blockquote><p>Quoted.</p></blockquote><pre>let x = 1;</pre><p>After.</p>";
+        let mut index = 0;
+        let segments = extract_segments_from_html(html, &mut index);
+
+        let types: Vec<SegmentType> = segments.iter().map(|s| s.segment_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                SegmentType::Heading { level: 2 },
+                SegmentType::Quote,
+                SegmentType::Code,
+                SegmentType::Text,
+            ]
+        );
+        assert_eq!(segments[1].content, "Quoted.");
+        assert_eq!(segments[2].content, "let x = 1;");
+    }
+
+    #[test]
+    fn test_extract_segments_images() {
+        let html = r#"<p>An <img src="icon.png"/> icon.</p><div><img src="a.png" alt="A"></div>
+<p><img src=b.png /><img src="c.png"/></p>"#;
+        let mut index = 0;
+        let segments = extract_segments_from_html(html, &mut index);
+
+        let types: Vec<SegmentType> = segments.iter().map(|s| s.segment_type).collect();
+        assert_eq!(
+            types,
+            vec![SegmentType::Text, SegmentType::Image, SegmentType::Image, SegmentType::Image]
+        );
+        assert_eq!(segments[0].content, "An icon.");
+        assert_eq!(segments[1].content, "A");
+        assert_eq!(index, 4);
+
+        let srcs: Vec<String> = segments[1..]
+            .iter()
+            .filter_map(|s| attribute(s.html.as_deref()?, "src"))
+            .collect();
+        assert_eq!(srcs, vec!["a.png", "b.png", "c.png"]);
+    }
 }
@@ -0,0 +1,281 @@
+//! HTML document parser.
+//!
+//! Parses single-file HTML documents (saved web articles, Calibre exports)
+//! into segments, reusing the EPUB block extraction.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use percent_encoding::percent_decode_str;
+
+use super::epub::{
+    attribute, extract_segments_from_html, find_next_segment, has_url_scheme, load_images,
+    strip_html_tags,
+};
+use super::{
+    detect_language, empty_chapter_warnings, heading_chapters, BookMetadata, ParseError,
+    ParsedBook,
+};
+
+/// Elements whose content is never readable text.
+const STRIPPED_ELEMENTS: [&str; 3] = ["script", "style", "noscript"];
+
+/// Parse an HTML file into a ParsedBook.
+///
+/// The title comes from `<title>`, falling back to the first `<h1>` and
+/// then the filename. The author comes from `<meta name="author">`.
+/// Script and style content is removed before segments are extracted.
+/// Images are read from files beside the document, as [`image_file`]
+/// resolves them. Each heading starts a chapter.
+///
+/// # Arguments
+/// * `path` - Path to the HTML file
+///
+/// # Returns
+/// * `Ok(ParsedBook)` - Successfully parsed book
+/// * `Err(ParseError)` - If the file cannot be read
+pub fn parse_html(path: &Path) -> Result<ParsedBook, ParseError> {
+    let content = fs::read_to_string(path)?;
+    let html = sanitize_html(&content);
+
+    let title = extract_title(&html).unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string()
+    });
+
+    let author = meta_content(&html, "author");
+
+    let mut segment_index: u32 = 0;
+    let mut segments = extract_segments_from_html(body(&html), &mut segment_index);
+
+    let mut warnings = Vec::new();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let images = load_images(
+        &mut segments,
+        |src| {
+            let file = image_file(directory, src)?;
+            let data = fs::read(&file).ok()?;
+            Some((file, data))
+        },
+        &mut warnings,
+    );
+    let chapters = heading_chapters(&segments);
+    warnings.extend(empty_chapter_warnings(&chapters, &segments));
+
+    Ok(ParsedBook {
+        title,
+        author,
+        language: detect_language(segments.iter().map(|s| s.content.as_str())),
+        segments,
+        chapters,
+        metadata: BookMetadata::default(),
+        warnings,
+        images,
+    })
+}
+
+/// Path of an image referenced from a document in `directory`.
+///
+/// Only relative references are followed, percent-decoded and without any
+/// `#fragment` or `?query`; external URLs, `data:` URIs and absolute paths
+/// give None.
+fn image_file(directory: &Path, src: &str) -> Option<PathBuf> {
+    let src = src.trim();
+    let src = &src[..src.find(['#', '?']).unwrap_or(src.len())];
+    let decoded = percent_decode_str(src).decode_utf8().ok()?;
+    let relative = Path::new(decoded.as_ref());
+    if decoded.is_empty() || has_url_scheme(&decoded) || relative.has_root() {
+        return None;
+    }
+    Some(directory.join(relative))
+}
+
+/// Remove comments and script, style and noscript elements from HTML.
+fn sanitize_html(html: &str) -> String {
+    let mut result = remove_between(html, "<!--", "-->");
+    for tag in STRIPPED_ELEMENTS {
+        result = remove_between(&result, &format!("<{}", tag), &format!("</{}>", tag));
+    }
+    result
+}
+
+/// Remove every span from `open` through `close`, matching ASCII case-insensitively.
+///
+/// An unterminated span is removed to the end of the input.
+fn remove_between(html: &str, open: &str, close: &str) -> String {
+    // ASCII lowercasing keeps byte offsets identical to the original
+    let lower = html.to_ascii_lowercase();
+    let mut result = String::with_capacity(html.len());
+    let mut position = 0;
+
+    while let Some(start) = lower[position..].find(open).map(|i| position + i) {
+        result.push_str(&html[position..start]);
+        position = match lower[start..].find(close) {
+            Some(end) => start + end + close.len(),
+            None => html.len(),
+        };
+    }
+
+    result.push_str(&html[position..]);
+    result
+}
+
+/// The contents of `<body>`, or the whole document if there is no body tag.
+fn body(html: &str) -> &str {
+    let lower = html.to_ascii_lowercase();
+    let Some(open) = lower.find("<body") else {
+        return html;
+    };
+    let Some(tag_end) = lower[open..].find('>') else {
+        return html;
+    };
+
+    let start = open + tag_end + 1;
+    let end = lower[start..].find("</body>").map_or(html.len(), |i| start + i);
+    &html[start..end]
+}
+
+/// Title from `<title>`, or the text of the first `<h1>`.
+fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let from_title_tag = lower.find("<title").and_then(|open| {
+        let start = open + lower[open..].find('>')? + 1;
+        let end = start + lower[start..].find("</title>")?;
+        Some(strip_html_tags(&html[start..end]))
+    });
+
+    from_title_tag
+        .filter(|title| !title.is_empty())
+        .or_else(|| first_heading(html))
+}
+
+/// Text of the first `<h1>` element.
+fn first_heading(html: &str) -> Option<String> {
+    let mut remaining = html;
+    while let Some((text, element, rest)) = find_next_segment(remaining) {
+        if element.starts_with("<h1") && !text.trim().is_empty() {
+            return Some(text.trim().to_string());
+        }
+        remaining = rest;
+    }
+    None
+}
+
+/// Value of the `content` attribute of the `<meta>` tag with the given name.
+fn meta_content(html: &str, name: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut position = 0;
+
+    while let Some(open) = lower[position..].find("<meta").map(|i| position + i) {
+        let end = open + lower[open..].find('>')?;
+        let tag = &html[open..end];
+        position = end;
+
+        let matches_name = attribute(tag, "name").is_some_and(|n| n.eq_ignore_ascii_case(name));
+        if matches_name {
+            return attribute(tag, "content")
+                .map(|content| strip_html_tags(&content))
+                .filter(|content| !content.is_empty());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SegmentType;
+
+    const ARTICLE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <TITLE>A Saved Article</TITLE>
+  <meta charset="utf-8">
+  <meta name="Author" content="Jane Doe">
+  <style>p { color: red; }</style>
+  <script>var p = "<p>not text</p>";</script>
+</head>
+<body>
+  <h1>Heading</h1>
+  <!-- <p>commented out</p> -->
+  <p>First paragraph.</p>
+  <noscript><p>Enable JavaScript</p></noscript>
+  <p>Second &amp; last.</p>
+</body>
+</html>"#;
+
+    #[test]
+    fn test_sanitize_removes_scripts_and_styles() {
+        let html = sanitize_html(ARTICLE);
+        assert!(!html.contains("not text"));
+        assert!(!html.contains("color: red"));
+        assert!(!html.contains("commented out"));
+        assert!(!html.contains("Enable JavaScript"));
+        assert!(html.contains("First paragraph."));
+    }
+
+    #[test]
+    fn test_extract_title_and_author() {
+        let html = sanitize_html(ARTICLE);
+        assert_eq!(extract_title(&html), Some("A Saved Article".to_string()));
+        assert_eq!(meta_content(&html, "author"), Some("Jane Doe".to_string()));
+        assert_eq!(meta_content(&html, "description"), None);
+    }
+
+    #[test]
+    fn test_title_falls_back_to_first_heading() {
+        let html = "<html><head></head><body><p>Intro</p><h1>The <em>Real</em> Title</h1></body></html>";
+        assert_eq!(extract_title(html), Some("The Real Title".to_string()));
+        assert_eq!(extract_title("<p>No headings</p>"), None);
+    }
+
+    #[test]
+    fn test_body_segments() {
+        let html = sanitize_html(ARTICLE);
+        let mut index = 0;
+        let segments = extract_segments_from_html(body(&html), &mut index);
+
+        let contents: Vec<&str> = segments.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["Heading", "First paragraph.", "Second & last."]);
+    }
+
+    #[test]
+    fn test_parse_html_images() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("my images")).unwrap();
+        std::fs::write(dir.path().join("my images/fig.PNG"), b"png data").unwrap();
+        let path = dir.path().join("article.html");
+        let html = r#"<html><body><p>Text.</p>
+<figure><img src="my%20images/fig.PNG?v=2" alt="A figure"></figure>
+<p><img src="https://example.com/remote.png" alt="Remote"/></p>
+</body></html>"#;
+        std::fs::write(&path, html).unwrap();
+
+        let book = parse_html(&path).unwrap();
+        let types: Vec<SegmentType> = book.segments.iter().map(|s| s.segment_type).collect();
+        assert_eq!(types, vec![SegmentType::Text, SegmentType::Image, SegmentType::Text]);
+
+        assert_eq!(book.images.len(), 1);
+        assert_eq!(book.images[0].segment_id, book.segments[1].id);
+        assert_eq!(book.images[0].extension, "png");
+        assert_eq!(book.images[0].data, b"png data");
+
+        // The remote image is kept as its alt text
+        assert_eq!(book.segments[2].content, "Remote");
+        assert_eq!(book.warnings.len(), 1);
+        assert!(book.warnings[0].message.contains("remote.png"));
+
+        assert_eq!(image_file(dir.path(), "/etc/passwd"), None);
+        assert_eq!(image_file(dir.path(), "data:image/png;base64,AAAA"), None);
+    }
+
+    #[test]
+    fn test_attribute_parsing() {
+        assert_eq!(attribute(r#"<meta name="author" content='A. Writer'"#, "content"), Some("A. Writer".to_string()));
+        assert_eq!(attribute("<meta name=author content=Someone", "name"), Some("author".to_string()));
+        assert_eq!(attribute(r#"<meta data-name="x""#, "name"), None);
+    }
+}
@@ -6,13 +6,17 @@ use std::fs;
 use std::path::Path;
 use pulldown_cmark::{Parser, Options, Event, Tag, TagEnd, html};
 
-use super::{ParseError, ParsedBook, Segment};
+use super::{
+    detect_language, empty_chapter_warnings, heading_chapters, BookMetadata, ParseError,
+    ParsedBook, Segment,
+};
 
 /// Parse a Markdown file into a ParsedBook.
 ///
 /// Uses pulldown-cmark to parse the Markdown content. Segments are created
-/// for each block-level element (paragraphs, headings, etc.). The title
-/// is extracted from the first H1 heading if present.
+/// for each block-level element (paragraphs, headings, etc.), and each
+/// heading starts a chapter. The title is extracted from the first H1
+/// heading if present.
 ///
 /// # Arguments
 /// * `path` - Path to the Markdown file
@@ -33,11 +37,18 @@ pub fn parse_markdown(path: &Path) -> Result<ParsedBook, ParseError> {
 
     // Parse into segments
     let segments = parse_content_to_segments(&content);
+    let chapters = heading_chapters(&segments);
+    let warnings = empty_chapter_warnings(&chapters, &segments);
 
     Ok(ParsedBook {
         title,
         author: None, // Markdown files don't have author metadata
+        language: detect_language(segments.iter().map(|s| s.content.as_str())),
         segments,
+        chapters,
+        metadata: BookMetadata::default(),
+        warnings,
+        images: Vec::new(),
     })
 }
 
@@ -178,6 +189,7 @@ fn extract_plain_text(events: &[Event]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::SegmentType;
 
     #[test]
     fn test_extract_title() {
@@ -231,4 +243,51 @@ mod tests {
         assert_eq!(segments[2].content, "Second paragraph.");
         assert_eq!(segments[2].index, 2);
     }
+
+    #[test]
+    fn test_segment_types() {
+        let content = "## Chapter\n\n> A quote.\n\n```\nlet x = 1;\n```\n\nText.";
+        let types: Vec<SegmentType> = parse_content_to_segments(content)
+            .iter()
+            .map(|s| s.segment_type)
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                SegmentType::Heading { level: 2 },
+                SegmentType::Quote,
+                SegmentType::Code,
+                SegmentType::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_markdown_chapters() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.md");
+        std::fs::write(&path, "# Book
+
+## One
+
+Text.
+
+## Two
+
+## Three
+
+More.").unwrap();
+
+        let book = parse_markdown(&path).unwrap();
+        let chapters: Vec<(&str, u32, u32)> = book
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.start_index, c.level))
+            .collect();
+        assert_eq!(chapters, vec![("Book", 0, 0), ("One", 1, 1), ("Two", 3, 1), ("Three", 4, 1)]);
+
+        let messages: Vec<&str> = book.warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(messages, vec!["Chapter \"Two\" has no text"]);
+    }
 }
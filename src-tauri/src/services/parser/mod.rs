@@ -1,17 +1,24 @@
 //! Document parsing services for Actual Reader.
 //!
-//! This module handles parsing various document formats (EPUB, Markdown, TXT)
+//! This module handles parsing various document formats (EPUB, HTML, Markdown, TXT)
 //! into a unified ParsedBook structure with segments.
 
 pub mod epub;
+pub mod html;
 pub mod markdown;
 pub mod txt;
 
+use std::io::Read;
 use std::path::Path;
+use std::sync::OnceLock;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
+pub use crate::models::BookMetadata;
+use crate::models::SegmentType;
+
 /// Errors that can occur during parsing
 #[derive(Error, Debug)]
 pub enum ParseError {
@@ -44,20 +51,335 @@ pub struct Segment {
     pub content: String,
     /// Optional HTML rendering of the content
     pub html: Option<String>,
+    /// Structural role of the block, taken from its HTML element
+    #[serde(default)]
+    pub segment_type: SegmentType,
 }
 
 impl Segment {
-    /// Create a new segment with a generated UUID
+    /// Create a new segment with a generated UUID, typed by its HTML element
     pub fn new(index: u32, content: String, html: Option<String>) -> Self {
+        let segment_type = html.as_deref().map(segment_type_from_html).unwrap_or_default();
         Self {
             id: format!("seg_{}", Uuid::new_v4()),
             index,
             content,
             html,
+            segment_type,
+        }
+    }
+
+    /// True if this segment is a heading (its HTML is an `<h1>`-`<h6>` element)
+    pub fn is_heading(&self) -> bool {
+        matches!(self.segment_type, SegmentType::Heading { .. })
+    }
+
+    /// True if this segment has something to read: non-blank text, or an image
+    pub fn is_readable(&self) -> bool {
+        self.segment_type == SegmentType::Image || !self.content.trim().is_empty()
+    }
+}
+
+/// Segment type for the element a segment's HTML starts with.
+///
+/// `<h1>`-`<h6>` are headings, `<blockquote>` quotes, `<pre>` code and
+/// `<img>` or SVG `<image>` images; anything else is plain text.
+pub fn segment_type_from_html(html: &str) -> SegmentType {
+    let Some(element) = html.trim_start().strip_prefix('<') else {
+        return SegmentType::Text;
+    };
+    let name_len = element
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(element.len());
+
+    match element[..name_len].to_ascii_lowercase().as_str() {
+        "blockquote" => SegmentType::Quote,
+        "pre" => SegmentType::Code,
+        "img" | "image" => SegmentType::Image,
+        name => match name.strip_prefix('h').map(str::parse) {
+            Some(Ok(level @ 1..=6)) => SegmentType::Heading { level },
+            _ => SegmentType::Text,
+        },
+    }
+}
+
+/// True if a segment's HTML is a heading element (`<h1>`-`<h6>`)
+pub fn is_heading_html(html: &str) -> bool {
+    let html = html.trim_start().as_bytes();
+    html.len() >= 3
+        && html[0] == b'<'
+        && html[1].eq_ignore_ascii_case(&b'h')
+        && (b'1'..=b'6').contains(&html[2])
+}
+
+/// Characters of text sampled when detecting a book's language.
+const LANGUAGE_SAMPLE_CHARS: usize = 4000;
+
+/// Detect the language of a book from the start of its text.
+///
+/// Returns an ISO 639-3 code, or None if the text is too short or too
+/// ambiguous for a reliable guess.
+pub fn detect_language<'a>(texts: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut sample = String::new();
+    for text in texts {
+        if sample.len() >= LANGUAGE_SAMPLE_CHARS {
+            break;
+        }
+        sample.push_str(text);
+        sample.push('\n');
+    }
+
+    whatlang::detect(&sample)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string())
+}
+
+/// Merge runs of consecutive short segments.
+///
+/// A segment shorter than `min_chars` characters absorbs the following
+/// segments while it stays under the threshold and they are short too, so
+/// dialogue and verse lines narrate as one unit. Text is joined with
+/// newlines and HTML is concatenated. Headings and images are never merged
+/// with neighboring segments, and only segments of the same type are merged.
+/// Indices are renumbered afterwards.
+pub fn merge_short_segments(segments: Vec<Segment>, min_chars: usize) -> Vec<Segment> {
+    let mut merger = SegmentMerger::new(min_chars);
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        merged.extend(merger.push(segment));
+    }
+    merged.extend(merger.finish());
+
+    merged
+}
+
+/// Streaming form of [`merge_short_segments`], for segments that arrive one
+/// at a time.
+///
+/// Holds back at most one segment, which may still absorb the next one.
+/// Emitted segments are numbered from 0.
+pub struct SegmentMerger {
+    min_chars: usize,
+    pending: Option<Segment>,
+    next_index: u32,
+}
+
+impl SegmentMerger {
+    /// Create a merger for segments shorter than `min_chars` characters.
+    pub fn new(min_chars: usize) -> Self {
+        Self {
+            min_chars,
+            pending: None,
+            next_index: 0,
+        }
+    }
+
+    /// Add the next segment, returning the previous one once it can no
+    /// longer grow.
+    pub fn push(&mut self, segment: Segment) -> Option<Segment> {
+        let min_chars = self.min_chars;
+        let is_short = |segment: &Segment| segment.content.chars().count() < min_chars;
+
+        if let Some(last) = self.pending.as_mut() {
+            let mergeable = !last.is_heading()
+                && last.segment_type != SegmentType::Image
+                && last.segment_type == segment.segment_type;
+            if mergeable && is_short(last) && is_short(&segment) {
+                last.content.push('\n');
+                last.content.push_str(&segment.content);
+                last.html = match (last.html.take(), segment.html) {
+                    (Some(a), Some(b)) => Some(a + &b),
+                    _ => None,
+                };
+                return None;
+            }
+        }
+
+        self.pending.replace(segment).map(|segment| self.number(segment))
+    }
+
+    /// Return the last held-back segment, if any.
+    pub fn finish(mut self) -> Option<Segment> {
+        self.pending.take().map(|segment| self.number(segment))
+    }
+
+    fn number(&mut self, mut segment: Segment) -> Segment {
+        segment.index = self.next_index;
+        self.next_index += 1;
+        segment
+    }
+}
+
+/// Longest segment checked for boilerplate, in characters.
+pub const BOILERPLATE_MAX_CHARS: usize = 1000;
+
+/// Longest line counted as a running header or footer, in characters.
+pub const RUNNING_HEADER_MAX_CHARS: usize = 80;
+
+/// Times a line must appear in a book to count as a running header or footer.
+pub const RUNNING_HEADER_MIN_REPEATS: u32 = 5;
+
+/// Whether a segment looks like page furniture rather than content: a bare
+/// page number or a copyright notice.
+///
+/// Running headers and footers can only be told apart by how often they
+/// repeat across the book; see [`could_be_running_header`].
+pub fn looks_like_boilerplate(text: &str) -> bool {
+    let text = text.trim();
+    if text.chars().count() > BOILERPLATE_MAX_CHARS {
+        return false;
+    }
+    is_page_number(text) || is_copyright_notice(text)
+}
+
+/// Whether a line that repeats throughout a book could be a running header
+/// or footer: short, and not ending like a sentence, as a recurring line of
+/// dialogue would.
+pub fn could_be_running_header(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty()
+        && text.chars().count() <= RUNNING_HEADER_MAX_CHARS
+        && !text.ends_with(['.', '!', '?', '"', '\'', '\u{201d}', '\u{2019}', '\u{2026}'])
+}
+
+/// "12", "- 12 -", "Page 12", "12 of 300" or a lowercase roman numeral.
+fn is_page_number(text: &str) -> bool {
+    let decoration = |c: char| c.is_whitespace() || "-\u{2013}\u{2014}|[]()".contains(c);
+    let text = text.trim_matches(decoration);
+    let text = ["Page ", "page ", "PAGE ", "p. ", "P. "]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text)
+        .trim();
+    match text.split_once(" of ") {
+        Some((page, total)) => is_numeral(page.trim()) && is_numeral(total.trim()),
+        None => is_numeral(text),
+    }
+}
+
+/// Arabic numerals, or roman numerals in lowercase as front matter pages are
+/// numbered. Uppercase roman numerals usually number chapters instead.
+///
+/// Roman numerals must be well formed and below cd (400), more pages than
+/// any front matter has, so words spelled with the same letters ("mild",
+/// "civil", "mix") don't count.
+fn is_numeral(text: &str) -> bool {
+    static ROMAN: OnceLock<Regex> = OnceLock::new();
+    let roman = ROMAN
+        .get_or_init(|| Regex::new("^c{0,3}(xc|xl|l?x{0,3})(ix|iv|v?i{0,3})$").unwrap());
+
+    let bytes = text.as_bytes();
+    match bytes.len() {
+        1..=5 if bytes.iter().all(u8::is_ascii_digit) => true,
+        1..=8 => roman.is_match(text),
+        _ => false,
+    }
+}
+
+fn is_copyright_notice(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    lower.contains("all rights reserved")
+        || lower.starts_with('\u{a9}')
+        || lower.starts_with("copyright \u{a9}")
+        || lower.starts_with("copyright (c)")
+}
+
+/// A table-of-contents entry pointing at the segment where it starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    /// Chapter title as shown in the table of contents
+    pub title: String,
+    /// Index of the chapter's first segment
+    pub start_index: u32,
+    /// Nesting depth; 0 for top-level chapters
+    pub level: u32,
+}
+
+/// What a [`ParseWarning`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParseWarningKind {
+    /// Part of the source couldn't be read or placed and was left out
+    SkippedContent,
+    /// Text wasn't valid in its declared encoding and was decoded another way
+    EncodingFallback,
+    /// A chapter has no text besides its heading
+    EmptyChapter,
+}
+
+/// A non-fatal problem found while parsing. The book still imports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    /// Description naming the file or chapter involved
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub fn new(kind: ParseWarningKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
         }
     }
 }
 
+/// Warn about chapters with no readable segment besides headings.
+///
+/// A chapter runs until the next chapter at the same or a shallower level,
+/// so a part whose text is all in its nested chapters isn't empty.
+pub fn empty_chapter_warnings(chapters: &[Chapter], segments: &[Segment]) -> Vec<ParseWarning> {
+    chapters
+        .iter()
+        .enumerate()
+        .filter(|(i, chapter)| {
+            let end = chapters[i + 1..]
+                .iter()
+                .find(|next| next.level <= chapter.level)
+                .map_or(segments.len(), |next| next.start_index as usize);
+            let start = (chapter.start_index as usize).min(end);
+            !segments
+                .get(start..end)
+                .unwrap_or_default()
+                .iter()
+                .any(|s| !s.is_heading() && s.is_readable())
+        })
+        .map(|(_, chapter)| {
+            ParseWarning::new(
+                ParseWarningKind::EmptyChapter,
+                format!("Chapter \"{}\" has no text", chapter.title),
+            )
+        })
+        .collect()
+}
+
+/// Chapters for a document without a table of contents, one per heading.
+///
+/// The shallowest heading level in the document becomes level 0.
+pub fn heading_chapters(segments: &[Segment]) -> Vec<Chapter> {
+    let headings: Vec<(u8, &Segment)> = segments
+        .iter()
+        .filter_map(|segment| match segment.segment_type {
+            SegmentType::Heading { level } => Some((level, segment)),
+            _ => None,
+        })
+        .filter(|(_, segment)| !segment.content.trim().is_empty())
+        .collect();
+    let top = headings.iter().map(|(level, _)| *level).min().unwrap_or(1);
+
+    headings
+        .into_iter()
+        .map(|(level, segment)| Chapter {
+            title: segment.content.split_whitespace().collect::<Vec<_>>().join(" "),
+            start_index: segment.index,
+            level: u32::from(level - top),
+        })
+        .collect()
+}
+
 /// Represents a fully parsed book ready for storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -68,12 +390,89 @@ pub struct ParsedBook {
     pub author: Option<String>,
     /// All text segments in reading order
     pub segments: Vec<Segment>,
+    /// Table of contents in reading order, empty if the format has none
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    /// Language declared by the source or detected from its text
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Publisher, date and identifiers, if the format declares them
+    #[serde(default)]
+    pub metadata: BookMetadata,
+    /// Problems that didn't stop the parse, for telling the user
+    #[serde(default)]
+    pub warnings: Vec<ParseWarning>,
+    /// Files of the image segments, stored with the book on import
+    #[serde(skip)]
+    pub images: Vec<ParsedImage>,
+}
+
+/// An image read from the source for an image segment.
+#[derive(Debug, Clone)]
+pub struct ParsedImage {
+    /// Id of the image segment
+    pub segment_id: String,
+    /// Lowercase file extension, or "bin" if the source name has none
+    pub extension: String,
+    /// Image file content
+    pub data: Vec<u8>,
+}
+
+impl ParsedImage {
+    /// Image for a segment, with the extension taken from its source file.
+    pub fn new(segment_id: &str, file: &Path, data: Vec<u8>) -> Self {
+        let extension = file
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|e| !e.is_empty() && e.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("bin")
+            .to_lowercase();
+        Self {
+            segment_id: segment_id.to_string(),
+            extension,
+            data,
+        }
+    }
+}
+
+impl ParsedBook {
+    /// True if any segment has something to read. A book without one can't
+    /// be read or narrated, so it isn't worth importing.
+    pub fn has_readable_content(&self) -> bool {
+        self.segments.iter().any(Segment::is_readable)
+    }
+
+    /// Merge runs of short segments, as in [`merge_short_segments`].
+    ///
+    /// Chapters are moved to the merged segment that now holds their first
+    /// segment, so they keep pointing at the same text.
+    pub fn merge_short_segments(&mut self, min_chars: usize) {
+        let old_ids: Vec<String> = self.segments.iter().map(|s| s.id.clone()).collect();
+        self.segments = merge_short_segments(std::mem::take(&mut self.segments), min_chars);
+
+        // A merged segment keeps the id of the first segment it absorbed
+        let mut new_indices = Vec::with_capacity(old_ids.len());
+        let mut current = 0;
+        for id in &old_ids {
+            if self.segments.get(current + 1).is_some_and(|s| &s.id == id) {
+                current += 1;
+            }
+            new_indices.push(current as u32);
+        }
+
+        for chapter in &mut self.chapters {
+            if let Some(&index) = new_indices.get(chapter.start_index as usize) {
+                chapter.start_index = index;
+            }
+        }
+    }
 }
 
 /// Supported source formats for parsing
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SourceFormat {
     Epub,
+    Html,
     Markdown,
     Txt,
 }
@@ -83,16 +482,109 @@ impl SourceFormat {
     pub fn from_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
             "epub" => Some(Self::Epub),
+            "html" | "htm" => Some(Self::Html),
             "md" | "markdown" => Some(Self::Markdown),
             "txt" | "text" => Some(Self::Txt),
             _ => None,
         }
     }
+
+    /// Extension a file of this format is stored under.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Epub => "epub",
+            Self::Html => "html",
+            Self::Markdown => "md",
+            Self::Txt => "txt",
+        }
+    }
+}
+
+/// Bytes a ZIP archive, and so an EPUB or a bundle, starts with.
+pub(crate) const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Bytes a PDF starts with.
+const PDF_MAGIC: &[u8] = b"%PDF-";
+
+/// Byte order mark some editors write at the start of UTF-8 text.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// How much of a file [`detect_format`] reads to tell text from binary.
+const SNIFF_BYTES: u64 = 8192;
+
+/// Detect a file's format from its contents, using the extension only to
+/// tell kinds of text apart.
+///
+/// A ZIP archive whose `mimetype` entry is application/epub+zip is an EPUB
+/// whatever the file is called, and a PDF is recognised by its header and
+/// refused. Anything else must be text, with or without a UTF-8 byte order
+/// mark. Text named .txt, .md or .html takes that format; text with no
+/// extension, or named .epub, is HTML if it starts with an HTML tag and
+/// plain text otherwise. Other extensions are refused unless the file turns
+/// out to be an EPUB.
+pub fn detect_format(path: &Path) -> Result<SourceFormat, ParseError> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let named = extension.and_then(SourceFormat::from_extension);
+
+    let mut head = Vec::new();
+    std::fs::File::open(path)?
+        .take(SNIFF_BYTES)
+        .read_to_end(&mut head)?;
+
+    if head.starts_with(ZIP_MAGIC) {
+        return if is_epub_archive(path) {
+            Ok(SourceFormat::Epub)
+        } else {
+            Err(ParseError::UnsupportedFormat("ZIP archive that is not an EPUB".to_string()))
+        };
+    }
+    if head.starts_with(PDF_MAGIC) {
+        return Err(ParseError::UnsupportedFormat("PDF".to_string()));
+    }
+
+    match (extension, named) {
+        (Some(extension), None) => Err(ParseError::UnsupportedFormat(extension.to_string())),
+        (_, Some(SourceFormat::Epub)) | (None, None) => {
+            let text = head.strip_prefix(UTF8_BOM).unwrap_or(&head);
+            if text.contains(&0) {
+                return Err(ParseError::UnsupportedFormat(
+                    "binary file that is not an EPUB".to_string(),
+                ));
+            }
+            Ok(if starts_with_html_tag(text) { SourceFormat::Html } else { SourceFormat::Txt })
+        }
+        (_, Some(format)) => Ok(format),
+    }
+}
+
+/// Whether a ZIP archive declares itself an EPUB in its `mimetype` entry.
+fn is_epub_archive(path: &Path) -> bool {
+    let mimetype = std::fs::File::open(path)
+        .map_err(zip::result::ZipError::from)
+        .and_then(zip::ZipArchive::new)
+        .and_then(|mut archive| {
+            let mut mimetype = String::new();
+            archive.by_name("mimetype")?.read_to_string(&mut mimetype)?;
+            Ok(mimetype)
+        });
+    mimetype.is_ok_and(|mimetype| mimetype.trim() == "application/epub+zip")
+}
+
+/// Whether text opens with a doctype or `<html>` tag.
+fn starts_with_html_tag(text: &[u8]) -> bool {
+    let start: Vec<u8> = text
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take(14)
+        .map(u8::to_ascii_lowercase)
+        .collect();
+    start.starts_with(b"<!doctype html") || start.starts_with(b"<html")
 }
 
 /// Parse a file at the given path into a ParsedBook.
 ///
-/// The format is auto-detected from the file extension.
+/// The format is detected from the file's contents and extension, as
+/// [`detect_format`] does.
 ///
 /// # Arguments
 /// * `path` - Path to the source file
@@ -110,16 +602,9 @@ impl SourceFormat {
 /// println!("Parsed {} segments from {}", book.segments.len(), book.title);
 /// ```
 pub fn parse_file(path: &Path) -> Result<ParsedBook, ParseError> {
-    let extension = path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or_else(|| ParseError::UnsupportedFormat("No file extension".to_string()))?;
-
-    let format = SourceFormat::from_extension(extension)
-        .ok_or_else(|| ParseError::UnsupportedFormat(extension.to_string()))?;
-
-    match format {
+    match detect_format(path)? {
         SourceFormat::Epub => epub::parse_epub(path),
+        SourceFormat::Html => html::parse_html(path),
         SourceFormat::Markdown => markdown::parse_markdown(path),
         SourceFormat::Txt => txt::parse_txt(path),
     }
@@ -138,10 +623,217 @@ mod tests {
         assert_eq!(segment.html, Some("<p>Hello world</p>".to_string()));
     }
 
+    #[test]
+    fn test_detect_language() {
+        let english = [
+            "It was the best of times, it was the worst of times.",
+            "It was the age of wisdom, it was the age of foolishness.",
+        ];
+        assert_eq!(detect_language(english).as_deref(), Some("eng"));
+
+        let french = [
+            "Longtemps, je me suis couché de bonne heure.",
+            "Parfois, à peine ma bougie éteinte, mes yeux se fermaient si vite que je n'avais pas le temps de me dire : je m'endors.",
+        ];
+        assert_eq!(detect_language(french).as_deref(), Some("fra"));
+
+        assert_eq!(detect_language(["ok"]), None);
+        assert_eq!(detect_language(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_merge_short_segments() {
+        let p = |text: &str| Segment::new(0, text.to_string(), Some(format!("<p>{}</p>", text)));
+        let segments = vec![
+            Segment::new(0, "Chapter 1".to_string(), Some("<h2>Chapter 1</h2>".to_string())),
+            p("\"Hi.\""),
+            p("\"Hello.\""),
+            p("\"Well then.\""),
+            p("A much longer paragraph of narration."),
+            p("Short."),
+        ];
+
+        let merged = merge_short_segments(segments, 20);
+        let contents: Vec<&str> = merged.iter().map(|s| s.content.as_str()).collect();
+
+        assert_eq!(
+            contents,
+            vec![
+                "Chapter 1",
+                "\"Hi.\"\n\"Hello.\"\n\"Well then.\"",
+                "A much longer paragraph of narration.",
+                "Short.",
+            ]
+        );
+        assert_eq!(merged[1].html.as_deref(), Some("<p>\"Hi.\"</p><p>\"Hello.\"</p><p>\"Well then.\"</p>"));
+        assert!(merged.iter().enumerate().all(|(i, s)| s.index == i as u32));
+    }
+
+    #[test]
+    fn test_empty_chapter_warnings() {
+        let h = |text: &str| Segment::new(0, text.to_string(), Some(format!("<h1>{}</h1>", text)));
+        let p = |text: &str| Segment::new(0, text.to_string(), Some(format!("<p>{}</p>", text)));
+        let segments = vec![
+            h("Part One"),
+            h("Chapter 1"),
+            p("Text."),
+            h("Chapter 2"),
+            h("Part Two"),
+            p(" "),
+        ];
+        let chapter = |title: &str, start_index, level| Chapter {
+            title: title.to_string(),
+            start_index,
+            level,
+        };
+        let chapters = vec![
+            chapter("Part One", 0, 0),
+            chapter("Chapter 1", 1, 1),
+            chapter("Chapter 2", 3, 1),
+            chapter("Part Two", 4, 0),
+        ];
+
+        let warnings = empty_chapter_warnings(&chapters, &segments);
+        let messages: Vec<&str> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec!["Chapter \"Chapter 2\" has no text", "Chapter \"Part Two\" has no text"]
+        );
+        assert!(warnings.iter().all(|w| w.kind == ParseWarningKind::EmptyChapter));
+    }
+
+    #[test]
+    fn test_heading_chapters() {
+        let segment = |index, text: &str, tag: &str| {
+            Segment::new(index, text.to_string(), Some(format!("<{0}>{1}</{0}>", tag, text)))
+        };
+        let segments = vec![
+            segment(0, "Part  One", "h2"),
+            segment(1, "Chapter 1", "h3"),
+            segment(2, "Text.", "p"),
+            segment(3, " ", "h2"),
+        ];
+
+        let chapters = heading_chapters(&segments);
+        assert_eq!(
+            chapters,
+            vec![
+                Chapter { title: "Part One".to_string(), start_index: 0, level: 0 },
+                Chapter { title: "Chapter 1".to_string(), start_index: 1, level: 1 },
+            ]
+        );
+        assert!(heading_chapters(&segments[2..3]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_short_segments_moves_chapters() {
+        let p = |text: &str| Segment::new(0, text.to_string(), Some(format!("<p>{}</p>", text)));
+        let mut book = ParsedBook {
+            title: "Book".to_string(),
+            author: None,
+            segments: vec![p("One."), p("Two."), p("A paragraph long enough to stand alone."), p("Three.")],
+            language: None,
+            chapters: vec![
+                Chapter { title: "First".to_string(), start_index: 0, level: 0 },
+                Chapter { title: "Second".to_string(), start_index: 1, level: 1 },
+                Chapter { title: "Third".to_string(), start_index: 3, level: 0 },
+            ],
+            metadata: BookMetadata::default(),
+            warnings: Vec::new(),
+            images: Vec::new(),
+        };
+        for (index, segment) in book.segments.iter_mut().enumerate() {
+            segment.index = index as u32;
+        }
+
+        book.merge_short_segments(20);
+
+        assert_eq!(book.segments.len(), 3);
+        let starts: Vec<u32> = book.chapters.iter().map(|c| c.start_index).collect();
+        assert_eq!(starts, vec![0, 0, 2]);
+    }
+
+    #[test]
+    fn test_looks_like_boilerplate() {
+        let page_numbers = ["12", " - 12 - ", "[xiv]", "Page 7", "p. 7", "7 of 300", "(3)"];
+        for text in page_numbers {
+            assert!(looks_like_boilerplate(text), "{:?}", text);
+        }
+        assert!(looks_like_boilerplate("\u{a9} 2021 Jane Doe"));
+        assert!(looks_like_boilerplate("Copyright (c) 2021. All rights reserved."));
+        assert!(looks_like_boilerplate("All Rights Reserved"));
+
+        for text in ["IV", "123456", "Page one", "Chapter 12", "I was born in 1984.", ""] {
+            assert!(!looks_like_boilerplate(text), "{:?}", text);
+        }
+        assert!(!looks_like_boilerplate(&format!("All rights reserved. {}", "x".repeat(1000))));
+    }
+
+    #[test]
+    fn test_is_numeral() {
+        for text in ["7", "12345", "i", "iv", "ix", "xiv", "xl", "lxxxviii", "cccxcix"] {
+            assert!(is_numeral(text), "{:?}", text);
+        }
+        for text in ["did", "mild", "civil", "mix", "iiii", "vx", "il", "cd", "123456", ""] {
+            assert!(!is_numeral(text), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_could_be_running_header() {
+        assert!(could_be_running_header("THE GREAT GATSBY"));
+        assert!(could_be_running_header("Chapter 3: The Party"));
+        assert!(!could_be_running_header("Yes."));
+        assert!(!could_be_running_header("\u{201c}No!\u{201d}"));
+        assert!(!could_be_running_header(""));
+        assert!(!could_be_running_header(&"x".repeat(81)));
+    }
+
+    #[test]
+    fn test_headings_and_images_never_merge() {
+        let segments = vec![
+            Segment::new(0, "Part".to_string(), Some("<H1 class=\"x\">Part</H1>".to_string())),
+            Segment::new(1, "Intro".to_string(), Some("<p>Intro</p>".to_string())),
+        ];
+        assert!(segments[0].is_heading());
+        assert!(!segments[1].is_heading());
+        assert_eq!(merge_short_segments(segments, 100).len(), 2);
+
+        // Nor are images, which each keep their own file
+        let image = |alt: &str| {
+            Segment::new(0, alt.to_string(), Some("<img src=\"a.png\"/>".to_string()))
+        };
+        assert_eq!(merge_short_segments(vec![image("A"), image("B")], 100).len(), 2);
+    }
+
+    #[test]
+    fn test_segment_type_from_html() {
+        assert_eq!(segment_type_from_html("<H2 id=\"x\">Two</H2>"), SegmentType::Heading { level: 2 });
+        assert_eq!(segment_type_from_html("<blockquote><p>Q</p></blockquote>"), SegmentType::Quote);
+        assert_eq!(segment_type_from_html("<pre><code>x</code></pre>"), SegmentType::Code);
+        assert_eq!(segment_type_from_html("<p>Text</p>"), SegmentType::Text);
+        assert_eq!(segment_type_from_html("<img src=\"a.png\"/>"), SegmentType::Image);
+        assert_eq!(segment_type_from_html("<hr/>"), SegmentType::Text);
+        assert_eq!(segment_type_from_html("<h7>No</h7>"), SegmentType::Text);
+
+        // Short quotes merge with each other but not with the text around them
+        let segments = vec![
+            Segment::new(0, "Hi.".to_string(), Some("<p>Hi.</p>".to_string())),
+            Segment::new(1, "A.".to_string(), Some("<blockquote>A.</blockquote>".to_string())),
+            Segment::new(2, "B.".to_string(), Some("<blockquote>B.</blockquote>".to_string())),
+        ];
+        let merged = merge_short_segments(segments, 100);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[1].segment_type, SegmentType::Quote);
+        assert_eq!(merged[1].content, "A.\nB.");
+    }
+
     #[test]
     fn test_source_format_from_extension() {
         assert_eq!(SourceFormat::from_extension("epub"), Some(SourceFormat::Epub));
         assert_eq!(SourceFormat::from_extension("EPUB"), Some(SourceFormat::Epub));
+        assert_eq!(SourceFormat::from_extension("html"), Some(SourceFormat::Html));
+        assert_eq!(SourceFormat::from_extension("HTM"), Some(SourceFormat::Html));
         assert_eq!(SourceFormat::from_extension("md"), Some(SourceFormat::Markdown));
         assert_eq!(SourceFormat::from_extension("markdown"), Some(SourceFormat::Markdown));
         assert_eq!(SourceFormat::from_extension("txt"), Some(SourceFormat::Txt));
@@ -149,4 +841,51 @@ mod tests {
         assert_eq!(SourceFormat::from_extension("pdf"), None);
         assert_eq!(SourceFormat::from_extension("doc"), None);
     }
+
+    #[test]
+    fn test_detect_format_mislabeled_files() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        // An EPUB renamed to .txt is still an EPUB
+        let epub = dir.path().join("book.txt");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&epub).unwrap());
+        zip.start_file("mimetype", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"application/epub+zip").unwrap();
+        zip.finish().unwrap();
+        assert_eq!(detect_format(&epub).unwrap(), SourceFormat::Epub);
+
+        let other_zip = dir.path().join("archive.epub");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&other_zip).unwrap());
+        zip.start_file("readme.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.finish().unwrap();
+        assert!(matches!(detect_format(&other_zip), Err(ParseError::UnsupportedFormat(_))));
+
+        let pdf = write("paper.txt", b"%PDF-1.7\n%binary");
+        let error = detect_format(&pdf).unwrap_err();
+        assert!(matches!(error, ParseError::UnsupportedFormat(ref f) if f == "PDF"));
+
+        // Text keeps the kind its extension names
+        let notes = write("notes.md", b"\xEF\xBB\xBF# Notes");
+        assert_eq!(detect_format(&notes).unwrap(), SourceFormat::Markdown);
+        assert_eq!(detect_format(&write("story.txt", b"<html>")).unwrap(), SourceFormat::Txt);
+
+        // Text misnamed .epub, or unnamed, is told apart by its opening tag
+        let bom_text = write("book.epub", b"\xEF\xBB\xBFOnce upon a time.");
+        assert_eq!(detect_format(&bom_text).unwrap(), SourceFormat::Txt);
+        let page = write("page", b"\n  <!DOCTYPE html><html><body>Hi</body></html>");
+        assert_eq!(detect_format(&page).unwrap(), SourceFormat::Html);
+        assert!(detect_format(&write("image.epub", b"\x89PNG\r\n\x1a\n\0\0")).is_err());
+
+        assert!(matches!(
+            detect_format(&write("data.csv", b"a,b")),
+            Err(ParseError::UnsupportedFormat(ref f)) if f == "csv"
+        ));
+    }
 }
@@ -1,16 +1,24 @@
 //! Plain text document parser.
 //!
-//! Parses plain text (.txt) files into segments.
+//! Parses plain text (.txt) files into segments. Files are read line by line,
+//! so very large texts can be imported without holding them in memory.
 
-use std::fs;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read};
 use std::path::Path;
 
-use super::{ParseError, ParsedBook, Segment};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+use super::{
+    detect_language, BookMetadata, ParseError, ParseWarning, ParseWarningKind, ParsedBook, Segment,
+};
 
 /// Parse a plain text file into a ParsedBook.
 ///
-/// Reads the file as UTF-8 and splits content into segments at double
-/// newlines (blank lines). No HTML is generated for plain text segments.
+/// Decodes the file as [`stream_txt`] does and splits content into segments
+/// at double newlines (blank lines). No HTML is generated for plain text
+/// segments.
 ///
 /// # Arguments
 /// * `path` - Path to the text file
@@ -19,59 +27,178 @@ use super::{ParseError, ParsedBook, Segment};
 /// * `Ok(ParsedBook)` - Successfully parsed book
 /// * `Err(ParseError)` - If the file cannot be read
 pub fn parse_txt(path: &Path) -> Result<ParsedBook, ParseError> {
-    let content = fs::read_to_string(path)?;
-
-    // Title from filename (without extension)
-    let title = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("Untitled")
-        .to_string();
-
-    // Split into segments at double newlines
-    let segments = parse_content_to_segments(&content);
+    let mut stream = stream_txt(path)?;
+    let segments = stream.by_ref().collect::<Result<Vec<_>, _>>()?;
 
     Ok(ParsedBook {
-        title,
+        title: txt_title(path),
         author: None, // Plain text files don't have author metadata
+        language: detect_language(segments.iter().map(|s| s.content.as_str())),
         segments,
+        chapters: Vec::new(),
+        metadata: BookMetadata::default(),
+        warnings: stream.warnings(),
+        images: Vec::new(),
     })
 }
 
-/// Parse plain text content into segments.
+/// Title of a plain text book: its file name without the extension.
+pub fn txt_title(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Untitled")
+        .to_string()
+}
+
+/// Open a plain text file for reading segment by segment.
+///
+/// Yields the same segments as [`parse_txt`] without reading the whole file
+/// first. A byte order mark decides the encoding; otherwise lines are read
+/// as UTF-8, and any line that isn't valid UTF-8 as Windows-1252, the usual
+/// encoding of older Western text files.
+pub fn stream_txt(path: &Path) -> Result<TxtSegments<Box<dyn BufRead + Send>>, ParseError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let bom = Encoding::for_bom(reader.fill_buf()?);
+    let reader: Box<dyn BufRead + Send> = match bom {
+        None => Box::new(reader),
+        Some((encoding, bom_length)) if encoding == UTF_8 => {
+            reader.consume(bom_length);
+            Box::new(reader)
+        }
+        // UTF-16 lines can't be split at a newline byte, so the whole file
+        // is transcoded to UTF-8 first
+        Some((encoding, _)) => {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            let (text, _) = encoding.decode_with_bom_removal(&bytes);
+            Box::new(Cursor::new(text.into_owned().into_bytes()))
+        }
+    };
+    Ok(TxtSegments::new(reader))
+}
+
+/// Iterator over the segments of plain text read from a [`BufRead`].
 ///
-/// Segments are separated by double newlines (blank lines).
-/// Each segment's content is the plain text with whitespace normalized.
-fn parse_content_to_segments(content: &str) -> Vec<Segment> {
-    let mut segments = Vec::new();
-    let mut segment_index: u32 = 0;
-
-    // Normalize line endings and split on double newlines
-    let normalized = content.replace("\r\n", "\n").replace('\r', "\n");
-
-    // Split on double (or more) newlines
-    for block in normalized.split("\n\n") {
-        let trimmed = block.trim();
-
-        // Skip empty blocks
-        if trimmed.is_empty() {
-            continue;
+/// Segments are separated by blank lines. `\r\n` and lone `\r` count as line
+/// breaks, and each segment's whitespace is normalized.
+pub struct TxtSegments<R> {
+    reader: R,
+    buf: Vec<u8>,
+    block: String,
+    ready: VecDeque<Segment>,
+    next_index: u32,
+    done: bool,
+    /// Lines that weren't valid UTF-8 and were decoded as Windows-1252.
+    fallback_lines: usize,
+}
+
+impl<R: BufRead> TxtSegments<R> {
+    /// Read segments from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            block: String::new(),
+            ready: VecDeque::new(),
+            next_index: 0,
+            done: false,
+            fallback_lines: 0,
+        }
+    }
+
+    /// Warnings about the text read so far.
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        if self.fallback_lines == 0 {
+            return Vec::new();
         }
+        vec![ParseWarning::new(
+            ParseWarningKind::EncodingFallback,
+            format!(
+                "{} line(s) were not valid UTF-8 and were read as Windows-1252",
+                self.fallback_lines
+            ),
+        )]
+    }
 
-        // Normalize internal whitespace (collapse multiple spaces, convert newlines to spaces)
-        let normalized_text = normalize_whitespace(trimmed);
+    /// Finish the current block, queueing it as a segment unless it is only
+    /// whitespace.
+    fn flush(&mut self) {
+        let text = normalize_whitespace(&self.block);
+        self.block.clear();
 
-        if !normalized_text.is_empty() {
-            segments.push(Segment::new(
-                segment_index,
-                normalized_text,
-                None, // No HTML for plain text
-            ));
-            segment_index += 1;
+        if !text.is_empty() {
+            self.ready.push_back(Segment::new(self.next_index, text, None)); // No HTML for plain text
+            self.next_index += 1;
         }
     }
 
-    segments
+    /// Read the next line from the file into the current block.
+    ///
+    /// Returns false at the end of the file.
+    fn read_line(&mut self) -> Result<bool, ParseError> {
+        self.buf.clear();
+        if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+            return Ok(false);
+        }
+
+        if self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+        }
+        if self.buf.last() == Some(&b'\r') {
+            self.buf.pop();
+        }
+        let line = match String::from_utf8(std::mem::take(&mut self.buf)) {
+            Ok(line) => line,
+            Err(e) => {
+                self.fallback_lines += 1;
+                WINDOWS_1252.decode_without_bom_handling(e.as_bytes()).0.into_owned()
+            }
+        };
+
+        // Any `\r` left is an old Mac line break, so one read can hold
+        // several lines
+        for part in line.split('\r') {
+            if part.is_empty() {
+                self.flush();
+            } else {
+                if !self.block.is_empty() {
+                    self.block.push('\n');
+                }
+                self.block.push_str(part);
+            }
+        }
+
+        // Reuse the line's allocation for the next read
+        self.buf = line.into_bytes();
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for TxtSegments<R> {
+    type Item = Result<Segment, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(segment) = self.ready.pop_front() {
+                return Some(Ok(segment));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.read_line() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    self.flush();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
 
 /// Normalize whitespace in a text block.
@@ -89,6 +216,12 @@ fn normalize_whitespace(text: &str) -> String {
 mod tests {
     use super::*;
 
+    fn parse_content_to_segments(content: &str) -> Vec<Segment> {
+        TxtSegments::new(content.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
     #[test]
     fn test_normalize_whitespace() {
         assert_eq!(normalize_whitespace("hello  world"), "hello world");
@@ -144,4 +277,68 @@ mod tests {
             "This is a long paragraph that wraps across multiple lines."
         );
     }
+
+    #[test]
+    fn test_streaming_matches_whole_file_split() {
+        // How the parser worked before streaming: normalize line endings,
+        // then split the whole text on blank lines
+        let whole_file = |content: &str| -> Vec<String> {
+            content
+                .replace("\r\n", "\n")
+                .replace('\r', "\n")
+                .split("\n\n")
+                .map(normalize_whitespace)
+                .filter(|text| !text.is_empty())
+                .collect()
+        };
+
+        let inputs = [
+            "",
+            "\n\n\n",
+            "One.",
+            "One.\n",
+            "  One.\n  \nstill one.\n\nTwo.",
+            "One.\r\rTwo.\rstill two.\r",
+            "One.\r\r\nTwo.\n\r\nThree.",
+            "One.\n\n\nTwo.\n\n\n\nThree.\n\n",
+            "\u{feff}Byte order mark.\n\n\tTabbed.",
+        ];
+        for input in inputs {
+            let streamed: Vec<String> =
+                parse_content_to_segments(input).into_iter().map(|s| s.content).collect();
+            assert_eq!(streamed, whole_file(input), "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_txt_encodings() {
+        let dir = tempfile::tempdir().unwrap();
+        let parse = |name: &str, bytes: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            let book = parse_txt(&path).unwrap();
+            let contents: Vec<String> = book.segments.into_iter().map(|s| s.content).collect();
+            (contents, book.warnings)
+        };
+
+        // Latin-1 lines are read as Windows-1252, with a warning
+        let latin1 = b"Caf\xe9 cr\xe8me.\n\nPlain.\n\n\x93Quoted.\x94";
+        let (contents, warnings) = parse("latin1.txt", latin1);
+        assert_eq!(contents, vec!["Café crème.", "Plain.", "\u{201c}Quoted.\u{201d}"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, ParseWarningKind::EncodingFallback);
+        assert!(warnings[0].message.starts_with("2 line(s)"));
+
+        let (contents, warnings) = parse("utf8.txt", "\u{feff}Café.\n\nNaïve.".as_bytes());
+        assert_eq!(contents, vec!["Café.", "Naïve."]);
+        assert!(warnings.is_empty());
+
+        let utf16: Vec<u8> = [0xfe, 0xff]
+            .into_iter()
+            .chain("Café.\r\n\r\nNaïve.".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        let (contents, warnings) = parse("utf16.txt", &utf16);
+        assert_eq!(contents, vec!["Café.", "Naïve."]);
+        assert!(warnings.is_empty());
+    }
 }
@@ -0,0 +1,155 @@
+//! Pronunciation rules applied to text before narration.
+//!
+//! Users supply replacements for names, acronyms and numbers the TTS engine
+//! gets wrong. Rules only change what is sent to the engine; the stored
+//! segment text, and the markers that point at it, are unchanged.
+
+use std::borrow::Cow;
+
+use regex::Regex;
+
+use crate::models::Pronunciation;
+
+/// How a rule finds the text it replaces.
+#[derive(Debug)]
+enum Matcher {
+    /// Exact text, matched only as a whole word where it starts or ends
+    /// with a letter or digit.
+    Literal(String),
+    /// Regular expression; the replacement may use `$1`-style captures.
+    Regex(Regex),
+}
+
+/// A single compiled replacement rule.
+#[derive(Debug)]
+struct Rule {
+    matcher: Matcher,
+    replacement: String,
+}
+
+/// Compiled pronunciation rules, applied in order.
+#[derive(Debug, Default)]
+pub struct PronunciationRules {
+    rules: Vec<Rule>,
+}
+
+impl PronunciationRules {
+    /// Compile dictionary entries in the order given.
+    pub fn compile(entries: &[Pronunciation]) -> Result<Self, regex::Error> {
+        let rules = entries
+            .iter()
+            .filter(|entry| !entry.pattern.is_empty())
+            .map(|entry| {
+                let matcher = if entry.is_regex {
+                    Matcher::Regex(Regex::new(&entry.pattern)?)
+                } else {
+                    Matcher::Literal(entry.pattern.clone())
+                };
+                Ok(Rule {
+                    matcher,
+                    replacement: entry.replacement.clone(),
+                })
+            })
+            .collect::<Result<_, regex::Error>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Whether there are no rules to apply.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Apply every rule in turn, each to the output of the one before.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for rule in &self.rules {
+            let replaced = match &rule.matcher {
+                Matcher::Literal(pattern) => replace_words(&text, pattern, &rule.replacement),
+                Matcher::Regex(regex) => match regex.replace_all(&text, rule.replacement.as_str()) {
+                    Cow::Borrowed(_) => None,
+                    Cow::Owned(replaced) => Some(replaced),
+                },
+            };
+            if let Some(replaced) = replaced {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+/// Replace whole-word occurrences of `pattern`, or None if there are none.
+///
+/// An occurrence only counts if it isn't glued to a letter or digit on a
+/// side where the pattern itself starts or ends with one, so "GNU" doesn't
+/// match inside "GNUstep" but "Dr." still matches before "Smith".
+fn replace_words(text: &str, pattern: &str, replacement: &str) -> Option<String> {
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let check_start = is_word(pattern.chars().next());
+    let check_end = is_word(pattern.chars().next_back());
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, _) in text.match_indices(pattern) {
+        let end = start + pattern.len();
+        if (check_start && is_word(text[..start].chars().next_back()))
+            || (check_end && is_word(text[end..].chars().next()))
+        {
+            continue;
+        }
+        result.push_str(&text[copied..start]);
+        result.push_str(replacement);
+        copied = end;
+    }
+
+    if copied == 0 {
+        return None;
+    }
+    result.push_str(&text[copied..]);
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pattern: &str, replacement: &str, is_regex: bool) -> Pronunciation {
+        Pronunciation {
+            id: 0,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            is_regex,
+        }
+    }
+
+    #[test]
+    fn test_literal_rules_match_whole_words() {
+        let rules = PronunciationRules::compile(&[
+            entry("Dr.", "Doctor", false),
+            entry("GNU", "gnoo", false),
+        ])
+        .unwrap();
+
+        assert_eq!(rules.apply("Dr. Stallman wrote GNU."), "Doctor Stallman wrote gnoo.");
+        assert_eq!(rules.apply("GNUstep and GNU/Linux"), "GNUstep and gnoo/Linux");
+        assert!(matches!(rules.apply("Nothing here"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_rules_apply_in_order() {
+        let rules = PronunciationRules::compile(&[
+            entry(r"\b(\d{2})(\d{2})\b", "$1 $2", true),
+            entry("19", "nineteen", false),
+            entry("84", "eighty-four", false),
+        ])
+        .unwrap();
+
+        assert_eq!(rules.apply("In 1984."), "In nineteen eighty-four.");
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected() {
+        assert!(PronunciationRules::compile(&[entry("(", "", true)]).is_err());
+    }
+}
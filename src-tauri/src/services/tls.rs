@@ -0,0 +1,235 @@
+//! TLS for the sync server.
+//!
+//! The sync server presents a self-signed certificate generated on first
+//! run and kept in the data directory. There's no CA to vouch for it, so
+//! clients pin it instead: the server advertises the certificate's SHA-256
+//! fingerprint in its mDNS TXT record, and clients only accept a
+//! certificate with that fingerprint.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// File name of the sync server's certificate, DER encoded.
+const CERT_FILE: &str = "sync-cert.der";
+
+/// File name of the sync server's private key, PKCS#8 DER encoded.
+const KEY_FILE: &str = "sync-key.der";
+
+/// Errors that can occur setting up TLS.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Failed to generate certificate: {0}")]
+    Generation(#[from] rcgen::Error),
+
+    #[error("Invalid TLS configuration: {0}")]
+    Config(#[from] rustls::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// The sync server's certificate and private key.
+pub struct ServerIdentity {
+    cert_der: Vec<u8>,
+    key_der: Vec<u8>,
+}
+
+impl ServerIdentity {
+    /// Load the identity stored in `dir`, generating and saving a new one
+    /// if there isn't one yet.
+    pub fn load_or_create(dir: &Path, server_name: &str) -> Result<Self, TlsError> {
+        let cert_path = dir.join(CERT_FILE);
+        let key_path = dir.join(KEY_FILE);
+
+        if cert_path.is_file() && key_path.is_file() {
+            return Ok(Self {
+                cert_der: std::fs::read(&cert_path)?,
+                key_der: std::fs::read(&key_path)?,
+            });
+        }
+
+        let certified = rcgen::generate_simple_self_signed(vec![
+            server_name.to_string(),
+            "localhost".to_string(),
+        ])?;
+        let identity = Self {
+            cert_der: certified.cert.der().to_vec(),
+            key_der: certified.key_pair.serialize_der(),
+        };
+
+        write_private(&key_path, &identity.key_der)?;
+        std::fs::write(&cert_path, &identity.cert_der)?;
+        log::info!("Generated sync certificate {}", identity.fingerprint());
+
+        Ok(identity)
+    }
+
+    /// SHA-256 fingerprint of the certificate, as advertised to clients.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.cert_der)
+    }
+
+    /// Server configuration presenting this identity.
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, TlsError> {
+        let mut config = rustls::ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(self.cert_der.clone())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.key_der.clone())),
+            )?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+/// Write a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// Lowercase hex SHA-256 of a DER certificate.
+pub fn fingerprint(cert_der: &[u8]) -> String {
+    Sha256::digest(cert_der)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Crypto provider shared by the server and client configurations.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Accepts a server certificate by fingerprint instead of by CA chain.
+///
+/// With an expected fingerprint only that certificate is accepted. Without
+/// one, any certificate is accepted and its fingerprint recorded, so a
+/// server reached by address can be pinned from then on.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    expected: Option<String>,
+    seen: Mutex<Option<String>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertVerifier {
+    /// Verifier accepting only the certificate with `expected`'s
+    /// fingerprint, or trusting the first one presented if it's None.
+    pub fn new(expected: Option<String>) -> Self {
+        Self {
+            expected: expected.map(|fp| fp.to_ascii_lowercase()),
+            seen: Mutex::new(None),
+            provider: provider(),
+        }
+    }
+
+    /// Fingerprint of the certificate the server last presented.
+    pub fn seen(&self) -> Option<String> {
+        self.seen.lock().unwrap().clone()
+    }
+
+    /// Client configuration using this verifier.
+    pub fn client_config(self: Arc<Self>) -> Result<rustls::ClientConfig, TlsError> {
+        Ok(rustls::ClientConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(self)
+            .with_no_client_auth())
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = fingerprint(end_entity);
+        *self.seen.lock().unwrap() = Some(presented.clone());
+
+        match &self.expected {
+            Some(expected) if *expected != presented => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let first = ServerIdentity::load_or_create(dir.path(), "desk").unwrap();
+        let second = ServerIdentity::load_or_create(dir.path(), "desk").unwrap();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        assert_eq!(first.fingerprint().len(), 64);
+        assert!(first.server_config().is_ok());
+    }
+
+    #[test]
+    fn test_verifier_pins_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = ServerIdentity::load_or_create(dir.path(), "desk").unwrap();
+        let cert = CertificateDer::from(identity.cert_der.clone());
+        let name = ServerName::try_from("desk").unwrap();
+
+        let verify = |verifier: &PinnedCertVerifier| {
+            verifier.verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+        };
+
+        let pinned = PinnedCertVerifier::new(Some(identity.fingerprint().to_uppercase()));
+        assert!(verify(&pinned).is_ok());
+
+        let wrong = PinnedCertVerifier::new(Some("00".repeat(32)));
+        assert!(verify(&wrong).is_err());
+        assert_eq!(wrong.seen(), Some(identity.fingerprint()));
+
+        let first_use = PinnedCertVerifier::new(None);
+        assert!(verify(&first_use).is_ok());
+        assert_eq!(first_use.seen(), Some(identity.fingerprint()));
+    }
+}
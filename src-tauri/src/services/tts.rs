@@ -3,13 +3,44 @@
 //! This service handles communication with the Chatterbox TTS server
 //! and provides utilities for audio generation and manipulation.
 
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
 use reqwest::Client;
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::models::SampleInfo;
+
 /// Default Chatterbox server URL.
 pub const CHATTERBOX_URL: &str = "http://localhost:60001";
 
+/// Exaggeration used when no setting overrides it.
+pub const DEFAULT_EXAG: f64 = 0.3;
+/// CFG weight used when no setting overrides it.
+pub const DEFAULT_CFG: f64 = 0.5;
+/// Temperature used when no setting overrides it.
+pub const DEFAULT_TEMP: f64 = 0.8;
+
+/// Chatterbox sampling parameters for a generation request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatterboxParams {
+    pub exag: f32,
+    pub cfg: f32,
+    pub temp: f32,
+}
+
+impl Default for ChatterboxParams {
+    fn default() -> Self {
+        Self {
+            exag: DEFAULT_EXAG as f32,
+            cfg: DEFAULT_CFG as f32,
+            temp: DEFAULT_TEMP as f32,
+        }
+    }
+}
+
 /// Errors that can occur during TTS operations.
 #[derive(Debug, Error)]
 pub enum TtsError {
@@ -37,6 +68,9 @@ pub enum TtsError {
 pub struct ChatterboxRequest {
     pub text: String,
     pub voice: String,
+    /// Language of the text, for models that support more than one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
     pub exag: f32,
     pub cfg: f32,
     pub temp: f32,
@@ -72,6 +106,11 @@ impl TtsService {
         }
     }
 
+    /// Get the configured server URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     /// Check if the Chatterbox server is available.
     pub async fn is_available(&self) -> bool {
         match self.client.get(&self.base_url).send().await {
@@ -85,9 +124,10 @@ impl TtsService {
     /// # Arguments
     /// * `text` - The text to convert to speech
     /// * `voice_sample` - Path/name of the voice sample file (e.g., "voice-name.wav")
-    /// * `exag` - Exaggeration parameter (default: 0.3)
-    /// * `cfg` - CFG parameter (default: 0.5)
-    /// * `temp` - Temperature parameter (default: 0.8)
+    /// * `language` - Language of the text (e.g., "en" or "fra"), if known
+    /// * `exag` - Exaggeration parameter (default: [`DEFAULT_EXAG`])
+    /// * `cfg` - CFG parameter (default: [`DEFAULT_CFG`])
+    /// * `temp` - Temperature parameter (default: [`DEFAULT_TEMP`])
     ///
     /// # Returns
     /// WAV audio data as bytes
@@ -95,6 +135,7 @@ impl TtsService {
         &self,
         text: &str,
         voice_sample: &str,
+        language: Option<&str>,
         exag: f32,
         cfg: f32,
         temp: f32,
@@ -102,6 +143,7 @@ impl TtsService {
         let request = ChatterboxRequest {
             text: text.to_string(),
             voice: voice_sample.to_string(),
+            language: language.map(str::to_string),
             exag,
             cfg,
             temp,
@@ -179,22 +221,8 @@ impl TtsService {
         let mut all_audio_data: Vec<u8> = Vec::new();
 
         for (i, segment) in segments.iter().enumerate() {
-            let segment_info = parse_wav_header(segment).map_err(|e| {
-                TtsError::ConcatenationError(format!("Invalid WAV in segment {}: {}", i, e))
-            })?;
-
-            // Verify format compatibility
-            if segment_info.channels != wav_info.channels
-                || segment_info.sample_rate != wav_info.sample_rate
-                || segment_info.bits_per_sample != wav_info.bits_per_sample
-            {
-                return Err(TtsError::ConcatenationError(format!(
-                    "Audio format mismatch in segment {}: expected {}ch/{}Hz/{}bit, got {}ch/{}Hz/{}bit",
-                    i,
-                    wav_info.channels, wav_info.sample_rate, wav_info.bits_per_sample,
-                    segment_info.channels, segment_info.sample_rate, segment_info.bits_per_sample
-                )));
-            }
+            let segment_info = parse_segment_header(segment, i)?;
+            check_same_format(&wav_info, &segment_info, i)?;
 
             // Extract audio data (skip header)
             all_audio_data.extend_from_slice(&segment[segment_info.data_offset..]);
@@ -301,14 +329,214 @@ fn parse_wav_header(data: &[u8]) -> Result<WavInfo, TtsError> {
     ))
 }
 
-/// Build a WAV file from format info and audio data.
-fn build_wav_file(info: &WavInfo, audio_data: &[u8]) -> Result<Vec<u8>, TtsError> {
+/// Parse the header of the `index`th segment being concatenated.
+fn parse_segment_header(segment: &[u8], index: usize) -> Result<WavInfo, TtsError> {
+    parse_wav_header(segment).map_err(|e| {
+        TtsError::ConcatenationError(format!("Invalid WAV in segment {}: {}", index, e))
+    })
+}
+
+/// Verify the `index`th segment can be concatenated after the first.
+fn check_same_format(first: &WavInfo, segment: &WavInfo, index: usize) -> Result<(), TtsError> {
+    if segment.channels != first.channels
+        || segment.sample_rate != first.sample_rate
+        || segment.bits_per_sample != first.bits_per_sample
+    {
+        return Err(TtsError::ConcatenationError(format!(
+            "Audio format mismatch in segment {}: expected {}ch/{}Hz/{}bit, got {}ch/{}Hz/{}bit",
+            index,
+            first.channels, first.sample_rate, first.bits_per_sample,
+            segment.channels, segment.sample_rate, segment.bits_per_sample
+        )));
+    }
+    Ok(())
+}
+
+/// Bytes read from the start of a WAV file to find its data chunk.
+const WAV_HEADER_READ_SIZE: u64 = 4096;
+
+/// Extract the audio between `start` and `end` seconds of a WAV file as a
+/// standalone WAV, reading only the header and that range of the file.
+///
+/// Times are rounded to whole frames and clamped to the audio, so a range
+/// running past the end of the file returns the audio that is there.
+pub fn read_wav_slice(path: &Path, start: f64, end: f64) -> Result<Vec<u8>, TtsError> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut header = Vec::new();
+    (&mut file).take(WAV_HEADER_READ_SIZE).read_to_end(&mut header)?;
+    let info = parse_wav_header(&header)?;
+
+    let frame_size = (info.channels as u64 * (info.bits_per_sample as u64 / 8)).max(1);
+    let total_frames = file_len.saturating_sub(info.data_offset as u64) / frame_size;
+    let to_frame = |seconds: f64| {
+        ((seconds.max(0.0) * info.sample_rate as f64).round() as u64).min(total_frames)
+    };
+    let first = to_frame(start);
+    let last = to_frame(end).max(first);
+
+    let mut audio_data = vec![0u8; ((last - first) * frame_size) as usize];
+    file.seek(SeekFrom::Start(info.data_offset as u64 + first * frame_size))?;
+    file.read_exact(&mut audio_data)?;
+
+    build_wav_file(&WavInfo { data_offset: 44, ..info }, &audio_data)
+}
+
+/// Largest data chunk a WAV file can describe, since the RIFF size is 32-bit.
+const MAX_WAV_DATA_SIZE: u64 = u32::MAX as u64 - 36;
+
+/// Replace the audio between `start` and `end` seconds of a WAV file with
+/// `replacement`, which must have the same sample rate and channel count.
+/// A replacement at another bit depth is requantized to match, as segments
+/// are synthesized at 16 bits but narration may be saved at 24.
+///
+/// Times are rounded to whole frames and clamped as in [`read_wav_slice`].
+/// The spliced audio is written to `output`, copying the untouched audio
+/// through rather than loading it, and the original is left as it was, so
+/// the caller decides when to swap the result in. Returns the new duration
+/// in seconds.
+pub fn splice_wav(
+    path: &Path,
+    output: &Path,
+    start: f64,
+    end: f64,
+    replacement: &[u8],
+) -> Result<f64, TtsError> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut header = Vec::new();
+    (&mut file).take(WAV_HEADER_READ_SIZE).read_to_end(&mut header)?;
+    let info = parse_wav_header(&header)?;
+    let replacement = requantize_wav(replacement, info.bits_per_sample)?;
+    let replacement_info = parse_wav_header(&replacement)?;
+    if replacement_info.channels != info.channels
+        || replacement_info.sample_rate != info.sample_rate
+        || replacement_info.bits_per_sample != info.bits_per_sample
+    {
+        return Err(TtsError::ConcatenationError(format!(
+            "Audio format mismatch: expected {}ch/{}Hz/{}bit, got {}ch/{}Hz/{}bit",
+            info.channels, info.sample_rate, info.bits_per_sample,
+            replacement_info.channels, replacement_info.sample_rate, replacement_info.bits_per_sample
+        )));
+    }
+
+    let frame_size = (info.channels as u64 * (info.bits_per_sample as u64 / 8)).max(1);
+    let total_frames = file_len.saturating_sub(info.data_offset as u64) / frame_size;
+    let to_frame = |seconds: f64| {
+        ((seconds.max(0.0) * info.sample_rate as f64).round() as u64).min(total_frames)
+    };
+    let first = to_frame(start);
+    let last = to_frame(end).max(first);
+
+    let inserted = &replacement[replacement_info.data_offset..];
+    let data_size = (total_frames - (last - first)) * frame_size + inserted.len() as u64;
+    if data_size > MAX_WAV_DATA_SIZE {
+        return Err(TtsError::ConcatenationError(
+            "Audio is too long for a single WAV file".to_string(),
+        ));
+    }
+
+    let written = (|| -> Result<(), TtsError> {
+        let mut output = BufWriter::new(File::create(output)?);
+        output.write_all(&wav_header(&info, data_size as u32))?;
+
+        file.seek(SeekFrom::Start(info.data_offset as u64))?;
+        std::io::copy(&mut (&mut file).take(first * frame_size), &mut output)?;
+        output.write_all(inserted)?;
+        file.seek(SeekFrom::Start(info.data_offset as u64 + last * frame_size))?;
+        std::io::copy(&mut (&mut file).take((total_frames - last) * frame_size), &mut output)?;
+
+        output.flush()?;
+        Ok(())
+    })();
+    drop(file);
+
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(output);
+        return Err(e);
+    }
+
+    Ok((data_size / frame_size) as f64 / info.sample_rate as f64)
+}
+
+/// Concatenates WAV segments straight into a file, so only one segment is
+/// held in memory at a time however long the narration.
+///
+/// The header is written with the first segment using placeholder sizes,
+/// which [`WavWriter::finish`] patches once every segment is appended.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    info: Option<WavInfo>,
+    data_size: u64,
+    segments: usize,
+}
+
+impl WavWriter {
+    /// Create (or truncate) the output file.
+    pub fn create(path: &Path) -> Result<Self, TtsError> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            info: None,
+            data_size: 0,
+            segments: 0,
+        })
+    }
+
+    /// Append a segment's audio data; its format must match the first's.
+    pub fn append(&mut self, segment: &[u8]) -> Result<(), TtsError> {
+        let index = self.segments;
+        let segment_info = parse_segment_header(segment, index)?;
+
+        match &self.info {
+            Some(info) => check_same_format(info, &segment_info, index)?,
+            None => {
+                self.file.write_all(&wav_header(&segment_info, 0))?;
+                self.info = Some(segment_info.clone());
+            }
+        }
+
+        let audio_data = &segment[segment_info.data_offset..];
+        if self.data_size + audio_data.len() as u64 > MAX_WAV_DATA_SIZE {
+            return Err(TtsError::ConcatenationError(
+                "Audio is too long for a single WAV file".to_string(),
+            ));
+        }
+
+        self.file.write_all(audio_data)?;
+        self.data_size += audio_data.len() as u64;
+        self.segments += 1;
+        Ok(())
+    }
+
+    /// Patch the header with the final sizes and flush the file.
+    ///
+    /// Returns the duration of the written audio in seconds.
+    pub fn finish(self) -> Result<f64, TtsError> {
+        let Some(info) = self.info else {
+            return Err(TtsError::ConcatenationError(
+                "No audio segments provided".to_string(),
+            ));
+        };
+
+        let mut file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&wav_header(&info, self.data_size as u32))?;
+        file.flush()?;
+
+        let frame_size = (info.channels as u64 * (info.bits_per_sample as u64 / 8)).max(1);
+        Ok((self.data_size / frame_size) as f64 / info.sample_rate as f64)
+    }
+}
+
+/// Canonical 44-byte header for PCM data of `data_size` bytes.
+fn wav_header(info: &WavInfo, data_size: u32) -> Vec<u8> {
     let byte_rate = info.sample_rate * info.channels as u32 * info.bits_per_sample as u32 / 8;
     let block_align = info.channels * info.bits_per_sample / 8;
-    let data_size = audio_data.len() as u32;
     let file_size = 36 + data_size; // RIFF size = file size - 8
 
-    let mut output = Vec::with_capacity(44 + audio_data.len());
+    let mut output = Vec::with_capacity(44);
 
     // RIFF header
     output.extend_from_slice(b"RIFF");
@@ -328,11 +556,447 @@ fn build_wav_file(info: &WavInfo, audio_data: &[u8]) -> Result<Vec<u8>, TtsError
     // data chunk
     output.extend_from_slice(b"data");
     output.extend_from_slice(&data_size.to_le_bytes());
+
+    output
+}
+
+/// Build a WAV file from format info and audio data.
+fn build_wav_file(info: &WavInfo, audio_data: &[u8]) -> Result<Vec<u8>, TtsError> {
+    let mut output = wav_header(info, audio_data.len() as u32);
+    output.reserve(audio_data.len());
     output.extend_from_slice(audio_data);
 
     Ok(output)
 }
 
+/// Sample rate and channel count that narration audio is normalized to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Convert 16-bit PCM WAV audio to the target sample rate and channel count.
+///
+/// Channels are averaged down to mono (or mono duplicated up) and the result
+/// is linearly resampled. Audio already in the target format is returned
+/// unchanged without decoding.
+pub fn convert_wav(data: &[u8], target: AudioFormat) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if info.sample_rate == target.sample_rate && info.channels == target.channels {
+        return Ok(data.to_vec());
+    }
+
+    if info.audio_format != 1 || info.bits_per_sample != 16 {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot convert audio format {} at {} bits; only 16-bit PCM is supported",
+            info.audio_format, info.bits_per_sample
+        )));
+    }
+
+    if info.channels == 0 || info.sample_rate == 0 || target.channels == 0 || target.sample_rate == 0 {
+        return Err(TtsError::InvalidAudio("Zero channels or sample rate".to_string()));
+    }
+
+    let samples: Vec<i16> = data[info.data_offset..]
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+
+    // Split into channels, mixing down to mono first if the count changes
+    let channels = info.channels as usize;
+    let source: Vec<Vec<f32>> = if info.channels == target.channels {
+        (0..channels)
+            .map(|c| samples.chunks_exact(channels).map(|frame| frame[c] as f32).collect())
+            .collect()
+    } else {
+        let mono: Vec<f32> = samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / channels as f32)
+            .collect();
+        vec![mono; target.channels as usize]
+    };
+
+    let resampled: Vec<Vec<f32>> = source
+        .iter()
+        .map(|channel| resample_linear(channel, info.sample_rate, target.sample_rate))
+        .collect();
+
+    let frames = resampled.first().map_or(0, Vec::len);
+    let mut audio_data = Vec::with_capacity(frames * target.channels as usize * 2);
+    for i in 0..frames {
+        for channel in &resampled {
+            let sample = channel[i].round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            audio_data.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    let output_info = WavInfo {
+        channels: target.channels,
+        sample_rate: target.sample_rate,
+        bits_per_sample: 16,
+        audio_format: 1,
+        data_offset: 44,
+    };
+
+    build_wav_file(&output_info, &audio_data)
+}
+
+/// Bit depths narration audio can be saved at.
+pub const PCM_BIT_DEPTHS: [u16; 2] = [16, 24];
+
+/// Decode 16- or 24-bit little-endian PCM samples, keeping their scale.
+fn decode_pcm(data: &[u8], bits_per_sample: u16) -> Vec<i32> {
+    if bits_per_sample == 24 {
+        data.chunks_exact(3)
+            .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8)
+            .collect()
+    } else {
+        data.chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32)
+            .collect()
+    }
+}
+
+/// Append a 16- or 24-bit PCM sample, rounded and clamped to full scale.
+fn push_pcm_sample(output: &mut Vec<u8>, sample: f32, bits_per_sample: u16) {
+    if bits_per_sample == 24 {
+        let sample = sample.round().clamp(-8_388_608.0, 8_388_607.0) as i32;
+        output.extend_from_slice(&sample.to_le_bytes()[..3]);
+    } else {
+        let sample = sample.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        output.extend_from_slice(&sample.to_le_bytes());
+    }
+}
+
+/// Convert 16- or 24-bit PCM WAV audio to the other bit depth.
+///
+/// Widening pads each sample with zero low bits, so it's lossless; narrowing
+/// rounds to the nearest step. Neither changes the duration. Audio already at
+/// the target depth is returned unchanged without decoding.
+pub fn requantize_wav(data: &[u8], bits_per_sample: u16) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if info.bits_per_sample == bits_per_sample {
+        return Ok(data.to_vec());
+    }
+
+    if info.audio_format != 1
+        || !PCM_BIT_DEPTHS.contains(&info.bits_per_sample)
+        || !PCM_BIT_DEPTHS.contains(&bits_per_sample)
+    {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot requantize audio format {} from {} to {} bits; only 16- and 24-bit PCM are supported",
+            info.audio_format, info.bits_per_sample, bits_per_sample
+        )));
+    }
+
+    let scale = 2f32.powi(i32::from(bits_per_sample) - i32::from(info.bits_per_sample));
+    let samples = decode_pcm(&data[info.data_offset..], info.bits_per_sample);
+    let mut audio_data = Vec::with_capacity(samples.len() * bits_per_sample as usize / 8);
+    for sample in samples {
+        push_pcm_sample(&mut audio_data, sample as f32 * scale, bits_per_sample);
+    }
+
+    let output_info = WavInfo {
+        bits_per_sample,
+        data_offset: 44,
+        ..info
+    };
+
+    build_wav_file(&output_info, &audio_data)
+}
+
+/// Peak level, as a fraction of full scale, that [`normalize_peak`] targets (about -1 dBFS).
+pub const NORMALIZE_TARGET_PEAK: f32 = 0.89;
+
+/// Scale 16-bit PCM WAV audio so its loudest sample reaches `target_peak`
+/// (a fraction of full scale).
+///
+/// Applying this to every segment evens out loudness jumps between them.
+/// Silent audio is returned unchanged, and the gain never pushes samples
+/// past full scale.
+pub fn normalize_peak(data: &[u8], target_peak: f32) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if info.audio_format != 1 || info.bits_per_sample != 16 {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot normalize audio format {} at {} bits; only 16-bit PCM is supported",
+            info.audio_format, info.bits_per_sample
+        )));
+    }
+
+    let samples = &data[info.data_offset..];
+    let peak = samples
+        .chunks_exact(2)
+        .map(|b| (i16::from_le_bytes([b[0], b[1]]) as i32).abs())
+        .max()
+        .unwrap_or(0);
+
+    if peak == 0 {
+        return Ok(data.to_vec());
+    }
+
+    let gain = target_peak.clamp(0.0, 1.0) * i16::MAX as f32 / peak as f32;
+
+    let mut output = data.to_vec();
+    for chunk in output[info.data_offset..].chunks_exact_mut(2) {
+        let sample = i16::from_le_bytes([chunk[0], chunk[1]]) as f32 * gain;
+        let sample = sample.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        chunk.copy_from_slice(&sample.to_le_bytes());
+    }
+
+    Ok(output)
+}
+
+/// Audio kept on each side of the speech when trimming silence, in
+/// milliseconds, so soft onsets and trailing consonants aren't clipped.
+const TRIM_MARGIN_MS: u32 = 20;
+
+/// Trim leading and trailing silence from 16-bit PCM WAV audio.
+///
+/// A frame is silent when every channel is below `threshold` (a fraction of
+/// full scale). A margin of [`TRIM_MARGIN_MS`] is kept either side of the
+/// first and last audible frames. Audio that is silent throughout trims to
+/// no frames at all.
+pub fn trim_silence(data: &[u8], threshold: f32) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if info.audio_format != 1 || info.bits_per_sample != 16 {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot trim audio format {} at {} bits; only 16-bit PCM is supported",
+            info.audio_format, info.bits_per_sample
+        )));
+    }
+
+    let frame_size = info.channels.max(1) as usize * 2;
+    let frames: Vec<&[u8]> = data[info.data_offset..].chunks_exact(frame_size).collect();
+    let limit = (threshold.clamp(0.0, 1.0) * i16::MAX as f32) as i32;
+    let is_audible = |frame: &&[u8]| {
+        frame
+            .chunks_exact(2)
+            .any(|b| (i16::from_le_bytes([b[0], b[1]]) as i32).abs() > limit)
+    };
+
+    let (Some(first), Some(last)) = (
+        frames.iter().position(is_audible),
+        frames.iter().rposition(is_audible),
+    ) else {
+        return build_wav_file(&info, &[]);
+    };
+
+    let margin = (info.sample_rate as u64 * TRIM_MARGIN_MS as u64 / 1000) as usize;
+    let start = first.saturating_sub(margin);
+    let end = (last + 1 + margin).min(frames.len());
+
+    build_wav_file(&info, &frames[start..end].concat())
+}
+
+/// Append `gap_ms` milliseconds of silence to 16-bit PCM WAV audio.
+pub fn append_silence(data: &[u8], gap_ms: u32) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if info.audio_format != 1 || info.bits_per_sample != 16 {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot pad audio format {} at {} bits; only 16-bit PCM is supported",
+            info.audio_format, info.bits_per_sample
+        )));
+    }
+
+    let frame_size = info.channels.max(1) as usize * 2;
+    let audio = &data[info.data_offset..];
+    let audio = &audio[..audio.len() - audio.len() % frame_size];
+    let gap_frames = (info.sample_rate as u64 * gap_ms as u64 / 1000) as usize;
+
+    let mut audio_data = Vec::with_capacity(audio.len() + gap_frames * frame_size);
+    audio_data.extend_from_slice(audio);
+    audio_data.resize(audio.len() + gap_frames * frame_size, 0);
+
+    build_wav_file(&info, &audio_data)
+}
+
+/// Linearly resample one channel of samples between two rates.
+fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let frames = (samples.len() as f64 / ratio).round() as usize;
+    let last = samples.len() - 1;
+
+    (0..frames)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = (position.floor() as usize).min(last);
+            let next = (index + 1).min(last);
+            let fraction = (position - index as f64) as f32;
+            samples[index] + (samples[next] - samples[index]) * fraction
+        })
+        .collect()
+}
+
+/// Length of each WSOLA analysis frame, in milliseconds.
+const STRETCH_FRAME_MS: u32 = 30;
+
+/// How far, in milliseconds, WSOLA may shift a frame from its nominal
+/// position to find the best waveform match.
+const STRETCH_TOLERANCE_MS: u32 = 8;
+
+/// Sample stride used when scoring candidate frame positions.
+const STRETCH_SEARCH_STRIDE: usize = 4;
+
+/// Change the speed of 16- or 24-bit PCM WAV audio without changing its pitch.
+///
+/// Uses WSOLA (waveform-similarity overlap-add): the output is built from
+/// overlapping windowed frames of the input, each taken from near its
+/// time-scaled position at the offset that best continues the previous
+/// frame. A `speed` of 2.0 halves the duration. Frame positions are chosen
+/// on a mono mix so stereo channels stay aligned.
+pub fn time_stretch_wav(data: &[u8], speed: f64) -> Result<Vec<u8>, TtsError> {
+    let info = parse_wav_header(data)?;
+
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err(TtsError::InvalidAudio(format!("Invalid playback speed: {}", speed)));
+    }
+
+    if (speed - 1.0).abs() < f64::EPSILON {
+        return Ok(data.to_vec());
+    }
+
+    if info.audio_format != 1 || !PCM_BIT_DEPTHS.contains(&info.bits_per_sample) {
+        return Err(TtsError::InvalidAudio(format!(
+            "Cannot time-stretch audio format {} at {} bits; only 16- and 24-bit PCM are supported",
+            info.audio_format, info.bits_per_sample
+        )));
+    }
+
+    if info.channels == 0 || info.sample_rate == 0 {
+        return Err(TtsError::InvalidAudio("Zero channels or sample rate".to_string()));
+    }
+
+    let channels = info.channels as usize;
+    let samples = decode_pcm(&data[info.data_offset..], info.bits_per_sample);
+    let source: Vec<Vec<f32>> = (0..channels)
+        .map(|c| samples.chunks_exact(channels).map(|frame| frame[c] as f32).collect())
+        .collect();
+    let guide: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / channels as f32)
+        .collect();
+
+    // Even frame length so consecutive frames overlap by exactly half
+    let frame = ((info.sample_rate * STRETCH_FRAME_MS / 1000) as usize).max(2) & !1;
+    let tolerance = (info.sample_rate * STRETCH_TOLERANCE_MS / 1000) as usize;
+    let out_len = (guide.len() as f64 / speed).round() as usize;
+
+    let positions = wsola_positions(&guide, speed, frame, tolerance, out_len);
+    let stretched: Vec<Vec<f32>> = source
+        .iter()
+        .map(|channel| overlap_add(channel, &positions, frame, out_len))
+        .collect();
+
+    let bytes_per_sample = info.bits_per_sample as usize / 8;
+    let mut audio_data = Vec::with_capacity(out_len * channels * bytes_per_sample);
+    for i in 0..out_len {
+        for channel in &stretched {
+            push_pcm_sample(&mut audio_data, channel[i], info.bits_per_sample);
+        }
+    }
+
+    let output_info = WavInfo {
+        data_offset: 44,
+        ..info
+    };
+
+    build_wav_file(&output_info, &audio_data)
+}
+
+/// Pick the input start position of each output frame for WSOLA.
+///
+/// Output frame `k` starts at `k * frame / 2`. Its input position is searched
+/// within `tolerance` of the time-scaled position for the best match with
+/// the natural continuation of the previous frame.
+fn wsola_positions(
+    guide: &[f32],
+    speed: f64,
+    frame: usize,
+    tolerance: usize,
+    out_len: usize,
+) -> Vec<usize> {
+    let hop = frame / 2;
+    let last_start = guide.len().saturating_sub(1);
+    let sample = |i: usize| guide.get(i).copied().unwrap_or(0.0);
+    let similarity = |a: usize, b: usize| -> f32 {
+        (0..frame)
+            .step_by(STRETCH_SEARCH_STRIDE)
+            .map(|i| sample(a + i) * sample(b + i))
+            .sum()
+    };
+
+    let frames = out_len.div_ceil(hop);
+    let mut positions = Vec::with_capacity(frames);
+    let mut previous = 0;
+
+    for k in 0..frames {
+        let nominal = (((k * hop) as f64 * speed).round() as usize).min(last_start);
+        let position = if k == 0 {
+            0
+        } else {
+            let natural = previous + hop;
+            let low = nominal.saturating_sub(tolerance);
+            let high = (nominal + tolerance).min(last_start);
+            let best_in = |range: std::iter::StepBy<std::ops::RangeInclusive<usize>>| {
+                range
+                    .map(|p| (p, similarity(p, natural)))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(nominal, |(p, _)| p)
+            };
+
+            // Coarse search, then refine around the best coarse candidate
+            let coarse = best_in((low..=high).step_by(STRETCH_SEARCH_STRIDE));
+            let fine_low = coarse.saturating_sub(STRETCH_SEARCH_STRIDE).max(low);
+            let fine_high = (coarse + STRETCH_SEARCH_STRIDE).min(high);
+            best_in((fine_low..=fine_high).step_by(1))
+        };
+
+        positions.push(position);
+        previous = position;
+    }
+
+    positions
+}
+
+/// Overlap-add Hann-windowed input frames at the given positions.
+fn overlap_add(samples: &[f32], positions: &[usize], frame: usize, out_len: usize) -> Vec<f32> {
+    let hop = frame / 2;
+    let window: Vec<f32> = (0..frame)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / frame as f32).cos())
+        .collect();
+
+    let mut output = vec![0.0f32; out_len + frame];
+    let mut weight = vec![0.0f32; out_len + frame];
+
+    for (k, &position) in positions.iter().enumerate() {
+        let start = k * hop;
+        for (i, &w) in window.iter().enumerate() {
+            let sample = samples.get(position + i).copied().unwrap_or(0.0);
+            output[start + i] += sample * w;
+            weight[start + i] += w;
+        }
+    }
+
+    output.truncate(out_len);
+    for (sample, &w) in output.iter_mut().zip(&weight) {
+        if w > 1e-3 {
+            *sample /= w;
+        }
+    }
+
+    output
+}
+
 /// Get the duration of WAV audio data in seconds.
 pub fn get_wav_duration(data: &[u8]) -> Result<f64, TtsError> {
     let info = parse_wav_header(data)?;
@@ -342,6 +1006,286 @@ pub fn get_wav_duration(data: &[u8]) -> Result<f64, TtsError> {
     Ok(samples as f64 / info.sample_rate as f64)
 }
 
+/// Probe an audio file's header to confirm it's decodable and read its format.
+///
+/// WAV files are read with the same header parser as narration; MP3, Ogg and
+/// FLAC go through symphonia, decoding the first packet and totting up the
+/// rest to get the duration. Symphonia has no Opus decoder, so Opus is
+/// measured from its Ogg pages instead. The extension picks the parser, so a
+/// mislabelled file is rejected rather than guessed at.
+pub fn probe_audio(path: &Path) -> Result<SampleInfo, TtsError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if extension == "wav" {
+        let data = std::fs::read(path)?;
+        let info = parse_wav_header(&data)?;
+        if info.channels == 0 || info.sample_rate == 0 || info.bits_per_sample < 8 {
+            return Err(TtsError::InvalidAudio("Unsupported WAV format".to_string()));
+        }
+        return Ok(SampleInfo {
+            duration: get_wav_duration(&data)?,
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+        });
+    }
+
+    if extension == "opus" {
+        return probe_opus(path);
+    }
+
+    probe_compressed(path, &extension)
+}
+
+/// Opus always decodes at 48 kHz, whatever rate the input had.
+const OPUS_SAMPLE_RATE: u32 = 48000;
+
+/// Probe an Ogg Opus file by reading its page headers.
+///
+/// The first packet must be an `OpusHead`. The duration comes from the last
+/// page's granule position, less the pre-skip, without decoding any audio.
+fn probe_opus(path: &Path) -> Result<SampleInfo, TtsError> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let invalid = |reason: &str| TtsError::InvalidAudio(format!("Invalid Opus file: {}", reason));
+
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut head: Option<(u32, u16, u16)> = None;
+    let mut last_granule: Option<u64> = None;
+
+    loop {
+        let mut header = [0u8; 27];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if &header[0..4] != b"OggS" {
+            return Err(invalid("not an Ogg page"));
+        }
+        let granule = u64::from_le_bytes(header[6..14].try_into().unwrap());
+        let serial = u32::from_le_bytes(header[14..18].try_into().unwrap());
+
+        let mut lacing = vec![0u8; header[26] as usize];
+        file.read_exact(&mut lacing)?;
+        let body_len: usize = lacing.iter().map(|&n| n as usize).sum();
+
+        match head {
+            None => {
+                let mut body = vec![0u8; body_len];
+                file.read_exact(&mut body)?;
+                if body.len() < 19 || &body[0..8] != b"OpusHead" {
+                    return Err(invalid("missing OpusHead"));
+                }
+                let channels = body[9] as u16;
+                let pre_skip = u16::from_le_bytes([body[10], body[11]]);
+                head = Some((serial, channels, pre_skip));
+            }
+            Some((opus_serial, _, _)) => {
+                // -1 marks a page on which no packet ends
+                if serial == opus_serial && granule != u64::MAX {
+                    last_granule = Some(granule);
+                }
+                file.seek(SeekFrom::Current(body_len as i64))?;
+            }
+        }
+    }
+
+    let (_, channels, pre_skip) = head.ok_or_else(|| invalid("empty file"))?;
+    let granule = last_granule.ok_or_else(|| invalid("no audio data"))?;
+    if channels == 0 {
+        return Err(invalid("no channels"));
+    }
+
+    Ok(SampleInfo {
+        duration: granule.saturating_sub(pre_skip as u64) as f64 / OPUS_SAMPLE_RATE as f64,
+        sample_rate: OPUS_SAMPLE_RATE,
+        channels,
+    })
+}
+
+/// A compressed audio file opened with symphonia, ready to decode its first
+/// audio track.
+struct CompressedTrack {
+    format: Box<dyn symphonia::core::formats::FormatReader>,
+    decoder: Box<dyn symphonia::core::codecs::Decoder>,
+    track_id: u32,
+    params: symphonia::core::codecs::CodecParameters,
+}
+
+fn symphonia_error(e: symphonia::core::errors::Error) -> TtsError {
+    TtsError::InvalidAudio(e.to_string())
+}
+
+/// Open a compressed audio file and a decoder for its first audio track.
+fn open_compressed(path: &Path, extension: &str) -> Result<CompressedTrack, TtsError> {
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let file = std::fs::File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(symphonia_error)?;
+    let format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| TtsError::InvalidAudio("No audio track found".to_string()))?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+
+    let decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .map_err(symphonia_error)?;
+
+    Ok(CompressedTrack { format, decoder, track_id, params })
+}
+
+/// Probe a compressed audio file with symphonia.
+fn probe_compressed(path: &Path, extension: &str) -> Result<SampleInfo, TtsError> {
+    use symphonia::core::errors::Error as SymphoniaError;
+
+    let CompressedTrack { mut format, mut decoder, track_id, params } =
+        open_compressed(path, extension)?;
+
+    let mut spec = None;
+    let mut frames: u64 = 0;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(symphonia_error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        if spec.is_none() {
+            spec = Some(*decoder.decode(&packet).map_err(symphonia_error)?.spec());
+        }
+        frames += packet.dur;
+    }
+
+    let spec = spec.ok_or_else(|| TtsError::InvalidAudio("No audio data found".to_string()))?;
+    if spec.rate == 0 {
+        return Err(TtsError::InvalidAudio("Missing sample rate".to_string()));
+    }
+
+    let frames = params.n_frames.unwrap_or(frames);
+    let duration = match params.time_base {
+        Some(time_base) => {
+            let time = time_base.calc_time(frames);
+            time.seconds as f64 + time.frac
+        }
+        None => frames as f64 / spec.rate as f64,
+    };
+
+    Ok(SampleInfo {
+        duration,
+        sample_rate: spec.rate,
+        channels: spec.channels.count() as u16,
+    })
+}
+
+/// Decode a compressed audio file (MP3, Ogg Vorbis, FLAC) to 16-bit PCM WAV.
+///
+/// Keeps the source's sample rate and channel count. Codecs symphonia can't
+/// decode, such as Opus, are rejected with `InvalidAudio`.
+pub fn transcode_to_wav(path: &Path) -> Result<Vec<u8>, TtsError> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::errors::Error as SymphoniaError;
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let CompressedTrack { mut format, mut decoder, track_id, .. } =
+        open_compressed(path, &extension)?;
+
+    let mut spec = None;
+    let mut audio_data = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(symphonia_error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet is skipped rather than failing the whole file
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(symphonia_error(e)),
+        };
+        let decoded_spec = *decoded.spec();
+        if *spec.get_or_insert(decoded_spec) != decoded_spec {
+            return Err(TtsError::InvalidAudio("Audio format changes mid-stream".to_string()));
+        }
+
+        let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, decoded_spec);
+        buffer.copy_interleaved_ref(decoded);
+        for sample in buffer.samples() {
+            audio_data.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    let spec = spec.ok_or_else(|| TtsError::InvalidAudio("No audio data found".to_string()))?;
+    if spec.rate == 0 || audio_data.is_empty() {
+        return Err(TtsError::InvalidAudio("No audio data found".to_string()));
+    }
+
+    let info = WavInfo {
+        channels: spec.channels.count() as u16,
+        sample_rate: spec.rate,
+        bits_per_sample: 16,
+        audio_format: 1,
+        data_offset: 44,
+    };
+    build_wav_file(&info, &audio_data)
+}
+
+/// Read a voice sample as 16-bit PCM WAV, the format Chatterbox reads.
+///
+/// 16-bit PCM WAV is returned as it is and 24-bit PCM is requantized; other
+/// WAV encodings (8- or 32-bit, floating point) and compressed formats are
+/// decoded with [`transcode_to_wav`].
+pub fn voice_sample_wav(path: &Path) -> Result<Vec<u8>, TtsError> {
+    let is_wav = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+    if !is_wav {
+        return transcode_to_wav(path);
+    }
+
+    let data = std::fs::read(path)?;
+    let info = parse_wav_header(&data)?;
+    if info.audio_format == 1 && PCM_BIT_DEPTHS.contains(&info.bits_per_sample) {
+        requantize_wav(&data, 16)
+    } else {
+        transcode_to_wav(path)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -413,6 +1357,292 @@ mod tests {
         assert!((duration - 1.0).abs() < 0.001);
     }
 
+    #[test]
+    fn test_convert_wav_fast_path() {
+        let wav = create_test_wav(1000, 24000, 1);
+        let target = AudioFormat { sample_rate: 24000, channels: 1 };
+
+        assert_eq!(convert_wav(&wav, target).unwrap(), wav);
+    }
+
+    #[test]
+    fn test_convert_wav_resamples_and_downmixes() {
+        let wav = create_test_wav(44100, 44100, 2); // 1 second of stereo
+        let target = AudioFormat { sample_rate: 22050, channels: 1 };
+
+        let converted = convert_wav(&wav, target).unwrap();
+        let info = parse_wav_header(&converted).unwrap();
+
+        assert_eq!(info.sample_rate, 22050);
+        assert_eq!(info.channels, 1);
+        assert!((get_wav_duration(&converted).unwrap() - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_convert_wav_averages_channels() {
+        // One stereo frame: left 1000, right -200
+        let mut wav = create_test_wav(1, 24000, 2);
+        let offset = wav.len() - 4;
+        wav[offset..offset + 2].copy_from_slice(&1000i16.to_le_bytes());
+        wav[offset + 2..].copy_from_slice(&(-200i16).to_le_bytes());
+
+        let converted = convert_wav(&wav, AudioFormat { sample_rate: 24000, channels: 1 }).unwrap();
+        let sample = i16::from_le_bytes([converted[44], converted[45]]);
+
+        assert_eq!(sample, 400);
+    }
+
+    #[test]
+    fn test_converted_segments_concatenate() {
+        let service = TtsService::new();
+        let target = AudioFormat { sample_rate: 24000, channels: 1 };
+
+        let wav1 = convert_wav(&create_test_wav(44100, 44100, 2), target).unwrap();
+        let wav2 = convert_wav(&create_test_wav(22050, 22050, 1), target).unwrap();
+
+        let combined = service.concatenate_audio(vec![wav1, wav2]).unwrap();
+        assert!((get_wav_duration(&combined).unwrap() - 2.0).abs() < 0.001);
+    }
+
+    /// Build a mono 16-bit WAV from explicit samples.
+    fn create_wav_from_samples(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+        let info = WavInfo {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            audio_format: 1,
+            data_offset: 44,
+        };
+        let audio_data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        build_wav_file(&info, &audio_data).unwrap()
+    }
+
+    /// Number of sign changes in a mono 16-bit WAV, a rough pitch measure.
+    fn zero_crossings(wav: &[u8]) -> usize {
+        let samples: Vec<i16> = wav[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        samples.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count()
+    }
+
+    fn create_sine_wav(frequency: f32, seconds: f32, sample_rate: u32) -> Vec<u8> {
+        let samples: Vec<i16> = (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                ((2.0 * std::f32::consts::PI * frequency * t).sin() * 10000.0) as i16
+            })
+            .collect();
+        create_wav_from_samples(&samples, sample_rate)
+    }
+
+    #[test]
+    fn test_requantize_wav() {
+        let wav = create_wav_from_samples(&[0, 1, -1, i16::MAX, i16::MIN], 22050);
+
+        let wide = requantize_wav(&wav, 24).unwrap();
+        let info = parse_wav_header(&wide).unwrap();
+        assert_eq!(info.bits_per_sample, 24);
+        assert_eq!(info.sample_rate, 22050);
+        assert_eq!(u32::from_le_bytes([wide[28], wide[29], wide[30], wide[31]]), 22050 * 3);
+        assert_eq!(u16::from_le_bytes([wide[32], wide[33]]), 3);
+        assert_eq!(
+            decode_pcm(&wide[44..], 24),
+            vec![0, 256, -256, i16::MAX as i32 * 256, i16::MIN as i32 * 256]
+        );
+        let duration = get_wav_duration(&wav).unwrap();
+        assert!((get_wav_duration(&wide).unwrap() - duration).abs() < 1e-12);
+
+        // Widening is lossless, and the same depth is left alone
+        assert_eq!(requantize_wav(&wide, 16).unwrap(), wav);
+        assert_eq!(requantize_wav(&wav, 16).unwrap(), wav);
+        assert!(requantize_wav(&wav, 32).is_err());
+    }
+
+    #[test]
+    fn test_time_stretch_24_bit() {
+        let wav = requantize_wav(&create_sine_wav(220.0, 2.0, 24000), 24).unwrap();
+
+        let faster = time_stretch_wav(&wav, 2.0).unwrap();
+
+        assert_eq!(parse_wav_header(&faster).unwrap().bits_per_sample, 24);
+        assert!((get_wav_duration(&faster).unwrap() - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_time_stretch_changes_duration() {
+        let wav = create_sine_wav(220.0, 2.0, 24000);
+
+        let faster = time_stretch_wav(&wav, 2.0).unwrap();
+        let slower = time_stretch_wav(&wav, 0.5).unwrap();
+
+        assert!((get_wav_duration(&faster).unwrap() - 1.0).abs() < 0.001);
+        assert!((get_wav_duration(&slower).unwrap() - 4.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_time_stretch_preserves_pitch() {
+        let wav = create_sine_wav(220.0, 2.0, 24000);
+        let faster = time_stretch_wav(&wav, 1.5).unwrap();
+
+        // Same frequency means the same crossings per second
+        let original_rate = zero_crossings(&wav) as f64 / get_wav_duration(&wav).unwrap();
+        let stretched_rate = zero_crossings(&faster) as f64 / get_wav_duration(&faster).unwrap();
+        assert!((stretched_rate / original_rate - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_time_stretch_unit_speed_is_unchanged() {
+        let wav = create_sine_wav(220.0, 0.5, 24000);
+        assert_eq!(time_stretch_wav(&wav, 1.0).unwrap(), wav);
+        assert!(time_stretch_wav(&wav, 0.0).is_err());
+    }
+
+    /// Largest absolute sample in a 16-bit WAV.
+    fn peak_of(wav: &[u8]) -> i32 {
+        wav[44..]
+            .chunks_exact(2)
+            .map(|b| (i16::from_le_bytes([b[0], b[1]]) as i32).abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_normalize_peak_raises_quiet_segment() {
+        let quiet = create_wav_from_samples(&[100, -300, 200, 0], 24000);
+        let target = (NORMALIZE_TARGET_PEAK * i16::MAX as f32).round() as i32;
+
+        let normalized = normalize_peak(&quiet, NORMALIZE_TARGET_PEAK).unwrap();
+
+        assert!(peak_of(&normalized) > peak_of(&quiet));
+        assert!((peak_of(&normalized) - target).abs() <= 1);
+        // Duration is unchanged
+        assert_eq!(normalized.len(), quiet.len());
+    }
+
+    #[test]
+    fn test_normalize_peak_lowers_loud_segment_without_clipping() {
+        let loud = create_wav_from_samples(&[i16::MIN, i16::MAX, 1000], 24000);
+
+        let normalized = normalize_peak(&loud, NORMALIZE_TARGET_PEAK).unwrap();
+
+        assert!(peak_of(&normalized) < peak_of(&loud));
+        assert!(peak_of(&normalized) <= i16::MAX as i32);
+    }
+
+    #[test]
+    fn test_normalize_peak_leaves_silence() {
+        let silence = create_test_wav(100, 24000, 1);
+        assert_eq!(normalize_peak(&silence, NORMALIZE_TARGET_PEAK).unwrap(), silence);
+    }
+
+    #[test]
+    fn test_trim_silence_removes_padding() {
+        // At 1kHz the trim margin is 20 frames
+        let mut samples = vec![0i16; 500];
+        samples.extend((0..100).map(|i| if i % 2 == 0 { 8000 } else { -8000 }));
+        samples.extend([50, -50, 100].iter().cycle().take(300));
+        let padded = create_wav_from_samples(&samples, 1000);
+
+        let trimmed = trim_silence(&padded, 0.01).unwrap();
+        assert!((get_wav_duration(&trimmed).unwrap() - 0.14).abs() < 1e-9);
+        assert_eq!(i16::from_le_bytes([trimmed[84], trimmed[85]]), 8000);
+
+        // A threshold below the noise floor keeps the quiet tail
+        let kept = trim_silence(&padded, 0.001).unwrap();
+        assert!((get_wav_duration(&kept).unwrap() - 0.42).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_trim_silence_of_silence_is_empty() {
+        let silence = create_test_wav(1000, 24000, 1);
+        let trimmed = trim_silence(&silence, 0.01).unwrap();
+        assert_eq!(get_wav_duration(&trimmed).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_append_silence() {
+        let audio = create_wav_from_samples(&[1000; 140], 1000);
+        let padded = append_silence(&audio, 250).unwrap();
+
+        assert!((get_wav_duration(&padded).unwrap() - 0.39).abs() < 1e-9);
+        assert_eq!(&padded[44..324], &audio[44..]);
+        assert!(padded[324..].iter().all(|&b| b == 0));
+        assert_eq!(append_silence(&audio, 0).unwrap(), audio);
+    }
+
+    #[test]
+    fn test_read_wav_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("narration.wav");
+        let samples: Vec<i16> = (0..1000).collect();
+        std::fs::write(&path, create_wav_from_samples(&samples, 1000)).unwrap();
+
+        let slice = read_wav_slice(&path, 0.25, 0.5).unwrap();
+        assert!((get_wav_duration(&slice).unwrap() - 0.25).abs() < 1e-9);
+        assert_eq!(i16::from_le_bytes([slice[44], slice[45]]), 250);
+
+        // An end past the audio is clamped to the last frame
+        let tail = read_wav_slice(&path, 0.9, 5.0).unwrap();
+        assert!((get_wav_duration(&tail).unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(i16::from_le_bytes([tail[tail.len() - 2], tail[tail.len() - 1]]), 999);
+
+        let empty = read_wav_slice(&path, 2.0, 3.0).unwrap();
+        assert_eq!(get_wav_duration(&empty).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_splice_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("narration.wav");
+        let samples: Vec<i16> = (0..1000).collect();
+        std::fs::write(&path, create_wav_from_samples(&samples, 1000)).unwrap();
+
+        // Replace 0.2s-0.5s with 0.1s of new audio
+        let original = std::fs::read(&path).unwrap();
+        let output = dir.path().join("narration.new.wav");
+        let replacement = create_wav_from_samples(&[-1; 100], 1000);
+        let duration = splice_wav(&path, &output, 0.2, 0.5, &replacement).unwrap();
+        assert!((duration - 0.8).abs() < 1e-9);
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+
+        let spliced = std::fs::read(&output).unwrap();
+        assert!((get_wav_duration(&spliced).unwrap() - 0.8).abs() < 1e-9);
+        let sample = |frame: usize| i16::from_le_bytes([spliced[44 + frame * 2], spliced[45 + frame * 2]]);
+        assert_eq!(sample(199), 199);
+        assert_eq!(sample(200), -1);
+        assert_eq!(sample(299), -1);
+        assert_eq!(sample(300), 500);
+        assert_eq!(sample(799), 999);
+
+        // A different format is refused and nothing is written
+        std::fs::remove_file(&output).unwrap();
+        let mismatched = create_test_wav(100, 2000, 1);
+        assert!(splice_wav(&path, &output, 0.0, 0.1, &mismatched).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_splice_wav_requantizes_replacement() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("narration.wav");
+        let narration = requantize_wav(&create_wav_from_samples(&[1; 1000], 1000), 24).unwrap();
+        std::fs::write(&path, narration).unwrap();
+
+        let output = dir.path().join("narration.new.wav");
+        let replacement = create_wav_from_samples(&[-1; 100], 1000);
+        let duration = splice_wav(&path, &output, 0.2, 0.5, &replacement).unwrap();
+        assert!((duration - 0.8).abs() < 1e-9);
+
+        let spliced = std::fs::read(&output).unwrap();
+        assert_eq!(parse_wav_header(&spliced).unwrap().bits_per_sample, 24);
+        let decoded = decode_pcm(&spliced[44..], 24);
+        assert_eq!(decoded[199], 256);
+        assert_eq!(decoded[200], -256);
+        assert_eq!(decoded[300], 256);
+    }
+
     #[test]
     fn test_concatenate_mismatched_formats() {
         let service = TtsService::new();
@@ -423,4 +1653,157 @@ mod tests {
         let result = service.concatenate_audio(vec![wav1, wav2]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_wav_writer_matches_concatenate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let segments = vec![
+            create_test_wav(1000, 24000, 1),
+            create_wav_from_samples(&[1, -2, 3, -4], 24000),
+            create_test_wav(500, 24000, 1),
+        ];
+
+        let mut writer = WavWriter::create(&path).unwrap();
+        for segment in &segments {
+            writer.append(segment).unwrap();
+        }
+        let duration = writer.finish().unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        let expected = TtsService::new().concatenate_audio(segments).unwrap();
+        assert_eq!(written, expected);
+        assert!((get_wav_duration(&written).unwrap() - 1504.0 / 24000.0).abs() < 1e-9);
+        assert_eq!(duration, get_wav_duration(&written).unwrap());
+    }
+
+    #[test]
+    fn test_wav_writer_rejects_mismatch_and_empty() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut writer = WavWriter::create(&dir.path().join("mixed.wav")).unwrap();
+        writer.append(&create_test_wav(1000, 44100, 1)).unwrap();
+        assert!(writer.append(&create_test_wav(1000, 22050, 1)).is_err());
+
+        let empty = WavWriter::create(&dir.path().join("empty.wav")).unwrap();
+        assert!(empty.finish().is_err());
+    }
+
+    #[test]
+    fn test_probe_audio_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.wav");
+        std::fs::write(&path, create_test_wav(22050 * 4, 22050, 2)).unwrap();
+
+        let info = probe_audio(&path).unwrap();
+        assert_eq!(info.sample_rate, 22050);
+        assert_eq!(info.channels, 2);
+        assert!((info.duration - 4.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_probe_audio_opus() {
+        // One Ogg page per packet; the checksums aren't read
+        let page = |granule: u64, body: &[u8]| {
+            let mut page = b"OggS\0\0".to_vec();
+            page.extend_from_slice(&granule.to_le_bytes());
+            page.extend_from_slice(&7u32.to_le_bytes());
+            page.extend_from_slice(&[0; 8]);
+            page.push(1);
+            page.push(body.len() as u8);
+            page.extend_from_slice(body);
+            page
+        };
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&24000u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+
+        let mut opus = page(0, &head);
+        opus.extend(page(0, b"OpusTags"));
+        opus.extend(page(48000 + 312, &[0; 40]));
+        opus.extend(page(2 * 48000 + 312, &[0; 40]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audio.opus");
+        std::fs::write(&path, &opus).unwrap();
+
+        let info = probe_audio(&path).unwrap();
+        assert_eq!(info.sample_rate, 48000);
+        assert_eq!(info.channels, 2);
+        assert!((info.duration - 2.0).abs() < 0.001);
+
+        std::fs::write(&path, page(0, b"OpusTags")).unwrap();
+        assert!(probe_audio(&path).is_err());
+    }
+
+    #[test]
+    fn test_probe_audio_rejects_mislabelled_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = create_test_wav(44100, 44100, 1);
+
+        // An MP3 renamed to .wav, and a WAV with a corrupt header renamed to .mp3
+        let fake_wav = dir.path().join("fake.wav");
+        std::fs::write(&fake_wav, b"ID3\x04\x00\x00\x00\x00\x00\x00not really audio").unwrap();
+        assert!(probe_audio(&fake_wav).is_err());
+
+        let fake_mp3 = dir.path().join("fake.mp3");
+        std::fs::write(&fake_mp3, &wav[12..]).unwrap();
+        assert!(probe_audio(&fake_mp3).is_err());
+    }
+
+    #[test]
+    fn test_transcode_to_wav() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // symphonia also reads WAV, so a PCM round trip must be lossless
+        let mut wav = create_test_wav(8000, 16000, 2);
+        for (i, sample) in wav[44..].chunks_exact_mut(2).enumerate() {
+            sample.copy_from_slice(&((i as i16).wrapping_mul(37)).to_le_bytes());
+        }
+        let path = dir.path().join("sample.wav");
+        std::fs::write(&path, &wav).unwrap();
+        assert_eq!(transcode_to_wav(&path).unwrap(), wav);
+
+        let garbage = dir.path().join("sample.flac");
+        std::fs::write(&garbage, b"fLaC but not really").unwrap();
+        assert!(matches!(transcode_to_wav(&garbage), Err(TtsError::InvalidAudio(_))));
+    }
+
+    #[test]
+    fn test_voice_sample_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = create_wav_from_samples(&[0, 1000, -1000, i16::MAX, i16::MIN], 22050);
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+
+        // 16-bit PCM is kept, 24-bit comes back down to 16
+        assert_eq!(voice_sample_wav(&write("plain.wav", &wav)).unwrap(), wav);
+        let wide = requantize_wav(&wav, 24).unwrap();
+        assert_eq!(voice_sample_wav(&write("wide.WAV", &wide)).unwrap(), wav);
+
+        // 32-bit float is decoded to 16-bit PCM
+        let info = WavInfo {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 32,
+            audio_format: 3,
+            data_offset: 44,
+        };
+        let samples: Vec<u8> =
+            [0.0f32, 0.5, -0.5].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let float = build_wav_file(&info, &samples).unwrap();
+        let converted = voice_sample_wav(&write("float.wav", &float)).unwrap();
+        let converted_info = parse_wav_header(&converted).unwrap();
+        assert_eq!((converted_info.audio_format, converted_info.bits_per_sample), (1, 16));
+        assert_eq!(converted_info.sample_rate, 22050);
+        let decoded = decode_pcm(&converted[converted_info.data_offset..], 16);
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0], 0);
+        assert!((decoded[1] - 16384).abs() <= 1, "{:?}", decoded);
+        assert!((decoded[2] + 16384).abs() <= 1, "{:?}", decoded);
+    }
 }
@@ -9,7 +9,10 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Default endpoint for the vision service
-const DEFAULT_ENDPOINT: &str = "http://localhost:60003";
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:60003";
+
+/// Prompt used by [`VisionService::caption_image`]
+pub const DEFAULT_CAPTION_PROMPT: &str = "Describe this image concisely for an audiobook listener.";
 
 /// Errors that can occur during vision operations
 #[derive(Error, Debug)]
@@ -106,11 +109,8 @@ impl VisionService {
     /// # }
     /// ```
     pub async fn caption_image(&self, image_base64: &str) -> Result<String, VisionError> {
-        self.caption_image_with_prompt(
-            image_base64,
-            "Describe this image concisely for an audiobook listener.",
-        )
-        .await
+        self.caption_image_with_prompt(image_base64, DEFAULT_CAPTION_PROMPT)
+            .await
     }
 
     /// Generate a caption for an image with a custom prompt.
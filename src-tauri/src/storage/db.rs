@@ -4,6 +4,16 @@ use rusqlite::{Connection, Result as SqliteResult};
 use std::path::Path;
 use std::sync::Mutex;
 
+use super::files::AppPaths;
+
+/// Id of the profile every install starts with, which books imported before
+/// profiles existed belong to.
+pub const DEFAULT_PROFILE_ID: &str = "default";
+
+/// Prepared statements kept per connection for `prepare_cached`, enough for
+/// every query on the hot read paths.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
 /// Wrapper around SQLite connection with thread-safe access.
 pub struct Database {
     conn: Mutex<Connection>,
@@ -16,6 +26,7 @@ impl Database {
 
         // Enable foreign keys
         conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
 
         Ok(Self {
             conn: Mutex::new(conn),
@@ -27,6 +38,11 @@ impl Database {
     pub fn connection(&self) -> &Mutex<Connection> {
         &self.conn
     }
+
+    /// Take the underlying connection, e.g. to swap it into another `Database`.
+    pub fn into_connection(self) -> Connection {
+        self.conn.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
 }
 
 /// Initialize the database with all required tables.
@@ -44,11 +60,78 @@ pub fn init_database(db_path: &Path) -> SqliteResult<Database> {
     {
         let conn = db.conn.lock().unwrap();
         create_tables(&conn)?;
+        migrate_tables(&conn)?;
     }
 
     Ok(db)
 }
 
+/// Reset books left in the 'generating' state by a previous run.
+///
+/// A crash or forced quit during narration leaves the status behind with no
+/// task to finish it. Any generating book whose id is not in `active` is put
+/// back to 'none' so it can be generated again. Returns the number of books reset.
+pub fn reset_stale_generations<S: AsRef<str>>(db: &Database, active: &[S]) -> SqliteResult<usize> {
+    let conn = db.conn.lock().unwrap();
+
+    let stale: Vec<String> = conn
+        .prepare("SELECT id FROM books WHERE narration_status = 'generating'")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<SqliteResult<Vec<_>>>()?
+        .into_iter()
+        .filter(|id| !active.iter().any(|a| a.as_ref() == id))
+        .collect();
+
+    for id in &stale {
+        conn.execute(
+            "UPDATE books SET narration_status = 'none' WHERE id = ?",
+            [id],
+        )?;
+    }
+
+    Ok(stale.len())
+}
+
+/// Rewrite absolute book paths as paths relative to the data directory.
+///
+/// Covers rows written before paths were stored relative, and rows left
+/// pointing at an old location after the data directory was moved. Paths
+/// that can't be found under the current root are left untouched, as are
+/// source files imported by reference. Returns the number of books updated.
+pub fn relativize_book_paths(db: &Database, paths: &AppPaths) -> SqliteResult<usize> {
+    let conn = db.conn.lock().unwrap();
+
+    let books: Vec<(String, String, Option<String>, bool)> = conn
+        .prepare("SELECT id, source_path, narration_path, source_is_reference FROM books")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut updated = 0;
+    for (id, source_path, narration_path, source_is_reference) in books {
+        let new_source = if source_is_reference {
+            None
+        } else {
+            paths.relocate(&source_path)
+        };
+        let new_narration = narration_path.as_deref().and_then(|p| paths.relocate(p));
+        if new_source.is_none() && new_narration.is_none() {
+            continue;
+        }
+
+        conn.execute(
+            "UPDATE books SET source_path = ?, narration_path = ? WHERE id = ?",
+            rusqlite::params![
+                new_source.unwrap_or(source_path),
+                new_narration.or(narration_path),
+                id
+            ],
+        )?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
 /// Create all database tables as defined in ARCHITECTURE.md.
 fn create_tables(conn: &Connection) -> SqliteResult<()> {
     conn.execute_batch(
@@ -64,7 +147,27 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             narration_path TEXT,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
-            last_opened_at INTEGER
+            last_opened_at INTEGER,
+            duration REAL,
+            caption_prompt TEXT,
+            source_is_reference INTEGER NOT NULL DEFAULT 0,
+            language TEXT,
+            finished_at INTEGER,
+            publisher TEXT,
+            published_date TEXT,
+            identifier TEXT,
+            isbn TEXT,
+            narration_meta TEXT,
+            original_filename TEXT,
+            profile_id TEXT NOT NULL DEFAULT 'default',
+            content_hash TEXT
+        );
+
+        -- Separate libraries within one install
+        CREATE TABLE IF NOT EXISTS profiles (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            created_at INTEGER NOT NULL
         );
 
         -- Text segments
@@ -74,9 +177,33 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             idx INTEGER NOT NULL,
             content TEXT NOT NULL,
             html TEXT,
+            segment_type TEXT NOT NULL DEFAULT 'text',
+            narrate INTEGER NOT NULL DEFAULT 1,
             UNIQUE(book_id, idx)
         );
 
+        -- Table of contents entries
+        CREATE TABLE IF NOT EXISTS chapters (
+            book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+            sort_order INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            start_segment_index INTEGER NOT NULL,
+            level INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (book_id, sort_order)
+        );
+
+        -- Image data for image segments
+        CREATE TABLE IF NOT EXISTS segment_images (
+            segment_id TEXT PRIMARY KEY REFERENCES segments(id) ON DELETE CASCADE,
+            book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+            source_path TEXT NOT NULL,
+            alt_text TEXT,
+            page_number INTEGER,
+            position TEXT NOT NULL DEFAULT 'middle',
+            caption TEXT,
+            caption_prompt TEXT
+        );
+
         -- Narration markers
         CREATE TABLE IF NOT EXISTS markers (
             id TEXT PRIMARY KEY,
@@ -84,6 +211,8 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             segment_id TEXT NOT NULL REFERENCES segments(id) ON DELETE CASCADE,
             start_time REAL NOT NULL,
             end_time REAL NOT NULL,
+            -- Set while a generation is in progress, NULL once finalized
+            partial_key TEXT,
             UNIQUE(segment_id)
         );
 
@@ -92,6 +221,8 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             book_id TEXT PRIMARY KEY REFERENCES books(id) ON DELETE CASCADE,
             segment_index INTEGER NOT NULL,
             audio_time REAL,
+            max_segment_index INTEGER NOT NULL DEFAULT 0,
+            max_audio_time REAL,
             updated_at INTEGER NOT NULL
         );
 
@@ -101,7 +232,17 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             name TEXT NOT NULL,
             engine TEXT NOT NULL,
             sample_path TEXT,
-            is_default INTEGER NOT NULL DEFAULT 0
+            is_default INTEGER NOT NULL DEFAULT 0,
+            original_filename TEXT
+        );
+
+        -- Per-segment voice overrides (inclusive index ranges)
+        CREATE TABLE IF NOT EXISTS segment_voices (
+            book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+            start_index INTEGER NOT NULL,
+            end_index INTEGER NOT NULL,
+            voice_id TEXT NOT NULL REFERENCES voices(id) ON DELETE CASCADE,
+            PRIMARY KEY (book_id, start_index)
         );
 
         -- Settings (key-value store)
@@ -110,16 +251,118 @@ fn create_tables(conn: &Connection) -> SqliteResult<()> {
             value TEXT NOT NULL
         );
 
+        -- Pronunciation dictionary, applied in id order before narration
+        CREATE TABLE IF NOT EXISTS pronunciations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            pattern TEXT NOT NULL,
+            replacement TEXT NOT NULL,
+            is_regex INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- Sync servers remembered for reconnecting without discovery
+        CREATE TABLE IF NOT EXISTS known_servers (
+            name TEXT PRIMARY KEY,
+            address TEXT NOT NULL,
+            port INTEGER NOT NULL,
+            last_seen INTEGER,
+            token TEXT,
+            fingerprint TEXT,
+            books_version TEXT
+        );
+
+        -- Listening sessions (for reading statistics)
+        CREATE TABLE IF NOT EXISTS reading_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+            started_at INTEGER NOT NULL,
+            ended_at INTEGER NOT NULL,
+            seconds_listened REAL NOT NULL
+        );
+
         -- Create indexes for common queries
         CREATE INDEX IF NOT EXISTS idx_segments_book_id ON segments(book_id);
         CREATE INDEX IF NOT EXISTS idx_markers_book_id ON markers(book_id);
+        CREATE INDEX IF NOT EXISTS idx_markers_book_start ON markers(book_id, start_time);
+        CREATE INDEX IF NOT EXISTS idx_segment_images_book_id ON segment_images(book_id);
         CREATE INDEX IF NOT EXISTS idx_books_last_opened ON books(last_opened_at);
+        CREATE INDEX IF NOT EXISTS idx_reading_sessions_book_id ON reading_sessions(book_id, ended_at);
         "#,
     )?;
 
     Ok(())
 }
 
+/// Bring tables created by older versions up to the current schema.
+///
+/// `CREATE TABLE IF NOT EXISTS` leaves existing tables untouched, so columns
+/// added after a table was first shipped must be added here as well.
+fn migrate_tables(conn: &Connection) -> SqliteResult<()> {
+    add_column_if_missing(conn, "books", "duration", "REAL")?;
+    add_column_if_missing(conn, "books", "caption_prompt", "TEXT")?;
+    add_column_if_missing(conn, "books", "source_is_reference", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "books", "language", "TEXT")?;
+    add_column_if_missing(conn, "books", "finished_at", "INTEGER")?;
+    add_column_if_missing(conn, "books", "publisher", "TEXT")?;
+    add_column_if_missing(conn, "books", "published_date", "TEXT")?;
+    add_column_if_missing(conn, "books", "identifier", "TEXT")?;
+    add_column_if_missing(conn, "books", "isbn", "TEXT")?;
+    add_column_if_missing(conn, "books", "narration_meta", "TEXT")?;
+    add_column_if_missing(conn, "books", "original_filename", "TEXT")?;
+    add_column_if_missing(conn, "progress", "max_segment_index", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "progress", "max_audio_time", "REAL")?;
+    add_column_if_missing(conn, "known_servers", "fingerprint", "TEXT")?;
+    add_column_if_missing(conn, "markers", "partial_key", "TEXT")?;
+    add_column_if_missing(conn, "segments", "segment_type", "TEXT NOT NULL DEFAULT 'text'")?;
+    add_column_if_missing(conn, "voices", "original_filename", "TEXT")?;
+    add_column_if_missing(conn, "books", "profile_id", "TEXT NOT NULL DEFAULT 'default'")?;
+    add_column_if_missing(conn, "known_servers", "books_version", "TEXT")?;
+    add_column_if_missing(conn, "segments", "narrate", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(conn, "books", "content_hash", "TEXT")?;
+
+    // Books from before profiles existed land in the default profile, which
+    // always exists
+    conn.execute(
+        "INSERT OR IGNORE INTO profiles (id, name, created_at) VALUES (?, 'Default', 0)",
+        [DEFAULT_PROFILE_ID],
+    )?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_books_profile_id ON books(profile_id);")?;
+
+    // Progress saved before the furthest position was tracked starts from
+    // the current position
+    conn.execute_batch(
+        "UPDATE progress SET max_segment_index = segment_index
+         WHERE max_segment_index < segment_index;
+         UPDATE progress SET max_audio_time = audio_time
+         WHERE audio_time IS NOT NULL AND (max_audio_time IS NULL OR max_audio_time < audio_time);",
+    )?;
+
+    Ok(())
+}
+
+/// Add a column to a table unless it already exists.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> SqliteResult<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqliteResult<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table, column, definition
+        ))?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,9 +388,163 @@ mod tests {
 
         assert!(tables.contains(&"books".to_string()));
         assert!(tables.contains(&"segments".to_string()));
+        assert!(tables.contains(&"chapters".to_string()));
         assert!(tables.contains(&"markers".to_string()));
         assert!(tables.contains(&"progress".to_string()));
         assert!(tables.contains(&"voices".to_string()));
         assert!(tables.contains(&"settings".to_string()));
+        assert!(tables.contains(&"reading_sessions".to_string()));
+        assert!(tables.contains(&"known_servers".to_string()));
+        assert!(tables.contains(&"pronunciations".to_string()));
+        assert!(tables.contains(&"segment_voices".to_string()));
+        assert!(tables.contains(&"segment_images".to_string()));
+        assert!(tables.contains(&"profiles".to_string()));
+    }
+
+    #[test]
+    fn test_reset_stale_generations() {
+        let dir = tempdir().unwrap();
+        let db = init_database(&dir.path().join("test.db")).unwrap();
+
+        {
+            let conn = db.conn.lock().unwrap();
+            for (id, status) in [("stale", "generating"), ("running", "generating"), ("done", "ready")] {
+                conn.execute(
+                    "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                     VALUES (?1, ?1, 'txt', '', ?2, 0, 0)",
+                    [id, status],
+                )
+                .unwrap();
+            }
+        }
+
+        assert_eq!(reset_stale_generations(&db, &["running"]).unwrap(), 1);
+
+        let conn = db.conn.lock().unwrap();
+        let status = |id: &str| -> String {
+            conn.query_row("SELECT narration_status FROM books WHERE id = ?", [id], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(status("stale"), "none");
+        assert_eq!(status("running"), "generating");
+        assert_eq!(status("done"), "ready");
+    }
+
+    #[test]
+    fn test_relativize_book_paths() {
+        let dir = tempdir().unwrap();
+        let db = init_database(&dir.path().join("test.db")).unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        std::fs::write(paths.source_path("moved", "txt"), "text").unwrap();
+
+        let current = paths.source_path("current", "txt").to_string_lossy().to_string();
+        {
+            let conn = db.conn.lock().unwrap();
+            for (id, source) in [
+                ("current", current.as_str()),
+                ("moved", "/old/root/sources/moved.txt"),
+                ("lost", "/old/root/sources/lost.txt"),
+                ("relative", "sources/relative.txt"),
+            ] {
+                conn.execute(
+                    "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at)
+                     VALUES (?1, ?1, 'txt', ?2, 'none', 0, 0)",
+                    [id, source],
+                )
+                .unwrap();
+            }
+            // A referenced file is the user's own, even if its path looks like ours
+            conn.execute(
+                "INSERT INTO books (id, title, source_format, source_path, narration_status, created_at, updated_at, source_is_reference)
+                 VALUES ('referenced', 'referenced', 'txt', '/home/me/sources/moved.txt', 'none', 0, 0, 1)",
+                [],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(relativize_book_paths(&db, &paths).unwrap(), 2);
+
+        let conn = db.conn.lock().unwrap();
+        let source = |id: &str| -> PathBuf {
+            conn.query_row("SELECT source_path FROM books WHERE id = ?", [id], |row| {
+                row.get::<_, String>(0)
+            })
+            .unwrap()
+            .into()
+        };
+        assert_eq!(source("current"), PathBuf::from("sources/current.txt"));
+        assert_eq!(source("moved"), PathBuf::from("sources/moved.txt"));
+        assert_eq!(source("lost"), PathBuf::from("/old/root/sources/lost.txt"));
+        assert_eq!(source("relative"), PathBuf::from("sources/relative.txt"));
+        assert_eq!(source("referenced"), PathBuf::from("/home/me/sources/moved.txt"));
+    }
+
+    #[test]
+    fn test_migrate_adds_missing_columns() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("old.db");
+
+        // Simulate a database created before the duration column existed
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE progress (
+                    book_id TEXT PRIMARY KEY,
+                    segment_index INTEGER NOT NULL,
+                    audio_time REAL,
+                    updated_at INTEGER NOT NULL
+                );
+                INSERT INTO progress VALUES ('book', 12, 340.5, 0);
+                CREATE TABLE books (
+                    id TEXT PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    author TEXT,
+                    source_format TEXT NOT NULL,
+                    source_path TEXT NOT NULL,
+                    narration_status TEXT NOT NULL DEFAULT 'none',
+                    narration_path TEXT,
+                    created_at INTEGER NOT NULL,
+                    updated_at INTEGER NOT NULL,
+                    last_opened_at INTEGER
+                );
+                INSERT INTO books (id, title, source_format, source_path, created_at, updated_at)
+                VALUES ('book', 'Old', 'txt', 'sources/book.txt', 0, 0);",
+            )
+            .unwrap();
+        }
+
+        let db = init_database(&db_path).expect("Failed to migrate database");
+        let conn = db.conn.lock().unwrap();
+        let columns: Vec<String> = conn
+            .prepare("PRAGMA table_info(books)")
+            .unwrap()
+            .query_map([], |row| row.get(1))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+
+        assert!(columns.contains(&"duration".to_string()));
+        assert!(columns.contains(&"caption_prompt".to_string()));
+
+        let furthest: (u32, Option<f64>) = conn
+            .query_row("SELECT max_segment_index, max_audio_time FROM progress", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(furthest, (12, Some(340.5)));
+
+        // Existing books are assigned to the default profile
+        let profile: (String, String) = conn
+            .query_row(
+                "SELECT p.id, p.name FROM books b JOIN profiles p ON p.id = b.profile_id",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(profile, (DEFAULT_PROFILE_ID.to_string(), "Default".to_string()));
+
+        // Running migrations again is a no-op
+        migrate_tables(&conn).unwrap();
     }
 }
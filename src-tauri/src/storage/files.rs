@@ -2,6 +2,43 @@
 
 use std::path::{Path, PathBuf};
 
+/// Audio format narration is saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NarrationCodec {
+    /// Uncompressed PCM WAV.
+    Wav,
+    /// MP3.
+    Mp3,
+    /// Opus in an Ogg container.
+    Opus,
+}
+
+impl NarrationCodec {
+    /// Every codec, in the order narration audio is looked for.
+    pub const ALL: [Self; 3] = [Self::Wav, Self::Mp3, Self::Opus];
+
+    /// File extension, also used as the `narrationCodec` setting value.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+        }
+    }
+
+    /// Parse a file extension or `narrationCodec` setting value.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.extension().eq_ignore_ascii_case(ext))
+    }
+
+    /// Codec of a narration audio file, from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+}
+
 /// Application directory paths.
 #[derive(Debug, Clone)]
 pub struct AppPaths {
@@ -17,6 +54,8 @@ pub struct AppPaths {
     pub bundles: PathBuf,
     /// Directory for voice sample files.
     pub voices: PathBuf,
+    /// Directory for images of image segments.
+    pub images: PathBuf,
 }
 
 impl AppPaths {
@@ -28,6 +67,7 @@ impl AppPaths {
             narration: root.join("narration"),
             bundles: root.join("bundles"),
             voices: root.join("voices"),
+            images: root.join("images"),
             root,
         }
     }
@@ -39,6 +79,7 @@ impl AppPaths {
         std::fs::create_dir_all(&self.narration)?;
         std::fs::create_dir_all(&self.bundles)?;
         std::fs::create_dir_all(&self.voices)?;
+        std::fs::create_dir_all(&self.images)?;
         Ok(())
     }
 
@@ -52,9 +93,22 @@ impl AppPaths {
         self.narration.join(book_id)
     }
 
-    /// Get the narration audio file path for a book.
-    pub fn narration_audio_path(&self, book_id: &str) -> PathBuf {
-        self.narration.join(book_id).join("audio.mp3")
+    /// Get the narration audio file path for a book saved with `codec`.
+    pub fn narration_audio_path(&self, book_id: &str, codec: NarrationCodec) -> PathBuf {
+        self.narration
+            .join(book_id)
+            .join(format!("audio.{}", codec.extension()))
+    }
+
+    /// Locate the narration audio file for a book, if one exists.
+    ///
+    /// The codec depends on the setting at generation time, or on the bundle
+    /// the book was imported from, so every known extension is tried.
+    pub fn find_narration_audio(&self, book_id: &str) -> Option<PathBuf> {
+        NarrationCodec::ALL
+            .into_iter()
+            .map(|codec| self.narration_audio_path(book_id, codec))
+            .find(|path| path.exists())
     }
 
     /// Get the markers file path for a book's narration.
@@ -62,6 +116,16 @@ impl AppPaths {
         self.narration.join(book_id).join("markers.json")
     }
 
+    /// Get the per-segment audio cache directory for a book's narration.
+    pub fn segment_cache_dir(&self, book_id: &str) -> PathBuf {
+        self.narration.join(book_id).join("cache")
+    }
+
+    /// Get the cached audio file path for a single narrated segment.
+    pub fn segment_cache_path(&self, book_id: &str, segment_id: &str) -> PathBuf {
+        self.segment_cache_dir(book_id).join(format!("{}.wav", segment_id))
+    }
+
     /// Get the bundle file path.
     pub fn bundle_path(&self, book_id: &str) -> PathBuf {
         self.bundles.join(format!("{}.actualbook", book_id))
@@ -71,6 +135,159 @@ impl AppPaths {
     pub fn voice_sample_path(&self, voice_id: &str, extension: &str) -> PathBuf {
         self.voices.join(format!("{}.{}", voice_id, extension))
     }
+
+    /// Get the directory holding a book's segment images.
+    pub fn images_path(&self, book_id: &str) -> PathBuf {
+        self.images.join(book_id)
+    }
+
+    /// Convert a path to the form stored in the database.
+    ///
+    /// Paths inside the root are stored relative to it so the data directory
+    /// can be moved; anything outside is kept as given.
+    pub fn to_stored(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Resolve a path stored in the database against the current root.
+    ///
+    /// Relative paths are joined onto the root; absolute paths (files kept
+    /// outside the data directory, or rows not yet repaired) are returned as-is.
+    pub fn resolve(&self, stored: &str) -> PathBuf {
+        let path = Path::new(stored);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    /// Find a relative replacement for a stored absolute path.
+    ///
+    /// Handles paths under the current root and paths left behind when the
+    /// data directory was moved: the part from the last `sources`, `narration`,
+    /// `voices`, `bundles` or `images` directory on is kept if it exists under
+    /// the current root. Returns `None` for relative paths and for paths that
+    /// can't be located.
+    pub fn relocate(&self, stored: &str) -> Option<String> {
+        let path = Path::new(stored);
+        if !path.is_absolute() {
+            return None;
+        }
+
+        if path.starts_with(&self.root) {
+            return Some(self.to_stored(path));
+        }
+
+        let components: Vec<_> = path.components().collect();
+        let data_dir = components.iter().rposition(|c| {
+            DATA_DIRS.iter().any(|dir| c.as_os_str() == *dir)
+        })?;
+        let relative: PathBuf = components[data_dir..].iter().collect();
+
+        self.root
+            .join(&relative)
+            .exists()
+            .then(|| relative.to_string_lossy().to_string())
+    }
+}
+
+/// Names of the data directories under the root.
+const DATA_DIRS: [&str; 5] = ["sources", "narration", "bundles", "voices", "images"];
+
+/// Total size in bytes of the files under `path`.
+///
+/// Symlinks are counted as links and never followed, so a link out of the
+/// data directory can't inflate the total or loop. A missing path is 0.
+pub fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            // DirEntry::file_type doesn't traverse symlinks
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Bytes free for this process on the volume holding `path`.
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    fs2::available_space(path)
+}
+
+/// Relative paths of all files under `root`, sorted.
+///
+/// Like [`dir_size`], symlinks are skipped rather than followed.
+pub fn list_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                let path = entry.path();
+                if let Ok(relative) = path.strip_prefix(root) {
+                    files.push(relative.to_path_buf());
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// File in the default data directory naming the data directory in use,
+/// written when the data directory is moved elsewhere.
+pub const DATA_LOCATION_FILE: &str = "data-location";
+
+/// Read the data directory recorded in `default_root`, if it was moved.
+pub fn read_data_location(default_root: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(default_root.join(DATA_LOCATION_FILE)).ok()?;
+    let location = contents.trim();
+    (!location.is_empty()).then(|| PathBuf::from(location))
+}
+
+/// Record `root` as the data directory in use.
+///
+/// The file is replaced with a rename so a crash leaves either the old or
+/// the new location recorded. Recording `default_root` itself removes it.
+pub fn write_data_location(default_root: &Path, root: &Path) -> std::io::Result<()> {
+    let path = default_root.join(DATA_LOCATION_FILE);
+    if root == default_root {
+        return match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    std::fs::create_dir_all(default_root)?;
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, root.to_string_lossy().as_bytes())?;
+    std::fs::rename(&temp_path, &path)
 }
 
 /// Get the sources directory path.
@@ -107,6 +324,7 @@ mod tests {
         assert_eq!(paths.narration, root.join("narration"));
         assert_eq!(paths.bundles, root.join("bundles"));
         assert_eq!(paths.voices, root.join("voices"));
+        assert_eq!(paths.images, root.join("images"));
     }
 
     #[test]
@@ -126,14 +344,61 @@ mod tests {
         let book_id = "550e8400-e29b-41d4-a716-446655440000";
 
         assert_eq!(
-            paths.narration_audio_path(book_id),
+            paths.narration_audio_path(book_id, NarrationCodec::Mp3),
             PathBuf::from("/data/narration/550e8400-e29b-41d4-a716-446655440000/audio.mp3")
         );
 
+        assert_eq!(
+            paths.narration_audio_path(book_id, NarrationCodec::Opus),
+            PathBuf::from("/data/narration/550e8400-e29b-41d4-a716-446655440000/audio.opus")
+        );
+
         assert_eq!(
             paths.markers_path(book_id),
             PathBuf::from("/data/narration/550e8400-e29b-41d4-a716-446655440000/markers.json")
         );
+
+        assert_eq!(
+            paths.segment_cache_path(book_id, "seg_001"),
+            PathBuf::from("/data/narration/550e8400-e29b-41d4-a716-446655440000/cache/seg_001.wav")
+        );
+    }
+
+    #[test]
+    fn test_narration_codec_from_extension() {
+        assert_eq!(NarrationCodec::from_extension("wav"), Some(NarrationCodec::Wav));
+        assert_eq!(NarrationCodec::from_extension("MP3"), Some(NarrationCodec::Mp3));
+        assert_eq!(NarrationCodec::from_extension("opus"), Some(NarrationCodec::Opus));
+        assert_eq!(NarrationCodec::from_extension("flac"), None);
+    }
+
+    #[test]
+    fn test_stored_paths_are_relative_to_root() {
+        let paths = AppPaths::new(PathBuf::from("/data"));
+        let source = paths.source_path("book-1", "epub");
+
+        let stored = paths.to_stored(&source);
+        assert_eq!(PathBuf::from(&stored), PathBuf::from("sources/book-1.epub"));
+        assert_eq!(paths.resolve(&stored), source);
+
+        // Files outside the root stay absolute
+        assert_eq!(paths.to_stored(Path::new("/elsewhere/book.epub")), "/elsewhere/book.epub");
+        assert_eq!(paths.resolve("/elsewhere/book.epub"), PathBuf::from("/elsewhere/book.epub"));
+    }
+
+    #[test]
+    fn test_relocate_moved_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = AppPaths::new(dir.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        std::fs::write(paths.source_path("book-1", "txt"), "text").unwrap();
+
+        let relocated = paths.relocate("/old/ActualReader/sources/book-1.txt").unwrap();
+        assert_eq!(paths.resolve(&relocated), paths.source_path("book-1", "txt"));
+
+        // Missing files and already-relative paths are left alone
+        assert_eq!(paths.relocate("/old/ActualReader/sources/missing.txt"), None);
+        assert_eq!(paths.relocate("sources/book-1.txt"), None);
     }
 
     #[test]
@@ -146,4 +411,41 @@ mod tests {
             PathBuf::from("/data/bundles/550e8400-e29b-41d4-a716-446655440000.actualbook")
         );
     }
+
+    #[test]
+    fn test_dir_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.path().join("one.bin"), [0u8; 10]).unwrap();
+        std::fs::write(nested.join("two.bin"), [0u8; 32]).unwrap();
+
+        #[cfg(unix)]
+        {
+            let outside = tempfile::tempdir().unwrap();
+            std::fs::write(outside.path().join("big.bin"), [0u8; 4096]).unwrap();
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        }
+
+        assert_eq!(dir_size(dir.path()).unwrap(), 42);
+        assert_eq!(dir_size(&nested.join("two.bin")).unwrap(), 32);
+        assert_eq!(dir_size(&dir.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_data_location_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let default_root = dir.path().join("default");
+        let moved = dir.path().join("moved");
+
+        assert_eq!(read_data_location(&default_root), None);
+
+        write_data_location(&default_root, &moved).unwrap();
+        assert_eq!(read_data_location(&default_root), Some(moved));
+
+        // Moving back to the default removes the record
+        write_data_location(&default_root, &default_root).unwrap();
+        assert_eq!(read_data_location(&default_root), None);
+        assert!(!default_root.join(DATA_LOCATION_FILE).exists());
+    }
 }
@@ -5,5 +5,11 @@
 mod db;
 mod files;
 
-pub use db::{init_database, Database};
-pub use files::{get_bundles_dir, get_narration_dir, get_sources_dir, get_voices_dir, AppPaths};
+pub use db::{
+    init_database, relativize_book_paths, reset_stale_generations, Database, DEFAULT_PROFILE_ID,
+};
+pub use files::{
+    available_space, dir_size, get_bundles_dir, get_narration_dir, get_sources_dir, get_voices_dir,
+    list_files, read_data_location, write_data_location, AppPaths, NarrationCodec,
+    DATA_LOCATION_FILE,
+};
@@ -178,7 +178,10 @@ export function useSync(): UseSyncReturn {
   const discoverServers = useCallback(async (): Promise<SyncServer[]> => {
     setError(null);
     try {
-      const discovered = await commands.discoverSyncServers();
+      const { servers: discovered, partial } = await commands.discoverSyncServers();
+      if (partial) {
+        setError('Network discovery was interrupted, so some servers may be missing. Try connecting manually.');
+      }
       // Merge with existing servers, avoiding duplicates
       setServers((prev) => {
         const merged = [...prev];
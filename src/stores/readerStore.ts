@@ -268,7 +268,8 @@ export const selectCurrentMarker = (state: ReaderStore): Marker | null => {
  * Select whether the book has narration available
  */
 export const selectHasNarration = (state: ReaderStore): boolean => {
-  return state.currentBook?.narrationStatus === 'ready' && state.markers.length > 0;
+  const status = state.currentBook?.narrationStatus;
+  return (status === 'ready' || status === 'partial') && state.markers.length > 0;
 };
 
 /**
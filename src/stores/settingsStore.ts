@@ -42,20 +42,6 @@ type SettingsStore = SettingsState & SettingsActions;
 // Helper Functions
 // =============================================================================
 
-/**
- * Convert a settings key from camelCase to snake_case for backend storage
- */
-function toSnakeCase(key: string): string {
-  return key.replace(/[A-Z]/g, (letter) => `_${letter.toLowerCase()}`);
-}
-
-/**
- * Convert a settings key from snake_case to camelCase
- */
-function toCamelCase(key: string): string {
-  return key.replace(/_([a-z])/g, (_, letter) => letter.toUpperCase());
-}
-
 /**
  * Serialize a setting value to string for storage
  */
@@ -125,11 +111,12 @@ export const useSettingsStore = create<SettingsStore>()((set, get) => ({
       // Map stored settings to state
       const settings: Partial<Settings> = {};
 
-      for (const [snakeKey, value] of Object.entries(storedSettings)) {
-        const camelKey = toCamelCase(snakeKey) as keyof Settings;
-        if (camelKey in DEFAULT_SETTINGS) {
-          const defaultValue = DEFAULT_SETTINGS[camelKey];
-          (settings as Record<string, unknown>)[camelKey] = deserializeValue(value, defaultValue);
+      // Keys are stored as the camelCase names the store uses
+      for (const [storedKey, value] of Object.entries(storedSettings)) {
+        const key = storedKey as keyof Settings;
+        if (key in DEFAULT_SETTINGS) {
+          const defaultValue = DEFAULT_SETTINGS[key];
+          (settings as Record<string, unknown>)[key] = deserializeValue(value, defaultValue);
         }
       }
 
@@ -152,8 +139,7 @@ export const useSettingsStore = create<SettingsStore>()((set, get) => ({
     set({ [key]: value } as Partial<SettingsState>);
 
     try {
-      const snakeKey = toSnakeCase(key);
-      await commands.setSetting(snakeKey, serializeValue(value));
+      await commands.setSetting(key, serializeValue(value));
     } catch (err) {
       // Rollback on error
       set({ [key]: previousValue } as Partial<SettingsState>);
@@ -177,10 +163,9 @@ export const useSettingsStore = create<SettingsStore>()((set, get) => ({
 
     try {
       // Save each setting to backend
-      const promises = Object.entries(settings).map(([key, value]) => {
-        const snakeKey = toSnakeCase(key);
-        return commands.setSetting(snakeKey, serializeValue(value));
-      });
+      const promises = Object.entries(settings).map(([key, value]) =>
+        commands.setSetting(key, serializeValue(value))
+      );
       await Promise.all(promises);
     } catch (err) {
       // Rollback on error
@@ -195,10 +180,9 @@ export const useSettingsStore = create<SettingsStore>()((set, get) => ({
     set({ loading: true, error: null });
     try {
       // Save all defaults to backend
-      const promises = Object.entries(DEFAULT_SETTINGS).map(([key, value]) => {
-        const snakeKey = toSnakeCase(key);
-        return commands.setSetting(snakeKey, serializeValue(value));
-      });
+      const promises = Object.entries(DEFAULT_SETTINGS).map(([key, value]) =>
+        commands.setSetting(key, serializeValue(value))
+      );
       await Promise.all(promises);
 
       set({
@@ -9,12 +9,24 @@ import { invoke as tauriInvoke } from '@tauri-apps/api/core';
 import type {
   Book,
   BookId,
+  BookUpdate,
+  BundleFileInfo,
+  DirectoryImport,
+  ImportedBook,
+  SourceFormat,
+  LibrarySort,
+  ImportMode,
   Segment,
+  SegmentId,
   Progress,
   Voice,
   VoiceId,
   SyncServer,
+  SyncDiscovery,
+  SyncPreview,
+  GenerationStatus,
   SyncResult,
+  Profile,
 } from '../types';
 
 // Check if we're running inside Tauri
@@ -37,18 +49,63 @@ async function invoke<T>(cmd: string, args?: Record<string, unknown>): Promise<T
 /**
  * Import a book from a source file path
  * @param path - Path to the source file (epub, markdown, txt, pdf)
- * @returns The imported Book
+ * @returns The imported Book, with warnings about content the parser skipped
+ */
+export async function importBook(path: string, mode?: ImportMode): Promise<ImportedBook> {
+  return invoke<ImportedBook>('import_book', { path, mode });
+}
+
+/**
+ * Import every supported file directly inside a directory
+ * @param path - Directory to import from
+ * @returns The imported books, and the files that failed with why
+ */
+export async function importDirectory(path: string, mode?: ImportMode): Promise<DirectoryImport> {
+  return invoke<DirectoryImport>('import_directory', { path, mode });
+}
+
+/**
+ * Download a book from a URL and import a copy of it
+ * @param url - http(s) link to an epub, html, markdown or txt file
+ * @returns The imported Book, with any parse warnings
  */
-export async function importBook(path: string): Promise<Book> {
-  return invoke<Book>('import_book', { path });
+export async function importFromUrl(url: string): Promise<ImportedBook> {
+  return invoke<ImportedBook>('import_from_url', { url });
 }
 
 /**
- * Get all books in the library
- * @returns Array of all books
+ * Get the books in the library
+ * @param sort - Order to return books in (default: recently opened)
+ * @param limit - Maximum number of books to return
+ * @param profile - Profile whose books to return (default: the active profile)
+ * @returns Array of books
  */
-export async function getLibrary(): Promise<Book[]> {
-  return invoke<Book[]>('get_library');
+export async function getLibrary(
+  sort?: LibrarySort,
+  limit?: number,
+  profile?: string
+): Promise<Book[]> {
+  return invoke<Book[]>('get_library', { sort, limit, profile });
+}
+
+/**
+ * Edit a book's title, author or language
+ * @param id - BookId to update
+ * @param update - Fields to change
+ * @returns The updated Book
+ */
+export async function updateBook(id: BookId, update: BookUpdate): Promise<Book> {
+  return invoke<Book>('update_book', { id, update });
+}
+
+/**
+ * Correct a book's recorded source format
+ * @param bookId - BookId to update
+ * @param format - Format to record, or omit to detect it from the source file's contents
+ * @returns The updated Book
+ */
+export async function setSourceFormat(bookId: BookId, format?: SourceFormat): Promise<Book> {
+  return invoke<Book>('set_source_format', { bookId, format: format ?? null });
 }
 
 /**
@@ -59,6 +116,45 @@ export async function deleteBook(id: BookId): Promise<void> {
   return invoke<void>('delete_book', { id });
 }
 
+/**
+ * Get every profile with its book count, oldest first
+ */
+export async function listProfiles(): Promise<Profile[]> {
+  return invoke<Profile[]>('list_profiles');
+}
+
+/**
+ * Get the profile the library currently shows
+ */
+export async function getActiveProfile(): Promise<Profile> {
+  return invoke<Profile>('get_active_profile');
+}
+
+/**
+ * Create an empty profile; names must be unique, ignoring case
+ * @param name - Display name
+ */
+export async function createProfile(name: string): Promise<Profile> {
+  return invoke<Profile>('create_profile', { name });
+}
+
+/**
+ * Make a profile active, so the library shows its books and imports go to it
+ * @param profileId - Profile to switch to
+ */
+export async function switchProfile(profileId: string): Promise<Profile> {
+  return invoke<Profile>('switch_profile', { profileId });
+}
+
+/**
+ * Move a book into another profile, keeping its narration and progress
+ * @param bookId - Book to move
+ * @param profileId - Profile to move it to
+ */
+export async function moveBookToProfile(bookId: BookId, profileId: string): Promise<void> {
+  return invoke<void>('move_book_to_profile', { bookId, profileId });
+}
+
 // =============================================================================
 // Reader Commands
 // =============================================================================
@@ -81,6 +177,26 @@ export async function getSegments(bookId: BookId): Promise<Segment[]> {
   return invoke<Segment[]>('get_segments', { bookId });
 }
 
+/**
+ * Include a segment in narration or leave it out, keeping it in the reader
+ * @param segmentId - SegmentId to change
+ * @param narrate - False to leave the segment out of narration
+ * @returns The updated segment
+ */
+export async function setSegmentNarrate(segmentId: SegmentId, narrate: boolean): Promise<Segment> {
+  return invoke<Segment>('set_segment_narrate', { segmentId, narrate });
+}
+
+/**
+ * Split a book into several, each new part starting at one of the indices
+ * @param bookId - BookId to split
+ * @param atSegmentIndices - Index of the first segment of each new part
+ * @returns Every part in order, starting with the original book
+ */
+export async function splitBook(bookId: BookId, atSegmentIndices: number[]): Promise<Book[]> {
+  return invoke<Book[]>('split_book', { bookId, atSegmentIndices });
+}
+
 /**
  * Get markers for a book (narration timing data)
  * @param bookId - BookId to get markers for
@@ -90,6 +206,45 @@ export async function getMarkers(bookId: BookId): Promise<import('../types').Mar
   return invoke<import('../types').Marker[]>('get_markers', { bookId });
 }
 
+/**
+ * Get the segment being narrated with its neighbors, for the "now playing" view
+ * @param bookId - BookId of the narrated book
+ * @param audioTime - Playback position in seconds
+ * @param radius - Segments to include on either side (at most 50)
+ */
+export async function getReadingContext(
+  bookId: BookId,
+  audioTime: number,
+  radius: number
+): Promise<import('../types').ReadingContext> {
+  return invoke<import('../types').ReadingContext>('get_reading_context', {
+    bookId,
+    audioTime,
+    radius,
+  });
+}
+
+/**
+ * Write a book's segments joined with their markers to a JSON file, for
+ * debugging narration timing
+ * @param bookId - BookId to export
+ * @param outputPath - Where to write the JSON file
+ */
+export async function exportNarrationData(bookId: BookId, outputPath: string): Promise<void> {
+  return invoke<void>('export_narration_data', { bookId, outputPath });
+}
+
+/**
+ * Get one segment's narration audio, cut from the book's WAV narration
+ * @param bookId - BookId the segment belongs to
+ * @param segmentId - Segment to get audio for
+ * @returns WAV audio data
+ */
+export async function getSegmentAudio(bookId: BookId, segmentId: string): Promise<Uint8Array> {
+  const wav = await invoke<number[]>('get_segment_audio', { bookId, segmentId });
+  return new Uint8Array(wav);
+}
+
 /**
  * Save reading progress for a book
  * @param bookId - BookId to save progress for
@@ -116,9 +271,20 @@ export async function getProgress(bookId: BookId): Promise<Progress | null> {
  * Generate narration for a book using specified voice
  * @param bookId - BookId to generate narration for
  * @param voiceId - VoiceId to use for generation
+ * @param range - Inclusive segment range to narrate (default: the whole book);
+ *   spliced into the book's narration if it has one
  */
-export async function generateNarration(bookId: BookId, voiceId: VoiceId): Promise<void> {
-  return invoke<void>('generate_narration', { bookId, voiceId });
+export async function generateNarration(
+  bookId: BookId,
+  voiceId: VoiceId,
+  range?: { startIndex?: number; endIndex?: number }
+): Promise<void> {
+  return invoke<void>('generate_narration', {
+    bookId,
+    voiceId,
+    startIndex: range?.startIndex,
+    endIndex: range?.endIndex,
+  });
 }
 
 /**
@@ -129,6 +295,14 @@ export async function getVoices(): Promise<Voice[]> {
   return invoke<Voice[]>('get_voices');
 }
 
+/**
+ * Create the default voice from the bundled sample, for a fresh install
+ * @returns The new voice, or null if a voice already exists
+ */
+export async function createDefaultVoice(): Promise<Voice | null> {
+  return invoke<Voice | null>('create_default_voice');
+}
+
 /**
  * Cancel ongoing narration generation
  */
@@ -136,6 +310,70 @@ export async function cancelGeneration(): Promise<void> {
   return invoke<void>('cancel_generation');
 }
 
+/**
+ * Re-narrate a single segment of a ready book and splice it into the narration
+ * @param bookId - BookId of the narrated book
+ * @param segmentId - Segment to regenerate
+ * @returns The updated markers
+ */
+export async function regenerateSegment(
+  bookId: BookId,
+  segmentId: SegmentId
+): Promise<import('../types').Marker[]> {
+  return invoke<import('../types').Marker[]>('regenerate_segment', { bookId, segmentId });
+}
+
+/**
+ * Find gaps and overlaps in a book's markers
+ * @param bookId - BookId to check
+ */
+export async function checkMarkers(bookId: BookId): Promise<import('../types').MarkerReport> {
+  return invoke<import('../types').MarkerReport>('check_markers', { bookId });
+}
+
+/**
+ * Snap marker ends to the next marker's start to close gaps or clamp overlaps
+ * @param bookId - BookId to repair
+ * @param strategy - Which problems to fix
+ * @returns What remains after the repair
+ */
+export async function repairMarkers(
+  bookId: BookId,
+  strategy: import('../types').MarkerRepairStrategy
+): Promise<import('../types').MarkerReport> {
+  return invoke<import('../types').MarkerReport>('repair_markers', { bookId, strategy });
+}
+
+/**
+ * Use narration made outside the app for a book, replacing any existing narration
+ * @param bookId - BookId to attach narration to
+ * @param audioPath - WAV, MP3 or Opus narration audio
+ * @param markersJsonPath - markers.json whose markers refer to the book's segments
+ */
+export async function attachNarration(
+  bookId: BookId,
+  audioPath: string,
+  markersJsonPath: string
+): Promise<void> {
+  return invoke<void>('attach_narration', { bookId, audioPath, markersJsonPath });
+}
+
+/**
+ * Delete a ready book's per-segment audio cache, keeping its narration and markers
+ * @param bookId - BookId to compact
+ * @returns Bytes reclaimed
+ */
+export async function compactBookNarration(bookId: BookId): Promise<number> {
+  return invoke<number>('compact_book_narration', { bookId });
+}
+
+/**
+ * List narration generations currently running, with their latest progress
+ */
+export async function listActiveGenerations(): Promise<GenerationStatus[]> {
+  return invoke<GenerationStatus[]>('list_active_generations');
+}
+
 // =============================================================================
 // Bundle Commands
 // =============================================================================
@@ -143,10 +381,12 @@ export async function cancelGeneration(): Promise<void> {
 /**
  * Export a book as a .actualbook bundle
  * @param bookId - BookId to export
- * @param path - Destination path for the bundle
+ * @param outputPath - Destination file, or a directory to name the bundle after
+ *   the book's original filename
+ * @returns The path written
  */
-export async function exportBundle(bookId: BookId, path: string): Promise<void> {
-  return invoke<void>('export_bundle', { bookId, path });
+export async function exportBundle(bookId: BookId, outputPath: string): Promise<string> {
+  return invoke<string>('export_bundle', { bookId, outputPath });
 }
 
 /**
@@ -158,6 +398,21 @@ export async function importBundle(path: string): Promise<Book> {
   return invoke<Book>('import_bundle', { path });
 }
 
+/**
+ * List the files in the bundles directory, newest first
+ */
+export async function listBundles(): Promise<BundleFileInfo[]> {
+  return invoke<BundleFileInfo[]>('list_bundles');
+}
+
+/**
+ * Delete a file from the bundles directory
+ * @param filename - File name as given by listBundles
+ */
+export async function deleteBundle(filename: string): Promise<void> {
+  return invoke<void>('delete_bundle', { filename });
+}
+
 // =============================================================================
 // Sync Commands
 // =============================================================================
@@ -178,19 +433,44 @@ export async function stopSyncServer(): Promise<void> {
 
 /**
  * Discover sync servers on the local network
- * @returns Array of discovered servers
+ * @returns Discovered servers, marked partial if browsing failed early
  */
-export async function discoverSyncServers(): Promise<SyncServer[]> {
-  return invoke<SyncServer[]>('discover_sync_servers');
+export async function discoverSyncServers(): Promise<SyncDiscovery> {
+  return invoke<SyncDiscovery>('discover_sync_servers');
+}
+
+/**
+ * Preview which books syncing with a server would download
+ * @param server - SyncServer to compare with
+ */
+export async function previewSync(server: SyncServer): Promise<SyncPreview> {
+  return invoke<SyncPreview>('preview_sync', { server });
 }
 
 /**
  * Sync with a discovered server
  * @param server - SyncServer to sync with
+ * @param bookIds - Books to download, from the preview; all of them if omitted
  * @returns Result of the sync operation
  */
-export async function syncWith(server: SyncServer): Promise<SyncResult> {
-  return invoke<SyncResult>('sync_with', { server });
+export async function syncWith(server: SyncServer, bookIds?: BookId[]): Promise<SyncResult> {
+  return invoke<SyncResult>('sync_with', { server, bookIds });
+}
+
+/**
+ * Follow a server's live progress and library updates, emitted as
+ * server_update events; replaces any existing subscription
+ * @param server - SyncServer to follow
+ */
+export async function subscribeServerUpdates(server: SyncServer): Promise<void> {
+  return invoke<void>('subscribe_server_updates', { server });
+}
+
+/**
+ * Stop following a server's live updates
+ */
+export async function unsubscribeServerUpdates(): Promise<void> {
+  return invoke<void>('unsubscribe_server_updates');
 }
 
 // =============================================================================
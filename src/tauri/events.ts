@@ -12,6 +12,7 @@ import type {
   GenerationErrorPayload,
   SyncDiscoveredPayload,
   SyncProgressPayload,
+  SyncServerStoppedPayload,
 } from '../types';
 
 // =============================================================================
@@ -29,6 +30,8 @@ export const EVENTS = {
   SYNC_DISCOVERED: 'sync_discovered',
   /** Sync operation progress update */
   SYNC_PROGRESS: 'sync_progress',
+  /** Sync server stopped, by request or after going idle */
+  SYNC_SERVER_STOPPED: 'sync_server_stopped',
 } as const;
 
 export type EventName = (typeof EVENTS)[keyof typeof EVENTS];
@@ -106,6 +109,19 @@ export async function onSyncProgress(
   });
 }
 
+/**
+ * Listen for the local sync server stopping
+ * @param callback - Called with the reason it stopped
+ * @returns Unlisten function to remove the listener
+ */
+export async function onSyncServerStopped(
+  callback: (payload: SyncServerStoppedPayload) => void
+): Promise<UnlistenFn> {
+  return listen<SyncServerStoppedPayload>(EVENTS.SYNC_SERVER_STOPPED, (event) => {
+    callback(event.payload);
+  });
+}
+
 // =============================================================================
 // Utility: Event Subscription Manager
 // =============================================================================
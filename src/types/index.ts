@@ -29,13 +29,18 @@ export type Duration = number;
 // =============================================================================
 
 /** Supported source file formats for import */
-export type SourceFormat = 'epub' | 'markdown' | 'txt' | 'pdf';
+export type SourceFormat = 'epub' | 'html' | 'markdown' | 'txt' | 'pdf';
 
 /** Status of narration generation for a book */
-export type NarrationStatus = 'none' | 'generating' | 'ready';
+export type NarrationStatus = 'none' | 'generating' | 'ready' | 'stale' | 'partial';
 
-/** Type of segment - text content or image */
-export type SegmentType = 'text' | 'image';
+/** Type of segment - text content, image, or a structural block of text */
+export type SegmentType =
+  | 'text'
+  | 'image'
+  | 'quote'
+  | 'code'
+  | { heading: { level: number } };
 
 /** Position of an image on the page */
 export type ImagePosition = 'top' | 'middle' | 'bottom' | 'full-page' | 'inline';
@@ -65,6 +70,62 @@ export interface Book {
   updatedAt: Timestamp;
   /** NULL if never opened, used for "Recent" section */
   lastOpenedAt: Timestamp | null;
+  /** BCP 47 tag from the EPUB or detected ISO 639-3 code, NULL if unknown */
+  language: string | null;
+  /** When reading first reached the end, NULL if never finished */
+  finishedAt: Timestamp | null;
+  /** Publisher, date and identifiers from the source file */
+  metadata: BookMetadata;
+  /** How the narration was generated, NULL if not recorded */
+  narrationMeta: NarrationMeta | null;
+  /** Name of the file the book was imported from, NULL if not recorded */
+  originalFilename: string | null;
+}
+
+/**
+ * Publication details read from an EPUB. Fields are NULL when the file
+ * doesn't declare them, and always NULL for other formats.
+ */
+export interface BookMetadata {
+  publisher: string | null;
+  /** As declared, usually ISO 8601 ("2004" or "2004-05-17") */
+  publishedDate: string | null;
+  /** The package's unique identifier, e.g. a UUID or ISBN URN */
+  identifier: string | null;
+  /** ISBN-10 or ISBN-13 digits, without hyphens */
+  isbn: string | null;
+}
+
+/**
+ * Engine, voice and parameters a book's narration was generated with.
+ */
+export interface NarrationMeta {
+  /** Always "chatterbox" for now */
+  engine: string;
+  voiceId: VoiceId | null;
+  /** Name of the voice at generation time */
+  voiceName: string | null;
+  exag: number;
+  cfg: number;
+  temp: number;
+  /** "wav", "mp3" or "opus" */
+  codec: string;
+  generatedAt: Timestamp;
+}
+
+/**
+ * Order of books returned by get_library.
+ */
+export type LibrarySort = 'recentlyOpened' | 'recentlyAdded' | 'titleAsc' | 'authorAsc' | 'duration';
+
+/**
+ * Metadata changes for update_book. Omitted fields are unchanged; an
+ * empty author or language clears it.
+ */
+export interface BookUpdate {
+  title?: string;
+  author?: string;
+  language?: string;
 }
 
 /**
@@ -100,6 +161,35 @@ export interface Segment {
   segmentType: SegmentType;
   /** Image data (only for image segments) */
   imageData: ImageData | null;
+  /** False if the segment is shown but left out of narration */
+  narrate: boolean;
+}
+
+/**
+ * A table-of-contents entry pointing at the segment where it starts.
+ */
+export interface Chapter {
+  bookId: BookId;
+  title: string;
+  /** Index of the chapter's first segment */
+  startSegmentIndex: number;
+  /** Position in the table of contents (0-based) */
+  order: number;
+  /** Nesting depth; 0 for top-level chapters */
+  level: number;
+}
+
+/**
+ * A pronunciation dictionary entry, applied to text before narration.
+ */
+export interface Pronunciation {
+  /** Entries are applied in ascending id order */
+  id: number;
+  /** Text or regular expression to find */
+  pattern: string;
+  /** Text to speak instead */
+  replacement: string;
+  isRegex: boolean;
 }
 
 /**
@@ -114,6 +204,43 @@ export interface Marker {
   end: Duration;
 }
 
+/** A stretch of narration not covered by any marker */
+export interface MarkerGap {
+  /** Marker before the gap, null at the start of the narration */
+  after: SegmentId | null;
+  /** Marker after the gap, null at the end of the audio */
+  before: SegmentId | null;
+  start: Duration;
+  end: Duration;
+}
+
+/** Two markers whose ranges intersect */
+export interface MarkerOverlap {
+  first: SegmentId;
+  second: SegmentId;
+  start: Duration;
+  end: Duration;
+}
+
+/** Result of check_markers and repair_markers */
+export interface MarkerReport {
+  markerCount: number;
+  gaps: MarkerGap[];
+  overlaps: MarkerOverlap[];
+  audioDuration: Duration | null;
+}
+
+export type MarkerRepairStrategy = 'closeGaps' | 'clampOverlaps';
+
+/** The segment narrated at a point in the audio, with its neighbors */
+export interface ReadingContext {
+  activeSegmentId: SegmentId;
+  /** The active segment and its neighbors, in index order */
+  segments: Segment[];
+  /** Markers of those segments, by start time */
+  markers: Marker[];
+}
+
 /**
  * How far through a book the user has read/listened.
  * Use "progress" not "position" or "location"
@@ -124,6 +251,14 @@ export interface Progress {
   segmentIndex: number;
   /** Position in narration (seconds), null if not using narration */
   audioTime: Duration | null;
+  /**
+   * Furthest segment index ever reached; never moves backward. Always set on
+   * progress from the backend; left out when saving, since the backend
+   * tracks it.
+   */
+  maxSegmentIndex?: number;
+  /** Furthest position in narration (seconds), null if not using narration */
+  maxAudioTime?: Duration | null;
   updatedAt: Timestamp;
 }
 
@@ -134,9 +269,21 @@ export interface Progress {
 export interface Voice {
   id: VoiceId;
   name: string;
-  /** Path to voice sample for cloning */
+  /** Path to voice sample for cloning, always WAV */
   samplePath: string;
+  /** File name the sample was created from, before conversion to WAV */
+  originalFilename: string | null;
   isDefault: boolean;
+  /** Probed sample format; only present on a newly created voice */
+  sampleInfo?: SampleInfo;
+}
+
+/** Format of a voice sample, read from its audio header */
+export interface SampleInfo {
+  /** Duration in seconds */
+  duration: number;
+  sampleRate: number;
+  channels: number;
 }
 
 // =============================================================================
@@ -164,6 +311,57 @@ export interface SyncServer {
   /** IP address */
   address: string;
   port: number;
+  /** SHA-256 fingerprint of the server's TLS certificate; null for plain HTTP */
+  fingerprint?: string | null;
+}
+
+/**
+ * Sync servers found by browsing the network
+ */
+export interface SyncDiscovery {
+  servers: SyncServer[];
+  /** Browsing failed early; servers may be missing, so suggest connecting manually */
+  partial: boolean;
+}
+
+/**
+ * A sync server saved for reconnecting without discovery
+ */
+export interface KnownServer {
+  /** Device name */
+  name: string;
+  /** Last known IP address */
+  address: string;
+  port: number;
+  /** Unix timestamp of the last successful sync */
+  lastSeen: number | null;
+  token: string | null;
+  /** Pinned TLS certificate fingerprint */
+  fingerprint: string | null;
+}
+
+/**
+ * A book in a sync server's library
+ */
+export interface RemoteBook {
+  id: BookId;
+  title: string;
+  author: string | null;
+  sourceFormat: string;
+  hasNarration: boolean;
+  /** SHA-256 of the book's text; missing from older servers */
+  contentHash?: string;
+  /** Size of the narration audio in bytes */
+  size?: number;
+}
+
+/**
+ * What syncing with a server would transfer
+ */
+export interface SyncPreview {
+  toAdd: RemoteBook[];
+  /** Books this library already has, by ID or text */
+  alreadyPresent: RemoteBook[];
 }
 
 /**
@@ -175,6 +373,75 @@ export interface SyncResult {
   errors: string[];
 }
 
+/**
+ * A change pushed over a sync server's WebSocket. On libraryChanged fetch the
+ * book list again; on resync, everything shown from the server.
+ */
+export type SyncUpdate =
+  | ({ type: 'progress' } & Progress)
+  | { type: 'libraryChanged' }
+  | { type: 'resync' };
+
+/** Payload of the server_update event */
+export interface ServerUpdate {
+  /** Name of the server it came from */
+  server: string;
+  update: SyncUpdate;
+}
+
+/**
+ * Disk space used by one book
+ */
+export interface BookStorage {
+  bookId: BookId;
+  title: string;
+  /** Narration directory, segment cache included */
+  narrationBytes: number;
+  /** Source copy; 0 for a source referenced in place */
+  sourceBytes: number;
+}
+
+/**
+ * A file in the bundles directory
+ */
+export interface BundleFileInfo {
+  filename: string;
+  size: number;
+  /** Unix timestamp (seconds), null if the platform doesn't report it */
+  modifiedAt: number | null;
+  /** From the bundle's manifest; null if it couldn't be read */
+  title: string | null;
+  author: string | null;
+}
+
+/**
+ * Disk space used by the data directory, by category
+ */
+export interface StorageUsage {
+  sourcesBytes: number;
+  narrationBytes: number;
+  bundlesBytes: number;
+  voicesBytes: number;
+  /** Database file with its write-ahead log */
+  databaseBytes: number;
+  totalBytes: number;
+  /** Free space on the data directory's volume, null if it couldn't be read */
+  availableBytes: number | null;
+  /** Largest narration first */
+  books: BookStorage[];
+}
+
+/**
+ * Result of importing a library archive
+ */
+export interface LibraryImportResult {
+  /** Books added to or updated in the library */
+  imported: Book[];
+  /** Books skipped because the library already has their text */
+  duplicates: number;
+  errors: string[];
+}
+
 // =============================================================================
 // Settings Types
 // =============================================================================
@@ -204,6 +471,12 @@ export interface Settings {
   autoPlay: boolean;
   /** Local sync server port */
   syncPort: number;
+  /** Chatterbox exaggeration for books without their own (0-2) */
+  exagDefault: number;
+  /** Chatterbox CFG weight for books without their own (0-1) */
+  cfgDefault: number;
+  /** Chatterbox temperature for books without their own (0.05-5) */
+  tempDefault: number;
 }
 
 /** Default settings values */
@@ -217,6 +490,9 @@ export const DEFAULT_SETTINGS: Settings = {
   defaultVoice: null,
   autoPlay: false,
   syncPort: 42069,
+  exagDefault: 0.3,
+  cfgDefault: 0.5,
+  tempDefault: 0.8,
 };
 
 // =============================================================================
@@ -232,11 +508,26 @@ export interface GenerationProgressPayload {
   message: string;
 }
 
+/** A running narration generation, from list_active_generations */
+export interface GenerationStatus {
+  bookId: BookId;
+  /** Stage of the latest progress update; null before the first one */
+  stage: GenerationStage | null;
+  current: number;
+  total: number;
+}
+
 /** Payload for generation_complete event */
 export interface GenerationCompletePayload {
   bookId: BookId;
 }
 
+/** Payload for book_finished event */
+export interface BookFinishedPayload {
+  bookId: BookId;
+  finishedAt: Timestamp;
+}
+
 /** Payload for generation_error event */
 export interface GenerationErrorPayload {
   bookId: BookId;
@@ -253,10 +544,80 @@ export interface SyncProgressPayload {
   percent: number;
 }
 
+/** Payload for sync_server_stopped event */
+export interface SyncServerStoppedPayload {
+  /** 'idle' when no request arrived within syncServerIdleTimeoutMins */
+  reason: 'requested' | 'idle';
+}
+
+/** Payload for library_export_progress and library_import_progress events */
+export interface LibraryProgressPayload {
+  current: number;
+  total: number;
+  title: string;
+}
+
+/** Payload for url_import_progress event */
+export interface DownloadProgressPayload {
+  url: string;
+  /** Bytes received so far */
+  received: number;
+  /** Size of the file, null if the server didn't send it */
+  total: number | null;
+}
+
+/** Payload for data_relocation_progress event */
+export interface RelocationProgressPayload {
+  current: number;
+  total: number;
+  copiedBytes: number;
+  totalBytes: number;
+}
+
 // =============================================================================
 // Import Preferences
 // =============================================================================
 
+/** How an imported book's source file is kept */
+export type ImportMode = 'copy' | 'reference';
+
+/** A problem found while parsing that didn't stop the import */
+export interface ParseWarning {
+  kind: 'skippedContent' | 'encodingFallback' | 'emptyChapter';
+  /** e.g. 'Chapter "Two" has no text' */
+  message: string;
+}
+
+/**
+ * A separate library within one install; every book belongs to one profile
+ */
+export interface Profile {
+  id: string;
+  name: string;
+  /** Unix timestamp (seconds) */
+  createdAt: number;
+  bookCount: number;
+}
+
+/** A book returned by import_book, with its parse warnings */
+export interface ImportedBook extends Book {
+  /** Empty for a clean parse */
+  warnings: ParseWarning[];
+}
+
+/** A file import_directory couldn't import */
+export interface ImportFailure {
+  path: string;
+  /** e.g. "x.epub is in use by another program. Close it and try again." */
+  error: string;
+}
+
+/** Result of import_directory */
+export interface DirectoryImport {
+  imported: ImportedBook[];
+  failed: ImportFailure[];
+}
+
 /**
  * User preferences for importing books
  */
@@ -265,10 +626,22 @@ export interface ImportPreferences {
   autoProcess: boolean;
   /** false after "Don't show again" is checked */
   showImportModal: boolean;
+  /** Merge runs of short consecutive segments on import */
+  mergeShortSegments: boolean;
+  /** Character threshold below which segments are merged */
+  mergeSegmentMinChars: number;
+  /** Leave likely page numbers, running headers and copyright notices out of narration */
+  skipBoilerplate: boolean;
+  /** Copy imported files into the library, or reference them in place */
+  importMode: ImportMode;
 }
 
 /** Default import preferences */
 export const DEFAULT_IMPORT_PREFERENCES: ImportPreferences = {
   autoProcess: false,
   showImportModal: true,
+  mergeShortSegments: false,
+  mergeSegmentMinChars: 120,
+  skipBoilerplate: false,
+  importMode: 'copy',
 };
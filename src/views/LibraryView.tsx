@@ -383,7 +383,7 @@ export function LibraryView() {
         filters: [
           {
             name: 'Books',
-            extensions: ['epub', 'md', 'txt', 'pdf'],
+            extensions: ['epub', 'html', 'htm', 'md', 'txt', 'pdf'],
           },
         ],
       });
@@ -9,7 +9,7 @@ import { useCallback, useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { open } from '@tauri-apps/plugin-dialog';
 import { useSettingsStore } from '../stores/settingsStore';
-import type { Theme } from '../types';
+import type { SampleInfo, Theme } from '../types';
 
 // =============================================================================
 // Types
@@ -19,9 +19,22 @@ interface Voice {
   id: string;
   name: string;
   samplePath: string;
+  originalFilename?: string | null;
   isDefault: boolean;
+  sampleInfo?: SampleInfo;
 }
 
+interface VoiceUsage {
+  isDefault: boolean;
+  defaultSetting: boolean;
+  bookIds: string[];
+  overrideCount: number;
+}
+
+/** Samples shorter or lower-rate than this still work but clone less faithfully */
+const RECOMMENDED_SAMPLE_SECONDS = 10;
+const RECOMMENDED_SAMPLE_RATE = 16000;
+
 // =============================================================================
 // Styles
 // =============================================================================
@@ -369,7 +382,7 @@ export function SettingsView() {
       // Open file picker for audio sample
       const selectedFile = await open({
         multiple: false,
-        filters: [{ name: 'Audio', extensions: ['wav', 'mp3'] }],
+        filters: [{ name: 'Audio', extensions: ['wav', 'mp3', 'ogg', 'flac'] }],
       });
 
       if (!selectedFile) {
@@ -391,6 +404,18 @@ export function SettingsView() {
       });
 
       setVoices((prev) => [...prev, newVoice]);
+
+      const info = newVoice.sampleInfo;
+      if (
+        info &&
+        (info.duration < RECOMMENDED_SAMPLE_SECONDS || info.sampleRate < RECOMMENDED_SAMPLE_RATE)
+      ) {
+        window.alert(
+          `This sample is ${info.duration.toFixed(1)}s at ${info.sampleRate} Hz. ` +
+            `For best results use at least ${RECOMMENDED_SAMPLE_SECONDS}s of clear speech ` +
+            `recorded at ${RECOMMENDED_SAMPLE_RATE} Hz or higher.`
+        );
+      }
     } catch (err) {
       setVoiceError(err instanceof Error ? err.message : 'Failed to create voice');
     } finally {
@@ -403,22 +428,47 @@ export function SettingsView() {
       return; // Cannot delete default voice
     }
 
-    const confirmed = window.confirm(
-      `Are you sure you want to delete the voice "${voice.name}"? This action cannot be undone.`
-    );
+    setVoiceOperationLoading(voice.id);
+    setVoiceError(null);
+
+    try {
+      const usage = await invoke<VoiceUsage>('voice_usage', { id: voice.id });
+      let inUse = '';
+      if (usage.defaultSetting) {
+        inUse += ' It is selected as your default voice, which will be reset.';
+      }
+      if (usage.overrideCount > 0) {
+        inUse += ` It narrates passages in ${usage.bookIds.length} book(s); those passages will use the book's voice instead.`;
+      }
+      const confirmed = window.confirm(
+        `Are you sure you want to delete the voice "${voice.name}"?${inUse} This action cannot be undone.`
+      );
+
+      if (!confirmed) {
+        return;
+      }
 
-    if (!confirmed) {
-      return;
+      await invoke('delete_voice', { id: voice.id, force: true });
+      setVoices((prev) => prev.filter((v) => v.id !== voice.id));
+    } catch (err) {
+      setVoiceError(err instanceof Error ? err.message : 'Failed to delete voice');
+    } finally {
+      setVoiceOperationLoading(null);
     }
+  };
 
+  const handlePreviewVoice = async (voice: Voice) => {
     setVoiceOperationLoading(voice.id);
     setVoiceError(null);
 
     try {
-      await invoke('delete_voice', { id: voice.id });
-      setVoices((prev) => prev.filter((v) => v.id !== voice.id));
+      const wav = await invoke<number[]>('preview_voice', { id: voice.id });
+      const url = URL.createObjectURL(new Blob([new Uint8Array(wav)], { type: 'audio/wav' }));
+      const audio = new Audio(url);
+      audio.onended = () => URL.revokeObjectURL(url);
+      await audio.play();
     } catch (err) {
-      setVoiceError(err instanceof Error ? err.message : 'Failed to delete voice');
+      setVoiceError(err instanceof Error ? err.message : 'Failed to preview voice');
     } finally {
       setVoiceOperationLoading(null);
     }
@@ -681,10 +731,23 @@ export function SettingsView() {
                         )}
                       </div>
                       <span style={styles.voicePath} title={voice.samplePath}>
-                        {voice.samplePath}
+                        {voice.originalFilename ?? voice.samplePath}
                       </span>
                     </div>
                     <div style={styles.voiceActions}>
+                      <button
+                        style={{
+                          ...styles.actionButton,
+                          ...(voiceOperationLoading === voice.id
+                            ? styles.actionButtonDisabled
+                            : {}),
+                        }}
+                        onClick={() => handlePreviewVoice(voice)}
+                        disabled={voiceOperationLoading === voice.id}
+                        title="Hear a short sample of this voice"
+                      >
+                        Preview
+                      </button>
                       {!voice.isDefault && (
                         <button
                           style={{